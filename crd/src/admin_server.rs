@@ -0,0 +1,111 @@
+//! The AdminServer of ZooKeeper 3.5+, an HTTP server serving the commands of the four letter
+//! words under `/commands/<command>` (e.g. `/commands/stat` or `/commands/mntr`).
+//!
+//! It is enabled by default. The operator uses it for the liveness and readiness probes of the
+//! servers and can expose it with a Service within the cluster.
+use crate::ADMIN_PORT;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const DEFAULT_ADMIN_PORT: u16 = 8080;
+/// The `zoo.cfg` property enabling the AdminServer.
+pub const ENABLE_SERVER: &str = "admin.enableServer";
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperAdminServer {
+    /// Whether the AdminServer is started, defaults to true. Without it the probes use the
+    /// `ruok` four letter word.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// The port of the AdminServer, defaults to 8080. `adminPort` in the config of a role group
+    /// takes precedence.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Creates the Service `<cluster>-admin` in front of the AdminServers of all servers.
+    #[serde(default)]
+    pub service: bool,
+}
+
+impl ZookeeperAdminServer {
+    pub fn enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(DEFAULT_ADMIN_PORT)
+    }
+
+    /// Returns the problems of the AdminServer configuration.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if self.service && !self.enabled() {
+            problems.push("adminServer.service: The AdminServer is disabled".to_string());
+        }
+        if self.port() < 1024 {
+            problems.push(format!(
+                "adminServer.port: [{}] must be at least 1024",
+                self.port()
+            ));
+        }
+        problems
+    }
+
+    /// Returns the `zoo.cfg` properties configuring the AdminServer.
+    pub fn zoo_cfg_properties(&self) -> BTreeMap<String, String> {
+        let mut properties = BTreeMap::new();
+        if !self.enabled() {
+            properties.insert(ENABLE_SERVER.to_string(), "false".to_string());
+        }
+        if let Some(port) = self.port {
+            properties.insert(ADMIN_PORT.to_string(), port.to_string());
+        }
+        properties
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_zoo_cfg_properties() {
+        let admin_server: ZookeeperAdminServer = serde_yaml::from_str(indoc! {"
+            port: 18080
+            service: true
+        "})
+        .unwrap();
+
+        assert!(admin_server.validate().is_empty());
+        assert_eq!(
+            admin_server.zoo_cfg_properties(),
+            vec![("admin.serverPort".to_string(), "18080".to_string())]
+                .into_iter()
+                .collect()
+        );
+    }
+
+    #[test]
+    fn test_disabled() {
+        let admin_server: ZookeeperAdminServer = serde_yaml::from_str(indoc! {"
+            enabled: false
+            service: true
+        "})
+        .unwrap();
+
+        assert_eq!(
+            admin_server
+                .zoo_cfg_properties()
+                .get(ENABLE_SERVER)
+                .unwrap(),
+            "false"
+        );
+        assert_eq!(
+            admin_server.validate(),
+            vec!["adminServer.service: The AdminServer is disabled"]
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! SASL (DIGEST-MD5) authentication with a super user managed by the operator.
+//!
+//! The operator generates the credentials of the super user into the Secret
+//! `<cluster name>-super-user` (see [`super_user_secret_name`]). Clients authenticate with SASL,
+//! tools which only support the `digest` scheme (like the znode controller of the operator) can
+//! authenticate as super user with the same credentials.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The directory the super user Secret is mounted to.
+pub const SASL_DIR: &str = "/stackable/sasl";
+/// The key of the name of the super user.
+pub const USERNAME_KEY: &str = "username";
+/// The key of the password of the super user.
+pub const PASSWORD_KEY: &str = "password";
+/// The key of the JAAS configuration of the servers.
+pub const JAAS_CONFIG_KEY: &str = "jaas.conf";
+/// The key of the digest of the super user credentials, see
+/// `zookeeper.DigestAuthenticationProvider.superDigest`.
+pub const SUPER_DIGEST_KEY: &str = "superDigest";
+
+const DEFAULT_SUPER_USER: &str = "super";
+const SASL_AUTHENTICATION_PROVIDER: &str =
+    "org.apache.zookeeper.server.auth.SASLAuthenticationProvider";
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperAuthentication {
+    /// The name of the super user, defaults to `super`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub super_user: Option<String>,
+    /// Whether the credentials of the super user are published in the client Secret. The super
+    /// user may do anything in the ensemble, so they are only published on request.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub client_credentials: bool,
+}
+
+impl ZookeeperAuthentication {
+    pub fn super_user(&self) -> &str {
+        self.super_user.as_deref().unwrap_or(DEFAULT_SUPER_USER)
+    }
+
+    /// Returns the `zoo.cfg` properties enabling SASL authentication.
+    pub fn zoo_cfg_properties(&self) -> BTreeMap<String, String> {
+        let mut properties = BTreeMap::new();
+        properties.insert(
+            "authProvider.1".to_string(),
+            SASL_AUTHENTICATION_PROVIDER.to_string(),
+        );
+        properties
+    }
+}
+
+/// Returns the name of the Secret with the super user credentials of the cluster with the given
+/// name.
+pub fn super_user_secret_name(cluster_name: &str) -> String {
+    format!("{}-super-user", cluster_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_super_user() {
+        assert_eq!(ZookeeperAuthentication::default().super_user(), "super");
+        assert_eq!(
+            ZookeeperAuthentication {
+                super_user: Some("admin".to_string()),
+                ..ZookeeperAuthentication::default()
+            }
+            .super_user(),
+            "admin"
+        );
+        assert_eq!(super_user_secret_name("simple"), "simple-super-user");
+    }
+}
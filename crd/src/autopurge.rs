@@ -0,0 +1,102 @@
+//! Automatic purging of old snapshots and transaction logs in the data directories.
+//!
+//! ZooKeeper keeps all snapshots and transaction logs unless autopurge is enabled, so the data
+//! directories grow until the disks are full. The operator enables it for every cluster with
+//! [`DEFAULT_SNAP_RETAIN_COUNT`] and [`DEFAULT_PURGE_INTERVAL_HOURS`] unless configured otherwise.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const SNAP_RETAIN_COUNT: &str = "autopurge.snapRetainCount";
+pub const PURGE_INTERVAL: &str = "autopurge.purgeInterval";
+
+pub const DEFAULT_SNAP_RETAIN_COUNT: u32 = 3;
+pub const DEFAULT_PURGE_INTERVAL_HOURS: u32 = 24;
+/// ZooKeeper silently raises lower retain counts to this minimum.
+pub const MIN_SNAP_RETAIN_COUNT: u32 = 3;
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperAutopurge {
+    /// The number of most recent snapshots (and their transaction logs) which are kept, at least
+    /// 3. Defaults to 3.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snap_retain_count: Option<u32>,
+    /// The interval of the purge task in hours, `0` disables purging. Defaults to 24.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purge_interval_hours: Option<u32>,
+}
+
+impl ZookeeperAutopurge {
+    pub fn snap_retain_count(&self) -> u32 {
+        self.snap_retain_count.unwrap_or(DEFAULT_SNAP_RETAIN_COUNT)
+    }
+
+    pub fn purge_interval_hours(&self) -> u32 {
+        self.purge_interval_hours
+            .unwrap_or(DEFAULT_PURGE_INTERVAL_HOURS)
+    }
+
+    /// Returns the problems of the autopurge configuration.
+    pub fn validate(&self) -> Vec<String> {
+        if self.snap_retain_count() < MIN_SNAP_RETAIN_COUNT {
+            vec![format!(
+                "autopurge.snapRetainCount: Must be at least {} but is [{}]",
+                MIN_SNAP_RETAIN_COUNT,
+                self.snap_retain_count()
+            )]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Returns the `zoo.cfg` properties configuring the purge task.
+    pub fn zoo_cfg_properties(&self) -> BTreeMap<String, String> {
+        let mut properties = BTreeMap::new();
+        properties.insert(
+            SNAP_RETAIN_COUNT.to_string(),
+            self.snap_retain_count().to_string(),
+        );
+        properties.insert(
+            PURGE_INTERVAL.to_string(),
+            self.purge_interval_hours().to_string(),
+        );
+        properties
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_defaults() {
+        let autopurge = ZookeeperAutopurge::default();
+
+        assert!(autopurge.validate().is_empty());
+        assert_eq!(
+            autopurge.zoo_cfg_properties(),
+            vec![
+                (PURGE_INTERVAL.to_string(), "24".to_string()),
+                (SNAP_RETAIN_COUNT.to_string(), "3".to_string()),
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let autopurge: ZookeeperAutopurge = serde_yaml::from_str(indoc! {"
+            snapRetainCount: 2
+            purgeIntervalHours: 0
+        "})
+        .unwrap();
+
+        assert_eq!(
+            autopurge.validate(),
+            vec!["autopurge.snapRetainCount: Must be at least 3 but is [2]"]
+        );
+    }
+}
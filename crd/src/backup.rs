@@ -0,0 +1,249 @@
+//! Scheduled backups of the data directory of a cluster to S3 compatible object storage and
+//! restores of them into new clusters.
+//!
+//! The operator takes the backups itself: on every scheduled time it copies the latest snapshot
+//! and the transaction logs from the leader to the bucket, next to a JSON manifest listing them.
+//! Only the newest `retainedBackups` backups are kept. A restore stages the files of a backup in
+//! the data directories of a new cluster before its servers start.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The default number of backups which are kept.
+pub const DEFAULT_RETAINED_BACKUPS: u32 = 7;
+/// The default region of the bucket.
+pub const DEFAULT_REGION: &str = "us-east-1";
+/// The key of the credentials Secret holding the access key id.
+pub const ACCESS_KEY_ID_KEY: &str = "accessKeyId";
+/// The key of the credentials Secret holding the secret access key.
+pub const SECRET_ACCESS_KEY_KEY: &str = "secretAccessKey";
+
+/// Returns the name of the Secret with the URLs of the files of the restore of the cluster
+/// `cluster_name`, it exists while the restore is in progress.
+pub fn restore_secret_name(cluster_name: &str) -> String {
+    format!("{}-restore", cluster_name)
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperBackup {
+    /// When backups are taken, a cron expression (`minute hour day-of-month month day-of-week`)
+    /// in UTC, e.g. `0 3 * * *`.
+    pub schedule: String,
+    /// The bucket the backups are uploaded to.
+    pub s3: S3Bucket,
+    /// How many backups are kept, older ones are deleted. Defaults to 7.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retained_backups: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct S3Bucket {
+    /// The name of the bucket.
+    pub bucket: String,
+    /// Prepended to the object keys, e.g. `zookeeper/`. The keys continue with
+    /// `<namespace>/<name>/<time>.json` for the manifest and `<namespace>/<name>/<time>/<file>`
+    /// for the files of a backup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    /// The endpoint of the object storage, defaults to `https://s3.<region>.amazonaws.com`.
+    /// Buckets are always addressed in the path (`<endpoint>/<bucket>/<key>`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// The region of the bucket, defaults to `us-east-1`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// The Secret in the namespace of the cluster holding the `accessKeyId` and
+    /// `secretAccessKey` of the bucket.
+    pub credentials_secret: String,
+}
+
+/// Restores a backup into a new cluster.
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperRestore {
+    /// The bucket the backup is read from, the `prefix` is not used.
+    pub s3: S3Bucket,
+    /// The key of the backup in the bucket, e.g.
+    /// `production/default/simple/20210901T030000Z.json`.
+    pub key: String,
+}
+
+impl ZookeeperBackup {
+    pub fn retained_backups(&self) -> u32 {
+        self.retained_backups.unwrap_or(DEFAULT_RETAINED_BACKUPS)
+    }
+
+    /// Returns the problems of the backup configuration, the schedule is validated by the
+    /// operator.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = self.s3.validate("backup.s3");
+        if self.retained_backups == Some(0) {
+            problems.push("backup.retainedBackups: Must be greater than 0".to_string());
+        }
+        problems
+    }
+}
+
+impl ZookeeperRestore {
+    /// Returns the problems of the restore configuration.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = self.s3.validate("restore.s3");
+        if self.key.is_empty() {
+            problems.push("restore.key: Must not be empty".to_string());
+        }
+        problems
+    }
+}
+
+impl S3Bucket {
+    /// Returns the problems of the bucket configured in the field `field`.
+    fn validate(&self, field: &str) -> Vec<String> {
+        let mut problems = vec![];
+        if self.bucket.is_empty() {
+            problems.push(format!("{}.bucket: Must not be empty", field));
+        }
+        if self.credentials_secret.is_empty() {
+            problems.push(format!("{}.credentialsSecret: Must not be empty", field));
+        }
+        if let Some(endpoint) = &self.endpoint {
+            if !endpoint.starts_with("https://") && !endpoint.starts_with("http://") {
+                problems.push(format!(
+                    "{}.endpoint: [{}] must be an http:// or https:// URL",
+                    field, endpoint
+                ));
+            }
+        }
+        problems
+    }
+
+    pub fn region(&self) -> &str {
+        self.region.as_deref().unwrap_or(DEFAULT_REGION)
+    }
+
+    pub fn endpoint(&self) -> String {
+        match &self.endpoint {
+            Some(endpoint) => endpoint.trim_end_matches('/').to_string(),
+            None => format!("https://s3.{}.amazonaws.com", self.region()),
+        }
+    }
+
+    /// Returns the key of the manifest of the backup of the cluster `name` in `namespace` taken at
+    /// `time` (e.g. `20210901T120000Z`).
+    pub fn object_key(&self, namespace: &str, name: &str, time: &str) -> String {
+        format!(
+            "{}{}/{}/{}.json",
+            self.prefix.as_deref().unwrap_or_default(),
+            namespace,
+            name,
+            time
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupStatus {
+    /// When the last backup was started (RFC 3339), successful or not.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_schedule_time: Option<String>,
+    /// When the last successful backup was started (RFC 3339).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_successful_time: Option<String>,
+    /// The snapshot in the last successful backup, e.g. `snapshot.100000002`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_backup_snapshot: Option<String>,
+    /// The size of the files of the last successful backup in bytes.
+    #[serde(default)]
+    pub last_backup_bytes: u64,
+    /// The keys of the retained backups in the bucket, the newest one last.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backups: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreStatus {
+    /// The key of the restored backup.
+    pub key: String,
+    /// When the restore was started (RFC 3339).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_time: Option<String>,
+    /// When the restore completed or was skipped (RFC 3339), it is not repeated afterwards.
+    /// Servers created later start with an empty data directory and get the data from the
+    /// leader.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_time: Option<String>,
+    /// The snapshot of the restored backup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_backup() {
+        let backup: ZookeeperBackup = serde_yaml::from_str(indoc! {"
+            schedule: 0 3 * * *
+            s3:
+              bucket: backups
+              prefix: zookeeper/
+              credentialsSecret: s3-credentials
+        "})
+        .unwrap();
+
+        assert!(backup.validate().is_empty());
+        assert_eq!(backup.retained_backups(), DEFAULT_RETAINED_BACKUPS);
+        assert_eq!(backup.s3.endpoint(), "https://s3.us-east-1.amazonaws.com");
+        assert_eq!(
+            backup
+                .s3
+                .object_key("default", "simple", "20210901T120000Z"),
+            "zookeeper/default/simple/20210901T120000Z.json"
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let backup: ZookeeperBackup = serde_yaml::from_str(indoc! {"
+            schedule: 0 3 * * *
+            s3:
+              bucket: ''
+              endpoint: minio:9000
+              credentialsSecret: s3-credentials
+            retainedBackups: 0
+        "})
+        .unwrap();
+
+        assert_eq!(
+            backup.validate(),
+            vec![
+                "backup.s3.bucket: Must not be empty",
+                "backup.s3.endpoint: [minio:9000] must be an http:// or https:// URL",
+                "backup.retainedBackups: Must be greater than 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_restore() {
+        let restore: ZookeeperRestore = serde_yaml::from_str(indoc! {"
+            s3:
+              bucket: backups
+              credentialsSecret: ''
+            key: ''
+        "})
+        .unwrap();
+
+        assert_eq!(
+            restore.validate(),
+            vec![
+                "restore.s3.credentialsSecret: Must not be empty",
+                "restore.key: Must not be empty",
+            ]
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! The `ZookeeperBenchmark` custom resource.
+//!
+//! A ZookeeperBenchmark runs a latency and throughput benchmark against a ZookeeperCluster with
+//! `zk-latencies.py` of [zk-smoketest](https://github.com/phunt/zk-smoketest). Every client runs
+//! the benchmark in rounds for the configured duration, the operator summarizes the results of
+//! all clients in the status once they finished. A benchmark runs only once, to repeat it the
+//! ZookeeperBenchmark has to be recreated.
+use crate::znode::ZookeeperClusterRef;
+
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_CLIENTS: u16 = 1;
+pub const DEFAULT_DURATION_SECONDS: u32 = 60;
+pub const DEFAULT_ZNODE_COUNT: u32 = 1000;
+pub const DEFAULT_ZNODE_SIZE: u32 = 25;
+
+/// The label carrying the name of the ZookeeperBenchmark on the pods of the clients, e.g. to
+/// allow them in `spec.networkPolicy` of the cluster.
+pub const BENCHMARK_LABEL: &str = "zookeeper.stackable.tech/benchmark";
+/// The key of the results (as JSON) in the results ConfigMap.
+pub const RESULTS_KEY: &str = "results.json";
+
+#[derive(Clone, CustomResource, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[kube(
+    group = "zookeeper.stackable.tech",
+    version = "v1alpha1",
+    kind = "ZookeeperBenchmark",
+    plural = "zookeeperbenchmarks",
+    shortname = "zbm",
+    namespaced
+)]
+#[kube(status = "ZookeeperBenchmarkStatus")]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperBenchmarkSpec {
+    pub cluster_ref: ZookeeperClusterRef,
+    /// The image of the clients, `zk-latencies.py` of zk-smoketest must be on the `PATH`.
+    pub image: String,
+    /// The number of clients running the benchmark concurrently, each one in its own pod.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clients: Option<u16>,
+    /// How long every client runs the benchmark, at least one round is always completed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<u32>,
+    /// The number of znodes every client creates, sets, gets and deletes per round.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub znode_count: Option<u32>,
+    /// The size of the data of every znode in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub znode_size: Option<u32>,
+    /// If set, the results are also written into the ConfigMap with this name (key
+    /// `results.json`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results_config_map: Option<String>,
+}
+
+impl ZookeeperBenchmarkSpec {
+    pub fn clients(&self) -> u16 {
+        self.clients.unwrap_or(DEFAULT_CLIENTS)
+    }
+
+    pub fn duration_seconds(&self) -> u32 {
+        self.duration_seconds.unwrap_or(DEFAULT_DURATION_SECONDS)
+    }
+
+    pub fn znode_count(&self) -> u32 {
+        self.znode_count.unwrap_or(DEFAULT_ZNODE_COUNT)
+    }
+
+    pub fn znode_size(&self) -> u32 {
+        self.znode_size.unwrap_or(DEFAULT_ZNODE_SIZE)
+    }
+
+    /// Returns the problems with the spec, an empty list if it is valid.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if self.image.is_empty() {
+            problems.push("image: Must not be empty".to_string());
+        }
+        if self.clients() == 0 {
+            problems.push("clients: Must be greater than 0".to_string());
+        }
+        if self.duration_seconds() == 0 {
+            problems.push("durationSeconds: Must be greater than 0".to_string());
+        }
+        if self.znode_count() == 0 {
+            problems.push("znodeCount: Must be greater than 0".to_string());
+        }
+        problems
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+pub enum BenchmarkPhase {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperBenchmarkStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase: Option<BenchmarkPhase>,
+    /// Why the benchmark failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// The Job running the clients.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_name: Option<String>,
+    /// When the Job was created (RFC 3339).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<String>,
+    /// When the benchmark succeeded or failed (RFC 3339).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completion_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results: Option<BenchmarkResults>,
+}
+
+impl ZookeeperBenchmarkStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.phase,
+            Some(BenchmarkPhase::Succeeded) | Some(BenchmarkPhase::Failed)
+        )
+    }
+}
+
+/// The summarized results of all clients.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchmarkResults {
+    pub clients: u16,
+    /// The rounds completed by all clients together.
+    pub rounds: u64,
+    /// The operations of all clients together.
+    pub operations: u64,
+    /// The operations per second of all clients together.
+    pub throughput: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub latencies: Vec<OperationLatency>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationLatency {
+    /// `create`, `set`, `get` or `delete`.
+    pub operation: String,
+    pub count: u64,
+    /// The average time an operation took in milliseconds.
+    pub average_millis: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_validate() {
+        let spec: ZookeeperBenchmarkSpec = serde_yaml::from_str(indoc! {"
+            clusterRef:
+              name: simple
+            image: ''
+            clients: 0
+            znodeCount: 0
+        "})
+        .unwrap();
+
+        assert_eq!(
+            spec.validate(),
+            vec![
+                "image: Must not be empty",
+                "clients: Must be greater than 0",
+                "znodeCount: Must be greater than 0",
+            ]
+        );
+        assert_eq!(spec.duration_seconds(), DEFAULT_DURATION_SECONDS);
+        assert_eq!(spec.znode_size(), DEFAULT_ZNODE_SIZE);
+    }
+}
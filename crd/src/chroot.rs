@@ -0,0 +1,221 @@
+//! Provisioning chroots for other operators.
+//!
+//! Operators of products which keep their state in ZooKeeper (Kafka, Druid, ...) request an
+//! isolated chroot for every product cluster with a [`ChrootRequest`] instead of sharing the root
+//! of the ensemble. The request is turned into a [`ZookeeperZnode`] named after and owned by the
+//! consumer (see [`chroot_znode_name`]), so the znode controller creates the znode with the
+//! requested ACLs and users, publishes the discovery ConfigMap of the consumer and removes
+//! everything again when the consumer is deleted.
+//!
+//! A ZookeeperZnode which already exists under that name but belongs to another resource is never
+//! taken over, [`request_chroot`] fails with [`Error::ChrootConflict`] instead.
+use crate::error::{Error, ZookeeperOperatorResult};
+use crate::util::ZookeeperReference;
+use crate::znode::{
+    user_secret_name, ZnodeAcl, ZnodeQuota, ZnodeUser, ZookeeperClusterRef, ZookeeperZnode,
+    ZookeeperZnodeSpec, ZOOKEEPER_DISCOVERY_KEY,
+};
+
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::api::PostParams;
+use kube::{Api, Resource, ResourceExt};
+use stackable_operator::builder::ObjectMetaBuilder;
+use stackable_operator::client::Client;
+use tracing::info;
+
+/// The chroot a consumer requests in a ZookeeperCluster.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChrootRequest {
+    /// The ZookeeperCluster to create the chroot in, the namespace defaults to the one of the
+    /// consumer.
+    pub cluster_ref: ZookeeperClusterRef,
+    /// See [`ZookeeperZnodeSpec::quota`].
+    pub quota: Option<ZnodeQuota>,
+    /// See [`ZookeeperZnodeSpec::acls`].
+    pub acls: Vec<ZnodeAcl>,
+    /// See [`ZookeeperZnodeSpec::users`].
+    pub users: Vec<ZnodeUser>,
+}
+
+impl ChrootRequest {
+    /// Builds the ZookeeperZnode for the chroot of `consumer`, which is owned by the consumer.
+    pub fn build_znode<T>(&self, consumer: &T) -> ZookeeperOperatorResult<ZookeeperZnode>
+    where
+        T: Resource<DynamicType = ()>,
+    {
+        let name = chroot_znode_name(&T::kind(&()), &consumer.name());
+        let mut znode = ZookeeperZnode::new(
+            &name,
+            ZookeeperZnodeSpec {
+                cluster_ref: self.cluster_ref.clone(),
+                quota: self.quota.clone(),
+                acls: self.acls.clone(),
+                users: self.users.clone(),
+                seed: None,
+            },
+        );
+        znode.metadata = ObjectMetaBuilder::new()
+            .name(name)
+            .namespace(consumer.namespace().unwrap_or_default())
+            .ownerreference_from_resource(consumer, Some(true), Some(true))?
+            .build()?;
+        Ok(znode)
+    }
+}
+
+/// The provisioned chroot of a consumer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChrootInformation {
+    /// References the cluster with the chroot, e.g. for
+    /// [`crate::util::get_zk_connection_info`].
+    pub reference: ZookeeperReference,
+    /// The connection string including the chroot, e.g. `server1:2181,server2:2181/znode-<uid>`.
+    pub connection_string: String,
+    /// The name of the discovery ConfigMap, which contains the connection string under
+    /// [`ZOOKEEPER_DISCOVERY_KEY`] and the chroot under
+    /// [`crate::znode::ZOOKEEPER_CHROOT_DISCOVERY_KEY`].
+    pub config_map: String,
+    /// The names of the Secrets with the credentials of the users, in the order of
+    /// [`ChrootRequest::users`].
+    pub user_secrets: Vec<String>,
+}
+
+/// Returns the name of the ZookeeperZnode (and of its discovery ConfigMap) with the chroot of the
+/// consumer of the given kind and name. The kind is part of the name, so consumers of different
+/// kinds with the same name do not conflict.
+pub fn chroot_znode_name(consumer_kind: &str, consumer_name: &str) -> String {
+    format!(
+        "{}-{}-zookeeper",
+        consumer_name,
+        consumer_kind.to_lowercase()
+    )
+}
+
+/// Creates or updates the ZookeeperZnode with the chroot of `consumer`.
+///
+/// Returns the provisioned chroot, or `None` as long as the znode controller did not publish the
+/// discovery ConfigMap yet, in which case the consumer should requeue. This is meant to be called
+/// on every reconciliation of the consumer, changes of the request are applied to the
+/// ZookeeperZnode.
+pub async fn request_chroot<T>(
+    client: &Client,
+    consumer: &T,
+    request: &ChrootRequest,
+) -> ZookeeperOperatorResult<Option<ChrootInformation>>
+where
+    T: Resource<DynamicType = ()>,
+{
+    let desired = request.build_znode(consumer)?;
+    let name = desired.name();
+    let namespace = desired.namespace().unwrap_or_default();
+    let znodes_api: Api<ZookeeperZnode> = client.get_namespaced_api(&namespace);
+
+    let znode = match znodes_api.get(&name).await {
+        Ok(existing) if !is_owned_by(&existing, consumer) => {
+            return Err(Error::ChrootConflict {
+                znode: format!("{}/{}", namespace, name),
+            })
+        }
+        Ok(existing) if existing.spec == desired.spec => existing,
+        Ok(mut existing) => {
+            info!("Updating the chroot request [{}/{}]", namespace, name);
+            existing.spec = desired.spec;
+            znodes_api
+                .replace(&name, &PostParams::default(), &existing)
+                .await?
+        }
+        Err(kube::Error::Api(response)) if response.code == 404 => {
+            info!("Requesting the chroot [{}/{}]", namespace, name);
+            znodes_api.create(&PostParams::default(), &desired).await?
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let chroot = match znode
+        .status
+        .as_ref()
+        .and_then(|status| status.znode_path.clone())
+    {
+        Some(chroot) => chroot,
+        None => return Ok(None),
+    };
+    let config_maps_api: Api<ConfigMap> = client.get_namespaced_api(&namespace);
+    let connection_string = match config_maps_api.get(&name).await {
+        Ok(config_map) => match config_map.data.get(ZOOKEEPER_DISCOVERY_KEY) {
+            Some(connection_string) => connection_string.clone(),
+            None => return Ok(None),
+        },
+        Err(kube::Error::Api(response)) if response.code == 404 => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok(Some(ChrootInformation {
+        reference: ZookeeperReference {
+            namespace: znode.cluster_namespace().unwrap_or(namespace),
+            name: znode.spec.cluster_ref.name.clone(),
+            chroot: Some(chroot),
+        },
+        connection_string,
+        config_map: name.clone(),
+        user_secrets: request
+            .users
+            .iter()
+            .map(|user| user_secret_name(&name, &user.name))
+            .collect(),
+    }))
+}
+
+/// Checks whether `znode` is owned by `consumer`.
+fn is_owned_by<T: Resource>(znode: &ZookeeperZnode, consumer: &T) -> bool {
+    let uid = consumer.meta().uid.as_deref();
+    uid.is_some()
+        && znode
+            .metadata
+            .owner_references
+            .iter()
+            .any(|owner| Some(owner.uid.as_str()) == uid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    fn consumer(uid: &str) -> ConfigMap {
+        serde_yaml::from_str(&format!(
+            indoc! {"
+                apiVersion: v1
+                kind: ConfigMap
+                metadata:
+                  name: kafka
+                  namespace: apps
+                  uid: {}
+            "},
+            uid
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_build_znode() {
+        let request = ChrootRequest {
+            cluster_ref: ZookeeperClusterRef {
+                name: "simple".to_string(),
+                namespace: None,
+            },
+            users: vec![ZnodeUser {
+                name: "broker".to_string(),
+                permissions: "cdrwa".to_string(),
+            }],
+            ..ChrootRequest::default()
+        };
+        let znode = request.build_znode(&consumer("1234")).unwrap();
+
+        assert_eq!(znode.name(), "kafka-configmap-zookeeper");
+        assert_eq!(znode.namespace(), Some("apps".to_string()));
+        assert_eq!(znode.cluster_namespace(), Some("apps".to_string()));
+        assert_eq!(znode.spec.users, request.users);
+        assert!(is_owned_by(&znode, &consumer("1234")));
+        assert!(!is_owned_by(&znode, &consumer("5678")));
+    }
+}
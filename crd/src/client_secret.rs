@@ -0,0 +1,44 @@
+//! The client Secret of a cluster.
+//!
+//! Next to the discovery ConfigMap the operator publishes the Secret `<cluster name>-client` (see
+//! [`client_secret_name`]), which bundles everything a client needs to connect: the connection
+//! string under [`ZOOKEEPER_DISCOVERY_KEY`](crate::znode::ZOOKEEPER_DISCOVERY_KEY), and depending
+//! on the cluster
+//!
+//! - with client TLS the connection string of the TLS port, the truststore and its password,
+//! - with SASL (DIGEST-MD5) authentication and `clientCredentials` the credentials of the super
+//!   user and a JAAS configuration with a `Client` section using them.
+//!
+//! Applications mount the Secret or read their environment from it, so they do not need access
+//! to the TLS Secret or the super user Secret of the servers.
+
+/// The key of the connection string of the TLS client port.
+pub const ZOOKEEPER_TLS_DISCOVERY_KEY: &str = "ZOOKEEPER_TLS";
+/// The key of the PKCS #12 truststore, a copy of the one of the servers.
+pub const TRUSTSTORE_KEY: &str = "truststore.p12";
+/// The key of the password of the truststore.
+pub const TRUSTSTORE_PASSWORD_KEY: &str = "truststore.password";
+/// The key of the PEM encoded CA certificate, only present if the TLS Secret contains one (e.g.
+/// if it was issued by cert-manager).
+pub const CA_CERT_KEY: &str = "ca.crt";
+/// The key of the name of the SASL user.
+pub const USERNAME_KEY: &str = "username";
+/// The key of the password of the SASL user.
+pub const PASSWORD_KEY: &str = "password";
+/// The key of the JAAS configuration of the clients.
+pub const JAAS_CONFIG_KEY: &str = "jaas.conf";
+
+/// Returns the name of the client Secret of the cluster with the given name.
+pub fn client_secret_name(cluster_name: &str) -> String {
+    format!("{}-client", cluster_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_secret_name() {
+        assert_eq!(client_secret_name("simple"), "simple-client");
+    }
+}
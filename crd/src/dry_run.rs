@@ -0,0 +1,76 @@
+//! The changes a reconciliation would make while the cluster is in dry-run mode.
+//!
+//! In dry-run mode the operator computes the objects of the cluster as usual, but instead of
+//! writing them it records how they differ from the existing objects in `status.dryRun`. This
+//! allows to preview changes of the spec (or of the operator) before they are rolled out to a
+//! production ensemble.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunStatus {
+    /// The `metadata.generation` of the ZookeeperCluster the changes were planned for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation: Option<i64>,
+    /// Whether the changed fields of the applied objects were computed by the API server with a
+    /// server-side dry run, which includes defaulting and admission webhooks.
+    #[serde(default)]
+    pub server_side: bool,
+    /// The changes the operator would make, in the order it would make them. Pods are changed one
+    /// at a time, so only the first pod operation happens in the next reconciliation.
+    #[serde(default)]
+    pub changes: Vec<PlannedChange>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedChange {
+    pub action: PlannedAction,
+    /// The kind of the object, e.g. `ConfigMap`.
+    pub kind: String,
+    /// The name of the object, pods which do not exist yet are named after their server.
+    pub name: String,
+    /// The paths of the fields which change (e.g. `spec.ports`), only set for updates.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<String>,
+    /// Why the change is needed, e.g. the version a pod is upgraded to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum PlannedAction {
+    /// The object does not exist yet and is created.
+    Create,
+    /// Fields of the existing object change, they are listed in `fields`.
+    Update,
+    /// The object is deleted, e.g. a pod after a scale-down.
+    Delete,
+    /// The pod is deleted and recreated, e.g. to apply a changed configuration.
+    Restart,
+}
+
+string_enum_schema!(PlannedAction {
+    Create,
+    Update,
+    Delete,
+    Restart
+});
+
+impl PlannedChange {
+    pub fn new(action: PlannedAction, kind: &str, name: &str) -> PlannedChange {
+        PlannedChange {
+            action,
+            kind: kind.to_string(),
+            name: name.to_string(),
+            fields: vec![],
+            reason: None,
+        }
+    }
+
+    pub fn with_reason(mut self, reason: String) -> PlannedChange {
+        self.reason = Some(reason);
+        self
+    }
+}
@@ -6,6 +6,18 @@ pub enum Error {
     #[error("Illegal znode [{znode}]: {reason}")]
     IllegalZnode { znode: String, reason: String },
 
+    #[error("Illegal ZooKeeper version [{version}]: {reason}")]
+    IllegalZookeeperVersion { version: String, reason: String },
+
+    #[error("Illegal ACL [{acl}]: {reason}")]
+    IllegalZnodeAcl { acl: String, reason: String },
+
+    #[error("Illegal znode user [{user}]: {reason}")]
+    IllegalZnodeUser { user: String, reason: String },
+
+    #[error("The ZookeeperZnode [{znode}] already exists and belongs to another resource, its chroot is not shared")]
+    ChrootConflict { znode: String },
+
     #[error("No pods are found for ZooKeeper cluster [{namespace}/{name}]. Please check the ZooKeeper custom resource and ZooKeeper Operator for errors.")]
     NoZookeeperPodsAvailableForConnectionInfo { namespace: String, name: String },
 
@@ -0,0 +1,92 @@
+//! Access to the client port from outside of Kubernetes.
+//!
+//! With `NodePort` every server gets a Service of its own, clients connect to the node the server
+//! runs on. With `LoadBalancer` a single Service balances the connections across all servers. The
+//! resulting addresses are published in the status and the discovery ConfigMap.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The key of the external connection string in the discovery ConfigMap.
+pub const ZOOKEEPER_EXTERNAL_DISCOVERY_KEY: &str = "ZOOKEEPER_EXTERNAL";
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperExternalAccess {
+    /// `NodePort` for a Service per server or `LoadBalancer` for a single Service.
+    #[serde(rename = "type")]
+    pub type_: ExternalAccessType,
+    /// Annotations added to the Services, e.g. to configure the load balancer of a cloud
+    /// provider.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize, strum_macros::Display,
+)]
+pub enum ExternalAccessType {
+    NodePort,
+    LoadBalancer,
+}
+
+impl Default for ExternalAccessType {
+    fn default() -> Self {
+        ExternalAccessType::NodePort
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalAccessStatus {
+    /// The addresses clients outside of Kubernetes connect to, e.g. `203.0.113.10:31181`. They
+    /// are missing until the nodes or the load balancer have an address.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub endpoints: Vec<String>,
+}
+
+impl ExternalAccessStatus {
+    /// Returns the connection string of the endpoints, if there are any.
+    pub fn connection_string(&self) -> Option<String> {
+        if self.endpoints.is_empty() {
+            None
+        } else {
+            Some(self.endpoints.join(","))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_deserialize() {
+        let external_access: ZookeeperExternalAccess = serde_yaml::from_str(indoc! {"
+            type: LoadBalancer
+            annotations:
+              service.beta.kubernetes.io/aws-load-balancer-internal: 'true'
+        "})
+        .unwrap();
+
+        assert_eq!(external_access.type_, ExternalAccessType::LoadBalancer);
+        assert_eq!(external_access.type_.to_string(), "LoadBalancer");
+        assert_eq!(external_access.annotations.len(), 1);
+    }
+
+    #[test]
+    fn test_connection_string() {
+        let status = ExternalAccessStatus {
+            endpoints: vec![
+                "203.0.113.10:31181".to_string(),
+                "203.0.113.11:31182".to_string(),
+            ],
+        };
+        assert_eq!(
+            status.connection_string().as_deref(),
+            Some("203.0.113.10:31181,203.0.113.11:31182")
+        );
+        assert_eq!(ExternalAccessStatus::default().connection_string(), None);
+    }
+}
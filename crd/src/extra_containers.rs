@@ -0,0 +1,145 @@
+//! Init containers and sidecars added to the server pods, e.g. a log shipper or a backup agent.
+//!
+//! The containers can share `emptyDir` volumes with the server container, which mounts them at
+//! the given paths. The sidecars mount them by name with their own `volumeMounts`.
+use crate::placement::objects_schema;
+
+use k8s_openapi::api::core::v1::Container;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperExtraContainers {
+    /// Containers which run to completion before the server starts, see the Kubernetes
+    /// `Container` documentation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(schema_with = "objects_schema")]
+    pub init_containers: Vec<Container>,
+    /// Containers which run next to the server, see the Kubernetes `Container` documentation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(schema_with = "objects_schema")]
+    pub sidecars: Vec<Container>,
+    /// `emptyDir` volumes which are mounted into the server container and can be mounted by the
+    /// init containers and sidecars.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shared_volumes: Vec<ZookeeperSharedVolume>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperSharedVolume {
+    pub name: String,
+    /// The absolute path the volume is mounted at in the server container.
+    pub mount_path: String,
+}
+
+impl ZookeeperExtraContainers {
+    /// Returns the problems of the extra containers. `reserved_containers` and `reserved_volumes`
+    /// are the names of the containers and volumes the operator adds to the pods.
+    pub fn validate(&self, reserved_containers: &[&str], reserved_volumes: &[&str]) -> Vec<String> {
+        let mut problems = vec![];
+        let mut container_names = BTreeSet::new();
+        let containers = self
+            .init_containers
+            .iter()
+            .map(|container| ("initContainers", container))
+            .chain(
+                self.sidecars
+                    .iter()
+                    .map(|container| ("sidecars", container)),
+            );
+        for (field, container) in containers {
+            let name = container.name.as_str();
+            if name.is_empty() {
+                problems.push(format!(
+                    "extraContainers.{}: The name must not be empty",
+                    field
+                ));
+            } else if reserved_containers.contains(&name) {
+                problems.push(format!(
+                    "extraContainers.{}: [{}] is the name of a container of the operator",
+                    field, name
+                ));
+            } else if !container_names.insert(name) {
+                problems.push(format!(
+                    "extraContainers.{}: [{}] is used more than once",
+                    field, name
+                ));
+            }
+            if container.image.is_none() {
+                problems.push(format!(
+                    "extraContainers.{}: [{}] has no image",
+                    field, name
+                ));
+            }
+        }
+
+        let mut volume_names = BTreeSet::new();
+        for volume in &self.shared_volumes {
+            let name = volume.name.as_str();
+            if name.is_empty() {
+                problems.push("extraContainers.sharedVolumes: Names must not be empty".to_string());
+            } else if reserved_volumes.contains(&name) {
+                problems.push(format!(
+                    "extraContainers.sharedVolumes: [{}] is the name of a volume of the operator",
+                    name
+                ));
+            } else if !volume_names.insert(name) {
+                problems.push(format!(
+                    "extraContainers.sharedVolumes: [{}] is used more than once",
+                    name
+                ));
+            }
+            if !volume.mount_path.starts_with('/') {
+                problems.push(format!(
+                    "extraContainers.sharedVolumes: The mount path of [{}] must be absolute",
+                    name
+                ));
+            }
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_validate() {
+        let extra_containers: ZookeeperExtraContainers = serde_yaml::from_str(indoc! {"
+            initContainers:
+              - name: prepare
+                image: busybox
+            sidecars:
+              - name: log-shipper
+                image: fluent/fluent-bit
+                volumeMounts:
+                  - name: logs
+                    mountPath: /logs
+              - name: prepare
+                image: busybox
+              - name: zookeeper
+            sharedVolumes:
+              - name: logs
+                mountPath: /stackable/logs
+              - name: tls
+                mountPath: tls
+        "})
+        .unwrap();
+
+        assert_eq!(
+            extra_containers.validate(&["zookeeper"], &["tls"]),
+            vec![
+                "extraContainers.sidecars: [prepare] is used more than once",
+                "extraContainers.sidecars: [zookeeper] is the name of a container of the operator",
+                "extraContainers.sidecars: [zookeeper] has no image",
+                "extraContainers.sharedVolumes: [tls] is the name of a volume of the operator",
+                "extraContainers.sharedVolumes: The mount path of [tls] must be absolute",
+            ]
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Volumes added to the server pods and mounted into the server container, e.g. a custom JAAS
+//! file from a Secret, a CA bundle from a ConfigMap or scratch space.
+use crate::placement::objects_schema;
+use crate::security_context::is_below;
+
+use k8s_openapi::api::core::v1::{Volume, VolumeMount};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperExtraVolumes {
+    /// Volumes added to the server pods (e.g. `secret`, `configMap`, `emptyDir` or
+    /// `persistentVolumeClaim`), see the Kubernetes `Volume` documentation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(schema_with = "objects_schema")]
+    pub volumes: Vec<Volume>,
+    /// Mounts of the `volumes` into the server container, see the Kubernetes `VolumeMount`
+    /// documentation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(schema_with = "objects_schema")]
+    pub mounts: Vec<VolumeMount>,
+}
+
+impl ZookeeperExtraVolumes {
+    /// Returns the problems of the extra volumes. `reserved_volumes` are the names of the volumes
+    /// the operator adds to the pods, `reserved_mounts` the directories it mounts into the server
+    /// container.
+    pub fn validate(&self, reserved_volumes: &[&str], reserved_mounts: &[&str]) -> Vec<String> {
+        let mut problems = vec![];
+        let mut volume_names = BTreeSet::new();
+        for volume in &self.volumes {
+            let name = volume.name.as_str();
+            if name.is_empty() {
+                problems.push("extraVolumes.volumes: Names must not be empty".to_string());
+            } else if reserved_volumes.contains(&name) {
+                problems.push(format!(
+                    "extraVolumes.volumes: [{}] is the name of a volume of the operator",
+                    name
+                ));
+            } else if !volume_names.insert(name) {
+                problems.push(format!(
+                    "extraVolumes.volumes: [{}] is used more than once",
+                    name
+                ));
+            }
+        }
+
+        let mut mount_paths = BTreeSet::new();
+        for mount in &self.mounts {
+            if !volume_names.contains(mount.name.as_str()) {
+                problems.push(format!(
+                    "extraVolumes.mounts: [{}] is not one of extraVolumes.volumes",
+                    mount.name
+                ));
+            }
+            if !mount.mount_path.starts_with('/') {
+                problems.push(format!(
+                    "extraVolumes.mounts: The mount path of [{}] must be absolute",
+                    mount.name
+                ));
+            } else if let Some(reserved) = reserved_mounts
+                .iter()
+                .find(|reserved| is_below(&mount.mount_path, reserved))
+            {
+                problems.push(format!(
+                    "extraVolumes.mounts: [{}] is in [{}], a directory the operator mounts",
+                    mount.mount_path, reserved
+                ));
+            } else if !mount_paths.insert(mount.mount_path.as_str()) {
+                problems.push(format!(
+                    "extraVolumes.mounts: [{}] is mounted more than once",
+                    mount.mount_path
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Checks whether `path` is stored on a volume which outlives the pods, i.e. it is below a
+    /// writable mount of a `persistentVolumeClaim` or `hostPath` volume.
+    pub fn is_persistent(&self, path: &str) -> bool {
+        self.mounts
+            .iter()
+            .filter(|mount| !mount.read_only.unwrap_or(false))
+            .filter(|mount| is_below(path, &mount.mount_path))
+            .any(|mount| {
+                self.volumes.iter().any(|volume| {
+                    volume.name == mount.name
+                        && (volume.persistent_volume_claim.is_some() || volume.host_path.is_some())
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_validate() {
+        let extra_volumes: ZookeeperExtraVolumes = serde_yaml::from_str(indoc! {"
+            volumes:
+              - name: jaas
+                secret:
+                  secretName: zookeeper-jaas
+              - name: ca-bundle
+                configMap:
+                  name: ca-bundle
+              - name: jaas
+                emptyDir: {}
+              - name: tls
+                emptyDir: {}
+            mounts:
+              - name: jaas
+                mountPath: /stackable/jaas
+                readOnly: true
+              - name: ca-bundle
+                mountPath: /stackable/jaas
+              - name: scratch
+                mountPath: scratch
+              - name: ca-bundle
+                mountPath: /stackable/tls/ca
+        "})
+        .unwrap();
+
+        assert_eq!(
+            extra_volumes.validate(&["tls"], &["/stackable/tls"]),
+            vec![
+                "extraVolumes.volumes: [jaas] is used more than once",
+                "extraVolumes.volumes: [tls] is the name of a volume of the operator",
+                "extraVolumes.mounts: [/stackable/jaas] is mounted more than once",
+                "extraVolumes.mounts: [scratch] is not one of extraVolumes.volumes",
+                "extraVolumes.mounts: The mount path of [scratch] must be absolute",
+                "extraVolumes.mounts: [/stackable/tls/ca] is in [/stackable/tls], a directory the operator mounts",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_valid() {
+        let extra_volumes: ZookeeperExtraVolumes = serde_yaml::from_str(indoc! {"
+            volumes:
+              - name: scratch
+                emptyDir: {}
+              - name: data
+                persistentVolumeClaim:
+                  claimName: zookeeper-scratch
+            mounts:
+              - name: scratch
+                mountPath: /scratch
+              - name: data
+                mountPath: /data
+        "})
+        .unwrap();
+
+        assert!(extra_volumes
+            .validate(&["tls"], &["/stackable/tls"])
+            .is_empty());
+    }
+
+    #[test]
+    fn test_is_persistent() {
+        let extra_volumes: ZookeeperExtraVolumes = serde_yaml::from_str(indoc! {"
+            volumes:
+              - name: scratch
+                emptyDir: {}
+              - name: data
+                persistentVolumeClaim:
+                  claimName: zookeeper-data
+              - name: host
+                hostPath:
+                  path: /var/lib/zookeeper
+              - name: snapshots
+                persistentVolumeClaim:
+                  claimName: zookeeper-snapshots
+            mounts:
+              - name: scratch
+                mountPath: /scratch
+              - name: data
+                mountPath: /data
+              - name: host
+                mountPath: /host/
+              - name: snapshots
+                mountPath: /snapshots
+                readOnly: true
+        "})
+        .unwrap();
+
+        assert!(extra_volumes.is_persistent("/data"));
+        assert!(extra_volumes.is_persistent("/data/zookeeper"));
+        assert!(extra_volumes.is_persistent("/host/zookeeper"));
+        assert!(!extra_volumes.is_persistent("/database"));
+        assert!(!extra_volumes.is_persistent("/scratch/zookeeper"));
+        assert!(!extra_volumes.is_persistent("/snapshots/zookeeper"));
+        assert!(!extra_volumes.is_persistent("/tmp/zookeeper"));
+    }
+}
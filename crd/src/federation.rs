@@ -0,0 +1,206 @@
+//! Ensembles spanning several ZookeeperClusters, e.g. in several namespaces or Kubernetes
+//! clusters.
+//!
+//! Every ZookeeperCluster of the ensemble runs its own servers and lists the servers of the
+//! others in `spec.federation`: the clusters in the same Kubernetes cluster by `clusterRefs`,
+//! whose servers are read from `status.federation`, and servers outside of it by their address
+//! in `members`. The ids of the servers must be unique in the whole ensemble, so every cluster
+//! assigns the ids of its servers above its own `idOffset`.
+use crate::ip_family;
+use crate::ports::{DEFAULT_CLIENT_PORT, DEFAULT_LEADER_ELECTION_PORT, DEFAULT_QUORUM_PORT};
+use crate::znode::ZookeeperClusterRef;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The key of the connection string of the whole ensemble in the discovery ConfigMap.
+pub const ZOOKEEPER_FEDERATION_DISCOVERY_KEY: &str = "ZOOKEEPER_FEDERATION";
+/// How often the servers of the referenced clusters are read, their changes do not trigger a
+/// reconciliation.
+pub const FEDERATION_REFRESH_INTERVAL_SECONDS: u64 = 60;
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperFederation {
+    /// The ids of the servers of this cluster are greater than `idOffset`, defaults to 0. Every
+    /// cluster of the ensemble needs its own range, e.g. 0, 100 and 200 for three clusters.
+    #[serde(default)]
+    pub id_offset: usize,
+    /// The other ZookeeperClusters of the ensemble in this Kubernetes cluster, the namespace
+    /// defaults to the one of this cluster.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cluster_refs: Vec<ZookeeperClusterRef>,
+    /// The servers of the ensemble outside of this Kubernetes cluster, e.g. the
+    /// `status.federation.servers` of the clusters in other Kubernetes clusters with their
+    /// external addresses.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<FederationMember>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FederationMember {
+    /// The `myid` of the server.
+    pub id: usize,
+    /// The host name or IP address the other servers and the clients reach the server at.
+    pub address: String,
+    /// Defaults to 2181.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_port: Option<u16>,
+    /// Defaults to 2888.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quorum_port: Option<u16>,
+    /// Defaults to 3888.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leader_election_port: Option<u16>,
+    /// Whether the server is an observer.
+    #[serde(default)]
+    pub observer: bool,
+}
+
+impl FederationMember {
+    /// Returns the value of the `server.<id>` entry of the server in `zoo.cfg`, e.g.
+    /// `zk-1.example.com:2888:3888:observer`.
+    pub fn server_entry(&self) -> String {
+        let mut entry = format!(
+            "{}:{}:{}",
+            ip_family::format_host(&self.address),
+            self.quorum_port.unwrap_or(DEFAULT_QUORUM_PORT),
+            self.leader_election_port
+                .unwrap_or(DEFAULT_LEADER_ELECTION_PORT)
+        );
+        if self.observer {
+            entry.push_str(":observer");
+        }
+        entry
+    }
+
+    /// Returns the address clients connect to, e.g. `zk-1.example.com:2181`.
+    pub fn client_address(&self) -> String {
+        ip_family::format_address(
+            &self.address,
+            self.client_port.unwrap_or(DEFAULT_CLIENT_PORT),
+        )
+    }
+}
+
+impl ZookeeperFederation {
+    /// Returns the ids the servers of this cluster must not get: the ids up to `idOffset` and
+    /// the ids of the `members`.
+    pub fn reserved_ids(&self) -> Vec<usize> {
+        (1..=self.id_offset)
+            .chain(self.members.iter().map(|member| member.id))
+            .collect()
+    }
+
+    /// Returns the problems of the federation of the cluster `name` in `namespace`.
+    pub fn validate(&self, namespace: &str, name: &str) -> Vec<String> {
+        let mut problems = vec![];
+        for cluster_ref in &self.cluster_refs {
+            if cluster_ref.namespace.as_deref().unwrap_or(namespace) == namespace
+                && cluster_ref.name == name
+            {
+                problems
+                    .push("federation.clusterRefs: A cluster can not reference itself".to_string());
+            }
+        }
+        for (index, member) in self.members.iter().enumerate() {
+            if member.id == 0 {
+                problems.push(format!(
+                    "federation.members[{}]: The id must be greater than 0",
+                    index
+                ));
+            }
+            if self.members[..index]
+                .iter()
+                .any(|other| other.id == member.id)
+            {
+                problems.push(format!(
+                    "federation.members[{}]: The id [{}] is used by another member",
+                    index, member.id
+                ));
+            }
+            if member.address.is_empty() {
+                problems.push(format!(
+                    "federation.members[{}]: The address must not be empty",
+                    index
+                ));
+            }
+        }
+        problems
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FederationStatus {
+    /// The servers of this cluster, read by the clusters referencing it in `clusterRefs`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub servers: Vec<FederationMember>,
+    /// The connection string of all servers of the ensemble.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_string: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_member() {
+        let member: FederationMember = serde_yaml::from_str(indoc! {"
+            id: 11
+            address: zk-1.eu-west.example.com
+            quorumPort: 12888
+            observer: true
+        "})
+        .unwrap();
+
+        assert_eq!(
+            member.server_entry(),
+            "zk-1.eu-west.example.com:12888:3888:observer"
+        );
+        assert_eq!(member.client_address(), "zk-1.eu-west.example.com:2181");
+    }
+
+    #[test]
+    fn test_reserved_ids() {
+        let federation: ZookeeperFederation = serde_yaml::from_str(indoc! {"
+            idOffset: 3
+            members:
+              - id: 11
+                address: 203.0.113.11
+        "})
+        .unwrap();
+
+        assert_eq!(federation.reserved_ids(), vec![1, 2, 3, 11]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let federation: ZookeeperFederation = serde_yaml::from_str(indoc! {"
+            clusterRefs:
+              - name: simple
+              - name: simple
+                namespace: other
+            members:
+              - id: 0
+                address: zk-1.example.com
+              - id: 0
+                address: ''
+        "})
+        .unwrap();
+
+        assert_eq!(
+            federation.validate("default", "simple"),
+            vec![
+                "federation.clusterRefs: A cluster can not reference itself",
+                "federation.members[0]: The id must be greater than 0",
+                "federation.members[1]: The id must be greater than 0",
+                "federation.members[1]: The id [0] is used by another member",
+                "federation.members[1]: The address must not be empty",
+            ]
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! The [four letter words](https://zookeeper.apache.org/doc/current/zookeeperAdmin.html#sc_4lw)
+//! the servers answer on the client port.
+//!
+//! ZooKeeper only serves `srvr` by default, while the probes, the health checks and monitoring
+//! tools depend on further commands. The operator therefore sets `4lw.commands.whitelist` to
+//! [`DEFAULT_WHITELIST`] unless configured otherwise.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The `zoo.cfg` property listing the four letter words which are served.
+pub const FOUR_LETTER_WORDS_WHITELIST: &str = "4lw.commands.whitelist";
+/// The whitelist entry enabling all four letter words.
+pub const WILDCARD: &str = "*";
+/// The commands the operator relies on (`srvr` is ZooKeeper's own default).
+pub const REQUIRED_COMMANDS: [&str; 3] = ["ruok", "srvr", "mntr"];
+pub const DEFAULT_WHITELIST: [&str; 5] = ["ruok", "srvr", "stat", "mntr", "conf"];
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperFourLetterWords {
+    /// The commands which are served, e.g. `cons` or `*` for all of them. It has to contain
+    /// `ruok`, `srvr` and `mntr`, which are used by the health checks of the operator. Defaults
+    /// to `ruok`, `srvr`, `stat`, `mntr` and `conf`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub whitelist: Option<Vec<String>>,
+}
+
+impl ZookeeperFourLetterWords {
+    pub fn whitelist(&self) -> Vec<String> {
+        match &self.whitelist {
+            Some(whitelist) => whitelist.clone(),
+            None => DEFAULT_WHITELIST
+                .iter()
+                .map(|command| command.to_string())
+                .collect(),
+        }
+    }
+
+    /// Returns the problems of the whitelist.
+    pub fn validate(&self) -> Vec<String> {
+        let whitelist = self.whitelist();
+        let mut problems = vec![];
+        for command in &whitelist {
+            if command != WILDCARD
+                && !(command.len() == 4 && command.chars().all(|c| c.is_ascii_lowercase()))
+            {
+                problems.push(format!(
+                    "fourLetterWords.whitelist: [{}] is not a four letter word",
+                    command
+                ));
+            }
+        }
+        if !whitelist.iter().any(|command| command == WILDCARD) {
+            for command in REQUIRED_COMMANDS.iter() {
+                if !whitelist.iter().any(|allowed| allowed == command) {
+                    problems.push(format!(
+                        "fourLetterWords.whitelist: [{}] is required by the operator",
+                        command
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Returns the `zoo.cfg` property with the whitelist.
+    pub fn zoo_cfg_properties(&self) -> BTreeMap<String, String> {
+        let mut properties = BTreeMap::new();
+        properties.insert(
+            FOUR_LETTER_WORDS_WHITELIST.to_string(),
+            self.whitelist().join(","),
+        );
+        properties
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use rstest::rstest;
+
+    #[test]
+    fn test_defaults() {
+        let four_letter_words = ZookeeperFourLetterWords::default();
+
+        assert!(four_letter_words.validate().is_empty());
+        assert_eq!(
+            four_letter_words
+                .zoo_cfg_properties()
+                .get(FOUR_LETTER_WORDS_WHITELIST)
+                .unwrap(),
+            "ruok,srvr,stat,mntr,conf"
+        );
+    }
+
+    #[rstest]
+    #[case("whitelist: ['*']", vec![])]
+    #[case("whitelist: [ruok, srvr, mntr, cons]", vec![])]
+    #[case(
+        "whitelist: [ruok, srvr, STAT]",
+        vec![
+            "fourLetterWords.whitelist: [STAT] is not a four letter word",
+            "fourLetterWords.whitelist: [mntr] is required by the operator",
+        ]
+    )]
+    #[case(
+        "whitelist: []",
+        vec![
+            "fourLetterWords.whitelist: [ruok] is required by the operator",
+            "fourLetterWords.whitelist: [srvr] is required by the operator",
+            "fourLetterWords.whitelist: [mntr] is required by the operator",
+        ]
+    )]
+    fn test_validate(#[case] input: &str, #[case] expected: Vec<&str>) {
+        let four_letter_words: ZookeeperFourLetterWords = serde_yaml::from_str(input).unwrap();
+        assert_eq!(four_letter_words.validate(), expected);
+    }
+
+    #[test]
+    fn test_zoo_cfg_properties() {
+        let four_letter_words: ZookeeperFourLetterWords = serde_yaml::from_str(indoc! {"
+            whitelist: [ruok, srvr, mntr, cons]
+        "})
+        .unwrap();
+
+        assert_eq!(
+            four_letter_words.zoo_cfg_properties(),
+            vec![(
+                "4lw.commands.whitelist".to_string(),
+                "ruok,srvr,mntr,cons".to_string()
+            )]
+            .into_iter()
+            .collect()
+        );
+    }
+}
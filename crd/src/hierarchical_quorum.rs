@@ -0,0 +1,171 @@
+//! Hierarchical quorums (`group.<n>` and `weight.<id>` in `zoo.cfg`).
+//!
+//! The participants are split into groups, e.g. one per data center, and a quorum needs a
+//! majority of the weight in a majority of the groups. Groups are declared per role group: all
+//! servers of a role group are in the same group and have the same weight.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The weight of a server if the role group does not set one, like in ZooKeeper.
+pub const DEFAULT_WEIGHT: u32 = 1;
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperHierarchicalQuorum {
+    /// The group and weight of the servers of every participant role group by role group name.
+    /// All participant role groups must be assigned to a group, observers must not.
+    pub role_groups: BTreeMap<String, QuorumGroupMembership>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuorumGroupMembership {
+    /// The number of the group (`group.<n>`), role groups with the same number form one group.
+    pub group: u32,
+    /// The weight of every server of the role group, defaults to 1. Servers with weight 0 do
+    /// not vote.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u32>,
+}
+
+impl QuorumGroupMembership {
+    pub fn weight(&self) -> u32 {
+        self.weight.unwrap_or(DEFAULT_WEIGHT)
+    }
+}
+
+impl ZookeeperHierarchicalQuorum {
+    /// Returns the problems of the hierarchical quorum of a cluster whose role groups are
+    /// given with whether they are observers.
+    pub fn validate(&self, role_groups: &BTreeMap<String, bool>) -> Vec<String> {
+        let mut problems = vec![];
+        for (role_group, membership) in &self.role_groups {
+            match role_groups.get(role_group) {
+                None => problems.push(format!(
+                    "hierarchicalQuorum.roleGroups: The role group [{}] does not exist",
+                    role_group
+                )),
+                Some(true) => problems.push(format!(
+                    "hierarchicalQuorum.roleGroups: The servers of the role group [{}] are observers, which are not part of a group",
+                    role_group
+                )),
+                Some(false) => {}
+            }
+            if membership.group == 0 {
+                problems.push(format!(
+                    "hierarchicalQuorum.roleGroups.{}.group: The group must be greater than 0",
+                    role_group
+                ));
+            }
+        }
+        for (role_group, _) in role_groups.iter().filter(|(_, observer)| !**observer) {
+            if !self.role_groups.contains_key(role_group) {
+                problems.push(format!(
+                    "hierarchicalQuorum.roleGroups: The participant role group [{}] is not assigned to a group",
+                    role_group
+                ));
+            }
+        }
+        if !self.role_groups.is_empty()
+            && self
+                .role_groups
+                .values()
+                .all(|membership| membership.weight() == 0)
+        {
+            problems.push(
+                "hierarchicalQuorum.roleGroups: At least one role group needs a weight greater than 0"
+                    .to_string(),
+            );
+        }
+        problems
+    }
+
+    /// Returns the `group.<n>` and `weight.<id>` properties for the servers with the given ids
+    /// by role group. Groups without servers are left out.
+    pub fn build_properties(
+        &self,
+        ids_by_role_group: &BTreeMap<String, Vec<usize>>,
+    ) -> BTreeMap<String, String> {
+        let mut groups = BTreeMap::<u32, Vec<usize>>::new();
+        let mut properties = BTreeMap::new();
+        for (role_group, membership) in &self.role_groups {
+            let ids = match ids_by_role_group.get(role_group) {
+                Some(ids) if !ids.is_empty() => ids,
+                _ => continue,
+            };
+            groups
+                .entry(membership.group)
+                .or_default()
+                .extend(ids.iter().copied());
+            for id in ids {
+                properties.insert(format!("weight.{}", id), membership.weight().to_string());
+            }
+        }
+        for (group, mut ids) in groups {
+            ids.sort_unstable();
+            properties.insert(
+                format!("group.{}", group),
+                ids.iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(":"),
+            );
+        }
+        properties
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    fn quorum() -> ZookeeperHierarchicalQuorum {
+        serde_yaml::from_str(indoc! {"
+            roleGroups:
+              east:
+                group: 1
+              west:
+                group: 2
+                weight: 2
+              tiebreaker:
+                group: 3
+        "})
+        .unwrap()
+    }
+
+    #[test]
+    fn test_build_properties() {
+        let mut ids_by_role_group = BTreeMap::new();
+        ids_by_role_group.insert("east".to_string(), vec![3, 1]);
+        ids_by_role_group.insert("west".to_string(), vec![2]);
+
+        let properties = quorum().build_properties(&ids_by_role_group);
+
+        let mut expected = BTreeMap::new();
+        expected.insert("group.1".to_string(), "1:3".to_string());
+        expected.insert("group.2".to_string(), "2".to_string());
+        expected.insert("weight.1".to_string(), "1".to_string());
+        expected.insert("weight.2".to_string(), "2".to_string());
+        expected.insert("weight.3".to_string(), "1".to_string());
+        assert_eq!(properties, expected);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut role_groups = BTreeMap::new();
+        role_groups.insert("east".to_string(), false);
+        role_groups.insert("west".to_string(), true);
+        role_groups.insert("south".to_string(), false);
+
+        assert_eq!(
+            quorum().validate(&role_groups),
+            vec![
+                "hierarchicalQuorum.roleGroups: The role group [tiebreaker] does not exist",
+                "hierarchicalQuorum.roleGroups: The servers of the role group [west] are observers, which are not part of a group",
+                "hierarchicalQuorum.roleGroups: The participant role group [south] is not assigned to a group",
+            ]
+        );
+    }
+}
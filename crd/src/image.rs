@@ -0,0 +1,189 @@
+//! Overrides of the image the servers run, e.g. to pull it from a private registry.
+//!
+//! The image is resolved from the version (see `ImageResolver` in the operator) and the parts set
+//! here replace the corresponding parts of the resolved reference.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperImage {
+    /// Replaces the registry and repository of the image, e.g.
+    /// `registry.example.com/mirror/zookeeper`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    /// Replaces the tag of the image, which is derived from the version by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Pins the image to a digest like `sha256:<hex>`, the tag is ignored if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    /// Defaults to `IfNotPresent` (`Always` for the tag `latest`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pull_policy: Option<ImagePullPolicy>,
+    /// The names of the Secrets (of type `kubernetes.io/dockerconfigjson`) in the namespace of
+    /// the cluster which are used to pull the image.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pull_secrets: Vec<String>,
+}
+
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize, strum_macros::Display,
+)]
+pub enum ImagePullPolicy {
+    Always,
+    IfNotPresent,
+    Never,
+}
+
+impl ZookeeperImage {
+    /// Replaces the parts of the `resolved` image reference which are overridden.
+    pub fn apply(&self, resolved: &str) -> String {
+        let (repository, tag) = split_reference(resolved);
+        let repository = self.repository.as_deref().unwrap_or(repository);
+        match (&self.digest, &self.tag, tag) {
+            (Some(digest), _, _) => format!("{}@{}", repository, digest),
+            (None, Some(tag), _) => format!("{}:{}", repository, tag),
+            (None, None, Some(tag)) => format!("{}:{}", repository, tag),
+            (None, None, None) => repository.to_string(),
+        }
+    }
+
+    /// Returns the problems of the image configuration.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if let Some(repository) = &self.repository {
+            if repository.is_empty() || repository.contains('@') || has_tag(repository) {
+                problems.push(format!(
+                    "image.repository: [{}] must not be empty or contain a tag or digest",
+                    repository
+                ));
+            }
+        }
+        if let Some(tag) = &self.tag {
+            if tag.is_empty() || tag.contains(':') || tag.contains('@') || tag.contains('/') {
+                problems.push(format!("image.tag: [{}] is not a valid tag", tag));
+            }
+        }
+        if let Some(digest) = &self.digest {
+            let valid = match digest.split_once(':') {
+                Some((algorithm, hex)) => {
+                    !algorithm.is_empty()
+                        && !hex.is_empty()
+                        && hex.chars().all(|c| c.is_ascii_hexdigit())
+                }
+                None => false,
+            };
+            if !valid {
+                problems.push(format!(
+                    "image.digest: [{}] must look like sha256:<hex>",
+                    digest
+                ));
+            }
+        }
+        for (index, secret) in self.pull_secrets.iter().enumerate() {
+            if secret.is_empty() {
+                problems.push(format!(
+                    "image.pullSecrets[{}]: The Secret name must not be empty",
+                    index
+                ));
+            }
+        }
+        problems
+    }
+}
+
+/// Splits an image reference into the repository (including the registry) and the tag, a digest
+/// is dropped.
+fn split_reference(reference: &str) -> (&str, Option<&str>) {
+    let reference = reference.split('@').next().unwrap_or_default();
+    if has_tag(reference) {
+        let (repository, tag) = reference.rsplit_once(':').unwrap();
+        (repository, Some(tag))
+    } else {
+        (reference, None)
+    }
+}
+
+/// Checks whether the last path component of `reference` has a tag, a colon before it belongs to
+/// the port of the registry.
+fn has_tag(reference: &str) -> bool {
+    reference
+        .rsplit('/')
+        .next()
+        .map_or(false, |name| name.contains(':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(None, None, None, "stackable/zookeeper:3.5.8")]
+    #[case(
+        Some("registry.example.com:5000/mirror/zookeeper"),
+        None,
+        None,
+        "registry.example.com:5000/mirror/zookeeper:3.5.8"
+    )]
+    #[case(None, Some("3.5.8-patched"), None, "stackable/zookeeper:3.5.8-patched")]
+    #[case(
+        Some("registry.example.com/zookeeper"),
+        Some("3.5.8-patched"),
+        Some("sha256:abc123"),
+        "registry.example.com/zookeeper@sha256:abc123"
+    )]
+    fn test_apply(
+        #[case] repository: Option<&str>,
+        #[case] tag: Option<&str>,
+        #[case] digest: Option<&str>,
+        #[case] expected: &str,
+    ) {
+        let image = ZookeeperImage {
+            repository: repository.map(str::to_string),
+            tag: tag.map(str::to_string),
+            digest: digest.map(str::to_string),
+            ..ZookeeperImage::default()
+        };
+
+        assert_eq!(image.apply("stackable/zookeeper:3.5.8"), expected);
+    }
+
+    #[test]
+    fn test_split_reference() {
+        assert_eq!(
+            split_reference("localhost:5000/zookeeper"),
+            ("localhost:5000/zookeeper", None)
+        );
+        assert_eq!(
+            split_reference("localhost:5000/zookeeper:3.5.8@sha256:abc"),
+            ("localhost:5000/zookeeper", Some("3.5.8"))
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let image: ZookeeperImage = serde_yaml::from_str(indoc! {"
+            repository: registry.example.com/zookeeper:3.5.8
+            tag: 3.5.8
+            digest: sha256:xyz
+            pullPolicy: Always
+            pullSecrets:
+              - registry-credentials
+              - ''
+        "})
+        .unwrap();
+
+        assert_eq!(
+            image.validate(),
+            vec![
+                "image.repository: [registry.example.com/zookeeper:3.5.8] must not be empty or contain a tag or digest",
+                "image.digest: [sha256:xyz] must look like sha256:<hex>",
+                "image.pullSecrets[1]: The Secret name must not be empty",
+            ]
+        );
+        assert_eq!(image.pull_policy, Some(ImagePullPolicy::Always));
+    }
+}
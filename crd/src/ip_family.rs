@@ -0,0 +1,157 @@
+//! The IP families of the servers, for IPv6-only and dual-stack Kubernetes clusters.
+//!
+//! `spec.ipFamilies` lists the families in order of preference. It selects the addresses of the
+//! nodes (on the host network and in the endpoints of the external access) and is set on the
+//! Services together with `spec.ipFamilyPolicy`. IPv6 literals are written in brackets wherever
+//! they are followed by a port, e.g. `[2001:db8::1]:2181`.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::net::IpAddr;
+
+/// The JVM flag making the servers prefer IPv6 addresses when resolving names.
+pub const PREFER_IPV6_ADDRESSES_FLAG: &str = "-Djava.net.preferIPv6Addresses=true";
+
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize, strum_macros::Display,
+)]
+pub enum IpFamily {
+    IPv4,
+    IPv6,
+}
+
+/// The `ipFamilyPolicy` of the Services, see the Kubernetes documentation of dual-stack Services.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, strum_macros::Display)]
+pub enum IpFamilyPolicy {
+    /// A single family, the first of the ipFamilies.
+    SingleStack,
+    /// Both families if the Kubernetes cluster is dual-stack, a single one otherwise.
+    PreferDualStack,
+    /// Both families, the Services can not be created in a single-stack cluster.
+    RequireDualStack,
+}
+
+string_enum_schema!(IpFamilyPolicy {
+    SingleStack,
+    PreferDualStack,
+    RequireDualStack
+});
+
+/// Returns the `ipFamilyPolicy` of the Services: the configured one, `PreferDualStack` if two
+/// families are preferred and `None` for the default of Kubernetes otherwise.
+pub fn ip_family_policy(
+    ip_families: &[IpFamily],
+    ip_family_policy: Option<IpFamilyPolicy>,
+) -> Option<IpFamilyPolicy> {
+    match ip_family_policy {
+        Some(ip_family_policy) => Some(ip_family_policy),
+        None if ip_families.len() > 1 => Some(IpFamilyPolicy::PreferDualStack),
+        None => None,
+    }
+}
+
+/// Returns the problems of the IP family settings.
+pub fn validate(ip_families: &[IpFamily], ip_family_policy: Option<IpFamilyPolicy>) -> Vec<String> {
+    let mut problems = vec![];
+    for (index, ip_family) in ip_families.iter().enumerate() {
+        if ip_families[..index].contains(ip_family) {
+            problems.push(format!("ipFamilies: [{}] is listed twice", ip_family));
+        }
+    }
+    if ip_families.len() > 1 && ip_family_policy == Some(IpFamilyPolicy::SingleStack) {
+        problems.push("ipFamilyPolicy: SingleStack allows only one of the ipFamilies".to_string());
+    }
+    problems
+}
+
+/// Returns the family of `address`, `None` if it is not an IP address, e.g. a host name.
+pub fn ip_family(address: &str) -> Option<IpFamily> {
+    match address.parse::<IpAddr>().ok()? {
+        IpAddr::V4(_) => Some(IpFamily::IPv4),
+        IpAddr::V6(_) => Some(IpFamily::IPv6),
+    }
+}
+
+/// Returns the first of `addresses` of the most preferred of the `ip_families`, the first
+/// address if none of them matches.
+pub fn preferred_address<'a>(addresses: &[&'a str], ip_families: &[IpFamily]) -> Option<&'a str> {
+    ip_families
+        .iter()
+        .find_map(|ip_family| {
+            addresses
+                .iter()
+                .find(|address| self::ip_family(address) == Some(*ip_family))
+        })
+        .or_else(|| addresses.first())
+        .copied()
+}
+
+/// Returns `host` in brackets if it is an IPv6 literal, unchanged otherwise.
+pub fn format_host(host: &str) -> String {
+    if ip_family(host) == Some(IpFamily::IPv6) {
+        format!("[{}]", host)
+    } else {
+        host.to_string()
+    }
+}
+
+/// Returns the address of `port` on `host`, e.g. `10.0.0.1:2181` or `[2001:db8::1]:2181`.
+pub fn format_address(host: &str, port: impl Display) -> String {
+    format!("{}:{}", format_host(host), port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("zk-1.example.com", 2181, "zk-1.example.com:2181")]
+    #[case("10.0.0.1", 2181, "10.0.0.1:2181")]
+    #[case("2001:db8::1", 2181, "[2001:db8::1]:2181")]
+    #[case("[2001:db8::1]", 2181, "[2001:db8::1]:2181")]
+    fn test_format_address(#[case] host: &str, #[case] port: u16, #[case] expected: &str) {
+        assert_eq!(format_address(host, port), expected);
+    }
+
+    #[rstest]
+    #[case(&[], Some("10.0.0.1"))]
+    #[case(&[IpFamily::IPv4], Some("10.0.0.1"))]
+    #[case(&[IpFamily::IPv6, IpFamily::IPv4], Some("2001:db8::1"))]
+    fn test_preferred_address(#[case] ip_families: &[IpFamily], #[case] expected: Option<&str>) {
+        assert_eq!(
+            preferred_address(&["10.0.0.1", "2001:db8::1"], ip_families),
+            expected
+        );
+        assert_eq!(preferred_address(&["node-1"], ip_families), Some("node-1"));
+        assert_eq!(preferred_address(&[], ip_families), None);
+    }
+
+    #[test]
+    fn test_ip_family_policy() {
+        assert_eq!(ip_family_policy(&[IpFamily::IPv6], None), None);
+        assert_eq!(
+            ip_family_policy(&[IpFamily::IPv6, IpFamily::IPv4], None),
+            Some(IpFamilyPolicy::PreferDualStack)
+        );
+        assert_eq!(
+            ip_family_policy(&[], Some(IpFamilyPolicy::RequireDualStack)),
+            Some(IpFamilyPolicy::RequireDualStack)
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&[IpFamily::IPv6, IpFamily::IPv4], None).is_empty());
+        assert_eq!(
+            validate(
+                &[IpFamily::IPv4, IpFamily::IPv4],
+                Some(IpFamilyPolicy::SingleStack)
+            ),
+            vec![
+                "ipFamilies: [IPv4] is listed twice",
+                "ipFamilyPolicy: SingleStack allows only one of the ipFamilies",
+            ]
+        );
+    }
+}
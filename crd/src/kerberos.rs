@@ -0,0 +1,112 @@
+//! SASL (GSSAPI) authentication of clients with Kerberos.
+//!
+//! The servers log in with the keytab from the Secret `keytabSecretName`. The principal contains
+//! `_HOST`, which is replaced with the name of the node a server runs on, so a single keytab with
+//! the principals of all nodes can be shared by all servers. The JAAS configuration is the same
+//! for all servers, the host name is passed as system property [`KERBEROS_HOST_PROPERTY`].
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The directory the keytab Secret is mounted to.
+pub const KEYTAB_DIR: &str = "/stackable/kerberos/keytab";
+/// The directory the `krb5.conf` ConfigMap is mounted to.
+pub const KRB5_CONF_DIR: &str = "/stackable/kerberos/krb5";
+/// The key of the keytab in its Secret.
+pub const KEYTAB_KEY: &str = "keytab";
+/// The key of the Kerberos configuration in its ConfigMap.
+pub const KRB5_CONF_KEY: &str = "krb5.conf";
+/// The name of the JAAS configuration file next to `zoo.cfg`.
+pub const JAAS_CONFIG_FILE: &str = "jaas.conf";
+/// The system property holding the host name of a server, which replaces `_HOST`.
+pub const KERBEROS_HOST_PROPERTY: &str = "zookeeper.kerberos.host";
+
+const HOST_PLACEHOLDER: &str = "_HOST";
+const SASL_AUTHENTICATION_PROVIDER: &str =
+    "org.apache.zookeeper.server.auth.SASLAuthenticationProvider";
+
+#[derive(Clone, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperKerberos {
+    /// The Secret containing the keytab (key `keytab`) with the principals of all servers.
+    pub keytab_secret_name: String,
+    /// The principal of the servers including the realm, `_HOST` is replaced with the name of
+    /// the node a server runs on, e.g. `zookeeper/_HOST@EXAMPLE.COM`.
+    pub principal: String,
+    /// The ConfigMap containing the Kerberos configuration (key `krb5.conf`).
+    pub krb5_config_map_name: String,
+}
+
+impl ZookeeperKerberos {
+    /// Returns the `zoo.cfg` properties enabling Kerberos authentication. Principals are mapped
+    /// to their primary, e.g. `app/host@EXAMPLE.COM` authenticates as `app`.
+    pub fn zoo_cfg_properties(&self) -> BTreeMap<String, String> {
+        let mut properties = BTreeMap::new();
+        properties.insert(
+            "authProvider.1".to_string(),
+            SASL_AUTHENTICATION_PROVIDER.to_string(),
+        );
+        properties.insert(
+            "kerberos.removeHostFromPrincipal".to_string(),
+            "true".to_string(),
+        );
+        properties.insert(
+            "kerberos.removeRealmFromPrincipal".to_string(),
+            "true".to_string(),
+        );
+        properties
+    }
+
+    /// Renders the JAAS configuration of the servers.
+    pub fn jaas_config(&self) -> String {
+        let principal = self.principal.replace(
+            HOST_PLACEHOLDER,
+            &format!("${{{}}}", KERBEROS_HOST_PROPERTY),
+        );
+        format!(
+            "Server {{\n  com.sun.security.auth.module.Krb5LoginModule required\n  useKeyTab=true\n  keyTab=\"{}/{}\"\n  storeKey=true\n  useTicketCache=false\n  principal=\"{}\";\n}};\n",
+            KEYTAB_DIR, KEYTAB_KEY, principal
+        )
+    }
+
+    /// Returns the problems of the Kerberos configuration.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !self.principal.contains('@') {
+            problems.push(format!(
+                "kerberos.principal: [{}] does not contain the realm, e.g. zookeeper/_HOST@EXAMPLE.COM",
+                self.principal
+            ));
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kerberos(principal: &str) -> ZookeeperKerberos {
+        ZookeeperKerberos {
+            keytab_secret_name: "zookeeper-keytab".to_string(),
+            principal: principal.to_string(),
+            krb5_config_map_name: "krb5".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_jaas_config() {
+        assert_eq!(
+            kerberos("zookeeper/_HOST@EXAMPLE.COM").jaas_config(),
+            "Server {\n  com.sun.security.auth.module.Krb5LoginModule required\n  useKeyTab=true\n  keyTab=\"/stackable/kerberos/keytab/keytab\"\n  storeKey=true\n  useTicketCache=false\n  principal=\"zookeeper/${zookeeper.kerberos.host}@EXAMPLE.COM\";\n};\n"
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(kerberos("zookeeper/_HOST@EXAMPLE.COM")
+            .validate()
+            .is_empty());
+        assert_eq!(kerberos("zookeeper/_HOST").validate().len(), 1);
+    }
+}
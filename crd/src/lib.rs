@@ -1,15 +1,80 @@
+#[macro_use]
+mod schema;
+
+pub mod admin_server;
+pub mod authentication;
+pub mod autopurge;
+pub mod backup;
+pub mod benchmark;
+pub mod chroot;
+pub mod client_secret;
+pub mod dry_run;
 pub mod error;
+pub mod external_access;
+pub mod extra_containers;
+pub mod extra_volumes;
+pub mod federation;
+pub mod four_letter_words;
+pub mod hierarchical_quorum;
+pub mod image;
+pub mod ip_family;
+pub mod kerberos;
+pub mod logging;
+pub mod member_replacement;
+pub mod monitoring;
+pub mod network_policy;
+pub mod placement;
+pub mod pod_overrides;
+pub mod ports;
+pub mod quorum;
+pub mod security_context;
+pub mod service_account;
+pub mod standby;
+pub mod tamper_detection;
+pub mod tls;
+pub mod upgrade;
 pub mod util;
+pub mod version;
+pub mod znode;
 
+use admin_server::ZookeeperAdminServer;
+use authentication::ZookeeperAuthentication;
+use autopurge::ZookeeperAutopurge;
+use backup::{BackupStatus, RestoreStatus, ZookeeperBackup, ZookeeperRestore};
+use dry_run::DryRunStatus;
+use external_access::{ExternalAccessStatus, ZookeeperExternalAccess};
+use extra_containers::ZookeeperExtraContainers;
+use extra_volumes::ZookeeperExtraVolumes;
+use federation::{FederationStatus, ZookeeperFederation};
+use four_letter_words::{ZookeeperFourLetterWords, FOUR_LETTER_WORDS_WHITELIST};
+use hierarchical_quorum::ZookeeperHierarchicalQuorum;
+use image::ZookeeperImage;
+use ip_family::{IpFamily, IpFamilyPolicy};
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
+use kerberos::ZookeeperKerberos;
 use kube::CustomResource;
+use logging::ZookeeperLogging;
+use member_replacement::{MemberReplacementStatus, ZookeeperMemberReplacement};
+use monitoring::ZookeeperMonitoring;
+use network_policy::ZookeeperNetworkPolicy;
+use placement::ZookeeperPlacement;
+use pod_overrides::ZookeeperPodOverrides;
+use ports::ZookeeperPorts;
+use quorum::{EnsembleMember, QuorumStatus, ZookeeperQuorumReporting};
 use schemars::JsonSchema;
-use semver::{Error as SemVerError, Version};
+use security_context::ZookeeperSecurityContext;
 use serde::{Deserialize, Serialize};
+use service_account::ZookeeperServiceAccount;
 use stackable_operator::product_config_utils::{ConfigError, Configuration};
 use stackable_operator::role_utils::Role;
 use stackable_operator::status::Conditions;
+use standby::{StandbyStatus, ZookeeperStandby};
 use std::collections::BTreeMap;
+use tamper_detection::ZookeeperTamperDetection;
+use tls::ZookeeperTls;
+use upgrade::{CanaryStatus, IntegrityCheckStatus, ZookeeperUpgrade};
+
+pub use version::ZookeeperVersion;
 
 pub const APP_NAME: &str = "zookeeper";
 pub const MANAGED_BY: &str = "zookeeper-operator";
@@ -21,7 +86,19 @@ pub const SYNC_LIMIT: &str = "syncLimit";
 pub const TICK_TIME: &str = "tickTime";
 pub const METRICS_PORT: &str = "metricsPort";
 pub const ADMIN_PORT: &str = "admin.serverPort";
+pub const PEER_TYPE: &str = "peerType";
+
+/// Properties of `zoo.cfg` which are managed by the operator and can not be set in
+/// `spec.config`. Keys starting with `server.` are protected as well.
+pub const PROTECTED_CONFIG_KEYS: [&str; 5] = [
+    CLIENT_PORT,
+    DATA_DIR,
+    ADMIN_PORT,
+    PEER_TYPE,
+    "dynamicConfigFile",
+];
 
+/// Features of the client protocol and the server, with the first version supporting them.
 pub const CONFIG_MAP_TYPE_DATA: &str = "data";
 pub const CONFIG_MAP_TYPE_ID: &str = "id";
 
@@ -37,9 +114,278 @@ pub const CONFIG_MAP_TYPE_ID: &str = "id";
     namespaced
 )]
 #[kube(status = "ZookeeperClusterStatus")]
+#[kube(
+    scale = r#"{"specReplicasPath":".spec.replicas","statusReplicasPath":".status.replicas","labelSelectorPath":".status.selector"}"#
+)]
+#[serde(rename_all = "camelCase")]
 pub struct ZookeeperClusterSpec {
     pub version: ZookeeperVersion,
+    /// Selects a variant of the image for the version (e.g. a hardened build), how it is mapped
+    /// to an image depends on the image resolver the operator runs with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_variant: Option<String>,
+    /// Overrides the repository, tag or digest of the image and sets how it is pulled, e.g. from
+    /// a private registry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<ZookeeperImage>,
+    /// How the servers are upgraded when `version` changes, e.g. with a canary.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upgrade: Option<ZookeeperUpgrade>,
     pub servers: Role<ZookeeperConfig>,
+    /// The number of servers, which replaces the `replicas` of the role group. It can only be
+    /// set if `servers` has a single role group and is the target of `kubectl scale` and
+    /// HorizontalPodAutoscalers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replicas: Option<u16>,
+    /// What happens if the number of participants is below 3 or even, which does not make the
+    /// ensemble more fault tolerant: `Warn` creates it anyway, `Reject` refuses to reconcile it.
+    /// Defaults to the `--ensemble-size-policy` of the operator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ensemble_size_policy: Option<EnsembleSizePolicy>,
+    /// Splits the participants into groups with weights, e.g. one group per data center, so a
+    /// quorum needs a majority of the weight in a majority of the groups.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hierarchical_quorum: Option<ZookeeperHierarchicalQuorum>,
+    /// The client, quorum and leader election ports of the servers, e.g. if the defaults are
+    /// blocked or already in use on the nodes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ports: Option<ZookeeperPorts>,
+    /// Runs the servers in the network namespace of their nodes, e.g. for clients which can only
+    /// reach the nodes. The servers address each other by the `InternalIP` of their nodes.
+    #[serde(default)]
+    pub host_network: bool,
+    /// The DNS policy of the server pods. Defaults to `ClusterFirstWithHostNet` with
+    /// `hostNetwork`, so the servers still resolve Services, and to `ClusterFirst` otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns_policy: Option<DnsPolicy>,
+    /// The IP families of the servers in order of preference, e.g. `[IPv6, IPv4]` for a
+    /// dual-stack cluster preferring IPv6. Selects the addresses of the nodes and is set on the
+    /// Services. Defaults to the family of the Kubernetes cluster.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ip_families: Vec<IpFamily>,
+    /// The `ipFamilyPolicy` of the Services. Defaults to `PreferDualStack` if two `ipFamilies`
+    /// are given and to `SingleStack` otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip_family_policy: Option<IpFamilyPolicy>,
+    /// Exposes the client port outside of Kubernetes with NodePort Services per server or a
+    /// LoadBalancer Service.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_access: Option<ZookeeperExternalAccess>,
+    /// Enables or disables the AdminServer (ZooKeeper 3.5+), sets its port and exposes it with a
+    /// Service.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_server: Option<ZookeeperAdminServer>,
+    /// The four letter words the servers answer on the client port. Without it, the operator
+    /// serves `ruok`, `srvr`, `stat`, `mntr` and `conf` unless `4lw.commands.whitelist` is set in
+    /// `config`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub four_letter_words: Option<ZookeeperFourLetterWords>,
+    /// If enabled the operator publishes a [`ClientRebalanceHint`] in the status whenever the
+    /// ensemble was scaled up, so clients know they should spread their connections across the
+    /// new members.
+    #[serde(default)]
+    pub rebalance_clients_after_scale_up: bool,
+    /// Stops the operator from changing the objects of the cluster (e.g. during manual
+    /// maintenance), only the status is updated.
+    #[serde(default)]
+    pub paused: bool,
+    /// Deletes all pods of the cluster while keeping its other objects (e.g. the ConfigMaps with
+    /// the ids of the servers) and its status, so the same ensemble comes back once it is unset.
+    /// Only allowed if the `dataDir` is on a persistent volume of `extraVolumes`, otherwise the
+    /// data would be deleted with the pods.
+    #[serde(default)]
+    pub stopped: bool,
+    /// Whether the pods are deleted together with the cluster (`Delete`) or are kept running
+    /// with their ConfigMaps and the super user Secret (`Orphan`).
+    #[serde(default)]
+    pub deletion_policy: DeletionPolicy,
+    /// How pods running with a changed configuration are restarted: one by one (`RollingUpdate`),
+    /// only once they were deleted by someone else (`OnDelete`) or one by one after the rollout
+    /// was approved (`ManualApproval`).
+    #[serde(default)]
+    pub update_strategy: UpdateStrategy,
+    /// Overrides the timings of the liveness and readiness probes of the servers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probes: Option<ZookeeperProbes>,
+    /// How often the operator probes the servers for the quorum health in `status.quorum`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quorum_reporting: Option<ZookeeperQuorumReporting>,
+    /// The compute resources of the server containers. Unset values use the defaults of the
+    /// operator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ZookeeperResources>,
+    /// The heap size and further flags of the JVM running the servers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jvm: Option<JvmConfig>,
+    /// The log levels and appenders of the servers. Without it, the servers use the logging
+    /// configuration of the image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logging: Option<ZookeeperLogging>,
+    /// Properties which are added to `zoo.cfg` of all servers, e.g. `maxClientCnxns` or
+    /// `autopurge.purgeInterval`. The properties of the role and role groups (including their
+    /// `configOverrides`) take precedence.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub config: BTreeMap<String, String>,
+    /// The purging of old snapshots and transaction logs, enabled by default. Without it, the
+    /// `autopurge.*` properties in `config` replace the defaults.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autopurge: Option<ZookeeperAutopurge>,
+    /// The scheduler which schedules the server pods. If not set, the operator binds the pods to
+    /// their nodes itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheduler_name: Option<String>,
+    /// The tolerations, node selector, affinity and anti-affinity of the server pods.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placement: Option<ZookeeperPlacement>,
+    /// Labels, annotations, environment variables, the security context and the PriorityClass
+    /// added to the server pods.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pod_overrides: Option<ZookeeperPodOverrides>,
+    /// The security context of the server pods, by default a non-root user with a read-only root
+    /// filesystem which passes the `restricted` PodSecurity standard.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub security_context: Option<ZookeeperSecurityContext>,
+    /// The ServiceAccount of the server pods, by default a dedicated one per cluster which is
+    /// created by the operator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_account: Option<ZookeeperServiceAccount>,
+    /// Init containers and sidecars added to the server pods and the volumes they share with the
+    /// server container.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_containers: Option<ZookeeperExtraContainers>,
+    /// Volumes added to the server pods and mounted into the server container, e.g. Secrets,
+    /// ConfigMaps, `emptyDir` volumes or PersistentVolumeClaims.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_volumes: Option<ZookeeperExtraVolumes>,
+    /// Enables TLS for client connections and/or the communication between the servers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<ZookeeperTls>,
+    /// Enables SASL (DIGEST-MD5) authentication with a super user managed by the operator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authentication: Option<ZookeeperAuthentication>,
+    /// Enables SASL (GSSAPI) authentication with Kerberos, it can not be combined with
+    /// `authentication`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kerberos: Option<ZookeeperKerberos>,
+    /// Exposes Prometheus metrics of the servers, with the metrics provider of ZooKeeper 3.6+ or
+    /// a JMX exporter sidecar for older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitoring: Option<ZookeeperMonitoring>,
+    /// Limits how many servers are added at once when the ensemble is scaled up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scaling: Option<ZookeeperScalingPolicy>,
+    /// Whether servers which stay not ready (e.g. because their node is gone) are replaced
+    /// automatically.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub member_replacement: Option<ZookeeperMemberReplacement>,
+    /// Isolates the servers with a NetworkPolicy which only allows the traffic between the
+    /// servers and from the operator and the listed clients.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_policy: Option<ZookeeperNetworkPolicy>,
+    /// Replaces the command of the server container. By default the servers are started by a
+    /// wrapper script rendered into the ConfigMap (`entrypoint.sh`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+    /// Replaces the arguments of the command. By default the ZooKeeper, config and data
+    /// directories are passed to the wrapper script.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Reports modifications of the pods and ConfigMaps of the cluster by unknown field managers
+    /// and optionally pauses disruptive operations until they are acknowledged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tamper_detection: Option<ZookeeperTamperDetection>,
+    /// Makes the cluster a warm standby of another cluster whose data is copied periodically.
+    /// Removing it promotes the cluster.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub standby_of: Option<ZookeeperStandby>,
+    /// Makes the servers part of an ensemble spanning several ZookeeperClusters, e.g. in other
+    /// namespaces or Kubernetes clusters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub federation: Option<ZookeeperFederation>,
+    /// Takes scheduled backups of the znodes and uploads them to S3 compatible object storage.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<ZookeeperBackup>,
+    /// Restores a backup into the data directories of a new cluster before its servers start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restore: Option<ZookeeperRestore>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperScalingPolicy {
+    /// The maximum number of servers added in one step, defaults to 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_step: Option<u16>,
+    /// The number of seconds to wait after a step was completed before the next one starts,
+    /// defaults to 60.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cooldown_seconds: Option<u64>,
+}
+
+/// Checks whether the `zoo.cfg` property `key` is managed by the operator, see
+/// [`PROTECTED_CONFIG_KEYS`].
+pub fn is_protected_config_key(key: &str) -> bool {
+    key.starts_with("server.") || PROTECTED_CONFIG_KEYS.contains(&key)
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JvmConfig {
+    /// The heap size as memory quantity (e.g. `2Gi`), defaults to 75% of the memory limit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heap_size: Option<String>,
+    /// Garbage collector options, e.g. `-XX:+UseG1GC`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gc_options: Vec<String>,
+    /// System properties, which are passed as `-D<key>=<value>`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub system_properties: BTreeMap<String, String>,
+    /// Further flags which are passed to the JVM as they are, after all other flags.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_flags: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperResources {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requests: Option<ResourceQuantities>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limits: Option<ResourceQuantities>,
+}
+
+/// CPU and memory quantities in the Kubernetes format, e.g. `500m` or `1Gi`.
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceQuantities {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperProbes {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub liveness: Option<ProbeTimings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub readiness: Option<ProbeTimings>,
+}
+
+/// Timings of a probe, see the Kubernetes `Probe` documentation. Unset values use the defaults
+/// of the operator.
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeTimings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initial_delay_seconds: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period_seconds: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_threshold: Option<i32>,
 }
 
 // TODO: These all should be "Property" Enums that can be either simple or complex where complex allows forcing/ignoring errors and/or warnings
@@ -53,6 +399,105 @@ pub struct ZookeeperConfig {
     pub tick_time: Option<u32>,   // int in Java
     pub metrics_port: Option<u16>,
     pub admin_port: Option<u16>,
+    /// Whether the servers of the role group vote (`participant`, the default) or only
+    /// replicate the data and serve clients (`observer`).
+    pub peer_type: Option<PeerType>,
+}
+
+/// What happens to the objects of a cluster when it is deleted.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum DeletionPolicy {
+    /// The pods are deleted, all other objects are garbage collected through their owner
+    /// references.
+    Delete,
+    /// The owner references of the pods, their ConfigMaps and the super user Secret are removed,
+    /// so the servers keep running without the cluster.
+    Orphan,
+}
+
+string_enum_schema!(DeletionPolicy { Delete, Orphan });
+
+impl Default for DeletionPolicy {
+    fn default() -> Self {
+        DeletionPolicy::Delete
+    }
+}
+
+/// How changes of the configuration are rolled out to the running servers.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum UpdateStrategy {
+    /// The outdated pods are restarted one by one.
+    RollingUpdate,
+    /// The outdated pods are not restarted, they get the new configuration once they are deleted.
+    OnDelete,
+    /// The outdated pods are restarted one by one once the rollout was approved with the
+    /// `zookeeper.stackable.tech/rollout-approved` annotation.
+    ManualApproval,
+}
+
+string_enum_schema!(UpdateStrategy {
+    RollingUpdate,
+    OnDelete,
+    ManualApproval
+});
+
+impl Default for UpdateStrategy {
+    fn default() -> Self {
+        UpdateStrategy::RollingUpdate
+    }
+}
+
+/// What happens to ensembles whose number of participants is not recommended.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum EnsembleSizePolicy {
+    /// The ensemble is created, a warning is published.
+    Warn,
+    /// The reconciliation fails until the number of participants is changed.
+    Reject,
+}
+
+string_enum_schema!(EnsembleSizePolicy { Warn, Reject });
+
+impl Default for EnsembleSizePolicy {
+    fn default() -> Self {
+        EnsembleSizePolicy::Warn
+    }
+}
+
+/// How the server pods resolve names, see the `dnsPolicy` of Kubernetes pods.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, strum_macros::Display)]
+pub enum DnsPolicy {
+    /// The cluster DNS, names outside of the cluster are forwarded to the DNS of the node.
+    ClusterFirst,
+    /// The cluster DNS for pods on the host network.
+    ClusterFirstWithHostNet,
+    /// The DNS of the node.
+    Default,
+}
+
+string_enum_schema!(DnsPolicy {
+    ClusterFirst,
+    ClusterFirstWithHostNet,
+    Default
+});
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Deserialize,
+    Eq,
+    JsonSchema,
+    PartialEq,
+    Serialize,
+    strum_macros::Display,
+    strum_macros::EnumString,
+)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum PeerType {
+    Participant,
+    Observer,
 }
 
 impl Configuration for ZookeeperConfig {
@@ -80,11 +525,103 @@ impl Configuration for ZookeeperConfig {
 
     fn compute_files(
         &self,
-        _resource: &Self::Configurable,
+        resource: &Self::Configurable,
         _role_name: &str,
         _file: &str,
     ) -> Result<BTreeMap<String, Option<String>>, ConfigError> {
-        let mut result = BTreeMap::new();
+        // the cluster wide properties come first so that the ones of the role group win
+        let mut result: BTreeMap<String, Option<String>> = BTreeMap::new();
+        if resource.spec.autopurge.is_none() {
+            result.extend(
+                ZookeeperAutopurge::default()
+                    .zoo_cfg_properties()
+                    .into_iter()
+                    .map(|(key, value)| (key, Some(value))),
+            );
+        }
+        result.extend(
+            resource
+                .spec
+                .config
+                .iter()
+                .filter(|(key, _)| !is_protected_config_key(key))
+                .map(|(key, value)| (key.clone(), Some(value.clone()))),
+        );
+        if let Some(client_port) = resource.spec.ports.as_ref().and_then(|ports| ports.client) {
+            result.insert(CLIENT_PORT.to_string(), Some(client_port.to_string()));
+        }
+        if let Some(admin_server) = &resource.spec.admin_server {
+            result.extend(
+                admin_server
+                    .zoo_cfg_properties()
+                    .into_iter()
+                    .map(|(key, value)| (key, Some(value))),
+            );
+        }
+        // the default whitelist is replaced by the one in `config`
+        let four_letter_words = &resource.spec.four_letter_words;
+        if four_letter_words.is_some()
+            || !resource
+                .spec
+                .config
+                .contains_key(FOUR_LETTER_WORDS_WHITELIST)
+        {
+            result.extend(
+                four_letter_words
+                    .clone()
+                    .unwrap_or_default()
+                    .zoo_cfg_properties()
+                    .into_iter()
+                    .map(|(key, value)| (key, Some(value))),
+            );
+        }
+        if let Some(autopurge) = &resource.spec.autopurge {
+            result.extend(
+                autopurge
+                    .zoo_cfg_properties()
+                    .into_iter()
+                    .map(|(key, value)| (key, Some(value))),
+            );
+        }
+        if let Some(logging) = &resource.spec.logging {
+            result.extend(
+                logging
+                    .zoo_cfg_properties()
+                    .into_iter()
+                    .map(|(key, value)| (key, Some(value))),
+            );
+        }
+        if let Some(tls) = &resource.spec.tls {
+            result.extend(
+                tls.zoo_cfg_properties()
+                    .into_iter()
+                    .map(|(key, value)| (key, Some(value))),
+            );
+        }
+        if let Some(authentication) = &resource.spec.authentication {
+            result.extend(
+                authentication
+                    .zoo_cfg_properties()
+                    .into_iter()
+                    .map(|(key, value)| (key, Some(value))),
+            );
+        }
+        if let Some(kerberos) = &resource.spec.kerberos {
+            result.extend(
+                kerberos
+                    .zoo_cfg_properties()
+                    .into_iter()
+                    .map(|(key, value)| (key, Some(value))),
+            );
+        }
+        if let Some(monitoring) = &resource.spec.monitoring {
+            result.extend(
+                monitoring
+                    .zoo_cfg_properties(&resource.spec.version)
+                    .into_iter()
+                    .map(|(key, value)| (key, Some(value))),
+            );
+        }
         if let Some(client_port) = &self.client_port {
             result.insert(CLIENT_PORT.to_string(), Some(client_port.to_string()));
         }
@@ -103,6 +640,9 @@ impl Configuration for ZookeeperConfig {
         if let Some(admin_port) = self.admin_port {
             result.insert(ADMIN_PORT.to_string(), Some(admin_port.to_string()));
         }
+        if let Some(peer_type) = self.peer_type {
+            result.insert(PEER_TYPE.to_string(), Some(peer_type.to_string()));
+        }
         Ok(result)
     }
 }
@@ -124,48 +664,6 @@ impl Conditions for ZookeeperCluster {
     }
 }
 
-#[allow(non_camel_case_types)]
-#[derive(
-    Clone,
-    Debug,
-    Deserialize,
-    Eq,
-    JsonSchema,
-    PartialEq,
-    Serialize,
-    strum_macros::Display,
-    strum_macros::EnumString,
-)]
-pub enum ZookeeperVersion {
-    #[serde(rename = "3.4.14")]
-    #[strum(serialize = "3.4.14")]
-    v3_4_14,
-
-    #[serde(rename = "3.5.8")]
-    #[strum(serialize = "3.5.8")]
-    v3_5_8,
-}
-
-impl ZookeeperVersion {
-    pub fn is_valid_upgrade(&self, to: &Self) -> Result<bool, SemVerError> {
-        let from_version = Version::parse(&self.to_string())?;
-        let to_version = Version::parse(&to.to_string())?;
-
-        Ok(to_version > from_version)
-    }
-
-    pub fn package_name(&self) -> String {
-        match self {
-            ZookeeperVersion::v3_4_14 => {
-                format!("zookeeper-{}", self.to_string())
-            }
-            ZookeeperVersion::v3_5_8 => {
-                format!("apache-zookeeper-{}-bin", self.to_string())
-            }
-        }
-    }
-}
-
 #[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ZookeeperClusterStatus {
@@ -173,9 +671,255 @@ pub struct ZookeeperClusterStatus {
     pub current_version: Option<ZookeeperVersion>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub target_version: Option<ZookeeperVersion>,
+    /// The canary of the upgrade to `targetVersion`, see `spec.upgrade.canary`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub canary: Option<CanaryStatus>,
+    /// The integrity check before the upgrade to `targetVersion`, see
+    /// `spec.upgrade.integrityCheck`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity_check: Option<IntegrityCheckStatus>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     #[schemars(schema_with = "stackable_operator::conditions::schema")]
     pub conditions: Vec<Condition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_rebalance: Option<ClientRebalanceHint>,
+    /// The addresses of the servers outside of Kubernetes if `spec.externalAccess` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_access: Option<ExternalAccessStatus>,
+    /// The number of servers whose pods are ready and which respond to health checks as part of
+    /// the quorum.
+    #[serde(default)]
+    pub ready_replicas: u16,
+    /// The number of server pods, as shown by the `scale` subresource.
+    #[serde(default)]
+    pub replicas: u16,
+    /// The label selector of the server pods, as shown by the `scale` subresource.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+    /// The `metadata.generation` of the ZookeeperCluster the status was last computed for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub observed_generation: Option<i64>,
+    /// How long it took to reach the desired state after the last creation (`creation`) and the
+    /// last spec change (`specChange`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub last_transition_durations: BTreeMap<String, TransitionDuration>,
+    /// The transition towards the desired state which is currently in progress.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_transition: Option<PendingTransition>,
+    /// When the cluster wide objects (e.g. the Services, the PodDisruptionBudget and the
+    /// NetworkPolicy) were last applied completely and what they were built from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_applied: Option<LastAppliedStatus>,
+    /// The last pod the operator restarted (or upgraded), followers and observers are restarted
+    /// before the leader.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_restart_step: Option<RestartStepStatus>,
+    /// The pods which still have to be restarted to roll out a changed configuration, see
+    /// `spec.updateStrategy`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rollout: Option<RolloutStatus>,
+    /// The features supported by the running servers, so clients can detect them without
+    /// comparing versions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<ZookeeperCapabilities>,
+    /// The progress of a scale-up limited by `spec.scaling`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scaling: Option<ScalingStatus>,
+    /// The replacement of a failed server which is in progress, see `spec.memberReplacement`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub member_replacement: Option<MemberReplacementStatus>,
+    /// The rolling restart requested with the `zookeeper.stackable.tech/restart` annotation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart: Option<RestartStatus>,
+    /// The last successful smoke test after a disruptive operation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smoke_test: Option<SmokeTestStatus>,
+    /// The leader, the synchronization of the followers and the load of every server, as
+    /// reported by the servers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quorum: Option<QuorumStatus>,
+    /// The current members of the ensemble with their id, role, address and readiness, ordered
+    /// by id.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<EnsembleMember>,
+    /// The progress of copying the data of the primary while the cluster is a standby.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub standby: Option<StandbyStatus>,
+    /// The servers of this cluster as members of the federation and the connection string of
+    /// the whole ensemble.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub federation: Option<FederationStatus>,
+    /// The scheduled backups of the cluster.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup: Option<BackupStatus>,
+    /// The restore of `spec.restore`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restore: Option<RestoreStatus>,
+    /// The external condition the reconciliation currently waits for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub waiting: Option<WaitingStatus>,
+    /// The settings of the spec which fall back to defaults of the operator, with the defaults
+    /// filled in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effective_spec: Option<ZookeeperEffectiveSpec>,
+    /// The changes the operator would make while the cluster is in dry-run mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dry_run: Option<DryRunStatus>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperEffectiveSpec {
+    /// The number of servers per role group. Role groups without `replicas` run a server on
+    /// every node matching their selector.
+    #[serde(default)]
+    pub replicas: BTreeMap<String, u16>,
+    pub probes: ZookeeperProbes,
+    pub resources: ZookeeperResources,
+    /// The cluster wide autopurge settings from `autopurge` or `config`.
+    pub autopurge: ZookeeperAutopurge,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WaitingStatus {
+    /// Why the reconciliation waits, e.g. `DisruptionBudget`.
+    pub reason: String,
+    /// What exactly the reconciliation waits for.
+    pub message: String,
+    /// RFC 3339 timestamp of when the reconciliation started waiting for this reason.
+    pub since: String,
+    /// How often the reconciliation was requeued for this reason so far.
+    pub attempts: u32,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestartStatus {
+    /// The value of the annotation which requested the restart.
+    pub token: String,
+    /// RFC 3339 timestamp of when the restart was requested, all pods created before are
+    /// restarted.
+    pub started_at: String,
+    /// RFC 3339 timestamp of when all pods were restarted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestartStepStatus {
+    pub pod: String,
+    /// Why the pod was restarted: `configuration`, `references`, `certificate`, `request`,
+    /// `upgrade` or `canary`.
+    pub reason: String,
+    /// The mode of the server in the pod before the restart, like in `status.quorum`.
+    pub mode: String,
+    /// The pod of the leader before the restart, which is restarted last.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leader: Option<String>,
+    /// The pods which are restarted for the same reason after this one, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remaining: Vec<String>,
+    /// RFC 3339 timestamp of when the pod was deleted.
+    pub restarted_at: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SmokeTestStatus {
+    /// Identifies the pods the smoke test succeeded with, it runs again when pods change.
+    pub pods_fingerprint: String,
+    /// How long it took to create, read and delete the probe znode.
+    pub latency_milliseconds: u64,
+    /// RFC 3339 timestamp of when the smoke test succeeded.
+    pub succeeded_at: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperCapabilities {
+    /// The version of all servers the features were derived from. During an upgrade this is the
+    /// version before the upgrade.
+    pub version: String,
+    /// The supported features: `multi`, `create2`, `removeWatches`, `adminServer`,
+    /// `containerNodes`, `clientTls`, `ttlNodes`, `persistentWatches` and `auditLog`. Features
+    /// which have to be enabled in the configuration (like TTL nodes) might still be disabled.
+    pub features: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScalingStatus {
+    /// The number of servers the scale step in progress works towards.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step_target: Option<usize>,
+    /// RFC 3339 timestamp of when the last scale step was completed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_step_completed_at: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransitionDuration {
+    /// The `metadata.generation` the desired state was reached for.
+    pub generation: i64,
+    /// The number of seconds from the start of the transition until the desired state was
+    /// reached.
+    pub seconds: i64,
+    /// RFC 3339 timestamp of when the desired state was reached.
+    pub completed_at: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingTransition {
+    /// `creation` or `specChange`.
+    pub trigger: String,
+    /// The latest `metadata.generation` the transition works towards.
+    pub generation: i64,
+    /// RFC 3339 timestamp of the start of the transition.
+    pub started_at: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RolloutStatus {
+    /// Identifies the configuration which is rolled out. With the `ManualApproval` strategy, the
+    /// rollout is approved by setting the `zookeeper.stackable.tech/rollout-approved` annotation
+    /// to this value.
+    pub revision: String,
+    /// The pods running with the previous configuration in the order they are restarted.
+    pub pods: Vec<String>,
+    /// Whether the operator restarts the pods.
+    pub approved: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastAppliedStatus {
+    /// The `metadata.generation` of the ZookeeperCluster the objects were applied for.
+    pub generation: i64,
+    /// The hash of everything besides the spec the objects were built from, e.g. the referenced
+    /// Secrets and ConfigMaps and the eligible nodes.
+    pub fingerprint: String,
+    /// RFC 3339 timestamp of when the objects were applied.
+    pub applied_at: String,
+}
+
+/// ZooKeeper clients stay connected to the server they initially connected to, so after a
+/// scale-up the new servers will not get any load until clients reconnect.
+/// This hint tells clients that they should rebalance their connections, e.g. by calling
+/// `ZooKeeper#updateServerList` with a fresh connection string, which (probabilistically) moves
+/// just enough sessions to the new servers without killing any of them.
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientRebalanceHint {
+    /// The number of servers in the ensemble when this hint was last updated.
+    pub servers: usize,
+    /// RFC 3339 timestamp of the last scale-up which requested a rebalance.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requested_at: Option<String>,
 }
 
 impl ZookeeperClusterStatus {
@@ -188,39 +932,20 @@ impl ZookeeperClusterStatus {
 
 #[cfg(test)]
 mod tests {
-    use crate::ZookeeperVersion;
-    use std::str::FromStr;
+    use crate::{is_protected_config_key, PeerType};
 
     #[test]
-    fn test_version_upgrade() {
-        assert!(ZookeeperVersion::v3_4_14
-            .is_valid_upgrade(&ZookeeperVersion::v3_5_8)
-            .unwrap());
-
-        assert!(!ZookeeperVersion::v3_5_8
-            .is_valid_upgrade(&ZookeeperVersion::v3_4_14)
-            .unwrap());
+    fn test_is_protected_config_key() {
+        assert!(is_protected_config_key("server.1"));
+        assert!(is_protected_config_key("dataDir"));
+        assert!(is_protected_config_key("peerType"));
+        assert!(!is_protected_config_key("maxClientCnxns"));
+        assert!(!is_protected_config_key("autopurge.purgeInterval"));
     }
 
     #[test]
-    fn test_version_conversion() {
-        ZookeeperVersion::from_str("3.4.14").unwrap();
-        ZookeeperVersion::from_str("3.5.8").unwrap();
-        ZookeeperVersion::from_str("1.2.3").unwrap_err();
-    }
-
-    #[test]
-    fn test_package_name() {
-        assert_eq!(
-            ZookeeperVersion::v3_4_14.package_name(),
-            format!("zookeeper-{}", ZookeeperVersion::v3_4_14.to_string())
-        );
-        assert_eq!(
-            ZookeeperVersion::v3_5_8.package_name(),
-            format!(
-                "apache-zookeeper-{}-bin",
-                ZookeeperVersion::v3_5_8.to_string()
-            )
-        );
+    fn test_peer_type() {
+        assert_eq!(PeerType::Observer.to_string(), "observer");
+        assert_eq!("participant".parse::<PeerType>(), Ok(PeerType::Participant));
     }
 }
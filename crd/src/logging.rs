@@ -0,0 +1,323 @@
+//! The log4j configuration of the servers.
+//!
+//! The configuration is rendered into the data ConfigMap as [`LOG4J_CONFIG_FILE`] and passed to
+//! the servers with `-Dlog4j.configuration`, so changes roll the servers like any other change of
+//! the ConfigMap. Without `spec.logging` the servers use the configuration of the image.
+//!
+//! The audit log (ZooKeeper 3.6+) is enabled with [`AUDIT_ENABLE`] in `zoo.cfg` and gets its own
+//! appender, its records do not show up in the regular log.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The name of the log4j configuration in the data ConfigMap.
+pub const LOG4J_CONFIG_FILE: &str = "log4j.properties";
+
+/// The layout of the log records, the default of ZooKeeper.
+const CONVERSION_PATTERN: &str = "%d{ISO8601} [myid:%X{myid}] - %-5p [%t:%C{1}@%L] - %m%n";
+
+/// The layout of the audit records, the default of ZooKeeper.
+const AUDIT_CONVERSION_PATTERN: &str = "%d{ISO8601} %p %c{2}: %m%n";
+
+const CONSOLE_APPENDER: &str = "CONSOLE";
+const FILE_APPENDER: &str = "ROLLINGFILE";
+const AUDIT_APPENDER: &str = "AUDIT";
+
+/// The `zoo.cfg` property enabling the audit log.
+pub const AUDIT_ENABLE: &str = "audit.enable";
+/// The logger the audit records are written to.
+const AUDIT_LOGGER: &str = "org.apache.zookeeper.audit.Log4jAuditLogger";
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperLogging {
+    /// The level of the root logger, defaults to `INFO`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root_level: Option<LogLevel>,
+    /// The levels of single loggers, e.g. `org.apache.zookeeper.server.quorum: DEBUG`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub loggers: BTreeMap<String, LogLevel>,
+    /// Whether the records are written to the console (and therefore to the pod log), defaults
+    /// to true.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub console: Option<bool>,
+    /// Additionally writes the records to a rolling file, e.g. in a volume shared with a log
+    /// shipper.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<ZookeeperLogFile>,
+    /// Enables the audit log of ZooKeeper 3.6+, which records the operations of the clients
+    /// together with the authenticated user.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit: Option<ZookeeperAuditLog>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperAuditLog {
+    /// Writes the audit records to a rolling file (e.g. in a volume shared with a log shipper)
+    /// instead of the console.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<ZookeeperLogFile>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperLogFile {
+    /// The absolute path of the log file, e.g. `/stackable/logs/zookeeper.log`.
+    pub path: String,
+    /// The size after which the file is rolled, e.g. `10MB`. Defaults to `10MB`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<String>,
+    /// The number of rolled files which are kept, defaults to 10.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_backup_index: Option<u32>,
+}
+
+impl ZookeeperLogFile {
+    fn validate(&self, field: &str) -> Vec<String> {
+        let mut problems = vec![];
+        if !self.path.starts_with('/') || self.path.ends_with('/') {
+            problems.push(format!(
+                "{}.path: [{}] must be an absolute path of a file",
+                field, self.path
+            ));
+        }
+        problems
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize, strum_macros::Display,
+)]
+#[serde(rename_all = "UPPERCASE")]
+#[strum(serialize_all = "UPPERCASE")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+    Off,
+}
+
+impl ZookeeperLogging {
+    pub fn console(&self) -> bool {
+        self.console.unwrap_or(true)
+    }
+
+    /// Returns the problems of the logging configuration.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if !self.console() && self.file.is_none() {
+            problems.push("logging: Either console or file has to be enabled".to_string());
+        }
+        if let Some(file) = &self.file {
+            problems.extend(file.validate("logging.file"));
+        }
+        if let Some(audit_file) = self.audit.as_ref().and_then(|audit| audit.file.as_ref()) {
+            problems.extend(audit_file.validate("logging.audit.file"));
+            if self.file.as_ref().map(|file| &file.path) == Some(&audit_file.path) {
+                problems
+                    .push("logging.audit.file.path: The audit log needs its own file".to_string());
+            }
+        }
+        for logger in self.loggers.keys() {
+            if logger.is_empty() || logger.contains(char::is_whitespace) || logger.contains('=') {
+                problems.push(format!(
+                    "logging.loggers: [{}] is not a valid logger name",
+                    logger
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Returns the `zoo.cfg` properties required by the logging configuration.
+    pub fn zoo_cfg_properties(&self) -> BTreeMap<String, String> {
+        let mut properties = BTreeMap::new();
+        if self.audit.is_some() {
+            properties.insert(AUDIT_ENABLE.to_string(), "true".to_string());
+        }
+        properties
+    }
+
+    /// Renders the log4j configuration.
+    pub fn log4j_properties(&self) -> String {
+        let mut appenders = vec![];
+        let mut lines = vec![];
+        if self.console() {
+            appenders.push(CONSOLE_APPENDER);
+            lines.extend(console_appender(CONSOLE_APPENDER, CONVERSION_PATTERN));
+        }
+        if let Some(file) = &self.file {
+            appenders.push(FILE_APPENDER);
+            lines.extend(rolling_file_appender(
+                FILE_APPENDER,
+                file,
+                CONVERSION_PATTERN,
+            ));
+        }
+        for (logger, level) in &self.loggers {
+            lines.push(format!("log4j.logger.{}={}", logger, level));
+        }
+        // the audit records are not additive, so they only end up in the audit appender
+        if let Some(audit) = &self.audit {
+            lines.extend(match &audit.file {
+                Some(file) => rolling_file_appender(AUDIT_APPENDER, file, AUDIT_CONVERSION_PATTERN),
+                None => console_appender(AUDIT_APPENDER, AUDIT_CONVERSION_PATTERN),
+            });
+            lines.push(format!(
+                "log4j.logger.{}=INFO, {}",
+                AUDIT_LOGGER, AUDIT_APPENDER
+            ));
+            lines.push(format!("log4j.additivity.{}=false", AUDIT_LOGGER));
+        }
+
+        let root_logger = std::iter::once(self.root_level.unwrap_or(LogLevel::Info).to_string())
+            .chain(appenders.into_iter().map(str::to_string))
+            .collect::<Vec<_>>()
+            .join(", ");
+        std::iter::once(format!("log4j.rootLogger={}", root_logger))
+            .chain(lines)
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+}
+
+fn console_appender(appender: &str, conversion_pattern: &str) -> Vec<String> {
+    let mut lines = vec![format!(
+        "log4j.appender.{}=org.apache.log4j.ConsoleAppender",
+        appender
+    )];
+    lines.extend(layout(appender, conversion_pattern));
+    lines
+}
+
+fn rolling_file_appender(
+    appender: &str,
+    file: &ZookeeperLogFile,
+    conversion_pattern: &str,
+) -> Vec<String> {
+    let mut lines = vec![
+        format!(
+            "log4j.appender.{}=org.apache.log4j.RollingFileAppender",
+            appender
+        ),
+        format!("log4j.appender.{}.File={}", appender, file.path),
+        format!(
+            "log4j.appender.{}.MaxFileSize={}",
+            appender,
+            file.max_file_size.as_deref().unwrap_or("10MB")
+        ),
+        format!(
+            "log4j.appender.{}.MaxBackupIndex={}",
+            appender,
+            file.max_backup_index.unwrap_or(10)
+        ),
+    ];
+    lines.extend(layout(appender, conversion_pattern));
+    lines
+}
+
+fn layout(appender: &str, conversion_pattern: &str) -> Vec<String> {
+    vec![
+        format!(
+            "log4j.appender.{}.layout=org.apache.log4j.PatternLayout",
+            appender
+        ),
+        format!(
+            "log4j.appender.{}.layout.ConversionPattern={}",
+            appender, conversion_pattern
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_log4j_properties() {
+        let logging: ZookeeperLogging = serde_yaml::from_str(indoc! {"
+            rootLevel: WARN
+            loggers:
+              org.apache.zookeeper.server.quorum: DEBUG
+            file:
+              path: /stackable/logs/zookeeper.log
+              maxBackupIndex: 3
+        "})
+        .unwrap();
+
+        assert!(logging.validate().is_empty());
+        assert_eq!(
+            logging.log4j_properties(),
+            indoc! {"
+                log4j.rootLogger=WARN, CONSOLE, ROLLINGFILE
+                log4j.appender.CONSOLE=org.apache.log4j.ConsoleAppender
+                log4j.appender.CONSOLE.layout=org.apache.log4j.PatternLayout
+                log4j.appender.CONSOLE.layout.ConversionPattern=%d{ISO8601} [myid:%X{myid}] - %-5p [%t:%C{1}@%L] - %m%n
+                log4j.appender.ROLLINGFILE=org.apache.log4j.RollingFileAppender
+                log4j.appender.ROLLINGFILE.File=/stackable/logs/zookeeper.log
+                log4j.appender.ROLLINGFILE.MaxFileSize=10MB
+                log4j.appender.ROLLINGFILE.MaxBackupIndex=3
+                log4j.appender.ROLLINGFILE.layout=org.apache.log4j.PatternLayout
+                log4j.appender.ROLLINGFILE.layout.ConversionPattern=%d{ISO8601} [myid:%X{myid}] - %-5p [%t:%C{1}@%L] - %m%n
+                log4j.logger.org.apache.zookeeper.server.quorum=DEBUG
+            "}
+        );
+    }
+
+    #[test]
+    fn test_audit_log() {
+        let logging: ZookeeperLogging = serde_yaml::from_str(indoc! {"
+            console: true
+            audit:
+              file:
+                path: /stackable/logs/audit.log
+        "})
+        .unwrap();
+
+        assert_eq!(
+            logging.zoo_cfg_properties().get(AUDIT_ENABLE).unwrap(),
+            "true"
+        );
+        assert!(logging.log4j_properties().ends_with(indoc! {"
+            log4j.appender.AUDIT=org.apache.log4j.RollingFileAppender
+            log4j.appender.AUDIT.File=/stackable/logs/audit.log
+            log4j.appender.AUDIT.MaxFileSize=10MB
+            log4j.appender.AUDIT.MaxBackupIndex=10
+            log4j.appender.AUDIT.layout=org.apache.log4j.PatternLayout
+            log4j.appender.AUDIT.layout.ConversionPattern=%d{ISO8601} %p %c{2}: %m%n
+            log4j.logger.org.apache.zookeeper.audit.Log4jAuditLogger=INFO, AUDIT
+            log4j.additivity.org.apache.zookeeper.audit.Log4jAuditLogger=false
+        "}));
+
+        let without_audit = ZookeeperLogging::default();
+        assert!(without_audit.zoo_cfg_properties().is_empty());
+        assert!(!without_audit.log4j_properties().contains("AUDIT"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let logging: ZookeeperLogging = serde_yaml::from_str(indoc! {"
+            console: false
+            loggers:
+              'org.apache zookeeper': INFO
+            audit:
+              file:
+                path: logs/audit.log
+        "})
+        .unwrap();
+
+        assert_eq!(
+            logging.validate(),
+            vec![
+                "logging: Either console or file has to be enabled",
+                "logging.audit.file.path: [logs/audit.log] must be an absolute path of a file",
+                "logging.loggers: [org.apache zookeeper] is not a valid logger name",
+            ]
+        );
+    }
+}
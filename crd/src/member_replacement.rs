@@ -0,0 +1,116 @@
+//! Replacing servers which stay not ready, e.g. because their node is gone or their data
+//! directory is broken.
+//!
+//! A server counts as failed once its pod was not ready for longer than
+//! [`DEFAULT_NOT_READY_SECONDS`]. Depending on the policy the operator only reports it or deletes
+//! the pod (and wipes the data directory of its replacement), so a new server with the same `myid`
+//! joins the ensemble. Only one server is replaced at a time, and only while the others still form
+//! a quorum. A replacement which is not ready within the same time is given up.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_NOT_READY_SECONDS: u32 = 600;
+/// Servers restart and resynchronize within this time, shorter thresholds would replace them.
+pub const MIN_NOT_READY_SECONDS: u32 = 60;
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperMemberReplacement {
+    /// What happens to failed servers: `Never` only reports them, `ReplacePod` deletes their
+    /// pods so they are recreated and `ReplacePodAndData` additionally empties the data directory
+    /// of the replacement. Defaults to `Never`.
+    #[serde(default)]
+    pub policy: MemberReplacementPolicy,
+    /// How long a pod has to be not ready before its server counts as failed, at least 60.
+    /// Defaults to 600.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_ready_seconds: Option<u32>,
+}
+
+impl ZookeeperMemberReplacement {
+    pub fn not_ready_seconds(&self) -> u32 {
+        self.not_ready_seconds.unwrap_or(DEFAULT_NOT_READY_SECONDS)
+    }
+
+    /// Returns the problems of the member replacement configuration.
+    pub fn validate(&self) -> Vec<String> {
+        if self.not_ready_seconds() < MIN_NOT_READY_SECONDS {
+            vec![format!(
+                "memberReplacement.notReadySeconds: Must be at least {} but is [{}]",
+                MIN_NOT_READY_SECONDS,
+                self.not_ready_seconds()
+            )]
+        } else {
+            vec![]
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum MemberReplacementPolicy {
+    /// Failed servers are only reported with an event.
+    Never,
+    /// The pod of a failed server is deleted and recreated with the same `myid`, on the same node
+    /// if it is still eligible. The data directory is kept.
+    ReplacePod,
+    /// Like `ReplacePod`, but the replacement starts with an empty data directory and fetches the
+    /// data from the leader.
+    ReplacePodAndData,
+}
+
+string_enum_schema!(MemberReplacementPolicy {
+    Never,
+    ReplacePod,
+    ReplacePodAndData
+});
+
+impl Default for MemberReplacementPolicy {
+    fn default() -> Self {
+        MemberReplacementPolicy::Never
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberReplacementStatus {
+    /// The pod of the failed server.
+    pub pod: String,
+    /// The `myid` of the failed server, its replacement gets the same one.
+    pub id: usize,
+    /// The node the failed server ran on.
+    pub node: String,
+    /// Whether the replacement starts with an empty data directory.
+    #[serde(default)]
+    pub wipe_data: bool,
+    /// RFC 3339 timestamp of when the failed pod was deleted.
+    pub started_at: String,
+    /// RFC 3339 timestamp after which the replacement is given up if it is not ready,
+    /// `notReadySeconds` after it started.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_deserialize() {
+        let member_replacement: ZookeeperMemberReplacement = serde_yaml::from_str(indoc! {"
+            policy: ReplacePodAndData
+            notReadySeconds: 30
+        "})
+        .unwrap();
+
+        assert_eq!(
+            member_replacement.policy,
+            MemberReplacementPolicy::ReplacePodAndData
+        );
+        assert_eq!(
+            member_replacement.validate(),
+            vec!["memberReplacement.notReadySeconds: Must be at least 60 but is [30]"]
+        );
+        assert!(ZookeeperMemberReplacement::default().validate().is_empty());
+    }
+}
@@ -0,0 +1,252 @@
+//! Prometheus metrics of the servers.
+//!
+//! ZooKeeper 3.6 and later ship a Prometheus metrics provider which serves the metrics from the
+//! server itself. Older versions only expose their metrics via JMX, so the operator adds a
+//! sidecar running the JMX exporter which reads them from a JMX port bound to localhost.
+use crate::ZookeeperVersion;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const METRICS_PROVIDER_CLASS_NAME: &str = "metricsProvider.className";
+pub const METRICS_PROVIDER_HTTP_PORT: &str = "metricsProvider.httpPort";
+pub const METRICS_PROVIDER_EXPORT_JVM_INFO: &str = "metricsProvider.exportJvmInfo";
+const PROMETHEUS_METRICS_PROVIDER: &str =
+    "org.apache.zookeeper.metrics.prometheus.PrometheusMetricsProvider";
+
+pub const DEFAULT_METRICS_PORT: u16 = 9505;
+pub const DEFAULT_JMX_EXPORTER_IMAGE: &str = "bitnami/jmx-exporter:0.16.1";
+/// The JMX port of the servers the sidecar reads the metrics from, only bound to localhost.
+pub const JMX_PORT: u16 = 9010;
+pub const DEFAULT_OUTSTANDING_REQUESTS_THRESHOLD: u64 = 1000;
+pub const DEFAULT_LEADER_CHANGES_THRESHOLD: u32 = 2;
+pub const DEFAULT_DISK_USAGE_THRESHOLD_PERCENT: u8 = 85;
+pub const DEFAULT_DATA_MOUNTPOINT: &str = "/";
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperMonitoring {
+    /// The port the metrics are served on, defaults to 9505.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Labels which are added to all metrics, e.g. `environment: production`. Only supported
+    /// by the JMX exporter (ZooKeeper before 3.6).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// The image of the JMX exporter sidecar for ZooKeeper before 3.6. It has to run
+    /// `jmx_prometheus_httpserver` with the port and the config file as arguments. Defaults to
+    /// `bitnami/jmx-exporter:0.16.1`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jmx_exporter_image: Option<String>,
+    /// Creates a PrometheusRule with alerts for the cluster if the Prometheus Operator is
+    /// installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alerts: Option<ZookeeperAlerts>,
+}
+
+/// The thresholds of the alerts of a cluster.
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperAlerts {
+    /// The number of requests queued on all servers above which `ZookeeperOutstandingRequestsHigh`
+    /// fires, defaults to 1000.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outstanding_requests_threshold: Option<u64>,
+    /// The number of leader changes within 15 minutes above which `ZookeeperLeaderFlapping`
+    /// fires, defaults to 2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leader_changes_threshold: Option<u32>,
+    /// The used space of the file system of the data directory in percent above which
+    /// `ZookeeperDiskNearlyFull` fires, defaults to 85.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_usage_threshold_percent: Option<u8>,
+    /// The mount point of the file system the data directory is on, as reported by the node
+    /// exporter. Defaults to `/`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_mountpoint: Option<String>,
+    /// Labels which are added to all alerts, e.g. to route them to a team.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl ZookeeperAlerts {
+    pub fn outstanding_requests_threshold(&self) -> u64 {
+        self.outstanding_requests_threshold
+            .unwrap_or(DEFAULT_OUTSTANDING_REQUESTS_THRESHOLD)
+    }
+
+    pub fn leader_changes_threshold(&self) -> u32 {
+        self.leader_changes_threshold
+            .unwrap_or(DEFAULT_LEADER_CHANGES_THRESHOLD)
+    }
+
+    pub fn disk_usage_threshold_percent(&self) -> u8 {
+        self.disk_usage_threshold_percent
+            .unwrap_or(DEFAULT_DISK_USAGE_THRESHOLD_PERCENT)
+    }
+
+    pub fn data_mountpoint(&self) -> &str {
+        self.data_mountpoint
+            .as_deref()
+            .unwrap_or(DEFAULT_DATA_MOUNTPOINT)
+    }
+}
+
+impl ZookeeperMonitoring {
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(DEFAULT_METRICS_PORT)
+    }
+
+    pub fn jmx_exporter_image(&self) -> &str {
+        self.jmx_exporter_image
+            .as_deref()
+            .unwrap_or(DEFAULT_JMX_EXPORTER_IMAGE)
+    }
+
+    /// Returns the problems of the monitoring configuration for the given version.
+    pub fn validate(&self, version: &ZookeeperVersion) -> Vec<String> {
+        let mut problems = vec![];
+        if self.port() == 0 {
+            problems.push("monitoring.port: Must be greater than 0".to_string());
+        }
+        if version.has_prometheus_metrics_provider() {
+            if !self.labels.is_empty() {
+                problems.push(format!(
+                    "monitoring.labels: Not supported by the metrics provider of ZooKeeper {}",
+                    version
+                ));
+            }
+        } else if self.port() == JMX_PORT {
+            problems.push(format!(
+                "monitoring.port: Must not be the JMX port [{}]",
+                JMX_PORT
+            ));
+        }
+        for name in self.labels.keys() {
+            if !is_valid_label_name(name) {
+                problems.push(format!(
+                    "monitoring.labels: [{}] is not a valid Prometheus label name",
+                    name
+                ));
+            }
+        }
+        if let Some(alerts) = &self.alerts {
+            let disk_usage = alerts.disk_usage_threshold_percent();
+            if disk_usage == 0 || disk_usage > 100 {
+                problems.push(
+                    "monitoring.alerts.diskUsageThresholdPercent: Must be between 1 and 100"
+                        .to_string(),
+                );
+            }
+            if alerts.data_mountpoint().contains('"') {
+                problems
+                    .push("monitoring.alerts.dataMountpoint: Must not contain quotes".to_string());
+            }
+            for name in alerts.labels.keys() {
+                if !is_valid_label_name(name) {
+                    problems.push(format!(
+                        "monitoring.alerts.labels: [{}] is not a valid Prometheus label name",
+                        name
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Returns the `zoo.cfg` properties enabling the metrics provider, which are empty for
+    /// versions without it.
+    pub fn zoo_cfg_properties(&self, version: &ZookeeperVersion) -> BTreeMap<String, String> {
+        let mut properties = BTreeMap::new();
+        if version.has_prometheus_metrics_provider() {
+            properties.insert(
+                METRICS_PROVIDER_CLASS_NAME.to_string(),
+                PROMETHEUS_METRICS_PROVIDER.to_string(),
+            );
+            properties.insert(
+                METRICS_PROVIDER_HTTP_PORT.to_string(),
+                self.port().to_string(),
+            );
+            properties.insert(
+                METRICS_PROVIDER_EXPORT_JVM_INFO.to_string(),
+                "true".to_string(),
+            );
+        }
+        properties
+    }
+}
+
+/// Checks the name against `[a-zA-Z_][a-zA-Z0-9_]*`, names starting with `__` are reserved by
+/// Prometheus.
+fn is_valid_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => {}
+        _ => return false,
+    }
+    !name.starts_with("__") && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("environment", true)]
+    #[case("_rack", true)]
+    #[case("rack_2", true)]
+    #[case("__name__", false)]
+    #[case("2nd", false)]
+    #[case("data-center", false)]
+    #[case("", false)]
+    fn test_is_valid_label_name(#[case] name: &str, #[case] expected: bool) {
+        assert_eq!(is_valid_label_name(name), expected);
+    }
+
+    #[test]
+    fn test_validate() {
+        let monitoring: ZookeeperMonitoring = serde_yaml::from_str(indoc! {"
+            port: 9010
+            labels:
+              environment: production
+              data-center: west
+        "})
+        .unwrap();
+
+        assert_eq!(
+            monitoring.validate(&"3.5.8".parse().unwrap()),
+            vec![
+                "monitoring.port: Must not be the JMX port [9010]",
+                "monitoring.labels: [data-center] is not a valid Prometheus label name",
+            ]
+        );
+        assert!(monitoring
+            .zoo_cfg_properties(&"3.5.8".parse().unwrap())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_alerts() {
+        let monitoring: ZookeeperMonitoring = serde_yaml::from_str(indoc! {"
+            alerts:
+              diskUsageThresholdPercent: 120
+              labels:
+                team-name: platform
+        "})
+        .unwrap();
+
+        assert_eq!(
+            monitoring.validate(&"3.6.3".parse().unwrap()),
+            vec![
+                "monitoring.alerts.diskUsageThresholdPercent: Must be between 1 and 100",
+                "monitoring.alerts.labels: [team-name] is not a valid Prometheus label name",
+            ]
+        );
+        let alerts = monitoring.alerts.unwrap();
+        assert_eq!(alerts.outstanding_requests_threshold(), 1000);
+        assert_eq!(alerts.data_mountpoint(), "/");
+    }
+}
@@ -0,0 +1,126 @@
+//! Isolation of the servers of a cluster with a NetworkPolicy.
+//!
+//! If enabled, the servers only accept quorum and leader election traffic from the other servers
+//! of the same cluster and client traffic from the operator and the clients listed in the spec.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The label Kubernetes (1.21+) sets on every namespace to its name.
+pub const NAMESPACE_NAME_LABEL: &str = "kubernetes.io/metadata.name";
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperNetworkPolicy {
+    /// The clients which may connect to the client, admin and metrics ports of the servers in
+    /// addition to the operator.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_clients: Vec<ZookeeperClientPeer>,
+}
+
+/// Pods which may connect to the servers. Without `namespace` and `namespaceLabels` the pods
+/// are selected in the namespace of the cluster.
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperClientPeer {
+    /// The name of the namespace the pods run in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// The labels of the namespaces the pods run in, an empty map selects all namespaces if
+    /// `podLabels` are set.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub namespace_labels: BTreeMap<String, String>,
+    /// The labels of the pods, all pods of the selected namespaces are allowed if empty.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pod_labels: BTreeMap<String, String>,
+}
+
+impl ZookeeperClientPeer {
+    /// Returns the labels selecting the namespaces of the pods or `None` if the pods are
+    /// selected in the namespace of the cluster.
+    pub fn namespace_selector_labels(&self) -> Option<BTreeMap<String, String>> {
+        match &self.namespace {
+            Some(namespace) => {
+                let mut labels = BTreeMap::new();
+                labels.insert(NAMESPACE_NAME_LABEL.to_string(), namespace.clone());
+                Some(labels)
+            }
+            None if !self.namespace_labels.is_empty() => Some(self.namespace_labels.clone()),
+            None => None,
+        }
+    }
+}
+
+impl ZookeeperNetworkPolicy {
+    /// Returns the problems of the NetworkPolicy configuration.
+    pub fn validate(&self) -> Vec<String> {
+        self.allowed_clients
+            .iter()
+            .enumerate()
+            .filter(|(_, peer)| peer.namespace.is_some() && !peer.namespace_labels.is_empty())
+            .map(|(index, _)| {
+                format!(
+                    "networkPolicy.allowedClients[{}]: namespace and namespaceLabels can not be combined",
+                    index
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_namespace_selector_labels() {
+        let policy: ZookeeperNetworkPolicy = serde_yaml::from_str(indoc! {"
+            allowedClients:
+              - namespace: kafka
+              - namespaceLabels:
+                  team: payments
+                podLabels:
+                  app: checkout
+              - podLabels:
+                  app: nifi
+        "})
+        .unwrap();
+        let selectors = policy
+            .allowed_clients
+            .iter()
+            .map(ZookeeperClientPeer::namespace_selector_labels)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            selectors[0]
+                .as_ref()
+                .and_then(|labels| labels.get(NAMESPACE_NAME_LABEL)),
+            Some(&"kafka".to_string())
+        );
+        assert_eq!(
+            selectors[1].as_ref().and_then(|labels| labels.get("team")),
+            Some(&"payments".to_string())
+        );
+        assert_eq!(selectors[2], None);
+        assert!(policy.validate().is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        let policy: ZookeeperNetworkPolicy = serde_yaml::from_str(indoc! {"
+            allowedClients:
+              - namespace: kafka
+                namespaceLabels:
+                  team: payments
+        "})
+        .unwrap();
+
+        assert_eq!(
+            policy.validate(),
+            vec![
+                "networkPolicy.allowedClients[0]: namespace and namespaceLabels can not be combined"
+            ]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Placement of the server pods: tolerations, node selector, affinity and the anti-affinity
+//! between the servers of a cluster.
+//!
+//! Unset values keep the defaults of the operator: the tolerations of the Stackable agent (unless
+//! the architectures or operating systems of the nodes or the images are declared) and a required
+//! anti-affinity on `kubernetes.io/hostname`, i.e. at most one server per node.
+use k8s_openapi::api::core::v1::{Affinity, Toleration, TopologySpreadConstraint};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{ArrayValidation, InstanceType, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The topology key of the default anti-affinity.
+pub const DEFAULT_TOPOLOGY_KEY: &str = "kubernetes.io/hostname";
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperPlacement {
+    /// Replaces the tolerations of the Stackable agent which are set by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "objects_schema")]
+    pub tolerations: Option<Vec<Toleration>>,
+    /// Whether the tolerations of the Stackable agent (`kubernetes.io/arch=stackable-linux`) are
+    /// added to the pods if `tolerations` is not set. Defaults to `true` unless architectures (in
+    /// `architectures` or for the images of the operator) or `operatingSystems` are declared.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stackable_agent_tolerations: Option<bool>,
+    /// The CPU architectures the image supports, i.e. the values of the `kubernetes.io/arch`
+    /// label of the nodes the servers can run on (e.g. `amd64` or `arm64`). Defaults to the
+    /// architectures declared for the images of the operator, all if none are declared.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub architectures: Vec<String>,
+    /// The operating systems the image supports, i.e. the values of the `kubernetes.io/os` label
+    /// of the nodes the servers can run on (e.g. `linux`), all if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub operating_systems: Vec<String>,
+    /// The labels a node must have to run a server.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub node_selector: BTreeMap<String, String>,
+    /// The affinity of the server pods, see the Kubernetes `Affinity` documentation. Required
+    /// node affinity terms also restrict the nodes the operator places the servers on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "object_schema")]
+    pub affinity: Option<Affinity>,
+    /// The anti-affinity between the servers of the cluster.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anti_affinity: Option<ZookeeperAntiAffinity>,
+    /// Topology spread constraints of the server pods, see the Kubernetes
+    /// `TopologySpreadConstraint` documentation. Constraints without `labelSelector` select the
+    /// servers of the cluster.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(schema_with = "objects_schema")]
+    pub topology_spread_constraints: Vec<TopologySpreadConstraint>,
+    /// The node label holding the rack (or zone) of a node, e.g. `topology.kubernetes.io/zone`.
+    /// If set, the value of the node is passed to the server in the `ZOOKEEPER_RACK` environment
+    /// variable and the pod is labelled with it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rack_label: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperAntiAffinity {
+    /// Whether two servers must not (`required`, the default) or should not (`preferred`) run
+    /// in the same topology domain.
+    #[serde(default)]
+    pub mode: AntiAffinityMode,
+    /// The node label defining the topology domains, defaults to `kubernetes.io/hostname`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topology_key: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AntiAffinityMode {
+    Required,
+    Preferred,
+}
+
+impl Default for AntiAffinityMode {
+    fn default() -> Self {
+        AntiAffinityMode::Required
+    }
+}
+
+impl ZookeeperPlacement {
+    /// Whether the tolerations of the Stackable agent are added to the pods, see
+    /// `stackable_agent_tolerations`. `image_architectures` are the architectures declared for
+    /// the images of the operator, the nodes of the agent match none of them.
+    pub fn stackable_agent_tolerations(&self, image_architectures: &[String]) -> bool {
+        self.stackable_agent_tolerations.unwrap_or_else(|| {
+            self.architectures.is_empty()
+                && image_architectures.is_empty()
+                && self.operating_systems.is_empty()
+        })
+    }
+}
+
+impl ZookeeperAntiAffinity {
+    pub fn topology_key(&self) -> &str {
+        self.topology_key.as_deref().unwrap_or(DEFAULT_TOPOLOGY_KEY)
+    }
+}
+
+/// Kubernetes objects are not validated by the CRD but when the pods are created.
+pub(crate) fn object_schema(_: &mut SchemaGenerator) -> Schema {
+    let mut schema = SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        ..SchemaObject::default()
+    };
+    schema.extensions.insert(
+        "x-kubernetes-preserve-unknown-fields".to_string(),
+        serde_json::Value::Bool(true),
+    );
+    Schema::Object(schema)
+}
+
+pub(crate) fn objects_schema(gen: &mut SchemaGenerator) -> Schema {
+    Schema::Object(SchemaObject {
+        instance_type: Some(InstanceType::Array.into()),
+        array: Some(Box::new(ArrayValidation {
+            items: Some(object_schema(gen).into()),
+            ..ArrayValidation::default()
+        })),
+        ..SchemaObject::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use rstest::rstest;
+
+    #[test]
+    fn test_deserialize() {
+        let placement: ZookeeperPlacement = serde_yaml::from_str(indoc! {"
+            tolerations:
+              - key: dedicated
+                operator: Equal
+                value: zookeeper
+                effect: NoSchedule
+            nodeSelector:
+              disktype: ssd
+            antiAffinity:
+              topologyKey: topology.kubernetes.io/zone
+            topologySpreadConstraints:
+              - maxSkew: 1
+                topologyKey: topology.kubernetes.io/zone
+                whenUnsatisfiable: DoNotSchedule
+            rackLabel: topology.kubernetes.io/zone
+        "})
+        .unwrap();
+
+        assert_eq!(
+            placement.tolerations.map(|tolerations| tolerations.len()),
+            Some(1)
+        );
+        assert_eq!(
+            placement.node_selector.get("disktype"),
+            Some(&"ssd".to_string())
+        );
+        assert_eq!(placement.topology_spread_constraints[0].max_skew, 1);
+        assert_eq!(
+            placement.rack_label.as_deref(),
+            Some("topology.kubernetes.io/zone")
+        );
+        let anti_affinity = placement.anti_affinity.unwrap();
+        assert_eq!(anti_affinity.mode, AntiAffinityMode::Required);
+        assert_eq!(anti_affinity.topology_key(), "topology.kubernetes.io/zone");
+        assert_eq!(
+            ZookeeperAntiAffinity::default().topology_key(),
+            DEFAULT_TOPOLOGY_KEY
+        );
+    }
+
+    #[rstest]
+    #[case::default("{}", &[], true)]
+    #[case::architectures("architectures: [amd64, arm64]", &[], false)]
+    #[case::image_architectures("{}", &["amd64"], false)]
+    #[case::operating_systems("operatingSystems: [linux]", &[], false)]
+    #[case::explicitly_enabled(
+        "{architectures: [amd64], stackableAgentTolerations: true}",
+        &["amd64"],
+        true
+    )]
+    #[case::explicitly_disabled("stackableAgentTolerations: false", &[], false)]
+    fn test_stackable_agent_tolerations(
+        #[case] placement: &str,
+        #[case] image_architectures: &[&str],
+        #[case] expected: bool,
+    ) {
+        let placement: ZookeeperPlacement = serde_yaml::from_str(placement).unwrap();
+        let image_architectures = image_architectures
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            placement.stackable_agent_tolerations(&image_architectures),
+            expected
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! Additions to the server pods the operator generates, e.g. labels required by company policies.
+//!
+//! The overrides are merged on top of the pod built by the operator, see the `pod_overrides`
+//! module of the operator for the merge order.
+use crate::placement::{object_schema, objects_schema};
+
+use k8s_openapi::api::core::v1::{EnvVar, PodSecurityContext};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperPodOverrides {
+    /// Labels added to the pods, the labels set by the operator take precedence.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// Annotations added to the pods, the annotations set by the operator take precedence.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+    /// Environment variables of the server container, see the Kubernetes `EnvVar`
+    /// documentation. They replace variables of the operator with the same name.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(schema_with = "objects_schema")]
+    pub env: Vec<EnvVar>,
+    /// The security context of the pods, see the Kubernetes `PodSecurityContext` documentation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "object_schema")]
+    pub security_context: Option<PodSecurityContext>,
+    /// The name of the PriorityClass of the pods.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority_class_name: Option<String>,
+}
+
+impl ZookeeperPodOverrides {
+    /// Returns the problems of the overrides. `reserved_env_vars` are the environment variables
+    /// the operator relies on, they can not be replaced.
+    pub fn validate(&self, reserved_env_vars: &[&str]) -> Vec<String> {
+        let mut problems = vec![];
+        for (index, env_var) in self.env.iter().enumerate() {
+            if env_var.name.is_empty() {
+                problems.push(format!(
+                    "podOverrides.env[{}]: The name must not be empty",
+                    index
+                ));
+            } else if reserved_env_vars.contains(&env_var.name.as_str()) {
+                problems.push(format!(
+                    "podOverrides.env[{}]: [{}] is managed by the operator",
+                    index, env_var.name
+                ));
+            }
+        }
+        if self.priority_class_name.as_deref() == Some("") {
+            problems.push("podOverrides.priorityClassName: The name must not be empty".to_string());
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_validate() {
+        let overrides: ZookeeperPodOverrides = serde_yaml::from_str(indoc! {"
+            labels:
+              cost-center: '4711'
+            env:
+              - name: ZOOKEEPER_MYID
+                value: '1'
+              - name: TZ
+                value: Europe/Berlin
+            securityContext:
+              runAsUser: 1000
+              fsGroup: 1000
+            priorityClassName: ''
+        "})
+        .unwrap();
+
+        assert_eq!(
+            overrides
+                .security_context
+                .and_then(|context| context.fs_group),
+            Some(1000)
+        );
+        assert_eq!(
+            overrides.validate(&["ZOOKEEPER_MYID"]),
+            vec![
+                "podOverrides.env[0]: [ZOOKEEPER_MYID] is managed by the operator",
+                "podOverrides.priorityClassName: The name must not be empty",
+            ]
+        );
+    }
+}
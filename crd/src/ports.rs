@@ -0,0 +1,104 @@
+//! The ports the servers listen on, e.g. to avoid conflicts with other services on the nodes.
+//!
+//! The client port of a role group (`clientPort` in its config) takes precedence over
+//! `ports.client`. The quorum and leader election ports are the same for all servers because every
+//! server connects to the others with the ports of its own `server.<id>` entries.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_CLIENT_PORT: u16 = 2181;
+/// The port the followers connect to the leader on.
+pub const DEFAULT_QUORUM_PORT: u16 = 2888;
+/// The port used for the leader election.
+pub const DEFAULT_LEADER_ELECTION_PORT: u16 = 3888;
+
+/// Ports below are reserved for system services on most nodes.
+const MIN_PORT: u16 = 1024;
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperPorts {
+    /// The port clients connect to, defaults to 2181.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<u16>,
+    /// The port the followers connect to the leader on, defaults to 2888.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quorum: Option<u16>,
+    /// The port used for the leader election, defaults to 3888.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leader_election: Option<u16>,
+}
+
+impl ZookeeperPorts {
+    pub fn client(&self) -> u16 {
+        self.client.unwrap_or(DEFAULT_CLIENT_PORT)
+    }
+
+    pub fn quorum(&self) -> u16 {
+        self.quorum.unwrap_or(DEFAULT_QUORUM_PORT)
+    }
+
+    pub fn leader_election(&self) -> u16 {
+        self.leader_election.unwrap_or(DEFAULT_LEADER_ELECTION_PORT)
+    }
+
+    /// Returns the problems of the ports.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+        let ports = [
+            ("client", self.client()),
+            ("quorum", self.quorum()),
+            ("leaderElection", self.leader_election()),
+        ];
+        for (index, (field, port)) in ports.iter().enumerate() {
+            if *port < MIN_PORT {
+                problems.push(format!(
+                    "ports.{}: [{}] must be at least {}",
+                    field, port, MIN_PORT
+                ));
+            }
+            if let Some((other_field, _)) = ports[..index]
+                .iter()
+                .find(|(_, other_port)| other_port == port)
+            {
+                problems.push(format!(
+                    "ports.{}: [{}] is already used by ports.{}",
+                    field, port, other_field
+                ));
+            }
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_defaults() {
+        let ports = ZookeeperPorts::default();
+        assert_eq!(ports.client(), 2181);
+        assert_eq!(ports.quorum(), 2888);
+        assert_eq!(ports.leader_election(), 3888);
+        assert!(ports.validate().is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        let ports: ZookeeperPorts = serde_yaml::from_str(indoc! {"
+            client: 2888
+            leaderElection: 80
+        "})
+        .unwrap();
+
+        assert_eq!(
+            ports.validate(),
+            vec![
+                "ports.quorum: [2888] is already used by ports.client",
+                "ports.leaderElection: [80] must be at least 1024",
+            ]
+        );
+    }
+}
@@ -0,0 +1,79 @@
+//! The health of the quorum as reported by the servers themselves.
+//!
+//! The operator asks every server with the `mntr` four letter word for its mode and load whenever
+//! it reconciles the cluster, and reconciles it at least every
+//! [`DEFAULT_PROBE_INTERVAL_SECONDS`] so the status stays current while nothing else changes.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PROBE_INTERVAL_SECONDS: u32 = 60;
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperQuorumReporting {
+    /// How often the servers are probed for `status.quorum` in addition to every reconciliation,
+    /// defaults to 60. `0` only probes them when the cluster is reconciled anyway.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probe_interval_seconds: Option<u32>,
+}
+
+impl ZookeeperQuorumReporting {
+    pub fn probe_interval_seconds(&self) -> u32 {
+        self.probe_interval_seconds
+            .unwrap_or(DEFAULT_PROBE_INTERVAL_SECONDS)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuorumStatus {
+    /// The pod of the current leader, missing while no server reports to be the leader (e.g.
+    /// during a leader election).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leader: Option<String>,
+    /// The number of followers and observers connected to the leader.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub followers: Option<u32>,
+    /// The number of followers which finished synchronizing with the leader.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synced_followers: Option<u32>,
+    /// The number of synchronizations the leader has not finished yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_syncs: Option<u32>,
+    /// The state of every server, ordered by pod name.
+    #[serde(default)]
+    pub servers: Vec<ServerQuorumStatus>,
+    /// RFC 3339 timestamp of when the servers were probed.
+    pub probed_at: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerQuorumStatus {
+    pub pod: String,
+    /// `leader`, `follower`, `observer` or `standalone` as reported by the server, `unknown` if it
+    /// did not answer or does not serve requests.
+    pub mode: String,
+    /// Whether the server is in sync with the quorum and serves requests.
+    pub synced: bool,
+    /// The number of requests the server has queued but not processed yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outstanding_requests: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnsembleMember {
+    pub pod: String,
+    /// The `myid` of the server, missing if the pod has no valid id label.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<usize>,
+    /// `leader`, `follower`, `observer` or `standalone` as reported by the server, `unknown` if it
+    /// did not answer or its pod is not ready.
+    pub role: String,
+    /// The address clients connect to, e.g. `node-1:2181`, missing until the pod is scheduled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Whether the pod is ready.
+    pub ready: bool,
+}
@@ -0,0 +1,46 @@
+//! Schemas of the CRDs which can not be derived.
+
+/// Implements `JsonSchema` for an enum with unit variants as a plain string enum, e.g.
+/// `string_enum_schema!(DeletionPolicy { Delete, Orphan })`. All variants must be listed.
+///
+/// The derived schema of an enum with documented variants is a `oneOf` with a description per
+/// variant, which Kubernetes does not accept in the structural schema of a CRD. The variants are
+/// described in the documentation of the fields instead.
+macro_rules! string_enum_schema {
+    ($name:ident { $($variant:ident),+ $(,)? }) => {
+        impl schemars::JsonSchema for $name {
+            fn schema_name() -> String {
+                stringify!($name).to_string()
+            }
+
+            fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+                // fails to compile if a variant is missing
+                let _ = |value: $name| match value {
+                    $($name::$variant => ()),+
+                };
+                schemars::schema::Schema::Object(schemars::schema::SchemaObject {
+                    instance_type: Some(schemars::schema::InstanceType::String.into()),
+                    enum_values: Some(vec![$(serde_json::json!($name::$variant)),+]),
+                    ..schemars::schema::SchemaObject::default()
+                })
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DeletionPolicy;
+    use schemars::schema_for;
+    use serde_json::json;
+
+    #[test]
+    fn test_string_enum_schema() {
+        let schema = serde_json::to_value(schema_for!(DeletionPolicy).schema).unwrap();
+
+        assert_eq!(
+            schema,
+            json!({ "type": "string", "enum": ["Delete", "Orphan"] })
+        );
+    }
+}
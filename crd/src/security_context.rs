@@ -0,0 +1,143 @@
+//! The security context the operator generates for the server pods.
+//!
+//! By default the pods run as a non-root user with a read-only root filesystem, without
+//! capabilities and with the `RuntimeDefault` seccomp profile, which passes the `restricted`
+//! PodSecurity standard. The directories ZooKeeper writes to are `emptyDir` volumes, see the
+//! `security_context` module of the operator.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The user and group the servers run as if not configured, the `stackable` user of the images.
+pub const DEFAULT_USER: i64 = 1000;
+/// The directory which is always writable, the default data directory is below it.
+pub const TMP_DIR: &str = "/tmp";
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperSecurityContext {
+    /// Whether the operator generates the security context, defaults to true. If disabled, the
+    /// pods run with the defaults of the image and `podOverrides.securityContext`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// The user the containers run as, defaults to 1000. It must not be root (0).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_as_user: Option<i64>,
+    /// The primary group of the containers, defaults to 1000.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_as_group: Option<i64>,
+    /// The group which owns the volumes of the pods, defaults to 1000.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fs_group: Option<i64>,
+    /// Whether the root filesystem of the operator's containers is read-only, defaults to true.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only_root_filesystem: Option<bool>,
+    /// Directories of the server container which stay writable with a read-only root
+    /// filesystem, besides `/tmp` and the directories of the log files, e.g. a `dataDir` outside
+    /// of `/tmp`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub writable_paths: Vec<String>,
+}
+
+impl ZookeeperSecurityContext {
+    pub fn enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+
+    pub fn run_as_user(&self) -> i64 {
+        self.run_as_user.unwrap_or(DEFAULT_USER)
+    }
+
+    pub fn run_as_group(&self) -> i64 {
+        self.run_as_group.unwrap_or(DEFAULT_USER)
+    }
+
+    pub fn fs_group(&self) -> i64 {
+        self.fs_group.unwrap_or(DEFAULT_USER)
+    }
+
+    pub fn read_only_root_filesystem(&self) -> bool {
+        self.read_only_root_filesystem.unwrap_or(true)
+    }
+
+    /// Returns the problems of the security context.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if self.run_as_user == Some(0) {
+            problems.push(
+                "securityContext.runAsUser: The servers must not run as root (0)".to_string(),
+            );
+        }
+        let ids = [
+            ("runAsUser", self.run_as_user),
+            ("runAsGroup", self.run_as_group),
+            ("fsGroup", self.fs_group),
+        ];
+        for (field, id) in ids.iter() {
+            if let Some(id) = id.filter(|id| *id < 0) {
+                problems.push(format!(
+                    "securityContext.{}: [{}] must not be negative",
+                    field, id
+                ));
+            }
+        }
+        for path in &self.writable_paths {
+            if !path.starts_with('/') {
+                problems.push(format!(
+                    "securityContext.writablePaths: [{}] must be an absolute path",
+                    path
+                ));
+            }
+        }
+        problems
+    }
+}
+
+/// Checks whether `path` is `dir` or below it.
+pub fn is_below(path: &str, dir: &str) -> bool {
+    let dir = dir.trim_end_matches('/');
+    path == dir || path.starts_with(&format!("{}/", dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_defaults() {
+        let security_context = ZookeeperSecurityContext::default();
+
+        assert!(security_context.enabled());
+        assert_eq!(security_context.run_as_user(), DEFAULT_USER);
+        assert_eq!(security_context.fs_group(), DEFAULT_USER);
+        assert!(security_context.read_only_root_filesystem());
+    }
+
+    #[test]
+    fn test_validate() {
+        let security_context: ZookeeperSecurityContext = serde_yaml::from_str(indoc! {"
+            runAsUser: 0
+            fsGroup: -1
+            writablePaths:
+              - /data
+              - data
+        "})
+        .unwrap();
+
+        assert_eq!(
+            security_context.validate(),
+            vec![
+                "securityContext.runAsUser: The servers must not run as root (0)",
+                "securityContext.fsGroup: [-1] must not be negative",
+                "securityContext.writablePaths: [data] must be an absolute path",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_below() {
+        assert!(is_below("/tmp/zookeeper", "/tmp"));
+        assert!(is_below("/tmp", "/tmp/"));
+        assert!(!is_below("/tmpdata", "/tmp"));
+    }
+}
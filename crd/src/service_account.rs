@@ -0,0 +1,139 @@
+//! The ServiceAccount the server pods run with.
+//!
+//! By default the operator creates a dedicated ServiceAccount per cluster, so the servers do not
+//! share the `default` account of the namespace with other workloads and can be given an identity
+//! of their own, e.g. with the annotations of IAM roles for service accounts or workload identity.
+//! A Role or ClusterRole prepared by an administrator can be bound to it with a RoleBinding.
+//!
+//! The operator never creates roles itself: everyone who may edit a ZookeeperCluster could
+//! otherwise grant the pods any permission the operator holds.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperServiceAccount {
+    /// Whether the operator creates the ServiceAccount, defaults to true. If false, `name` must
+    /// refer to an existing ServiceAccount.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub create: Option<bool>,
+    /// The name of the ServiceAccount, defaults to `<cluster>-server`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Annotations of the created ServiceAccount, e.g. `eks.amazonaws.com/role-arn` or
+    /// `iam.gke.io/gcp-service-account`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+    /// An existing Role or ClusterRole which is bound to the ServiceAccount in the namespace of
+    /// the cluster with a RoleBinding.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role_ref: Option<ZookeeperRoleRef>,
+    /// Whether the token of the ServiceAccount is mounted into the pods. Defaults to true if a
+    /// role is bound and to false otherwise, the servers do not talk to the API server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub automount_token: Option<bool>,
+}
+
+/// A Role or ClusterRole created by an administrator.
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperRoleRef {
+    /// `Role` or `ClusterRole`.
+    pub kind: RoleKind,
+    pub name: String,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum RoleKind {
+    /// A Role in the namespace of the cluster.
+    Role,
+    /// A ClusterRole, whose rules are only granted in the namespace of the cluster.
+    ClusterRole,
+}
+
+string_enum_schema!(RoleKind { Role, ClusterRole });
+
+impl ZookeeperServiceAccount {
+    pub fn create(&self) -> bool {
+        self.create.unwrap_or(true)
+    }
+
+    /// Returns the name of the ServiceAccount of the cluster `cluster_name`.
+    pub fn name(&self, cluster_name: &str) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("{}-server", cluster_name))
+    }
+
+    pub fn automount_token(&self) -> bool {
+        self.automount_token.unwrap_or(self.role_ref.is_some())
+    }
+
+    /// Returns the problems of the ServiceAccount settings.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+        match self.name.as_deref() {
+            Some("") => {
+                problems.push("serviceAccount.name: The name must not be empty".to_string())
+            }
+            None if !self.create() => problems.push(
+                "serviceAccount.name: The name of an existing ServiceAccount is required if create is false"
+                    .to_string(),
+            ),
+            _ => {}
+        }
+        if !self.create() && !self.annotations.is_empty() {
+            problems.push(
+                "serviceAccount.annotations: Annotations can only be set on a created ServiceAccount"
+                    .to_string(),
+            );
+        }
+        if let Some(role_ref) = &self.role_ref {
+            if role_ref.name.is_empty() {
+                problems
+                    .push("serviceAccount.roleRef.name: The name must not be empty".to_string());
+            }
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_defaults() {
+        let service_account = ZookeeperServiceAccount::default();
+
+        assert!(service_account.create());
+        assert_eq!(service_account.name("simple"), "simple-server");
+        assert!(!service_account.automount_token());
+        assert!(service_account.validate().is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        let service_account: ZookeeperServiceAccount = serde_yaml::from_str(indoc! {"
+            create: false
+            annotations:
+              eks.amazonaws.com/role-arn: arn:aws:iam::123456789012:role/zookeeper
+            roleRef:
+              kind: ClusterRole
+              name: ''
+        "})
+        .unwrap();
+
+        assert!(service_account.automount_token());
+        assert_eq!(
+            service_account.validate(),
+            vec![
+                "serviceAccount.name: The name of an existing ServiceAccount is required if create is false",
+                "serviceAccount.annotations: Annotations can only be set on a created ServiceAccount",
+                "serviceAccount.roleRef.name: The name must not be empty",
+            ]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! Warm standby ensembles.
+//!
+//! A cluster with `spec.standbyOf` is a passive copy of another (primary) cluster: the operator
+//! periodically copies all persistent znodes of the primary into it. Removing `standbyOf`
+//! promotes the standby to a regular cluster, its data is no longer overwritten.
+use crate::znode::ZookeeperClusterRef;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The default interval between two copies of the primary's data.
+pub const DEFAULT_SYNC_INTERVAL_SECONDS: u64 = 300;
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperStandby {
+    /// The primary cluster, the namespace defaults to the one of the standby.
+    pub cluster_ref: ZookeeperClusterRef,
+    /// How often the data of the primary is copied, defaults to 300 seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync_interval_seconds: Option<u64>,
+}
+
+impl ZookeeperStandby {
+    pub fn sync_interval_seconds(&self) -> u64 {
+        self.sync_interval_seconds
+            .unwrap_or(DEFAULT_SYNC_INTERVAL_SECONDS)
+    }
+
+    /// Returns the problems of the standby configuration of the cluster `name` in `namespace`.
+    pub fn validate(&self, namespace: &str, name: &str) -> Vec<String> {
+        let mut problems = vec![];
+        let primary_namespace = self.cluster_ref.namespace.as_deref().unwrap_or(namespace);
+        if primary_namespace == namespace && self.cluster_ref.name == name {
+            problems.push("standbyOf.clusterRef: A cluster can not be its own standby".to_string());
+        }
+        if self.sync_interval_seconds == Some(0) {
+            problems.push("standbyOf.syncIntervalSeconds: Must be greater than 0".to_string());
+        }
+        problems
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StandbyStatus {
+    /// The primary cluster as `<namespace>/<name>`.
+    pub primary: String,
+    /// When the last copy was started (RFC 3339), successful or not.
+    pub last_attempt_time: String,
+    /// When the last successful copy was started (RFC 3339), the data of the standby is at least
+    /// as recent as the data of the primary at this time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_sync_time: Option<String>,
+    /// The number of znodes copied by the last successful copy.
+    #[serde(default)]
+    pub synced_znodes: u64,
+    /// How far the data of the standby lagged behind the primary at the last attempt, in seconds.
+    /// It is 0 after a successful copy and grows while copies fail.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lag_seconds: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_validate() {
+        let standby: ZookeeperStandby = serde_yaml::from_str(indoc! {"
+            clusterRef:
+              name: primary
+        "})
+        .unwrap();
+
+        assert_eq!(
+            standby.sync_interval_seconds(),
+            DEFAULT_SYNC_INTERVAL_SECONDS
+        );
+        assert!(standby.validate("default", "standby").is_empty());
+        assert!(standby.validate("other", "primary").is_empty());
+        assert_eq!(
+            standby.validate("default", "primary"),
+            vec!["standbyOf.clusterRef: A cluster can not be its own standby"]
+        );
+    }
+}
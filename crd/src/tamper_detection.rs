@@ -0,0 +1,17 @@
+//! Detection of modifications of the objects owned by a cluster by unknown field managers.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperTamperDetection {
+    /// Further field managers which may modify the objects owned by the cluster, in addition to
+    /// the operator and the Kubernetes components (`kubelet`, `kube-controller-manager` and
+    /// `kube-scheduler`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_managers: Vec<String>,
+    /// Pause restarts, upgrades and scale-downs while a modification by an unknown field manager
+    /// is not acknowledged.
+    #[serde(default)]
+    pub pause_disruptive_operations: bool,
+}
@@ -0,0 +1,232 @@
+//! TLS for the client port and the quorum communication.
+//!
+//! The keystore and truststore are taken from a Secret which is mounted into the server pods.
+//! Their locations are rendered into `zoo.cfg` (ZooKeeper turns unknown `zoo.cfg` properties
+//! into `zookeeper.` system properties), the passwords are passed as system properties because
+//! `zoo.cfg` ends up in a ConfigMap.
+//!
+//! Instead of providing the Secret, the certificate can be issued by a cert-manager issuer. The
+//! operator then creates the Certificate and the Secret holding the store password.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The directory the TLS Secret is mounted to.
+pub const TLS_DIR: &str = "/stackable/tls";
+/// The key of the PKCS #12 keystore containing the certificate and private key of the servers.
+pub const KEYSTORE_KEY: &str = "keystore.p12";
+/// The key of the PKCS #12 truststore containing the trusted CA certificates.
+pub const TRUSTSTORE_KEY: &str = "truststore.p12";
+/// The key of the password of both stores.
+pub const PASSWORD_KEY: &str = "password";
+
+pub const SECURE_CLIENT_PORT: &str = "secureClientPort";
+pub const DEFAULT_SECURE_CLIENT_PORT: u16 = 2281;
+
+const NETTY_SERVER_CNXN_FACTORY: &str = "org.apache.zookeeper.server.NettyServerCnxnFactory";
+
+#[derive(Clone, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperTls {
+    /// The Secret containing `keystore.p12`, `truststore.p12` and the `password` of both stores.
+    pub secret_name: String,
+    /// Serve clients via TLS on the `secureClientPort`. The plaintext client port stays open
+    /// because the operator uses it to query the state of the servers.
+    #[serde(default = "default_true")]
+    pub client: bool,
+    /// The port for TLS client connections, defaults to 2281.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secure_client_port: Option<u16>,
+    /// Whether clients have to authenticate with a certificate: `none`, `want` or `need`
+    /// (the default of ZooKeeper).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_auth: Option<String>,
+    /// Encrypt and authenticate the communication between the servers.
+    #[serde(default = "default_true")]
+    pub quorum: bool,
+    /// Let cert-manager issue the certificate into the Secret `secretName` instead of providing
+    /// the Secret.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issuer_ref: Option<CertIssuerRef>,
+}
+
+/// A cert-manager issuer.
+#[derive(Clone, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertIssuerRef {
+    pub name: String,
+    /// `Issuer` (the default) or `ClusterIssuer`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl ZookeeperTls {
+    /// Returns the name of the Secret containing the store password. cert-manager reads the
+    /// password from a Secret of its own, which is created by the operator.
+    pub fn password_secret_name(&self) -> String {
+        match self.issuer_ref {
+            Some(_) => format!("{}-password", self.secret_name),
+            None => self.secret_name.clone(),
+        }
+    }
+
+    /// Returns the `zoo.cfg` properties enabling TLS.
+    pub fn zoo_cfg_properties(&self) -> BTreeMap<String, String> {
+        let mut properties = BTreeMap::new();
+        if self.client {
+            properties.insert(
+                SECURE_CLIENT_PORT.to_string(),
+                self.secure_client_port
+                    .unwrap_or(DEFAULT_SECURE_CLIENT_PORT)
+                    .to_string(),
+            );
+            // only the Netty connection factory supports TLS
+            properties.insert(
+                "serverCnxnFactory".to_string(),
+                NETTY_SERVER_CNXN_FACTORY.to_string(),
+            );
+            insert_store_locations(&mut properties, "ssl");
+            if let Some(client_auth) = &self.client_auth {
+                properties.insert("ssl.clientAuth".to_string(), client_auth.clone());
+            }
+        }
+        if self.quorum {
+            properties.insert("sslQuorum".to_string(), "true".to_string());
+            insert_store_locations(&mut properties, "ssl.quorum");
+        }
+        properties
+    }
+
+    /// Returns the names of the system properties which must be set to the store password.
+    pub fn password_properties(&self) -> Vec<String> {
+        let mut prefixes = vec![];
+        if self.client {
+            prefixes.push("zookeeper.ssl");
+        }
+        if self.quorum {
+            prefixes.push("zookeeper.ssl.quorum");
+        }
+        prefixes
+            .into_iter()
+            .flat_map(|prefix| {
+                vec![
+                    format!("{}.keyStore.password", prefix),
+                    format!("{}.trustStore.password", prefix),
+                ]
+            })
+            .collect()
+    }
+
+    /// Checks the settings and returns a description of every problem found.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if !self.client && !self.quorum {
+            problems.push("tls: Neither client nor quorum TLS is enabled".to_string());
+        }
+        if let Some(client_auth) = &self.client_auth {
+            if !["none", "want", "need"].contains(&client_auth.as_str()) {
+                problems.push(format!(
+                    "tls.clientAuth: [{}] is not one of none, want or need",
+                    client_auth
+                ));
+            }
+        }
+        if let Some(kind) = self
+            .issuer_ref
+            .as_ref()
+            .and_then(|issuer| issuer.kind.as_ref())
+        {
+            if kind != "Issuer" && kind != "ClusterIssuer" {
+                problems.push(format!(
+                    "tls.issuerRef.kind: [{}] is not one of Issuer or ClusterIssuer",
+                    kind
+                ));
+            }
+        }
+        problems
+    }
+}
+
+fn insert_store_locations(properties: &mut BTreeMap<String, String>, prefix: &str) {
+    properties.insert(
+        format!("{}.keyStore.location", prefix),
+        format!("{}/{}", TLS_DIR, KEYSTORE_KEY),
+    );
+    properties.insert(format!("{}.keyStore.type", prefix), "PKCS12".to_string());
+    properties.insert(
+        format!("{}.trustStore.location", prefix),
+        format!("{}/{}", TLS_DIR, TRUSTSTORE_KEY),
+    );
+    properties.insert(format!("{}.trustStore.type", prefix), "PKCS12".to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_zoo_cfg_properties() {
+        let tls: ZookeeperTls = serde_yaml::from_str(indoc! {"
+            secretName: zookeeper-tls
+            clientAuth: want
+        "})
+        .unwrap();
+
+        let properties = tls.zoo_cfg_properties();
+        assert_eq!(
+            properties.get("secureClientPort"),
+            Some(&"2281".to_string())
+        );
+        assert_eq!(properties.get("ssl.clientAuth"), Some(&"want".to_string()));
+        assert_eq!(
+            properties.get("ssl.quorum.keyStore.location"),
+            Some(&"/stackable/tls/keystore.p12".to_string())
+        );
+        assert_eq!(properties.get("sslQuorum"), Some(&"true".to_string()));
+        assert_eq!(tls.password_properties().len(), 4);
+        assert!(tls.validate().is_empty());
+    }
+
+    #[test]
+    fn test_quorum_only() {
+        let tls: ZookeeperTls = serde_yaml::from_str(indoc! {"
+            secretName: zookeeper-tls
+            client: false
+        "})
+        .unwrap();
+
+        let properties = tls.zoo_cfg_properties();
+        assert!(!properties.contains_key("secureClientPort"));
+        assert!(!properties.contains_key("ssl.keyStore.location"));
+        assert_eq!(
+            tls.password_properties(),
+            vec![
+                "zookeeper.ssl.quorum.keyStore.password",
+                "zookeeper.ssl.quorum.trustStore.password"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let tls = ZookeeperTls {
+            secret_name: "zookeeper-tls".to_string(),
+            client: false,
+            secure_client_port: None,
+            client_auth: Some("required".to_string()),
+            quorum: false,
+            issuer_ref: Some(CertIssuerRef {
+                name: "ca".to_string(),
+                kind: Some("CertificateAuthority".to_string()),
+            }),
+        };
+
+        assert_eq!(tls.validate().len(), 3);
+        assert_eq!(tls.password_secret_name(), "zookeeper-tls-password");
+    }
+}
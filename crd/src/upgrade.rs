@@ -0,0 +1,212 @@
+//! How the servers are upgraded to a new version.
+//!
+//! Servers are upgraded one at a time. With a canary, a single server is upgraded first and has
+//! to serve requests with a healthy quorum for [`DEFAULT_CANARY_SOAK_SECONDS`] before the others
+//! follow. If the canary fails the upgrade halts until `spec.version` is set back to the current
+//! version, which rolls the canary back.
+//!
+//! With an integrity check, the latest snapshot in the data directory of the leader is verified
+//! in its server container before any server is upgraded. If the snapshot is corrupt the upgrade halts until
+//! `spec.version` is set back to the current version, which cancels it.
+use crate::ZookeeperVersion;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_CANARY_SOAK_SECONDS: u32 = 300;
+pub const DEFAULT_CANARY_START_TIMEOUT_SECONDS: u32 = 600;
+/// Servers need some time to start and to synchronize with the leader, shorter timeouts would
+/// fail healthy canaries.
+pub const MIN_CANARY_START_TIMEOUT_SECONDS: u32 = 60;
+pub const DEFAULT_INTEGRITY_CHECK_TIMEOUT_SECONDS: u32 = 600;
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperUpgrade {
+    /// Upgrades a single server first and only proceeds with the others once it proved to work.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canary: Option<ZookeeperCanary>,
+    /// Verifies the latest snapshot of the leader in its server container before any server is
+    /// upgraded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity_check: Option<ZookeeperIntegrityCheck>,
+}
+
+impl ZookeeperUpgrade {
+    /// Returns the problems of the upgrade configuration.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = self
+            .canary
+            .as_ref()
+            .map(ZookeeperCanary::validate)
+            .unwrap_or_default();
+        if let Some(integrity_check) = &self.integrity_check {
+            if integrity_check.timeout_seconds() == 0 {
+                problems.push(
+                    "upgrade.integrityCheck.timeoutSeconds: Must be greater than 0".to_string(),
+                );
+            }
+        }
+        problems
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperIntegrityCheck {
+    /// How long the check may take before it counts as failed. Defaults to 600.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u32>,
+}
+
+impl ZookeeperIntegrityCheck {
+    pub fn timeout_seconds(&self) -> u32 {
+        self.timeout_seconds
+            .unwrap_or(DEFAULT_INTEGRITY_CHECK_TIMEOUT_SECONDS)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperCanary {
+    /// How long the canary has to serve requests while the quorum is healthy before the other
+    /// servers are upgraded. Defaults to 300.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub soak_seconds: Option<u32>,
+    /// How long the canary may take to start serving requests after its pod was deleted, at
+    /// least 60. Defaults to 600.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_timeout_seconds: Option<u32>,
+}
+
+impl ZookeeperCanary {
+    pub fn soak_seconds(&self) -> u32 {
+        self.soak_seconds.unwrap_or(DEFAULT_CANARY_SOAK_SECONDS)
+    }
+
+    pub fn start_timeout_seconds(&self) -> u32 {
+        self.start_timeout_seconds
+            .unwrap_or(DEFAULT_CANARY_START_TIMEOUT_SECONDS)
+    }
+
+    /// Returns the problems of the canary configuration.
+    pub fn validate(&self) -> Vec<String> {
+        if self.start_timeout_seconds() < MIN_CANARY_START_TIMEOUT_SECONDS {
+            vec![format!(
+                "upgrade.canary.startTimeoutSeconds: Must be at least {} but is [{}]",
+                MIN_CANARY_START_TIMEOUT_SECONDS,
+                self.start_timeout_seconds()
+            )]
+        } else {
+            vec![]
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum CanaryPhase {
+    /// The pod of the canary was deleted and the operator waits for the upgraded server to serve
+    /// requests.
+    Starting,
+    /// The canary serves requests and is watched for the soak time.
+    Soaking,
+    /// The canary worked for the soak time, the other servers are upgraded.
+    Passed,
+    /// The canary did not start or stopped working, the upgrade is halted.
+    Failed,
+}
+
+string_enum_schema!(CanaryPhase {
+    Starting,
+    Soaking,
+    Passed,
+    Failed
+});
+
+#[derive(Clone, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CanaryStatus {
+    /// The pod which was deleted to upgrade the canary.
+    pub pod: String,
+    /// The `myid` of the canary, its upgraded pod gets the same one.
+    pub id: usize,
+    /// The version the canary was upgraded to.
+    pub version: ZookeeperVersion,
+    /// `Starting`, `Soaking`, `Passed` or `Failed`.
+    pub phase: CanaryPhase,
+    /// RFC 3339 timestamp of when the pod of the canary was deleted.
+    pub started_at: String,
+    /// RFC 3339 timestamp of when the canary started to serve requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub soak_started_at: Option<String>,
+    /// Why the canary failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum IntegrityCheckPhase {
+    /// The snapshot is verified.
+    Running,
+    /// The snapshot is valid, the servers are upgraded.
+    Passed,
+    /// The snapshot is corrupt or could not be verified, the upgrade is halted.
+    Failed,
+}
+
+string_enum_schema!(IntegrityCheckPhase {
+    Running,
+    Passed,
+    Failed
+});
+
+#[derive(Clone, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityCheckStatus {
+    /// The version the servers are upgraded to once the check passed.
+    pub version: ZookeeperVersion,
+    /// The pod of the leader whose data is checked.
+    pub pod: String,
+    /// `Running`, `Passed` or `Failed`.
+    pub phase: IntegrityCheckPhase,
+    /// RFC 3339 timestamp of when the check started.
+    pub started_at: String,
+    /// The result of the check.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_deserialize() {
+        let upgrade: ZookeeperUpgrade = serde_yaml::from_str(indoc! {"
+            canary:
+              soakSeconds: 60
+              startTimeoutSeconds: 30
+        "})
+        .unwrap();
+        let canary = upgrade.canary.clone().unwrap();
+
+        assert_eq!(canary.soak_seconds(), 60);
+        assert_eq!(
+            upgrade.validate(),
+            vec!["upgrade.canary.startTimeoutSeconds: Must be at least 60 but is [30]"]
+        );
+        assert_eq!(ZookeeperCanary::default().soak_seconds(), 300);
+        assert!(ZookeeperUpgrade::default().validate().is_empty());
+
+        let upgrade: ZookeeperUpgrade = serde_yaml::from_str(indoc! {"
+            integrityCheck:
+              timeoutSeconds: 0
+        "})
+        .unwrap();
+        assert_eq!(
+            upgrade.validate(),
+            vec!["upgrade.integrityCheck.timeoutSeconds: Must be greater than 0"]
+        );
+        assert_eq!(ZookeeperIntegrityCheck::default().timeout_seconds(), 600);
+    }
+}
@@ -3,6 +3,8 @@ use crate::error::Error::{
     ObjectWithoutName, OperatorFrameworkError, PodMissingLabels, PodWithoutHostname,
 };
 use crate::error::ZookeeperOperatorResult;
+use crate::ip_family;
+use crate::ports::{ZookeeperPorts, DEFAULT_CLIENT_PORT};
 use crate::util::TicketReferences::ErrZkPodWithoutName;
 use crate::{ZookeeperCluster, ZookeeperClusterSpec, APP_NAME, MANAGED_BY};
 use k8s_openapi::api::core::v1::Pod;
@@ -15,6 +17,7 @@ use stackable_operator::labels::{
     APP_INSTANCE_LABEL, APP_MANAGED_BY_LABEL, APP_NAME_LABEL, APP_ROLE_GROUP_LABEL,
 };
 use std::collections::{BTreeMap, HashSet};
+use std::convert::TryFrom;
 use std::string::ToString;
 use strum_macros::Display;
 use tracing::{debug, warn};
@@ -281,9 +284,8 @@ async fn check_zookeeper_reference(
 }
 
 // Builds the actual connection string after all necessary information has been retrieved.
-// Takes a list of pods belonging to this cluster from which the hostnames are retrieved and
-// the cluster spec itself, from which the port per host will be retrieved (unimplemented at
-// this time)
+// Takes a list of pods belonging to this cluster from which the hostnames and client ports are
+// retrieved and the cluster spec itself, which provides the client port of pods not exposing one
 fn get_zk_connection_string_from_pods(
     zookeeper_spec: ZookeeperClusterSpec,
     zk_pods: Vec<Pod>,
@@ -295,6 +297,7 @@ fn get_zk_connection_string_from_pods(
     let mut server_and_port_list = Vec::new();
 
     for pod in zk_pods {
+        let port = get_zk_port(&zookeeper_spec, &pod);
         let pod_name = match pod.metadata.name {
             None => {
                 return Err(ObjectWithoutName {
@@ -313,17 +316,14 @@ fn get_zk_connection_string_from_pods(
             Some(node_name) => node_name,
         };
 
-        let role_group = match pod.metadata.labels.get(APP_ROLE_GROUP_LABEL) {
-            None => {
-                return Err(PodMissingLabels {
-                    labels: vec![String::from(APP_ROLE_GROUP_LABEL)],
-                    pod: pod_name,
-                })
-            }
-            Some(role_group) => role_group.to_owned(),
-        };
+        if !pod.metadata.labels.contains_key(APP_ROLE_GROUP_LABEL) {
+            return Err(PodMissingLabels {
+                labels: vec![String::from(APP_ROLE_GROUP_LABEL)],
+                pod: pod_name,
+            });
+        }
 
-        server_and_port_list.push((node_name, get_zk_port(&zookeeper_spec, &role_group)?));
+        server_and_port_list.push((node_name, port));
     }
 
     // Sort list by hostname to make resulting connection strings predictable
@@ -333,7 +333,7 @@ fn get_zk_connection_string_from_pods(
 
     let conn_string = server_and_port_list
         .iter()
-        .map(|(host, port)| format!("{}:{}", host, port))
+        .map(|(host, port)| ip_family::format_address(host, port))
         .collect::<Vec<_>>()
         .join(",");
 
@@ -344,15 +344,26 @@ fn get_zk_connection_string_from_pods(
     }
 }
 
-// Retrieve the port for the specified rolegroup from the cluster spec
-// TODO: Currently hard coded to 2181 as we do not support this setting yet.
-//  Depends on https://github.com/stackabletech/zookeeper-operator/pull/71
-//  Depends on https://github.com/stackabletech/zookeeper-operator/issues/85
-fn get_zk_port(
-    _zk_cluster: &ZookeeperClusterSpec,
-    _role_group: &str,
-) -> ZookeeperOperatorResult<u16> {
-    Ok(2181)
+/// Returns the client port exposed by the ZooKeeper container of the pod.
+pub fn pod_client_port(pod: &Pod) -> Option<u16> {
+    pod.spec
+        .iter()
+        .flat_map(|spec| spec.containers.iter())
+        .flat_map(|container| container.ports.iter())
+        .find(|port| port.name.as_deref() == Some("client"))
+        .and_then(|port| u16::try_from(port.container_port).ok())
+}
+
+// Retrieve the client port of the pod, the role group may have overridden `ports.client` of the
+// cluster spec
+fn get_zk_port(zk_cluster: &ZookeeperClusterSpec, pod: &Pod) -> u16 {
+    pod_client_port(pod).unwrap_or_else(|| {
+        zk_cluster
+            .ports
+            .as_ref()
+            .map(ZookeeperPorts::client)
+            .unwrap_or(DEFAULT_CLIENT_PORT)
+    })
 }
 
 #[cfg(test)]
@@ -555,6 +566,50 @@ mod tests {
       Some("/prod"),
       "worker-1.stackable.demo:2181,worker-2.stackable.demo:2181/prod"
     )]
+    #[case::custom_client_ports(
+      indoc! {"
+        version: 3.5.8
+        ports:
+          client: 12181
+        servers:
+          roleGroups:
+            default:
+              selector:
+                matchLabels:
+                  kubernetes.io/hostname: debian
+              replicas: 1
+      "},
+      indoc! {"
+        - apiVersion: v1
+          kind: Pod
+          metadata:
+            name: test
+            labels:
+              app.kubernetes.io/name: zookeeper
+              app.kubernetes.io/role-group: default
+              app.kubernetes.io/instance: test
+          spec:
+            nodeName: worker-1.stackable.demo
+            containers:
+              - name: zookeeper
+                ports:
+                  - name: client
+                    containerPort: 22181
+        - apiVersion: v1
+          kind: Pod
+          metadata:
+            name: test
+            labels:
+              app.kubernetes.io/name: zookeeper
+              app.kubernetes.io/role-group: default
+              app.kubernetes.io/instance: test
+          spec:
+            nodeName: worker-2.stackable.demo
+            containers: []
+      "},
+      None,
+      "worker-1.stackable.demo:22181,worker-2.stackable.demo:12181"
+    )]
     fn get_connection_string(
         #[case] zookeeper_spec: &str,
         #[case] zk_pods: &str,
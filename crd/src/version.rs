@@ -0,0 +1,253 @@
+//! The ZooKeeper version an ensemble runs.
+//!
+//! Any release from 3.4.0 on is accepted. Version-specific behaviour (e.g. TLS or the
+//! AdminServer) is decided by comparing the parsed version with the release the feature was added
+//! in, so new releases can be used without changing the operator.
+use crate::error::Error;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// The oldest release the operator supports.
+pub const MIN_VERSION: &str = "3.4.0";
+
+/// Features of the client protocol and the server, with the first version supporting them.
+const FEATURES: [(&str, &str); 9] = [
+    ("multi", "3.4.0"),
+    ("create2", "3.5.0"),
+    ("removeWatches", "3.5.0"),
+    ("adminServer", "3.5.0"),
+    ("containerNodes", "3.5.3"),
+    ("clientTls", "3.5.5"),
+    ("ttlNodes", "3.6.0"),
+    ("persistentWatches", "3.6.0"),
+    ("auditLog", "3.6.0"),
+];
+
+/// A ZooKeeper release like `3.5.8`, rendered without any prefix.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ZookeeperVersion(Version);
+
+impl ZookeeperVersion {
+    pub fn is_valid_upgrade(&self, to: &Self) -> bool {
+        to > self
+    }
+
+    /// Checks whether a running ensemble can be moved from this version to `to`.
+    ///
+    /// Upgrades are always possible. Downgrades are only possible within the same minor release
+    /// line (e.g. from 3.5.8 to 3.5.7) because data written by a newer release line is not
+    /// guaranteed to be readable by an older one.
+    pub fn is_valid_version_change(&self, to: &Self) -> bool {
+        to >= self || (to.0.major == self.0.major && to.0.minor == self.0.minor)
+    }
+
+    /// Checks whether this version ships the AdminServer (added in 3.5.0).
+    pub fn has_admin_server(&self) -> bool {
+        self.is_at_least(3, 5, 0)
+    }
+
+    /// Returns the features (see [`FEATURES`]) supported by this version, e.g. `containerNodes`.
+    pub fn features(&self) -> Vec<String> {
+        FEATURES
+            .iter()
+            .filter(|(_, since)| self.0 >= Version::parse(since).unwrap())
+            .map(|(feature, _)| feature.to_string())
+            .collect()
+    }
+
+    /// Checks whether this version supports TLS for client and quorum connections (added in
+    /// 3.5.5).
+    pub fn supports_tls(&self) -> bool {
+        self.is_at_least(3, 5, 5)
+    }
+
+    /// Checks whether this version ships the Prometheus metrics provider (added in 3.6.0).
+    pub fn has_prometheus_metrics_provider(&self) -> bool {
+        self.is_at_least(3, 6, 0)
+    }
+
+    /// Checks whether this version can write an audit log (added in 3.6.0).
+    pub fn supports_audit_log(&self) -> bool {
+        self.is_at_least(3, 6, 0)
+    }
+
+    /// Checks whether this version logs with log4j 1.x, 3.8.0 replaced it with logback.
+    pub fn uses_log4j(&self) -> bool {
+        !self.is_at_least(3, 8, 0)
+    }
+
+    /// Returns the name of the directory the release archive unpacks to, the binary archives
+    /// are named `apache-zookeeper-<version>-bin` since 3.5.0.
+    pub fn package_name(&self) -> String {
+        if self.is_at_least(3, 5, 0) {
+            format!("apache-zookeeper-{}-bin", self)
+        } else {
+            format!("zookeeper-{}", self)
+        }
+    }
+
+    fn is_at_least(&self, major: u64, minor: u64, patch: u64) -> bool {
+        self.0 >= Version::new(major, minor, patch)
+    }
+}
+
+impl FromStr for ZookeeperVersion {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let illegal = |reason: String| Error::IllegalZookeeperVersion {
+            version: input.to_string(),
+            reason,
+        };
+        let version = Version::parse(input).map_err(|err| illegal(err.to_string()))?;
+        if !version.pre.is_empty() || !version.build.is_empty() {
+            return Err(illegal(
+                "only releases like 3.5.8 are supported, without a pre-release or build suffix"
+                    .to_string(),
+            ));
+        }
+        if version < Version::parse(MIN_VERSION).unwrap() {
+            return Err(illegal(format!(
+                "only releases from {} on are supported",
+                MIN_VERSION
+            )));
+        }
+        Ok(ZookeeperVersion(version))
+    }
+}
+
+impl TryFrom<String> for ZookeeperVersion {
+    type Error = Error;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        input.parse()
+    }
+}
+
+impl From<ZookeeperVersion> for String {
+    fn from(version: ZookeeperVersion) -> Self {
+        version.to_string()
+    }
+}
+
+impl fmt::Display for ZookeeperVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl JsonSchema for ZookeeperVersion {
+    fn schema_name() -> String {
+        "ZookeeperVersion".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let mut schema = String::json_schema(gen).into_object();
+        schema.string().pattern = Some(r"^\d+\.\d+\.\d+$".to_string());
+        schema.into()
+    }
+
+    fn is_referenceable() -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn version(version: &str) -> ZookeeperVersion {
+        version.parse().unwrap()
+    }
+
+    #[rstest]
+    #[case("3.4.14", true)]
+    #[case("3.5.8", true)]
+    #[case("3.6.2", true)]
+    #[case("3.8.0", true)]
+    #[case("3.3.6", false)]
+    #[case("1.2.3", false)]
+    #[case("3.6.2-rc1", false)]
+    #[case("v3.6.2", false)]
+    #[case("3.6", false)]
+    fn test_parse(#[case] input: &str, #[case] valid: bool) {
+        assert_eq!(input.parse::<ZookeeperVersion>().is_ok(), valid);
+    }
+
+    #[test]
+    fn test_rendering() {
+        assert_eq!(version("3.6.2").to_string(), "3.6.2");
+        assert_eq!(
+            serde_json::to_string(&version("3.6.2")).unwrap(),
+            "\"3.6.2\""
+        );
+        assert_eq!(
+            serde_json::from_str::<ZookeeperVersion>("\"3.6.2\"").unwrap(),
+            version("3.6.2")
+        );
+        assert!(serde_json::from_str::<ZookeeperVersion>("\"3.3.6\"").is_err());
+    }
+
+    #[test]
+    fn test_features() {
+        assert_eq!(version("3.4.14").features(), vec!["multi"]);
+
+        let features = version("3.5.8").features();
+        assert!(features.contains(&"containerNodes".to_string()));
+        assert!(features.contains(&"clientTls".to_string()));
+        assert!(features.contains(&"adminServer".to_string()));
+        assert!(!features.contains(&"reconfig".to_string()));
+        assert!(!features.contains(&"ttlNodes".to_string()));
+    }
+
+    #[rstest]
+    #[case("3.4.14", false, false, false, "zookeeper-3.4.14")]
+    #[case("3.5.4", true, false, false, "apache-zookeeper-3.5.4-bin")]
+    #[case("3.5.8", true, true, false, "apache-zookeeper-3.5.8-bin")]
+    #[case("3.6.2", true, true, true, "apache-zookeeper-3.6.2-bin")]
+    fn test_version_specific_features(
+        #[case] input: &str,
+        #[case] admin_server: bool,
+        #[case] tls: bool,
+        #[case] prometheus: bool,
+        #[case] package_name: &str,
+    ) {
+        let version = version(input);
+        assert_eq!(version.has_admin_server(), admin_server);
+        assert_eq!(version.supports_tls(), tls);
+        assert_eq!(version.has_prometheus_metrics_provider(), prometheus);
+        assert_eq!(version.supports_audit_log(), prometheus);
+        assert_eq!(version.package_name(), package_name);
+        assert!(version.uses_log4j());
+    }
+
+    #[test]
+    fn test_uses_log4j() {
+        assert!(version("3.7.1").uses_log4j());
+        assert!(!version("3.8.0").uses_log4j());
+    }
+
+    #[test]
+    fn test_version_upgrade() {
+        assert!(version("3.4.14").is_valid_upgrade(&version("3.5.8")));
+        assert!(!version("3.5.8").is_valid_upgrade(&version("3.4.14")));
+        assert!(version("3.5.8").is_valid_upgrade(&version("3.5.10")));
+    }
+
+    #[test]
+    fn test_version_change() {
+        assert!(version("3.4.14").is_valid_version_change(&version("3.5.8")));
+        assert!(version("3.5.8").is_valid_version_change(&version("3.5.8")));
+        assert!(version("3.5.8").is_valid_version_change(&version("3.5.7")));
+        assert!(!version("3.5.8").is_valid_version_change(&version("3.4.14")));
+    }
+}
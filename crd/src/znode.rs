@@ -0,0 +1,461 @@
+//! The `ZookeeperZnode` custom resource.
+//!
+//! A ZookeeperZnode requests a znode in a ZookeeperCluster, which an application can use as its
+//! chroot. The operator creates the znode under a unique path, publishes the connection string
+//! (including the chroot) in a discovery ConfigMap of the same name and deletes the znode
+//! (including all of its children) again when the ZookeeperZnode is deleted.
+//!
+//! Applications get their own credentials on the znode with `users`: the operator generates a
+//! password for every user into the Secret `<znode name>.<user name>` (see [`user_secret_name`])
+//! and grants the user its permissions with a `digest` ACL. Clients authenticate with
+//! `addauth digest <username>:<password>`.
+//!
+//! Applications which expect configuration znodes to exist can get them seeded from a ConfigMap
+//! with `seed`, see [`ZnodeSeed`].
+use crate::error::{Error, ZookeeperOperatorResult};
+use crate::util::is_valid_zookeeper_path;
+
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use stackable_operator::status::Conditions;
+
+/// The key of the connection string (e.g. `server1:2181,server2:2181/znode-<uid>`) in the
+/// discovery ConfigMap.
+pub const ZOOKEEPER_DISCOVERY_KEY: &str = "ZOOKEEPER";
+/// The key of the znode path (e.g. `/znode-<uid>`) in the discovery ConfigMap.
+pub const ZOOKEEPER_CHROOT_DISCOVERY_KEY: &str = "ZOOKEEPER_CHROOT";
+
+/// The permissions of ZooKeeper ACLs, see `org.apache.zookeeper.ZooDefs.Perms`.
+pub const PERMISSION_READ: u32 = 1;
+pub const PERMISSION_WRITE: u32 = 2;
+pub const PERMISSION_CREATE: u32 = 4;
+pub const PERMISSION_DELETE: u32 = 8;
+pub const PERMISSION_ADMIN: u32 = 16;
+pub const PERMISSION_ALL: u32 = 31;
+
+/// The key of the `digest` ACL id (`<user>:<digest>`) in the Secret of a [`ZnodeUser`].
+pub const DIGEST_KEY: &str = "digest";
+/// The label on the Secrets of the users of a ZookeeperZnode, holding its name.
+pub const ZNODE_LABEL: &str = "zookeeper.stackable.tech/znode";
+/// The default of [`ZnodeSeed::path_separator`].
+pub const DEFAULT_SEED_PATH_SEPARATOR: &str = ".";
+
+#[derive(Clone, CustomResource, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[kube(
+    group = "zookeeper.stackable.tech",
+    version = "v1alpha1",
+    kind = "ZookeeperZnode",
+    plural = "zookeeperznodes",
+    shortname = "zno",
+    namespaced
+)]
+#[kube(status = "ZookeeperZnodeStatus")]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperZnodeSpec {
+    pub cluster_ref: ZookeeperClusterRef,
+    /// Limits the number of znodes and bytes below the znode. ZooKeeper only logs a warning when
+    /// a quota is exceeded, it does not reject any requests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota: Option<ZnodeQuota>,
+    /// The ACLs of the znode, it is world accessible if there are none. The super user of the
+    /// cluster always retains all permissions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub acls: Vec<ZnodeAcl>,
+    /// Users with generated credentials, which are granted the given permissions on the znode
+    /// in addition to `acls`. Requires `spec.authentication` in the cluster.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub users: Vec<ZnodeUser>,
+    /// Child znodes which are created below the znode from a ConfigMap, e.g. the configuration
+    /// an application expects to exist.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<ZnodeSeed>,
+}
+
+/// References a ZookeeperCluster, e.g. the one a znode should be created in.
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperClusterRef {
+    pub name: String,
+    /// Defaults to the namespace of the referencing resource.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZnodeQuota {
+    /// The maximum number of znodes (including the znode itself).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nodes: Option<i64>,
+    /// The maximum number of bytes of data stored in the znodes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<i64>,
+}
+
+impl ZnodeQuota {
+    /// Renders the quota in the format ZooKeeper expects in the `zookeeper_limits` znode, e.g.
+    /// `count=100,bytes=-1`. Unset limits are written as `-1`, which means unlimited.
+    pub fn to_limits(&self) -> String {
+        format!(
+            "count={},bytes={}",
+            self.nodes.unwrap_or(-1),
+            self.bytes.unwrap_or(-1)
+        )
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZnodeAcl {
+    pub scheme: ZnodeAclScheme,
+    /// The id within the scheme: `anyone` for `world` (the default) and
+    /// `<user>:<base64 encoded SHA-1 digest of "<user>:<password>">` for `digest`. `auth` grants
+    /// the permissions to the authenticated users of the session setting the ACL (the super
+    /// user of the cluster) and does not take an id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// The permissions in the notation of the ZooKeeper CLI, a combination of `c` (create),
+    /// `d` (delete), `r` (read), `w` (write) and `a` (admin), e.g. `cdrwa`.
+    pub permissions: String,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ZnodeAclScheme {
+    World,
+    Auth,
+    Digest,
+}
+
+impl ZnodeAclScheme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ZnodeAclScheme::World => "world",
+            ZnodeAclScheme::Auth => "auth",
+            ZnodeAclScheme::Digest => "digest",
+        }
+    }
+}
+
+impl ZnodeAcl {
+    /// Returns the id as passed to ZooKeeper.
+    pub fn zookeeper_id(&self) -> ZookeeperOperatorResult<String> {
+        match (self.scheme, &self.id) {
+            (ZnodeAclScheme::World, None) => Ok("anyone".to_string()),
+            (ZnodeAclScheme::World, Some(id)) if id == "anyone" => Ok(id.clone()),
+            (ZnodeAclScheme::Auth, None) => Ok(String::new()),
+            (ZnodeAclScheme::Digest, Some(id)) if id.split_once(':').is_some() => Ok(id.clone()),
+            _ => Err(self.illegal(
+                "the id must be `anyone` for the `world` scheme, `<user>:<digest>` for the `digest` scheme and must not be set for the `auth` scheme",
+            )),
+        }
+    }
+
+    /// Returns the permissions as bit mask, see [`PERMISSION_ALL`].
+    pub fn permission_bits(&self) -> ZookeeperOperatorResult<u32> {
+        if self.permissions.is_empty() {
+            return Err(self.illegal("no permissions are granted"));
+        }
+        self.permissions.chars().try_fold(0, |bits, permission| {
+            let bit = match permission {
+                'r' => PERMISSION_READ,
+                'w' => PERMISSION_WRITE,
+                'c' => PERMISSION_CREATE,
+                'd' => PERMISSION_DELETE,
+                'a' => PERMISSION_ADMIN,
+                _ => {
+                    return Err(self.illegal(&format!("unknown permission [{}]", permission)));
+                }
+            };
+            Ok(bits | bit)
+        })
+    }
+
+    fn illegal(&self, reason: &str) -> Error {
+        Error::IllegalZnodeAcl {
+            acl: format!(
+                "{}:{}:{}",
+                self.scheme.as_str(),
+                self.id.as_deref().unwrap_or_default(),
+                self.permissions
+            ),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZnodeUser {
+    /// The name of the user, a lowercase DNS label because it is part of the name of the Secret
+    /// with the credentials.
+    pub name: String,
+    /// The permissions of the user in the notation of `acls`, e.g. `crw`.
+    pub permissions: String,
+}
+
+impl ZnodeUser {
+    /// Checks that the name can be used in the name of the Secret and as `digest` user.
+    pub fn validate(&self) -> ZookeeperOperatorResult<()> {
+        let illegal = |reason: &str| Error::IllegalZnodeUser {
+            user: self.name.clone(),
+            reason: reason.to_string(),
+        };
+        if self.name.is_empty() || self.name.len() > 63 {
+            return Err(illegal("the name must have between 1 and 63 characters"));
+        }
+        if !self
+            .name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            || self.name.starts_with('-')
+            || self.name.ends_with('-')
+        {
+            return Err(illegal(
+                "the name must consist of lowercase letters, digits and `-` and must start and end with a letter or digit",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the ACL granting the permissions to the user with the given `digest` id
+    /// (`<user>:<digest>`).
+    pub fn acl(&self, digest: &str) -> ZnodeAcl {
+        ZnodeAcl {
+            scheme: ZnodeAclScheme::Digest,
+            id: Some(digest.to_string()),
+            permissions: self.permissions.clone(),
+        }
+    }
+}
+
+/// Seeds child znodes from a ConfigMap in the namespace of the ZookeeperZnode.
+///
+/// Every key of the ConfigMap is the path of a znode relative to the znode, with
+/// `pathSeparator` instead of `/` because keys can not contain `/` (e.g. `config.db.url` for
+/// `<znode>/config/db/url`), and its value (from `data` or `binaryData`) the data of the znode.
+/// Missing parents are created without data.
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZnodeSeed {
+    pub config_map_name: String,
+    /// Separates the znodes of a path in the keys, `.` by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_separator: Option<String>,
+    /// Whether the data of existing znodes is replaced with the one in the ConfigMap. By default
+    /// only missing znodes are created, so changes of the application are kept.
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+impl ZnodeSeed {
+    pub fn path_separator(&self) -> &str {
+        self.path_separator
+            .as_deref()
+            .filter(|separator| !separator.is_empty())
+            .unwrap_or(DEFAULT_SEED_PATH_SEPARATOR)
+    }
+
+    /// Returns the path of the znode seeded from the ConfigMap `key` below `znode_path`.
+    pub fn seed_path(&self, znode_path: &str, key: &str) -> ZookeeperOperatorResult<String> {
+        let path = format!("{}/{}", znode_path, key.replace(self.path_separator(), "/"));
+        is_valid_zookeeper_path(&path)?;
+        Ok(path)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZookeeperZnodeStatus {
+    /// The path of the znode, it is assigned once and never changes afterwards.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub znode_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(schema_with = "stackable_operator::conditions::schema")]
+    pub conditions: Vec<Condition>,
+}
+
+impl ZookeeperZnode {
+    /// Returns the namespace of the referenced ZookeeperCluster.
+    pub fn cluster_namespace(&self) -> Option<String> {
+        self.spec
+            .cluster_ref
+            .namespace
+            .clone()
+            .or_else(|| self.metadata.namespace.clone())
+    }
+}
+
+impl Conditions for ZookeeperZnode {
+    fn conditions(&self) -> Option<&[Condition]> {
+        self.status
+            .as_ref()
+            .map(|status| status.conditions.as_slice())
+    }
+
+    fn conditions_mut(&mut self) -> &mut Vec<Condition> {
+        &mut self
+            .status
+            .get_or_insert_with(ZookeeperZnodeStatus::default)
+            .conditions
+    }
+}
+
+/// Builds the unique znode path for the ZookeeperZnode with the given `uid`.
+///
+/// The uid is used (instead of the name) so that deleting and recreating a ZookeeperZnode with
+/// the same name does not hand out the data of its predecessor.
+pub fn build_znode_path(uid: &str) -> ZookeeperOperatorResult<String> {
+    let path = format!("/znode-{}", uid);
+    is_valid_zookeeper_path(&path)?;
+    Ok(path)
+}
+
+/// Returns the name of the Secret with the credentials of `user` of the ZookeeperZnode with the
+/// given name, `<znode name>.<user name>`. User names can not contain dots, so no other znode and
+/// user share the name.
+pub fn user_secret_name(znode_name: &str, user: &str) -> String {
+    format!("{}.{}", znode_name, user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use rstest::rstest;
+
+    #[test]
+    fn test_build_znode_path() {
+        assert_eq!(
+            build_znode_path("c2e8a6f4-8c3a-4c5e-9a1e-0d4f7a3c2b1d").unwrap(),
+            "/znode-c2e8a6f4-8c3a-4c5e-9a1e-0d4f7a3c2b1d"
+        );
+        build_znode_path("with//empty-element").unwrap_err();
+    }
+
+    #[test]
+    fn test_quota_to_limits() {
+        let quota = ZnodeQuota {
+            nodes: Some(100),
+            bytes: None,
+        };
+        assert_eq!(quota.to_limits(), "count=100,bytes=-1");
+        assert_eq!(ZnodeQuota::default().to_limits(), "count=-1,bytes=-1");
+    }
+
+    #[rstest]
+    #[case("r", PERMISSION_READ)]
+    #[case("rw", PERMISSION_READ | PERMISSION_WRITE)]
+    #[case("cdrwa", PERMISSION_ALL)]
+    fn test_acl_permission_bits(#[case] permissions: &str, #[case] expected: u32) {
+        let acl = ZnodeAcl {
+            scheme: ZnodeAclScheme::World,
+            id: None,
+            permissions: permissions.to_string(),
+        };
+        assert_eq!(acl.permission_bits().unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(ZnodeAclScheme::World, None, "", true)]
+    #[case(ZnodeAclScheme::World, None, "rx", true)]
+    #[case(ZnodeAclScheme::World, Some("app"), "r", true)]
+    #[case(ZnodeAclScheme::Auth, Some("app"), "r", true)]
+    #[case(ZnodeAclScheme::Digest, None, "r", true)]
+    #[case(ZnodeAclScheme::Digest, Some("app"), "r", true)]
+    #[case(ZnodeAclScheme::Digest, Some("app:bWFkZSB1cA=="), "r", false)]
+    #[case(ZnodeAclScheme::Auth, None, "cdrwa", false)]
+    fn test_acl_validation(
+        #[case] scheme: ZnodeAclScheme,
+        #[case] id: Option<&str>,
+        #[case] permissions: &str,
+        #[case] illegal: bool,
+    ) {
+        let acl = ZnodeAcl {
+            scheme,
+            id: id.map(str::to_string),
+            permissions: permissions.to_string(),
+        };
+        assert_eq!(
+            acl.zookeeper_id()
+                .and_then(|_| acl.permission_bits())
+                .is_err(),
+            illegal
+        );
+    }
+
+    #[rstest]
+    #[case("kafka", false)]
+    #[case("kafka-1", false)]
+    #[case("", true)]
+    #[case("Kafka", true)]
+    #[case("kafka:admin", true)]
+    #[case("-kafka", true)]
+    fn test_user_validation(#[case] name: &str, #[case] illegal: bool) {
+        let user = ZnodeUser {
+            name: name.to_string(),
+            permissions: "r".to_string(),
+        };
+        assert_eq!(user.validate().is_err(), illegal);
+    }
+
+    #[test]
+    fn test_user_acl() {
+        let user = ZnodeUser {
+            name: "kafka".to_string(),
+            permissions: "crw".to_string(),
+        };
+        let acl = user.acl("kafka:bWFkZSB1cA==");
+
+        assert_eq!(acl.zookeeper_id().unwrap(), "kafka:bWFkZSB1cA==");
+        assert_eq!(
+            acl.permission_bits().unwrap(),
+            PERMISSION_CREATE | PERMISSION_READ | PERMISSION_WRITE
+        );
+        assert_eq!(
+            user_secret_name("simple-kafka", "kafka"),
+            "simple-kafka.kafka"
+        );
+        assert_ne!(user_secret_name("a-b", "c"), user_secret_name("a", "b-c"));
+        assert_ne!(user_secret_name("a.b", "c"), user_secret_name("a", "b.c"));
+    }
+
+    #[test]
+    fn test_cluster_namespace() {
+        let znode: ZookeeperZnode = serde_yaml::from_str(indoc! {"
+            apiVersion: zookeeper.stackable.tech/v1alpha1
+            kind: ZookeeperZnode
+            metadata:
+              name: kafka
+              namespace: apps
+            spec:
+              clusterRef:
+                name: simple
+        "})
+        .unwrap();
+        assert_eq!(znode.cluster_namespace(), Some("apps".to_string()));
+    }
+
+    #[rstest]
+    #[case::default_separator(None, "config.db.url", Some("/znode-1/config/db/url"))]
+    #[case::custom_separator(Some("__"), "app.conf__url", Some("/znode-1/app.conf/url"))]
+    #[case::empty_separator(Some(""), "config.url", Some("/znode-1/config/url"))]
+    #[case::empty_segment(None, "config..url", None)]
+    #[case::trailing_separator(None, "config.", None)]
+    #[case::relative_segment(Some("__"), "..", None)]
+    fn test_seed_path(
+        #[case] path_separator: Option<&str>,
+        #[case] key: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        let seed = ZnodeSeed {
+            config_map_name: "seed".to_string(),
+            path_separator: path_separator.map(str::to_string),
+            overwrite: false,
+        };
+
+        assert_eq!(seed.seed_path("/znode-1", key).ok().as_deref(), expected);
+    }
+}
@@ -0,0 +1,94 @@
+//! Building the Service in front of the AdminServers of a cluster, see
+//! [`stackable_zookeeper_crd::admin_server`].
+use crate::error::Error;
+
+use k8s_openapi::api::core::v1::{Service, ServicePort, ServiceSpec};
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use kube::ResourceExt;
+use stackable_operator::builder::ObjectMetaBuilder;
+use stackable_operator::labels::build_common_labels_for_all_managed_resources;
+use stackable_zookeeper_crd::admin_server::ZookeeperAdminServer;
+use stackable_zookeeper_crd::ip_family::{self, IpFamily};
+use stackable_zookeeper_crd::{ZookeeperCluster, APP_NAME};
+
+/// The name of the container port of the AdminServer.
+const ADMIN_PORT_NAME: &str = "admin";
+
+/// Returns the name of the AdminServer Service of the given cluster.
+pub fn service_name(cluster_name: &str) -> String {
+    format!("{}-admin", cluster_name)
+}
+
+/// Builds the ClusterIP Service balancing the requests across the AdminServers of all servers of
+/// `cluster`. It forwards to the named container port, so role groups with their own `adminPort`
+/// are covered as well.
+pub fn build_service(
+    cluster: &ZookeeperCluster,
+    admin_server: &ZookeeperAdminServer,
+) -> Result<Service, Error> {
+    let cluster_name = cluster.name();
+    let labels = build_common_labels_for_all_managed_resources(APP_NAME, &cluster_name);
+
+    let mut metadata = ObjectMetaBuilder::new()
+        .name(service_name(&cluster_name))
+        .namespace(cluster.metadata.namespace.as_deref().unwrap_or_default())
+        .ownerreference_from_resource(cluster, Some(true), Some(true))?
+        .build()?;
+    metadata.labels = labels.clone();
+
+    Ok(Service {
+        metadata,
+        spec: Some(ServiceSpec {
+            selector: labels,
+            ports: vec![ServicePort {
+                name: Some(ADMIN_PORT_NAME.to_string()),
+                port: i32::from(admin_server.port()),
+                target_port: Some(IntOrString::String(ADMIN_PORT_NAME.to_string())),
+                protocol: Some("TCP".to_string()),
+                ..ServicePort::default()
+            }],
+            ip_families: cluster
+                .spec
+                .ip_families
+                .iter()
+                .map(IpFamily::to_string)
+                .collect(),
+            ip_family_policy: ip_family::ip_family_policy(
+                &cluster.spec.ip_families,
+                cluster.spec.ip_family_policy,
+            )
+            .map(|policy| policy.to_string()),
+            ..ServiceSpec::default()
+        }),
+        ..Service::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use serde_json::json;
+
+    #[test]
+    fn test_build_service() {
+        let cluster = test_support::cluster(
+            "simple",
+            json!({ "adminServer": { "port": 18080, "service": true } }),
+        );
+
+        let service = build_service(&cluster, cluster.spec.admin_server.as_ref().unwrap()).unwrap();
+        let spec = service.spec.unwrap();
+
+        assert_eq!(service.metadata.name.as_deref(), Some("simple-admin"));
+        assert_eq!(spec.selector, service.metadata.labels);
+        assert_eq!(spec.type_, None);
+        assert_eq!(spec.ports[0].port, 18080);
+        assert_eq!(
+            spec.ports[0].target_port,
+            Some(IntOrString::String("admin".to_string()))
+        );
+        assert!(spec.ip_families.is_empty());
+        assert_eq!(spec.ip_family_policy, None);
+    }
+}
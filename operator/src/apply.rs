@@ -0,0 +1,551 @@
+//! Writing the objects of the operator with server-side apply.
+//!
+//! All writes of the operator use the field manager [`FIELD_MANAGER`], so Kubernetes records
+//! which fields the operator manages and drops fields it stopped applying. The objects are applied
+//! without the fields the operator does not manage (see [`strip_unmanaged_fields`]), otherwise the
+//! operator would claim e.g. the status written by other controllers.
+//!
+//! Applying a field which another manager changed is a conflict. How it is resolved depends on
+//! the [`ConflictPolicy`]: objects owned by the operator are taken over, other objects with the
+//! same name (e.g. a Service created by an administrator) are left alone and reported as
+//! conflict, whether their fields conflict or not. Otherwise they would get an owner reference to
+//! the cluster and be deleted together with it.
+//!
+//! Earlier versions of the operator applied objects as [`LEGACY_FIELD_MANAGER`]. The fields of
+//! that manager are moved to [`FIELD_MANAGER`] before an object is applied, otherwise they would
+//! stay owned by the old manager and never be removed when the operator stops applying them.
+//!
+//! Objects which already contain everything the operator would apply are not applied again, see
+//! [`diff`](crate::diff). In dry-run mode the changes are only planned, see [`plan`].
+use crate::error::Error;
+use crate::{diff, dry_run};
+
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{FieldsV1, ManagedFieldsEntry, ObjectMeta};
+use kube::api::{DeleteParams, Patch, PatchParams};
+use kube::{Api, Resource, ResourceExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use stackable_zookeeper_crd::dry_run::{PlannedAction, PlannedChange};
+use std::fmt::Debug;
+use tracing::{debug, warn};
+
+/// The field manager of all writes of the operator. The client is created with it as well, so it
+/// is also used for creations and merge patches.
+pub const FIELD_MANAGER: &str = "zookeeper.stackable.tech";
+
+/// The field manager of the server-side applies of earlier versions of the operator.
+pub const LEGACY_FIELD_MANAGER: &str = stackable_zookeeper_crd::MANAGED_BY;
+
+/// How conflicts with other field managers are resolved.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ConflictPolicy {
+    /// The fields are always taken over, e.g. for the CRDs of the operator.
+    Force,
+    /// The fields are taken over if the object is owned by the object with the given uid (e.g.
+    /// the ZookeeperCluster), otherwise the conflict is reported as error.
+    ForceIfOwnedBy(String),
+}
+
+/// Returns the parameters for merge patches, which only set the given fields and therefore never
+/// conflict.
+pub fn merge_params() -> PatchParams {
+    PatchParams {
+        field_manager: Some(FIELD_MANAGER.to_string()),
+        ..PatchParams::default()
+    }
+}
+
+/// Applies `object` and resolves conflicts according to `policy`. Returns the object as stored.
+pub async fn apply<K>(api: &Api<K>, object: &K, policy: &ConflictPolicy) -> Result<K, Error>
+where
+    K: Clone + Debug + DeserializeOwned + Resource + Serialize,
+{
+    let name = object.name();
+    let patch = build_patch(object)?;
+
+    if let Some(existing) = get_existing(api, &name).await? {
+        if !should_force(existing.meta(), policy) {
+            return Err(Error::ApplyConflict {
+                object: name,
+                message: "The object exists already and is not owned by the cluster".to_string(),
+            });
+        }
+        if let Some(managed_fields) = migrate_managed_fields(&existing.meta().managed_fields) {
+            debug!(
+                "Moving the fields of [{}] managed by [{}] to [{}]",
+                name, LEGACY_FIELD_MANAGER, FIELD_MANAGER
+            );
+            let patch = json!({ "metadata": { "managedFields": managed_fields } });
+            api.patch(&name, &merge_params(), &Patch::Merge(&patch))
+                .await?;
+        }
+        if diff::is_applied(&patch, &serde_json::to_value(&existing)?) {
+            debug!("[{}] is up to date, not applying it", name);
+            return Ok(existing);
+        }
+    }
+
+    let force = match policy {
+        ConflictPolicy::Force => true,
+        ConflictPolicy::ForceIfOwnedBy(_) => false,
+    };
+    match apply_patch(api, &name, &patch, force).await {
+        Err(Error::KubeError {
+            source: kube::Error::Api(response),
+        }) if response.code == 409 => {
+            let existing = api.get(&name).await?;
+            if !should_force(existing.meta(), policy) {
+                return Err(Error::ApplyConflict {
+                    object: name,
+                    message: response.message,
+                });
+            }
+            warn!(
+                "Taking over the fields of [{}] changed by other field managers: {}",
+                name, response.message
+            );
+            apply_patch(api, &name, &patch, true).await
+        }
+        result => result,
+    }
+}
+
+/// Returns the change applying `object` would make, `None` if it is up to date, see
+/// [`dry_run`](crate::dry_run). With `server_side` the object is applied as server-side dry run
+/// and the changed fields are taken from the object the API server would store, which also shows
+/// fields the operator stopped applying. Conflicts are not reported, they are resolved when the
+/// object is applied.
+pub async fn plan<K>(
+    api: &Api<K>,
+    object: &K,
+    server_side: bool,
+) -> Result<Option<PlannedChange>, Error>
+where
+    K: Clone + Debug + DeserializeOwned + Resource + Serialize,
+    K::DynamicType: Default,
+{
+    let name = object.name();
+    let kind = K::kind(&K::DynamicType::default()).to_string();
+    let patch = build_patch(object)?;
+
+    let existing = match get_existing(api, &name).await? {
+        Some(existing) => serde_json::to_value(&existing)?,
+        None => {
+            if server_side {
+                // rejects objects which are invalid or denied by admission webhooks
+                apply_patch_with_params(api, &name, &patch, dry_run_params()).await?;
+            }
+            return Ok(Some(PlannedChange::new(
+                PlannedAction::Create,
+                &kind,
+                &name,
+            )));
+        }
+    };
+    if diff::is_applied(&patch, &existing) {
+        return Ok(None);
+    }
+
+    let mut fields = if server_side {
+        let mut applied = serde_json::to_value(
+            apply_patch_with_params(api, &name, &patch, dry_run_params()).await?,
+        )?;
+        let mut existing = existing;
+        strip_unmanaged_fields(&mut applied);
+        strip_unmanaged_fields(&mut existing);
+        let mut fields = dry_run::changed_fields(&applied, &existing);
+        fields.extend(dry_run::changed_fields(&existing, &applied));
+        fields.sort();
+        fields.dedup();
+        fields
+    } else {
+        dry_run::changed_fields(&patch, &existing)
+    };
+    // the hash changes with every other field
+    let hash_field = format!("metadata.annotations.{}", diff::APPLIED_HASH_ANNOTATION);
+    fields.retain(|field| field != &hash_field);
+
+    let mut change = PlannedChange::new(PlannedAction::Update, &kind, &name);
+    change.fields = fields;
+    Ok(Some(change))
+}
+
+/// Returns the deletion of the object with the given `name`, `None` if it does not exist.
+pub async fn plan_delete<K>(api: &Api<K>, name: &str) -> Result<Option<PlannedChange>, Error>
+where
+    K: Clone + DeserializeOwned + Resource,
+    K::DynamicType: Default,
+{
+    let kind = K::kind(&K::DynamicType::default()).to_string();
+    Ok(get_existing(api, name)
+        .await?
+        .map(|_| PlannedChange::new(PlannedAction::Delete, &kind, name)))
+}
+
+/// Deletes the object with the given `name` if it exists and `policy` allows to take it over.
+/// Returns whether it was deleted. Objects which do not exist are not deleted blindly, which saves
+/// the writes and the permission to delete them for the objects which were never created.
+pub async fn delete<K>(api: &Api<K>, name: &str, policy: &ConflictPolicy) -> Result<bool, Error>
+where
+    K: Clone + Debug + DeserializeOwned + Resource,
+{
+    match get_existing(api, name).await? {
+        Some(existing) if should_force(existing.meta(), policy) => {
+            match api.delete(name, &DeleteParams::default()).await {
+                Ok(_) => Ok(true),
+                Err(kube::Error::Api(response)) if response.code == 404 => Ok(false),
+                Err(err) => Err(err.into()),
+            }
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Serializes `object` without the unmanaged fields and annotates it with its hash.
+fn build_patch<K: Serialize>(object: &K) -> Result<Value, Error> {
+    let mut patch = serde_json::to_value(object)?;
+    strip_unmanaged_fields(&mut patch);
+    let hash = diff::hash_applied_object(&patch)?;
+    diff::set_annotation(&mut patch, diff::APPLIED_HASH_ANNOTATION, &hash);
+    Ok(patch)
+}
+
+/// Returns the object with the given `name`, `None` if it does not exist.
+pub async fn get_existing<K>(api: &Api<K>, name: &str) -> Result<Option<K>, Error>
+where
+    K: Clone + DeserializeOwned,
+{
+    match api.get(name).await {
+        Ok(existing) => Ok(Some(existing)),
+        Err(kube::Error::Api(response)) if response.code == 404 => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn apply_patch<K>(api: &Api<K>, name: &str, patch: &Value, force: bool) -> Result<K, Error>
+where
+    K: Clone + Debug + DeserializeOwned + Resource,
+{
+    let mut params = PatchParams::apply(FIELD_MANAGER);
+    params.force = force;
+    apply_patch_with_params(api, name, patch, params).await
+}
+
+async fn apply_patch_with_params<K>(
+    api: &Api<K>,
+    name: &str,
+    patch: &Value,
+    params: PatchParams,
+) -> Result<K, Error>
+where
+    K: Clone + Debug + DeserializeOwned + Resource,
+{
+    Ok(api.patch(name, &params, &Patch::Apply(patch)).await?)
+}
+
+/// Returns the parameters of server-side dry runs, which take over all fields like the operator
+/// would for the objects it owns.
+fn dry_run_params() -> PatchParams {
+    let mut params = PatchParams::apply(FIELD_MANAGER).force();
+    params.dry_run = true;
+    params
+}
+
+/// Checks whether the existing object with the given metadata may be applied, taking over the
+/// fields of other field managers on conflicts.
+fn should_force(metadata: &ObjectMeta, policy: &ConflictPolicy) -> bool {
+    match policy {
+        ConflictPolicy::Force => true,
+        ConflictPolicy::ForceIfOwnedBy(uid) => metadata
+            .owner_references
+            .iter()
+            .any(|owner| &owner.uid == uid),
+    }
+}
+
+/// Returns `managed_fields` with the entries of [`LEGACY_FIELD_MANAGER`] moved to
+/// [`FIELD_MANAGER`], `None` if there are no such entries. The fields of an entry are merged into
+/// the entry of [`FIELD_MANAGER`] with the same operation and API version if there is one.
+fn migrate_managed_fields(
+    managed_fields: &[ManagedFieldsEntry],
+) -> Option<Vec<ManagedFieldsEntry>> {
+    if !managed_fields
+        .iter()
+        .any(|entry| entry.manager.as_deref() == Some(LEGACY_FIELD_MANAGER))
+    {
+        return None;
+    }
+
+    let mut migrated: Vec<ManagedFieldsEntry> = vec![];
+    for entry in managed_fields {
+        let mut entry = entry.clone();
+        if entry.manager.as_deref() == Some(LEGACY_FIELD_MANAGER) {
+            entry.manager = Some(FIELD_MANAGER.to_string());
+        }
+        let existing = migrated.iter_mut().find(|migrated| {
+            migrated.manager == entry.manager
+                && migrated.operation == entry.operation
+                && migrated.api_version == entry.api_version
+        });
+        match existing {
+            Some(existing) => {
+                let mut fields = existing
+                    .fields_v1
+                    .take()
+                    .map_or_else(|| json!({}), |fields| fields.0);
+                if let Some(other) = entry.fields_v1 {
+                    merge_fields(&mut fields, &other.0);
+                }
+                existing.fields_v1 = Some(FieldsV1(fields));
+                existing.time = existing.time.clone().max(entry.time);
+            }
+            None => migrated.push(entry),
+        }
+    }
+    Some(migrated)
+}
+
+/// Adds the fields of the `fieldsV1` set `other` to `fields`.
+fn merge_fields(fields: &mut Value, other: &Value) {
+    match (fields.as_object_mut(), other.as_object()) {
+        (Some(fields), Some(other)) => {
+            for (key, value) in other {
+                merge_fields(
+                    fields.entry(key.clone()).or_insert_with(|| json!({})),
+                    value,
+                );
+            }
+        }
+        _ => *fields = other.clone(),
+    }
+}
+
+/// Removes the fields of a serialized object which are set by the API server or other
+/// controllers: the status and the server generated metadata. Server-side apply rejects objects
+/// with `managedFields`.
+pub fn strip_unmanaged_fields(object: &mut Value) {
+    if let Some(object) = object.as_object_mut() {
+        object.remove("status");
+        if let Some(metadata) = object.get_mut("metadata").and_then(Value::as_object_mut) {
+            for field in &[
+                "creationTimestamp",
+                "generation",
+                "managedFields",
+                "resourceVersion",
+                "selfLink",
+                "uid",
+            ] {
+                metadata.remove(*field);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, FakeApiServer};
+    use indoc::indoc;
+    use k8s_openapi::api::core::v1::ServiceAccount;
+    use rstest::rstest;
+
+    #[test]
+    fn test_strip_unmanaged_fields() {
+        let mut object = json!({
+            "apiVersion": "v1",
+            "kind": "Service",
+            "metadata": {
+                "name": "simple-admin",
+                "labels": { "app.kubernetes.io/name": "zookeeper" },
+                "managedFields": [{ "manager": "kubectl-edit" }],
+                "resourceVersion": "42",
+                "uid": "1234"
+            },
+            "spec": { "ports": [{ "port": 8080 }] },
+            "status": { "loadBalancer": {} }
+        });
+
+        strip_unmanaged_fields(&mut object);
+
+        assert_eq!(
+            object,
+            json!({
+                "apiVersion": "v1",
+                "kind": "Service",
+                "metadata": {
+                    "name": "simple-admin",
+                    "labels": { "app.kubernetes.io/name": "zookeeper" }
+                },
+                "spec": { "ports": [{ "port": 8080 }] }
+            })
+        );
+    }
+
+    #[test]
+    fn test_should_force() {
+        let metadata: ObjectMeta = serde_yaml::from_str(indoc! {"
+            name: simple-admin
+            ownerReferences:
+              - apiVersion: zookeeper.stackable.tech/v1alpha1
+                kind: ZookeeperCluster
+                name: simple
+                uid: cluster
+        "})
+        .unwrap();
+
+        assert!(should_force(&metadata, &ConflictPolicy::Force));
+        assert!(should_force(
+            &metadata,
+            &ConflictPolicy::ForceIfOwnedBy("cluster".to_string())
+        ));
+        assert!(!should_force(
+            &metadata,
+            &ConflictPolicy::ForceIfOwnedBy("other".to_string())
+        ));
+        assert!(!should_force(
+            &ObjectMeta::default(),
+            &ConflictPolicy::ForceIfOwnedBy("cluster".to_string())
+        ));
+    }
+
+    #[test]
+    fn test_migrate_managed_fields() {
+        let managed_fields: Vec<ManagedFieldsEntry> = serde_yaml::from_str(indoc! {"
+            - manager: zookeeper-operator
+              operation: Apply
+              apiVersion: v1
+              fieldsType: FieldsV1
+              fieldsV1:
+                f:data:
+                  f:old: {}
+                  f:both: {}
+              time: '2021-06-01T12:00:00Z'
+            - manager: zookeeper.stackable.tech
+              operation: Apply
+              apiVersion: v1
+              fieldsType: FieldsV1
+              fieldsV1:
+                f:data:
+                  f:both: {}
+                  f:new: {}
+              time: '2021-07-01T12:00:00Z'
+            - manager: kubectl-edit
+              operation: Update
+              apiVersion: v1
+        "})
+        .unwrap();
+
+        let migrated = migrate_managed_fields(&managed_fields).unwrap();
+
+        assert_eq!(
+            migrated
+                .iter()
+                .map(|entry| entry.manager.as_deref().unwrap())
+                .collect::<Vec<_>>(),
+            vec![FIELD_MANAGER, "kubectl-edit"]
+        );
+        assert_eq!(
+            migrated[0].fields_v1.as_ref().unwrap().0,
+            json!({ "f:data": { "f:old": {}, "f:both": {}, "f:new": {} } })
+        );
+        assert_eq!(
+            migrated[0].time.as_ref().unwrap().0.to_rfc3339(),
+            "2021-07-01T12:00:00+00:00"
+        );
+        assert_eq!(migrate_managed_fields(&migrated), None);
+    }
+
+    fn service_account(owner_uid: Option<&str>, managers: &[&str]) -> ServiceAccount {
+        serde_json::from_value(json!({
+            "apiVersion": "v1",
+            "kind": "ServiceAccount",
+            "metadata": {
+                "name": "simple-server",
+                "namespace": test_support::NAMESPACE,
+                "labels": { "app": "zookeeper" },
+                "ownerReferences": owner_uid.map(|uid| vec![json!({
+                    "apiVersion": "zookeeper.stackable.tech/v1alpha1",
+                    "kind": "ZookeeperCluster",
+                    "name": "simple",
+                    "uid": uid,
+                    "controller": true
+                })]),
+                "managedFields": managers
+                    .iter()
+                    .map(|manager| json!({ "manager": manager, "operation": "Apply" }))
+                    .collect::<Vec<_>>()
+            }
+        }))
+        .unwrap()
+    }
+
+    #[rstest]
+    #[case::owned(Some("cluster"), ConflictPolicy::ForceIfOwnedBy("cluster".to_string()), true)]
+    #[case::owned_by_other(Some("other"), ConflictPolicy::ForceIfOwnedBy("cluster".to_string()), false)]
+    #[case::not_owned(None, ConflictPolicy::ForceIfOwnedBy("cluster".to_string()), false)]
+    #[case::forced(None, ConflictPolicy::Force, true)]
+    #[tokio::test]
+    async fn test_apply_ownership(
+        #[case] owner_uid: Option<&str>,
+        #[case] policy: ConflictPolicy,
+        #[case] applied: bool,
+    ) {
+        let server = FakeApiServer::new();
+        server.insert(&service_account(owner_uid, &[]));
+        let api: Api<ServiceAccount> = Api::namespaced(server.client(), test_support::NAMESPACE);
+        let mut object = service_account(Some("cluster"), &[]);
+        object
+            .metadata
+            .labels
+            .insert("app".to_string(), "other".to_string());
+
+        let result = apply(&api, &object, &policy).await;
+
+        let stored = server
+            .get::<ServiceAccount>(Some(test_support::NAMESPACE), "simple-server")
+            .unwrap();
+        if applied {
+            assert!(result.is_ok());
+            assert_eq!(stored.metadata.labels.get("app").unwrap(), "other");
+        } else {
+            assert!(matches!(result, Err(Error::ApplyConflict { .. })));
+            assert_eq!(stored.metadata.labels.get("app").unwrap(), "zookeeper");
+            assert_eq!(
+                stored.metadata.owner_references,
+                service_account(owner_uid, &[]).metadata.owner_references
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply_migrates_legacy_field_manager() {
+        let server = FakeApiServer::new();
+        server.insert(&service_account(
+            Some("cluster"),
+            &[LEGACY_FIELD_MANAGER, "kubectl-edit"],
+        ));
+        let api: Api<ServiceAccount> = Api::namespaced(server.client(), test_support::NAMESPACE);
+
+        apply(
+            &api,
+            &service_account(Some("cluster"), &[]),
+            &ConflictPolicy::ForceIfOwnedBy("cluster".to_string()),
+        )
+        .await
+        .unwrap();
+
+        let stored = server
+            .get::<ServiceAccount>(Some(test_support::NAMESPACE), "simple-server")
+            .unwrap();
+        assert_eq!(
+            stored
+                .metadata
+                .managed_fields
+                .iter()
+                .map(|entry| entry.manager.as_deref().unwrap())
+                .collect::<Vec<_>>(),
+            vec![FIELD_MANAGER, "kubectl-edit"]
+        );
+    }
+}
@@ -0,0 +1,322 @@
+//! The super user Secret and its use by the servers, see
+//! [`stackable_zookeeper_crd::authentication`], and the Secrets of the users of znodes, see
+//! [`stackable_zookeeper_crd::znode`].
+use crate::error::Error;
+
+use k8s_openapi::api::core::v1::{
+    EnvVar, EnvVarSource, Secret, SecretKeySelector, SecretVolumeSource, Volume, VolumeMount,
+};
+use k8s_openapi::ByteString;
+use kube::Api;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sha1::{Digest, Sha1};
+use stackable_operator::builder::ObjectMetaBuilder;
+use stackable_operator::client::Client;
+use stackable_operator::labels::{
+    build_common_labels_for_all_managed_resources, APP_MANAGED_BY_LABEL, APP_NAME_LABEL,
+};
+use stackable_zookeeper_crd::authentication::{
+    super_user_secret_name, ZookeeperAuthentication, JAAS_CONFIG_KEY, PASSWORD_KEY, SASL_DIR,
+    SUPER_DIGEST_KEY, USERNAME_KEY,
+};
+use stackable_zookeeper_crd::znode::{
+    user_secret_name, ZnodeUser, ZookeeperZnode, DIGEST_KEY, ZNODE_LABEL,
+};
+use stackable_zookeeper_crd::{ZookeeperCluster, APP_NAME, MANAGED_BY};
+use std::collections::BTreeMap;
+
+/// The environment variable holding the super user digest, it is referenced by the JVM flags.
+const SUPER_DIGEST_ENV: &str = "ZK_SUPER_DIGEST";
+
+pub const SASL_VOLUME_NAME: &str = "sasl";
+const PASSWORD_LENGTH: usize = 32;
+
+/// Builds the Secret with the super user credentials, a newly generated password, the JAAS
+/// configuration of the servers and the digest of the credentials.
+pub fn build_super_user_secret(
+    cluster: &ZookeeperCluster,
+    authentication: &ZookeeperAuthentication,
+) -> Result<Secret, Error> {
+    let username = authentication.super_user();
+    let password = generate_password();
+
+    let mut data = BTreeMap::new();
+    data.insert(USERNAME_KEY.to_string(), username.to_string());
+    data.insert(
+        JAAS_CONFIG_KEY.to_string(),
+        build_jaas_config(username, &password),
+    );
+    data.insert(
+        SUPER_DIGEST_KEY.to_string(),
+        build_super_digest(username, &password),
+    );
+    data.insert(PASSWORD_KEY.to_string(), password);
+
+    Ok(Secret {
+        metadata: ObjectMetaBuilder::new()
+            .name(super_user_secret_name(
+                cluster.metadata.name.as_deref().unwrap_or_default(),
+            ))
+            .namespace(cluster.metadata.namespace.as_deref().unwrap_or_default())
+            .with_labels(build_common_labels_for_all_managed_resources(
+                APP_NAME,
+                cluster.metadata.name.as_deref().unwrap_or_default(),
+            ))
+            .ownerreference_from_resource(cluster, Some(true), Some(true))?
+            .build()?,
+        data: data
+            .into_iter()
+            .map(|(key, value)| (key, ByteString(value.into_bytes())))
+            .collect(),
+        ..Secret::default()
+    })
+}
+
+/// Builds the Secret with the credentials of a user of a znode: the user name, the `password`
+/// (see [`generate_password`]) and the id of the user in the `digest` scheme.
+pub fn build_user_secret(
+    znode: &ZookeeperZnode,
+    user: &ZnodeUser,
+    password: String,
+) -> Result<Secret, Error> {
+    let znode_name = znode.metadata.name.as_deref().unwrap_or_default();
+
+    let mut labels = BTreeMap::new();
+    labels.insert(APP_NAME_LABEL.to_string(), APP_NAME.to_string());
+    labels.insert(APP_MANAGED_BY_LABEL.to_string(), MANAGED_BY.to_string());
+    labels.insert(ZNODE_LABEL.to_string(), znode_name.to_string());
+
+    let mut data = BTreeMap::new();
+    data.insert(USERNAME_KEY.to_string(), user.name.clone());
+    data.insert(
+        DIGEST_KEY.to_string(),
+        build_super_digest(&user.name, &password),
+    );
+    data.insert(PASSWORD_KEY.to_string(), password);
+
+    Ok(Secret {
+        metadata: ObjectMetaBuilder::new()
+            .name(user_secret_name(znode_name, &user.name))
+            .namespace(znode.metadata.namespace.as_deref().unwrap_or_default())
+            .with_labels(labels)
+            .ownerreference_from_resource(znode, Some(true), Some(true))?
+            .build()?,
+        data: data
+            .into_iter()
+            .map(|(key, value)| (key, ByteString(value.into_bytes())))
+            .collect(),
+        ..Secret::default()
+    })
+}
+
+pub fn generate_password() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(PASSWORD_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+/// Renders the JAAS configuration which lets the super user log in via SASL (DIGEST-MD5).
+fn build_jaas_config(username: &str, password: &str) -> String {
+    format!(
+        "Server {{\n  org.apache.zookeeper.server.auth.DigestLoginModule required\n  user_{}=\"{}\";\n}};\n",
+        username, password
+    )
+}
+
+/// Builds the digest of the credentials in the format of the `digest` authentication scheme:
+/// `<user>:<base64 encoded SHA-1 of "<user>:<password>">`.
+pub fn build_super_digest(username: &str, password: &str) -> String {
+    let hash = Sha1::digest(format!("{}:{}", username, password).as_bytes());
+    format!("{}:{}", username, base64::encode(hash))
+}
+
+/// Builds the volume containing the super user Secret and its mount.
+pub fn build_volume(cluster_name: &str) -> (Volume, VolumeMount) {
+    let volume = Volume {
+        name: SASL_VOLUME_NAME.to_string(),
+        secret: Some(SecretVolumeSource {
+            secret_name: Some(super_user_secret_name(cluster_name)),
+            ..SecretVolumeSource::default()
+        }),
+        ..Volume::default()
+    };
+    let volume_mount = VolumeMount {
+        name: SASL_VOLUME_NAME.to_string(),
+        mount_path: SASL_DIR.to_string(),
+        read_only: Some(true),
+        ..VolumeMount::default()
+    };
+    (volume, volume_mount)
+}
+
+/// Builds the environment variable with the super user digest. It must precede the variable
+/// containing the flags of [`build_jvm_flags`], otherwise Kubernetes does not expand the
+/// reference.
+pub fn build_super_digest_env_var(cluster_name: &str) -> EnvVar {
+    EnvVar {
+        name: SUPER_DIGEST_ENV.to_string(),
+        value_from: Some(EnvVarSource {
+            secret_key_ref: Some(SecretKeySelector {
+                name: Some(super_user_secret_name(cluster_name)),
+                key: SUPER_DIGEST_KEY.to_string(),
+                optional: Some(false),
+            }),
+            ..EnvVarSource::default()
+        }),
+        ..EnvVar::default()
+    }
+}
+
+/// Builds the JVM flags configuring the login and the super user of the servers.
+pub fn build_jvm_flags(authentication: &ZookeeperAuthentication) -> Vec<String> {
+    vec![
+        format!(
+            "-Djava.security.auth.login.config={}/{}",
+            SASL_DIR, JAAS_CONFIG_KEY
+        ),
+        format!("-Dzookeeper.superUser={}", authentication.super_user()),
+        format!(
+            "-Dzookeeper.DigestAuthenticationProvider.superDigest=$({})",
+            SUPER_DIGEST_ENV
+        ),
+    ]
+}
+
+/// Reads the super user credentials (user name and password) of a cluster.
+pub async fn read_super_user_credentials(
+    client: &Client,
+    namespace: &str,
+    cluster_name: &str,
+) -> Result<(String, String), Error> {
+    let secrets_api: Api<Secret> = client.get_namespaced_api(namespace);
+    let secret = secrets_api
+        .get(&super_user_secret_name(cluster_name))
+        .await?;
+    read_credentials(&secret)
+}
+
+/// Reads the credentials (user name and password) from a Secret of the super user or a user of a
+/// znode.
+pub fn read_credentials(secret: &Secret) -> Result<(String, String), Error> {
+    let value = |key: &str| {
+        secret
+            .data
+            .get(key)
+            .map(|value| String::from_utf8_lossy(&value.0).to_string())
+            .ok_or_else(|| {
+                Error::ReconcileError(format!(
+                    "The Secret [{}] does not contain the key [{}]",
+                    secret.metadata.name.as_deref().unwrap_or_default(),
+                    key
+                ))
+            })
+    };
+    Ok((value(USERNAME_KEY)?, value(PASSWORD_KEY)?))
+}
+
+/// Checks whether `secret` is the Secret with the credentials of the user `username` of `znode`,
+/// i.e. it is labeled with and owned by the znode and contains the credentials of this user.
+/// Other Secrets with the same name are never used, their credentials could belong to someone
+/// else.
+pub fn is_user_secret(secret: &Secret, znode: &ZookeeperZnode, username: &str) -> bool {
+    let owned = znode.metadata.uid.as_deref().map_or(false, |uid| {
+        secret
+            .metadata
+            .owner_references
+            .iter()
+            .any(|owner| owner.uid == uid)
+    });
+    owned
+        && secret.metadata.labels.get(ZNODE_LABEL) == znode.metadata.name.as_ref()
+        && read_credentials(secret).map_or(false, |(name, _)| name == username)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_build_super_digest() {
+        // as generated by org.apache.zookeeper.server.auth.DigestAuthenticationProvider
+        assert_eq!(
+            build_super_digest("super", "secret"),
+            "super:lK75jTNcA+U9vtVEw5vB51mj/w4="
+        );
+    }
+
+    #[test]
+    fn test_read_credentials() {
+        let znode: ZookeeperZnode = serde_yaml::from_str(indoc! {"
+            apiVersion: zookeeper.stackable.tech/v1alpha1
+            kind: ZookeeperZnode
+            metadata:
+              name: kafka
+              namespace: apps
+              uid: c2e8a6f4-8c3a-4c5e-9a1e-0d4f7a3c2b1d
+            spec:
+              clusterRef:
+                name: simple
+        "})
+        .unwrap();
+        let user = ZnodeUser {
+            name: "broker".to_string(),
+            permissions: "cdrw".to_string(),
+        };
+
+        let secret = build_user_secret(&znode, &user, generate_password()).unwrap();
+        let (username, password) = read_credentials(&secret).unwrap();
+
+        assert_eq!(secret.metadata.name.as_deref(), Some("kafka.broker"));
+        assert_eq!(secret.metadata.labels.get(ZNODE_LABEL).unwrap(), "kafka");
+        assert_eq!(username, "broker");
+        assert_eq!(password.len(), PASSWORD_LENGTH);
+        assert_eq!(
+            secret.data.get(DIGEST_KEY).unwrap().0,
+            build_super_digest(&username, &password).into_bytes()
+        );
+    }
+
+    #[test]
+    fn test_is_user_secret() {
+        let znode: ZookeeperZnode = serde_yaml::from_str(indoc! {"
+            apiVersion: zookeeper.stackable.tech/v1alpha1
+            kind: ZookeeperZnode
+            metadata:
+              name: kafka
+              namespace: apps
+              uid: c2e8a6f4-8c3a-4c5e-9a1e-0d4f7a3c2b1d
+            spec:
+              clusterRef:
+                name: simple
+        "})
+        .unwrap();
+        let user = ZnodeUser {
+            name: "broker".to_string(),
+            permissions: "cdrw".to_string(),
+        };
+        let secret = build_user_secret(&znode, &user, generate_password()).unwrap();
+
+        assert!(is_user_secret(&secret, &znode, "broker"));
+        assert!(!is_user_secret(&secret, &znode, "admin"));
+
+        let mut other_znode = znode.clone();
+        other_znode.metadata.uid = Some("5f0c1a8e-3b7d-4e2a-8c9f-1d6b4a7e2c3f".to_string());
+        assert!(!is_user_secret(&secret, &other_znode, "broker"));
+
+        let mut unlabeled = secret.clone();
+        unlabeled.metadata.labels.remove(ZNODE_LABEL);
+        assert!(!is_user_secret(&unlabeled, &znode, "broker"));
+    }
+
+    #[test]
+    fn test_build_jaas_config() {
+        assert_eq!(
+            build_jaas_config("super", "secret"),
+            "Server {\n  org.apache.zookeeper.server.auth.DigestLoginModule required\n  user_super=\"secret\";\n};\n"
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Exponential backoff for retrying failed reconciliations of ZookeeperClusters.
+//!
+//! Retrying every failure after the same interval either hammers the API server (and the servers)
+//! while a problem persists or delays the recovery from short hiccups. Instead, the interval
+//! starts at the configured requeue interval and doubles with every consecutive failure of the
+//! same cluster up to a cap. The interval is randomized a bit, so clusters failing for the same
+//! reason (e.g. an unavailable API server) are not retried all at once.
+//!
+//! Conflicts (an object was changed concurrently) usually resolve as soon as the reconciliation
+//! runs again with the current objects, so the first one is retried right away.
+use crate::object_ref::ObjectRef;
+
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Counts the consecutive failures of every cluster.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    failures: Arc<Mutex<BTreeMap<ObjectRef, u32>>>,
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Backoff {
+        Backoff {
+            base,
+            max: max.max(base),
+            failures: Arc::default(),
+        }
+    }
+
+    /// Records a failed reconciliation of `key` and returns the interval after which it is
+    /// retried.
+    pub fn failed(&self, key: &ObjectRef) -> Duration {
+        let mut failures = self.failures.lock().unwrap();
+        let attempts = failures.entry(key.clone()).or_default();
+        *attempts = attempts.saturating_add(1);
+        let interval = backoff_interval(self.base, self.max, *attempts);
+        with_jitter(interval, self.base, rand::thread_rng().gen_range(0.0..=1.0))
+    }
+
+    /// Records a reconciliation of `key` which failed because of a conflict and returns the
+    /// interval after which it is retried: right away after the first consecutive failure,
+    /// afterwards with the same backoff as [`Backoff::failed`], so a persisting conflict does not
+    /// retry in a tight loop.
+    pub fn conflicted(&self, key: &ObjectRef) -> Duration {
+        let first = !self.failures.lock().unwrap().contains_key(key);
+        let interval = self.failed(key);
+        if first {
+            Duration::from_secs(0)
+        } else {
+            interval
+        }
+    }
+
+    /// Forgets the failures of `key` after a successful reconciliation or its deletion.
+    pub fn reset(&self, key: &ObjectRef) {
+        self.failures.lock().unwrap().remove(key);
+    }
+}
+
+/// Returns `base` doubled for every failure after the first one, capped at `max`.
+fn backoff_interval(base: Duration, max: Duration, attempts: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempts.max(1) - 1);
+    base.checked_mul(factor).unwrap_or(max).min(max)
+}
+
+/// Shortens `interval` by up to a quarter depending on `random` (between 0 and 1), but not below
+/// `min`.
+fn with_jitter(interval: Duration, min: Duration, random: f64) -> Duration {
+    interval.mul_f64(1.0 - random / 4.0).max(min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(0, 10)]
+    #[case(1, 10)]
+    #[case(2, 20)]
+    #[case(4, 80)]
+    #[case(6, 300)]
+    #[case(100, 300)]
+    fn test_backoff_interval(#[case] attempts: u32, #[case] expected_seconds: u64) {
+        assert_eq!(
+            backoff_interval(Duration::from_secs(10), Duration::from_secs(300), attempts),
+            Duration::from_secs(expected_seconds)
+        );
+    }
+
+    #[rstest]
+    #[case(80, 0.0, 80)]
+    #[case(80, 1.0, 60)]
+    #[case(10, 1.0, 10)]
+    fn test_with_jitter(
+        #[case] interval_seconds: u64,
+        #[case] random: f64,
+        #[case] expected_seconds: u64,
+    ) {
+        assert_eq!(
+            with_jitter(
+                Duration::from_secs(interval_seconds),
+                Duration::from_secs(10),
+                random
+            ),
+            Duration::from_secs(expected_seconds)
+        );
+    }
+
+    #[test]
+    fn test_reset() {
+        let backoff = Backoff::new(Duration::from_secs(10), Duration::from_secs(300));
+        let cluster = ObjectRef::new("default", "simple");
+        let other = ObjectRef::new("default", "other");
+
+        backoff.failed(&cluster);
+        assert!(backoff.failed(&cluster) >= Duration::from_secs(15));
+        assert_eq!(backoff.failed(&other), Duration::from_secs(10));
+
+        backoff.reset(&cluster);
+        assert_eq!(backoff.failed(&cluster), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_conflicted() {
+        let backoff = Backoff::new(Duration::from_secs(10), Duration::from_secs(300));
+        let cluster = ObjectRef::new("default", "simple");
+
+        assert_eq!(backoff.conflicted(&cluster), Duration::from_secs(0));
+        assert!(backoff.conflicted(&cluster) >= Duration::from_secs(15));
+
+        backoff.reset(&cluster);
+        backoff.failed(&cluster);
+        assert!(backoff.conflicted(&cluster) >= Duration::from_secs(15));
+    }
+}
@@ -0,0 +1,571 @@
+//! Scheduled backups of the data directory of a cluster and their restore, see
+//! [`stackable_zookeeper_crd::backup`].
+//!
+//! A backup consists of the latest snapshot in the data directory of the leader and the
+//! transaction logs which bring it up to date. `backup.sh` lists and reads them in the server
+//! container of the leader (see [`crate::pod_exec`]), so the files are copied as ZooKeeper wrote
+//! them, wherever the data directory is stored. They are uploaded next to a manifest listing
+//! them, see [`BackupManifest`]. Snapshots are fuzzy, the servers replay the transaction logs on
+//! top of them when they start, so a restored ensemble gets the state of the last complete
+//! transaction in the logs.
+//!
+//! A restore stages the files in the data directories before the servers of a new cluster start:
+//! the operator passes presigned URLs of the files in a Secret (see [`build_restore_secret`]) to
+//! the entrypoint of the servers (see [`crate::entrypoint`]), which downloads them into the empty
+//! data directory. The data directory is not necessarily on a volume another container could
+//! mount, so the files are not staged by an init container.
+use crate::cron::CronSchedule;
+use crate::entrypoint;
+use crate::error::Error;
+use crate::pod_exec::PodExecutor;
+use crate::s3::S3Client;
+
+use k8s_openapi::api::core::v1::{Pod, Secret};
+use k8s_openapi::chrono::{DateTime, Utc};
+use k8s_openapi::ByteString;
+use kube::{Api, ResourceExt};
+use serde::{Deserialize, Serialize};
+use stackable_operator::builder::ObjectMetaBuilder;
+use stackable_operator::labels::build_common_labels_for_all_managed_resources;
+use stackable_zookeeper_crd::backup::restore_secret_name;
+use stackable_zookeeper_crd::{ZookeeperCluster, APP_NAME};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// The condition which tells whether the last scheduled backup succeeded.
+pub const BACKUP_SUCCEEDED_CONDITION: &str = "BackupSucceeded";
+/// The condition which tells whether `spec.restore` was restored.
+pub const RESTORE_SUCCEEDED_CONDITION: &str = "RestoreSucceeded";
+/// The version of the format of the backup manifests, the first version contained the znodes.
+pub const MANIFEST_FORMAT_VERSION: u32 = 2;
+/// How long the URLs of a restore are valid, the maximum of AWS.
+const RESTORE_URL_EXPIRES_SECONDS: u32 = 7 * 24 * 60 * 60;
+
+const BACKUP_SCRIPT: &str = include_str!("backup.sh");
+
+/// How long to wait if the schedule never matches (again).
+const NEVER_REQUEUE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The object of a backup, the files are stored next to it (see [`file_key`]).
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupManifest {
+    pub format_version: u32,
+    /// The cluster as `<namespace>/<name>`.
+    pub cluster: String,
+    /// When the backup was started (RFC 3339).
+    pub created_at: String,
+    /// The pod the files were copied from.
+    pub pod: String,
+    /// The snapshot first, followed by the transaction logs.
+    pub files: Vec<BackupFile>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupFile {
+    /// The name of the file in the `version-2` directory of the data directory, e.g.
+    /// `snapshot.100000002`.
+    pub name: String,
+    pub bytes: u64,
+}
+
+impl BackupManifest {
+    /// Starts the manifest of the backup of `cluster` (`<namespace>/<name>`) from `pod`.
+    pub fn new(cluster: &str, created_at: &DateTime<Utc>, pod: &Pod) -> BackupManifest {
+        BackupManifest {
+            format_version: MANIFEST_FORMAT_VERSION,
+            cluster: cluster.to_string(),
+            created_at: created_at.to_rfc3339(),
+            pod: pod.name(),
+            files: vec![],
+        }
+    }
+
+    /// The name of the snapshot of the backup.
+    pub fn snapshot(&self) -> Option<&str> {
+        self.files
+            .iter()
+            .map(|file| file.name.as_str())
+            .find(|name| name.starts_with("snapshot."))
+    }
+
+    /// The size of all files of the backup.
+    pub fn bytes(&self) -> u64 {
+        self.files.iter().map(|file| file.bytes).sum()
+    }
+}
+
+/// Returns the problems of a backup schedule.
+pub fn validate_schedule(schedule: &str) -> Vec<String> {
+    match schedule.parse::<CronSchedule>() {
+        Ok(cron_schedule) if cron_schedule.next_after(&Utc::now()).is_none() => {
+            vec![format!("backup.schedule: [{}] never matches", schedule)]
+        }
+        Ok(_) => vec![],
+        Err(problem) => vec![format!("backup.schedule: {}", problem)],
+    }
+}
+
+/// Returns how long to wait until the next backup is due, `None` if it is due now.
+pub fn next_backup_in(
+    schedule: &CronSchedule,
+    last_schedule: &DateTime<Utc>,
+    now: &DateTime<Utc>,
+) -> Option<Duration> {
+    match schedule.next_after(last_schedule) {
+        Some(next_backup) => (next_backup - *now)
+            .to_std()
+            .ok()
+            .filter(|wait| *wait > Duration::default()),
+        None => Some(NEVER_REQUEUE_INTERVAL),
+    }
+}
+
+/// Returns the time part of the object key of a backup started at `time`.
+pub fn object_time(time: &DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Removes all but the newest `retained` keys from `backups` (the newest one last) and returns the
+/// removed keys.
+pub fn expire(backups: &mut Vec<String>, retained: u32) -> Vec<String> {
+    let expired = backups.len().saturating_sub(retained as usize);
+    backups.drain(..expired).collect()
+}
+
+/// Returns the key of the file `file` of the backup `key`, e.g.
+/// `production/default/simple/20210901T030000Z/snapshot.100000002`.
+pub fn file_key(key: &str, file: &str) -> String {
+    format!("{}/{}", key.strip_suffix(".json").unwrap_or(key), file)
+}
+
+/// Builds the command listing the files of a backup in `data_dir` or, with `file`, printing one
+/// of them.
+pub fn build_command(data_dir: &str, file: Option<&str>) -> Vec<String> {
+    vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        BACKUP_SCRIPT.to_string(),
+        "backup.sh".to_string(),
+        data_dir.to_string(),
+    ]
+    .into_iter()
+    .chain(file.map(str::to_string))
+    .collect()
+}
+
+/// Copies the files of a backup from the `data_dir` of the server `pod` to the bucket and adds
+/// them to the `manifest`, which is uploaded as `key` at last.
+pub async fn upload(
+    executor: &dyn PodExecutor,
+    pods_api: Api<Pod>,
+    pod: &Pod,
+    data_dir: &str,
+    s3_client: &S3Client,
+    key: &str,
+    mut manifest: BackupManifest,
+) -> Result<BackupManifest, Error> {
+    for name in list_files(executor, pods_api.clone(), pod, data_dir).await? {
+        let content = read_file(executor, pods_api.clone(), pod, data_dir, &name).await?;
+        let bytes = content.len() as u64;
+        s3_client.put_object(&file_key(key, &name), content).await?;
+        manifest.files.push(BackupFile { name, bytes });
+    }
+    s3_client
+        .put_object(key, serde_json::to_vec(&manifest)?)
+        .await?;
+    Ok(manifest)
+}
+
+/// Deletes the backup `key` with its files. A backup without a readable manifest is deleted
+/// anyway, its files (if any) are left behind.
+pub async fn delete(s3_client: &S3Client, key: &str) -> Result<(), Error> {
+    let manifest = s3_client
+        .get_object(key)
+        .await
+        .ok()
+        .and_then(|document| serde_json::from_slice::<BackupManifest>(&document).ok());
+    if let Some(manifest) = manifest {
+        for file in &manifest.files {
+            s3_client.delete_object(&file_key(key, &file.name)).await?;
+        }
+    }
+    s3_client.delete_object(key).await
+}
+
+/// Lists the files of a backup in the `data_dir` of `pod`, the snapshot first.
+async fn list_files(
+    executor: &dyn PodExecutor,
+    pods_api: Api<Pod>,
+    pod: &Pod,
+    data_dir: &str,
+) -> Result<Vec<String>, Error> {
+    let output = executor
+        .exec(
+            pods_api,
+            &pod.name(),
+            APP_NAME,
+            build_command(data_dir, None),
+        )
+        .await?;
+    let mut lines = output
+        .stdout
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    if !output.success {
+        return Err(exec_error(
+            pod,
+            lines
+                .last()
+                .unwrap_or("Listing the files of the backup failed"),
+        ));
+    }
+    Ok(lines.map(str::to_string).collect())
+}
+
+/// Reads the file `name` of a backup in the `data_dir` of `pod`.
+async fn read_file(
+    executor: &dyn PodExecutor,
+    pods_api: Api<Pod>,
+    pod: &Pod,
+    data_dir: &str,
+    name: &str,
+) -> Result<Vec<u8>, Error> {
+    let output = executor
+        .exec(
+            pods_api,
+            &pod.name(),
+            APP_NAME,
+            build_command(data_dir, Some(name)),
+        )
+        .await?;
+    if !output.success {
+        return Err(exec_error(pod, &format!("Reading [{}] failed", name)));
+    }
+    // base64 wraps the lines
+    let encoded = output.stdout.split_whitespace().collect::<String>();
+    base64::decode(&encoded)
+        .map_err(|err| exec_error(pod, &format!("[{}] was not read completely: {}", name, err)))
+}
+
+fn exec_error(pod: &Pod, message: &str) -> Error {
+    Error::PodExecError {
+        pod: pod.name(),
+        container: APP_NAME.to_string(),
+        message: message.to_string(),
+    }
+}
+
+/// Parses the manifest of a backup, only the current format version is supported.
+pub fn parse_manifest(document: &[u8]) -> Result<BackupManifest, Error> {
+    let manifest: BackupManifest = serde_json::from_slice(document)?;
+    if manifest.format_version != MANIFEST_FORMAT_VERSION {
+        return Err(Error::ReconcileError(format!(
+            "The backup has the format version [{}], only [{}] is supported",
+            manifest.format_version, MANIFEST_FORMAT_VERSION
+        )));
+    }
+    if manifest.snapshot().is_none() {
+        return Err(Error::ReconcileError(
+            "The backup does not contain a snapshot".to_string(),
+        ));
+    }
+    // the entrypoint writes the files into the data directory under these names
+    if let Some(file) = manifest.files.iter().find(|file| {
+        !(file.name.starts_with("snapshot.") || file.name.starts_with("log."))
+            || file.name.contains('/')
+            || file.name.contains(char::is_whitespace)
+    }) {
+        return Err(Error::ReconcileError(format!(
+            "The backup contains the file [{}] which is neither a snapshot nor a transaction log",
+            file.name
+        )));
+    }
+    Ok(manifest)
+}
+
+/// Builds the list of the files of the backup `key` which the entrypoint of the servers
+/// downloads into an empty data directory: one line per file with its name and a presigned URL
+/// (see [`S3Client::presigned_url`]). The URLs are signed at `signed_at`, so the list stays the
+/// same during a restore.
+pub fn build_restore_files(
+    manifest: &BackupManifest,
+    key: &str,
+    s3_client: &S3Client,
+    signed_at: &DateTime<Utc>,
+) -> Result<String, Error> {
+    let mut files = String::new();
+    for file in &manifest.files {
+        let url = s3_client.presigned_url(
+            &file_key(key, &file.name),
+            signed_at,
+            RESTORE_URL_EXPIRES_SECONDS,
+        )?;
+        files.push_str(&format!("{} {}\n", file.name, url));
+    }
+    Ok(files)
+}
+
+/// Builds the Secret holding the [`build_restore_files`] for the servers of `cluster`.
+pub fn build_restore_secret(cluster: &ZookeeperCluster, files: &str) -> Result<Secret, Error> {
+    let mut data = BTreeMap::new();
+    data.insert(
+        entrypoint::RESTORE_FILES_KEY.to_string(),
+        ByteString(files.as_bytes().to_vec()),
+    );
+    Ok(Secret {
+        metadata: ObjectMetaBuilder::new()
+            .name(restore_secret_name(&cluster.name()))
+            .namespace(&cluster.namespace().unwrap_or_default())
+            .with_labels(build_common_labels_for_all_managed_resources(
+                APP_NAME,
+                &cluster.name(),
+            ))
+            .ownerreference_from_resource(cluster, Some(true), Some(true))?
+            .build()?,
+        data,
+        ..Secret::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pod_exec::ExecOutput;
+    use crate::s3::S3Credentials;
+    use crate::test_support::{self, FakeApiServer, FakePodExecutor};
+    use k8s_openapi::chrono::Duration as ChronoDuration;
+    use serde_json::json;
+    use stackable_zookeeper_crd::backup::S3Bucket;
+
+    fn time(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_next_backup_in() {
+        let schedule: CronSchedule = "0 3 * * *".parse().unwrap();
+        let last_schedule = time("2021-09-01T03:00:00Z");
+
+        assert_eq!(
+            next_backup_in(&schedule, &last_schedule, &time("2021-09-01T12:00:00Z")),
+            Some(Duration::from_secs(15 * 60 * 60))
+        );
+        assert_eq!(
+            next_backup_in(&schedule, &last_schedule, &time("2021-09-02T03:00:00Z")),
+            None
+        );
+        // a missed backup is due right away
+        assert_eq!(
+            next_backup_in(
+                &schedule,
+                &last_schedule,
+                &(time("2021-09-05T12:00:00Z") + ChronoDuration::seconds(1))
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_validate_schedule() {
+        assert!(validate_schedule("0 3 * * *").is_empty());
+        assert_eq!(
+            validate_schedule("0 0 31 2 *"),
+            vec!["backup.schedule: [0 0 31 2 *] never matches"]
+        );
+        assert_eq!(validate_schedule("daily").len(), 1);
+    }
+
+    #[test]
+    fn test_expire() {
+        let mut backups = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+
+        assert_eq!(expire(&mut backups, 5), Vec::<String>::new());
+        assert_eq!(expire(&mut backups, 2), vec!["a"]);
+        assert_eq!(backups, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_object_time() {
+        assert_eq!(
+            object_time(&time("2021-09-01T12:00:00Z")),
+            "20210901T120000Z"
+        );
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = parse_manifest(
+            br#"{"formatVersion":2,"cluster":"default/simple","createdAt":"2021-09-01T03:00:00+00:00","pod":"simple-server-default-1","files":[{"name":"snapshot.20000000a","bytes":100},{"name":"log.200000001","bytes":20}]}"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.snapshot(), Some("snapshot.20000000a"));
+
+        // the znodes of the first format version
+        parse_manifest(br#"{"formatVersion":1,"cluster":"","createdAt":"","znodes":[]}"#)
+            .unwrap_err();
+        parse_manifest(br#"{"formatVersion":2,"cluster":"","createdAt":"","pod":"","files":[]}"#)
+            .unwrap_err();
+        parse_manifest(
+            br#"{"formatVersion":2,"cluster":"","createdAt":"","pod":"","files":[{"name":"snapshot.1","bytes":1},{"name":"log.1/../../myid","bytes":1}]}"#,
+        )
+        .unwrap_err();
+        parse_manifest(b"not json").unwrap_err();
+    }
+
+    fn leader() -> Pod {
+        let cluster = test_support::cluster("simple", json!({}));
+        test_support::server_pod(&cluster, "default", 1, "node-1")
+    }
+
+    #[tokio::test]
+    async fn test_list_files() {
+        let server = FakeApiServer::new();
+        let executor = FakePodExecutor::new(ExecOutput {
+            success: true,
+            stdout: "snapshot.20000000a\nlog.200000001\nlog.200000050\n".to_string(),
+        });
+
+        let files = list_files(
+            executor.executor().as_ref(),
+            Api::namespaced(server.client(), test_support::NAMESPACE),
+            &leader(),
+            "/tmp/zookeeper",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            files,
+            vec!["snapshot.20000000a", "log.200000001", "log.200000050"]
+        );
+        let execs = executor.execs();
+        assert_eq!(execs[0].pod, "simple-server-default-1");
+        assert_eq!(execs[0].container, "zookeeper");
+        assert_eq!(&execs[0].command[3..], &["backup.sh", "/tmp/zookeeper"]);
+    }
+
+    #[tokio::test]
+    async fn test_list_files_without_snapshot() {
+        let server = FakeApiServer::new();
+        let executor = FakePodExecutor::new(ExecOutput {
+            success: false,
+            stdout: "No snapshot found in /tmp/zookeeper/version-2\n".to_string(),
+        });
+
+        let err = list_files(
+            executor.executor().as_ref(),
+            Api::namespaced(server.client(), test_support::NAMESPACE),
+            &leader(),
+            "/tmp/zookeeper",
+        )
+        .await
+        .unwrap_err();
+
+        assert!(err
+            .to_string()
+            .ends_with("No snapshot found in /tmp/zookeeper/version-2"));
+    }
+
+    #[tokio::test]
+    async fn test_read_file() {
+        let server = FakeApiServer::new();
+        // wrapped like the output of base64
+        let executor = FakePodExecutor::new(ExecOutput {
+            success: true,
+            stdout: "c25hcHNo\nb3Q=\n".to_string(),
+        });
+
+        let content = read_file(
+            executor.executor().as_ref(),
+            Api::namespaced(server.client(), test_support::NAMESPACE),
+            &leader(),
+            "/tmp/zookeeper",
+            "snapshot.20000000a",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(content, b"snapshot");
+        assert_eq!(
+            &executor.execs()[0].command[4..],
+            &["/tmp/zookeeper", "snapshot.20000000a"]
+        );
+    }
+
+    #[test]
+    fn test_manifest() {
+        let mut manifest =
+            BackupManifest::new("default/simple", &time("2021-09-01T03:00:00Z"), &leader());
+        manifest.files = vec![
+            BackupFile {
+                name: "snapshot.20000000a".to_string(),
+                bytes: 100,
+            },
+            BackupFile {
+                name: "log.200000001".to_string(),
+                bytes: 20,
+            },
+        ];
+
+        assert_eq!(manifest.snapshot(), Some("snapshot.20000000a"));
+        assert_eq!(manifest.bytes(), 120);
+        assert_eq!(
+            file_key(
+                "production/default/simple/20210901T030000Z.json",
+                "snapshot.20000000a"
+            ),
+            "production/default/simple/20210901T030000Z/snapshot.20000000a"
+        );
+    }
+
+    #[test]
+    fn test_build_restore_files() {
+        let bucket = S3Bucket {
+            bucket: "backups".to_string(),
+            endpoint: Some("https://minio.example.com/".to_string()),
+            credentials_secret: "s3-credentials".to_string(),
+            ..S3Bucket::default()
+        };
+        let s3_client = S3Client::new(
+            &bucket,
+            S3Credentials {
+                access_key_id: "access".to_string(),
+                secret_access_key: "secret".to_string(),
+            },
+        );
+        let mut manifest =
+            BackupManifest::new("default/simple", &time("2021-09-01T03:00:00Z"), &leader());
+        manifest.files = vec![
+            BackupFile {
+                name: "snapshot.20000000a".to_string(),
+                bytes: 100,
+            },
+            BackupFile {
+                name: "log.200000001".to_string(),
+                bytes: 20,
+            },
+        ];
+        let key = "default/simple/20210901T030000Z.json";
+        let signed_at = time("2021-09-02T10:00:00Z");
+
+        let files = build_restore_files(&manifest, key, &s3_client, &signed_at).unwrap();
+
+        let lines = files.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(
+            "snapshot.20000000a https://minio.example.com/backups/default/simple/20210901T030000Z/snapshot.20000000a?X-Amz-Algorithm=AWS4-HMAC-SHA256&"
+        ));
+        assert!(lines[1].starts_with("log.200000001 https://"));
+        assert!(lines[1].contains("&X-Amz-Expires=604800&"));
+        // signed at the start of the restore, so the Secret does not change
+        assert_eq!(
+            build_restore_files(&manifest, key, &s3_client, &signed_at).unwrap(),
+            files
+        );
+    }
+}
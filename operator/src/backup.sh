@@ -0,0 +1,61 @@
+#!/usr/bin/env sh
+# Reads the files of a backup from the data directory of a ZooKeeper server, run by the
+# zookeeper-operator in the server container of the leader.
+#
+# Usage: backup.sh <data directory>          lists the files of the backup, one per line
+#        backup.sh <data directory> <file>   prints the content of one of them base64 encoded
+#
+# A backup consists of the latest snapshot and the transaction logs which bring it up to date: the
+# log containing the zxid of the snapshot (the latest one starting before it) and all later ones.
+# If the listing fails, the last line of the output says why. Nothing is written to the
+# filesystem, so the script works with a read-only root filesystem.
+set -eu
+
+DATA_DIR="$1"
+VERSION_DIR="$DATA_DIR/version-2"
+
+# the zxid in the name of a snapshot or log file, e.g. snapshot.100000002
+zxid() {
+  printf '%d' "0x${1##*.}"
+}
+
+if [ $# -gt 1 ]; then
+  case "$2" in
+    */*) ;;
+    snapshot.* | log.*) exec base64 "$VERSION_DIR/$2" ;;
+  esac
+  echo "[$2] is not a file of a backup"
+  exit 1
+fi
+
+snapshot=""
+snapshot_zxid=-1
+for file in "$VERSION_DIR"/snapshot.*; do
+  [ -e "$file" ] || continue
+  file_zxid="$(zxid "$file")"
+  if [ "$file_zxid" -gt "$snapshot_zxid" ]; then
+    snapshot="$file"
+    snapshot_zxid="$file_zxid"
+  fi
+done
+if [ -z "$snapshot" ]; then
+  echo "No snapshot found in $VERSION_DIR"
+  exit 1
+fi
+
+first_log_zxid=-1
+for file in "$VERSION_DIR"/log.*; do
+  [ -e "$file" ] || continue
+  file_zxid="$(zxid "$file")"
+  if [ "$file_zxid" -le "$snapshot_zxid" ] && [ "$file_zxid" -gt "$first_log_zxid" ]; then
+    first_log_zxid="$file_zxid"
+  fi
+done
+
+echo "${snapshot##*/}"
+for file in "$VERSION_DIR"/log.*; do
+  [ -e "$file" ] || continue
+  if [ "$(zxid "$file")" -ge "$first_log_zxid" ]; then
+    echo "${file##*/}"
+  fi
+done
@@ -0,0 +1,586 @@
+//! Controller for `ZookeeperBenchmark` resources.
+//!
+//! For every ZookeeperBenchmark a Job is created which runs the configured number of clients
+//! concurrently against the referenced ZookeeperCluster. The clients run a wrapper script around
+//! `zk-latencies.py` (rendered into a ConfigMap of the same name as the Job) which reports the
+//! summed up operations of all rounds in the termination message of its container. Once all
+//! clients succeeded, the results are summarized in the status and, if requested, in a results
+//! ConfigMap. The Job and the ConfigMaps are owned by the ZookeeperBenchmark and deleted with it.
+use crate::error::Error;
+use crate::watch_namespace::scoped_api;
+
+use async_trait::async_trait;
+use k8s_openapi::api::batch::v1::{Job, JobSpec};
+use k8s_openapi::api::core::v1::{
+    ConfigMap, ConfigMapVolumeSource, Container, EnvVar, Pod, PodSpec, PodTemplateSpec, Volume,
+    VolumeMount,
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use k8s_openapi::chrono::Utc;
+use kube::api::ListParams;
+use kube::Api;
+use serde_json::json;
+use stackable_operator::builder::ObjectMetaBuilder;
+use stackable_operator::client::Client;
+use stackable_operator::configmap;
+use stackable_operator::controller::Controller;
+use stackable_operator::controller::{ControllerStrategy, ReconciliationState};
+use stackable_operator::error::OperatorResult;
+use stackable_operator::labels::{APP_MANAGED_BY_LABEL, APP_NAME_LABEL};
+use stackable_operator::reconcile::{
+    ReconcileFunctionAction, ReconcileResult, ReconciliationContext,
+};
+use stackable_zookeeper_crd::benchmark::{
+    BenchmarkPhase, BenchmarkResults, OperationLatency, ZookeeperBenchmark, BENCHMARK_LABEL,
+    RESULTS_KEY,
+};
+use stackable_zookeeper_crd::util::{get_zk_connection_info, ZookeeperReference};
+use stackable_zookeeper_crd::{APP_NAME, MANAGED_BY};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// The name of the wrapper script in the script ConfigMap.
+const SCRIPT_FILE: &str = "benchmark.sh";
+const SCRIPT_DIR: &str = "/benchmark";
+const BENCHMARK_SCRIPT: &str = include_str!("benchmark.sh");
+
+/// The operations reported by the clients, in the order they are shown in the status.
+const OPERATIONS: [&str; 4] = ["create", "set", "get", "delete"];
+
+/// How long the clients may take in addition to the configured duration (e.g. for the last
+/// round) before the Job is failed.
+const DEADLINE_MARGIN_SECONDS: u32 = 600;
+
+type BenchmarkReconcileResult = ReconcileResult<Error>;
+
+/// The operations a single client reported in its termination message.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct ClientResults {
+    rounds: u64,
+    /// The number of operations and their total duration in milliseconds by operation.
+    operations: BTreeMap<String, (u64, u64)>,
+}
+
+struct BenchmarkState {
+    context: ReconciliationContext<ZookeeperBenchmark>,
+}
+
+impl BenchmarkState {
+    fn job_name(&self) -> Option<String> {
+        self.context
+            .resource
+            .status
+            .as_ref()
+            .and_then(|status| status.job_name.clone())
+    }
+
+    /// Records the outcome of the benchmark, after which it is not reconciled anymore.
+    async fn finish(
+        &mut self,
+        phase: BenchmarkPhase,
+        message: Option<String>,
+        results: Option<BenchmarkResults>,
+    ) -> BenchmarkReconcileResult {
+        match &message {
+            Some(message) => warn!(
+                "ZookeeperBenchmark {}: Benchmark failed: {}",
+                self.context.log_name(),
+                message
+            ),
+            None => info!(
+                "ZookeeperBenchmark {}: Benchmark succeeded",
+                self.context.log_name()
+            ),
+        }
+        self.context.resource = self
+            .context
+            .client
+            .merge_patch_status(
+                &self.context.resource,
+                &json!({
+                    "phase": phase,
+                    "message": message,
+                    "completionTime": Utc::now().to_rfc3339(),
+                    "results": results,
+                }),
+            )
+            .await?;
+        Ok(ReconcileFunctionAction::Done)
+    }
+
+    /// Stops the reconciliation of benchmarks which already finished.
+    async fn check_finished(&self) -> BenchmarkReconcileResult {
+        let finished = self
+            .context
+            .resource
+            .status
+            .as_ref()
+            .map_or(false, |status| status.is_finished());
+        if finished {
+            Ok(ReconcileFunctionAction::Done)
+        } else {
+            Ok(ReconcileFunctionAction::Continue)
+        }
+    }
+
+    async fn validate_spec(&mut self) -> BenchmarkReconcileResult {
+        let problems = self.context.resource.spec.validate();
+        if problems.is_empty() {
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+        self.finish(
+            BenchmarkPhase::Failed,
+            Some(format!("Invalid spec: {}", problems.join(", "))),
+            None,
+        )
+        .await
+    }
+
+    /// Creates the script ConfigMap and the Job running the clients once.
+    async fn create_job(&mut self) -> BenchmarkReconcileResult {
+        if self.job_name().is_some() {
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+
+        let benchmark = &self.context.resource;
+        let reference = ZookeeperReference {
+            namespace: benchmark
+                .spec
+                .cluster_ref
+                .namespace
+                .clone()
+                .unwrap_or_else(|| self.context.namespace()),
+            name: benchmark.spec.cluster_ref.name.clone(),
+            chroot: None,
+        };
+        let connection_info = get_zk_connection_info(&self.context.client, &reference).await?;
+
+        let job_name = self.context.name();
+        let mut data = BTreeMap::new();
+        data.insert(SCRIPT_FILE.to_string(), BENCHMARK_SCRIPT.to_string());
+        let config_map = configmap::build_config_map(
+            benchmark,
+            &job_name,
+            &self.context.namespace(),
+            labels(),
+            data,
+        )?;
+        configmap::create_config_map(&self.context.client, config_map).await?;
+
+        let jobs_api: Api<Job> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+        match jobs_api.get(&job_name).await {
+            // created by a previous reconciliation which failed to update the status
+            Ok(_) => {}
+            Err(kube::Error::Api(response)) if response.code == 404 => {
+                let job = build_job(benchmark, &job_name, &connection_info.connection_string)?;
+                self.context.client.create(&job).await?;
+            }
+            Err(err) => return Err(err.into()),
+        }
+        info!(
+            "ZookeeperBenchmark {}: Started [{}] clients against [{}/{}]",
+            self.context.log_name(),
+            benchmark.spec.clients(),
+            reference.namespace,
+            reference.name
+        );
+
+        self.context.resource = self
+            .context
+            .client
+            .merge_patch_status(
+                &self.context.resource,
+                &json!({
+                    "phase": BenchmarkPhase::Running,
+                    "jobName": job_name,
+                    "startTime": Utc::now().to_rfc3339(),
+                }),
+            )
+            .await?;
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Summarizes the results of the clients once the Job completed.
+    async fn collect_results(&mut self) -> BenchmarkReconcileResult {
+        let job_name = match self.job_name() {
+            Some(job_name) => job_name,
+            None => return Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10))),
+        };
+
+        let jobs_api: Api<Job> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+        let job = match jobs_api.get(&job_name).await {
+            Ok(job) => job,
+            Err(kube::Error::Api(response)) if response.code == 404 => {
+                return self
+                    .finish(
+                        BenchmarkPhase::Failed,
+                        Some(format!("The Job [{}] was deleted", job_name)),
+                        None,
+                    )
+                    .await
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let job_status = job.status.unwrap_or_default();
+        let clients = self.context.resource.spec.clients();
+        if job_status.failed.unwrap_or(0) > 0 {
+            return self
+                .finish(
+                    BenchmarkPhase::Failed,
+                    Some(format!(
+                        "[{}] of [{}] clients failed, see the logs of the pods of the Job [{}]",
+                        job_status.failed.unwrap_or(0),
+                        clients,
+                        job_name
+                    )),
+                    None,
+                )
+                .await;
+        }
+        if job_status.succeeded.unwrap_or(0) < i32::from(clients) {
+            return Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10)));
+        }
+
+        let pods_api: Api<Pod> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+        let pods = pods_api
+            .list(&ListParams::default().labels(&format!("job-name={}", job_name)))
+            .await?;
+        let client_results = pods
+            .items
+            .iter()
+            .filter(|pod| {
+                pod.status
+                    .as_ref()
+                    .and_then(|status| status.phase.as_deref())
+                    == Some("Succeeded")
+            })
+            .map(|pod| parse_client_results(termination_message(pod).unwrap_or_default()))
+            .collect::<Result<Vec<_>, _>>();
+        let results = match client_results {
+            Ok(client_results) => summarize(&client_results),
+            Err(err) => {
+                return self
+                    .finish(
+                        BenchmarkPhase::Failed,
+                        Some(format!("Invalid results of a client: {}", err)),
+                        None,
+                    )
+                    .await
+            }
+        };
+
+        if let Some(name) = &self.context.resource.spec.results_config_map {
+            let mut data = BTreeMap::new();
+            data.insert(RESULTS_KEY.to_string(), serde_json::to_string(&results)?);
+            let config_map = configmap::build_config_map(
+                &self.context.resource,
+                name,
+                &self.context.namespace(),
+                labels(),
+                data,
+            )?;
+            configmap::create_config_map(&self.context.client, config_map).await?;
+        }
+
+        self.finish(BenchmarkPhase::Succeeded, None, Some(results))
+            .await
+    }
+}
+
+impl ReconciliationState for BenchmarkState {
+    type Error = Error;
+
+    fn reconcile(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<ReconcileFunctionAction, Self::Error>> + Send + '_>>
+    {
+        Box::pin(async move {
+            self.check_finished()
+                .await?
+                .then(self.validate_spec())
+                .await?
+                .then(self.create_job())
+                .await?
+                .then(self.collect_results())
+                .await
+        })
+    }
+}
+
+fn labels() -> BTreeMap<String, String> {
+    let mut labels = BTreeMap::new();
+    labels.insert(APP_NAME_LABEL.to_string(), APP_NAME.to_string());
+    labels.insert(APP_MANAGED_BY_LABEL.to_string(), MANAGED_BY.to_string());
+    labels
+}
+
+/// Builds the Job running `spec.clients` clients against the ensemble at `connection_string`.
+fn build_job(
+    benchmark: &ZookeeperBenchmark,
+    name: &str,
+    connection_string: &str,
+) -> Result<Job, Error> {
+    let spec = &benchmark.spec;
+    let env = [
+        ("ZOOKEEPER", connection_string.to_string()),
+        (
+            "BENCHMARK_DURATION_SECONDS",
+            spec.duration_seconds().to_string(),
+        ),
+        ("BENCHMARK_ZNODE_COUNT", spec.znode_count().to_string()),
+        ("BENCHMARK_ZNODE_SIZE", spec.znode_size().to_string()),
+    ]
+    .iter()
+    .map(|(name, value)| EnvVar {
+        name: name.to_string(),
+        value: Some(value.clone()),
+        ..EnvVar::default()
+    })
+    .collect();
+    let mut pod_labels = labels();
+    pod_labels.insert(BENCHMARK_LABEL.to_string(), name.to_string());
+
+    Ok(Job {
+        metadata: ObjectMetaBuilder::new()
+            .name(name)
+            .namespace(benchmark.metadata.namespace.as_deref().unwrap_or_default())
+            .with_labels(labels())
+            .ownerreference_from_resource(benchmark, Some(true), Some(true))?
+            .build()?,
+        spec: Some(JobSpec {
+            parallelism: Some(i32::from(spec.clients())),
+            completions: Some(i32::from(spec.clients())),
+            backoff_limit: Some(0),
+            active_deadline_seconds: Some(
+                i64::from(spec.duration_seconds()) + i64::from(DEADLINE_MARGIN_SECONDS),
+            ),
+            template: PodTemplateSpec {
+                metadata: Some(ObjectMeta {
+                    labels: pod_labels,
+                    ..ObjectMeta::default()
+                }),
+                spec: Some(PodSpec {
+                    containers: vec![Container {
+                        name: "benchmark".to_string(),
+                        image: Some(spec.image.clone()),
+                        command: vec!["sh".to_string(), format!("{}/{}", SCRIPT_DIR, SCRIPT_FILE)],
+                        env,
+                        volume_mounts: vec![VolumeMount {
+                            name: "script".to_string(),
+                            mount_path: SCRIPT_DIR.to_string(),
+                            ..VolumeMount::default()
+                        }],
+                        ..Container::default()
+                    }],
+                    volumes: vec![Volume {
+                        name: "script".to_string(),
+                        config_map: Some(ConfigMapVolumeSource {
+                            name: Some(name.to_string()),
+                            ..ConfigMapVolumeSource::default()
+                        }),
+                        ..Volume::default()
+                    }],
+                    restart_policy: Some("Never".to_string()),
+                    ..PodSpec::default()
+                }),
+            },
+            ..JobSpec::default()
+        }),
+        ..Job::default()
+    })
+}
+
+/// Returns the termination message of the (first) container of a finished client pod.
+pub fn termination_message(pod: &Pod) -> Option<&str> {
+    pod.status
+        .as_ref()?
+        .container_statuses
+        .first()?
+        .state
+        .as_ref()?
+        .terminated
+        .as_ref()?
+        .message
+        .as_deref()
+}
+
+/// Parses the termination message of a client, see `benchmark.sh`.
+fn parse_client_results(message: &str) -> Result<ClientResults, String> {
+    let invalid = |line: &str| format!("Invalid line [{}]", line);
+    let mut results = ClientResults::default();
+    for line in message.lines().filter(|line| !line.trim().is_empty()) {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        match fields.as_slice() {
+            ["rounds", rounds] => results.rounds = rounds.parse().map_err(|_| invalid(line))?,
+            [operation, count, millis] if OPERATIONS.contains(operation) => {
+                let count = count.parse().map_err(|_| invalid(line))?;
+                let millis = millis.parse().map_err(|_| invalid(line))?;
+                results
+                    .operations
+                    .insert(operation.to_string(), (count, millis));
+            }
+            _ => return Err(invalid(line)),
+        }
+    }
+    if results.rounds == 0 {
+        return Err("No round was reported".to_string());
+    }
+    Ok(results)
+}
+
+/// Summarizes the results of all clients.
+///
+/// The throughput of every client is its number of operations divided by the time it spent on
+/// them, as the clients ran concurrently their throughputs add up.
+fn summarize(client_results: &[ClientResults]) -> BenchmarkResults {
+    let round = |value: f64| (value * 1000.0).round() / 1000.0;
+
+    let mut throughput = 0.0;
+    for client in client_results {
+        let (count, millis) = client
+            .operations
+            .values()
+            .fold((0, 0), |(count, millis), (c, m)| (count + c, millis + m));
+        if millis > 0 {
+            throughput += count as f64 * 1000.0 / millis as f64;
+        }
+    }
+
+    let latencies = OPERATIONS
+        .iter()
+        .filter_map(|operation| {
+            let (count, millis) = client_results
+                .iter()
+                .filter_map(|client| client.operations.get(*operation))
+                .fold((0, 0), |(count, millis), (c, m)| (count + c, millis + m));
+            if count == 0 {
+                return None;
+            }
+            Some(OperationLatency {
+                operation: operation.to_string(),
+                count,
+                average_millis: round(millis as f64 / count as f64),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    BenchmarkResults {
+        clients: client_results.len() as u16,
+        rounds: client_results.iter().map(|client| client.rounds).sum(),
+        operations: latencies.iter().map(|latency| latency.count).sum(),
+        throughput: round(throughput),
+        latencies,
+    }
+}
+
+struct BenchmarkStrategy {}
+
+#[async_trait]
+impl ControllerStrategy for BenchmarkStrategy {
+    type Item = ZookeeperBenchmark;
+    type State = BenchmarkState;
+    type Error = Error;
+
+    async fn init_reconcile_state(
+        &self,
+        context: ReconciliationContext<Self::Item>,
+    ) -> Result<Self::State, Self::Error> {
+        Ok(BenchmarkState { context })
+    }
+}
+
+/// Creates the controller for `ZookeeperBenchmark` resources and runs it until the process exits.
+pub async fn create_benchmark_controller(
+    client: Client,
+    namespace: Option<String>,
+    requeue_interval: Duration,
+) -> OperatorResult<()> {
+    let benchmark_api: Api<ZookeeperBenchmark> = scoped_api(&client, namespace.as_deref());
+    let jobs_api: Api<Job> = scoped_api(&client, namespace.as_deref());
+    let config_maps_api: Api<ConfigMap> = scoped_api(&client, namespace.as_deref());
+
+    let controller = Controller::new(benchmark_api)
+        .owns(jobs_api, ListParams::default())
+        .owns(config_maps_api, ListParams::default());
+
+    controller
+        .run(client, BenchmarkStrategy {}, requeue_interval)
+        .await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_parse_client_results() {
+        let results = parse_client_results(indoc! {"
+            rounds 2
+            create 4000 800
+            set 2000 300
+            get 2000 200
+            delete 4000 700
+        "})
+        .unwrap();
+
+        assert_eq!(results.rounds, 2);
+        assert_eq!(results.operations.get("create"), Some(&(4000, 800)));
+        assert_eq!(results.operations.len(), 4);
+
+        parse_client_results("rounds 0\n").unwrap_err();
+        parse_client_results("rounds 1\nwatched 1000 100\n").unwrap_err();
+        parse_client_results("zk-latencies.py failed in round 1\n").unwrap_err();
+    }
+
+    #[test]
+    fn test_summarize() {
+        let client = |create: (u64, u64), get: (u64, u64)| ClientResults {
+            rounds: 1,
+            operations: vec![("create".to_string(), create), ("get".to_string(), get)]
+                .into_iter()
+                .collect(),
+        };
+
+        let results = summarize(&[
+            client((1000, 500), (1000, 500)),
+            client((1000, 1500), (1000, 500)),
+        ]);
+
+        assert_eq!(results.clients, 2);
+        assert_eq!(results.rounds, 2);
+        assert_eq!(results.operations, 4000);
+        // 2000 operations in 1s and 2000 operations in 2s
+        assert_eq!(results.throughput, 3000.0);
+        assert_eq!(
+            results.latencies,
+            vec![
+                OperationLatency {
+                    operation: "create".to_string(),
+                    count: 2000,
+                    average_millis: 1.0,
+                },
+                OperationLatency {
+                    operation: "get".to_string(),
+                    count: 2000,
+                    average_millis: 0.5,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,49 @@
+#!/usr/bin/env sh
+# Client of a ZookeeperBenchmark, rendered into a ConfigMap by the zookeeper-operator.
+#
+# Runs `zk-latencies.py` of zk-smoketest against ZOOKEEPER in rounds until
+# BENCHMARK_DURATION_SECONDS passed (at least one round) and writes the summed up number and
+# duration of the operations to the termination log, where the operator picks them up:
+#
+#   rounds <rounds>
+#   <operation> <count> <milliseconds>
+set -eu
+
+ROOT_ZNODE="/zk-latencies-${HOSTNAME}"
+OUTPUT=/tmp/benchmark.log
+ROUND_OUTPUT=/tmp/round.log
+
+end=$(( $(date +%s) + BENCHMARK_DURATION_SECONDS ))
+rounds=0
+: > "$OUTPUT"
+
+while [ "$rounds" -eq 0 ] || [ "$(date +%s)" -lt "$end" ]; do
+  if ! zk-latencies.py \
+    --cluster="$ZOOKEEPER" \
+    --root_znode="$ROOT_ZNODE" \
+    --znode_count="$BENCHMARK_ZNODE_COUNT" \
+    --znode_size="$BENCHMARK_ZNODE_SIZE" > "$ROUND_OUTPUT" 2>&1; then
+    cat "$ROUND_OUTPUT"
+    echo "zk-latencies.py failed in round $((rounds + 1)), see the log of the pod" > /dev/termination-log
+    exit 1
+  fi
+  tee -a "$OUTPUT" < "$ROUND_OUTPUT"
+  rounds=$((rounds + 1))
+done
+
+# e.g. "created    1000 permanent znodes  in     196 ms (0.196236 ms/op 5095.910429/sec)"
+awk -v rounds="$rounds" '
+  / in +[0-9]+ ms/ {
+    if ($1 == "created") operation = "create"
+    else if ($1 == "set") operation = "set"
+    else if ($1 == "get") operation = "get"
+    else if ($1 == "deleted") operation = "delete"
+    else next
+    for (i = 2; i < NF; i++) if ($i == "in") millis = $(i + 1)
+    count[operation] += $2
+    total[operation] += millis
+  }
+  END {
+    print "rounds " rounds
+    for (operation in count) print operation " " count[operation] " " total[operation]
+  }' "$OUTPUT" > /dev/termination-log
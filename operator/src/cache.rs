@@ -0,0 +1,335 @@
+//! Caches of the pods, ConfigMaps and Services owned by the ZookeeperClusters.
+//!
+//! Every reconciliation compares the desired state with the objects of its cluster. Listing them
+//! from the API server on every pass costs a few LIST requests per cluster, which adds up with
+//! dozens of ensembles. The [`OwnedObjectCache`] keeps the objects in reflector stores instead,
+//! filled by watches which run next to the controller.
+//!
+//! The stores lag behind the writes of the reconciliations. A reconciliation which still saw a
+//! pod it just deleted could delete the next server and break the quorum, so the objects the
+//! operator creates or deletes are recorded as expectations (see
+//! [`OwnedStore::expect_created`] and [`OwnedStore::expect_deleted`]). The objects of a cluster
+//! are only read from a store once it observed all expected writes, otherwise they are listed
+//! from the API server. Expectations expire after [`EXPECTATION_TIMEOUT`], e.g. when a created
+//! object was deleted again before the store saw it.
+use crate::metrics::Metrics;
+
+use futures::StreamExt;
+use k8s_openapi::api::core::v1::{ConfigMap, Pod, Service};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use kube::api::ListParams;
+use kube::{Api, Resource, ResourceExt};
+use kube_runtime::reflector::store::Writer;
+use kube_runtime::reflector::{reflector, ObjectRef, Store};
+use kube_runtime::watcher;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// The time after which an expected write which was not observed by a store is given up.
+pub const EXPECTATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The delay before a failed watch of a store is retried.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// The caches of the objects of all clusters of one controller, clones share the stores.
+#[derive(Clone)]
+pub struct OwnedObjectCache {
+    pub pods: OwnedStore<Pod>,
+    pub config_maps: OwnedStore<ConfigMap>,
+    pub services: OwnedStore<Service>,
+}
+
+impl OwnedObjectCache {
+    /// Creates the cache and the future which keeps it up to date by watching the objects with
+    /// the `app.kubernetes.io/name` label of the operator in the scope of the given APIs. The
+    /// future runs until it is dropped.
+    pub fn new(
+        pods_api: Api<Pod>,
+        config_maps_api: Api<ConfigMap>,
+        services_api: Api<Service>,
+        list_params: ListParams,
+        metrics: Metrics,
+    ) -> (OwnedObjectCache, impl std::future::Future<Output = ()>) {
+        let (pods, pods_writer) = OwnedStore::new("Pod", metrics.clone());
+        let (config_maps, config_maps_writer) = OwnedStore::new("ConfigMap", metrics.clone());
+        let (services, services_writer) = OwnedStore::new("Service", metrics);
+        let cache = OwnedObjectCache {
+            pods: pods.clone(),
+            config_maps: config_maps.clone(),
+            services: services.clone(),
+        };
+        let reflect = async move {
+            futures::join!(
+                pods.reflect(pods_writer, pods_api, list_params.clone()),
+                config_maps.reflect(config_maps_writer, config_maps_api, list_params.clone()),
+                services.reflect(services_writer, services_api, list_params),
+            );
+        };
+        (cache, reflect)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Expectation {
+    /// The object with the given name was created.
+    Created,
+    /// The object with the given uid was deleted.
+    Deleted { uid: String },
+}
+
+#[derive(Clone, Debug)]
+struct ExpectedWrite {
+    owner_uid: String,
+    namespace: String,
+    name: String,
+    expectation: Expectation,
+    expires_at: Instant,
+}
+
+/// The store of one kind of objects, see the [module documentation](self).
+pub struct OwnedStore<K>
+where
+    K: Clone + Resource + 'static,
+    K::DynamicType: Eq + Hash,
+{
+    kind: &'static str,
+    metrics: Metrics,
+    store: Store<K>,
+    synced: Arc<AtomicBool>,
+    expected_writes: Arc<Mutex<Vec<ExpectedWrite>>>,
+}
+
+impl<K> Clone for OwnedStore<K>
+where
+    K: Clone + Resource + 'static,
+    K::DynamicType: Eq + Hash,
+{
+    fn clone(&self) -> Self {
+        OwnedStore {
+            kind: self.kind,
+            metrics: self.metrics.clone(),
+            store: self.store.clone(),
+            synced: self.synced.clone(),
+            expected_writes: self.expected_writes.clone(),
+        }
+    }
+}
+
+impl<K> OwnedStore<K>
+where
+    K: Clone + Debug + Resource + Send + Sync + serde::de::DeserializeOwned + 'static,
+    K::DynamicType: Clone + Default + Eq + Hash,
+{
+    fn new(kind: &'static str, metrics: Metrics) -> (OwnedStore<K>, Writer<K>) {
+        let writer = Writer::default();
+        let store = OwnedStore {
+            kind,
+            metrics,
+            store: writer.as_reader(),
+            synced: Arc::new(AtomicBool::new(false)),
+            expected_writes: Arc::new(Mutex::new(vec![])),
+        };
+        (store, writer)
+    }
+
+    async fn reflect(&self, writer: Writer<K>, api: Api<K>, list_params: ListParams) {
+        let mut events = reflector(writer, watcher(api, list_params)).boxed();
+        while let Some(event) = events.next().await {
+            match event {
+                Ok(watcher::Event::Restarted(objects)) => {
+                    debug!("Cached [{}] {} objects", objects.len(), self.kind);
+                    self.metrics.set_cached_objects(self.kind, objects.len());
+                    self.synced.store(true, Ordering::SeqCst);
+                }
+                // the reflector updated the store before passing the event on
+                Ok(_) => self
+                    .metrics
+                    .set_cached_objects(self.kind, self.store.state().len()),
+                Err(err) => {
+                    warn!(
+                        "Watch of the cached {} objects failed, retrying in {:?}: {}",
+                        self.kind, RETRY_DELAY, err
+                    );
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+
+    /// Returns the objects in the namespace of the `owner` which are owned by it and carry all
+    /// `labels`, `None` if the store did not observe all expected writes to them yet.
+    pub fn list_owned<O: Resource>(
+        &self,
+        owner: &O,
+        labels: &BTreeMap<String, String>,
+    ) -> Option<Vec<K>> {
+        if !self.synced.load(Ordering::SeqCst) {
+            return None;
+        }
+        let owner_uid = owner.meta().uid.as_deref()?;
+        let namespace = owner.meta().namespace.as_deref()?;
+
+        let now = Instant::now();
+        let mut expected_writes = self.expected_writes.lock().unwrap();
+        expected_writes.retain(|write| {
+            write.expires_at > now
+                && !is_observed(
+                    &write.expectation,
+                    self.store
+                        .get(&ObjectRef::new(&write.name).within(&write.namespace))
+                        .as_ref()
+                        .map(Resource::meta),
+                )
+        });
+        if expected_writes
+            .iter()
+            .any(|write| write.owner_uid == owner_uid)
+        {
+            debug!(
+                "The {} cache did not observe all writes yet, listing them instead",
+                self.kind
+            );
+            return None;
+        }
+
+        Some(
+            self.store
+                .state()
+                .into_iter()
+                .filter(|object| is_owned_by(object.meta(), namespace, owner_uid, labels))
+                .collect(),
+        )
+    }
+
+    /// Records that `object` owned by `owner` was created.
+    pub fn expect_created<O: Resource>(&self, owner: &O, object: &K) {
+        self.expect(owner, object, Expectation::Created);
+    }
+
+    /// Records that the deletion of `object` owned by `owner` was requested.
+    pub fn expect_deleted<O: Resource>(&self, owner: &O, object: &K) {
+        if let Some(uid) = object.meta().uid.clone() {
+            self.expect(owner, object, Expectation::Deleted { uid });
+        }
+    }
+
+    fn expect<O: Resource>(&self, owner: &O, object: &K, expectation: Expectation) {
+        let (owner_uid, namespace) = match (&owner.meta().uid, &owner.meta().namespace) {
+            (Some(owner_uid), Some(namespace)) => (owner_uid.clone(), namespace.clone()),
+            _ => return,
+        };
+        self.expected_writes.lock().unwrap().push(ExpectedWrite {
+            owner_uid,
+            namespace,
+            name: object.name(),
+            expectation,
+            expires_at: Instant::now() + EXPECTATION_TIMEOUT,
+        });
+    }
+}
+
+/// Checks whether the `cached` metadata of an object (`None` if it is not cached) reflects the
+/// `expectation`. Objects which are being deleted count as deleted, like the terminating pods
+/// which are excluded from the ensemble.
+fn is_observed(expectation: &Expectation, cached: Option<&ObjectMeta>) -> bool {
+    match expectation {
+        Expectation::Created => cached.is_some(),
+        Expectation::Deleted { uid } => match cached {
+            Some(metadata) => {
+                metadata.uid.as_ref() != Some(uid) || metadata.deletion_timestamp.is_some()
+            }
+            None => true,
+        },
+    }
+}
+
+/// Checks whether the object with the given metadata is in `namespace`, owned by the object with
+/// the `owner_uid` and carries all `labels`.
+fn is_owned_by(
+    metadata: &ObjectMeta,
+    namespace: &str,
+    owner_uid: &str,
+    labels: &BTreeMap<String, String>,
+) -> bool {
+    metadata.namespace.as_deref() == Some(namespace)
+        && metadata
+            .owner_references
+            .iter()
+            .any(|owner| owner.uid == owner_uid)
+        && labels
+            .iter()
+            .all(|(key, value)| metadata.labels.get(key) == Some(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+    use k8s_openapi::chrono::Utc;
+
+    fn metadata() -> ObjectMeta {
+        serde_yaml::from_str(indoc! {"
+            name: simple-server-default-1
+            namespace: default
+            uid: pod
+            labels:
+              app.kubernetes.io/name: zookeeper
+              app.kubernetes.io/instance: simple
+            ownerReferences:
+              - apiVersion: zookeeper.stackable.tech/v1alpha1
+                kind: ZookeeperCluster
+                name: simple
+                uid: cluster
+        "})
+        .unwrap()
+    }
+
+    #[test]
+    fn test_is_observed() {
+        let deleted = Expectation::Deleted {
+            uid: "pod".to_string(),
+        };
+        let mut terminating = metadata();
+        terminating.deletion_timestamp = Some(Time(Utc::now()));
+        let mut recreated = metadata();
+        recreated.uid = Some("replacement".to_string());
+
+        assert!(is_observed(&Expectation::Created, Some(&metadata())));
+        assert!(!is_observed(&Expectation::Created, None));
+        assert!(!is_observed(&deleted, Some(&metadata())));
+        assert!(is_observed(&deleted, Some(&terminating)));
+        assert!(is_observed(&deleted, Some(&recreated)));
+        assert!(is_observed(&deleted, None));
+    }
+
+    #[test]
+    fn test_is_owned_by() {
+        let labels = |entries: &[(&str, &str)]| {
+            entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        assert!(is_owned_by(
+            &metadata(),
+            "default",
+            "cluster",
+            &labels(&[("app.kubernetes.io/instance", "simple")])
+        ));
+        assert!(!is_owned_by(
+            &metadata(),
+            "default",
+            "cluster",
+            &labels(&[("app.kubernetes.io/instance", "other")])
+        ));
+        assert!(!is_owned_by(&metadata(), "default", "other", &labels(&[])));
+        assert!(!is_owned_by(&metadata(), "other", "cluster", &labels(&[])));
+    }
+}
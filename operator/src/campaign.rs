@@ -0,0 +1,483 @@
+//! Fleet wide rolling restarts ("restart all clusters with label team=payments").
+//!
+//! A restart campaign is stored in a ConfigMap labelled with [`CAMPAIGN_LABEL`], which holds the
+//! label selector, the time the restart was requested and the progress per ZookeeperCluster.
+//! Clusters are restarted one at a time: the operator sets the [`RESTART_ANNOTATION`] on the
+//! cluster to the time the restart was requested, which makes its controller restart the
+//! pods one by one (honoring PodDisruptionBudgets), and moves on once all pods of the cluster
+//! were recreated and are ready again.
+use crate::apply;
+use crate::error::Error;
+use crate::object_ref::ObjectRef;
+use crate::usage::UsageStatistics;
+use crate::watch_namespace::{scoped_api, WatchNamespace};
+use crate::{is_pod_condition_true, is_pod_created_before, RESTART_ANNOTATION};
+
+use k8s_openapi::api::core::v1::{ConfigMap, Pod};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube::api::{ListParams, Patch, PostParams};
+use kube::{Api, ResourceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use stackable_operator::client::Client;
+use stackable_operator::labels::{APP_INSTANCE_LABEL, APP_NAME_LABEL};
+use stackable_zookeeper_crd::{ZookeeperCluster, APP_NAME};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Label identifying campaign ConfigMaps, the value is the kind of campaign.
+pub const CAMPAIGN_LABEL: &str = "zookeeper.stackable.tech/campaign";
+const RESTART_CAMPAIGN: &str = "restart";
+
+const SELECTOR_KEY: &str = "selector";
+const REQUESTED_AT_KEY: &str = "requestedAt";
+const CLUSTERS_KEY: &str = "clusters";
+
+/// How often campaigns are checked for progress.
+const CAMPAIGN_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ClusterRestartState {
+    Pending,
+    InProgress,
+    Done,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestartCampaign {
+    pub name: String,
+    pub namespace: String,
+    /// The label selector the clusters were selected with.
+    pub selector: String,
+    /// RFC 3339 timestamp, pods created before are restarted.
+    pub requested_at: String,
+    /// The progress per cluster.
+    pub clusters: BTreeMap<ObjectRef, ClusterRestartState>,
+}
+
+impl RestartCampaign {
+    fn from_config_map(config_map: &ConfigMap) -> Result<RestartCampaign, Error> {
+        let get = |key: &str| {
+            config_map.data.get(key).cloned().ok_or_else(|| {
+                Error::ReconcileError(format!(
+                    "Campaign ConfigMap [{}] is missing [{}]",
+                    config_map.name(),
+                    key
+                ))
+            })
+        };
+
+        Ok(RestartCampaign {
+            name: config_map.name(),
+            namespace: config_map.namespace().unwrap_or_default(),
+            selector: get(SELECTOR_KEY)?,
+            requested_at: get(REQUESTED_AT_KEY)?,
+            clusters: serde_json::from_str(&get(CLUSTERS_KEY)?)?,
+        })
+    }
+
+    fn to_data(&self) -> Result<BTreeMap<String, String>, Error> {
+        let mut data = BTreeMap::new();
+        data.insert(SELECTOR_KEY.to_string(), self.selector.clone());
+        data.insert(REQUESTED_AT_KEY.to_string(), self.requested_at.clone());
+        data.insert(
+            CLUSTERS_KEY.to_string(),
+            serde_json::to_string(&self.clusters)?,
+        );
+        Ok(data)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.clusters
+            .values()
+            .all(|state| *state == ClusterRestartState::Done)
+    }
+
+    /// Returns the cluster to work on next: the one in progress or else the first pending one.
+    fn next_cluster(&self) -> Option<(&ObjectRef, ClusterRestartState)> {
+        let find = |wanted: ClusterRestartState| {
+            self.clusters
+                .iter()
+                .find(|(_, state)| **state == wanted)
+                .map(|(cluster, state)| (cluster, *state))
+        };
+        find(ClusterRestartState::InProgress).or_else(|| find(ClusterRestartState::Pending))
+    }
+}
+
+/// Checks whether all `pods` of a cluster were recreated after `requested_at` and are ready.
+fn is_restart_complete(pods: &[Pod], requested_at: &DateTime<Utc>) -> bool {
+    pods.iter()
+        .all(|pod| !is_pod_created_before(pod, requested_at) && is_pod_condition_true(pod, "Ready"))
+}
+
+/// Creates a restart campaign for all ZookeeperClusters matching the label `selector` and returns
+/// the name of the campaign ConfigMap, which is created in `namespace`.
+pub async fn create_restart_campaign(
+    client: &Client,
+    namespace: &str,
+    selector: &str,
+) -> Result<String, Error> {
+    let clusters_api: Api<ZookeeperCluster> = client.get_all_api();
+    let clusters = clusters_api
+        .list(&ListParams::default().labels(selector))
+        .await?
+        .items;
+
+    let now = Utc::now();
+    let campaign = RestartCampaign {
+        name: format!("zookeeper-restart-{}", now.timestamp()),
+        namespace: namespace.to_string(),
+        selector: selector.to_string(),
+        requested_at: now.to_rfc3339(),
+        clusters: clusters
+            .iter()
+            .map(|cluster| (ObjectRef::from_obj(cluster), ClusterRestartState::Pending))
+            .collect(),
+    };
+
+    let mut labels = BTreeMap::new();
+    labels.insert(CAMPAIGN_LABEL.to_string(), RESTART_CAMPAIGN.to_string());
+    let config_map = ConfigMap {
+        metadata: ObjectMeta {
+            name: Some(campaign.name.clone()),
+            namespace: Some(campaign.namespace.clone()),
+            labels,
+            ..ObjectMeta::default()
+        },
+        data: campaign.to_data()?,
+        ..ConfigMap::default()
+    };
+
+    let config_maps_api: Api<ConfigMap> = client.get_namespaced_api(namespace);
+    config_maps_api
+        .create(&PostParams::default(), &config_map)
+        .await?;
+
+    Ok(campaign.name)
+}
+
+/// Advances all restart campaigns in the watched namespaces every [`CAMPAIGN_INTERVAL`] and reports their progress to
+/// `usage_statistics`. Runs until the process exits.
+pub async fn run_restart_campaigns(
+    client: Client,
+    watch_namespace: WatchNamespace,
+    usage_statistics: UsageStatistics,
+) {
+    loop {
+        if let Err(err) = process_campaigns(&client, &watch_namespace, &usage_statistics).await {
+            warn!("Failed to process restart campaigns: {}", err);
+        }
+        tokio::time::sleep(CAMPAIGN_INTERVAL).await;
+    }
+}
+
+/// Advances every campaign by at most one step, a failing campaign is logged and skipped so it
+/// does not hold back the others.
+async fn process_campaigns(
+    client: &Client,
+    watch_namespace: &WatchNamespace,
+    usage_statistics: &UsageStatistics,
+) -> Result<(), Error> {
+    let mut config_maps = vec![];
+    for namespace in watch_namespace.scopes() {
+        let config_maps_api: Api<ConfigMap> = scoped_api(client, namespace.as_deref());
+        config_maps.extend(
+            config_maps_api
+                .list(
+                    &ListParams::default()
+                        .labels(&format!("{}={}", CAMPAIGN_LABEL, RESTART_CAMPAIGN)),
+                )
+                .await?
+                .items,
+        );
+    }
+
+    for config_map in config_maps {
+        match process_campaign(client, &config_map).await {
+            Ok(campaign) => usage_statistics.record_campaign(campaign),
+            Err(err) => warn!(
+                "Failed to process restart campaign [{}/{}], skipping it: {}",
+                config_map.namespace().unwrap_or_default(),
+                config_map.name(),
+                err
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+/// Advances the campaign stored in `config_map` and writes its progress back.
+async fn process_campaign(
+    client: &Client,
+    config_map: &ConfigMap,
+) -> Result<RestartCampaign, Error> {
+    let mut campaign = RestartCampaign::from_config_map(config_map)?;
+
+    if advance_campaign(client, &mut campaign).await? {
+        let config_maps_api: Api<ConfigMap> = client.get_namespaced_api(&campaign.namespace);
+        config_maps_api
+            .patch(
+                &campaign.name,
+                &apply::merge_params(),
+                &Patch::Merge(json!({ "data": campaign.to_data()? })),
+            )
+            .await?;
+    }
+
+    Ok(campaign)
+}
+
+/// Moves the campaign forward by at most one step and returns whether its state changed.
+async fn advance_campaign(client: &Client, campaign: &mut RestartCampaign) -> Result<bool, Error> {
+    let (cluster_ref, state) = match campaign.next_cluster() {
+        Some((cluster_ref, state)) => (cluster_ref.clone(), state),
+        None => return Ok(false),
+    };
+    let (namespace, name) = (cluster_ref.namespace.as_str(), cluster_ref.name.as_str());
+    let requested_at = DateTime::parse_from_rfc3339(&campaign.requested_at)
+        .map_err(|err| {
+            Error::ReconcileError(format!(
+                "Invalid timestamp [{}] in campaign [{}]: {}",
+                campaign.requested_at, campaign.name, err
+            ))
+        })?
+        .with_timezone(&Utc);
+
+    let clusters_api: Api<ZookeeperCluster> = client.get_namespaced_api(namespace);
+    if let Err(kube::Error::Api(response)) = clusters_api.get(name).await {
+        if response.code == 404 {
+            info!(
+                "Campaign [{}]: ZookeeperCluster [{}] does not exist anymore, skipping it",
+                campaign.name, cluster_ref
+            );
+            campaign
+                .clusters
+                .insert(cluster_ref, ClusterRestartState::Done);
+            return Ok(true);
+        }
+    }
+
+    let next_state = match state {
+        ClusterRestartState::Pending => {
+            info!(
+                "Campaign [{}]: Requesting restart of ZookeeperCluster [{}]",
+                campaign.name, cluster_ref
+            );
+            clusters_api
+                .patch(
+                    name,
+                    &apply::merge_params(),
+                    &Patch::Merge(json!({
+                        "metadata": {
+                            "annotations": {
+                                RESTART_ANNOTATION: campaign.requested_at
+                            }
+                        }
+                    })),
+                )
+                .await?;
+            ClusterRestartState::InProgress
+        }
+        ClusterRestartState::InProgress => {
+            let pods_api: Api<Pod> = client.get_namespaced_api(namespace);
+            let pods = pods_api
+                .list(&ListParams::default().labels(&format!(
+                    "{}={},{}={}",
+                    APP_NAME_LABEL, APP_NAME, APP_INSTANCE_LABEL, name
+                )))
+                .await?
+                .items;
+            if !is_restart_complete(&pods, &requested_at) {
+                return Ok(false);
+            }
+            info!(
+                "Campaign [{}]: ZookeeperCluster [{}] was restarted",
+                campaign.name, cluster_ref
+            );
+            ClusterRestartState::Done
+        }
+        ClusterRestartState::Done => ClusterRestartState::Done,
+    };
+
+    campaign.clusters.insert(cluster_ref, next_state);
+    Ok(next_state != state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, FakeApiServer};
+    use indoc::indoc;
+    use serde_json::json;
+
+    fn campaign(clusters: &[(&str, ClusterRestartState)]) -> RestartCampaign {
+        RestartCampaign {
+            name: "zookeeper-restart-1".to_string(),
+            namespace: "default".to_string(),
+            selector: "team=payments".to_string(),
+            requested_at: "2021-09-01T12:00:00+00:00".to_string(),
+            clusters: clusters
+                .iter()
+                .map(|(cluster, state)| (cluster.parse().unwrap(), *state))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_next_cluster() {
+        use ClusterRestartState::*;
+
+        let first = ObjectRef::new("default", "first");
+        let second = ObjectRef::new("default", "second");
+
+        assert_eq!(
+            campaign(&[("default/first", Pending), ("default/second", Pending)]).next_cluster(),
+            Some((&first, Pending))
+        );
+        assert_eq!(
+            campaign(&[("default/first", Pending), ("default/second", InProgress)]).next_cluster(),
+            Some((&second, InProgress))
+        );
+        assert_eq!(
+            campaign(&[("default/first", Done), ("default/second", Done)]).next_cluster(),
+            None
+        );
+        assert!(campaign(&[("default/first", Done)]).is_done());
+        assert!(!campaign(&[("default/first", InProgress)]).is_done());
+    }
+
+    #[test]
+    fn test_config_map_round_trip() {
+        let campaign = campaign(&[("default/first", ClusterRestartState::InProgress)]);
+        let config_map = ConfigMap {
+            metadata: ObjectMeta {
+                name: Some(campaign.name.clone()),
+                namespace: Some(campaign.namespace.clone()),
+                ..ObjectMeta::default()
+            },
+            data: campaign.to_data().unwrap(),
+            ..ConfigMap::default()
+        };
+
+        assert_eq!(
+            RestartCampaign::from_config_map(&config_map).unwrap(),
+            campaign
+        );
+    }
+
+    #[test]
+    fn test_same_name_in_different_namespaces() {
+        use ClusterRestartState::*;
+
+        let campaign = campaign(&[("team-a/simple", Done), ("team-b/simple", Pending)]);
+
+        assert_eq!(campaign.clusters.len(), 2);
+        assert_eq!(
+            campaign.next_cluster(),
+            Some((&ObjectRef::new("team-b", "simple"), Pending))
+        );
+        assert_eq!(
+            campaign.to_data().unwrap().get(CLUSTERS_KEY),
+            Some(&r#"{"team-a/simple":"Done","team-b/simple":"Pending"}"#.to_string())
+        );
+    }
+
+    #[test]
+    fn test_is_restart_complete() {
+        let requested_at = DateTime::parse_from_rfc3339("2021-09-01T12:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let pods: Vec<Pod> = serde_yaml::from_str(indoc! {"
+            - apiVersion: v1
+              kind: Pod
+              metadata:
+                name: restarted
+                creationTimestamp: 2021-09-01T12:05:00Z
+              status:
+                conditions:
+                  - type: Ready
+                    status: 'True'
+            - apiVersion: v1
+              kind: Pod
+              metadata:
+                name: old
+                creationTimestamp: 2021-09-01T11:00:00Z
+              status:
+                conditions:
+                  - type: Ready
+                    status: 'True'
+            - apiVersion: v1
+              kind: Pod
+              metadata:
+                name: not-ready
+                creationTimestamp: 2021-09-01T12:05:00Z
+              status:
+                conditions:
+                  - type: Ready
+                    status: 'False'
+        "})
+        .unwrap();
+
+        assert!(is_restart_complete(&pods[..1], &requested_at));
+        assert!(!is_restart_complete(&pods[..2], &requested_at));
+        assert!(!is_restart_complete(
+            &[pods[0].clone(), pods[2].clone()],
+            &requested_at
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_process_campaigns_skips_failing_campaign() {
+        let server = FakeApiServer::new();
+        server.insert(&test_support::cluster("simple", json!({})));
+        let mut labels = BTreeMap::new();
+        labels.insert(CAMPAIGN_LABEL.to_string(), RESTART_CAMPAIGN.to_string());
+        // the broken campaign is listed first
+        server.insert(&ConfigMap {
+            metadata: ObjectMeta {
+                name: Some("zookeeper-restart-0".to_string()),
+                namespace: Some("default".to_string()),
+                labels: labels.clone(),
+                ..ObjectMeta::default()
+            },
+            ..ConfigMap::default()
+        });
+        let campaign = campaign(&[("default/simple", ClusterRestartState::Pending)]);
+        server.insert(&ConfigMap {
+            metadata: ObjectMeta {
+                name: Some(campaign.name.clone()),
+                namespace: Some(campaign.namespace.clone()),
+                labels,
+                ..ObjectMeta::default()
+            },
+            data: campaign.to_data().unwrap(),
+            ..ConfigMap::default()
+        });
+
+        process_campaigns(
+            &server.operator_client(),
+            &WatchNamespace::All,
+            &UsageStatistics::default(),
+        )
+        .await
+        .unwrap();
+
+        let config_map = server
+            .get::<ConfigMap>(Some("default"), &campaign.name)
+            .unwrap();
+        assert_eq!(
+            RestartCampaign::from_config_map(&config_map)
+                .unwrap()
+                .next_cluster(),
+            Some((
+                &ObjectRef::new("default", "simple"),
+                ClusterRestartState::InProgress
+            ))
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! Issuing the TLS certificate of the servers with cert-manager, see `spec.tls.issuerRef`.
+//!
+//! The operator creates a `Certificate` for every cluster which lets cert-manager write a PKCS #12
+//! keystore and truststore into the TLS Secret, protected by a generated password. cert-manager
+//! increments `status.revision` of the Certificate whenever it issued a new certificate. The
+//! revision the pods were started with is recorded in an annotation, so pods are restarted (one by
+//! one) after a renewal.
+use crate::error::Error;
+
+use k8s_openapi::api::core::v1::Secret;
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use k8s_openapi::ByteString;
+use kube::{Api, CustomResource};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use stackable_operator::builder::ObjectMetaBuilder;
+use stackable_operator::client::Client;
+use stackable_zookeeper_crd::tls::{CertIssuerRef, ZookeeperTls, PASSWORD_KEY};
+use stackable_zookeeper_crd::ZookeeperCluster;
+use std::collections::BTreeMap;
+use tracing::{info, warn};
+
+const PASSWORD_LENGTH: usize = 32;
+
+/// The subset of the cert-manager `Certificate` used by the operator.
+#[derive(Clone, CustomResource, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[kube(
+    group = "cert-manager.io",
+    version = "v1",
+    kind = "Certificate",
+    plural = "certificates",
+    namespaced
+)]
+#[kube(status = "CertificateStatus")]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateSpec {
+    pub secret_name: String,
+    pub common_name: String,
+    pub dns_names: Vec<String>,
+    pub issuer_ref: CertificateIssuerRef,
+    pub keystores: CertificateKeystores,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateIssuerRef {
+    pub name: String,
+    pub kind: String,
+    pub group: String,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateKeystores {
+    pub pkcs12: Pkcs12Keystore,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pkcs12Keystore {
+    pub create: bool,
+    pub password_secret_ref: SecretKeyRef,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretKeyRef {
+    pub name: String,
+    pub key: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateStatus {
+    /// Incremented by cert-manager whenever a certificate was issued.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revision: Option<i64>,
+}
+
+/// Checks whether the cert-manager CRDs are installed. Certificates are only watched if they are,
+/// otherwise the watch would fail in clusters without cert-manager.
+pub async fn is_installed(client: &Client) -> bool {
+    let crds_api: Api<CustomResourceDefinition> = client.get_all_api();
+    match crds_api.get("certificates.cert-manager.io").await {
+        Ok(_) => true,
+        Err(kube::Error::Api(response)) if response.code == 404 => {
+            info!("cert-manager is not installed, not watching Certificates");
+            false
+        }
+        Err(err) => {
+            warn!(
+                "Could not check whether cert-manager is installed, not watching Certificates: {}",
+                err
+            );
+            false
+        }
+    }
+}
+
+/// Returns the name of the Certificate of the cluster with the given name.
+pub fn certificate_name(cluster_name: &str) -> String {
+    format!("{}-tls", cluster_name)
+}
+
+/// Builds the Certificate for the servers, valid for the names of all nodes they may run on.
+pub fn build_certificate(
+    cluster: &ZookeeperCluster,
+    tls: &ZookeeperTls,
+    issuer_ref: &CertIssuerRef,
+    mut dns_names: Vec<String>,
+) -> Result<Certificate, Error> {
+    dns_names.sort();
+    dns_names.dedup();
+
+    let cluster_name = cluster.metadata.name.clone().unwrap_or_default();
+    let mut certificate = Certificate::new(
+        &certificate_name(&cluster_name),
+        CertificateSpec {
+            secret_name: tls.secret_name.clone(),
+            common_name: cluster_name.clone(),
+            dns_names,
+            issuer_ref: CertificateIssuerRef {
+                name: issuer_ref.name.clone(),
+                kind: issuer_ref
+                    .kind
+                    .clone()
+                    .unwrap_or_else(|| "Issuer".to_string()),
+                group: "cert-manager.io".to_string(),
+            },
+            keystores: CertificateKeystores {
+                pkcs12: Pkcs12Keystore {
+                    create: true,
+                    password_secret_ref: SecretKeyRef {
+                        name: tls.password_secret_name(),
+                        key: PASSWORD_KEY.to_string(),
+                    },
+                },
+            },
+        },
+    );
+    certificate.metadata = ObjectMetaBuilder::new()
+        .name(certificate_name(&cluster_name))
+        .namespace(cluster.metadata.namespace.as_deref().unwrap_or_default())
+        .ownerreference_from_resource(cluster, Some(true), Some(true))?
+        .build()?;
+    Ok(certificate)
+}
+
+/// Builds the Secret with a newly generated password for the keystore and truststore.
+pub fn build_password_secret(
+    cluster: &ZookeeperCluster,
+    tls: &ZookeeperTls,
+) -> Result<Secret, Error> {
+    let password: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(PASSWORD_LENGTH)
+        .map(char::from)
+        .collect();
+
+    let mut data = BTreeMap::new();
+    data.insert(PASSWORD_KEY.to_string(), ByteString(password.into_bytes()));
+
+    Ok(Secret {
+        metadata: ObjectMetaBuilder::new()
+            .name(tls.password_secret_name())
+            .namespace(cluster.metadata.namespace.as_deref().unwrap_or_default())
+            .ownerreference_from_resource(cluster, Some(true), Some(true))?
+            .build()?,
+        data,
+        ..Secret::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use serde_json::json;
+
+    #[test]
+    fn test_build_certificate() {
+        let cluster = test_support::cluster(
+            "simple",
+            json!({ "tls": { "secretName": "simple-tls", "issuerRef": { "name": "ca" } } }),
+        );
+        let tls = cluster.spec.tls.as_ref().unwrap();
+
+        let certificate = build_certificate(
+            &cluster,
+            tls,
+            tls.issuer_ref.as_ref().unwrap(),
+            vec![
+                "node-2".to_string(),
+                "node-1".to_string(),
+                "node-2".to_string(),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(certificate.metadata.name, Some("simple-tls".to_string()));
+        assert_eq!(certificate.spec.dns_names, vec!["node-1", "node-2"]);
+        assert_eq!(certificate.spec.issuer_ref.kind, "Issuer");
+        assert_eq!(
+            certificate.spec.keystores.pkcs12.password_secret_ref.name,
+            "simple-tls-password"
+        );
+        assert_eq!(certificate.metadata.owner_references.len(), 1);
+    }
+
+    #[test]
+    fn test_build_password_secret() {
+        let cluster = test_support::cluster(
+            "simple",
+            json!({ "tls": { "secretName": "simple-tls", "issuerRef": { "name": "ca" } } }),
+        );
+
+        let secret = build_password_secret(&cluster, cluster.spec.tls.as_ref().unwrap()).unwrap();
+
+        assert_eq!(
+            secret.metadata.name,
+            Some("simple-tls-password".to_string())
+        );
+        assert_eq!(
+            secret.data.get(PASSWORD_KEY).unwrap().0.len(),
+            PASSWORD_LENGTH
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! Building the client Secret of a cluster, see [`stackable_zookeeper_crd::client_secret`].
+use crate::error::Error;
+
+use k8s_openapi::api::core::v1::{Pod, Secret};
+use k8s_openapi::ByteString;
+use kube::ResourceExt;
+use stackable_operator::builder::ObjectMetaBuilder;
+use stackable_operator::labels::build_common_labels_for_all_managed_resources;
+use stackable_zookeeper_crd::client_secret::{
+    client_secret_name, CA_CERT_KEY, JAAS_CONFIG_KEY, PASSWORD_KEY, TRUSTSTORE_KEY,
+    TRUSTSTORE_PASSWORD_KEY, USERNAME_KEY, ZOOKEEPER_TLS_DISCOVERY_KEY,
+};
+use stackable_zookeeper_crd::ip_family;
+use stackable_zookeeper_crd::tls;
+use stackable_zookeeper_crd::znode::ZOOKEEPER_DISCOVERY_KEY;
+use stackable_zookeeper_crd::{ZookeeperCluster, APP_NAME};
+use std::collections::BTreeMap;
+
+/// The name of the TLS client port of the server containers.
+const TLS_CLIENT_PORT_NAME: &str = "client-tls";
+
+/// Returns the connection string of the TLS client ports of `pods`, ordered like the one in the
+/// discovery ConfigMap. Pods which are not scheduled yet or serve no TLS clients are skipped.
+pub fn tls_connection_string(pods: &[Pod]) -> Option<String> {
+    let mut addresses = pods
+        .iter()
+        .filter_map(|pod| {
+            let spec = pod.spec.as_ref()?;
+            let port = spec
+                .containers
+                .iter()
+                .flat_map(|container| container.ports.iter())
+                .find(|port| port.name.as_deref() == Some(TLS_CLIENT_PORT_NAME))?;
+            Some((spec.node_name.clone()?, port.container_port))
+        })
+        .collect::<Vec<_>>();
+    if addresses.is_empty() {
+        return None;
+    }
+    addresses.sort();
+    Some(
+        addresses
+            .iter()
+            .map(|(host, port)| ip_family::format_address(host, port))
+            .collect::<Vec<_>>()
+            .join(","),
+    )
+}
+
+/// Returns the truststore of the servers and its password for the client Secret, and the CA
+/// certificate if the TLS Secret contains one.
+pub fn tls_data(
+    tls_secret: &Secret,
+    password_secret: &Secret,
+) -> Result<BTreeMap<String, ByteString>, Error> {
+    let value = |secret: &Secret, key: &str| {
+        secret.data.get(key).cloned().ok_or_else(|| {
+            Error::ReconcileError(format!(
+                "The Secret [{}] does not contain the key [{}]",
+                secret.name(),
+                key
+            ))
+        })
+    };
+
+    let mut data = BTreeMap::new();
+    data.insert(
+        TRUSTSTORE_KEY.to_string(),
+        value(tls_secret, tls::TRUSTSTORE_KEY)?,
+    );
+    data.insert(
+        TRUSTSTORE_PASSWORD_KEY.to_string(),
+        value(password_secret, tls::PASSWORD_KEY)?,
+    );
+    if let Some(ca_cert) = tls_secret.data.get(CA_CERT_KEY) {
+        data.insert(CA_CERT_KEY.to_string(), ca_cert.clone());
+    }
+    Ok(data)
+}
+
+/// Returns the SASL credentials and the JAAS configuration of the clients for the client Secret.
+pub fn sasl_data(username: &str, password: &str) -> BTreeMap<String, ByteString> {
+    let mut data = BTreeMap::new();
+    data.insert(USERNAME_KEY.to_string(), username.to_string());
+    data.insert(PASSWORD_KEY.to_string(), password.to_string());
+    data.insert(
+        JAAS_CONFIG_KEY.to_string(),
+        build_client_jaas_config(username, password),
+    );
+    data.into_iter()
+        .map(|(key, value)| (key, ByteString(value.into_bytes())))
+        .collect()
+}
+
+/// Renders the JAAS configuration which lets a client log in via SASL (DIGEST-MD5).
+fn build_client_jaas_config(username: &str, password: &str) -> String {
+    format!(
+        "Client {{\n  org.apache.zookeeper.server.auth.DigestLoginModule required\n  username=\"{}\"\n  password=\"{}\";\n}};\n",
+        username, password
+    )
+}
+
+/// Builds the client Secret with the connection strings and the `tls` and `sasl` data (see
+/// [`tls_data`] and [`sasl_data`]) of the enabled features.
+pub fn build_client_secret(
+    cluster: &ZookeeperCluster,
+    connection_string: &str,
+    tls_connection_string: Option<&str>,
+    tls: Option<BTreeMap<String, ByteString>>,
+    sasl: Option<BTreeMap<String, ByteString>>,
+) -> Result<Secret, Error> {
+    let mut data = BTreeMap::new();
+    data.insert(
+        ZOOKEEPER_DISCOVERY_KEY.to_string(),
+        ByteString(connection_string.as_bytes().to_vec()),
+    );
+    if let Some(tls_connection_string) = tls_connection_string {
+        data.insert(
+            ZOOKEEPER_TLS_DISCOVERY_KEY.to_string(),
+            ByteString(tls_connection_string.as_bytes().to_vec()),
+        );
+    }
+    data.extend(tls.unwrap_or_default());
+    data.extend(sasl.unwrap_or_default());
+
+    Ok(Secret {
+        metadata: ObjectMetaBuilder::new()
+            .name(client_secret_name(&cluster.name()))
+            .namespace(cluster.metadata.namespace.as_deref().unwrap_or_default())
+            .with_labels(build_common_labels_for_all_managed_resources(
+                APP_NAME,
+                &cluster.name(),
+            ))
+            .ownerreference_from_resource(cluster, Some(true), Some(true))?
+            .build()?,
+        data,
+        ..Secret::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use indoc::{formatdoc, indoc};
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn pod(node_name: &str, port_name: &str) -> Pod {
+        serde_yaml::from_str(&formatdoc! {"
+            spec:
+              nodeName: {}
+              containers:
+                - name: zookeeper
+                  ports:
+                    - name: client
+                      containerPort: 2181
+                    - name: {}
+                      containerPort: 2281
+        ", node_name, port_name})
+        .unwrap()
+    }
+
+    #[rstest]
+    #[case::no_pods(vec![], None)]
+    #[case::no_tls(vec![pod("node-1", "admin")], None)]
+    #[case::sorted(
+        vec![pod("node-2", "client-tls"), pod("node-1", "client-tls")],
+        Some("node-1:2281,node-2:2281")
+    )]
+    fn test_tls_connection_string(#[case] pods: Vec<Pod>, #[case] expected: Option<&str>) {
+        assert_eq!(tls_connection_string(&pods).as_deref(), expected);
+    }
+
+    #[test]
+    fn test_tls_data() {
+        let tls_secret: Secret = serde_yaml::from_str(indoc! {"
+            metadata:
+              name: simple-tls
+            data:
+              keystore.p12: a2V5c3RvcmU=
+              truststore.p12: dHJ1c3RzdG9yZQ==
+              ca.crt: Y2E=
+        "})
+        .unwrap();
+        let password_secret: Secret = serde_yaml::from_str(indoc! {"
+            metadata:
+              name: simple-tls-password
+            data:
+              password: c2VjcmV0
+        "})
+        .unwrap();
+
+        let data = tls_data(&tls_secret, &password_secret).unwrap();
+
+        assert_eq!(
+            data.keys().collect::<Vec<_>>(),
+            vec![CA_CERT_KEY, TRUSTSTORE_KEY, TRUSTSTORE_PASSWORD_KEY]
+        );
+        assert_eq!(data[TRUSTSTORE_KEY].0, b"truststore");
+        assert_eq!(data[TRUSTSTORE_PASSWORD_KEY].0, b"secret");
+        assert!(tls_data(&password_secret, &password_secret).is_err());
+    }
+
+    #[test]
+    fn test_build_client_secret() {
+        let cluster = test_support::cluster("simple", json!({}));
+
+        let secret = build_client_secret(
+            &cluster,
+            "node-1:2181",
+            Some("node-1:2281"),
+            None,
+            Some(sasl_data("super", "secret")),
+        )
+        .unwrap();
+
+        assert_eq!(secret.metadata.name.as_deref(), Some("simple-client"));
+        assert_eq!(secret.metadata.owner_references.len(), 1);
+        assert_eq!(secret.data[ZOOKEEPER_DISCOVERY_KEY].0, b"node-1:2181");
+        assert_eq!(secret.data[ZOOKEEPER_TLS_DISCOVERY_KEY].0, b"node-1:2281");
+        assert_eq!(secret.data[USERNAME_KEY].0, b"super");
+        assert_eq!(
+            String::from_utf8_lossy(&secret.data[JAAS_CONFIG_KEY].0),
+            "Client {\n  org.apache.zookeeper.server.auth.DigestLoginModule required\n  username=\"super\"\n  password=\"secret\";\n};\n"
+        );
+        assert!(!secret.data.contains_key(TRUSTSTORE_KEY));
+    }
+}
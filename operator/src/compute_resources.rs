@@ -0,0 +1,219 @@
+//! Compute resources (CPU and memory requests and limits) of the ZooKeeper container.
+use crate::jvm;
+
+use k8s_openapi::api::core::v1::ResourceRequirements;
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+use stackable_zookeeper_crd::{ResourceQuantities, ZookeeperResources};
+use std::collections::BTreeMap;
+
+const DEFAULT_CPU_REQUEST: &str = "250m";
+const DEFAULT_MEMORY_REQUEST: &str = "512Mi";
+/// ZooKeeper keeps the whole data tree in memory, so the memory is always limited to keep a
+/// growing data tree from taking down the node. The CPU is not limited by default.
+const DEFAULT_MEMORY_LIMIT: &str = "1Gi";
+
+/// Builds the resource requirements of the server container, every value set in `resources`
+/// overrides the respective default.
+pub fn build_resource_requirements(resources: Option<&ZookeeperResources>) -> ResourceRequirements {
+    let resources = default_resources(resources);
+
+    ResourceRequirements {
+        requests: build_quantities(resources.requests.as_ref()),
+        limits: build_quantities(resources.limits.as_ref()),
+    }
+}
+
+/// Returns `resources` with the defaults filled in for all unset values.
+///
+/// Kubernetes rejects pods requesting more than their limit, so a default request is lowered to
+/// a smaller limit set in `resources` and the default memory limit is raised to a larger request
+/// set in `resources`.
+pub fn default_resources(resources: Option<&ZookeeperResources>) -> ZookeeperResources {
+    let requests = resources.and_then(|resources| resources.requests.as_ref());
+    let limits = resources.and_then(|resources| resources.limits.as_ref());
+
+    let mut default_requests = with_defaults(
+        requests,
+        Some(DEFAULT_CPU_REQUEST),
+        Some(DEFAULT_MEMORY_REQUEST),
+    );
+    let mut default_limits = with_defaults(limits, None, Some(DEFAULT_MEMORY_LIMIT));
+    if requests
+        .and_then(|requests| requests.cpu.as_ref())
+        .is_none()
+    {
+        if let (Some(request), Some(limit)) = (&default_requests.cpu, &default_limits.cpu) {
+            if exceeds(request, limit, parse_cpu_quantity) {
+                default_requests.cpu = Some(limit.clone());
+            }
+        }
+    }
+    if let (Some(request), Some(limit)) = (&default_requests.memory, &default_limits.memory) {
+        if exceeds(request, limit, parse_memory_quantity) {
+            if requests
+                .and_then(|requests| requests.memory.as_ref())
+                .is_none()
+            {
+                default_requests.memory = Some(limit.clone());
+            } else if limits.and_then(|limits| limits.memory.as_ref()).is_none() {
+                default_limits.memory = Some(request.clone());
+            }
+        }
+    }
+
+    ZookeeperResources {
+        requests: Some(default_requests),
+        limits: Some(default_limits),
+    }
+}
+
+/// Whether the quantity `a` is greater than `b`. Quantities which can not be parsed are left to
+/// the validation of the API server.
+fn exceeds(a: &str, b: &str, parse: fn(&str) -> Option<u64>) -> bool {
+    matches!((parse(a), parse(b)), (Some(a), Some(b)) if a > b)
+}
+
+/// Parses a Kubernetes CPU quantity (e.g. `250m`, `0.5` or `2`) into millicores.
+fn parse_cpu_quantity(quantity: &str) -> Option<u64> {
+    let quantity = quantity.trim();
+    match quantity.strip_suffix('m') {
+        Some(millicores) => millicores.parse().ok(),
+        None => quantity
+            .parse::<f64>()
+            .ok()
+            .map(|cores| (cores * 1000.0) as u64),
+    }
+}
+
+fn parse_memory_quantity(quantity: &str) -> Option<u64> {
+    jvm::parse_memory_quantity(quantity).ok()
+}
+
+fn with_defaults(
+    quantities: Option<&ResourceQuantities>,
+    default_cpu: Option<&str>,
+    default_memory: Option<&str>,
+) -> ResourceQuantities {
+    ResourceQuantities {
+        cpu: quantities
+            .and_then(|quantities| quantities.cpu.clone())
+            .or_else(|| default_cpu.map(str::to_string)),
+        memory: quantities
+            .and_then(|quantities| quantities.memory.clone())
+            .or_else(|| default_memory.map(str::to_string)),
+    }
+}
+
+fn build_quantities(quantities: Option<&ResourceQuantities>) -> BTreeMap<String, Quantity> {
+    let mut result = BTreeMap::new();
+    if let Some(quantities) = quantities {
+        if let Some(cpu) = &quantities.cpu {
+            result.insert("cpu".to_string(), Quantity(cpu.clone()));
+        }
+        if let Some(memory) = &quantities.memory {
+            result.insert("memory".to_string(), Quantity(memory.clone()));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn quantity(requirements: &BTreeMap<String, Quantity>, resource: &str) -> Option<String> {
+        requirements
+            .get(resource)
+            .map(|quantity| quantity.0.clone())
+    }
+
+    #[test]
+    fn test_default_resource_requirements() {
+        let requirements = build_resource_requirements(None);
+
+        assert_eq!(
+            quantity(&requirements.requests, "cpu"),
+            Some("250m".to_string())
+        );
+        assert_eq!(
+            quantity(&requirements.requests, "memory"),
+            Some("512Mi".to_string())
+        );
+        assert_eq!(quantity(&requirements.limits, "cpu"), None);
+        assert_eq!(
+            quantity(&requirements.limits, "memory"),
+            Some("1Gi".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resource_requirements_with_overrides() {
+        let resources = ZookeeperResources {
+            requests: None,
+            limits: Some(ResourceQuantities {
+                cpu: Some("2".to_string()),
+                memory: Some("4Gi".to_string()),
+            }),
+        };
+
+        let requirements = build_resource_requirements(Some(&resources));
+
+        assert_eq!(
+            quantity(&requirements.requests, "memory"),
+            Some("512Mi".to_string())
+        );
+        assert_eq!(quantity(&requirements.limits, "cpu"), Some("2".to_string()));
+        assert_eq!(
+            quantity(&requirements.limits, "memory"),
+            Some("4Gi".to_string())
+        );
+    }
+
+    #[rstest]
+    #[case::lower_limits(
+        Some("100m"),
+        Some("256Mi"),
+        None,
+        Some("100m"),
+        Some("256Mi"),
+        Some("256Mi")
+    )]
+    #[case::cores(Some("0.2"), None, None, Some("0.2"), Some("512Mi"), Some("1Gi"))]
+    #[case::higher_limits(Some("2"), Some("4Gi"), None, Some("250m"), Some("512Mi"), Some("4Gi"))]
+    #[case::higher_request(None, None, Some("2Gi"), Some("250m"), Some("2Gi"), Some("2Gi"))]
+    fn test_default_requests_within_limits(
+        #[case] cpu_limit: Option<&str>,
+        #[case] memory_limit: Option<&str>,
+        #[case] memory_request: Option<&str>,
+        #[case] expected_cpu_request: Option<&str>,
+        #[case] expected_memory_request: Option<&str>,
+        #[case] expected_memory_limit: Option<&str>,
+    ) {
+        let resources = ZookeeperResources {
+            requests: Some(ResourceQuantities {
+                cpu: None,
+                memory: memory_request.map(str::to_string),
+            }),
+            limits: Some(ResourceQuantities {
+                cpu: cpu_limit.map(str::to_string),
+                memory: memory_limit.map(str::to_string),
+            }),
+        };
+
+        let requirements = build_resource_requirements(Some(&resources));
+
+        assert_eq!(
+            quantity(&requirements.requests, "cpu").as_deref(),
+            expected_cpu_request
+        );
+        assert_eq!(
+            quantity(&requirements.requests, "memory").as_deref(),
+            expected_memory_request
+        );
+        assert_eq!(
+            quantity(&requirements.limits, "memory").as_deref(),
+            expected_memory_limit
+        );
+    }
+}
@@ -0,0 +1,423 @@
+//! Rendering of the configuration files that end up in the ConfigMaps of a ZooKeeper ensemble.
+//!
+//! Everything rendered here needs to be fully deterministic: the same input has to produce the
+//! same output (byte for byte) across reconcile runs and operator restarts. This is what allows
+//! us to compare hashes of the rendered configuration to decide whether pods need to be restarted.
+use crate::error::Error;
+
+use k8s_openapi::api::core::v1::ConfigMap;
+use sha2::{Digest, Sha256};
+use stackable_zookeeper_crd::autopurge;
+use stackable_zookeeper_crd::federation::FederationMember;
+use stackable_zookeeper_crd::ip_family;
+use stackable_zookeeper_crd::is_protected_config_key;
+use stackable_zookeeper_crd::ports::ZookeeperPorts;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Annotation which holds the hash of the rendered configuration.
+pub const CONFIG_HASH_ANNOTATION: &str = "zookeeper.stackable.tech/config-hash";
+
+/// Renders the `zoo.cfg` file.
+///
+/// The `server.<id>` entries are generated from `node_name_to_id` and the `federation_members`
+/// and are ordered by their numeric id (and not lexicographically) to keep the file readable. All other properties are ordered by
+/// key.
+///
+/// # Arguments
+///
+/// - `config` - The validated properties for the `zoo.cfg` file.
+/// - `node_name_to_id` - The mapping of node names to the `myid` of the server running there.
+/// - `node_addresses` - The addresses the servers are reached at by node name, e.g. the IPs of
+///   the nodes on the host network. Servers on other nodes are addressed by the node name.
+/// - `observer_node_names` - The nodes whose servers are observers.
+/// - `federation_members` - The servers of the other clusters of the federation. Their ids must
+///   not collide with the ids of the servers of this cluster, the reconciliation does not
+///   render `zoo.cfg` while they do.
+/// - `ports` - The ports of the cluster, the quorum and leader election ports are part of the
+///   `server.<id>` entries.
+///
+pub fn build_zoo_cfg(
+    config: &BTreeMap<String, String>,
+    node_name_to_id: &BTreeMap<String, usize>,
+    node_addresses: &BTreeMap<String, String>,
+    observer_node_names: &BTreeSet<String>,
+    federation_members: &[FederationMember],
+    ports: &ZookeeperPorts,
+) -> Result<String, Error> {
+    // We need to convert from <String, String> to <String, Option<String>> to deal with
+    // CLI flags or the java properties writer etc. We can not currently represent that
+    // via operator-rs / product-config. This is a preparation for that.
+    let mut properties: Vec<(String, Option<String>)> = config
+        .iter()
+        .map(|(k, v)| (k.clone(), Some(v.clone())))
+        .collect();
+
+    let mut server_entries =
+        build_server_entries(node_name_to_id, node_addresses, observer_node_names, ports);
+    for member in federation_members {
+        server_entries
+            .entry(member.id)
+            .or_insert_with(|| member.server_entry());
+    }
+    properties.extend(
+        server_entries
+            .into_iter()
+            .map(|(id, address)| (format!("server.{}", id), Some(address))),
+    );
+
+    Ok(product_config::writer::to_java_properties_string(
+        properties.iter().map(|(k, v)| (k, v)),
+    )?)
+}
+
+/// Builds the `server.<id>` addresses ordered by id, the addresses of observers end with
+/// `:observer`.
+fn build_server_entries(
+    node_name_to_id: &BTreeMap<String, usize>,
+    node_addresses: &BTreeMap<String, String>,
+    observer_node_names: &BTreeSet<String>,
+    ports: &ZookeeperPorts,
+) -> BTreeMap<usize, String> {
+    node_name_to_id
+        .iter()
+        .map(|(node_name, id)| {
+            let mut address = format!(
+                "{}:{}:{}",
+                ip_family::format_host(node_addresses.get(node_name).unwrap_or(node_name)),
+                ports.quorum(),
+                ports.leader_election()
+            );
+            if observer_node_names.contains(node_name) {
+                address.push_str(":observer");
+            }
+            (*id, address)
+        })
+        .collect()
+}
+
+/// `zoo.cfg` properties which must be non-negative integers.
+const NON_NEGATIVE_INTEGER_PROPERTIES: [&str; 6] = [
+    "tickTime",
+    "initLimit",
+    "syncLimit",
+    "maxClientCnxns",
+    "autopurge.snapRetainCount",
+    "autopurge.purgeInterval",
+];
+
+/// Values which are accepted by ZooKeeper but put the data or the security of the ensemble at
+/// risk.
+const DANGEROUS_VALUES: [(&str, &str, &str); 3] = [
+    (
+        "forceSync",
+        "no",
+        "transactions would not be synced to disk before they are acknowledged",
+    ),
+    ("skipACL", "yes", "all ACLs would be ignored"),
+    (
+        "reconfigEnabled",
+        "true",
+        "the ensemble membership is managed by the operator",
+    ),
+];
+
+/// Validates the `zoo.cfg` properties in `spec.config` and returns a description of every
+/// problem found.
+///
+/// Properties managed by the operator must not be set and values which are known to be
+/// dangerous are rejected.
+pub fn validate_config_properties(config: &BTreeMap<String, String>) -> Vec<String> {
+    let mut problems = vec![];
+    for (key, value) in config {
+        if is_protected_config_key(key) {
+            problems.push(format!("[{}] is managed by the operator", key));
+        } else if NON_NEGATIVE_INTEGER_PROPERTIES.contains(&key.as_str())
+            && value.trim().parse::<u32>().is_err()
+        {
+            problems.push(format!(
+                "[{}] must be a non-negative integer but is [{}]",
+                key, value
+            ));
+        } else if key == autopurge::SNAP_RETAIN_COUNT
+            && value.trim().parse::<u32>().unwrap_or_default() < autopurge::MIN_SNAP_RETAIN_COUNT
+        {
+            problems.push(format!(
+                "[{}] must be at least {} but is [{}]",
+                key,
+                autopurge::MIN_SNAP_RETAIN_COUNT,
+                value
+            ));
+        } else if let Some(reason) = find_danger(key, value) {
+            problems.push(format!("[{}={}] is not allowed: {}", key, value, reason));
+        }
+    }
+    problems
+}
+
+/// Returns why the property is dangerous, see [`DANGEROUS_VALUES`].
+pub fn find_danger(key: &str, value: &str) -> Option<&'static str> {
+    DANGEROUS_VALUES
+        .iter()
+        .find(|(dangerous_key, dangerous_value, _)| {
+            *dangerous_key == key && value.trim().eq_ignore_ascii_case(dangerous_value)
+        })
+        .map(|(_, _, reason)| *reason)
+}
+
+/// Calculates a stable hash for the content of the given ConfigMap.
+/// See [`hash_config_map_data`] for details.
+pub fn hash_config_map(config_map: &ConfigMap) -> Result<String, Error> {
+    hash_config_map_data(&config_map.data)
+}
+
+/// Calculates a stable hash for the data of a ConfigMap.
+///
+/// The data is serialized to canonical JSON (keys ordered) before hashing, which means the
+/// result only depends on the content and not on the order in which entries were inserted.
+/// We use SHA-256 instead of the `std` hasher because the latter is not guaranteed to be stable
+/// across Rust releases.
+pub fn hash_config_map_data(data: &BTreeMap<String, String>) -> Result<String, Error> {
+    let canonical = serde_json::to_vec(data)?;
+    Ok(format!("{:x}", Sha256::digest(&canonical)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn config(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn ids(entries: &[(&str, usize)]) -> BTreeMap<String, usize> {
+        entries.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+
+    #[test]
+    fn test_server_entries_ordered_by_id() {
+        let zoo_cfg = build_zoo_cfg(
+            &config(&[("tickTime", "3000")]),
+            &ids(&[("node-a", 10), ("node-b", 2), ("node-c", 1)]),
+            &BTreeMap::new(),
+            &BTreeSet::new(),
+            &[],
+            &ZookeeperPorts::default(),
+        )
+        .unwrap();
+
+        let servers = zoo_cfg
+            .lines()
+            .filter(|line| line.starts_with("server."))
+            .filter_map(|line| line.split('=').next())
+            .collect::<Vec<_>>();
+
+        assert_eq!(servers, vec!["server.1", "server.2", "server.10"]);
+    }
+
+    #[test]
+    fn test_zoo_cfg_independent_of_insertion_order() {
+        let mut first_config = BTreeMap::new();
+        first_config.insert("tickTime".to_string(), "3000".to_string());
+        first_config.insert("clientPort".to_string(), "2181".to_string());
+        let mut first_ids = BTreeMap::new();
+        first_ids.insert("node-a".to_string(), 1);
+        first_ids.insert("node-b".to_string(), 2);
+
+        let mut second_config = BTreeMap::new();
+        second_config.insert("clientPort".to_string(), "2181".to_string());
+        second_config.insert("tickTime".to_string(), "3000".to_string());
+        let mut second_ids = BTreeMap::new();
+        second_ids.insert("node-b".to_string(), 2);
+        second_ids.insert("node-a".to_string(), 1);
+        let ports = ZookeeperPorts::default();
+
+        assert_eq!(
+            build_zoo_cfg(
+                &first_config,
+                &first_ids,
+                &BTreeMap::new(),
+                &BTreeSet::new(),
+                &[],
+                &ports
+            )
+            .unwrap(),
+            build_zoo_cfg(
+                &second_config,
+                &second_ids,
+                &BTreeMap::new(),
+                &BTreeSet::new(),
+                &[],
+                &ports
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_observer_server_entries() {
+        let observers = vec!["node-b".to_string()].into_iter().collect();
+        let zoo_cfg = build_zoo_cfg(
+            &config(&[("tickTime", "3000")]),
+            &ids(&[("node-a", 1), ("node-b", 2)]),
+            &BTreeMap::new(),
+            &observers,
+            &[],
+            &ZookeeperPorts::default(),
+        )
+        .unwrap();
+
+        let observer_entries = zoo_cfg
+            .lines()
+            .filter(|line| line.starts_with("server.") && line.ends_with("observer"))
+            .filter_map(|line| line.split('=').next())
+            .collect::<Vec<_>>();
+
+        assert_eq!(observer_entries, vec!["server.2"]);
+    }
+
+    #[test]
+    fn test_server_entries_with_custom_ports() {
+        let ports = ZookeeperPorts {
+            quorum: Some(12888),
+            leader_election: Some(13888),
+            ..ZookeeperPorts::default()
+        };
+        let zoo_cfg = build_zoo_cfg(
+            &config(&[("tickTime", "3000")]),
+            &ids(&[("node-a", 1)]),
+            &BTreeMap::new(),
+            &BTreeSet::new(),
+            &[],
+            &ports,
+        )
+        .unwrap();
+
+        let server = zoo_cfg
+            .lines()
+            .find(|line| line.starts_with("server.1="))
+            .unwrap();
+        assert!(server.contains("12888") && server.ends_with("13888"));
+    }
+
+    #[test]
+    fn test_server_entries_with_node_addresses() {
+        let mut node_addresses = BTreeMap::new();
+        node_addresses.insert("node-a".to_string(), "10.0.0.1".to_string());
+        node_addresses.insert("node-c".to_string(), "2001:db8::3".to_string());
+        let zoo_cfg = build_zoo_cfg(
+            &config(&[("tickTime", "3000")]),
+            &ids(&[("node-a", 1), ("node-b", 2), ("node-c", 3)]),
+            &node_addresses,
+            &BTreeSet::new(),
+            &[],
+            &ZookeeperPorts::default(),
+        )
+        .unwrap();
+
+        let servers = zoo_cfg
+            .lines()
+            .filter(|line| line.starts_with("server."))
+            .collect::<Vec<_>>();
+        assert!(servers[0].starts_with("server.1=10.0.0.1"));
+        assert!(servers[1].starts_with("server.2=node-b"));
+        // IPv6 literals are bracketed, the colons separate the ports
+        assert!(servers[2].starts_with("server.3=[2001"));
+    }
+
+    #[test]
+    fn test_server_entries_with_federation_members() {
+        let members = vec![
+            FederationMember {
+                id: 1,
+                address: "zk-1.example.com".to_string(),
+                ..FederationMember::default()
+            },
+            FederationMember {
+                id: 11,
+                address: "zk-11.example.com".to_string(),
+                observer: true,
+                ..FederationMember::default()
+            },
+        ];
+        let zoo_cfg = build_zoo_cfg(
+            &config(&[("tickTime", "3000")]),
+            &ids(&[("node-a", 1), ("node-b", 2)]),
+            &BTreeMap::new(),
+            &BTreeSet::new(),
+            &members,
+            &ZookeeperPorts::default(),
+        )
+        .unwrap();
+
+        let servers = zoo_cfg
+            .lines()
+            .filter(|line| line.starts_with("server."))
+            .collect::<Vec<_>>();
+        assert_eq!(servers.len(), 3);
+        assert!(servers[0].starts_with("server.1=node-a"));
+        assert!(servers[2].starts_with("server.11=zk-11.example.com"));
+        assert!(servers[2].ends_with("observer"));
+    }
+
+    #[rstest]
+    #[case(&[], "44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a")]
+    #[case(&[("myid", "1")], "9a005e7c65b913e399b0f72a053d2ac1535bba8d379fe591ca4878da488d0e1e")]
+    fn test_hash_is_stable(#[case] data: &[(&str, &str)], #[case] expected: &str) {
+        // These values are pinned on purpose: if they change, every pod of every cluster
+        // will be restarted after an operator upgrade.
+        assert_eq!(hash_config_map_data(&config(data)).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_hash_independent_of_insertion_order() {
+        let mut first = BTreeMap::new();
+        first.insert("a".to_string(), "1".to_string());
+        first.insert("b".to_string(), "2".to_string());
+
+        let mut second = BTreeMap::new();
+        second.insert("b".to_string(), "2".to_string());
+        second.insert("a".to_string(), "1".to_string());
+
+        assert_eq!(
+            hash_config_map_data(&first).unwrap(),
+            hash_config_map_data(&second).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_validate_config_properties() {
+        assert!(validate_config_properties(&config(&[
+            ("maxClientCnxns", "100"),
+            ("autopurge.purgeInterval", "24"),
+            ("forceSync", "yes"),
+        ]))
+        .is_empty());
+
+        assert_eq!(
+            validate_config_properties(&config(&[
+                ("server.1", "node-1:2888:3888"),
+                ("skipACL", "YES"),
+                ("tickTime", "-1"),
+                ("autopurge.snapRetainCount", "1"),
+            ])),
+            vec![
+                "[autopurge.snapRetainCount] must be at least 3 but is [1]",
+                "[server.1] is managed by the operator",
+                "[skipACL=YES] is not allowed: all ACLs would be ignored",
+                "[tickTime] must be a non-negative integer but is [-1]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hash_changes_with_content() {
+        assert_ne!(
+            hash_config_map_data(&config(&[("myid", "1")])).unwrap(),
+            hash_config_map_data(&config(&[("myid", "2")])).unwrap()
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! `${secret:NAME:KEY}` placeholders in the `zoo.cfg` properties (e.g. in `configOverrides`).
+//!
+//! Secret values must never end up in a ConfigMap. Every placeholder is therefore replaced with a
+//! reference to an environment variable (`${ZOOKEEPER_SECRET_<hash>}`) which is filled from the
+//! key of the Secret, and the entrypoint (see [`crate::entrypoint`]) substitutes the variable when
+//! the server starts.
+use crate::error::Error;
+
+use k8s_openapi::api::core::v1::{EnvVar, EnvVarSource, SecretKeySelector};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+
+const PLACEHOLDER_PREFIX: &str = "${secret:";
+const PLACEHOLDER_SUFFIX: char = '}';
+const ENV_VAR_PREFIX: &str = "ZOOKEEPER_SECRET_";
+
+/// A key of a Secret in the namespace of the cluster.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct SecretKeyRef {
+    pub name: String,
+    pub key: String,
+}
+
+impl SecretKeyRef {
+    /// Returns the name of the environment variable holding the value of the key. It is derived
+    /// from a hash of the name and the key, so it is stable and can not collide.
+    pub fn env_var_name(&self) -> String {
+        let hash = Sha256::digest(format!("{}/{}", self.name, self.key).as_bytes());
+        let suffix = hash[..6]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<String>();
+        format!("{}{}", ENV_VAR_PREFIX, suffix)
+    }
+}
+
+/// Returns the properties with all placeholders replaced by references to the environment
+/// variables holding the values.
+pub fn replace_secret_placeholders(
+    properties: &BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>, Error> {
+    properties
+        .iter()
+        .map(|(key, value)| {
+            let (value, _) = parse_value(value).map_err(|problem| invalid(key, problem))?;
+            Ok((key.clone(), value))
+        })
+        .collect()
+}
+
+/// Builds the environment variables holding the values of all Secret keys referenced by the
+/// properties.
+pub fn build_env_vars(properties: &BTreeMap<String, String>) -> Result<Vec<EnvVar>, Error> {
+    let mut secret_key_refs = BTreeSet::new();
+    for (key, value) in properties {
+        let (_, refs) = parse_value(value).map_err(|problem| invalid(key, problem))?;
+        secret_key_refs.extend(refs);
+    }
+
+    Ok(secret_key_refs
+        .into_iter()
+        .map(|secret_key_ref| EnvVar {
+            name: secret_key_ref.env_var_name(),
+            value: None,
+            value_from: Some(EnvVarSource {
+                secret_key_ref: Some(SecretKeySelector {
+                    name: Some(secret_key_ref.name),
+                    key: secret_key_ref.key,
+                    optional: Some(false),
+                }),
+                ..EnvVarSource::default()
+            }),
+        })
+        .collect())
+}
+
+/// Returns the names of the Secrets referenced by the placeholders in the properties, malformed
+/// placeholders are skipped.
+pub fn referenced_secrets(properties: &BTreeMap<String, String>) -> BTreeSet<String> {
+    properties
+        .values()
+        .filter_map(|value| parse_value(value).ok())
+        .flat_map(|(_, refs)| refs)
+        .map(|secret_key_ref| secret_key_ref.name)
+        .collect()
+}
+
+/// Returns a description of every malformed placeholder in the properties.
+pub fn validate(properties: &BTreeMap<String, String>) -> Vec<String> {
+    properties
+        .iter()
+        .filter_map(|(key, value)| parse_value(value).err().map(|problem| (key, problem)))
+        .map(|(key, problem)| format!("[{}]: {}", key, problem))
+        .collect()
+}
+
+fn invalid(key: &str, problem: String) -> Error {
+    Error::InvalidConfig {
+        problems: vec![format!("[{}]: {}", key, problem)],
+    }
+}
+
+/// Replaces the placeholders in `value` and returns the resulting value and the referenced
+/// Secret keys.
+fn parse_value(value: &str) -> Result<(String, Vec<SecretKeyRef>), String> {
+    let mut result = String::new();
+    let mut refs = vec![];
+    let mut rest = value;
+    while let Some(start) = rest.find(PLACEHOLDER_PREFIX) {
+        result.push_str(&rest[..start]);
+        let placeholder = &rest[start + PLACEHOLDER_PREFIX.len()..];
+        let end = placeholder.find(PLACEHOLDER_SUFFIX).ok_or_else(|| {
+            "Unterminated secret placeholder, expected ${secret:NAME:KEY}".to_string()
+        })?;
+        let secret_key_ref = parse_secret_key_ref(&placeholder[..end])?;
+        result.push_str(&format!("${{{}}}", secret_key_ref.env_var_name()));
+        refs.push(secret_key_ref);
+        rest = &placeholder[end + 1..];
+    }
+    result.push_str(rest);
+    Ok((result, refs))
+}
+
+fn parse_secret_key_ref(reference: &str) -> Result<SecretKeyRef, String> {
+    let mut parts = reference.splitn(2, ':');
+    let name = parts.next().unwrap_or_default();
+    let key = parts.next().unwrap_or_default();
+    let valid_name = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.');
+    let valid_key = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if valid_name && valid_key {
+        Ok(SecretKeyRef {
+            name: name.to_string(),
+            key: key.to_string(),
+        })
+    } else {
+        Err(format!(
+            "Invalid secret placeholder [${{secret:{}}}], expected ${{secret:NAME:KEY}}",
+            reference
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn properties(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_replace_secret_placeholders() {
+        let secret_key_ref = SecretKeyRef {
+            name: "zookeeper-tls".to_string(),
+            key: "keystore-password".to_string(),
+        };
+        let properties = properties(&[
+            (
+                "ssl.keyStore.password",
+                "${secret:zookeeper-tls:keystore-password}",
+            ),
+            ("tickTime", "3000"),
+        ]);
+
+        let replaced = replace_secret_placeholders(&properties).unwrap();
+
+        assert_eq!(
+            replaced.get("ssl.keyStore.password"),
+            Some(&format!("${{{}}}", secret_key_ref.env_var_name()))
+        );
+        assert_eq!(replaced.get("tickTime"), Some(&"3000".to_string()));
+        assert!(secret_key_ref.env_var_name().starts_with(ENV_VAR_PREFIX));
+
+        let env_vars = build_env_vars(&properties).unwrap();
+        assert_eq!(env_vars.len(), 1);
+        assert_eq!(env_vars[0].name, secret_key_ref.env_var_name());
+        assert_eq!(env_vars[0].value, None);
+        let selector = env_vars[0]
+            .value_from
+            .as_ref()
+            .and_then(|source| source.secret_key_ref.as_ref())
+            .unwrap();
+        assert_eq!(selector.name.as_deref(), Some("zookeeper-tls"));
+        assert_eq!(selector.key, "keystore-password");
+
+        assert_eq!(
+            referenced_secrets(&properties)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["zookeeper-tls".to_string()]
+        );
+    }
+
+    #[rstest]
+    #[case("plain", "plain", 0)]
+    #[case("user:${secret:creds:user}", "user:${ZOOKEEPER_SECRET_", 1)]
+    #[case("${secret:a:b}/${secret:c:d}", "${ZOOKEEPER_SECRET_", 2)]
+    #[case("${HOME}", "${HOME}", 0)]
+    fn test_parse_value(#[case] value: &str, #[case] prefix: &str, #[case] refs: usize) {
+        let (parsed, secret_key_refs) = parse_value(value).unwrap();
+
+        assert!(parsed.starts_with(prefix));
+        assert!(!parsed.contains("secret:"));
+        assert_eq!(secret_key_refs.len(), refs);
+    }
+
+    #[rstest]
+    #[case("${secret:creds:user")]
+    #[case("${secret:creds}")]
+    #[case("${secret::user}")]
+    #[case("${secret:Creds:user}")]
+    #[case("${secret:creds:user name}")]
+    fn test_validate(#[case] value: &str) {
+        assert_eq!(validate(&properties(&[("password", value)])).len(), 1);
+    }
+}
@@ -0,0 +1,353 @@
+//! Printing, installing and checking the CRDs of the operator.
+//!
+//! The CRDs are generated from the Rust types, so they always match the operator binary, which
+//! avoids a mismatch between the manifests in `deploy/crd` and the deployed version.
+//!
+//! CRDs installed by other means (e.g. from an older or newer release) may not match. The API
+//! server prunes fields which are not part of the installed schema, so an operator running with
+//! an older CRD would silently lose the fields it writes, and one running with a newer CRD would
+//! ignore fields it does not know. [`check_crds`] compares the installed CRDs with the ones of
+//! the binary on startup.
+use crate::apply::{self, ConflictPolicy};
+use crate::error::Error;
+
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::{
+    CustomResourceDefinition, CustomResourceDefinitionVersion, JSONSchemaProps,
+    JSONSchemaPropsOrArray, JSONSchemaPropsOrBool,
+};
+use kube::{Api, CustomResourceExt, ResourceExt};
+use stackable_operator::client::Client;
+use stackable_zookeeper_crd::benchmark::ZookeeperBenchmark;
+use stackable_zookeeper_crd::znode::ZookeeperZnode;
+use stackable_zookeeper_crd::ZookeeperCluster;
+use tracing::info;
+
+/// Returns the CRDs of all custom resources of the operator.
+pub fn crds() -> Vec<CustomResourceDefinition> {
+    vec![
+        ZookeeperCluster::crd(),
+        ZookeeperZnode::crd(),
+        ZookeeperBenchmark::crd(),
+    ]
+}
+
+/// Renders the CRDs as YAML, one document per CRD, e.g. for `kubectl apply -f -`.
+pub fn crds_yaml() -> Result<String, Error> {
+    let mut yaml = String::new();
+    for crd in crds() {
+        // every document starts with `---`
+        yaml.push_str(&serde_yaml::to_string(&crd)?);
+    }
+    Ok(yaml)
+}
+
+/// Creates or updates the CRDs with a server-side apply, which needs permissions to patch
+/// `customresourcedefinitions`.
+pub async fn install_crds(client: &Client) -> Result<(), Error> {
+    let crds_api: Api<CustomResourceDefinition> = client.get_all_api();
+    for crd in crds() {
+        apply::apply(&crds_api, &crd, &ConflictPolicy::Force).await?;
+        info!("Installed CRD [{}]", crd.name());
+    }
+    Ok(())
+}
+
+/// Compares the installed CRDs with the ones of the operator and fails with the
+/// incompatibilities of the first CRD which does not match, see [`incompatibilities`].
+pub async fn check_crds(client: &Client) -> Result<(), Error> {
+    let crds_api: Api<CustomResourceDefinition> = client.get_all_api();
+    for crd in crds() {
+        let installed = crds_api.get(&crd.name()).await?;
+        let problems = incompatibilities(&installed, &crd);
+        if !problems.is_empty() {
+            return Err(Error::IncompatibleCrd {
+                crd: crd.name(),
+                problems,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Returns why the `installed` CRD does not match the `expected` one of the operator:
+///
+/// - objects are stored in or served with versions the operator does not know,
+/// - versions of the operator are not served or
+/// - fields of the operator are missing in the schema (they would be pruned) or the schema
+///   contains fields the operator does not know.
+///
+/// Descriptions, defaults and validations are not compared.
+pub fn incompatibilities(
+    installed: &CustomResourceDefinition,
+    expected: &CustomResourceDefinition,
+) -> Vec<String> {
+    let known = |version: &str| {
+        expected
+            .spec
+            .versions
+            .iter()
+            .any(|expected| expected.name == version)
+    };
+
+    let mut problems = vec![];
+    if let Some(status) = &installed.status {
+        for version in status
+            .stored_versions
+            .iter()
+            .filter(|version| !known(version))
+        {
+            problems.push(format!(
+                "objects are stored in the unknown version [{}]",
+                version
+            ));
+        }
+    }
+    for version in installed
+        .spec
+        .versions
+        .iter()
+        .filter(|version| version.served && !known(&version.name))
+    {
+        problems.push(format!("the unknown version [{}] is served", version.name));
+    }
+    for version in &expected.spec.versions {
+        let installed_version = installed
+            .spec
+            .versions
+            .iter()
+            .find(|installed| installed.name == version.name && installed.served);
+        let installed_version = match installed_version {
+            Some(installed_version) => installed_version,
+            None => {
+                problems.push(format!("the version [{}] is not served", version.name));
+                continue;
+            }
+        };
+        compare_schemas(
+            &format!("{}:", version.name),
+            &schema(installed_version),
+            &schema(version),
+            &mut problems,
+        );
+    }
+    problems
+}
+
+/// Returns the OpenAPI schema of `version`, an empty one if it has none.
+fn schema(version: &CustomResourceDefinitionVersion) -> JSONSchemaProps {
+    version
+        .schema
+        .as_ref()
+        .and_then(|validation| validation.open_api_v3_schema.clone())
+        .unwrap_or_default()
+}
+
+/// Adds the fields which are missing in the `installed` schema or unknown in the `expected` one
+/// at `path` to `problems`.
+fn compare_schemas(
+    path: &str,
+    installed: &JSONSchemaProps,
+    expected: &JSONSchemaProps,
+    problems: &mut Vec<String>,
+) {
+    // nothing is pruned from or validated in free-form objects
+    if installed.x_kubernetes_preserve_unknown_fields == Some(true)
+        || expected.x_kubernetes_preserve_unknown_fields == Some(true)
+    {
+        return;
+    }
+
+    for (name, expected_property) in &expected.properties {
+        let property_path = format!("{}.{}", path, name);
+        match installed.properties.get(name) {
+            Some(installed_property) => compare_schemas(
+                &property_path,
+                installed_property,
+                expected_property,
+                problems,
+            ),
+            None => problems.push(format!(
+                "the field [{}] is missing and would be pruned",
+                property_path
+            )),
+        }
+    }
+    for name in installed
+        .properties
+        .keys()
+        .filter(|name| !expected.properties.contains_key(*name))
+    {
+        problems.push(format!(
+            "the field [{}.{}] is unknown to the operator",
+            path, name
+        ));
+    }
+
+    if let (
+        Some(JSONSchemaPropsOrArray::Schema(installed_items)),
+        Some(JSONSchemaPropsOrArray::Schema(expected_items)),
+    ) = (&installed.items, &expected.items)
+    {
+        compare_schemas(
+            &format!("{}[]", path),
+            installed_items,
+            expected_items,
+            problems,
+        );
+    }
+    if let (
+        Some(JSONSchemaPropsOrBool::Schema(installed_values)),
+        Some(JSONSchemaPropsOrBool::Schema(expected_values)),
+    ) = (
+        &installed.additional_properties,
+        &expected.additional_properties,
+    ) {
+        compare_schemas(
+            &format!("{}.*", path),
+            installed_values,
+            expected_values,
+            problems,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_crds_are_structural() {
+        for crd in crds() {
+            for version in &crd.spec.versions {
+                let schema = version
+                    .schema
+                    .as_ref()
+                    .and_then(|validation| validation.open_api_v3_schema.as_ref())
+                    .unwrap();
+                assert_eq!(schema.type_.as_deref(), Some("object"), "{}", crd.name());
+                assert!(schema.properties.contains_key("spec"), "{}", crd.name());
+                assert!(
+                    version
+                        .subresources
+                        .as_ref()
+                        .and_then(|subresources| subresources.status.as_ref())
+                        .is_some(),
+                    "{}",
+                    crd.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_cluster_crd_has_scale_subresource() {
+        let crd = ZookeeperCluster::crd();
+        let scale = crd.spec.versions[0]
+            .subresources
+            .as_ref()
+            .and_then(|subresources| subresources.scale.as_ref())
+            .unwrap();
+        assert_eq!(scale.spec_replicas_path, ".spec.replicas");
+        assert_eq!(scale.status_replicas_path, ".status.replicas");
+        assert_eq!(
+            scale.label_selector_path.as_deref(),
+            Some(".status.selector")
+        );
+    }
+
+    #[test]
+    fn test_crds_yaml() {
+        let yaml = crds_yaml().unwrap();
+        assert!(yaml.contains("apiVersion: apiextensions.k8s.io/v1\n"));
+
+        let names = serde_yaml::Deserializer::from_str(&yaml)
+            .map(|document| {
+                CustomResourceDefinition::deserialize(document)
+                    .unwrap()
+                    .name()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "zookeeperclusters.zookeeper.stackable.tech",
+                "zookeeperznodes.zookeeper.stackable.tech",
+                "zookeeperbenchmarks.zookeeper.stackable.tech",
+            ]
+        );
+    }
+
+    /// Returns the properties of `spec` in the schema of the first version of `crd`.
+    fn spec_properties(
+        crd: &mut CustomResourceDefinition,
+    ) -> &mut BTreeMap<String, JSONSchemaProps> {
+        &mut crd.spec.versions[0]
+            .schema
+            .as_mut()
+            .and_then(|validation| validation.open_api_v3_schema.as_mut())
+            .unwrap()
+            .properties
+            .get_mut("spec")
+            .unwrap()
+            .properties
+    }
+
+    #[test]
+    fn test_same_crd_is_compatible() {
+        let crd = ZookeeperCluster::crd();
+
+        assert_eq!(incompatibilities(&crd, &crd), Vec::<String>::new());
+    }
+
+    #[rstest]
+    #[case::older_crd(
+        |crd: &mut CustomResourceDefinition| {
+            spec_properties(crd).remove("replicas");
+        },
+        "v1alpha1:.spec.replicas] is missing"
+    )]
+    #[case::newer_crd(
+        |crd: &mut CustomResourceDefinition| {
+            spec_properties(crd).insert("newField".to_string(), JSONSchemaProps::default());
+        },
+        "v1alpha1:.spec.newField] is unknown"
+    )]
+    #[case::unknown_served_version(
+        |crd: &mut CustomResourceDefinition| {
+            let mut version = crd.spec.versions[0].clone();
+            version.name = "v1beta1".to_string();
+            crd.spec.versions.push(version);
+        },
+        "the unknown version [v1beta1] is served"
+    )]
+    #[case::unknown_stored_version(
+        |crd: &mut CustomResourceDefinition| {
+            crd.status = serde_json::from_value(serde_json::json!({
+                "acceptedNames": {"kind": "ZookeeperCluster", "plural": "zookeeperclusters"},
+                "storedVersions": ["v1alpha1", "v1beta1"]
+            }))
+            .unwrap();
+        },
+        "stored in the unknown version [v1beta1]"
+    )]
+    #[case::version_not_served(
+        |crd: &mut CustomResourceDefinition| crd.spec.versions[0].served = false,
+        "the version [v1alpha1] is not served"
+    )]
+    fn test_incompatible_crd(
+        #[case] change: fn(&mut CustomResourceDefinition),
+        #[case] expected_problem: &str,
+    ) {
+        let expected = ZookeeperCluster::crd();
+        let mut installed = expected.clone();
+        change(&mut installed);
+
+        let problems = incompatibilities(&installed, &expected);
+
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert!(problems[0].contains(expected_problem), "{:?}", problems);
+    }
+}
@@ -0,0 +1,172 @@
+//! Cron schedules (`minute hour day-of-month month day-of-week`) in UTC, e.g. of `spec.backup`.
+//!
+//! Fields are numbers, `*`, ranges (`1-5`), steps (`*/15`, `0-30/10`) and lists of them. Like in
+//! cron, a day matches if both the day of month and the day of week match, unless both are
+//! restricted, then either one has to match. Sunday is `0` or `7`.
+use k8s_openapi::chrono::{DateTime, Datelike, Duration as ChronoDuration, Timelike, Utc};
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+/// How far ahead the next matching time is searched, schedules like `0 0 31 2 *` never match.
+const MAX_DAYS_AHEAD: i64 = 5 * 366;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CronSchedule {
+    minutes: BTreeSet<u32>,
+    hours: BTreeSet<u32>,
+    days_of_month: BTreeSet<u32>,
+    months: BTreeSet<u32>,
+    days_of_week: BTreeSet<u32>,
+    days_of_month_restricted: bool,
+    days_of_week_restricted: bool,
+}
+
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(schedule: &str) -> Result<Self, Self::Err> {
+        let fields = schedule.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err(format!(
+                "[{}] must have 5 fields: minute hour day-of-month month day-of-week",
+                schedule
+            ));
+        }
+        let days_of_week = parse_field(fields[4], "day-of-week", 0, 7)?
+            .into_iter()
+            .map(|day| day % 7)
+            .collect();
+        Ok(CronSchedule {
+            minutes: parse_field(fields[0], "minute", 0, 59)?,
+            hours: parse_field(fields[1], "hour", 0, 23)?,
+            days_of_month: parse_field(fields[2], "day-of-month", 1, 31)?,
+            months: parse_field(fields[3], "month", 1, 12)?,
+            days_of_week,
+            days_of_month_restricted: !fields[2].starts_with('*'),
+            days_of_week_restricted: !fields[4].starts_with('*'),
+        })
+    }
+}
+
+impl CronSchedule {
+    /// Returns the first matching time strictly after `time`, `None` if there is none within the
+    /// next years.
+    pub fn next_after(&self, time: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = *time + ChronoDuration::minutes(1);
+        for days in 0..MAX_DAYS_AHEAD {
+            let date = start.date() + ChronoDuration::days(days);
+            if !self.matches_day(
+                date.day(),
+                date.month(),
+                date.weekday().num_days_from_sunday(),
+            ) {
+                continue;
+            }
+            for hour in &self.hours {
+                for minute in &self.minutes {
+                    if days == 0 && (*hour, *minute) < (start.hour(), start.minute()) {
+                        continue;
+                    }
+                    return Some(date.and_hms(*hour, *minute, 0));
+                }
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, day_of_month: u32, month: u32, day_of_week: u32) -> bool {
+        let day_of_month = self.days_of_month.contains(&day_of_month);
+        let day_of_week = self.days_of_week.contains(&day_of_week);
+        self.months.contains(&month)
+            && if self.days_of_month_restricted && self.days_of_week_restricted {
+                day_of_month || day_of_week
+            } else {
+                day_of_month && day_of_week
+            }
+    }
+}
+
+fn parse_field(field: &str, name: &str, min: u32, max: u32) -> Result<BTreeSet<u32>, String> {
+    let invalid = || format!("Invalid {} [{}]", name, field);
+    let parse = |value: &str| {
+        value
+            .parse::<u32>()
+            .ok()
+            .filter(|value| (min..=max).contains(value))
+            .ok_or_else(invalid)
+    };
+
+    let mut values = BTreeSet::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step.parse::<usize>().map_err(|_| invalid())?)),
+            None => (part, None),
+        };
+        let (first, last) = if range == "*" {
+            (min, max)
+        } else if let Some((first, last)) = range.split_once('-') {
+            (parse(first)?, parse(last)?)
+        } else if step.is_some() {
+            (parse(range)?, max)
+        } else {
+            (parse(range)?, parse(range)?)
+        };
+        if first > last || step == Some(0) {
+            return Err(invalid());
+        }
+        values.extend((first..=last).step_by(step.unwrap_or(1)));
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn time(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[rstest]
+    #[case("0 3 * * *", "2021-09-01T12:00:00Z", "2021-09-02T03:00:00Z")]
+    #[case("0 3 * * *", "2021-09-01T02:59:59Z", "2021-09-01T03:00:00Z")]
+    #[case("0 3 * * *", "2021-09-01T03:00:00Z", "2021-09-02T03:00:00Z")]
+    #[case("*/15 * * * *", "2021-09-01T12:07:30Z", "2021-09-01T12:15:00Z")]
+    #[case("30 1 1 * *", "2021-12-05T00:00:00Z", "2022-01-01T01:30:00Z")]
+    // 2021-09-01 is a Wednesday
+    #[case("0 0 * * 0", "2021-09-01T12:00:00Z", "2021-09-05T00:00:00Z")]
+    #[case("0 0 * * 7", "2021-09-01T12:00:00Z", "2021-09-05T00:00:00Z")]
+    #[case("0 0 * * 1-5", "2021-09-03T12:00:00Z", "2021-09-06T00:00:00Z")]
+    // either the day of month or the day of week has to match
+    #[case("0 0 15 * 0", "2021-09-01T12:00:00Z", "2021-09-05T00:00:00Z")]
+    #[case("0 0 29 2 *", "2021-03-01T00:00:00Z", "2024-02-29T00:00:00Z")]
+    fn test_next_after(#[case] schedule: &str, #[case] after: &str, #[case] expected: &str) {
+        let schedule: CronSchedule = schedule.parse().unwrap();
+
+        assert_eq!(schedule.next_after(&time(after)), Some(time(expected)));
+    }
+
+    #[test]
+    fn test_never() {
+        let schedule: CronSchedule = "0 0 31 2 *".parse().unwrap();
+
+        assert_eq!(schedule.next_after(&time("2021-09-01T12:00:00Z")), None);
+    }
+
+    #[rstest]
+    #[case("0 3 * *")]
+    #[case("60 * * * *")]
+    #[case("* 24 * * *")]
+    #[case("* * 0 * *")]
+    #[case("* * * 13 *")]
+    #[case("* * * * 8")]
+    #[case("*/0 * * * *")]
+    #[case("5-1 * * * *")]
+    #[case("@daily")]
+    fn test_invalid(#[case] schedule: &str) {
+        assert!(schedule.parse::<CronSchedule>().is_err());
+    }
+}
@@ -0,0 +1,112 @@
+//! The defaults of the optional settings of the spec.
+//!
+//! Minimal manifests rely on the defaults of the operator for the probe timings, the compute
+//! resources, the autopurge settings and the number of servers. They are applied where the
+//! respective objects are built (e.g. [`crate::probes::build_probes`]), [`default_spec`] collects
+//! the resulting values for `status.effectiveSpec`, so users can see what a manifest resulted in.
+use crate::compute_resources;
+use crate::probes;
+
+use stackable_zookeeper_crd::autopurge::{ZookeeperAutopurge, PURGE_INTERVAL, SNAP_RETAIN_COUNT};
+use stackable_zookeeper_crd::{ZookeeperClusterSpec, ZookeeperEffectiveSpec};
+use std::collections::BTreeMap;
+
+/// Returns the effective values of the settings of `spec` which have defaults.
+///
+/// # Arguments
+///
+/// - `spec` - The spec of the cluster.
+/// - `replicas` - The number of servers per role group, which depends on the matching nodes if
+///   `replicas` is not set.
+///
+pub fn default_spec(
+    spec: &ZookeeperClusterSpec,
+    replicas: BTreeMap<String, u16>,
+) -> ZookeeperEffectiveSpec {
+    ZookeeperEffectiveSpec {
+        replicas,
+        probes: probes::default_probes(spec.probes.as_ref()),
+        resources: compute_resources::default_resources(spec.resources.as_ref()),
+        autopurge: default_autopurge(spec),
+    }
+}
+
+/// Returns the autopurge settings of `spec.autopurge` or, if not set, of the `autopurge.*`
+/// properties in `spec.config`, with the defaults filled in.
+fn default_autopurge(spec: &ZookeeperClusterSpec) -> ZookeeperAutopurge {
+    let configured = spec.autopurge.clone().unwrap_or_else(|| {
+        let property = |key: &str| spec.config.get(key).and_then(|value| value.parse().ok());
+        ZookeeperAutopurge {
+            snap_retain_count: property(SNAP_RETAIN_COUNT),
+            purge_interval_hours: property(PURGE_INTERVAL),
+        }
+    });
+    ZookeeperAutopurge {
+        snap_retain_count: Some(configured.snap_retain_count()),
+        purge_interval_hours: Some(configured.purge_interval_hours()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use stackable_zookeeper_crd::autopurge::{
+        DEFAULT_PURGE_INTERVAL_HOURS, DEFAULT_SNAP_RETAIN_COUNT,
+    };
+
+    fn spec(yaml: &str) -> ZookeeperClusterSpec {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_default_spec_of_minimal_spec() {
+        let spec = spec(indoc! {"
+            version: 3.5.8
+            servers:
+              roleGroups: {}
+        "});
+
+        let effective_spec = default_spec(&spec, BTreeMap::new());
+
+        let liveness = effective_spec.probes.liveness.unwrap();
+        assert_eq!(liveness.initial_delay_seconds, Some(30));
+        assert_eq!(liveness.failure_threshold, Some(6));
+        assert_eq!(
+            effective_spec.resources.limits.unwrap().memory,
+            Some("1Gi".to_string())
+        );
+        assert_eq!(
+            effective_spec.autopurge,
+            ZookeeperAutopurge {
+                snap_retain_count: Some(DEFAULT_SNAP_RETAIN_COUNT),
+                purge_interval_hours: Some(DEFAULT_PURGE_INTERVAL_HOURS),
+            }
+        );
+    }
+
+    #[test]
+    fn test_default_spec_keeps_configured_values() {
+        let spec = spec(indoc! {"
+            version: 3.5.8
+            servers:
+              roleGroups: {}
+            probes:
+              readiness:
+                periodSeconds: 30
+            config:
+              autopurge.purgeInterval: '1'
+        "});
+
+        let effective_spec = default_spec(&spec, BTreeMap::new());
+
+        let readiness = effective_spec.probes.readiness.unwrap();
+        assert_eq!(readiness.period_seconds, Some(30));
+        assert_eq!(readiness.timeout_seconds, Some(5));
+        assert_eq!(effective_spec.autopurge.purge_interval_hours, Some(1));
+        assert_eq!(
+            effective_spec.autopurge.snap_retain_count,
+            Some(DEFAULT_SNAP_RETAIN_COUNT)
+        );
+    }
+}
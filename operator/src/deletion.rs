@@ -0,0 +1,118 @@
+//! Orphaning of the objects of a deleted ZookeeperCluster with the `Orphan` deletion policy.
+//!
+//! The pods, the ConfigMaps, the super user Secret and the client Secret are owned by the
+//! cluster, so they would be
+//! garbage collected together with it. Removing the owner references keeps the servers running,
+//! e.g. to hand them over to other tooling.
+use crate::apply;
+use crate::error::Error;
+
+use k8s_openapi::api::core::v1::{ConfigMap, Pod, Secret};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ObjectMeta, OwnerReference};
+use kube::api::{ListParams, Patch};
+use kube::{Api, Resource, ResourceExt};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use stackable_operator::client::Client;
+use stackable_zookeeper_crd::authentication::super_user_secret_name;
+use stackable_zookeeper_crd::client_secret::client_secret_name;
+use stackable_zookeeper_crd::ZookeeperCluster;
+use std::fmt::Debug;
+use tracing::info;
+
+/// Removes the owner references to `cluster` from `pods`, the ConfigMaps matching
+/// `label_selector`, the super user Secret and the client Secret.
+pub async fn orphan_objects(
+    client: &Client,
+    cluster: &ZookeeperCluster,
+    pods: &[Pod],
+    label_selector: &str,
+) -> Result<(), Error> {
+    let namespace = cluster.namespace().unwrap_or_else(|| "default".to_string());
+    let owner_uid = cluster.uid().unwrap_or_default();
+
+    let pods_api: Api<Pod> = client.get_namespaced_api(&namespace);
+    for pod in pods {
+        orphan(&pods_api, pod, &owner_uid).await?;
+    }
+
+    let config_maps_api: Api<ConfigMap> = client.get_namespaced_api(&namespace);
+    for config_map in config_maps_api
+        .list(&ListParams::default().labels(label_selector))
+        .await?
+        .items
+    {
+        orphan(&config_maps_api, &config_map, &owner_uid).await?;
+    }
+
+    let secrets_api: Api<Secret> = client.get_namespaced_api(&namespace);
+    for secret_name in [
+        super_user_secret_name(&cluster.name()),
+        client_secret_name(&cluster.name()),
+    ] {
+        match secrets_api.get(&secret_name).await {
+            Ok(secret) => orphan(&secrets_api, &secret, &owner_uid).await?,
+            Err(kube::Error::Api(response)) if response.code == 404 => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+async fn orphan<K>(api: &Api<K>, object: &K, owner_uid: &str) -> Result<(), Error>
+where
+    K: Resource + Clone + DeserializeOwned + Debug,
+{
+    if let Some(owner_references) = remove_owner_reference(object.meta(), owner_uid) {
+        api.patch(
+            &object.name(),
+            &apply::merge_params(),
+            &Patch::Merge(json!({ "metadata": { "ownerReferences": owner_references } })),
+        )
+        .await?;
+        info!("Orphaned [{}]", object.name());
+    }
+    Ok(())
+}
+
+/// Returns the owner references of `metadata` without the ones to `owner_uid`, or `None` if
+/// there are none to remove.
+fn remove_owner_reference(metadata: &ObjectMeta, owner_uid: &str) -> Option<Vec<OwnerReference>> {
+    let remaining = metadata
+        .owner_references
+        .iter()
+        .filter(|owner_reference| owner_reference.uid != owner_uid)
+        .cloned()
+        .collect::<Vec<_>>();
+    if remaining.len() == metadata.owner_references.len() {
+        None
+    } else {
+        Some(remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner_reference(uid: &str) -> OwnerReference {
+        OwnerReference {
+            uid: uid.to_string(),
+            ..OwnerReference::default()
+        }
+    }
+
+    #[test]
+    fn test_remove_owner_reference() {
+        let metadata = ObjectMeta {
+            owner_references: vec![owner_reference("cluster"), owner_reference("other")],
+            ..ObjectMeta::default()
+        };
+
+        assert_eq!(
+            remove_owner_reference(&metadata, "cluster"),
+            Some(vec![owner_reference("other")])
+        );
+        assert_eq!(remove_owner_reference(&metadata, "unknown"), None);
+    }
+}
@@ -0,0 +1,208 @@
+//! Comparing the desired state of objects with their actual state before writing them.
+//!
+//! A write which does not change anything still costs an API request and, for the status, a new
+//! `resourceVersion` which wakes up every watch on the cluster. With many clusters this adds up,
+//! so the operator only writes what differs:
+//!
+//! - Status updates are JSON merge patches, which are reduced to the fields whose value changes
+//!   (see [`prune_merge_patch`]).
+//! - Objects written with server-side apply carry the hash of the applied object in the
+//!   [`APPLIED_HASH_ANNOTATION`]. They are only applied again if the hash changed, i.e. the
+//!   operator sets other fields than before, or if one of the applied fields was changed by
+//!   someone else (see [`is_applied`]). Comparing only the fields would miss fields the operator
+//!   stopped setting, which have to be removed by applying the object again.
+use crate::error::Error;
+
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+/// Annotation holding the hash of the object the operator applied, see [`hash_applied_object`].
+pub const APPLIED_HASH_ANNOTATION: &str = "zookeeper.stackable.tech/applied-hash";
+
+/// Returns the part of the merge `patch` which changes `current`, `None` if applying the patch
+/// would not change anything.
+///
+/// Arrays are replaced as a whole by merge patches, so they are only considered unchanged if they
+/// are equal.
+pub fn prune_merge_patch(patch: &Value, current: &Value) -> Option<Value> {
+    let patch_fields = match patch.as_object() {
+        Some(patch_fields) => patch_fields,
+        None if patch == current => return None,
+        None => return Some(patch.clone()),
+    };
+    let current_fields = match current.as_object() {
+        Some(current_fields) => current_fields,
+        None => return Some(patch.clone()),
+    };
+
+    let mut changed = Map::new();
+    for (key, value) in patch_fields {
+        let current_value = current_fields.get(key).unwrap_or(&Value::Null);
+        if value.is_null() {
+            // null removes the field
+            if !current_value.is_null() {
+                changed.insert(key.clone(), Value::Null);
+            }
+        } else if value.is_object() && current_value.is_object() {
+            if let Some(changed_value) = prune_merge_patch(value, current_value) {
+                changed.insert(key.clone(), changed_value);
+            }
+        } else if value != current_value {
+            changed.insert(key.clone(), value.clone());
+        }
+    }
+
+    if changed.is_empty() {
+        None
+    } else {
+        Some(Value::Object(changed))
+    }
+}
+
+/// Checks whether the `actual` object already contains all fields of the `desired` object.
+///
+/// Fields which are only set in `actual` are ignored, they are defaulted by the API server or set
+/// by other field managers. This also applies to the items of arrays of the same length, e.g. the
+/// API server adds the `protocol` to the ports of a Service.
+pub fn is_applied(desired: &Value, actual: &Value) -> bool {
+    match (desired, actual) {
+        (Value::Object(desired), Value::Object(actual)) => desired
+            .iter()
+            .all(|(key, value)| is_applied(value, actual.get(key).unwrap_or(&Value::Null))),
+        (Value::Array(desired), Value::Array(actual)) => {
+            desired.len() == actual.len()
+                && desired
+                    .iter()
+                    .zip(actual)
+                    .all(|(desired, actual)| is_applied(desired, actual))
+        }
+        (desired, actual) => desired == actual,
+    }
+}
+
+/// Calculates a stable hash of the object to apply, stored in the [`APPLIED_HASH_ANNOTATION`].
+pub fn hash_applied_object(object: &Value) -> Result<String, Error> {
+    let canonical = serde_json::to_vec(object)?;
+    Ok(format!("{:x}", Sha256::digest(&canonical)))
+}
+
+/// Sets the annotation `key` of the serialized `object` to `value`.
+pub fn set_annotation(object: &mut Value, key: &str, value: &str) {
+    if !object["metadata"]["annotations"].is_object() {
+        object["metadata"]["annotations"] = Value::Object(Map::new());
+    }
+    object["metadata"]["annotations"][key] = Value::String(value.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_prune_merge_patch() {
+        let current = json!({
+            "readyReplicas": 3,
+            "waiting": { "reason": "DisruptionBudget", "since": "2021-07-01T10:00:00Z" },
+            "capabilities": { "features": ["containerNodes", "clientTls"] }
+        });
+
+        assert_eq!(
+            prune_merge_patch(
+                &json!({
+                    "readyReplicas": 3,
+                    "waiting": { "reason": "DisruptionBudget" },
+                    "capabilities": { "features": ["containerNodes", "clientTls"] },
+                    "smokeTest": null
+                }),
+                &current
+            ),
+            None
+        );
+        assert_eq!(
+            prune_merge_patch(
+                &json!({
+                    "readyReplicas": 2,
+                    "waiting": { "reason": "DisruptionBudget", "since": "2021-07-01T10:00:00Z" },
+                    "capabilities": { "features": ["containerNodes"] }
+                }),
+                &current
+            ),
+            Some(json!({
+                "readyReplicas": 2,
+                "capabilities": { "features": ["containerNodes"] }
+            }))
+        );
+        assert_eq!(
+            prune_merge_patch(&json!({ "waiting": null, "replicas": 3 }), &current),
+            Some(json!({ "waiting": null, "replicas": 3 }))
+        );
+        assert_eq!(
+            prune_merge_patch(&json!({ "readyReplicas": 3 }), &Value::Null),
+            Some(json!({ "readyReplicas": 3 }))
+        );
+    }
+
+    #[test]
+    fn test_is_applied() {
+        let actual = json!({
+            "metadata": {
+                "name": "simple-admin",
+                "annotations": { "zookeeper.stackable.tech/applied-hash": "1234", "note": "kept" },
+                "resourceVersion": "42"
+            },
+            "spec": {
+                "ports": [{ "name": "http", "port": 8080, "protocol": "TCP" }],
+                "type": "ClusterIP"
+            }
+        });
+
+        assert!(is_applied(
+            &json!({
+                "metadata": {
+                    "name": "simple-admin",
+                    "annotations": { "zookeeper.stackable.tech/applied-hash": "1234" }
+                },
+                "spec": { "ports": [{ "name": "http", "port": 8080 }] }
+            }),
+            &actual
+        ));
+        assert!(!is_applied(
+            &json!({
+                "metadata": {
+                    "name": "simple-admin",
+                    "annotations": { "zookeeper.stackable.tech/applied-hash": "5678" }
+                }
+            }),
+            &actual
+        ));
+        assert!(!is_applied(
+            &json!({ "spec": { "ports": [{ "name": "http", "port": 8081 }] } }),
+            &actual
+        ));
+        assert!(!is_applied(
+            &json!({ "spec": { "ports": [{ "port": 8080 }, { "port": 8081 }] } }),
+            &actual
+        ));
+    }
+
+    #[test]
+    fn test_set_annotation() {
+        let mut object = json!({ "metadata": { "name": "simple-server" } });
+        set_annotation(&mut object, APPLIED_HASH_ANNOTATION, "1234");
+        set_annotation(&mut object, "note", "kept");
+
+        assert_eq!(
+            object,
+            json!({
+                "metadata": {
+                    "name": "simple-server",
+                    "annotations": {
+                        "zookeeper.stackable.tech/applied-hash": "1234",
+                        "note": "kept"
+                    }
+                }
+            })
+        );
+    }
+}
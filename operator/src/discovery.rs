@@ -0,0 +1,272 @@
+//! Controller publishing the discovery ConfigMap of every ZookeeperCluster.
+//!
+//! The ConfigMap is named `<cluster name>-discovery` and contains the connection string of the
+//! ensemble under the `ZOOKEEPER` key, so that applications can mount it or read their
+//! environment from it. With external access the connection string for clients outside of
+//! Kubernetes is added under the `ZOOKEEPER_EXTERNAL` key, with a federation the connection
+//! string of the whole ensemble under the `ZOOKEEPER_FEDERATION` key. This is a controller of its own (instead of a step of the cluster
+//! controller) because it watches the pods of the cluster: the connection string is updated
+//! within seconds after servers were added, removed or moved, independent of where the cluster
+//! controller is in its (possibly long running) rollout.
+//!
+//! The client Secret (see [`stackable_zookeeper_crd::client_secret`]) is published together with
+//! the ConfigMap. Changes of the super user Secret trigger a reconciliation because it is owned
+//! by the cluster as well (only the Secrets of the operator are watched), changes of the TLS
+//! Secret are picked up with the next periodic one.
+use crate::apply::{self, ConflictPolicy};
+use crate::authentication::read_super_user_credentials;
+use crate::client_secret;
+use crate::error::Error;
+use crate::watch_namespace::scoped_api;
+
+use async_trait::async_trait;
+use k8s_openapi::api::core::v1::{ConfigMap, Pod, Secret};
+use kube::api::ListParams;
+use kube::{Api, ResourceExt};
+use stackable_operator::client::Client;
+use stackable_operator::configmap;
+use stackable_operator::controller::Controller;
+use stackable_operator::controller::{ControllerStrategy, ReconciliationState};
+use stackable_operator::error::OperatorResult;
+use stackable_operator::labels::{APP_INSTANCE_LABEL, APP_MANAGED_BY_LABEL, APP_NAME_LABEL};
+use stackable_operator::reconcile::{
+    ReconcileFunctionAction, ReconcileResult, ReconciliationContext,
+};
+use stackable_zookeeper_crd::error::Error as CrdError;
+use stackable_zookeeper_crd::external_access::ZOOKEEPER_EXTERNAL_DISCOVERY_KEY;
+use stackable_zookeeper_crd::federation::ZOOKEEPER_FEDERATION_DISCOVERY_KEY;
+use stackable_zookeeper_crd::util::{get_zk_connection_info, ZookeeperReference};
+use stackable_zookeeper_crd::znode::ZOOKEEPER_DISCOVERY_KEY;
+use stackable_zookeeper_crd::{ZookeeperCluster, APP_NAME, MANAGED_BY};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use tracing::debug;
+
+type DiscoveryReconcileResult = ReconcileResult<Error>;
+
+/// Returns the name of the discovery ConfigMap of the cluster with the given name.
+pub fn discovery_config_map_name(cluster_name: &str) -> String {
+    format!("{}-discovery", cluster_name)
+}
+
+struct DiscoveryState {
+    context: ReconciliationContext<ZookeeperCluster>,
+}
+
+impl DiscoveryState {
+    /// Creates or updates the discovery ConfigMap with the current connection string.
+    async fn publish_discovery_config_map(&self) -> DiscoveryReconcileResult {
+        let reference = ZookeeperReference {
+            namespace: self.context.namespace(),
+            name: self.context.name(),
+            chroot: None,
+        };
+        let connection_info = match get_zk_connection_info(&self.context.client, &reference).await {
+            Ok(connection_info) => connection_info,
+            // There is nothing to publish until the first server was created, the pod watch
+            // triggers the next reconciliation.
+            Err(CrdError::NoZookeeperPodsAvailableForConnectionInfo { .. }) => {
+                debug!(
+                    "ZookeeperCluster {}: No servers yet, not publishing a discovery ConfigMap",
+                    self.context.log_name()
+                );
+                return Ok(ReconcileFunctionAction::Done);
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut labels = BTreeMap::new();
+        labels.insert(APP_NAME_LABEL.to_string(), APP_NAME.to_string());
+        labels.insert(APP_INSTANCE_LABEL.to_string(), self.context.name());
+        labels.insert(APP_MANAGED_BY_LABEL.to_string(), MANAGED_BY.to_string());
+
+        let mut data = BTreeMap::new();
+        data.insert(
+            ZOOKEEPER_DISCOVERY_KEY.to_string(),
+            connection_info.connection_string,
+        );
+        if let Some(external_connection_string) = self
+            .context
+            .resource
+            .status
+            .as_ref()
+            .and_then(|status| status.external_access.as_ref())
+            .and_then(|external_access| external_access.connection_string())
+        {
+            data.insert(
+                ZOOKEEPER_EXTERNAL_DISCOVERY_KEY.to_string(),
+                external_connection_string,
+            );
+        }
+        if let Some(federation_connection_string) = self
+            .context
+            .resource
+            .status
+            .as_ref()
+            .and_then(|status| status.federation.as_ref())
+            .and_then(|federation| federation.connection_string.clone())
+        {
+            data.insert(
+                ZOOKEEPER_FEDERATION_DISCOVERY_KEY.to_string(),
+                federation_connection_string,
+            );
+        }
+
+        let config_map = configmap::build_config_map(
+            &self.context.resource,
+            &discovery_config_map_name(&self.context.name()),
+            &self.context.namespace(),
+            labels,
+            data,
+        )?;
+        configmap::create_config_map(&self.context.client, config_map).await?;
+
+        self.publish_client_secret(&connection_info.connection_string)
+            .await?;
+
+        Ok(ReconcileFunctionAction::Done)
+    }
+
+    /// Creates or updates the client Secret with the connection strings and the TLS and SASL
+    /// materials of the enabled features. Materials whose Secrets do not exist yet (e.g. while
+    /// cert-manager issues the certificate) are left out until they do.
+    async fn publish_client_secret(&self, connection_string: &str) -> Result<(), Error> {
+        let cluster = &self.context.resource;
+        let namespace = self.context.namespace();
+        let secrets_api: Api<Secret> = self.context.client.get_namespaced_api(&namespace);
+
+        let (tls_connection_string, tls) = match cluster.spec.tls.as_ref() {
+            Some(tls) if tls.client => {
+                let pods_api: Api<Pod> = self.context.client.get_namespaced_api(&namespace);
+                let pods = pods_api
+                    .list(&ListParams::default().labels(&format!(
+                        "{}={},{}={}",
+                        APP_NAME_LABEL,
+                        APP_NAME,
+                        APP_INSTANCE_LABEL,
+                        self.context.name()
+                    )))
+                    .await?;
+                let tls_data = match (
+                    secrets_api.get(&tls.secret_name).await,
+                    secrets_api.get(&tls.password_secret_name()).await,
+                ) {
+                    (Ok(tls_secret), Ok(password_secret)) => {
+                        Some(client_secret::tls_data(&tls_secret, &password_secret)?)
+                    }
+                    (Err(kube::Error::Api(response)), _) | (_, Err(kube::Error::Api(response)))
+                        if response.code == 404 =>
+                    {
+                        debug!(
+                            "ZookeeperCluster {}: The TLS Secret does not exist yet, publishing the client Secret without the truststore",
+                            self.context.log_name()
+                        );
+                        None
+                    }
+                    (Err(err), _) | (_, Err(err)) => return Err(err.into()),
+                };
+                (client_secret::tls_connection_string(&pods.items), tls_data)
+            }
+            _ => (None, None),
+        };
+        let sasl = match &cluster.spec.authentication {
+            Some(authentication) if authentication.client_credentials => {
+                match read_super_user_credentials(&self.context.client, &namespace, &cluster.name())
+                    .await
+                {
+                    Ok((username, password)) => {
+                        Some(client_secret::sasl_data(&username, &password))
+                    }
+                    // created by the cluster controller, the next reconciliation adds them
+                    Err(Error::KubeError {
+                        source: kube::Error::Api(response),
+                    }) if response.code == 404 => None,
+                    Err(err) => return Err(err),
+                }
+            }
+            _ => None,
+        };
+
+        let secret = client_secret::build_client_secret(
+            cluster,
+            connection_string,
+            tls_connection_string.as_deref(),
+            tls,
+            sasl,
+        )?;
+        let policy = ConflictPolicy::ForceIfOwnedBy(cluster.uid().unwrap_or_default());
+        apply::apply(&secrets_api, &secret, &policy).await?;
+        Ok(())
+    }
+}
+
+impl ReconciliationState for DiscoveryState {
+    type Error = Error;
+
+    fn reconcile(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<ReconcileFunctionAction, Self::Error>> + Send + '_>>
+    {
+        Box::pin(async move {
+            // The ConfigMap is owned by the cluster, Kubernetes deletes it together with it.
+            if self.context.resource.metadata.deletion_timestamp.is_some() {
+                return Ok(ReconcileFunctionAction::Done);
+            }
+            self.publish_discovery_config_map().await
+        })
+    }
+}
+
+struct DiscoveryStrategy {}
+
+#[async_trait]
+impl ControllerStrategy for DiscoveryStrategy {
+    type Item = ZookeeperCluster;
+    type State = DiscoveryState;
+    type Error = Error;
+
+    async fn init_reconcile_state(
+        &self,
+        context: ReconciliationContext<Self::Item>,
+    ) -> Result<Self::State, Self::Error> {
+        Ok(DiscoveryState { context })
+    }
+}
+
+/// Creates the controller for the discovery ConfigMaps and runs it until the process exits.
+pub async fn create_discovery_controller(
+    client: Client,
+    namespace: Option<String>,
+    requeue_interval: Duration,
+) -> OperatorResult<()> {
+    let zk_api: Api<ZookeeperCluster> = scoped_api(&client, namespace.as_deref());
+    let pods_api: Api<Pod> = scoped_api(&client, namespace.as_deref());
+    let config_maps_api: Api<ConfigMap> = scoped_api(&client, namespace.as_deref());
+    let secrets_api: Api<Secret> = scoped_api(&client, namespace.as_deref());
+
+    let controller = Controller::new(zk_api)
+        .owns(pods_api, ListParams::default())
+        .owns(config_maps_api, ListParams::default())
+        .owns(
+            secrets_api,
+            ListParams::default().labels(&format!("{}={}", APP_NAME_LABEL, APP_NAME)),
+        );
+
+    controller
+        .run(client, DiscoveryStrategy {}, requeue_interval)
+        .await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discovery_config_map_name() {
+        assert_eq!(discovery_config_map_name("simple"), "simple-discovery");
+    }
+}
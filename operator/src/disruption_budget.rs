@@ -0,0 +1,258 @@
+//! PodDisruptionBudgets of the clusters and checks whether budgets allow the operator to take
+//! down a pod.
+//!
+//! Every cluster gets a budget allowing at most `(replicas - 1) / 2` voluntary disruptions, so
+//! e.g. draining nodes never takes down the quorum.
+//!
+//! The operator deletes pods directly (instead of evicting them), which bypasses
+//! PodDisruptionBudgets. To not take down more servers than allowed (e.g. while a node is being
+//! drained) rolling operations check the budgets covering a pod first and wait if there is no
+//! disruption left.
+use crate::error::Error;
+
+use k8s_openapi::api::core::v1::Pod;
+use k8s_openapi::api::policy::v1::{PodDisruptionBudget, PodDisruptionBudgetSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector;
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use kube::ResourceExt;
+use stackable_operator::builder::ObjectMetaBuilder;
+use stackable_operator::labels::build_common_labels_for_all_managed_resources;
+use stackable_zookeeper_crd::{ZookeeperCluster, APP_NAME};
+use std::collections::BTreeMap;
+
+/// Returns the name of the PodDisruptionBudget of the given cluster.
+pub fn budget_name(cluster_name: &str) -> String {
+    format!("{}-server", cluster_name)
+}
+
+/// Returns the number of servers which can be unavailable without losing the quorum.
+pub fn max_unavailable(replicas: usize) -> usize {
+    replicas.saturating_sub(1) / 2
+}
+
+/// Builds the PodDisruptionBudget covering all servers of `cluster`.
+pub fn build_budget(
+    cluster: &ZookeeperCluster,
+    replicas: usize,
+) -> Result<PodDisruptionBudget, Error> {
+    let cluster_name = cluster.name();
+    Ok(PodDisruptionBudget {
+        metadata: ObjectMetaBuilder::new()
+            .name(budget_name(&cluster_name))
+            .namespace(cluster.metadata.namespace.as_deref().unwrap_or_default())
+            .ownerreference_from_resource(cluster, Some(true), Some(true))?
+            .build()?,
+        spec: Some(PodDisruptionBudgetSpec {
+            max_unavailable: Some(IntOrString::Int(max_unavailable(replicas) as i32)),
+            selector: Some(LabelSelector {
+                match_labels: build_common_labels_for_all_managed_resources(
+                    APP_NAME,
+                    &cluster_name,
+                ),
+                ..LabelSelector::default()
+            }),
+            ..PodDisruptionBudgetSpec::default()
+        }),
+        ..PodDisruptionBudget::default()
+    })
+}
+
+/// Checks whether `budget` is the budget of the cluster `cluster_name` and does not allow any
+/// disruption at all (i.e. the cluster has less than three servers).
+///
+/// Such a budget must not block the restarts of the operator, otherwise they would never happen.
+pub fn is_own_budget_without_disruptions(budget: &PodDisruptionBudget, cluster_name: &str) -> bool {
+    budget.name() == budget_name(cluster_name)
+        && budget
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.max_unavailable.as_ref())
+            == Some(&IntOrString::Int(0))
+}
+
+/// Checks whether `labels` are matched by `selector`.
+///
+/// Following the `policy/v1` semantics an empty selector matches all pods, budgets without a
+/// selector match none (see [`find_blocking_budget`]).
+pub fn selector_matches(selector: &LabelSelector, labels: &BTreeMap<String, String>) -> bool {
+    let labels_match = selector
+        .match_labels
+        .iter()
+        .all(|(key, value)| labels.get(key) == Some(value));
+
+    let expressions_match = selector.match_expressions.iter().all(|requirement| {
+        let value = labels.get(&requirement.key);
+        match requirement.operator.as_str() {
+            "In" => value
+                .map(|value| requirement.values.contains(value))
+                .unwrap_or(false),
+            "NotIn" => value
+                .map(|value| !requirement.values.contains(value))
+                .unwrap_or(true),
+            "Exists" => value.is_some(),
+            "DoesNotExist" => value.is_none(),
+            _ => false,
+        }
+    });
+
+    labels_match && expressions_match
+}
+
+/// Returns the first PodDisruptionBudget which covers `pod` but does not allow any further
+/// disruptions.
+pub fn find_blocking_budget<'a>(
+    budgets: &'a [PodDisruptionBudget],
+    pod: &Pod,
+) -> Option<&'a PodDisruptionBudget> {
+    budgets.iter().find(|budget| {
+        let covers_pod = budget
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.selector.as_ref())
+            .map(|selector| selector_matches(selector, pod.labels()))
+            .unwrap_or(false);
+        let disruptions_allowed = budget
+            .status
+            .as_ref()
+            .map(|status| status.disruptions_allowed)
+            .unwrap_or(0);
+
+        covers_pod && disruptions_allowed < 1
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use indoc::indoc;
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[rstest]
+    #[case::match_labels("matchLabels: {app: zookeeper}", true)]
+    #[case::match_labels_mismatch("matchLabels: {app: kafka}", false)]
+    #[case::empty("{}", true)]
+    #[case::in_(
+        "matchExpressions: [{key: role, operator: In, values: [server]}]",
+        true
+    )]
+    #[case::not_in(
+        "matchExpressions: [{key: role, operator: NotIn, values: [server]}]",
+        false
+    )]
+    #[case::exists("matchExpressions: [{key: app, operator: Exists}]", true)]
+    #[case::does_not_exist("matchExpressions: [{key: app, operator: DoesNotExist}]", false)]
+    #[case::combined(
+        "{matchLabels: {app: zookeeper}, matchExpressions: [{key: group, operator: Exists}]}",
+        false
+    )]
+    fn test_selector_matches(#[case] selector: &str, #[case] expected: bool) {
+        let selector: LabelSelector = serde_yaml::from_str(selector).unwrap();
+        let labels = labels(&[("app", "zookeeper"), ("role", "server")]);
+
+        assert_eq!(selector_matches(&selector, &labels), expected);
+    }
+
+    #[test]
+    fn test_find_blocking_budget() {
+        let budgets: Vec<PodDisruptionBudget> = serde_yaml::from_str(indoc! {"
+            - apiVersion: policy/v1
+              kind: PodDisruptionBudget
+              metadata:
+                name: exhausted-without-selector
+              spec: {}
+              status:
+                currentHealthy: 2
+                desiredHealthy: 3
+                disruptionsAllowed: 0
+                expectedPods: 3
+            - apiVersion: policy/v1
+              kind: PodDisruptionBudget
+              metadata:
+                name: exhausted-other-app
+              spec:
+                selector:
+                  matchLabels:
+                    app: kafka
+              status:
+                currentHealthy: 2
+                desiredHealthy: 3
+                disruptionsAllowed: 0
+                expectedPods: 3
+            - apiVersion: policy/v1
+              kind: PodDisruptionBudget
+              metadata:
+                name: available
+              spec:
+                selector:
+                  matchLabels:
+                    app: zookeeper
+              status:
+                currentHealthy: 3
+                desiredHealthy: 2
+                disruptionsAllowed: 1
+                expectedPods: 3
+            - apiVersion: policy/v1
+              kind: PodDisruptionBudget
+              metadata:
+                name: exhausted
+              spec:
+                selector:
+                  matchLabels:
+                    app: zookeeper
+              status:
+                currentHealthy: 2
+                desiredHealthy: 2
+                disruptionsAllowed: 0
+                expectedPods: 3
+        "})
+        .unwrap();
+        let mut pod = Pod::default();
+        pod.metadata.labels = labels(&[("app", "zookeeper")]);
+
+        assert_eq!(
+            find_blocking_budget(&budgets, &pod).map(|budget| budget.name()),
+            Some("exhausted".to_string())
+        );
+        assert_eq!(find_blocking_budget(&budgets[..3], &pod), None);
+    }
+
+    #[rstest]
+    #[case(1, 0)]
+    #[case(2, 0)]
+    #[case(3, 1)]
+    #[case(4, 1)]
+    #[case(5, 2)]
+    #[case(0, 0)]
+    fn test_max_unavailable(#[case] replicas: usize, #[case] expected: usize) {
+        assert_eq!(max_unavailable(replicas), expected);
+    }
+
+    #[test]
+    fn test_build_budget() {
+        let cluster = test_support::cluster("simple", json!({}));
+
+        let budget = build_budget(&cluster, 5).unwrap();
+        let spec = budget.spec.as_ref().unwrap();
+
+        assert_eq!(budget.name(), "simple-server");
+        assert_eq!(spec.max_unavailable, Some(IntOrString::Int(2)));
+        assert!(selector_matches(
+            spec.selector.as_ref().unwrap(),
+            &build_common_labels_for_all_managed_resources(APP_NAME, "simple")
+        ));
+        assert!(!is_own_budget_without_disruptions(&budget, "simple"));
+
+        let budget = build_budget(&cluster, 2).unwrap();
+        assert!(is_own_budget_without_disruptions(&budget, "simple"));
+        assert!(!is_own_budget_without_disruptions(&budget, "other"));
+    }
+}
@@ -0,0 +1,206 @@
+//! Previewing the changes of a reconciliation without making them.
+//!
+//! Clusters are in dry-run mode if the operator was started with `--dry-run` or the cluster has
+//! the [`DRY_RUN_ANNOTATION`]. Once the spec was validated, instead of running the remaining
+//! reconciliation steps, the operator then computes the desired objects, compares them with the
+//! existing ones and records the differences in `status.dryRun` (see
+//! [`stackable_zookeeper_crd::dry_run`]), in an event and in the log. Only the status (and the
+//! finalizer) of the ZookeeperCluster is written.
+//!
+//! With [`DryRunMode::Server`] the objects the operator applies are also sent to the API server
+//! as server-side dry run, so the changed fields include defaults and the changes of admission
+//! webhooks, and invalid objects are reported before they are rolled out.
+use serde_json::Value;
+use stackable_zookeeper_crd::dry_run::{PlannedAction, PlannedChange};
+use stackable_zookeeper_crd::ZookeeperCluster;
+use std::str::FromStr;
+use tracing::warn;
+
+/// Annotation on a ZookeeperCluster which enables the dry-run mode: `client` (or `true`) computes
+/// the changes in the operator, `server` uses server-side dry runs and `false` disables the
+/// mode even if the operator was started with `--dry-run`.
+pub const DRY_RUN_ANNOTATION: &str = "zookeeper.stackable.tech/dry-run";
+/// Condition which is set while the cluster is in dry-run mode.
+pub const DRY_RUN_CONDITION: &str = "DryRun";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DryRunMode {
+    /// The changes are made.
+    Disabled,
+    /// The changes are computed by the operator.
+    Client,
+    /// The changes of applied objects are computed by the API server.
+    Server,
+}
+
+impl Default for DryRunMode {
+    fn default() -> Self {
+        DryRunMode::Disabled
+    }
+}
+
+impl FromStr for DryRunMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.trim().to_ascii_lowercase().as_str() {
+            "false" | "none" => Ok(DryRunMode::Disabled),
+            "true" | "client" => Ok(DryRunMode::Client),
+            "server" => Ok(DryRunMode::Server),
+            _ => Err(format!(
+                "Invalid dry-run mode [{}], expected one of none, client or server",
+                mode
+            )),
+        }
+    }
+}
+
+/// Returns the dry-run mode of the `cluster`: the [`DRY_RUN_ANNOTATION`] if set, otherwise the
+/// `default` of the operator. An invalid annotation enables the dry run, the user apparently did
+/// not want the changes to be made.
+pub fn mode(cluster: &ZookeeperCluster, default: DryRunMode) -> DryRunMode {
+    match cluster.metadata.annotations.get(DRY_RUN_ANNOTATION) {
+        Some(value) => value.parse().unwrap_or_else(|err| {
+            warn!("{}, assuming client", err);
+            DryRunMode::Client
+        }),
+        None => default,
+    }
+}
+
+/// Returns the paths of the fields of `desired` whose value differs from `actual`, see
+/// [`crate::diff::is_applied`]. Arrays are reported as a whole.
+pub fn changed_fields(desired: &Value, actual: &Value) -> Vec<String> {
+    let mut fields = vec![];
+    collect_changed_fields("", desired, actual, &mut fields);
+    fields
+}
+
+fn collect_changed_fields(path: &str, desired: &Value, actual: &Value, fields: &mut Vec<String>) {
+    match (desired, actual) {
+        (Value::Object(desired), Value::Object(actual)) => {
+            for (key, value) in desired {
+                let field_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                collect_changed_fields(
+                    &field_path,
+                    value,
+                    actual.get(key).unwrap_or(&Value::Null),
+                    fields,
+                );
+            }
+        }
+        (desired, actual) => {
+            if !crate::diff::is_applied(desired, actual) {
+                fields.push(path.to_string());
+            }
+        }
+    }
+}
+
+/// Summarizes the planned `changes` in one line for the event and the condition.
+pub fn summarize(changes: &[PlannedChange]) -> String {
+    if changes.is_empty() {
+        return "The cluster is up to date, no changes are planned".to_string();
+    }
+    let changes = changes
+        .iter()
+        .map(|change| {
+            let action = match change.action {
+                PlannedAction::Create => "create",
+                PlannedAction::Update => "update",
+                PlannedAction::Delete => "delete",
+                PlannedAction::Restart => "restart",
+            };
+            let mut summary = format!("{} {} [{}]", action, change.kind, change.name);
+            if !change.fields.is_empty() {
+                summary.push_str(&format!(" ({})", change.fields.join(", ")));
+            }
+            summary
+        })
+        .collect::<Vec<_>>();
+    format!(
+        "{} change(s) planned: {}",
+        changes.len(),
+        changes.join("; ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use rstest::*;
+    use serde_json::json;
+
+    #[rstest]
+    #[case(None, DryRunMode::Disabled, DryRunMode::Disabled)]
+    #[case(None, DryRunMode::Server, DryRunMode::Server)]
+    #[case(Some("true"), DryRunMode::Disabled, DryRunMode::Client)]
+    #[case(Some("Server"), DryRunMode::Disabled, DryRunMode::Server)]
+    #[case(Some("false"), DryRunMode::Client, DryRunMode::Disabled)]
+    #[case(Some("yes please"), DryRunMode::Disabled, DryRunMode::Client)]
+    fn test_mode(
+        #[case] annotation: Option<&str>,
+        #[case] default: DryRunMode,
+        #[case] expected: DryRunMode,
+    ) {
+        let mut cluster = test_support::cluster("simple", json!({}));
+        if let Some(annotation) = annotation {
+            cluster
+                .metadata
+                .annotations
+                .insert(DRY_RUN_ANNOTATION.to_string(), annotation.to_string());
+        }
+
+        assert_eq!(mode(&cluster, default), expected);
+    }
+
+    #[test]
+    fn test_changed_fields() {
+        let actual = json!({
+            "metadata": { "name": "simple-admin", "labels": { "team": "payments" } },
+            "spec": {
+                "ports": [{ "name": "http", "port": 8080, "protocol": "TCP" }],
+                "type": "ClusterIP"
+            }
+        });
+
+        assert!(changed_fields(
+            &json!({ "spec": { "ports": [{ "name": "http", "port": 8080 }] } }),
+            &actual
+        )
+        .is_empty());
+        assert_eq!(
+            changed_fields(
+                &json!({
+                    "metadata": { "name": "simple-admin", "labels": { "app": "zookeeper" } },
+                    "spec": { "ports": [{ "name": "http", "port": 8081 }], "type": "NodePort" }
+                }),
+                &actual
+            ),
+            vec!["metadata.labels.app", "spec.ports", "spec.type"]
+        );
+    }
+
+    #[test]
+    fn test_summarize() {
+        let mut update = PlannedChange::new(PlannedAction::Update, "Service", "simple-admin");
+        update.fields = vec!["spec.ports".to_string()];
+
+        assert_eq!(
+            summarize(&[]),
+            "The cluster is up to date, no changes are planned"
+        );
+        assert_eq!(
+            summarize(&[
+                update,
+                PlannedChange::new(PlannedAction::Restart, "Pod", "simple-server-default-1")
+            ]),
+            "2 change(s) planned: update Service [simple-admin] (spec.ports); restart Pod [simple-server-default-1]"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! The command the servers are started with.
+//!
+//! By default the servers are started by a wrapper script which is rendered into the data
+//! ConfigMap next to `zoo.cfg`, instead of relying on the entrypoint of the image. It writes the
+//! `myid` file, stages the files of a restore (see [`crate::backup`]), replaces `${NAME}` in
+//! `zoo.cfg` with environment variables and forwards termination signals to the server, so all
+//! supported images behave the same. `spec.command` and `spec.args` replace the command and its
+//! arguments.
+use k8s_openapi::api::core::v1::{EnvVar, EnvVarSource, SecretKeySelector};
+
+/// The name of the wrapper script in the data ConfigMap.
+pub const ENTRYPOINT_FILE: &str = "entrypoint.sh";
+/// The environment variable holding the `myid` of the server.
+pub const MYID_ENV_VAR: &str = "ZOOKEEPER_MYID";
+/// The environment variable holding the files of a restore, one line per file with its name and
+/// the URL it is downloaded from.
+pub const RESTORE_FILES_ENV_VAR: &str = "ZOOKEEPER_RESTORE_FILES";
+/// The key of the restore Secret holding the files of the restore.
+pub const RESTORE_FILES_KEY: &str = "files";
+
+const ENTRYPOINT_SCRIPT: &str = include_str!("entrypoint.sh");
+
+/// Returns the wrapper script.
+pub fn build_entrypoint_script() -> String {
+    ENTRYPOINT_SCRIPT.to_string()
+}
+
+/// Builds the environment variable with the files of the restore from the Secret `secret_name`.
+/// The Secret is deleted once the restore completed, restarted containers start without the
+/// variable then.
+pub fn build_restore_env_var(secret_name: &str) -> EnvVar {
+    EnvVar {
+        name: RESTORE_FILES_ENV_VAR.to_string(),
+        value_from: Some(EnvVarSource {
+            secret_key_ref: Some(SecretKeySelector {
+                name: Some(secret_name.to_string()),
+                key: RESTORE_FILES_KEY.to_string(),
+                optional: Some(true),
+            }),
+            ..EnvVarSource::default()
+        }),
+        ..EnvVar::default()
+    }
+}
+
+/// Builds the command and arguments of the server container.
+///
+/// # Arguments
+///
+/// - `zookeeper_home` - The installation directory of ZooKeeper.
+/// - `config_dir` - The directory the data ConfigMap is mounted to.
+/// - `data_dir` - The data directory containing the `myid` file.
+/// - `command` - Overrides the command, the wrapper script is used if empty.
+/// - `args` - Overrides the arguments, the directories are passed to the wrapper if empty.
+///
+pub fn build_command(
+    zookeeper_home: &str,
+    config_dir: &str,
+    data_dir: &str,
+    command: &[String],
+    args: &[String],
+) -> (Vec<String>, Vec<String>) {
+    let command = if command.is_empty() {
+        vec![
+            "sh".to_string(),
+            format!("{}/{}", config_dir, ENTRYPOINT_FILE),
+        ]
+    } else {
+        command.to_vec()
+    };
+    let args = if args.is_empty() {
+        vec![
+            zookeeper_home.to_string(),
+            config_dir.to_string(),
+            data_dir.to_string(),
+        ]
+    } else {
+        args.to_vec()
+    };
+    (command, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_command() {
+        assert_eq!(
+            build_command("zookeeper-3.5.8", "/conf", "/data", &[], &[]),
+            (
+                vec!["sh".to_string(), "/conf/entrypoint.sh".to_string()],
+                vec![
+                    "zookeeper-3.5.8".to_string(),
+                    "/conf".to_string(),
+                    "/data".to_string()
+                ]
+            )
+        );
+        assert_eq!(
+            build_command(
+                "zookeeper-3.5.8",
+                "/conf",
+                "/data",
+                &["/custom-entrypoint.sh".to_string()],
+                &[]
+            ),
+            (
+                vec!["/custom-entrypoint.sh".to_string()],
+                vec![
+                    "zookeeper-3.5.8".to_string(),
+                    "/conf".to_string(),
+                    "/data".to_string()
+                ]
+            )
+        );
+        assert_eq!(
+            build_command(
+                "zookeeper-3.5.8",
+                "/conf",
+                "/data",
+                &[],
+                &["--verbose".to_string()]
+            )
+            .1,
+            vec!["--verbose".to_string()]
+        );
+    }
+}
@@ -0,0 +1,79 @@
+#!/usr/bin/env sh
+# Entrypoint of the ZooKeeper servers, rendered into the ConfigMap by the zookeeper-operator.
+#
+# Usage: entrypoint.sh <ZooKeeper home> <config directory> <data directory>
+#
+# - empties the data directory once per ZOOKEEPER_WIPE_DATA_TOKEN, for the replacement of a
+#   failed server
+# - downloads the files of a restore from the URLs in ZOOKEEPER_RESTORE_FILES (one line per file
+#   with its name and URL) into an empty data directory
+# - writes the `myid` file from ZOOKEEPER_MYID unless it already contains the id
+# - replaces `${NAME}` in zoo.cfg with the environment variable NAME (unset variables are
+#   replaced with an empty string)
+# - forwards SIGTERM and SIGINT to the server and waits for it to shut down
+set -eu
+
+ZOOKEEPER_HOME="$1"
+CONFIG_DIR="$2"
+DATA_DIR="$3"
+RUNTIME_CONFIG_DIR="${ZOOKEEPER_RUNTIME_CONFIG_DIR:-/tmp/zookeeper-config}"
+
+if [ -n "${ZOOKEEPER_WIPE_DATA_TOKEN:-}" ] &&
+  [ "$(cat "$DATA_DIR/wipe-data-token" 2>/dev/null || true)" != "$ZOOKEEPER_WIPE_DATA_TOKEN" ]; then
+  echo "Emptying the data directory $DATA_DIR for the replacement of a failed server"
+  rm -rf "$DATA_DIR/version-2" "$DATA_DIR/myid"
+  mkdir -p "$DATA_DIR"
+  echo "$ZOOKEEPER_WIPE_DATA_TOKEN" > "$DATA_DIR/wipe-data-token"
+fi
+
+if [ -n "${ZOOKEEPER_RESTORE_FILES:-}" ] && [ ! -d "$DATA_DIR/version-2" ]; then
+  echo "Restoring the backup into the data directory $DATA_DIR"
+  # downloaded next to version-2 first, so an interrupted restore is repeated
+  rm -rf "$DATA_DIR/version-2.restore"
+  mkdir -p "$DATA_DIR/version-2.restore"
+  while read -r name url; do
+    if [ -n "$name" ]; then
+      echo "Downloading $name"
+      curl --fail --silent --show-error --retry 3 --output "$DATA_DIR/version-2.restore/$name" "$url"
+    fi
+  done <<EOF
+$ZOOKEEPER_RESTORE_FILES
+EOF
+  mv "$DATA_DIR/version-2.restore" "$DATA_DIR/version-2"
+fi
+
+if [ -n "${ZOOKEEPER_MYID:-}" ]; then
+  mkdir -p "$DATA_DIR"
+  if [ "$(cat "$DATA_DIR/myid" 2>/dev/null || true)" != "$ZOOKEEPER_MYID" ]; then
+    echo "$ZOOKEEPER_MYID" > "$DATA_DIR/myid"
+  fi
+fi
+
+mkdir -p "$RUNTIME_CONFIG_DIR"
+awk '{
+  rest = $0
+  line = ""
+  while (match(rest, /\$\{[A-Za-z_][A-Za-z0-9_]*\}/)) {
+    line = line substr(rest, 1, RSTART - 1) ENVIRON[substr(rest, RSTART + 2, RLENGTH - 3)]
+    rest = substr(rest, RSTART + RLENGTH)
+  }
+  print line rest
+}' "$CONFIG_DIR/zoo.cfg" > "$RUNTIME_CONFIG_DIR/zoo.cfg"
+
+"$ZOOKEEPER_HOME/bin/zkServer.sh" start-foreground "$RUNTIME_CONFIG_DIR/zoo.cfg" &
+server=$!
+
+trap 'kill -TERM "$server" 2>/dev/null || true' TERM INT
+
+status=0
+wait "$server" || status=$?
+# wait returns early when a signal was trapped, so wait for the shutdown of the server as well
+# (127 means the server was already reaped and the first status is the one of the server)
+if [ "$status" -gt 128 ]; then
+  shutdown_status=0
+  wait "$server" || shutdown_status=$?
+  if [ "$shutdown_status" -ne 127 ]; then
+    status=$shutdown_status
+  fi
+fi
+exit "$status"
@@ -1,7 +1,9 @@
+use crate::validation::SpecViolation;
+
 use std::num::ParseIntError;
 
 #[allow(clippy::enum_variant_names)]
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, thiserror::Error, strum_macros::IntoStaticStr)]
 pub enum Error {
     #[error(
         "ConfigMap of type [{cm_type}] is for pod with generate_name [{pod_name}] is missing."
@@ -32,12 +34,112 @@ pub enum Error {
         source: serde_json::Error,
     },
 
+    #[error("Error from serde_yaml: {source}")]
+    YamlError {
+        #[from]
+        source: serde_yaml::Error,
+    },
+
     #[error("Pod contains invalid id: {source}")]
     InvalidId {
         #[from]
         source: ParseIntError,
     },
 
+    #[error("Failed to send four letter word [{command}] to [{host}:{port}]: {source}")]
+    FourLetterWordError {
+        host: String,
+        port: u16,
+        command: String,
+        source: std::io::Error,
+    },
+
+    #[error("HTTP server failed: {source}")]
+    HttpServerError {
+        #[from]
+        source: hyper::Error,
+    },
+
+    #[error("Metrics error: {source}")]
+    MetricsError {
+        #[from]
+        source: prometheus::Error,
+    },
+
+    #[cfg(feature = "otel")]
+    #[error("Failed to set up the export of traces: {source}")]
+    TraceError {
+        #[from]
+        source: opentelemetry::trace::TraceError,
+    },
+
+    #[error("Invalid log filter [{filter}]: {source}")]
+    InvalidLogFilter {
+        filter: String,
+        source: tracing_subscriber::filter::ParseError,
+    },
+
+    #[error("ZooKeeper reported error: {source}")]
+    ZookeeperClientError {
+        #[from]
+        source: zookeeper_async::ZkError,
+    },
+
+    #[cfg(feature = "backup")]
+    #[error("Request to the object storage failed: {source}")]
+    HttpClientError {
+        #[from]
+        source: reqwest::Error,
+    },
+
+    #[error("Object storage responded with [{status}] to {operation} [{key}]: {message}")]
+    ObjectStorageError {
+        operation: String,
+        key: String,
+        status: u16,
+        message: String,
+    },
+
+    #[error("Error from ZooKeeper CRD: {source}")]
+    ZookeeperCrdError {
+        #[from]
+        source: stackable_zookeeper_crd::error::Error,
+    },
+
+    #[error("Invalid zoo.cfg properties in spec.config: {}", problems.join(", "))]
+    InvalidConfig { problems: Vec<String> },
+
+    #[error("The spec violates the rules for ZookeeperClusters: {}", violations.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    ZookeeperClusterIsBad { violations: Vec<SpecViolation> },
+
+    #[error(
+        "Invalid scheduler name [{scheduler_name}], it must be a lowercase RFC 1123 subdomain"
+    )]
+    InvalidSchedulerName { scheduler_name: String },
+
+    #[error("The spec contains unknown fields: {fields:?}")]
+    UnknownSpecFields { fields: Vec<String> },
+
+    #[error("The installed CRD [{crd}] does not match the operator: {}", problems.join(", "))]
+    IncompatibleCrd { crd: String, problems: Vec<String> },
+
+    #[error("The Secret [{secret}] exists already but does not contain the credentials of the user [{user}] of this ZookeeperZnode, it is not used")]
+    UserSecretConflict { secret: String, user: String },
+
+    #[error(
+        "Applying [{object}] conflicts with an object which is not owned by the cluster: {message}"
+    )]
+    ApplyConflict { object: String, message: String },
+
+    #[error(
+        "Running a command in the container [{container}] of the pod [{pod}] failed: {message}"
+    )]
+    PodExecError {
+        pod: String,
+        container: String,
+        message: String,
+    },
+
     #[error("Error during reconciliation: {0}")]
     ReconcileError(String),
 
@@ -56,3 +158,188 @@ pub enum Error {
         source: stackable_operator::product_config_utils::ConfigError,
     },
 }
+
+/// How a failure can be resolved, which decides how the reconciliation is retried.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, strum_macros::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum ErrorClass {
+    /// The operation may succeed when it is retried, e.g. because the API server or the servers
+    /// were not reachable or not ready yet.
+    Transient,
+    /// An object was changed concurrently, a retry with its current version succeeds.
+    Conflict,
+    /// The spec or the configuration is invalid, retrying does not help until it is changed.
+    Terminal,
+}
+
+/// Classifies a failed request to the Kubernetes API server by its status code.
+fn kube_error_class(error: &kube::Error) -> ErrorClass {
+    match error {
+        kube::Error::Api(response) => match response.code {
+            409 => ErrorClass::Conflict,
+            // the object built from the spec was rejected
+            400 | 422 => ErrorClass::Terminal,
+            _ => ErrorClass::Transient,
+        },
+        _ => ErrorClass::Transient,
+    }
+}
+
+impl Error {
+    /// Returns the name of the variant, e.g. `KubeError`.
+    pub fn variant_name(&self) -> &'static str {
+        self.into()
+    }
+
+    /// Classifies the error by how it can be resolved. Errors are transient unless they are
+    /// known to be caused by the spec (or the configuration of the operator) or by a concurrent
+    /// change. Every variant is listed, so new ones have to be classified.
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            Error::KubeError { source }
+            | Error::OperatorError {
+                source: stackable_operator::error::Error::KubeError { source },
+            }
+            | Error::ZookeeperCrdError {
+                source: stackable_zookeeper_crd::error::Error::KubeError { source },
+            } => kube_error_class(source),
+            Error::ZookeeperCrdError { source } => match source {
+                stackable_zookeeper_crd::error::Error::IllegalZookeeperPath { .. }
+                | stackable_zookeeper_crd::error::Error::IllegalZnode { .. }
+                | stackable_zookeeper_crd::error::Error::IllegalZookeeperVersion { .. }
+                | stackable_zookeeper_crd::error::Error::IllegalZnodeAcl { .. }
+                | stackable_zookeeper_crd::error::Error::IllegalZnodeUser { .. } => {
+                    ErrorClass::Terminal
+                }
+                _ => ErrorClass::Transient,
+            },
+            Error::InvalidLogFilter { .. }
+            | Error::InvalidConfig { .. }
+            | Error::ZookeeperClusterIsBad { .. }
+            | Error::InvalidSchedulerName { .. }
+            | Error::UnknownSpecFields { .. }
+            | Error::IncompatibleCrd { .. }
+            | Error::ProductConfigError { .. }
+            | Error::OperatorConfigError { .. } => ErrorClass::Terminal,
+            // resolved by deleting or handing over the conflicting object, which does not trigger
+            // a reconciliation of the cluster, so they are retried
+            Error::UserSecretConflict { .. } | Error::ApplyConflict { .. } => ErrorClass::Transient,
+            Error::MissingConfigMapError { .. }
+            | Error::MissingConfigMapNameError { .. }
+            | Error::OperatorError { .. }
+            | Error::SerdeError { .. }
+            | Error::YamlError { .. }
+            | Error::InvalidId { .. }
+            | Error::FourLetterWordError { .. }
+            | Error::HttpServerError { .. }
+            | Error::MetricsError { .. }
+            | Error::ZookeeperClientError { .. }
+            | Error::ObjectStorageError { .. }
+            | Error::PodExecError { .. }
+            | Error::ReconcileError(_)
+            | Error::PropertiesError(_) => ErrorClass::Transient,
+            #[cfg(feature = "otel")]
+            Error::TraceError { .. } => ErrorClass::Transient,
+            #[cfg(feature = "backup")]
+            Error::HttpClientError { .. } => ErrorClass::Transient,
+        }
+    }
+
+    /// Classifies the error by its source for metrics: `kubernetes` for failed requests to the
+    /// Kubernetes API, `zookeeper` for failed requests to the servers and `operator` otherwise.
+    pub fn source_kind(&self) -> &'static str {
+        match self {
+            Error::KubeError { .. }
+            | Error::OperatorError {
+                source: stackable_operator::error::Error::KubeError { .. },
+            } => "kubernetes",
+            Error::ZookeeperClientError { .. } | Error::FourLetterWordError { .. } => "zookeeper",
+            _ => "operator",
+        }
+    }
+
+    /// Whether the error means that the Kubernetes API server could not be reached (as opposed
+    /// to the server rejecting the request).
+    pub fn is_api_unreachable(&self) -> bool {
+        match self {
+            Error::KubeError { source }
+            | Error::OperatorError {
+                source: stackable_operator::error::Error::KubeError { source },
+            } => !matches!(source, kube::Error::Api(_)),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn api_error(code: u16) -> Error {
+        Error::KubeError {
+            source: kube::Error::Api(
+                serde_json::from_value(json!({
+                    "status": "Failure",
+                    "message": "test",
+                    "reason": "test",
+                    "code": code,
+                }))
+                .unwrap(),
+            ),
+        }
+    }
+
+    #[rstest]
+    #[case::conflict(api_error(409), ErrorClass::Conflict)]
+    #[case::invalid(api_error(422), ErrorClass::Terminal)]
+    #[case::forbidden(api_error(403), ErrorClass::Transient)]
+    #[case::unavailable(api_error(503), ErrorClass::Transient)]
+    #[case::invalid_spec(Error::ZookeeperClusterIsBad { violations: vec![] }, ErrorClass::Terminal)]
+    #[case::unknown_fields(Error::UnknownSpecFields { fields: vec!["spec.foo".to_string()] }, ErrorClass::Terminal)]
+    #[case::illegal_znode(
+        Error::ZookeeperCrdError {
+            source: stackable_zookeeper_crd::error::Error::IllegalZnode {
+                znode: "simple".to_string(),
+                reason: "test".to_string(),
+            },
+        },
+        ErrorClass::Terminal
+    )]
+    #[case::no_pods(
+        Error::ZookeeperCrdError {
+            source: stackable_zookeeper_crd::error::Error::NoZookeeperPodsAvailableForConnectionInfo {
+                namespace: "default".to_string(),
+                name: "simple".to_string(),
+            },
+        },
+        ErrorClass::Transient
+    )]
+    #[case::missing_config_map(Error::MissingConfigMapNameError { cm_type: "data" }, ErrorClass::Transient)]
+    #[case::apply_conflict(
+        Error::ApplyConflict { object: "Service/simple-admin".to_string(), message: "test".to_string() },
+        ErrorClass::Transient
+    )]
+    #[case::user_secret_conflict(
+        Error::UserSecretConflict { secret: "simple-app".to_string(), user: "app".to_string() },
+        ErrorClass::Transient
+    )]
+    #[case::pod_exec(
+        Error::PodExecError {
+            pod: "simple-server-default-1".to_string(),
+            container: "zookeeper".to_string(),
+            message: "test".to_string(),
+        },
+        ErrorClass::Transient
+    )]
+    fn test_class(#[case] error: Error, #[case] expected: ErrorClass) {
+        assert_eq!(error.class(), expected);
+    }
+
+    #[test]
+    fn test_class_display() {
+        assert_eq!(ErrorClass::Transient.to_string(), "transient");
+        assert_eq!(ErrorClass::Terminal.to_string(), "terminal");
+    }
+}
@@ -0,0 +1,123 @@
+//! Publishing of Kubernetes Events.
+//!
+//! Events are attached to the object they are about (usually the ZookeeperCluster) and show up in
+//! `kubectl describe`, which makes it a lot easier to find out what the operator did (or refused
+//! to do) than searching through the operator logs.
+use crate::error::Error;
+
+use k8s_openapi::api::core::v1::{Event, EventSource};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ObjectMeta, Time};
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube::Resource;
+use stackable_operator::client::Client;
+use strum_macros::Display;
+use tracing::warn;
+
+/// The component reported as the source of all events published by this operator.
+const REPORTING_COMPONENT: &str = "zookeeper-operator";
+
+#[derive(Clone, Copy, Debug, Display, Eq, PartialEq)]
+pub enum EventType {
+    Normal,
+    Warning,
+}
+
+/// Builds an Event about the given `resource`.
+///
+/// # Arguments
+///
+/// - `resource` - The object the event is about.
+/// - `event_type` - Whether this is a normal or a warning event.
+/// - `reason` - A short, machine understandable, CamelCase reason (e.g. `DowngradeRejected`).
+/// - `message` - A human readable description.
+/// - `now` - The time the event happened.
+///
+pub fn build_event<T>(
+    resource: &T,
+    event_type: EventType,
+    reason: &str,
+    message: &str,
+    now: DateTime<Utc>,
+) -> Event
+where
+    T: Resource<DynamicType = ()>,
+{
+    let involved_object = resource.object_ref(&());
+
+    Event {
+        metadata: ObjectMeta {
+            generate_name: Some(format!(
+                "{}-",
+                involved_object.name.as_deref().unwrap_or("unknown")
+            )),
+            namespace: involved_object.namespace.clone(),
+            ..ObjectMeta::default()
+        },
+        involved_object,
+        type_: Some(event_type.to_string()),
+        reason: Some(reason.to_string()),
+        message: Some(message.to_string()),
+        count: Some(1),
+        first_timestamp: Some(Time(now)),
+        last_timestamp: Some(Time(now)),
+        source: Some(EventSource {
+            component: Some(REPORTING_COMPONENT.to_string()),
+            host: None,
+        }),
+        reporting_component: Some(REPORTING_COMPONENT.to_string()),
+        ..Event::default()
+    }
+}
+
+/// Publishes an Event about the given `resource`, see [`build_event`].
+///
+/// Events are informational only, so failing to publish one is logged but not treated as an error
+/// that should abort the reconciliation.
+pub async fn publish_event<T>(
+    client: &Client,
+    resource: &T,
+    event_type: EventType,
+    reason: &str,
+    message: &str,
+) where
+    T: Resource<DynamicType = ()>,
+{
+    let event = build_event(resource, event_type, reason, message, Utc::now());
+    let result: Result<Event, Error> = client.create(&event).await.map_err(Error::from);
+
+    if let Err(err) = result {
+        warn!(?err, "Failed to publish event [{}]: {}", reason, message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use serde_json::json;
+
+    #[test]
+    fn test_build_event() {
+        let zk = test_support::cluster("simple", json!({}));
+        let now = Utc::now();
+
+        let event = build_event(
+            &zk,
+            EventType::Warning,
+            "DowngradeRejected",
+            "Downgrade not possible",
+            now,
+        );
+
+        assert_eq!(event.metadata.generate_name, Some("simple-".to_string()));
+        assert_eq!(event.metadata.namespace, Some("default".to_string()));
+        assert_eq!(
+            event.involved_object.kind,
+            Some("ZookeeperCluster".to_string())
+        );
+        assert_eq!(event.involved_object.name, Some("simple".to_string()));
+        assert_eq!(event.type_, Some("Warning".to_string()));
+        assert_eq!(event.reason, Some("DowngradeRejected".to_string()));
+        assert_eq!(event.last_timestamp, Some(Time(now)));
+    }
+}
@@ -0,0 +1,332 @@
+//! Building the Services exposing the client port outside of Kubernetes and deriving the external
+//! endpoints from them, see [`stackable_zookeeper_crd::external_access`].
+use crate::error::Error;
+use crate::ID_LABEL;
+
+use k8s_openapi::api::core::v1::{Node, Service, ServicePort, ServiceSpec};
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use kube::ResourceExt;
+use stackable_operator::builder::ObjectMetaBuilder;
+use stackable_operator::labels::build_common_labels_for_all_managed_resources;
+use stackable_zookeeper_crd::external_access::{ExternalAccessType, ZookeeperExternalAccess};
+use stackable_zookeeper_crd::ip_family::{self, IpFamily};
+use stackable_zookeeper_crd::{ZookeeperCluster, APP_NAME};
+use std::collections::BTreeMap;
+
+/// The label of the Services created for the external access, it selects the Services which are
+/// deleted once they are not needed anymore.
+pub const EXTERNAL_ACCESS_LABEL: &str = "zookeeper.stackable.tech/external-access";
+
+/// The name of the container port the Services forward to.
+const CLIENT_PORT_NAME: &str = "client";
+
+/// Returns the name of the Service of the server with the given id (`NodePort`) or of all servers
+/// (`LoadBalancer`).
+pub fn service_name(cluster_name: &str, id: Option<usize>) -> String {
+    match id {
+        Some(id) => format!("{}-server-{}-external", cluster_name, id),
+        None => format!("{}-external", cluster_name),
+    }
+}
+
+/// Builds the Services of `cluster`.
+///
+/// # Arguments
+///
+/// - `cluster` - The cluster the Services belong to.
+/// - `external_access` - The external access of the cluster.
+/// - `servers` - The ids and client ports of the servers, a `NodePort` Service is built for
+///   every one of them.
+///
+pub fn build_services(
+    cluster: &ZookeeperCluster,
+    external_access: &ZookeeperExternalAccess,
+    servers: &[(usize, u16)],
+) -> Result<Vec<Service>, Error> {
+    let cluster_name = cluster.name();
+    let server_labels = build_common_labels_for_all_managed_resources(APP_NAME, &cluster_name);
+
+    match external_access.type_ {
+        ExternalAccessType::NodePort => servers
+            .iter()
+            .map(|(id, client_port)| {
+                let mut selector = server_labels.clone();
+                selector.insert(ID_LABEL.to_string(), id.to_string());
+                build_service(
+                    cluster,
+                    external_access,
+                    service_name(&cluster_name, Some(*id)),
+                    selector,
+                    *client_port,
+                )
+            })
+            .collect(),
+        ExternalAccessType::LoadBalancer => {
+            let client_port = cluster.spec.ports.clone().unwrap_or_default().client();
+            Ok(vec![build_service(
+                cluster,
+                external_access,
+                service_name(&cluster_name, None),
+                server_labels,
+                client_port,
+            )?])
+        }
+    }
+}
+
+fn build_service(
+    cluster: &ZookeeperCluster,
+    external_access: &ZookeeperExternalAccess,
+    name: String,
+    selector: BTreeMap<String, String>,
+    port: u16,
+) -> Result<Service, Error> {
+    let mut labels = build_common_labels_for_all_managed_resources(APP_NAME, &cluster.name());
+    labels.insert(EXTERNAL_ACCESS_LABEL.to_string(), "true".to_string());
+
+    let mut metadata = ObjectMetaBuilder::new()
+        .name(name)
+        .namespace(cluster.metadata.namespace.as_deref().unwrap_or_default())
+        .ownerreference_from_resource(cluster, Some(true), Some(true))?
+        .build()?;
+    metadata.labels = labels;
+    metadata.annotations = external_access.annotations.clone();
+
+    Ok(Service {
+        metadata,
+        spec: Some(ServiceSpec {
+            type_: Some(external_access.type_.to_string()),
+            selector,
+            ports: vec![ServicePort {
+                name: Some(CLIENT_PORT_NAME.to_string()),
+                port: i32::from(port),
+                target_port: Some(IntOrString::String(CLIENT_PORT_NAME.to_string())),
+                protocol: Some("TCP".to_string()),
+                ..ServicePort::default()
+            }],
+            // the clients connect to the node of the server, so the connection must not be
+            // forwarded to another node
+            external_traffic_policy: match external_access.type_ {
+                ExternalAccessType::NodePort => Some("Local".to_string()),
+                ExternalAccessType::LoadBalancer => None,
+            },
+            ip_families: cluster
+                .spec
+                .ip_families
+                .iter()
+                .map(IpFamily::to_string)
+                .collect(),
+            ip_family_policy: ip_family::ip_family_policy(
+                &cluster.spec.ip_families,
+                cluster.spec.ip_family_policy,
+            )
+            .map(|policy| policy.to_string()),
+            ..ServiceSpec::default()
+        }),
+        ..Service::default()
+    })
+}
+
+/// Returns the address of `node` and the node port of `service`, e.g. `203.0.113.10:31181`.
+///
+/// The external IP of the node is preferred over its internal IP, on dual-stack nodes the
+/// address of the most preferred of the `ip_families`. `None` is returned as long as the node
+/// port was not allocated.
+pub fn node_port_endpoint(
+    service: &Service,
+    node: &Node,
+    ip_families: &[IpFamily],
+) -> Option<String> {
+    let node_port = service
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.ports.first())
+        .and_then(|port| port.node_port)?;
+    let addresses = node
+        .status
+        .as_ref()
+        .map(|status| status.addresses.as_slice())
+        .unwrap_or_default();
+    let address = ["ExternalIP", "InternalIP"].iter().find_map(|type_| {
+        let addresses = addresses
+            .iter()
+            .filter(|address| address.type_ == *type_)
+            .map(|address| address.address.as_str())
+            .collect::<Vec<_>>();
+        ip_family::preferred_address(&addresses, ip_families)
+    })?;
+    Some(ip_family::format_address(address, node_port))
+}
+
+/// Returns the address of the load balancer of `service` and its port, e.g.
+/// `zk.example.com:2181`. `None` is returned until the load balancer was provisioned.
+pub fn load_balancer_endpoint(service: &Service) -> Option<String> {
+    let port = service
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.ports.first())
+        .map(|port| port.port)?;
+    let ingress = service
+        .status
+        .as_ref()
+        .and_then(|status| status.load_balancer.as_ref())
+        .and_then(|load_balancer| load_balancer.ingress.first())?;
+    let address = ingress.hostname.as_ref().or_else(|| ingress.ip.as_ref())?;
+    Some(ip_family::format_address(address, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use indoc::indoc;
+    use serde_json::json;
+
+    fn cluster(external_access_type: &str) -> ZookeeperCluster {
+        test_support::cluster(
+            "simple",
+            json!({ "externalAccess": { "type": external_access_type } }),
+        )
+    }
+
+    #[test]
+    fn test_build_node_port_services() {
+        let cluster = cluster("NodePort");
+        let services = build_services(
+            &cluster,
+            cluster.spec.external_access.as_ref().unwrap(),
+            &[(1, 2181), (2, 12181)],
+        )
+        .unwrap();
+
+        assert_eq!(services.len(), 2);
+        assert_eq!(
+            services[1].metadata.name.as_deref(),
+            Some("simple-server-2-external")
+        );
+        assert_eq!(
+            services[1]
+                .metadata
+                .labels
+                .get(EXTERNAL_ACCESS_LABEL)
+                .unwrap(),
+            "true"
+        );
+        let spec = services[1].spec.as_ref().unwrap();
+        assert_eq!(spec.type_.as_deref(), Some("NodePort"));
+        assert_eq!(spec.selector.get(ID_LABEL).unwrap(), "2");
+        assert_eq!(spec.ports[0].port, 12181);
+        assert_eq!(
+            spec.ports[0].target_port,
+            Some(IntOrString::String("client".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_build_load_balancer_service() {
+        let cluster = cluster("LoadBalancer");
+        let services = build_services(
+            &cluster,
+            cluster.spec.external_access.as_ref().unwrap(),
+            &[(1, 2181), (2, 2181)],
+        )
+        .unwrap();
+
+        assert_eq!(services.len(), 1);
+        assert_eq!(
+            services[0].metadata.name.as_deref(),
+            Some("simple-external")
+        );
+        let spec = services[0].spec.as_ref().unwrap();
+        assert_eq!(spec.type_.as_deref(), Some("LoadBalancer"));
+        assert!(!spec.selector.contains_key(ID_LABEL));
+        assert_eq!(spec.ports[0].port, 2181);
+    }
+
+    #[test]
+    fn test_endpoints() {
+        let service: Service = serde_yaml::from_str(indoc! {"
+            spec:
+              ports:
+                - port: 2181
+                  nodePort: 31181
+            status:
+              loadBalancer:
+                ingress:
+                  - ip: 198.51.100.7
+        "})
+        .unwrap();
+        let node: Node = serde_yaml::from_str(indoc! {"
+            status:
+              addresses:
+                - type: InternalIP
+                  address: 10.0.0.10
+                - type: ExternalIP
+                  address: 203.0.113.10
+        "})
+        .unwrap();
+
+        assert_eq!(
+            node_port_endpoint(&service, &node, &[]).as_deref(),
+            Some("203.0.113.10:31181")
+        );
+        assert_eq!(
+            load_balancer_endpoint(&service).as_deref(),
+            Some("198.51.100.7:2181")
+        );
+        assert_eq!(load_balancer_endpoint(&Service::default()), None);
+    }
+
+    #[test]
+    fn test_endpoints_of_dual_stack_nodes() {
+        let service: Service = serde_yaml::from_str(indoc! {"
+            spec:
+              ports:
+                - port: 2181
+                  nodePort: 31181
+            status:
+              loadBalancer:
+                ingress:
+                  - ip: 2001:db8::7
+        "})
+        .unwrap();
+        let node: Node = serde_yaml::from_str(indoc! {"
+            status:
+              addresses:
+                - type: InternalIP
+                  address: 10.0.0.10
+                - type: InternalIP
+                  address: 2001:db8::10
+        "})
+        .unwrap();
+
+        assert_eq!(
+            node_port_endpoint(&service, &node, &[IpFamily::IPv6, IpFamily::IPv4]).as_deref(),
+            Some("[2001:db8::10]:31181")
+        );
+        assert_eq!(
+            node_port_endpoint(&service, &node, &[IpFamily::IPv4]).as_deref(),
+            Some("10.0.0.10:31181")
+        );
+        assert_eq!(
+            load_balancer_endpoint(&service).as_deref(),
+            Some("[2001:db8::7]:2181")
+        );
+    }
+
+    #[test]
+    fn test_build_dual_stack_service() {
+        let mut cluster = cluster("LoadBalancer");
+        cluster.spec.ip_families = vec![IpFamily::IPv6, IpFamily::IPv4];
+        let services = build_services(
+            &cluster,
+            cluster.spec.external_access.as_ref().unwrap(),
+            &[(1, 2181)],
+        )
+        .unwrap();
+
+        let spec = services[0].spec.as_ref().unwrap();
+        assert_eq!(spec.ip_families, vec!["IPv6", "IPv4"]);
+        assert_eq!(spec.ip_family_policy.as_deref(), Some("PreferDualStack"));
+    }
+}
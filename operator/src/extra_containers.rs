@@ -0,0 +1,98 @@
+//! Adds the init containers, sidecars and shared volumes of `spec.extraContainers` to the server
+//! pods, see [`stackable_zookeeper_crd::extra_containers`].
+use crate::authentication::SASL_VOLUME_NAME;
+use crate::kerberos::{KEYTAB_VOLUME_NAME, KRB5_CONF_VOLUME_NAME};
+use crate::monitoring::{JMX_EXPORTER_CONTAINER_NAME, JMX_EXPORTER_VOLUME_NAME};
+use crate::tls::TLS_VOLUME_NAME;
+
+use k8s_openapi::api::core::v1::{EmptyDirVolumeSource, PodSpec, Volume, VolumeMount};
+use stackable_zookeeper_crd::extra_containers::ZookeeperExtraContainers;
+use stackable_zookeeper_crd::APP_NAME;
+
+/// The names of the containers the operator adds to the server pods.
+pub const RESERVED_CONTAINER_NAMES: [&str; 2] = [APP_NAME, JMX_EXPORTER_CONTAINER_NAME];
+/// The names of the volumes the operator adds to the server pods, besides the ones of the
+/// ConfigMaps which are named after the cluster.
+pub const RESERVED_VOLUME_NAMES: [&str; 5] = [
+    TLS_VOLUME_NAME,
+    SASL_VOLUME_NAME,
+    KEYTAB_VOLUME_NAME,
+    KRB5_CONF_VOLUME_NAME,
+    JMX_EXPORTER_VOLUME_NAME,
+];
+
+/// Adds the containers and the shared volumes to `spec`, the shared volumes are mounted into the
+/// server container.
+pub fn apply(spec: &mut PodSpec, extra_containers: &ZookeeperExtraContainers) {
+    spec.init_containers
+        .extend(extra_containers.init_containers.iter().cloned());
+    spec.containers
+        .extend(extra_containers.sidecars.iter().cloned());
+
+    for shared_volume in &extra_containers.shared_volumes {
+        spec.volumes.push(Volume {
+            name: shared_volume.name.clone(),
+            empty_dir: Some(EmptyDirVolumeSource::default()),
+            ..Volume::default()
+        });
+        if let Some(container) = spec
+            .containers
+            .iter_mut()
+            .find(|container| container.name == APP_NAME)
+        {
+            container.volume_mounts.push(VolumeMount {
+                name: shared_volume.name.clone(),
+                mount_path: shared_volume.mount_path.clone(),
+                ..VolumeMount::default()
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_apply() {
+        let mut spec: PodSpec = serde_yaml::from_str(indoc! {"
+            containers:
+              - name: zookeeper
+        "})
+        .unwrap();
+        let extra_containers: ZookeeperExtraContainers = serde_yaml::from_str(indoc! {"
+            initContainers:
+              - name: prepare
+                image: busybox
+            sidecars:
+              - name: log-shipper
+                image: fluent/fluent-bit
+                volumeMounts:
+                  - name: logs
+                    mountPath: /logs
+            sharedVolumes:
+              - name: logs
+                mountPath: /stackable/logs
+        "})
+        .unwrap();
+
+        apply(&mut spec, &extra_containers);
+
+        assert_eq!(spec.init_containers[0].name, "prepare");
+        assert_eq!(
+            spec.containers
+                .iter()
+                .map(|container| container.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["zookeeper", "log-shipper"]
+        );
+        assert_eq!(spec.volumes[0].name, "logs");
+        assert!(spec.volumes[0].empty_dir.is_some());
+        assert_eq!(
+            spec.containers[0].volume_mounts[0].mount_path,
+            "/stackable/logs"
+        );
+        assert_eq!(spec.containers[1].volume_mounts.len(), 1);
+    }
+}
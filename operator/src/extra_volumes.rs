@@ -0,0 +1,71 @@
+//! Adds the volumes of `spec.extraVolumes` to the server pods, see
+//! [`stackable_zookeeper_crd::extra_volumes`].
+use k8s_openapi::api::core::v1::PodSpec;
+use stackable_zookeeper_crd::authentication::SASL_DIR;
+use stackable_zookeeper_crd::extra_volumes::ZookeeperExtraVolumes;
+use stackable_zookeeper_crd::kerberos::{KEYTAB_DIR, KRB5_CONF_DIR};
+use stackable_zookeeper_crd::tls::TLS_DIR;
+use stackable_zookeeper_crd::APP_NAME;
+
+/// The directories the operator mounts into the server container. The config directory is
+/// mounted relative to the config root of the pod, which the absolute paths of the extra mounts
+/// can not collide with.
+pub const RESERVED_MOUNT_PATHS: [&str; 4] = [TLS_DIR, SASL_DIR, KEYTAB_DIR, KRB5_CONF_DIR];
+
+/// Adds the volumes to `spec` and mounts them into the server container.
+pub fn apply(spec: &mut PodSpec, extra_volumes: &ZookeeperExtraVolumes) {
+    spec.volumes.extend(extra_volumes.volumes.iter().cloned());
+    if let Some(container) = spec
+        .containers
+        .iter_mut()
+        .find(|container| container.name == APP_NAME)
+    {
+        container
+            .volume_mounts
+            .extend(extra_volumes.mounts.iter().cloned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_apply() {
+        let mut spec: PodSpec = serde_yaml::from_str(indoc! {"
+            containers:
+              - name: zookeeper
+              - name: log-shipper
+        "})
+        .unwrap();
+        let extra_volumes: ZookeeperExtraVolumes = serde_yaml::from_str(indoc! {"
+            volumes:
+              - name: jaas
+                secret:
+                  secretName: zookeeper-jaas
+            mounts:
+              - name: jaas
+                mountPath: /stackable/jaas
+                readOnly: true
+        "})
+        .unwrap();
+
+        apply(&mut spec, &extra_volumes);
+
+        assert_eq!(spec.volumes.len(), 1);
+        assert_eq!(
+            spec.volumes[0]
+                .secret
+                .as_ref()
+                .and_then(|secret| secret.secret_name.as_deref()),
+            Some("zookeeper-jaas")
+        );
+        assert_eq!(
+            spec.containers[0].volume_mounts[0].mount_path,
+            "/stackable/jaas"
+        );
+        assert_eq!(spec.containers[0].volume_mounts[0].read_only, Some(true));
+        assert!(spec.containers[1].volume_mounts.is_empty());
+    }
+}
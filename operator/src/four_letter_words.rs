@@ -0,0 +1,181 @@
+//! Minimal client for ZooKeeper's [four letter words](https://zookeeper.apache.org/doc/current/zookeeperAdmin.html#sc_4lw).
+//!
+//! A command is sent as plain text over a new TCP connection to the client port and the server
+//! answers with plain text and closes the connection.
+use crate::error::Error;
+
+use std::collections::BTreeMap;
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The modes a ZooKeeper server reports once it is serving requests.
+const SERVING_MODES: [&str; 4] = ["leader", "follower", "observer", "standalone"];
+
+/// The answer to `ruok` of a server that is running (and not in an error state).
+const RUOK_RESPONSE: &str = "imok";
+
+/// Sends the four letter word `command` to the server at `host:port` and returns its response.
+pub async fn send_command(host: &str, port: u16, command: &str) -> Result<String, Error> {
+    let to_error = |source: io::Error| Error::FourLetterWordError {
+        host: host.to_string(),
+        port,
+        command: command.to_string(),
+        source,
+    };
+    let timed_out = |_| io::Error::new(io::ErrorKind::TimedOut, "command timed out");
+
+    let mut stream = timeout(COMMAND_TIMEOUT, TcpStream::connect((host, port)))
+        .await
+        .map_err(timed_out)
+        .and_then(|result| result)
+        .map_err(to_error)?;
+
+    stream
+        .write_all(command.as_bytes())
+        .await
+        .map_err(to_error)?;
+
+    let mut response = String::new();
+    timeout(COMMAND_TIMEOUT, stream.read_to_string(&mut response))
+        .await
+        .map_err(timed_out)
+        .and_then(|result| result)
+        .map_err(to_error)?;
+
+    Ok(response)
+}
+
+/// Parses the output of the `srvr` (and `stat`) command into key value pairs, e.g.
+/// `Mode: follower` becomes `("Mode", "follower")`.
+pub fn parse_srvr(response: &str) -> BTreeMap<String, String> {
+    response
+        .lines()
+        .filter_map(|line| line.split_once(": "))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/// Checks whether the `srvr` response indicates that the server is serving requests.
+///
+/// A follower (or observer) only starts serving requests after it has finished synchronizing
+/// its data with the leader. If it is still synchronizing (or not part of a quorum at all) it
+/// answers with "This ZooKeeper instance is not currently serving requests" instead.
+pub fn is_serving(srvr_response: &str) -> bool {
+    parse_srvr(srvr_response)
+        .get("Mode")
+        .map(|mode| SERVING_MODES.contains(&mode.as_str()))
+        .unwrap_or(false)
+}
+
+/// Parses the output of the `mntr` command into key value pairs, e.g.
+/// `zk_server_state\tfollower` becomes `("zk_server_state", "follower")`.
+pub fn parse_mntr(response: &str) -> BTreeMap<String, String> {
+    response
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/// Checks whether the `ruok` and `mntr` responses indicate a healthy server which is part of the
+/// quorum.
+///
+/// `ruok` only tells whether the process is running, so the `zk_server_state` reported by `mntr`
+/// needs to be one of the serving modes as well. Servers which are not part of a quorum answer
+/// `mntr` with "This ZooKeeper instance is not currently serving requests" instead.
+pub fn is_healthy(ruok_response: &str, mntr_response: &str) -> bool {
+    ruok_response.trim() == RUOK_RESPONSE
+        && parse_mntr(mntr_response)
+            .get("zk_server_state")
+            .map(|state| SERVING_MODES.contains(&state.as_str()))
+            .unwrap_or(false)
+}
+
+/// Probes the server at `host:port` with `ruok` and `mntr` and returns whether it is healthy (see
+/// [`is_healthy`]) together with the parsed `mntr` response, which is empty if the server did not
+/// answer `ruok`.
+pub async fn check_health(
+    host: &str,
+    port: u16,
+) -> Result<(bool, BTreeMap<String, String>), Error> {
+    let ruok_response = send_command(host, port, "ruok").await?;
+    if ruok_response.trim() != RUOK_RESPONSE {
+        return Ok((false, BTreeMap::new()));
+    }
+    let mntr_response = send_command(host, port, "mntr").await?;
+    Ok((
+        is_healthy(&ruok_response, &mntr_response),
+        parse_mntr(&mntr_response),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use rstest::rstest;
+
+    const SRVR_FOLLOWER: &str = indoc! {"
+        Zookeeper version: 3.5.8-f439ca583e70862c3068a1f2a7d4d068eec33315, built on 05/04/2020 15:07 GMT
+        Latency min/avg/max: 0/0/0
+        Received: 3
+        Sent: 2
+        Connections: 1
+        Outstanding: 0
+        Zxid: 0x100000000
+        Mode: follower
+        Node count: 5
+    "};
+
+    #[test]
+    fn test_parse_srvr() {
+        let parsed = parse_srvr(SRVR_FOLLOWER);
+        assert_eq!(parsed.get("Mode"), Some(&"follower".to_string()));
+        assert_eq!(parsed.get("Node count"), Some(&"5".to_string()));
+        assert_eq!(parsed.get("Zxid"), Some(&"0x100000000".to_string()));
+    }
+
+    #[rstest]
+    #[case::follower(SRVR_FOLLOWER, true)]
+    #[case::leader("Mode: leader\n", true)]
+    #[case::observer("Mode: observer\n", true)]
+    #[case::standalone("Mode: standalone\n", true)]
+    #[case::not_serving("This ZooKeeper instance is not currently serving requests\n", false)]
+    #[case::unknown_mode("Mode: looking\n", false)]
+    #[case::empty("", false)]
+    fn test_is_serving(#[case] response: &str, #[case] expected: bool) {
+        assert_eq!(is_serving(response), expected);
+    }
+
+    const MNTR_LEADER: &str = "zk_version\t3.5.8-f439ca583e70862c3068a1f2a7d4d068eec33315\n\
+        zk_avg_latency\t0\n\
+        zk_server_state\tleader\n\
+        zk_znode_count\t5\n\
+        zk_followers\t2\n";
+
+    #[test]
+    fn test_parse_mntr() {
+        let parsed = parse_mntr(MNTR_LEADER);
+        assert_eq!(parsed.get("zk_server_state"), Some(&"leader".to_string()));
+        assert_eq!(parsed.get("zk_followers"), Some(&"2".to_string()));
+    }
+
+    #[rstest]
+    #[case::leader("imok", MNTR_LEADER, true)]
+    #[case::follower("imok", "zk_server_state\tfollower\n", true)]
+    #[case::not_ok("", MNTR_LEADER, false)]
+    #[case::not_serving(
+        "imok",
+        "This ZooKeeper instance is not currently serving requests\n",
+        false
+    )]
+    #[case::looking("imok", "zk_server_state\tlooking\n", false)]
+    fn test_is_healthy(#[case] ruok: &str, #[case] mntr: &str, #[case] expected: bool) {
+        assert_eq!(is_healthy(ruok, mntr), expected);
+    }
+}
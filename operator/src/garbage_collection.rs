@@ -0,0 +1,175 @@
+//! Garbage collection of the ConfigMaps of a ZooKeeper ensemble which are no longer needed.
+//!
+//! The data and id ConfigMaps are looked up by their labels, which include the version, the role
+//! group and the `myid`, and every configuration of a role group gets its own data ConfigMap.
+//! After a configuration change, an upgrade, the removal of a role group or a scale down, the
+//! ConfigMaps created for the previous state are still owned by the cluster but are not looked up
+//! anymore. Pods are not collected here, `delete_illegal_pods` and `delete_excess_pods` take care
+//! of them.
+use k8s_openapi::api::core::v1::{ConfigMap, Pod};
+use stackable_operator::configmap::CONFIGMAP_TYPE_LABEL;
+use stackable_operator::labels::{APP_COMPONENT_LABEL, APP_ROLE_GROUP_LABEL, APP_VERSION_LABEL};
+use stackable_zookeeper_crd::{CONFIG_MAP_TYPE_DATA, CONFIG_MAP_TYPE_ID};
+use std::collections::BTreeSet;
+
+/// What the data and id ConfigMaps of the cluster are expected to look like.
+pub struct DesiredConfigMaps<'a> {
+    /// The version the servers run or are upgraded to.
+    pub version: &'a str,
+    /// The role and role group of every eligible role group.
+    pub role_groups: BTreeSet<(String, String)>,
+    /// The `myid` of every existing and every planned server.
+    pub ids: BTreeSet<usize>,
+    /// The names of the data ConfigMaps of the current configuration of every role group.
+    pub data_names: BTreeSet<String>,
+}
+
+/// Returns the data and id ConfigMaps which are neither mounted by one of `pods` nor part of the
+/// `desired` state. Other ConfigMaps, e.g. the discovery ConfigMap, are never returned.
+pub fn find_obsolete_config_maps<'a>(
+    config_maps: &'a [ConfigMap],
+    pods: &[Pod],
+    desired: &DesiredConfigMaps,
+) -> Vec<&'a ConfigMap> {
+    let mounted = mounted_config_maps(pods);
+    config_maps
+        .iter()
+        .filter(|config_map| {
+            let labels = &config_map.metadata.labels;
+            let cm_type = match labels.get(CONFIGMAP_TYPE_LABEL) {
+                Some(cm_type)
+                    if cm_type == CONFIG_MAP_TYPE_DATA || cm_type == CONFIG_MAP_TYPE_ID =>
+                {
+                    cm_type
+                }
+                _ => return false,
+            };
+            if let Some(name) = &config_map.metadata.name {
+                if mounted.contains(name.as_str()) {
+                    return false;
+                }
+            }
+
+            let role_group = (
+                labels.get(APP_COMPONENT_LABEL).cloned().unwrap_or_default(),
+                labels
+                    .get(APP_ROLE_GROUP_LABEL)
+                    .cloned()
+                    .unwrap_or_default(),
+            );
+            let id_desired = cm_type != CONFIG_MAP_TYPE_ID
+                || labels
+                    .get(crate::ID_LABEL)
+                    .and_then(|id| id.parse::<usize>().ok())
+                    .map_or(false, |id| desired.ids.contains(&id));
+            let data_desired = cm_type != CONFIG_MAP_TYPE_DATA
+                || config_map
+                    .metadata
+                    .name
+                    .as_ref()
+                    .map_or(false, |name| desired.data_names.contains(name));
+
+            labels.get(APP_VERSION_LABEL).map(String::as_str) != Some(desired.version)
+                || !desired.role_groups.contains(&role_group)
+                || !id_desired
+                || !data_desired
+        })
+        .collect()
+}
+
+/// Returns the names of the ConfigMaps mounted by `pods`.
+fn mounted_config_maps(pods: &[Pod]) -> BTreeSet<&str> {
+    pods.iter()
+        .filter_map(|pod| pod.spec.as_ref())
+        .flat_map(|spec| spec.volumes.iter())
+        .filter_map(|volume| volume.config_map.as_ref())
+        .filter_map(|config_map| config_map.name.as_deref())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::api::core::v1::{ConfigMapVolumeSource, PodSpec, Volume};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+
+    fn config_map(name: &str, cm_type: &str, version: &str, group: &str, id: &str) -> ConfigMap {
+        let mut labels = vec![
+            (CONFIGMAP_TYPE_LABEL, cm_type),
+            (APP_VERSION_LABEL, version),
+            (APP_COMPONENT_LABEL, "server"),
+            (APP_ROLE_GROUP_LABEL, group),
+        ];
+        if !id.is_empty() {
+            labels.push((crate::ID_LABEL, id));
+        }
+        ConfigMap {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                labels: labels
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+                ..ObjectMeta::default()
+            },
+            ..ConfigMap::default()
+        }
+    }
+
+    fn pod_mounting(config_map: &str) -> Pod {
+        Pod {
+            spec: Some(PodSpec {
+                volumes: vec![Volume {
+                    name: "config".to_string(),
+                    config_map: Some(ConfigMapVolumeSource {
+                        name: Some(config_map.to_string()),
+                        ..ConfigMapVolumeSource::default()
+                    }),
+                    ..Volume::default()
+                }],
+                ..PodSpec::default()
+            }),
+            ..Pod::default()
+        }
+    }
+
+    #[test]
+    fn test_find_obsolete_config_maps() {
+        let desired = DesiredConfigMaps {
+            version: "3.5.8",
+            role_groups: vec![("server".to_string(), "default".to_string())]
+                .into_iter()
+                .collect(),
+            ids: vec![1, 2].into_iter().collect(),
+            data_names: vec!["data-abc".to_string()].into_iter().collect(),
+        };
+        let mut discovery = config_map("discovery", "", "3.5.8", "default", "");
+        discovery.metadata.labels.remove(CONFIGMAP_TYPE_LABEL);
+        let config_maps = vec![
+            config_map("data-abc", "data", "3.5.8", "default", ""),
+            config_map("data-old-configuration", "data", "3.5.8", "default", ""),
+            config_map("id-1", "id", "3.5.8", "default", "1"),
+            config_map("id-3", "id", "3.5.8", "default", "3"),
+            config_map("old-data-mounted", "data", "3.4.14", "default", ""),
+            config_map("old-data", "data", "3.4.14", "default", ""),
+            config_map("removed-group", "data", "3.5.8", "removed", ""),
+            discovery,
+        ];
+
+        let obsolete =
+            find_obsolete_config_maps(&config_maps, &[pod_mounting("old-data-mounted")], &desired)
+                .into_iter()
+                .filter_map(|config_map| config_map.metadata.name.as_deref())
+                .collect::<Vec<_>>();
+
+        assert_eq!(
+            obsolete,
+            vec![
+                "data-old-configuration",
+                "id-3",
+                "old-data",
+                "removed-group"
+            ]
+        );
+    }
+}
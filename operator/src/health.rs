@@ -0,0 +1,64 @@
+//! Liveness and readiness of the operator, served next to the [`Metrics`](crate::Metrics) they
+//! are derived from.
+//!
+//! - Liveness: the operator serves the endpoint. Stopped controllers are restarted by the
+//!   supervisor (see [`crate::supervise_controller`]), restarting the whole process would not
+//!   help them.
+//! - Readiness: additionally, the last reconciliation of a ZookeeperCluster did not fail because
+//!   the Kubernetes API server could not be reached.
+use serde::Serialize;
+
+/// The path the liveness is served on.
+pub const LIVENESS_PATH: &str = "/healthz";
+/// The path the readiness is served on.
+pub const READINESS_PATH: &str = "/readyz";
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+    pub live: bool,
+    pub ready: bool,
+    /// Whether this replica runs the controllers, see [`crate::leader_election`].
+    pub leader: bool,
+    pub api_reachable: bool,
+    /// The seconds since a ZookeeperCluster was reconciled successfully the last time, not set
+    /// if none was reconciled yet. Idle clusters are not reconciled, so this is only a hint.
+    pub seconds_since_last_successful_reconcile: Option<i64>,
+}
+
+/// Builds the report from the state recorded in the metrics.
+pub fn evaluate(
+    leader: bool,
+    api_reachable: bool,
+    seconds_since_last_successful_reconcile: Option<i64>,
+) -> HealthReport {
+    HealthReport {
+        live: true,
+        ready: api_reachable,
+        leader,
+        api_reachable,
+        seconds_since_last_successful_reconcile,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(true, true, true, true)]
+    #[case(false, true, true, true)]
+    #[case(true, false, true, false)]
+    #[case(false, false, true, false)]
+    fn test_evaluate(
+        #[case] leader: bool,
+        #[case] api_reachable: bool,
+        #[case] live: bool,
+        #[case] ready: bool,
+    ) {
+        let report = evaluate(leader, api_reachable, None);
+
+        assert_eq!((report.live, report.ready), (live, ready));
+    }
+}
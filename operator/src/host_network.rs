@@ -0,0 +1,316 @@
+//! Running the servers in the network namespace of their nodes (`spec.hostNetwork`).
+//!
+//! On the host network the servers listen directly on the addresses of their nodes, so:
+//!
+//! - The `server.<id>` entries use the `InternalIP` of the nodes (see [`node_addresses`]), node
+//!   names are usually not resolvable with the cluster DNS. On dual-stack nodes the address of
+//!   the preferred `ipFamilies` is used. ZooKeeper listens for the quorum on
+//!   all addresses of the node ([`QUORUM_LISTEN_ON_ALL_IPS`]), e.g. on nodes with several
+//!   networks.
+//! - All ports of a server must differ from each other (see [`validate_ports`]) and no node may
+//!   run two servers of the cluster (see [`validate_shared_nodes`]). Ports used by other
+//!   processes on the nodes can not be detected.
+//! - The DNS policy defaults to `ClusterFirstWithHostNet` (see [`dns_policy`]).
+use crate::{resources, PROPERTIES_FILE};
+
+use k8s_openapi::api::core::v1::Node;
+use kube::ResourceExt;
+use product_config::types::PropertyNameKind;
+use stackable_operator::product_config_utils::ValidatedRoleConfigByPropertyKind;
+use stackable_operator::role_utils::EligibleNodesForRoleAndGroup;
+use stackable_zookeeper_crd::admin_server::ZookeeperAdminServer;
+use stackable_zookeeper_crd::ip_family::{self, IpFamily};
+use stackable_zookeeper_crd::monitoring::JMX_PORT;
+use stackable_zookeeper_crd::tls::DEFAULT_SECURE_CLIENT_PORT;
+use stackable_zookeeper_crd::{
+    DnsPolicy, ZookeeperCluster, ZookeeperVersion, ADMIN_PORT, CLIENT_PORT, METRICS_PORT,
+};
+use std::collections::BTreeMap;
+
+/// The `zoo.cfg` property which makes the servers listen for the quorum on all addresses.
+pub const QUORUM_LISTEN_ON_ALL_IPS: &str = "quorumListenOnAllIPs";
+
+/// Returns the DNS policy of the server pods, `None` for the default of Kubernetes.
+pub fn dns_policy(cluster: &ZookeeperCluster) -> Option<DnsPolicy> {
+    match cluster.spec.dns_policy {
+        Some(dns_policy) => Some(dns_policy),
+        None if cluster.spec.host_network => Some(DnsPolicy::ClusterFirstWithHostNet),
+        None => None,
+    }
+}
+
+/// Returns the `InternalIP` of the eligible nodes by node name, of the most preferred of the
+/// `ip_families` on dual-stack nodes. Nodes without one are missing and addressed by their name.
+pub fn node_addresses(
+    eligible_nodes: &EligibleNodesForRoleAndGroup,
+    ip_families: &[IpFamily],
+) -> BTreeMap<String, String> {
+    eligible_nodes
+        .values()
+        .flat_map(|role_groups| role_groups.values())
+        .flat_map(|(nodes, _)| nodes)
+        .filter_map(|node| Some((node.name(), internal_ip(node, ip_families)?)))
+        .collect()
+}
+
+fn internal_ip(node: &Node, ip_families: &[IpFamily]) -> Option<String> {
+    let internal_ips = node
+        .status
+        .as_ref()?
+        .addresses
+        .iter()
+        .filter(|address| address.type_ == "InternalIP")
+        .map(|address| address.address.as_str())
+        .collect::<Vec<_>>();
+    ip_family::preferred_address(&internal_ips, ip_families).map(str::to_string)
+}
+
+/// Returns the ports which would collide on the host network: every role group uses its client,
+/// quorum, leader election, admin, metrics, JMX and secure client port on the nodes.
+pub fn validate_ports(
+    cluster: &ZookeeperCluster,
+    version: &ZookeeperVersion,
+    validated_role_config: &ValidatedRoleConfigByPropertyKind,
+) -> Vec<String> {
+    let spec = &cluster.spec;
+    let ports = spec.ports.clone().unwrap_or_default();
+    let admin_server_enabled = version.has_admin_server()
+        && spec
+            .admin_server
+            .as_ref()
+            .map_or(true, ZookeeperAdminServer::enabled);
+
+    let mut problems = vec![];
+    for role_groups in validated_role_config.values() {
+        for (group, config) in role_groups {
+            let properties = config.get(&PropertyNameKind::File(PROPERTIES_FILE.to_string()));
+            let property = |name: &str| {
+                properties
+                    .and_then(|properties| properties.get(name))
+                    .and_then(|port| port.parse::<u16>().ok())
+            };
+            let env_metrics_port = config
+                .get(&PropertyNameKind::Env)
+                .and_then(|env| env.get(METRICS_PORT))
+                .and_then(|port| port.parse::<u16>().ok());
+
+            let mut group_ports = vec![
+                (
+                    "client",
+                    Some(property(CLIENT_PORT).unwrap_or_else(|| ports.client())),
+                ),
+                ("quorum", Some(ports.quorum())),
+                ("leader election", Some(ports.leader_election())),
+                (
+                    "admin",
+                    property(ADMIN_PORT).filter(|_| admin_server_enabled),
+                ),
+                (
+                    "metrics",
+                    env_metrics_port
+                        .or_else(|| spec.monitoring.as_ref().map(|monitoring| monitoring.port())),
+                ),
+            ];
+            // the JMX exporter sidecar reads the metrics from the JMX port of the server
+            if resources::jmx_exporter_monitoring(cluster, version).is_some() {
+                group_ports.push(("JMX", Some(JMX_PORT)));
+            }
+            if let Some(tls_config) = spec.tls.as_ref().filter(|tls_config| tls_config.client) {
+                group_ports.push((
+                    "secure client",
+                    Some(
+                        tls_config
+                            .secure_client_port
+                            .unwrap_or(DEFAULT_SECURE_CLIENT_PORT),
+                    ),
+                ));
+            }
+
+            let group_ports = group_ports
+                .into_iter()
+                .filter_map(|(name, port)| Some((name, port?)))
+                .collect::<Vec<_>>();
+            // conflicts between the `ports` of the cluster are reported by their validation
+            let is_spec_port = |name: &str, port: u16| match name {
+                "quorum" | "leader election" => true,
+                "client" => port == ports.client(),
+                _ => false,
+            };
+            for (index, (name, port)) in group_ports.iter().enumerate() {
+                if let Some((other_name, _)) =
+                    group_ports[..index]
+                        .iter()
+                        .find(|(other_name, other_port)| {
+                            other_port == port
+                                && !(is_spec_port(name, *port)
+                                    && is_spec_port(other_name, *other_port))
+                        })
+                {
+                    problems.push(format!(
+                        "hostNetwork: The {} port [{}] of the role group [{}] is already used by the {} port",
+                        name, port, group, other_name
+                    ));
+                }
+            }
+        }
+    }
+    problems
+}
+
+/// Returns the nodes which are eligible for more than one role group. Their servers would use
+/// the same ports on the host network.
+pub fn validate_shared_nodes(eligible_nodes: &EligibleNodesForRoleAndGroup) -> Vec<String> {
+    let mut groups_by_node = BTreeMap::<String, Vec<String>>::new();
+    for role_groups in eligible_nodes.values() {
+        for (group, (nodes, _)) in role_groups {
+            for node in nodes {
+                groups_by_node
+                    .entry(node.name())
+                    .or_default()
+                    .push(group.clone());
+            }
+        }
+    }
+    groups_by_node
+        .into_iter()
+        .filter(|(_, groups)| groups.len() > 1)
+        .map(|(node, mut groups)| {
+            groups.sort();
+            format!(
+                "hostNetwork: The node [{}] is eligible for the role groups [{}], their servers would use the same ports",
+                node,
+                groups.join(", ")
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    fn cluster(spec: Value) -> ZookeeperCluster {
+        test_support::cluster("simple", spec)
+    }
+
+    fn node(name: &str, internal_ips: &[&str]) -> Node {
+        let mut node: Node = serde_yaml::from_str(&format!("metadata:\n  name: {}", name)).unwrap();
+        if !internal_ips.is_empty() {
+            let addresses = internal_ips
+                .iter()
+                .map(|internal_ip| {
+                    format!("\n  - type: InternalIP\n    address: '{}'", internal_ip)
+                })
+                .collect::<String>();
+            node.status = Some(
+                serde_yaml::from_str(&format!(
+                    "addresses:\n  - type: Hostname\n    address: {}{}",
+                    name, addresses
+                ))
+                .unwrap(),
+            );
+        }
+        node
+    }
+
+    fn eligible_nodes(groups: &[(&str, Vec<Node>)]) -> EligibleNodesForRoleAndGroup {
+        let mut role_groups = HashMap::new();
+        for (group, nodes) in groups {
+            role_groups.insert(group.to_string(), (nodes.clone(), None));
+        }
+        let mut eligible_nodes = HashMap::new();
+        eligible_nodes.insert("server".to_string(), role_groups);
+        eligible_nodes
+    }
+
+    #[test]
+    fn test_dns_policy() {
+        assert_eq!(dns_policy(&cluster(json!({ "hostNetwork": false }))), None);
+        assert_eq!(
+            dns_policy(&cluster(json!({ "hostNetwork": true }))),
+            Some(DnsPolicy::ClusterFirstWithHostNet)
+        );
+        assert_eq!(
+            dns_policy(&cluster(json!({ "dnsPolicy": "Default" }))),
+            Some(DnsPolicy::Default)
+        );
+    }
+
+    #[test]
+    fn test_node_addresses() {
+        let eligible_nodes = eligible_nodes(&[(
+            "default",
+            vec![node("node-1", &["10.0.0.1"]), node("node-2", &[])],
+        )]);
+
+        let mut expected = BTreeMap::new();
+        expected.insert("node-1".to_string(), "10.0.0.1".to_string());
+        assert_eq!(node_addresses(&eligible_nodes, &[]), expected);
+    }
+
+    #[test]
+    fn test_node_addresses_of_dual_stack_nodes() {
+        let eligible_nodes = eligible_nodes(&[(
+            "default",
+            vec![node("node-1", &["10.0.0.1", "2001:db8::1"])],
+        )]);
+
+        assert_eq!(
+            node_addresses(&eligible_nodes, &[IpFamily::IPv6, IpFamily::IPv4])
+                .get("node-1")
+                .map(String::as_str),
+            Some("2001:db8::1")
+        );
+        assert_eq!(
+            node_addresses(&eligible_nodes, &[])
+                .get("node-1")
+                .map(String::as_str),
+            Some("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn test_validate_ports() {
+        let cluster = cluster(json!({ "monitoring": { "port": 2888 } }));
+        let mut properties = BTreeMap::new();
+        properties.insert(CLIENT_PORT.to_string(), "3888".to_string());
+        let mut config = HashMap::new();
+        config.insert(
+            PropertyNameKind::File(PROPERTIES_FILE.to_string()),
+            properties,
+        );
+        let mut role_groups = HashMap::new();
+        role_groups.insert("default".to_string(), config);
+        let mut validated_role_config = HashMap::new();
+        validated_role_config.insert("server".to_string(), role_groups);
+
+        assert_eq!(
+            validate_ports(
+                &cluster,
+                &ZookeeperVersion::from_str("3.5.8").unwrap(),
+                &validated_role_config
+            ),
+            vec![
+                "hostNetwork: The leader election port [3888] of the role group [default] is already used by the client port",
+                "hostNetwork: The metrics port [2888] of the role group [default] is already used by the quorum port",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_shared_nodes() {
+        let eligible_nodes = eligible_nodes(&[
+            ("default", vec![node("node-1", &[]), node("node-2", &[])]),
+            ("observers", vec![node("node-2", &[])]),
+        ]);
+
+        assert_eq!(
+            validate_shared_nodes(&eligible_nodes),
+            vec!["hostNetwork: The node [node-2] is eligible for the role groups [default, observers], their servers would use the same ports"]
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Resolution of the image (for the Stackable Agent: the package) the servers run.
+//!
+//! Organizations building their own ZooKeeper images with their own naming scheme can provide
+//! an [`ImageResolver`], either by passing an image template on the command line (see
+//! [`TemplateImageResolver`]) or by implementing the trait when embedding the operator.
+use stackable_zookeeper_crd::ZookeeperVersion;
+
+/// Maps the version, the CPU architecture of the node and the image variant requested in the
+/// spec to a concrete image reference.
+pub trait ImageResolver: Send + Sync {
+    /// Returns the image for a server.
+    ///
+    /// # Arguments
+    ///
+    /// - `version` - The ZooKeeper version to run.
+    /// - `arch` - The CPU architecture of the node (its `kubernetes.io/arch` label), if known.
+    /// - `variant` - The `imageVariant` from the spec, if set.
+    ///
+    fn resolve(
+        &self,
+        version: &ZookeeperVersion,
+        arch: Option<&str>,
+        variant: Option<&str>,
+    ) -> String;
+
+    /// Returns the CPU architectures (values of the `kubernetes.io/arch` label) the images are
+    /// available for, empty if unknown. The servers of clusters which do not declare
+    /// `spec.placement.architectures` are only placed on nodes with one of these architectures.
+    fn architectures(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Resolves to the official Stackable images: `stackable/zookeeper:<version>`, or
+/// `stackable/zookeeper:<version>-<variant>` if a variant is requested.
+#[derive(Clone, Debug, Default)]
+pub struct DefaultImageResolver {
+    architectures: Vec<String>,
+}
+
+impl DefaultImageResolver {
+    /// Declares the CPU architectures the images are available for.
+    pub fn with_architectures(mut self, architectures: Vec<String>) -> DefaultImageResolver {
+        self.architectures = architectures;
+        self
+    }
+}
+
+impl ImageResolver for DefaultImageResolver {
+    fn resolve(
+        &self,
+        version: &ZookeeperVersion,
+        _arch: Option<&str>,
+        variant: Option<&str>,
+    ) -> String {
+        match variant {
+            Some(variant) => format!("stackable/zookeeper:{}-{}", version, variant),
+            None => format!("stackable/zookeeper:{}", version),
+        }
+    }
+
+    fn architectures(&self) -> Vec<String> {
+        self.architectures.clone()
+    }
+}
+
+/// Resolves images from a template like `registry.example.com/zookeeper:{version}-{arch}`.
+///
+/// The placeholders `{version}`, `{arch}` and `{variant}` are replaced, unknown values are
+/// replaced with an empty string.
+#[derive(Clone, Debug)]
+pub struct TemplateImageResolver {
+    template: String,
+    architectures: Vec<String>,
+}
+
+impl TemplateImageResolver {
+    pub fn new(template: &str) -> TemplateImageResolver {
+        TemplateImageResolver {
+            template: template.to_string(),
+            architectures: Vec::new(),
+        }
+    }
+
+    /// Declares the CPU architectures the images are available for.
+    pub fn with_architectures(mut self, architectures: Vec<String>) -> TemplateImageResolver {
+        self.architectures = architectures;
+        self
+    }
+}
+
+impl ImageResolver for TemplateImageResolver {
+    fn resolve(
+        &self,
+        version: &ZookeeperVersion,
+        arch: Option<&str>,
+        variant: Option<&str>,
+    ) -> String {
+        self.template
+            .replace("{version}", &version.to_string())
+            .replace("{arch}", arch.unwrap_or_default())
+            .replace("{variant}", variant.unwrap_or_default())
+    }
+
+    fn architectures(&self) -> Vec<String> {
+        self.architectures.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_image_resolver() {
+        let resolver = DefaultImageResolver::default();
+
+        assert_eq!(
+            resolver.resolve(&"3.5.8".parse().unwrap(), Some("amd64"), None),
+            "stackable/zookeeper:3.5.8"
+        );
+        assert_eq!(
+            resolver.resolve(&"3.5.8".parse().unwrap(), None, Some("fips")),
+            "stackable/zookeeper:3.5.8-fips"
+        );
+        assert_eq!(
+            resolver.resolve(&"3.6.2".parse().unwrap(), None, None),
+            "stackable/zookeeper:3.6.2"
+        );
+    }
+
+    #[test]
+    fn test_template_image_resolver() {
+        let resolver =
+            TemplateImageResolver::new("registry.example.com/zookeeper-{variant}:{version}-{arch}");
+
+        assert_eq!(
+            resolver.resolve(&"3.4.14".parse().unwrap(), Some("arm64"), Some("hardened")),
+            "registry.example.com/zookeeper-hardened:3.4.14-arm64"
+        );
+        assert_eq!(
+            resolver.resolve(&"3.4.14".parse().unwrap(), None, None),
+            "registry.example.com/zookeeper-:3.4.14-"
+        );
+    }
+
+    #[test]
+    fn test_architectures() {
+        let architectures = vec!["amd64".to_string(), "arm64".to_string()];
+
+        assert!(DefaultImageResolver::default().architectures().is_empty());
+        assert_eq!(
+            DefaultImageResolver::default()
+                .with_architectures(architectures.clone())
+                .architectures(),
+            architectures
+        );
+        assert_eq!(
+            TemplateImageResolver::new("registry.example.com/zookeeper:{version}-{arch}")
+                .with_architectures(architectures.clone())
+                .architectures(),
+            architectures
+        );
+    }
+}
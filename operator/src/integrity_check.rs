@@ -0,0 +1,231 @@
+//! Verifying the data of the leader before an upgrade, see
+//! [`stackable_zookeeper_crd::upgrade`].
+//!
+//! The check runs `integrity_check.sh` in the server container of the leader (see
+//! [`crate::pod_exec`]), which verifies the latest snapshot in the data directory with
+//! `zkSnapShotToolkit.sh` (or the `SnapshotFormatter` of versions before 3.5.5). So it checks the
+//! data the leader actually serves, with the tools of the current version, wherever the data
+//! directory is stored. The script writes nothing, which keeps it working with a read-only root
+//! filesystem.
+use crate::error::Error;
+use crate::pod_exec::PodExecutor;
+
+use k8s_openapi::api::core::v1::Pod;
+use kube::{Api, ResourceExt};
+use stackable_zookeeper_crd::APP_NAME;
+use std::time::Duration;
+
+const INTEGRITY_CHECK_SCRIPT: &str = include_str!("integrity_check.sh");
+
+/// The result of a finished check.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CheckResult {
+    /// Whether the snapshot is valid.
+    pub passed: bool,
+    /// Why the check passed or failed, the last line of the output.
+    pub message: String,
+    /// The output of the script, i.e. the end of the output of the tool if the check failed.
+    pub output: String,
+}
+
+/// Builds the command verifying the latest snapshot in `data_dir` with the tools in
+/// `zookeeper_home`, the installation directory of the version the leader runs.
+pub fn build_command(zookeeper_home: &str, data_dir: &str) -> Vec<String> {
+    vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        INTEGRITY_CHECK_SCRIPT.to_string(),
+        "integrity_check.sh".to_string(),
+        zookeeper_home.to_string(),
+        data_dir.to_string(),
+    ]
+}
+
+/// Verifies the latest snapshot in `data_dir` in the server container of the `leader`. A check
+/// which takes longer than `timeout_seconds` fails.
+pub async fn run(
+    executor: &dyn PodExecutor,
+    pods_api: Api<Pod>,
+    leader: &Pod,
+    zookeeper_home: &str,
+    data_dir: &str,
+    timeout_seconds: u32,
+) -> Result<CheckResult, Error> {
+    let has_server_container = leader
+        .spec
+        .iter()
+        .flat_map(|spec| spec.containers.iter())
+        .any(|container| container.name == APP_NAME);
+    if !has_server_container {
+        return Err(Error::ReconcileError(format!(
+            "The pod [{}] has no [{}] container",
+            leader.name(),
+            APP_NAME
+        )));
+    }
+
+    let exec = executor.exec(
+        pods_api,
+        &leader.name(),
+        APP_NAME,
+        build_command(zookeeper_home, data_dir),
+    );
+    let output =
+        match tokio::time::timeout(Duration::from_secs(u64::from(timeout_seconds)), exec).await {
+            Ok(output) => output?,
+            Err(_) => {
+                return Ok(CheckResult {
+                    passed: false,
+                    message: format!(
+                        "The check did not finish within {} seconds",
+                        timeout_seconds
+                    ),
+                    output: String::new(),
+                })
+            }
+        };
+    let message = output
+        .stdout
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| {
+            if output.success {
+                "The snapshot is valid".to_string()
+            } else {
+                "The check failed".to_string()
+            }
+        });
+    Ok(CheckResult {
+        passed: output.success,
+        message,
+        output: output.stdout,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pod_exec::ExecOutput;
+    use crate::security_context;
+    use crate::test_support::{self, FakeApiServer, FakePodExecutor};
+    use serde_json::json;
+    use stackable_zookeeper_crd::security_context::ZookeeperSecurityContext;
+
+    const DATA_DIR: &str = "/tmp/zookeeper";
+
+    /// The leader with the security context of the operator, i.e. a read-only root filesystem
+    /// with an `emptyDir` volume at `/tmp` containing the data directory.
+    fn leader() -> Pod {
+        let cluster = test_support::cluster("simple", json!({}));
+        let mut leader = test_support::server_pod(&cluster, "default", 1, "node-1");
+        let security_context = ZookeeperSecurityContext::default();
+        security_context::apply(
+            leader.spec.as_mut().unwrap(),
+            &security_context,
+            &security_context::writable_dirs(&security_context, None),
+        );
+        leader
+    }
+
+    #[tokio::test]
+    async fn test_run_in_leader_with_read_only_root_filesystem() {
+        let server = FakeApiServer::new();
+        let executor = FakePodExecutor::new(ExecOutput {
+            success: true,
+            stdout: "The snapshot /tmp/zookeeper/version-2/snapshot.1 is valid\n".to_string(),
+        });
+        let leader = leader();
+        let container = &leader.spec.as_ref().unwrap().containers[0];
+        assert_eq!(
+            container
+                .security_context
+                .as_ref()
+                .and_then(|context| context.read_only_root_filesystem),
+            Some(true)
+        );
+        assert!(container
+            .volume_mounts
+            .iter()
+            .any(|mount| DATA_DIR.starts_with(&mount.mount_path)));
+
+        let result = run(
+            executor.executor().as_ref(),
+            Api::namespaced(server.client(), test_support::NAMESPACE),
+            &leader,
+            "apache-zookeeper-3.5.8-bin",
+            DATA_DIR,
+            600,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            result,
+            CheckResult {
+                passed: true,
+                message: "The snapshot /tmp/zookeeper/version-2/snapshot.1 is valid".to_string(),
+                output: "The snapshot /tmp/zookeeper/version-2/snapshot.1 is valid\n".to_string(),
+            }
+        );
+        let execs = executor.execs();
+        assert_eq!(execs.len(), 1);
+        assert_eq!(execs[0].pod, "simple-server-default-1");
+        assert_eq!(execs[0].container, "zookeeper");
+        assert_eq!(
+            &execs[0].command[4..],
+            &["apache-zookeeper-3.5.8-bin", DATA_DIR]
+        );
+        // nothing is written, e.g. to `/tmp` of a Job without its volumes before
+        assert!(!INTEGRITY_CHECK_SCRIPT
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .any(|line| line.contains("> ") && !line.contains("/dev/null")));
+    }
+
+    #[tokio::test]
+    async fn test_run_with_corrupt_snapshot() {
+        let server = FakeApiServer::new();
+        let executor = FakePodExecutor::new(ExecOutput {
+            success: false,
+            stdout: "java.io.IOException: CRC corruption\nThe snapshot /tmp/zookeeper/version-2/snapshot.1 is corrupt: java.io.IOException: CRC corruption\n".to_string(),
+        });
+
+        let result = run(
+            executor.executor().as_ref(),
+            Api::namespaced(server.client(), test_support::NAMESPACE),
+            &leader(),
+            "apache-zookeeper-3.5.8-bin",
+            DATA_DIR,
+            600,
+        )
+        .await
+        .unwrap();
+
+        assert!(!result.passed);
+        assert_eq!(
+            result.message,
+            "The snapshot /tmp/zookeeper/version-2/snapshot.1 is corrupt: java.io.IOException: CRC corruption"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_without_server_container() {
+        let server = FakeApiServer::new();
+        let executor = FakePodExecutor::new(ExecOutput::default());
+
+        assert!(run(
+            executor.executor().as_ref(),
+            Api::namespaced(server.client(), test_support::NAMESPACE),
+            &Pod::default(),
+            "zookeeper",
+            DATA_DIR,
+            600
+        )
+        .await
+        .is_err());
+        assert!(executor.execs().is_empty());
+    }
+}
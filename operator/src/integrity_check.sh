@@ -0,0 +1,45 @@
+#!/usr/bin/env sh
+# Verifies the latest snapshot in the data directory of a ZooKeeper server, run by the
+# zookeeper-operator in the server container of the leader before an upgrade.
+#
+# Usage: integrity_check.sh <ZooKeeper home> <data directory>
+#
+# The last line of the output is the result. The check fails if the snapshot can not be
+# deserialized or its checksum does not match, a data directory without snapshots passes.
+# Nothing is written to the filesystem, so the check works with a read-only root filesystem.
+set -eu
+exec 2>&1
+
+ZOOKEEPER_HOME="$1"
+DATA_DIR="$2"
+FAILED_MARKER="integrity check failed with exit code"
+
+snapshot="$(ls -t "$DATA_DIR"/version-2/snapshot.* 2>/dev/null | head -n 1 || true)"
+if [ -z "$snapshot" ]; then
+  echo "No snapshot found in $DATA_DIR/version-2"
+  exit 0
+fi
+
+# zkSnapShotToolkit.sh is only shipped since 3.5.5, older versions run the formatter directly
+if [ -x "$ZOOKEEPER_HOME/bin/zkSnapShotToolkit.sh" ]; then
+  set -- "$ZOOKEEPER_HOME/bin/zkSnapShotToolkit.sh" "$snapshot"
+else
+  set +u
+  . "$ZOOKEEPER_HOME/bin/zkEnv.sh"
+  set -u
+  set -- java -cp "$CLASSPATH" org.apache.zookeeper.server.SnapshotFormatter "$snapshot"
+fi
+
+# the tools print every znode, only the end of the output is kept
+output="$( { "$@" 2>&1 || echo "$FAILED_MARKER $?"; } | tail -n 20)"
+case "$(echo "$output" | tail -n 1)" in
+  "$FAILED_MARKER"*)
+    output="$(echo "$output" | sed '$d')"
+    echo "$output"
+    echo "The snapshot $snapshot is corrupt: $(echo "$output" | grep -i -m 1 'exception\|error\|corrupt' || echo "$output" | tail -n 1)"
+    exit 1
+    ;;
+  *)
+    echo "The snapshot $snapshot is valid"
+    ;;
+esac
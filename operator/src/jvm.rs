@@ -0,0 +1,167 @@
+//! Rendering of the JVM flags of the ZooKeeper servers.
+//!
+//! All flags end up in the `SERVER_JVMFLAGS` environment variable, which `zkServer.sh` only
+//! passes to the server (and not to the CLI tools). The JVM uses the last occurrence of a flag,
+//! so the flags rendered here take precedence over the defaults of the start scripts (e.g. the
+//! heap set via `ZK_SERVER_HEAP`).
+use crate::error::Error;
+
+use stackable_zookeeper_crd::JvmConfig;
+
+/// The environment variable holding the JVM flags of the server.
+pub const SERVER_JVMFLAGS: &str = "SERVER_JVMFLAGS";
+
+/// The share of the memory limit used for the heap if no heap size is configured. The rest is
+/// left for metaspace, thread stacks, direct buffers etc.
+const HEAP_SHARE_OF_MEMORY_LIMIT: f64 = 0.75;
+
+/// Renders the JVM flags of the server.
+///
+/// # Arguments
+///
+/// - `jvm` - The JVM configuration from the spec.
+/// - `memory_limit` - The memory limit of the container (e.g. `1Gi`), the heap is derived from it
+///   if `jvm.heapSize` is not set.
+/// - `java_agent` - A `-javaagent` flag to add (e.g. for the metrics exporter).
+///
+pub fn build_server_jvm_flags(
+    jvm: Option<&JvmConfig>,
+    memory_limit: Option<&str>,
+    java_agent: Option<String>,
+) -> Result<String, Error> {
+    let mut flags = vec![];
+    flags.extend(java_agent);
+
+    let heap_bytes = match jvm.and_then(|jvm| jvm.heap_size.as_deref()) {
+        Some(heap_size) => Some(parse_memory_quantity(heap_size)?),
+        None => match memory_limit {
+            Some(memory_limit) => Some(
+                (parse_memory_quantity(memory_limit)? as f64 * HEAP_SHARE_OF_MEMORY_LIMIT) as u64,
+            ),
+            None => None,
+        },
+    };
+    if let Some(heap_bytes) = heap_bytes {
+        // Xms == Xmx avoids pauses for growing the heap, which can cause session expirations
+        let heap_megabytes = (heap_bytes / (1024 * 1024)).max(1);
+        flags.push(format!("-Xms{}m", heap_megabytes));
+        flags.push(format!("-Xmx{}m", heap_megabytes));
+    }
+
+    if let Some(jvm) = jvm {
+        flags.extend(jvm.gc_options.iter().cloned());
+        flags.extend(
+            jvm.system_properties
+                .iter()
+                .map(|(key, value)| format!("-D{}={}", key, value)),
+        );
+        flags.extend(jvm.extra_flags.iter().cloned());
+    }
+
+    Ok(flags.join(" "))
+}
+
+/// Joins the flags for [`SERVER_JVMFLAGS`], skipping empty ones (e.g. the result of
+/// [`build_server_jvm_flags`] without any flags).
+pub fn join_flags(flags: &[String]) -> String {
+    flags
+        .iter()
+        .filter(|flag| !flag.is_empty())
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parses a Kubernetes memory quantity (e.g. `512Mi`, `1G` or `1000000`) into bytes.
+pub fn parse_memory_quantity(quantity: &str) -> Result<u64, Error> {
+    let quantity = quantity.trim();
+    let split_at = quantity
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or_else(|| quantity.len());
+    let (number, suffix) = quantity.split_at(split_at);
+
+    let multiplier: u64 = match suffix {
+        "" => 1,
+        "k" => 1000,
+        "M" => 1000_u64.pow(2),
+        "G" => 1000_u64.pow(3),
+        "T" => 1000_u64.pow(4),
+        "Ki" => 1024,
+        "Mi" => 1024_u64.pow(2),
+        "Gi" => 1024_u64.pow(3),
+        "Ti" => 1024_u64.pow(4),
+        _ => return Err(invalid_quantity(quantity)),
+    };
+    let number: f64 = number.parse().map_err(|_| invalid_quantity(quantity))?;
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+fn invalid_quantity(quantity: &str) -> Error {
+    Error::ReconcileError(format!("Invalid memory quantity [{}]", quantity))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::collections::BTreeMap;
+
+    #[rstest]
+    #[case("1Gi", 1024 * 1024 * 1024)]
+    #[case("512Mi", 512 * 1024 * 1024)]
+    #[case("1.5Gi", 1536 * 1024 * 1024)]
+    #[case("2G", 2_000_000_000)]
+    #[case("1000", 1000)]
+    fn test_parse_memory_quantity(#[case] quantity: &str, #[case] expected: u64) {
+        assert_eq!(parse_memory_quantity(quantity).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("1Xi")]
+    #[case("Gi")]
+    fn test_parse_invalid_memory_quantity(#[case] quantity: &str) {
+        assert!(parse_memory_quantity(quantity).is_err());
+    }
+
+    #[test]
+    fn test_heap_derived_from_memory_limit() {
+        assert_eq!(
+            build_server_jvm_flags(None, Some("1Gi"), None).unwrap(),
+            "-Xms768m -Xmx768m"
+        );
+        assert_eq!(build_server_jvm_flags(None, None, None).unwrap(), "");
+    }
+
+    #[test]
+    fn test_join_flags() {
+        assert_eq!(
+            join_flags(&[
+                "".to_string(),
+                "-Xms768m -Xmx768m".to_string(),
+                "-Dzookeeper.ssl.keyStore.password=$(STORE_PASSWORD)".to_string(),
+            ]),
+            "-Xms768m -Xmx768m -Dzookeeper.ssl.keyStore.password=$(STORE_PASSWORD)"
+        );
+        assert_eq!(join_flags(&["".to_string()]), "");
+    }
+
+    #[test]
+    fn test_server_jvm_flags() {
+        let mut system_properties = BTreeMap::new();
+        system_properties.insert("zookeeper.snapCount".to_string(), "50000".to_string());
+        let jvm = JvmConfig {
+            heap_size: Some("2Gi".to_string()),
+            gc_options: vec!["-XX:+UseG1GC".to_string()],
+            system_properties,
+            extra_flags: vec!["-XX:+HeapDumpOnOutOfMemoryError".to_string()],
+        };
+
+        assert_eq!(
+            build_server_jvm_flags(Some(&jvm), Some("1Gi"), Some("-javaagent:agent.jar".to_string()))
+                .unwrap(),
+            "-javaagent:agent.jar -Xms2048m -Xmx2048m -XX:+UseG1GC -Dzookeeper.snapCount=50000 -XX:+HeapDumpOnOutOfMemoryError"
+        );
+    }
+}
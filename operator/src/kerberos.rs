@@ -0,0 +1,84 @@
+//! Mounting the keytab and the Kerberos configuration into the server pods, see
+//! [`stackable_zookeeper_crd::kerberos`] for the `zoo.cfg` properties and the JAAS configuration.
+use k8s_openapi::api::core::v1::{ConfigMapVolumeSource, SecretVolumeSource, Volume, VolumeMount};
+use stackable_zookeeper_crd::kerberos::{
+    ZookeeperKerberos, KERBEROS_HOST_PROPERTY, KEYTAB_DIR, KRB5_CONF_DIR, KRB5_CONF_KEY,
+};
+
+pub const KEYTAB_VOLUME_NAME: &str = "keytab";
+pub const KRB5_CONF_VOLUME_NAME: &str = "krb5";
+
+/// Builds the volumes containing the keytab and the Kerberos configuration and their mounts.
+pub fn build_volumes(kerberos: &ZookeeperKerberos) -> Vec<(Volume, VolumeMount)> {
+    let keytab_volume = Volume {
+        name: KEYTAB_VOLUME_NAME.to_string(),
+        secret: Some(SecretVolumeSource {
+            secret_name: Some(kerberos.keytab_secret_name.clone()),
+            ..SecretVolumeSource::default()
+        }),
+        ..Volume::default()
+    };
+    let krb5_conf_volume = Volume {
+        name: KRB5_CONF_VOLUME_NAME.to_string(),
+        config_map: Some(ConfigMapVolumeSource {
+            name: Some(kerberos.krb5_config_map_name.clone()),
+            ..ConfigMapVolumeSource::default()
+        }),
+        ..Volume::default()
+    };
+
+    vec![
+        (
+            keytab_volume,
+            build_volume_mount(KEYTAB_VOLUME_NAME, KEYTAB_DIR),
+        ),
+        (
+            krb5_conf_volume,
+            build_volume_mount(KRB5_CONF_VOLUME_NAME, KRB5_CONF_DIR),
+        ),
+    ]
+}
+
+fn build_volume_mount(name: &str, mount_path: &str) -> VolumeMount {
+    VolumeMount {
+        name: name.to_string(),
+        mount_path: mount_path.to_string(),
+        read_only: Some(true),
+        ..VolumeMount::default()
+    }
+}
+
+/// Builds the JVM flags of a server running on `node_name`.
+///
+/// # Arguments
+///
+/// - `jaas_config_path` - The path of the JAAS configuration rendered into the config directory.
+/// - `node_name` - The name of the node, it replaces `_HOST` in the principal.
+///
+pub fn build_jvm_flags(jaas_config_path: &str, node_name: &str) -> Vec<String> {
+    vec![
+        format!("-Djava.security.auth.login.config={}", jaas_config_path),
+        format!(
+            "-Djava.security.krb5.conf={}/{}",
+            KRB5_CONF_DIR, KRB5_CONF_KEY
+        ),
+        format!("-D{}={}", KERBEROS_HOST_PROPERTY, node_name),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_jvm_flags() {
+        assert_eq!(
+            build_jvm_flags("/stackable/conf/jaas.conf", "node-1"),
+            vec![
+                "-Djava.security.auth.login.config=/stackable/conf/jaas.conf",
+                "-Djava.security.krb5.conf=/stackable/kerberos/krb5/krb5.conf",
+                "-Dzookeeper.kerberos.host=node-1"
+            ]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! Skipping the cluster wide objects if nothing they are built from changed.
+//!
+//! Every status update and every change of an owned object triggers another reconciliation, but
+//! the cluster wide objects (the super user Secret, the ServiceAccount, the PodDisruptionBudget,
+//! the NetworkPolicy, the admin Service, the PodMonitor and the PrometheusRule) only depend on the
+//! spec and a few other inputs. Once they were applied completely, `status.lastApplied` records the generation
+//! and a fingerprint of these inputs, and they are applied again only if
+//!
+//! - the generation changed,
+//! - the fingerprint changed, i.e. a referenced Secret or ConfigMap, an owned ConfigMap or
+//!   Service, the eligible nodes or the operator version, or
+//! - [`FULL_APPLY_INTERVAL_SECONDS`] passed, which restores objects which were changed or
+//!   deleted by someone else but are not cached by the operator.
+//!
+//! The pods, the health checks and the status are reconciled every time.
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use k8s_openapi::chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+use stackable_zookeeper_crd::LastAppliedStatus;
+
+/// The cluster wide objects are applied at least this often, even if nothing changed.
+pub const FULL_APPLY_INTERVAL_SECONDS: i64 = 600;
+
+/// Hashes everything besides the spec the cluster wide objects are built from.
+///
+/// `owned_objects` only contribute their names and resource versions, the order of them and of
+/// `node_names` does not matter.
+pub fn fingerprint(
+    generation: i64,
+    references_hash: Option<&str>,
+    owned_objects: &[&ObjectMeta],
+    node_names: &[String],
+) -> String {
+    let mut owned_objects = owned_objects
+        .iter()
+        .map(|metadata| {
+            format!(
+                "{}@{}",
+                metadata.name.as_deref().unwrap_or_default(),
+                metadata.resource_version.as_deref().unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>();
+    owned_objects.sort();
+    let mut node_names = node_names.to_vec();
+    node_names.sort();
+    node_names.dedup();
+
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hasher.update(b"\n");
+    hasher.update(generation.to_string());
+    hasher.update(b"\n");
+    hasher.update(references_hash.unwrap_or_default());
+    hasher.update(b"\n");
+    hasher.update(owned_objects.join(","));
+    hasher.update(b"\n");
+    hasher.update(node_names.join(","));
+    format!("{:x}", hasher.finalize())
+}
+
+/// Returns whether the cluster wide objects were applied for `generation` and `fingerprint`
+/// less than [`FULL_APPLY_INTERVAL_SECONDS`] before `now`.
+pub fn is_applied(
+    last_applied: Option<&LastAppliedStatus>,
+    generation: i64,
+    fingerprint: &str,
+    now: DateTime<Utc>,
+) -> bool {
+    let last_applied = match last_applied {
+        Some(last_applied) => last_applied,
+        None => return false,
+    };
+    let applied_at = match DateTime::parse_from_rfc3339(&last_applied.applied_at) {
+        Ok(applied_at) => applied_at.with_timezone(&Utc),
+        Err(_) => return false,
+    };
+    last_applied.generation == generation
+        && last_applied.fingerprint == fingerprint
+        && applied_at <= now
+        && now - applied_at < Duration::seconds(FULL_APPLY_INTERVAL_SECONDS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn metadata(name: &str, resource_version: &str) -> ObjectMeta {
+        ObjectMeta {
+            name: Some(name.to_string()),
+            resource_version: Some(resource_version.to_string()),
+            ..ObjectMeta::default()
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_order() {
+        let config_map = metadata("simple-server-default", "10");
+        let service = metadata("simple-admin", "11");
+        let nodes = vec!["node-1".to_string(), "node-2".to_string()];
+        let reversed_nodes = vec!["node-2".to_string(), "node-1".to_string()];
+
+        assert_eq!(
+            fingerprint(1, Some("abc"), &[&config_map, &service], &nodes),
+            fingerprint(1, Some("abc"), &[&service, &config_map], &reversed_nodes)
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_changes_with_inputs() {
+        let config_map = metadata("simple-server-default", "10");
+        let changed_config_map = metadata("simple-server-default", "12");
+        let nodes = vec!["node-1".to_string()];
+        let base = fingerprint(1, Some("abc"), &[&config_map], &nodes);
+
+        assert_ne!(base, fingerprint(2, Some("abc"), &[&config_map], &nodes));
+        assert_ne!(base, fingerprint(1, None, &[&config_map], &nodes));
+        assert_ne!(
+            base,
+            fingerprint(1, Some("abc"), &[&changed_config_map], &nodes)
+        );
+        assert_ne!(base, fingerprint(1, Some("abc"), &[&config_map], &[]));
+    }
+
+    #[rstest]
+    #[case::never_applied(None, 3, "abc", false)]
+    #[case::applied(Some((3, "abc", "2021-08-01T10:00:00Z")), 3, "abc", true)]
+    #[case::generation_changed(Some((2, "abc", "2021-08-01T10:00:00Z")), 3, "abc", false)]
+    #[case::fingerprint_changed(Some((3, "def", "2021-08-01T10:00:00Z")), 3, "abc", false)]
+    #[case::interval_passed(Some((3, "abc", "2021-08-01T09:50:00Z")), 3, "abc", false)]
+    #[case::applied_in_the_future(Some((3, "abc", "2021-08-01T10:06:00Z")), 3, "abc", false)]
+    #[case::invalid_timestamp(Some((3, "abc", "yesterday")), 3, "abc", false)]
+    fn test_is_applied(
+        #[case] last_applied: Option<(i64, &str, &str)>,
+        #[case] generation: i64,
+        #[case] fingerprint: &str,
+        #[case] expected: bool,
+    ) {
+        let last_applied =
+            last_applied.map(|(generation, fingerprint, applied_at)| LastAppliedStatus {
+                generation,
+                fingerprint: fingerprint.to_string(),
+                applied_at: applied_at.to_string(),
+            });
+        let now = DateTime::parse_from_rfc3339("2021-08-01T10:05:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(
+            is_applied(last_applied.as_ref(), generation, fingerprint, now),
+            expected
+        );
+    }
+}
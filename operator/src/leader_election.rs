@@ -0,0 +1,391 @@
+//! Leader election between the replicas of the operator.
+//!
+//! The replicas compete for a `Lease` (`coordination.k8s.io/v1`) and only the one holding it
+//! runs the controllers, the others wait until it is released or expires. The leader renews
+//! the Lease every [`RENEW_INTERVAL`]. If it can not renew it within [`RENEW_DEADLINE`] after the
+//! last successful renewal (failed and hanging renewals alike), it exits before the Lease expires
+//! and another replica may take over, instead of reconciling alongside the new leader. When
+//! the operator shuts down, the leader releases the Lease (see [`crate::shutdown`]), so a standby
+//! takes over without waiting for the expiry (e.g. during a rollout of the operator).
+use crate::error::Error;
+use crate::metrics::Metrics;
+
+use k8s_openapi::api::coordination::v1::{Lease, LeaseSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta};
+use k8s_openapi::chrono::{DateTime, Duration as ChronoDuration, Utc};
+use kube::api::PostParams;
+use kube::Api;
+use stackable_operator::client::Client;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+
+/// The default name of the Lease.
+pub const DEFAULT_LEASE_NAME: &str = "zookeeper-operator-leader";
+
+/// How long the Lease is valid after it was renewed.
+const LEASE_DURATION: Duration = Duration::from_secs(15);
+/// How often the leader renews the Lease.
+const RENEW_INTERVAL: Duration = Duration::from_secs(5);
+/// How long after the last successful renewal the leader gives up, clearly before the Lease
+/// expires so it stops reconciling before another replica can take over.
+const RENEW_DEADLINE: Duration = Duration::from_secs(10);
+/// How often a standby tries to acquire the Lease.
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+pub struct LeaderElection {
+    client: Client,
+    namespace: String,
+    lease_name: String,
+    identity: String,
+    metrics: Metrics,
+    /// Set once the Lease is released, the leader stops renewing it afterwards.
+    released: AtomicBool,
+}
+
+impl LeaderElection {
+    /// Creates the leader election for the Lease `namespace/lease_name`. The `identity` has to
+    /// be unique per replica, e.g. the name of its pod.
+    pub fn new(
+        client: Client,
+        namespace: &str,
+        lease_name: &str,
+        identity: &str,
+        metrics: Metrics,
+    ) -> LeaderElection {
+        LeaderElection {
+            client,
+            namespace: namespace.to_string(),
+            lease_name: lease_name.to_string(),
+            identity: identity.to_string(),
+            metrics,
+            released: AtomicBool::new(false),
+        }
+    }
+
+    /// Waits until this replica holds the Lease.
+    pub async fn acquire(&self) {
+        self.metrics.set_leader(false);
+        info!(
+            "Waiting to acquire the Lease [{}/{}] as [{}]",
+            self.namespace, self.lease_name, self.identity
+        );
+        loop {
+            match self.try_acquire_or_renew().await {
+                Ok(true) => break,
+                Ok(false) => debug!(
+                    "The Lease [{}/{}] is held by another replica",
+                    self.namespace, self.lease_name
+                ),
+                Err(err) => warn!(
+                    "Failed to acquire the Lease [{}/{}]: {}",
+                    self.namespace, self.lease_name, err
+                ),
+            }
+            tokio::time::sleep(RETRY_INTERVAL).await;
+        }
+        info!(
+            "Acquired the Lease [{}/{}], this replica is the leader",
+            self.namespace, self.lease_name
+        );
+        self.metrics.set_leader(true);
+    }
+
+    /// Renews the Lease until it is released. Exits the process if the leadership was lost.
+    pub async fn keep_leading(&self) {
+        let lease = format!("{}/{}", self.namespace, self.lease_name);
+        if let Some(reason) = renew_until_lost(
+            || self.try_acquire_or_renew(),
+            &self.released,
+            RENEW_INTERVAL,
+            RENEW_DEADLINE,
+            &lease,
+        )
+        .await
+        {
+            self.lose_leadership(&reason);
+        }
+    }
+
+    fn lose_leadership(&self, reason: &str) -> ! {
+        self.metrics.set_leader(false);
+        error!(
+            "Lost the Lease [{}/{}] because {}, exiting to not reconcile alongside the new leader",
+            self.namespace, self.lease_name, reason
+        );
+        std::process::exit(1);
+    }
+
+    /// Takes over or renews the Lease if possible and returns whether this replica holds it.
+    /// Concurrent updates by other replicas are rejected by the API server because of the
+    /// resource version, so only one of them wins.
+    async fn try_acquire_or_renew(&self) -> Result<bool, Error> {
+        let api: Api<Lease> = self.client.get_namespaced_api(&self.namespace);
+        let now = Utc::now();
+        let result = match api.get(&self.lease_name).await {
+            Ok(mut lease) => {
+                if !can_acquire(lease.spec.as_ref(), &self.identity, now) {
+                    return Ok(false);
+                }
+                lease.spec = Some(build_lease_spec(lease.spec.as_ref(), &self.identity, now));
+                api.replace(&self.lease_name, &PostParams::default(), &lease)
+                    .await
+            }
+            Err(kube::Error::Api(response)) if response.code == 404 => {
+                let lease = Lease {
+                    metadata: ObjectMeta {
+                        name: Some(self.lease_name.clone()),
+                        namespace: Some(self.namespace.clone()),
+                        ..ObjectMeta::default()
+                    },
+                    spec: Some(build_lease_spec(None, &self.identity, now)),
+                };
+                api.create(&PostParams::default(), &lease).await
+            }
+            Err(err) => return Err(err.into()),
+        };
+        match result {
+            Ok(_) => Ok(true),
+            Err(kube::Error::Api(response)) if response.code == 409 => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Releases the Lease if this replica still holds it, so a standby can take over at once.
+    /// The controllers must not reconcile anymore afterwards.
+    pub async fn release(&self) {
+        self.released.store(true, Ordering::SeqCst);
+        self.metrics.set_leader(false);
+        let api: Api<Lease> = self.client.get_namespaced_api(&self.namespace);
+        let result = match api.get(&self.lease_name).await {
+            Ok(mut lease) => {
+                let spec = lease.spec.get_or_insert_with(LeaseSpec::default);
+                if spec.holder_identity.as_deref() != Some(self.identity.as_str()) {
+                    return;
+                }
+                spec.holder_identity = None;
+                api.replace(&self.lease_name, &PostParams::default(), &lease)
+                    .await
+                    .map(|_| ())
+            }
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(()) => info!(
+                "Released the Lease [{}/{}]",
+                self.namespace, self.lease_name
+            ),
+            Err(err) => warn!(
+                "Failed to release the Lease [{}/{}], it expires in {:?}: {}",
+                self.namespace, self.lease_name, LEASE_DURATION, err
+            ),
+        }
+    }
+}
+
+/// Renews the Lease with `renew` every `interval` until it is `released` (returns `None`) or the
+/// leadership is lost (returns the reason): `renew` reports that another replica holds it, or
+/// `deadline` passed since the last successful renewal. Every renewal is cut off at the deadline,
+/// so a renewal which never completes does not keep the leader running.
+async fn renew_until_lost<F, Fut>(
+    mut renew: F,
+    released: &AtomicBool,
+    interval: Duration,
+    deadline: Duration,
+    lease: &str,
+) -> Option<String>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<bool, Error>>,
+{
+    let mut last_renewal = Instant::now();
+    let mut last_error = None;
+    loop {
+        tokio::time::sleep(interval.min(deadline.saturating_sub(last_renewal.elapsed()))).await;
+        if released.load(Ordering::SeqCst) {
+            return None;
+        }
+        let remaining = deadline.saturating_sub(last_renewal.elapsed());
+        if remaining.is_zero() {
+            return Some(format!(
+                "it could not be renewed within {:?}: {}",
+                deadline,
+                last_error.unwrap_or_else(|| "the renewal timed out".to_string())
+            ));
+        }
+        match tokio::time::timeout(remaining, renew()).await {
+            Ok(Ok(true)) => {
+                last_renewal = Instant::now();
+                last_error = None;
+            }
+            Ok(Ok(false)) => return Some("it is held by another replica".to_string()),
+            Ok(Err(err)) => {
+                warn!("Failed to renew the Lease [{}], retrying: {}", lease, err);
+                last_error = Some(err.to_string());
+            }
+            Err(_) => {
+                warn!("Renewing the Lease [{}] timed out", lease);
+                last_error = Some("the renewal timed out".to_string());
+            }
+        }
+    }
+}
+
+/// Checks whether the Lease can be taken by `identity`, which is the case if nobody holds it,
+/// `identity` already holds it or the holder did not renew it in time.
+fn can_acquire(spec: Option<&LeaseSpec>, identity: &str, now: DateTime<Utc>) -> bool {
+    let spec = match spec {
+        Some(spec) => spec,
+        None => return true,
+    };
+    match spec.holder_identity.as_deref() {
+        None | Some("") => true,
+        Some(holder) if holder == identity => true,
+        Some(_) => match (&spec.renew_time, spec.lease_duration_seconds) {
+            (Some(MicroTime(renew_time)), Some(duration)) => {
+                *renew_time + ChronoDuration::seconds(i64::from(duration)) < now
+            }
+            _ => true,
+        },
+    }
+}
+
+/// Builds the spec of the Lease held by `identity`, counting a transition if it was taken over
+/// from another replica.
+fn build_lease_spec(previous: Option<&LeaseSpec>, identity: &str, now: DateTime<Utc>) -> LeaseSpec {
+    let previous = previous.cloned().unwrap_or_default();
+    let previous_holder = previous.holder_identity.as_deref().unwrap_or_default();
+    let is_holder = previous_holder == identity;
+    LeaseSpec {
+        holder_identity: Some(identity.to_string()),
+        lease_duration_seconds: Some(LEASE_DURATION.as_secs() as i32),
+        acquire_time: if is_holder {
+            previous.acquire_time
+        } else {
+            Some(MicroTime(now))
+        },
+        renew_time: Some(MicroTime(now)),
+        lease_transitions: if is_holder || previous_holder.is_empty() {
+            Some(previous.lease_transitions.unwrap_or(0))
+        } else {
+            Some(previous.lease_transitions.unwrap_or(0) + 1)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn lease_spec(holder: Option<&str>, renewed_seconds_ago: i64) -> LeaseSpec {
+        let now = Utc::now();
+        LeaseSpec {
+            holder_identity: holder.map(str::to_string),
+            lease_duration_seconds: Some(15),
+            acquire_time: Some(MicroTime(now - ChronoDuration::seconds(60))),
+            renew_time: Some(MicroTime(
+                now - ChronoDuration::seconds(renewed_seconds_ago),
+            )),
+            lease_transitions: Some(2),
+        }
+    }
+
+    #[rstest]
+    #[case(None, 0, true)]
+    #[case(Some(""), 0, true)]
+    #[case(Some("operator-a"), 0, true)]
+    #[case(Some("operator-b"), 5, false)]
+    #[case(Some("operator-b"), 20, true)]
+    fn test_can_acquire(
+        #[case] holder: Option<&str>,
+        #[case] renewed_seconds_ago: i64,
+        #[case] expected: bool,
+    ) {
+        let spec = lease_spec(holder, renewed_seconds_ago);
+        assert_eq!(can_acquire(Some(&spec), "operator-a", Utc::now()), expected);
+        assert!(can_acquire(None, "operator-a", Utc::now()));
+    }
+
+    #[tokio::test]
+    async fn test_hanging_renewal_loses_leadership() {
+        let released = AtomicBool::new(false);
+        let started = Instant::now();
+
+        let reason = renew_until_lost(
+            || std::future::pending::<Result<bool, Error>>(),
+            &released,
+            Duration::from_millis(10),
+            Duration::from_millis(100),
+            "default/lease",
+        )
+        .await;
+
+        assert_eq!(
+            reason.as_deref(),
+            Some("it could not be renewed within 100ms: the renewal timed out")
+        );
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_renewal_until_released() {
+        let released = AtomicBool::new(false);
+        let mut renewals = 0;
+
+        let reason = renew_until_lost(
+            || {
+                renewals += 1;
+                if renewals == 3 {
+                    released.store(true, Ordering::SeqCst);
+                }
+                async { Ok(true) }
+            },
+            &released,
+            Duration::from_millis(10),
+            Duration::from_millis(100),
+            "default/lease",
+        )
+        .await;
+
+        assert_eq!(reason, None);
+        assert_eq!(renewals, 3);
+    }
+
+    #[tokio::test]
+    async fn test_lease_held_by_another_replica() {
+        let released = AtomicBool::new(false);
+
+        let reason = renew_until_lost(
+            || async { Ok(false) },
+            &released,
+            Duration::from_millis(10),
+            Duration::from_millis(100),
+            "default/lease",
+        )
+        .await;
+
+        assert_eq!(reason.as_deref(), Some("it is held by another replica"));
+    }
+
+    #[test]
+    fn test_build_lease_spec() {
+        let now = Utc::now();
+        let previous = lease_spec(Some("operator-a"), 5);
+
+        let renewed = build_lease_spec(Some(&previous), "operator-a", now);
+        assert_eq!(renewed.acquire_time, previous.acquire_time);
+        assert_eq!(renewed.renew_time, Some(MicroTime(now)));
+        assert_eq!(renewed.lease_transitions, Some(2));
+
+        let taken_over = build_lease_spec(Some(&previous), "operator-b", now);
+        assert_eq!(taken_over.holder_identity, Some("operator-b".to_string()));
+        assert_eq!(taken_over.acquire_time, Some(MicroTime(now)));
+        assert_eq!(taken_over.lease_transitions, Some(3));
+
+        let created = build_lease_spec(None, "operator-a", now);
+        assert_eq!(created.lease_duration_seconds, Some(15));
+        assert_eq!(created.lease_transitions, Some(0));
+    }
+}
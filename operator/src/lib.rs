@@ -1,20 +1,134 @@
+mod admin_server;
+mod apply;
+mod authentication;
+mod backoff;
+#[cfg(feature = "backup")]
+mod backup;
+mod benchmark;
+mod cache;
+mod campaign;
+#[cfg(feature = "cert-manager")]
+mod cert_manager;
+mod client_secret;
+mod compute_resources;
+mod config;
+mod config_secrets;
+mod crds;
+#[cfg(feature = "backup")]
+mod cron;
+mod defaulting;
+mod deletion;
+mod diff;
+mod discovery;
+mod disruption_budget;
+mod dry_run;
+mod entrypoint;
 mod error;
+mod events;
+mod external_access;
+mod extra_containers;
+mod extra_volumes;
+mod four_letter_words;
+mod garbage_collection;
+mod health;
+mod host_network;
+mod image;
+mod integrity_check;
+mod jvm;
+mod kerberos;
+mod last_applied;
+mod leader_election;
+pub mod lint;
+pub mod logging;
+mod member_replacement;
+mod metrics;
+mod monitoring;
+mod network_policy;
+mod object_ref;
+mod pod_exec;
+mod pod_overrides;
+mod probes;
+#[cfg(feature = "prometheus-operator")]
+mod prometheus_operator;
+mod quorum;
+mod reconcile_state;
+mod references;
+mod resources;
+mod restart_order;
+mod rollout;
+#[cfg(feature = "backup")]
+mod s3;
+mod scaling;
+mod scheduling;
+mod security_context;
+mod service_account;
+mod shutdown;
+mod smoke_test;
+mod standby;
+mod status;
+mod status_refresh;
+mod strict;
+mod supervisor;
+mod tamper_detection;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+mod throttle;
+mod tls;
+mod upgrade;
+mod usage;
+pub mod validation;
+mod waiting;
+mod watch_namespace;
+mod zk_client;
+mod znode;
 
-use crate::error::Error;
+use crate::apply::ConflictPolicy;
+pub use crate::apply::FIELD_MANAGER;
+use crate::backoff::Backoff;
+pub use crate::benchmark::create_benchmark_controller;
+use crate::cache::OwnedObjectCache;
+pub use crate::campaign::{create_restart_campaign, run_restart_campaigns};
+pub use crate::crds::{check_crds, crds_yaml, install_crds};
+pub use crate::discovery::create_discovery_controller;
+pub use crate::dry_run::DryRunMode;
+pub use crate::error::{Error, ErrorClass};
+use crate::events::EventType;
+pub use crate::image::{DefaultImageResolver, ImageResolver, TemplateImageResolver};
+pub use crate::leader_election::{LeaderElection, DEFAULT_LEASE_NAME};
+pub use crate::metrics::{serve_metrics, Metrics};
+use crate::object_ref::ObjectRef;
+use crate::pod_exec::PodExecutor;
+use crate::references::{ReferenceIndex, ReferenceKind};
+pub use crate::resources::{build_data_config_map, build_id_config_map, build_pod, PodParameters};
+use crate::restart_order::RestartOrder;
+use crate::scaling::ScaleStep;
+pub use crate::shutdown::{shut_down, wait_for_termination};
+use crate::status_refresh::StatusRefresh;
+pub use crate::supervisor::supervise_controller;
+pub use crate::throttle::ReconcileThrottle;
+pub use crate::usage::{serve_usage_report, UsageStatistics};
+use crate::waiting::WaitReason;
+use crate::watch_namespace::scoped_api;
+pub use crate::watch_namespace::WatchNamespace;
+use crate::zk_client::{ZookeeperClient, ZookeeperConnector};
+pub use crate::znode::create_znode_controller;
+pub use stackable_zookeeper_crd::{ZookeeperCluster, ZookeeperClusterSpec, ZookeeperClusterStatus};
 
 use async_trait::async_trait;
-use k8s_openapi::api::core::v1::{ConfigMap, EnvVar, Pod, PodSpec};
-use kube::api::{ListParams, ResourceExt};
+use k8s_openapi::api::core::v1::{ConfigMap, Node, Pod, PodSpec, Service, ServiceAccount};
+use k8s_openapi::api::policy::v1::PodDisruptionBudget;
+use k8s_openapi::api::rbac::v1::{Role, RoleBinding};
+use kube::api::{DeleteParams, ListParams, Patch, PostParams, ResourceExt};
 use kube::Api;
+use kube_runtime::reflector;
+use rand::Rng;
 use serde_json::json;
-use tracing::{debug, error, info, trace, warn};
+use tracing::{debug, error, info, info_span, instrument, trace, warn, Instrument};
 
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
+use k8s_openapi::chrono::{DateTime, Duration as ChronoDuration, Utc};
 use product_config::types::PropertyNameKind;
 use product_config::ProductConfigManager;
-use stackable_operator::builder::{
-    ContainerBuilder, ContainerPortBuilder, ObjectMetaBuilder, PodBuilder,
-};
 use stackable_operator::client::Client;
 use stackable_operator::conditions::ConditionStatus;
 use stackable_operator::configmap;
@@ -23,10 +137,7 @@ use stackable_operator::controller::{ControllerStrategy, ReconciliationState};
 use stackable_operator::error::OperatorResult;
 use stackable_operator::k8s_utils;
 use stackable_operator::labels;
-use stackable_operator::labels::{
-    build_common_labels_for_all_managed_resources, get_recommended_labels,
-};
-use stackable_operator::name_utils;
+use stackable_operator::labels::build_common_labels_for_all_managed_resources;
 use stackable_operator::product_config_utils::{
     config_for_role_and_group, transform_all_roles_to_config, validate_all_roles_and_groups_config,
     ValidatedRoleConfigByPropertyKind,
@@ -38,11 +149,38 @@ use stackable_operator::role_utils;
 use stackable_operator::role_utils::{
     get_role_and_group_labels, list_eligible_nodes_for_role_and_group, EligibleNodesForRoleAndGroup,
 };
+use stackable_zookeeper_crd::authentication::super_user_secret_name;
+#[cfg(feature = "backup")]
+use stackable_zookeeper_crd::backup::{
+    restore_secret_name, BackupStatus, RestoreStatus, ZookeeperBackup, ZookeeperRestore,
+};
+use stackable_zookeeper_crd::dry_run::{DryRunStatus, PlannedAction, PlannedChange};
+use stackable_zookeeper_crd::external_access::{ExternalAccessStatus, ExternalAccessType};
+use stackable_zookeeper_crd::federation::{
+    FederationMember, FederationStatus, FEDERATION_REFRESH_INTERVAL_SECONDS,
+};
+use stackable_zookeeper_crd::four_letter_words::FOUR_LETTER_WORDS_WHITELIST;
+use stackable_zookeeper_crd::ip_family;
+use stackable_zookeeper_crd::member_replacement::{
+    MemberReplacementPolicy, MemberReplacementStatus,
+};
+use stackable_zookeeper_crd::monitoring::ZookeeperMonitoring;
+use stackable_zookeeper_crd::ports::DEFAULT_CLIENT_PORT;
+use stackable_zookeeper_crd::quorum::{QuorumStatus, DEFAULT_PROBE_INTERVAL_SECONDS};
+use stackable_zookeeper_crd::standby::StandbyStatus;
+use stackable_zookeeper_crd::upgrade::{
+    CanaryPhase, CanaryStatus, IntegrityCheckPhase, IntegrityCheckStatus, ZookeeperCanary,
+    ZookeeperIntegrityCheck,
+};
+use stackable_zookeeper_crd::util::{get_zk_connection_info, pod_client_port, ZookeeperReference};
 use stackable_zookeeper_crd::{
-    ZookeeperCluster, ZookeeperClusterSpec, ZookeeperClusterStatus, ZookeeperVersion, ADMIN_PORT,
-    APP_NAME, CLIENT_PORT, CONFIG_MAP_TYPE_DATA, CONFIG_MAP_TYPE_ID, DATA_DIR, METRICS_PORT,
+    ClientRebalanceHint, DeletionPolicy, EnsembleSizePolicy, LastAppliedStatus, PeerType,
+    RestartStatus, RestartStepStatus, RolloutStatus, SmokeTestStatus, UpdateStrategy,
+    WaitingStatus, ZookeeperCapabilities, ZookeeperVersion, APP_NAME, CONFIG_MAP_TYPE_DATA,
+    CONFIG_MAP_TYPE_ID, DATA_DIR, METRICS_PORT, PEER_TYPE,
 };
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::TryFrom;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -56,6 +194,41 @@ const ID_LABEL: &str = "zookeeper.stackable.tech/id";
 const SHOULD_BE_SCRAPED: &str = "monitoring.stackable.tech/should_be_scraped";
 const PROPERTIES_FILE: &str = "zoo.cfg";
 const CONFIG_DIR_NAME: &str = "conf";
+/// Pod condition (used as readiness gate) which is set once the server has synchronized its data
+/// with the leader.
+const SYNCED_CONDITION: &str = "zookeeper.stackable.tech/synced";
+/// Condition which is set while the spec requests an unsupported version change (i.e. a
+/// downgrade to an older release line).
+const VERSION_CHANGE_REJECTED_CONDITION: &str = "VersionChangeRejected";
+/// Condition which is set while a rolling operation waits for a PodDisruptionBudget to allow the
+/// next pod to be taken down.
+const WAITING_FOR_DISRUPTION_BUDGET_CONDITION: &str = "WaitingForDisruptionBudget";
+/// Annotation on a ZookeeperCluster requesting a rolling restart of all pods whenever its value
+/// changes, e.g. to the output of `date`. The last value is recorded in `status.restart`.
+pub const RESTART_ANNOTATION: &str = "zookeeper.stackable.tech/restart";
+/// Annotation on the pods holding the revision of the certificate issued by cert-manager they
+/// were started with.
+const CERTIFICATE_REVISION_ANNOTATION: &str = "zookeeper.stackable.tech/certificate-revision";
+/// Annotation on a ZookeeperCluster which pauses the reconciliation if set to `true`, like
+/// `spec.paused`.
+pub const PAUSED_ANNOTATION: &str = "zookeeper.stackable.tech/paused";
+/// Condition which is set while the reconciliation is paused.
+const PAUSED_CONDITION: &str = "Paused";
+/// Condition which is set while the servers are stopped with `spec.stopped`.
+const STOPPED_CONDITION: &str = "Stopped";
+/// Condition which is set while servers of this cluster have the ids of servers of other
+/// clusters of `spec.federation`.
+const FEDERATION_ID_CONFLICT_CONDITION: &str = "FederationIdConflict";
+/// Condition which is set in strict mode while the spec contains unknown fields.
+const UNKNOWN_SPEC_FIELDS_CONDITION: &str = "UnknownSpecFields";
+/// Condition which is set while objects owned by the cluster show modifications by unknown field
+/// managers which were not acknowledged.
+const TAMPER_DETECTED_CONDITION: &str = "TamperDetected";
+/// Condition which is set while the number of participants is not recommended, see
+/// `check_ensemble_size`.
+const ENSEMBLE_SIZE_NOT_RECOMMENDED_CONDITION: &str = "EnsembleSizeNotRecommended";
+/// Client rebalances are requested at most once per interval (in minutes).
+const CLIENT_REBALANCE_MIN_INTERVAL_MINUTES: i64 = 5;
 
 type ZookeeperReconcileResult = ReconcileResult<error::Error>;
 
@@ -65,11 +238,58 @@ pub enum ZookeeperRole {
     Server,
 }
 
-struct ZookeeperState {
+/// The state of one reconciliation of a ZookeeperCluster, see [`ZookeeperStrategy`].
+pub struct ZookeeperState {
     context: ReconciliationContext<ZookeeperCluster>,
+    managed_resources: ManagedResources,
+    strict_spec_validation: bool,
+    /// The dry-run mode of this cluster, see [`dry_run::mode`].
+    dry_run: DryRunMode,
+    /// `spec.ensembleSizePolicy` or the default of the operator.
+    ensemble_size_policy: EnsembleSizePolicy,
+    metrics: Metrics,
+    image_resolver: Arc<dyn ImageResolver>,
+    zk_connector: Arc<dyn ZookeeperConnector>,
+    pod_executor: Arc<dyn PodExecutor>,
+    backoff: Backoff,
+    /// Decides whether only the status is refreshed, see [`status_refresh`].
+    status_refresh: StatusRefresh,
+    /// See [`ControllerConfig::resync_interval`].
+    resync_interval: Option<Duration>,
+    /// Held until the reconciliation finished, see [`throttle`].
+    _reconcile_permit: tokio::sync::OwnedSemaphorePermit,
+    usage_statistics: UsageStatistics,
     zk_spec: ZookeeperClusterSpec,
     zk_status: Option<ZookeeperClusterStatus>,
     id_information: Option<IdInformation>,
+    /// The servers of the other clusters of `spec.federation`, see `reconcile_federation`.
+    federation_members: Vec<FederationMember>,
+    /// The data ConfigMap of every role group by role and role group, see
+    /// `build_data_config_maps`.
+    data_config_maps: BTreeMap<(String, String), ConfigMap>,
+    /// How many servers may be added, decided in `assign_ids`.
+    scale_step: ScaleStep,
+    /// The revision of the certificate issued by cert-manager, see `reconcile_certificate`.
+    certificate_revision: Option<i64>,
+    /// See [`references`].
+    references: ReferenceIndex,
+    /// The pods, ConfigMaps and Services of all clusters, see [`cache`].
+    cache: OwnedObjectCache,
+    /// The hash of the Secrets and ConfigMaps the servers use, see `track_references`.
+    references_hash: Option<String>,
+    /// The rollout of a changed configuration, see [`rollout`].
+    rollout: Option<RolloutStatus>,
+    /// The fingerprint of the cluster wide objects, set once they were applied completely in
+    /// this reconciliation, see [`last_applied`].
+    applied_fingerprint: Option<String>,
+    /// Whether disruptive operations are paused because of unacknowledged modifications, see
+    /// `detect_tampering`.
+    disruptions_paused: bool,
+    /// The external condition this reconciliation waits for, see [`waiting`].
+    waiting: Option<WaitingStatus>,
+    /// When the next periodic task (e.g. a standby copy or a backup) is due, the reconciliation
+    /// is requeued accordingly once all steps ran.
+    scheduled_requeue: Option<Duration>,
     existing_pods: Vec<Pod>,
     eligible_nodes: EligibleNodesForRoleAndGroup,
     validated_role_config: ValidatedRoleConfigByPropertyKind,
@@ -77,22 +297,81 @@ struct ZookeeperState {
 
 struct IdInformation {
     used_ids: Vec<usize>,
-    node_name_to_pod: HashMap<String, Pod>,
-    node_name_to_id: HashMap<String, usize>,
+    node_name_to_pod: BTreeMap<String, Pod>,
+    node_name_to_id: BTreeMap<String, usize>,
 }
 
 impl IdInformation {
-    fn new(
-        used_ids: Vec<usize>,
-        node_name_to_pod: HashMap<String, Pod>,
-        node_name_to_id: HashMap<String, usize>,
-    ) -> IdInformation {
+    /// Reads the ids of the given pods, pods which are not scheduled yet or have no valid id are
+    /// skipped.
+    fn from_pods(pods: &[Pod]) -> IdInformation {
+        let mut used_ids = Vec::with_capacity(pods.len());
+        let mut node_name_to_pod = BTreeMap::new();
+        let mut node_name_to_id = BTreeMap::new();
+        for pod in pods {
+            if let (Ok(node_name), Some(id)) = (pod_node_name(pod), pod_id(pod)) {
+                used_ids.push(id);
+                node_name_to_id.insert(node_name.to_string(), id);
+                node_name_to_pod.insert(node_name.to_string(), pod.clone());
+            }
+        }
         IdInformation {
             used_ids,
             node_name_to_pod,
             node_name_to_id,
         }
     }
+
+    /// Assigns an id to every eligible node without a pod and returns these nodes with their id.
+    /// With `nodes_in_step` only the nodes of the current scale step are assigned an id. The
+    /// replacement of a failed server keeps the `replaced_id`, all other servers get the lowest
+    /// free id.
+    fn assign_new_ids(
+        &mut self,
+        eligible_nodes: &EligibleNodesForRoleAndGroup,
+        nodes_in_step: Option<&[String]>,
+        replaced_id: Option<usize>,
+    ) -> Vec<(String, usize)> {
+        self.used_ids.sort_unstable();
+        let mut assigned = vec![];
+        for role in ZookeeperRole::iter() {
+            for (nodes, _replicas) in eligible_nodes
+                .get(&role.to_string())
+                .into_iter()
+                .flat_map(|groups| groups.values())
+            {
+                for node in nodes {
+                    let node_name = match &node.metadata.name {
+                        Some(name) => name,
+                        None => continue,
+                    };
+                    // a node eligible for several role groups runs one server
+                    if self.node_name_to_pod.contains_key(node_name)
+                        || self.node_name_to_id.contains_key(node_name)
+                    {
+                        continue;
+                    }
+                    if nodes_in_step.map_or(false, |nodes| !nodes.contains(node_name)) {
+                        trace!(
+                            "Server for node [{}] is deferred to a later scale step",
+                            node_name
+                        );
+                        continue;
+                    }
+                    // TODO: Need to check whether the topology has changed. If it has we need to restart all servers depending on the ZK version
+                    let new_id = match replaced_id {
+                        Some(id) if !self.used_ids.contains(&id) => id,
+                        _ => find_first_missing(&self.used_ids),
+                    };
+                    self.used_ids.push(new_id);
+                    self.used_ids.sort_unstable();
+                    self.node_name_to_id.insert(node_name.clone(), new_id);
+                    assigned.push((node_name.clone(), new_id));
+                }
+            }
+        }
+        assigned
+    }
 }
 
 /// This finds the first missing number in a sorted vector.
@@ -113,10 +392,749 @@ fn find_first_missing(vec: &[usize]) -> usize {
     added_id.unwrap_or_else(|| vec.len() + 1)
 }
 
+/// Adds the ids reserved by `spec.federation` and the ids of the `federation_members` (including
+/// the servers of the `clusterRefs`) to `used_ids` and sorts them, so no server of this cluster
+/// gets the id of a server of another cluster of the federation.
+fn reserve_federation_ids(
+    cluster: &ZookeeperCluster,
+    federation_members: &[FederationMember],
+    used_ids: &mut Vec<usize>,
+) {
+    if let Some(federation) = &cluster.spec.federation {
+        used_ids.extend(federation.reserved_ids());
+        used_ids.extend(federation_members.iter().map(|member| member.id));
+    }
+    used_ids.sort_unstable();
+    used_ids.dedup();
+}
+
+/// Returns the ids of the servers of this cluster which are also used by `federation_members`,
+/// ordered and without duplicates.
+fn conflicting_federation_ids(
+    servers: &[FederationMember],
+    federation_members: &[FederationMember],
+) -> Vec<usize> {
+    servers
+        .iter()
+        .map(|server| server.id)
+        .filter(|id| federation_members.iter().any(|member| member.id == *id))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Returns the `myid` of the server running in this pod, if the pod has a valid id label.
+fn pod_id(pod: &Pod) -> Option<usize> {
+    pod.metadata
+        .labels
+        .get(ID_LABEL)
+        .and_then(|id| id.parse::<usize>().ok())
+}
+
+/// Checks whether the pod was created with a readiness gate on the given condition.
+fn has_readiness_gate(pod: &Pod, condition_type: &str) -> bool {
+    pod.spec
+        .as_ref()
+        .map(|spec| {
+            spec.readiness_gates
+                .iter()
+                .any(|gate| gate.condition_type == condition_type)
+        })
+        .unwrap_or(false)
+}
+
+/// Checks whether the given condition is set to `True` on the pod.
+fn is_pod_condition_true(pod: &Pod, condition_type: &str) -> bool {
+    pod.status
+        .as_ref()
+        .map(|status| {
+            status
+                .conditions
+                .iter()
+                .any(|condition| condition.type_ == condition_type && condition.status == "True")
+        })
+        .unwrap_or(false)
+}
+
+/// Returns the client port exposed by the ZooKeeper container of the pod.
+fn client_port(pod: &Pod) -> u16 {
+    pod_client_port(pod).unwrap_or(DEFAULT_CLIENT_PORT)
+}
+
+/// Computes the [`ClientRebalanceHint`] that should be published for an ensemble with `servers`
+/// members. Returns `None` if the currently published hint is still up to date.
+///
+/// A rebalance is requested when the number of servers increased, but at most once per
+/// [`CLIENT_REBALANCE_MIN_INTERVAL_MINUTES`]. A scale-up within that interval is not lost, the
+/// previous number of servers is kept until the next rebalance can be requested.
+fn next_client_rebalance_hint(
+    previous: Option<&ClientRebalanceHint>,
+    servers: usize,
+    now: DateTime<Utc>,
+) -> Option<ClientRebalanceHint> {
+    let previous = match previous {
+        // Nothing to compare against, we only record the current size
+        None => {
+            return Some(ClientRebalanceHint {
+                servers,
+                requested_at: None,
+            })
+        }
+        Some(previous) => previous,
+    };
+
+    if servers < previous.servers {
+        return Some(ClientRebalanceHint {
+            servers,
+            requested_at: previous.requested_at.clone(),
+        });
+    }
+
+    if servers == previous.servers {
+        return None;
+    }
+
+    let rate_limited = previous
+        .requested_at
+        .as_deref()
+        .and_then(|requested_at| DateTime::parse_from_rfc3339(requested_at).ok())
+        .map(|requested_at| {
+            now.signed_duration_since(requested_at)
+                < ChronoDuration::minutes(CLIENT_REBALANCE_MIN_INTERVAL_MINUTES)
+        })
+        .unwrap_or(false);
+
+    if rate_limited {
+        None
+    } else {
+        Some(ClientRebalanceHint {
+            servers,
+            requested_at: Some(now.to_rfc3339()),
+        })
+    }
+}
+
+/// Returns the name of the node the given pod is scheduled to, which is the address of its server
+/// (just like in the `server.<id>` entries of `zoo.cfg`).
+fn pod_node_name(pod: &Pod) -> Result<&str, Error> {
+    pod.spec
+        .as_ref()
+        .and_then(|spec| spec.node_name.as_deref())
+        .ok_or_else(|| {
+            Error::ReconcileError(format!(
+                "Pod [{}] is not scheduled to a node yet",
+                pod.name()
+            ))
+        })
+}
+
+/// Asks the server running in the given pod whether it is serving requests, see
+/// [`four_letter_words::is_serving`].
+async fn is_server_serving(pod: &Pod) -> Result<bool, Error> {
+    let response =
+        four_letter_words::send_command(pod_node_name(pod)?, client_port(pod), "srvr").await?;
+    Ok(four_letter_words::is_serving(&response))
+}
+
+/// Probes the server running in the given pod with `ruok` and `mntr`, see
+/// [`four_letter_words::check_health`].
+async fn check_server_health(pod: &Pod) -> Result<(bool, BTreeMap<String, String>), Error> {
+    four_letter_words::check_health(pod_node_name(pod)?, client_port(pod)).await
+}
+
+/// Checks whether the reconciliation of `cluster` is paused with `spec.paused` or the
+/// [`PAUSED_ANNOTATION`].
+fn is_paused(cluster: &ZookeeperCluster) -> bool {
+    cluster.spec.paused
+        || cluster
+            .annotations()
+            .get(PAUSED_ANNOTATION)
+            .map_or(false, |paused| paused.trim().eq_ignore_ascii_case("true"))
+}
+
+/// Checks whether the condition of the given type is set to `True`.
+fn is_condition_true(conditions: &[Condition], condition_type: &str) -> bool {
+    conditions
+        .iter()
+        .any(|condition| condition.type_ == condition_type && condition.status == "True")
+}
+
+/// Checks whether `conditions` already contain the given condition for the current `generation`,
+/// in which case setting it again would not change anything.
+fn is_condition_current(
+    conditions: &[Condition],
+    condition: &status::ClusterCondition,
+    generation: Option<i64>,
+) -> bool {
+    let status = if condition.status { "True" } else { "False" };
+    conditions.iter().any(|existing| {
+        existing.type_ == condition.condition_type
+            && existing.status == status
+            && existing.reason == condition.reason
+            && existing.message == condition.message
+            && (existing.observed_generation.is_none()
+                || existing.observed_generation == generation)
+    })
+}
+
+/// Returns the change writing `config_map` would make to the `existing` ConfigMap with the same
+/// name, `None` if its data is up to date.
+fn plan_config_map(config_map: &ConfigMap, existing: &[ConfigMap]) -> Option<PlannedChange> {
+    let name = config_map.name();
+    let existing = match existing.iter().find(|existing| existing.name() == name) {
+        Some(existing) => existing,
+        None => {
+            return Some(PlannedChange::new(
+                PlannedAction::Create,
+                "ConfigMap",
+                &name,
+            ))
+        }
+    };
+    let fields = dry_run::changed_fields(
+        &json!({ "data": config_map.data }),
+        &json!({ "data": existing.data }),
+    );
+    if fields.is_empty() {
+        return None;
+    }
+    let mut change = PlannedChange::new(PlannedAction::Update, "ConfigMap", &name);
+    change.fields = fields;
+    Some(change)
+}
+
+/// Orders pods in the order they should be removed from the ensemble: highest `myid` first.
+/// Pods without a (parseable) id are removed first as they can not be part of the ensemble.
+fn order_pods_for_removal(mut pods: Vec<&Pod>) -> Vec<&Pod> {
+    pods.sort_by_key(|pod| std::cmp::Reverse(pod_id(pod).unwrap_or(usize::MAX)));
+    pods
+}
+
+/// Returns the hash of the data ConfigMap of every role group, see [`config::hash_config_map`].
+fn config_hashes(
+    data_config_maps: &BTreeMap<(String, String), ConfigMap>,
+) -> Result<BTreeMap<(String, String), String>, Error> {
+    data_config_maps
+        .iter()
+        .map(|(role_group, config_map)| {
+            Ok((role_group.clone(), config::hash_config_map(config_map)?))
+        })
+        .collect()
+}
+
+/// Checks whether `pod` was created with the given version of ZooKeeper.
+fn runs_version(pod: &Pod, version: &ZookeeperVersion) -> bool {
+    pod.labels().get(labels::APP_VERSION_LABEL) == Some(&version.to_string())
+}
+
+/// Returns the pods which are not needed on the eligible nodes anymore in the order they are
+/// removed, see [`order_pods_for_removal`].
+fn excess_pods<'a>(
+    eligible_nodes: &EligibleNodesForRoleAndGroup,
+    existing_pods: &'a [Pod],
+) -> Vec<&'a Pod> {
+    order_pods_for_removal(k8s_utils::find_excess_pods(
+        &list_eligible_nodes_for_role_and_group(eligible_nodes),
+        existing_pods,
+    ))
+}
+
+/// Checks whether `pod` was created before `timestamp`, pods without a creation timestamp are
+/// treated as if they were.
+fn is_pod_created_before(pod: &Pod, timestamp: &DateTime<Utc>) -> bool {
+    pod.metadata
+        .creation_timestamp
+        .as_ref()
+        .map(|created| &created.0 < timestamp)
+        .unwrap_or(true)
+}
+
+/// Returns the wait recorded by the previous reconciliation, see [`waiting`].
+fn previous_waiting(status: &Option<ZookeeperClusterStatus>) -> Option<&WaitingStatus> {
+    status.as_ref().and_then(|status| status.waiting.as_ref())
+}
+
+/// Returns the number of servers requested by the spec, limited to the number of nodes they can
+/// be placed on.
+fn desired_replicas(eligible_nodes: &EligibleNodesForRoleAndGroup) -> usize {
+    eligible_nodes
+        .values()
+        .flat_map(|role_groups| role_groups.values())
+        .map(|(nodes, replicas)| group_replicas(nodes, *replicas))
+        .sum()
+}
+
+/// Returns the number of servers per role group.
+fn replicas_per_role_group(eligible_nodes: &EligibleNodesForRoleAndGroup) -> BTreeMap<String, u16> {
+    eligible_nodes
+        .values()
+        .flat_map(|role_groups| role_groups.iter())
+        .map(|(role_group, (nodes, replicas))| {
+            let replicas = group_replicas(nodes, *replicas);
+            (
+                role_group.clone(),
+                u16::try_from(replicas).unwrap_or(u16::MAX),
+            )
+        })
+        .collect()
+}
+
+/// Returns the number of desired servers which vote, i.e. the servers of all role groups which
+/// are not observers.
+fn desired_participants(
+    eligible_nodes: &EligibleNodesForRoleAndGroup,
+    validated_role_config: &ValidatedRoleConfigByPropertyKind,
+) -> usize {
+    eligible_nodes
+        .iter()
+        .flat_map(|(role, role_groups)| role_groups.iter().map(move |group| (role, group)))
+        .filter(|(role, (group, _))| !is_observer_group(validated_role_config, role, group))
+        .map(|(_, (_, (nodes, replicas)))| group_replicas(nodes, *replicas))
+        .sum()
+}
+
+/// Returns the number of participants the `replicas` of the participant role groups ask for, or
+/// `None` if a participant role group runs on all matching nodes, so its size follows the nodes.
+fn explicit_participants(
+    eligible_nodes: &EligibleNodesForRoleAndGroup,
+    validated_role_config: &ValidatedRoleConfigByPropertyKind,
+) -> Option<usize> {
+    eligible_nodes
+        .iter()
+        .flat_map(|(role, role_groups)| role_groups.iter().map(move |group| (role, group)))
+        .filter(|(role, (group, _))| !is_observer_group(validated_role_config, role, group))
+        .map(|(_, (_, (_, replicas)))| replicas.map(usize::from))
+        .sum()
+}
+
+/// Returns the names of the nodes whose server belongs to an observer role group, given the role
+/// group of the server on every node (see [`node_role_groups`]).
+fn observer_node_names(
+    node_role_groups: &BTreeMap<String, String>,
+    validated_role_config: &ValidatedRoleConfigByPropertyKind,
+) -> BTreeSet<String> {
+    let role = ZookeeperRole::Server.to_string();
+    node_role_groups
+        .iter()
+        .filter(|(_, group)| is_observer_group(validated_role_config, &role, group))
+        .map(|(node_name, _)| node_name.clone())
+        .collect()
+}
+
+/// Returns the nodes which are eligible for an observer and a participant role group. It would
+/// depend on the order of the role groups whether their server is an observer.
+fn validate_peer_types_of_nodes(
+    eligible_nodes: &EligibleNodesForRoleAndGroup,
+    validated_role_config: &ValidatedRoleConfigByPropertyKind,
+) -> Vec<String> {
+    let mut groups_by_node = BTreeMap::<String, Vec<(String, bool)>>::new();
+    for (role, role_groups) in eligible_nodes {
+        for (group, (nodes, _)) in role_groups {
+            let observer = is_observer_group(validated_role_config, role, group);
+            for node in nodes {
+                groups_by_node
+                    .entry(node.name())
+                    .or_default()
+                    .push((group.clone(), observer));
+            }
+        }
+    }
+    groups_by_node
+        .into_iter()
+        .filter(|(_, groups)| {
+            groups.iter().any(|(_, observer)| *observer)
+                && groups.iter().any(|(_, observer)| !*observer)
+        })
+        .map(|(node, groups)| {
+            let mut groups = groups
+                .into_iter()
+                .map(|(group, _)| group)
+                .collect::<Vec<_>>();
+            groups.sort();
+            format!(
+                "servers: The node [{}] is eligible for the observer and participant role groups [{}], narrow their selectors",
+                node,
+                groups.join(", ")
+            )
+        })
+        .collect()
+}
+
+/// Returns the role group of the server on every node: the one of its pod if it exists,
+/// otherwise the first role group (by name) the node is eligible for.
+fn node_role_groups(
+    existing_pods: &[Pod],
+    eligible_nodes: &EligibleNodesForRoleAndGroup,
+) -> BTreeMap<String, String> {
+    let mut node_role_groups = BTreeMap::new();
+    for pod in existing_pods {
+        if let (Ok(node_name), Some(role_group)) = (
+            pod_node_name(pod),
+            pod.labels().get(labels::APP_ROLE_GROUP_LABEL),
+        ) {
+            node_role_groups.insert(node_name.to_string(), role_group.clone());
+        }
+    }
+    // ordered by name, the rendered zoo.cfg must not depend on the order of the HashMap
+    let mut role_groups = eligible_nodes.values().flatten().collect::<Vec<_>>();
+    role_groups.sort_by_key(|(role_group, _)| *role_group);
+    for (role_group, (nodes, _)) in role_groups {
+        for node in nodes {
+            node_role_groups
+                .entry(node.name())
+                .or_insert_with(|| role_group.clone());
+        }
+    }
+    node_role_groups
+}
+
+/// Returns whether the servers of a role group are observers (`peerType: observer`).
+fn is_observer_group(
+    validated_role_config: &ValidatedRoleConfigByPropertyKind,
+    role: &str,
+    group: &str,
+) -> bool {
+    validated_role_config
+        .get(role)
+        .and_then(|role_groups| role_groups.get(group))
+        .and_then(|config| config.get(&PropertyNameKind::File(PROPERTIES_FILE.to_string())))
+        .and_then(|properties| properties.get(PEER_TYPE))
+        .map_or(false, |peer_type| {
+            *peer_type == PeerType::Observer.to_string()
+        })
+}
+
+/// Validates the roles and role groups of `cluster` against the product config, see
+/// [`validate_all_roles_and_groups_config`].
+fn validate_role_config(
+    cluster: &ZookeeperCluster,
+    product_config: &ProductConfigManager,
+) -> Result<ValidatedRoleConfigByPropertyKind, Error> {
+    let mut roles = HashMap::new();
+    roles.insert(
+        ZookeeperRole::Server.to_string(),
+        (
+            vec![
+                PropertyNameKind::File(PROPERTIES_FILE.to_string()),
+                PropertyNameKind::Env,
+            ],
+            cluster.spec.servers.clone().into(),
+        ),
+    );
+
+    let role_config = transform_all_roles_to_config(cluster, roles);
+    let validated_role_config = validate_all_roles_and_groups_config(
+        &cluster.spec.version.to_string(),
+        &role_config,
+        product_config,
+        false,
+        false,
+    )?;
+    Ok(validated_role_config)
+}
+
+/// Returns the problems of the spec of `cluster` which can be found without looking at the
+/// Kubernetes cluster: `zoo.cfg` properties in `spec.config` which are managed by the operator or
+/// known to be dangerous (see [`config::validate_config_properties`]), malformed secret
+/// placeholders, data directories which are not writable with the security context and invalid
+/// TLS, Kerberos, standby or backup settings. `version` is the version the servers run.
+fn validate_cluster(
+    cluster: &ZookeeperCluster,
+    version: &ZookeeperVersion,
+    validated_role_config: &ValidatedRoleConfigByPropertyKind,
+) -> Vec<String> {
+    let spec = &cluster.spec;
+    let mut problems = config::validate_config_properties(&spec.config);
+    if let Some(image) = &spec.image {
+        problems.extend(image.validate());
+    }
+    if let Some(ports) = &spec.ports {
+        problems.extend(ports.validate());
+    }
+    if let Some(admin_server) = &spec.admin_server {
+        problems.extend(admin_server.validate());
+        if admin_server.enabled() && !version.has_admin_server() {
+            problems.push(format!(
+                "adminServer: The AdminServer is not supported by ZooKeeper {}",
+                version
+            ));
+        }
+    }
+    if let Some(member_replacement) = &spec.member_replacement {
+        problems.extend(member_replacement.validate());
+    }
+    if let Some(upgrade) = &spec.upgrade {
+        problems.extend(upgrade.validate());
+    }
+    if let Some(four_letter_words) = &spec.four_letter_words {
+        problems.extend(four_letter_words.validate());
+        if spec.config.contains_key(FOUR_LETTER_WORDS_WHITELIST) {
+            problems.push(format!(
+                "fourLetterWords.whitelist: [{}] is set in config as well",
+                FOUR_LETTER_WORDS_WHITELIST
+            ));
+        }
+    }
+    if let Some(tls_config) = &spec.tls {
+        problems.extend(tls_config.validate());
+        if !version.supports_tls() {
+            problems.push(format!(
+                "tls: TLS is not supported by ZooKeeper {}",
+                version
+            ));
+        }
+        if tls_config.issuer_ref.is_some() && !cfg!(feature = "cert-manager") {
+            problems.push(
+                "tls.issuerRef: The operator was built without cert-manager support".to_string(),
+            );
+        }
+    }
+    if let Some(kerberos_config) = &spec.kerberos {
+        problems.extend(kerberos_config.validate());
+        // both would need their own login module in the `Server` section of the JAAS config
+        if spec.authentication.is_some() {
+            problems.push("kerberos: Kerberos can not be combined with authentication".to_string());
+        }
+    }
+    if let Some(logging) = &spec.logging {
+        problems.extend(logging.validate());
+        if !version.uses_log4j() {
+            problems.push(format!(
+                "logging: ZooKeeper {} logs with logback, which is not supported yet",
+                version
+            ));
+        }
+        if logging.audit.is_some() && !version.supports_audit_log() {
+            problems.push(format!(
+                "logging.audit: The audit log is not supported by ZooKeeper {}",
+                version
+            ));
+        }
+    }
+    if let Some(autopurge) = &spec.autopurge {
+        problems.extend(autopurge.validate());
+    }
+    if let Some(network_policy) = &spec.network_policy {
+        problems.extend(network_policy.validate());
+    }
+    problems.extend(ip_family::validate(
+        &spec.ip_families,
+        spec.ip_family_policy,
+    ));
+    // the NetworkPolicy only admits pods to the client port
+    if spec.external_access.is_some() && spec.network_policy.is_some() {
+        problems.push(
+            "externalAccess: External access can not be combined with networkPolicy".to_string(),
+        );
+    }
+    if spec.host_network {
+        problems.extend(host_network::validate_ports(
+            cluster,
+            version,
+            validated_role_config,
+        ));
+        // NetworkPolicies do not apply to pods on the host network
+        if spec.network_policy.is_some() {
+            problems.push(
+                "hostNetwork: The host network can not be combined with networkPolicy".to_string(),
+            );
+        }
+    }
+    if let Some(overrides) = &spec.pod_overrides {
+        problems.extend(overrides.validate(&pod_overrides::RESERVED_ENV_VARS));
+    }
+    if let Some(extra) = &spec.extra_containers {
+        problems.extend(extra.validate(
+            &extra_containers::RESERVED_CONTAINER_NAMES,
+            &extra_containers::RESERVED_VOLUME_NAMES,
+        ));
+    }
+    if let Some(service_account) = &spec.service_account {
+        problems.extend(service_account.validate());
+    }
+    let shared_volume_names = spec
+        .extra_containers
+        .iter()
+        .flat_map(|extra| extra.shared_volumes.iter())
+        .map(|volume| volume.name.as_str())
+        .collect::<Vec<_>>();
+    if let Some(extra_volumes) = &spec.extra_volumes {
+        problems.extend(extra_volumes.validate(
+            &extra_containers::RESERVED_VOLUME_NAMES,
+            &extra_volumes::RESERVED_MOUNT_PATHS,
+        ));
+        for volume in extra_volumes
+            .volumes
+            .iter()
+            .filter(|volume| shared_volume_names.contains(&volume.name.as_str()))
+        {
+            problems.push(format!(
+                "extraVolumes.volumes: [{}] is also the name of one of extraContainers.sharedVolumes",
+                volume.name
+            ));
+        }
+    }
+    let security = spec.security_context.clone().unwrap_or_default();
+    problems.extend(security.validate());
+    if security.enabled() {
+        let shared_volumes = shared_volume_names
+            .iter()
+            .map(|name| ("extraContainers.sharedVolumes", *name));
+        let extra_volumes = spec
+            .extra_volumes
+            .iter()
+            .flat_map(|extra| extra.volumes.iter())
+            .map(|volume| ("extraVolumes.volumes", volume.name.as_str()));
+        for (field, name) in shared_volumes
+            .chain(extra_volumes)
+            .filter(|(_, name)| name.starts_with(security_context::WRITABLE_VOLUME_PREFIX))
+        {
+            problems.push(format!(
+                "{}: [{}] is reserved for the writable directories of securityContext",
+                field, name
+            ));
+        }
+    }
+    if let Some(monitoring) = &spec.monitoring {
+        problems.extend(monitoring.validate(version));
+        // both would serve the metrics on a port named `metrics`
+        let has_metrics_port = validated_role_config.values().any(|role_groups| {
+            role_groups.values().any(|config| {
+                config
+                    .get(&PropertyNameKind::Env)
+                    .map_or(false, |env| env.contains_key(METRICS_PORT))
+            })
+        });
+        if has_metrics_port {
+            problems.push(format!(
+                "monitoring: Monitoring can not be combined with [{}]",
+                METRICS_PORT
+            ));
+        }
+    }
+    if let Some(standby_of) = &spec.standby_of {
+        problems.extend(standby_of.validate(
+            &cluster.namespace().unwrap_or_else(|| "default".to_string()),
+            &cluster.name(),
+        ));
+    }
+    if let Some(federation) = &spec.federation {
+        problems.extend(federation.validate(
+            &cluster.namespace().unwrap_or_else(|| "default".to_string()),
+            &cluster.name(),
+        ));
+    }
+    if let Some(hierarchical_quorum) = &spec.hierarchical_quorum {
+        let role_groups = validated_role_config
+            .iter()
+            .flat_map(|(role, role_groups)| {
+                role_groups.keys().map(move |group| {
+                    (
+                        group.clone(),
+                        is_observer_group(validated_role_config, role, group),
+                    )
+                })
+            })
+            .collect();
+        problems.extend(hierarchical_quorum.validate(&role_groups));
+        // the servers of the other clusters would not be part of any group
+        if spec.federation.is_some() {
+            problems.push(
+                "hierarchicalQuorum: A hierarchical quorum can not be combined with federation"
+                    .to_string(),
+            );
+        }
+    }
+    if let Some(backup_config) = &spec.backup {
+        problems.extend(backup_config.validate());
+        #[cfg(feature = "backup")]
+        problems.extend(backup::validate_schedule(&backup_config.schedule));
+        #[cfg(not(feature = "backup"))]
+        problems.push("backup: The operator was built without backup support".to_string());
+    }
+    if let Some(restore) = &spec.restore {
+        problems.extend(restore.validate());
+        if spec.standby_of.is_some() {
+            problems.push("restore: A standby can not be restored, its data is copied".to_string());
+        }
+        if !spec.command.is_empty() {
+            problems.push(
+                "restore: The files of the backup are staged by the default command, it can not be \
+                 combined with command"
+                    .to_string(),
+            );
+        }
+        #[cfg(not(feature = "backup"))]
+        problems.push("restore: The operator was built without backup support".to_string());
+    }
+    for role_groups in validated_role_config.values() {
+        for config in role_groups.values() {
+            if let Some(properties) =
+                config.get(&PropertyNameKind::File(PROPERTIES_FILE.to_string()))
+            {
+                problems.extend(config_secrets::validate(properties));
+                if let Some(data_dir) = properties.get(DATA_DIR) {
+                    problems.extend(security_context::validate_data_dir(
+                        &security,
+                        spec.logging.as_ref(),
+                        spec.extra_containers.as_ref(),
+                        spec.extra_volumes.as_ref(),
+                        data_dir,
+                    ));
+                    if spec.stopped
+                        && !spec
+                            .extra_volumes
+                            .as_ref()
+                            .map_or(false, |extra_volumes| extra_volumes.is_persistent(data_dir))
+                    {
+                        problems.push(format!(
+                            "stopped: The dataDir [{}] is not on a persistentVolumeClaim or hostPath of extraVolumes, stopping the servers would delete all znodes",
+                            data_dir
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    problems
+}
+
+/// Replaces the `replicas` of the role group with `spec.replicas` if it is set. Clusters with
+/// several role groups can not set it, see [`validation::validate`].
+fn apply_spec_replicas(eligible_nodes: &mut EligibleNodesForRoleAndGroup, replicas: Option<u16>) {
+    if let Some(replicas) = replicas {
+        for role_groups in eligible_nodes.values_mut() {
+            if role_groups.len() == 1 {
+                for (_, group_replicas) in role_groups.values_mut() {
+                    *group_replicas = Some(replicas);
+                }
+            }
+        }
+    }
+}
+
+/// Builds the label selector of the server pods (and ConfigMaps) of the cluster `name`, e.g. for
+/// the `scale` subresource: `app.kubernetes.io/instance=simple,app.kubernetes.io/name=zookeeper`.
+fn build_pod_selector(name: &str) -> String {
+    build_common_labels_for_all_managed_resources(APP_NAME, name)
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn group_replicas(nodes: &[Node], replicas: Option<u16>) -> usize {
+    match replicas {
+        Some(replicas) => nodes.len().min(usize::from(replicas)),
+        None => nodes.len(),
+    }
+}
+
 impl ZookeeperState {
-    async fn set_upgrading_condition(
+    async fn set_condition(
         &self,
         conditions: &[Condition],
+        condition_type: &str,
         message: &str,
         reason: &str,
         status: ConditionStatus,
@@ -128,803 +1146,5242 @@ impl ZookeeperState {
                 message.to_string(),
                 reason.to_string(),
                 status,
-                "Upgrading".to_string(),
+                condition_type.to_string(),
             )
             .await?;
 
         Ok(resource)
     }
 
-    async fn set_current_version(
-        &self,
-        version: Option<&ZookeeperVersion>,
-    ) -> OperatorResult<ZookeeperCluster> {
-        let resource = self
-            .context
-            .client
-            .merge_patch_status(
-                &self.context.resource,
-                &json!({ "currentVersion": version }),
-            )
-            .await?;
+    /// Rejects specs violating the rules of [`validation`], invalid settings, see
+    /// [`validate_cluster`], and role groups which are all observers.
+    ///
+    /// Ensembles of a size which is not recommended (e.g. an even number of participants) are
+    /// accepted or rejected depending on the [`EnsembleSizePolicy`], see `check_ensemble_size`.
+    /// Only `replicas` asking for too many participants are rejected, the size of role groups
+    /// running on all matching nodes changes with the nodes and is only warned about.
+    async fn validate_config(&mut self) -> ZookeeperReconcileResult {
+        let participants = desired_participants(&self.eligible_nodes, &self.validated_role_config);
+        let explicit_participants =
+            explicit_participants(&self.eligible_nodes, &self.validated_role_config);
+        let violations = validation::validate(&self.context.resource.spec, explicit_participants);
+        if !violations.is_empty() {
+            return Err(Error::ZookeeperClusterIsBad { violations });
+        }
+        if explicit_participants.is_none() && participants > validation::MAX_PARTICIPANTS {
+            warn!(
+                "ZookeeperCluster {}: [{}] participants run on the matching nodes, which exceeds the supported maximum of [{}], set `replicas` or narrow the selectors of the role groups",
+                self.context.log_name(),
+                participants,
+                validation::MAX_PARTICIPANTS
+            );
+        }
+        self.check_ensemble_size(participants).await?;
 
-        Ok(resource)
+        let mut problems = validate_cluster(
+            &self.context.resource,
+            &self.desired_version(),
+            &self.validated_role_config,
+        );
+        if self.context.resource.spec.host_network {
+            problems.extend(host_network::validate_shared_nodes(&self.eligible_nodes));
+        }
+        problems.extend(validate_peer_types_of_nodes(
+            &self.eligible_nodes,
+            &self.validated_role_config,
+        ));
+        if desired_replicas(&self.eligible_nodes) > 0 && participants == 0 {
+            problems.push(
+                "servers: At least one role group must run participants, all are observers"
+                    .to_string(),
+            );
+        }
+        if problems.is_empty() {
+            Ok(ReconcileFunctionAction::Continue)
+        } else {
+            Err(Error::InvalidConfig { problems })
+        }
     }
 
-    async fn set_target_version(
-        &self,
-        version: Option<&ZookeeperVersion>,
-    ) -> OperatorResult<ZookeeperCluster> {
-        let resource = self
-            .context
-            .client
-            .merge_patch_status(&self.context.resource, &json!({ "targetVersion": version }))
-            .await?;
+    /// Applies the [`EnsembleSizePolicy`] if the number of `participants` is not recommended,
+    /// see [`validation::ensemble_size_warning`]. The decision is shown in the
+    /// `EnsembleSizeNotRecommended` condition and published as warning event once per generation
+    /// of the spec.
+    async fn check_ensemble_size(&mut self, participants: usize) -> Result<(), Error> {
+        let warning = validation::ensemble_size_warning(participants);
+        let conditions = self
+            .zk_status
+            .as_ref()
+            .map(|status| status.conditions.clone())
+            .unwrap_or_default();
+        let condition = match &warning {
+            Some(warning) => status::ClusterCondition {
+                condition_type: ENSEMBLE_SIZE_NOT_RECOMMENDED_CONDITION,
+                status: true,
+                reason: match self.ensemble_size_policy {
+                    EnsembleSizePolicy::Warn => "Accepted",
+                    EnsembleSizePolicy::Reject => "Rejected",
+                },
+                message: warning.clone(),
+            },
+            None if is_condition_true(&conditions, ENSEMBLE_SIZE_NOT_RECOMMENDED_CONDITION) => {
+                status::ClusterCondition {
+                    condition_type: ENSEMBLE_SIZE_NOT_RECOMMENDED_CONDITION,
+                    status: false,
+                    reason: "Recommended",
+                    message: format!("[{}] participants are recommended", participants),
+                }
+            }
+            None => return Ok(()),
+        };
+        if !is_condition_current(
+            &conditions,
+            &condition,
+            self.context.resource.metadata.generation,
+        ) {
+            self.zk_status = self
+                .set_condition(
+                    &conditions,
+                    condition.condition_type,
+                    &condition.message,
+                    condition.reason,
+                    if condition.status {
+                        ConditionStatus::True
+                    } else {
+                        ConditionStatus::False
+                    },
+                )
+                .await?
+                .status;
+        }
 
-        Ok(resource)
+        let warning = match warning {
+            Some(warning) => warning,
+            None => return Ok(()),
+        };
+        let observed_generation = self
+            .zk_status
+            .as_ref()
+            .and_then(|status| status.observed_generation);
+        let reason = match self.ensemble_size_policy {
+            EnsembleSizePolicy::Warn => "EnsembleSizeNotRecommended",
+            EnsembleSizePolicy::Reject => "EnsembleSizeRejected",
+        };
+        if observed_generation != self.context.resource.metadata.generation {
+            self.publish_event(EventType::Warning, reason, &warning)
+                .await;
+        }
+        if self.ensemble_size_policy == EnsembleSizePolicy::Reject {
+            return Err(Error::ZookeeperClusterIsBad {
+                violations: vec![validation::SpecViolation::EnsembleSizeRejected { warning }],
+            });
+        }
+        Ok(())
     }
 
-    /// Required labels for pods. Pods without any of these will deleted and/or replaced.
-    // TODO: Now we create this every reconcile run, should be created once and reused.
-    pub fn get_required_labels(&self) -> BTreeMap<String, Option<Vec<String>>> {
-        let roles = ZookeeperRole::iter()
-            .map(|role| role.to_string())
-            .collect::<Vec<_>>();
-        let mut mandatory_labels = BTreeMap::new();
-
-        mandatory_labels.insert(labels::APP_COMPONENT_LABEL.to_string(), Some(roles));
-        mandatory_labels.insert(
-            labels::APP_INSTANCE_LABEL.to_string(),
-            Some(vec![self.context.name()]),
-        );
-        mandatory_labels.insert(
-            labels::APP_VERSION_LABEL.to_string(),
-            Some(vec![self.context.resource.spec.version.to_string()]),
-        );
-        mandatory_labels.insert(ID_LABEL.to_string(), None);
+    /// Stops the reconciliation while the cluster is paused, see [`is_paused`], so that its
+    /// objects can be changed manually. The `Paused` condition tells whether the operator leaves
+    /// the cluster alone, the rest of the status is still updated.
+    async fn check_paused(&mut self) -> ZookeeperReconcileResult {
+        let conditions = self
+            .zk_status
+            .as_ref()
+            .map(|status| status.conditions.clone())
+            .unwrap_or_default();
 
-        mandatory_labels
-    }
+        if !is_paused(&self.context.resource) {
+            if is_condition_true(&conditions, PAUSED_CONDITION) {
+                self.zk_status = self
+                    .set_condition(
+                        &conditions,
+                        PAUSED_CONDITION,
+                        "The operator reconciles the cluster",
+                        "Resumed",
+                        ConditionStatus::False,
+                    )
+                    .await?
+                    .status;
+                self.publish_event(
+                    EventType::Normal,
+                    "Resumed",
+                    "The reconciliation was resumed",
+                )
+                .await;
+            }
+            return Ok(ReconcileFunctionAction::Continue);
+        }
 
-    /// Will initialize the status object if it's never been set.
-    async fn init_status(&mut self) -> ZookeeperReconcileResult {
-        // We'll begin by setting an empty status here because later in this method we might
-        // update its conditions. To avoid any issues we'll just create it once here.
-        if self.zk_status.is_none() {
-            let status = ZookeeperClusterStatus::default();
-            self.context
-                .client
-                .merge_patch_status(&self.context.resource, &status)
-                .await?;
-            self.zk_status = Some(status);
+        if !is_condition_true(&conditions, PAUSED_CONDITION) {
+            self.zk_status = self
+                .set_condition(
+                    &conditions,
+                    PAUSED_CONDITION,
+                    &format!(
+                        "The operator does not change the cluster until spec.paused and the [{}] annotation are removed",
+                        PAUSED_ANNOTATION
+                    ),
+                    "Paused",
+                    ConditionStatus::True,
+                )
+                .await?
+                .status;
+            self.publish_event(EventType::Normal, "Paused", "The reconciliation was paused")
+                .await;
         }
+        info!(
+            "ZookeeperCluster {}: Reconciliation is paused",
+            self.context.log_name()
+        );
+        Ok(ReconcileFunctionAction::Done)
+    }
 
-        // This should always return either the existing one or the one we just created above.
-        let status = self.zk_status.take().unwrap_or_default();
-        let spec_version = self.zk_spec.version.clone();
+    /// Deletes all pods and stops the reconciliation while `spec.stopped` is set. The ConfigMaps
+    /// with the ids of the servers are kept, so the pods are recreated on the same nodes with the
+    /// same ids once it is unset. The `Stopped` condition tells whether the servers are stopped.
+    async fn stop_servers(&mut self) -> ZookeeperReconcileResult {
+        let conditions = self
+            .zk_status
+            .as_ref()
+            .map(|status| status.conditions.clone())
+            .unwrap_or_default();
 
-        match (&status.current_version, &status.target_version) {
-            (None, None) => {
-                // No current_version and no target_version must be initial installation.
-                // We'll set the Upgrading condition and the target_version to the version from spec.
-                info!(
-                    "Initial installation, now moving towards version [{}]",
-                    self.zk_spec.version
-                );
+        if !self.zk_spec.stopped {
+            if is_condition_true(&conditions, STOPPED_CONDITION) {
                 self.zk_status = self
-                    .set_upgrading_condition(
-                        &status.conditions,
-                        &format!("Initial installation to version [{:?}]", spec_version),
-                        "InitialInstallation",
-                        ConditionStatus::True,
+                    .set_condition(
+                        &conditions,
+                        STOPPED_CONDITION,
+                        "The servers are running",
+                        "Started",
+                        ConditionStatus::False,
                     )
                     .await?
                     .status;
-                self.zk_status = self.set_target_version(Some(&spec_version)).await?.status;
+                self.publish_event(
+                    EventType::Normal,
+                    "Started",
+                    "The servers are started again",
+                )
+                .await;
             }
-            (None, Some(target_version)) => {
-                // No current_version but a target_version means we're still doing the initial
-                // installation. Will continue working towards that goal even if another version
-                // was set in the meantime.
-                debug!(
-                    "Initial installation, still moving towards version [{}]",
-                    target_version
-                );
-                if &spec_version != target_version {
-                    info!("A new target version ([{}]) was requested while we still do the initial installation to [{}], finishing running upgrade first", spec_version, target_version)
-                }
-                // We do this here to update the observedGeneration if needed
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+
+        for pod in &self.existing_pods {
+            info!(
+                "ZookeeperCluster {}: The cluster is stopped, deleting pod [{}]",
+                self.context.log_name(),
+                pod.name()
+            );
+            self.delete_pod(pod).await?;
+        }
+        if !is_condition_true(&conditions, STOPPED_CONDITION) {
+            self.zk_status = self
+                .set_condition(
+                    &conditions,
+                    STOPPED_CONDITION,
+                    "All servers are stopped until spec.stopped is unset",
+                    "Stopped",
+                    ConditionStatus::True,
+                )
+                .await?
+                .status;
+            self.publish_event(EventType::Normal, "Stopped", "All servers were stopped")
+                .await;
+        }
+        Ok(ReconcileFunctionAction::Done)
+    }
+
+    /// Records the changes the reconciliation would make instead of making them while the cluster
+    /// is in dry-run mode, see [`dry_run`]. It runs once the spec was validated, so invalid specs
+    /// fail as usual and nothing is planned for deleted or paused clusters. The remaining steps
+    /// do not run, so besides the finalizer nothing but the status is written.
+    async fn plan_dry_run(&mut self) -> ZookeeperReconcileResult {
+        let conditions = self
+            .zk_status
+            .as_ref()
+            .map(|status| status.conditions.clone())
+            .unwrap_or_default();
+
+        if self.dry_run == DryRunMode::Disabled {
+            if self
+                .zk_status
+                .as_ref()
+                .map_or(false, |status| status.dry_run.is_some())
+            {
                 self.zk_status = self
-                    .set_upgrading_condition(
-                        &status.conditions,
-                        &format!("Initial installation to version [{:?}]", target_version),
-                        "InitialInstallation",
-                        ConditionStatus::True,
-                    )
+                    .context
+                    .client
+                    .merge_patch_status(&self.context.resource, &json!({ "dryRun": null }))
                     .await?
                     .status;
             }
-            (Some(current_version), None) => {
-                // We are at a stable version but have no target_version set.
-                // This will be the normal state.
-                // We'll check if there is a different version in spec and if it is will
-                // set it in target_version, but only if it's actually a compatible upgrade.
-                if current_version != &spec_version {
-                    if current_version.is_valid_upgrade(&spec_version).unwrap() {
-                        let new_version = spec_version;
-                        let message = format!(
-                            "Upgrading from [{:?}] to [{:?}]",
-                            current_version, &new_version
-                        );
-                        info!("{}", message);
-                        self.zk_status = self.set_target_version(Some(&new_version)).await?.status;
-                        self.zk_status = self
-                            .set_upgrading_condition(
-                                &status.conditions,
-                                &message,
-                                "Upgrading",
-                                ConditionStatus::True,
-                            )
-                            .await?
-                            .status;
-                    } else {
-                        // TODO: This should be caught by an validating admission webhook
-                        warn!("Upgrade from [{}] to [{}] not possible but requested in spec: Ignoring, will continue reconcile as if the invalid version weren't set", current_version, spec_version);
-                    }
-                } else {
-                    let message = format!(
-                        "No upgrade required [{:?}] is still the current_version",
-                        current_version
-                    );
-                    trace!("{}", message);
-                    self.zk_status = self
-                        .set_upgrading_condition(
-                            &status.conditions,
-                            &message,
-                            "",
-                            ConditionStatus::False,
-                        )
-                        .await?
-                        .status;
-                }
-            }
-            (Some(current_version), Some(target_version)) => {
-                // current_version and target_version are set means we're still in the process
-                // of upgrading. We'll only do some logging and checks and will update
-                // the condition so observedGeneration can be updated.
-                debug!(
-                    "Still upgrading from [{}] to [{}]",
-                    current_version, target_version
-                );
-                if &self.zk_spec.version != target_version {
-                    info!("A new target version was requested while we still upgrade from [{}] to [{}], finishing running upgrade first", current_version, target_version)
-                }
-                let message = format!(
-                    "Upgrading from [{:?}] to [{:?}]",
-                    current_version, target_version
-                );
-
+            if is_condition_true(&conditions, dry_run::DRY_RUN_CONDITION) {
                 self.zk_status = self
-                    .set_upgrading_condition(
-                        &status.conditions,
-                        &message,
-                        "",
+                    .set_condition(
+                        &conditions,
+                        dry_run::DRY_RUN_CONDITION,
+                        "The operator makes the changes",
+                        "Disabled",
                         ConditionStatus::False,
                     )
                     .await?
                     .status;
+                self.publish_event(
+                    EventType::Normal,
+                    "DryRunDisabled",
+                    "The dry run was disabled, the planned changes are made now",
+                )
+                .await;
             }
+            return Ok(ReconcileFunctionAction::Continue);
         }
 
-        Ok(ReconcileFunctionAction::Continue)
-    }
-
-    // This looks at all currently existing Pods for the current ZookeeperCluster object.
-    // It checks if all the pods are valid (i.e. contain required labels) and then builds an `IdInformation`
-    // object and sets it on the current state.
-    async fn read_existing_pod_information(&mut self) -> ZookeeperReconcileResult {
-        trace!(
-            "Reading existing pod information for {}",
-            self.context.log_name()
-        );
+        let changes = self.plan_changes().await?;
+        for change in &changes {
+            info!(
+                "ZookeeperCluster {}: Dry run, would {:?} {} [{}]{}{}",
+                self.context.log_name(),
+                change.action,
+                change.kind,
+                change.name,
+                change
+                    .reason
+                    .as_ref()
+                    .map(|reason| format!(": {}", reason))
+                    .unwrap_or_default(),
+                if change.fields.is_empty() {
+                    String::new()
+                } else {
+                    format!(" (changed fields: {})", change.fields.join(", "))
+                }
+            );
+        }
+        let summary = dry_run::summarize(&changes);
+        let dry_run_status = DryRunStatus {
+            generation: self.context.resource.metadata.generation,
+            server_side: self.dry_run == DryRunMode::Server,
+            changes,
+        };
 
-        // We first create a list of all used ids (`myid`) so we know which we can reuse
-        // At the same time we create a map of id to pod for all pods which already exist
-        // Later we fill those up.
-        // It depends on the ZooKeeper version on whether this requires a full restart of the cluster
-        // or whether this can be done dynamically.
-        // This list also includes all ids currently in use by terminating or otherwise not-ready pods.
-        // We never want to use those as long as there's a chance that some process might be actively
-        // using it.
-        // There can be a maximum of 255 (I believe) ids.
-        let mut used_ids = Vec::with_capacity(self.existing_pods.len());
-        let mut node_name_to_pod = HashMap::new(); // This is going to own the pods
-        let mut node_name_to_id = HashMap::new();
+        // the plan is only published when it changes, not with every reconciliation
+        if self
+            .zk_status
+            .as_ref()
+            .and_then(|status| status.dry_run.as_ref())
+            != Some(&dry_run_status)
+        {
+            self.zk_status = self
+                .context
+                .client
+                .merge_patch_status(&self.context.resource, &json!({ "dryRun": dry_run_status }))
+                .await?
+                .status;
+            self.publish_event(EventType::Normal, "DryRun", &summary)
+                .await;
+        }
+        let condition = status::ClusterCondition {
+            condition_type: dry_run::DRY_RUN_CONDITION,
+            status: true,
+            reason: "Planned",
+            message: summary,
+        };
+        if !is_condition_current(
+            &conditions,
+            &condition,
+            self.context.resource.metadata.generation,
+        ) {
+            self.zk_status = self
+                .set_condition(
+                    &conditions,
+                    condition.condition_type,
+                    &condition.message,
+                    condition.reason,
+                    ConditionStatus::True,
+                )
+                .await?
+                .status;
+        }
 
-        // Iterate over all existing pods and read the label which contains the `myid`
-        for pod in &self.existing_pods {
-            if let Some(PodSpec {
-                node_name: Some(node_name),
-                ..
-            }) = &pod.spec
-            {
-                match pod.metadata.labels.get(ID_LABEL) {
-                    None => {
-                        error!("ZookeeperCluster {}: Pod [{:?}] does not have the `id` label, this is illegal, deleting it.",
-                               self.context.log_name(),
-                               pod);
-                        self.context.client.delete(pod).await?;
-                    }
-                    Some(label) => {
-                        let id = match label.parse::<usize>() {
-                            Ok(id) => id,
-                            Err(_) => {
-                                error!("ZookeeperCluster {}: Pod [{:?}] does have the `id` label but the label ([{}]) cannot be parsed, this is illegal, deleting the pod.",
-                                       self.context.log_name(), pod, label);
-                                self.context.client.delete(pod).await?;
-                                continue;
-                            }
-                        };
+        Ok(ReconcileFunctionAction::Done)
+    }
 
-                        // Check if we have seen the same id before
-                        // This should never happen and would currently require manual cleanup
-                        if used_ids.contains(&id) {
-                            // TODO: Update status
-                            error!(
-                                "Found a duplicate `myid` [{}] in Pod [{}], we can't recover \
-                                 from this error and you need to clean up manually",
-                                id,
-                                pod.name()
-                            );
-                            return Err(Error::ReconcileError("Found duplicate id".to_string()));
-                        }
+    /// Computes the changes the reconciliation would make in dry-run mode: the applied objects,
+    /// the ConfigMaps, the pods which are created or deleted and the pods which are restarted
+    /// because of a changed configuration or an upgrade. The ids, the excess pods and the outdated
+    /// pods are determined like in the reconciliation steps. Scale steps and restarts for other
+    /// reasons (e.g. changed references) are not planned.
+    async fn plan_changes(&self) -> Result<Vec<PlannedChange>, Error> {
+        let cluster = &self.context.resource;
+        let namespace = self.context.namespace();
+        let server_side = self.dry_run == DryRunMode::Server;
+        let mut changes = vec![];
 
-                        used_ids.push(id);
-                        node_name_to_id.insert(node_name.clone(), id);
-                        node_name_to_pod.insert(node_name.clone(), pod.clone());
-                    }
-                };
-            } else {
-                error!("ZookeeperCluster {}: Pod [{:?}] does not have any spec or labels, this is illegal, deleting it.",
-                       self.context.log_name(),
-                       pod);
-                self.context.client.delete(pod).await?;
+        let service_account = service_account::config(cluster);
+        if service_account.create() {
+            let service_accounts_api: Api<ServiceAccount> =
+                self.context.client.get_namespaced_api(&namespace);
+            let object = service_account::build_service_account(cluster, &service_account)?;
+            changes.extend(apply::plan(&service_accounts_api, &object, server_side).await?);
+        }
+        let roles_api: Api<Role> = self.context.client.get_namespaced_api(&namespace);
+        let role_bindings_api: Api<RoleBinding> =
+            self.context.client.get_namespaced_api(&namespace);
+        let name = service_account::role_binding_name(&self.context.name());
+        match service_account::build_role_binding(cluster, &service_account)? {
+            Some(role_binding) => {
+                changes.extend(apply::plan(&role_bindings_api, &role_binding, server_side).await?)
             }
+            None => changes.extend(apply::plan_delete(&role_bindings_api, &name).await?),
         }
+        changes.extend(apply::plan_delete(&roles_api, &name).await?);
 
-        debug!(
-            "ZookeeperCluster {}: Found these myids in use [{:?}]",
-            self.context.log_name(),
-            used_ids
-        );
-
-        let id_information = IdInformation::new(used_ids, node_name_to_pod, node_name_to_id);
-        self.id_information = Some(id_information);
-
-        Ok(ReconcileFunctionAction::Continue)
-    }
+        let budgets_api: Api<PodDisruptionBudget> =
+            self.context.client.get_namespaced_api(&namespace);
+        let budget = disruption_budget::build_budget(
+            cluster,
+            desired_participants(&self.eligible_nodes, &self.validated_role_config),
+        )?;
+        changes.extend(apply::plan(&budgets_api, &budget, server_side).await?);
 
-    /// This function looks at all the requested servers from the spec and assigns ids to those
-    /// that don't have one yet.
-    /// We do this here - and not later - because we need the id mapping information for the
-    /// ConfigMap generation later.
-    /// NOTE: This method will _not_ work if multiple servers should run on a single node
-    async fn assign_ids(&mut self) -> ZookeeperReconcileResult {
-        trace!("Assigning ids to new servers from the spec",);
+        let network_policies_api: Api<k8s_openapi::api::networking::v1::NetworkPolicy> =
+            self.context.client.get_namespaced_api(&namespace);
+        changes.extend(match &cluster.spec.network_policy {
+            Some(network_policy) => {
+                let network_policy = network_policy::build_network_policy(cluster, network_policy)?;
+                apply::plan(&network_policies_api, &network_policy, server_side).await?
+            }
+            None => {
+                let name = network_policy::network_policy_name(&self.context.name());
+                apply::plan_delete(&network_policies_api, &name).await?
+            }
+        });
 
-        let id_information = self.id_information.as_mut().ok_or_else(|| error::Error::ReconcileError(
-            "id_information missing, this is a programming error and should never happen. Please report in our issue tracker.".to_string(),
-        ))?;
+        let services_api: Api<Service> = self.context.client.get_namespaced_api(&namespace);
+        changes.extend(
+            match cluster
+                .spec
+                .admin_server
+                .as_ref()
+                .filter(|admin_server| admin_server.service)
+            {
+                Some(admin_server) => {
+                    let service = admin_server::build_service(cluster, admin_server)?;
+                    apply::plan(&services_api, &service, server_side).await?
+                }
+                None => {
+                    let name = admin_server::service_name(&self.context.name());
+                    apply::plan_delete(&services_api, &name).await?
+                }
+            },
+        );
 
-        id_information.used_ids.sort_unstable();
+        // the servers which would be added get their ids like in `assign_ids`
+        let federation_members = self.read_federation_members().await?;
+        let mut id_information = IdInformation::from_pods(&self.existing_pods);
+        reserve_federation_ids(cluster, &federation_members, &mut id_information.used_ids);
+        id_information.assign_new_ids(&self.eligible_nodes, None, self.replaced_id());
 
-        for role in ZookeeperRole::iter() {
-            if let Some(eligible_nodes_for_role) = self.eligible_nodes.get(&role.to_string()) {
-                for (eligible_nodes, _replicas) in eligible_nodes_for_role.values() {
-                    for node in eligible_nodes {
-                        let node_name = match &node.metadata.name {
-                            Some(name) => name,
-                            None => continue,
-                        };
+        let existing_config_maps = self.list_owned_config_maps().await?;
+        let data_config_maps =
+            self.desired_data_config_maps(&id_information, &federation_members)?;
+        if self.managed_resources.config_maps {
+            for config_map in data_config_maps.values() {
+                changes.extend(plan_config_map(config_map, &existing_config_maps));
+            }
+        }
 
-                        match id_information.node_name_to_pod.get(node_name) {
-                            None => {
-                                // TODO: Need to check whether the topology has changed. If it has we need to restart all servers depending on the ZK version
-                                let new_id = find_first_missing(&id_information.used_ids);
+        let excess_pods = excess_pods(&self.eligible_nodes, &self.existing_pods);
+        for pod in &excess_pods {
+            changes.push(
+                PlannedChange::new(PlannedAction::Delete, "Pod", &pod.name())
+                    .with_reason("The ensemble is scaled down".to_string()),
+            );
+        }
 
-                                id_information.used_ids.push(new_id);
-                                id_information.used_ids.sort_unstable();
-                                id_information
-                                    .node_name_to_id
-                                    .insert(node_name.clone(), new_id);
+        // the missing pods are found like in `create_missing_pods`
+        let version = self.desired_version();
+        let pods_api: Api<Pod> = self.context.client.get_namespaced_api(&namespace);
+        for zookeeper_role in ZookeeperRole::iter() {
+            let role = zookeeper_role.to_string();
+            for (role_group, (nodes, replicas)) in
+                self.eligible_nodes.get(&role).into_iter().flatten()
+            {
+                for node in k8s_utils::find_nodes_that_need_pods(
+                    nodes,
+                    &self.existing_pods,
+                    &get_role_and_group_labels(&role, role_group),
+                    *replicas,
+                ) {
+                    let node_name = node.metadata.name.as_deref().unwrap_or_default();
+                    let id = match id_information.node_name_to_id.get(node_name) {
+                        Some(id) => *id,
+                        None => continue,
+                    };
+                    let validated_config =
+                        config_for_role_and_group(&role, role_group, &self.validated_role_config)?;
+                    let id_config_map =
+                        resources::build_id_config_map(cluster, &version, &role, role_group, id)?;
+                    if self.managed_resources.config_maps {
+                        changes.extend(plan_config_map(&id_config_map, &existing_config_maps));
+                    }
+                    let mut config_maps = BTreeMap::new();
+                    if let Some(config_map) =
+                        data_config_maps.get(&(role.clone(), role_group.clone()))
+                    {
+                        config_maps.insert(CONFIG_MAP_TYPE_DATA, config_map.clone());
+                    }
+                    config_maps.insert(CONFIG_MAP_TYPE_ID, id_config_map);
 
-                                info!(
-                                    "Assigning new id [{}] to server/node [{}]",
-                                    new_id, node_name
-                                )
-                            }
-                            Some(_) => {
-                                trace!(
-                                    "Pod for node [{}] already exists and is assigned id [{:?}]",
-                                    node_name,
-                                    id_information.node_name_to_id.get(node_name)
-                                );
-                            }
-                        }
+                    let pod = self.build_pod(
+                        &role,
+                        role_group,
+                        node_name,
+                        &node.metadata.labels,
+                        id,
+                        &config_maps,
+                        validated_config,
+                    )?;
+                    if server_side {
+                        // rejects pods which are invalid or denied by admission webhooks
+                        let params = PostParams {
+                            dry_run: true,
+                            ..PostParams::default()
+                        };
+                        pods_api.create(&params, &pod).await?;
                     }
+                    let name = pod
+                        .metadata
+                        .name
+                        .clone()
+                        .or_else(|| pod.metadata.generate_name.clone())
+                        .unwrap_or_default();
+                    changes.push(
+                        PlannedChange::new(PlannedAction::Create, "Pod", &name)
+                            .with_reason(format!("Server [{}] on node [{}]", id, node_name)),
+                    );
                 }
             }
         }
 
-        Ok(ReconcileFunctionAction::Continue)
-    }
-
-    pub async fn create_missing_pods(&mut self) -> ZookeeperReconcileResult {
-        trace!("Starting `create_missing_pods`");
+        // the outdated pods are found like in `upgrade_pods` and
+        // `restart_pods_with_outdated_config`
+        let target_version = self
+            .zk_status
+            .as_ref()
+            .and_then(|status| status.target_version.as_ref());
+        let desired_hashes = rollout::DesiredHashes {
+            config: config_hashes(&data_config_maps)?,
+            references: None,
+        };
+        let order = RestartOrder::new(self.existing_pods.iter().collect(), self.recorded_quorum());
+        for pod in order.pods {
+            if excess_pods
+                .iter()
+                .any(|excess_pod| excess_pod.name() == pod.name())
+            {
+                continue;
+            }
+            let reason = match target_version {
+                Some(target_version) if !runs_version(pod, target_version) => {
+                    format!("Upgrade to version [{}]", target_version)
+                }
+                _ if desired_hashes.is_outdated(pod) => "The configuration changed".to_string(),
+                _ => continue,
+            };
+            changes.push(
+                PlannedChange::new(PlannedAction::Restart, "Pod", &pod.name()).with_reason(reason),
+            );
+        }
+
+        Ok(changes)
+    }
+
+    /// In strict mode, fails the reconciliation while the applied spec contains fields which are
+    /// not part of the CRD (and were therefore silently pruned by the API server) and reports
+    /// them in the `UnknownSpecFields` condition.
+    async fn validate_spec(&mut self) -> ZookeeperReconcileResult {
+        if !self.strict_spec_validation {
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+
+        let unknown_fields = match self
+            .context
+            .resource
+            .annotations()
+            .get(strict::LAST_APPLIED_CONFIGURATION_ANNOTATION)
+        {
+            Some(applied) => strict::find_unknown_fields(applied).unwrap_or_else(|err| {
+                warn!(
+                    "ZookeeperCluster {}: Could not check the last applied configuration for unknown fields: {}",
+                    self.context.log_name(),
+                    err
+                );
+                vec![]
+            }),
+            None => vec![],
+        };
+        let conditions = self
+            .zk_status
+            .as_ref()
+            .map(|status| status.conditions.clone())
+            .unwrap_or_default();
+
+        if unknown_fields.is_empty() {
+            if is_condition_true(&conditions, UNKNOWN_SPEC_FIELDS_CONDITION) {
+                self.zk_status = self
+                    .set_condition(
+                        &conditions,
+                        UNKNOWN_SPEC_FIELDS_CONDITION,
+                        "The spec does not contain unknown fields",
+                        "SpecValid",
+                        ConditionStatus::False,
+                    )
+                    .await?
+                    .status;
+            }
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+
+        self.zk_status = self
+            .set_condition(
+                &conditions,
+                UNKNOWN_SPEC_FIELDS_CONDITION,
+                &format!(
+                    "The spec contains unknown fields: [{}]",
+                    unknown_fields.join(", ")
+                ),
+                "UnknownFields",
+                ConditionStatus::True,
+            )
+            .await?
+            .status;
+        Err(Error::UnknownSpecFields {
+            fields: unknown_fields,
+        })
+    }
+
+    /// Checks the pods and ConfigMaps of the cluster for modifications by unknown field managers
+    /// if `spec.tamperDetection` is set, see [`tamper_detection`].
+    ///
+    /// Detected modifications set the `TamperDetected` condition and are published as warning
+    /// event (once per set of managers). If configured, disruptive operations are paused until
+    /// the modifications are acknowledged with the
+    /// [`tamper_detection::TAMPER_ACKNOWLEDGED_AT_ANNOTATION`].
+    async fn detect_tampering(&mut self) -> ZookeeperReconcileResult {
+        let conditions = self
+            .zk_status
+            .as_ref()
+            .map(|status| status.conditions.clone())
+            .unwrap_or_default();
+        let tamper_detection = match &self.context.resource.spec.tamper_detection {
+            Some(tamper_detection) => tamper_detection.clone(),
+            None => {
+                self.clear_tamper_detected(&conditions).await?;
+                return Ok(ReconcileFunctionAction::Continue);
+            }
+        };
+
+        let acknowledged_at = match self
+            .context
+            .resource
+            .annotations()
+            .get(tamper_detection::TAMPER_ACKNOWLEDGED_AT_ANNOTATION)
+        {
+            Some(value) => Some(
+                DateTime::parse_from_rfc3339(value)
+                    .map_err(|err| {
+                        Error::ReconcileError(format!(
+                            "Invalid value [{}] of annotation [{}]: {}",
+                            value,
+                            tamper_detection::TAMPER_ACKNOWLEDGED_AT_ANNOTATION,
+                            err
+                        ))
+                    })?
+                    .with_timezone(&Utc),
+            ),
+            None => None,
+        };
+        let config_maps = self.list_owned_config_maps().await?;
+
+        let managers = tamper_detection::find_unknown_managers(
+            self.existing_pods
+                .iter()
+                .map(|pod| &pod.metadata)
+                .chain(config_maps.iter().map(|config_map| &config_map.metadata)),
+            &tamper_detection,
+            acknowledged_at.as_ref(),
+        );
+        if managers.is_empty() {
+            self.clear_tamper_detected(&conditions).await?;
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+
+        let message = format!(
+            "Objects owned by the cluster were modified by unknown field managers: [{}]",
+            managers.into_iter().collect::<Vec<_>>().join(", ")
+        );
+        let already_reported = conditions.iter().any(|condition| {
+            condition.type_ == TAMPER_DETECTED_CONDITION
+                && condition.status == "True"
+                && condition.message == message
+        });
+        if !already_reported {
+            warn!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+            self.publish_event(EventType::Warning, "TamperDetected", &message)
+                .await;
+            self.zk_status = self
+                .set_condition(
+                    &conditions,
+                    TAMPER_DETECTED_CONDITION,
+                    &message,
+                    "UnknownFieldManager",
+                    ConditionStatus::True,
+                )
+                .await?
+                .status;
+        }
+        self.disruptions_paused = tamper_detection.pause_disruptive_operations;
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    async fn clear_tamper_detected(&mut self, conditions: &[Condition]) -> OperatorResult<()> {
+        if is_condition_true(conditions, TAMPER_DETECTED_CONDITION) {
+            self.zk_status = self
+                .set_condition(
+                    conditions,
+                    TAMPER_DETECTED_CONDITION,
+                    "No unacknowledged modifications by unknown field managers",
+                    "",
+                    ConditionStatus::False,
+                )
+                .await?
+                .status;
+        }
+        Ok(())
+    }
+
+    /// Publishes an event about the ZookeeperCluster, see [`events::publish_event`].
+    async fn publish_event(&self, event_type: EventType, reason: &str, message: &str) {
+        events::publish_event(
+            &self.context.client,
+            &self.context.resource,
+            event_type,
+            reason,
+            message,
+        )
+        .await;
+    }
+
+    /// Checks whether a PodDisruptionBudget allows taking down `pod` for `operation` (e.g.
+    /// "restart") and returns the reason and message of the wait if it does not.
+    ///
+    /// If a budget covering the pod is exhausted (because another disruption already consumed it)
+    /// the `WaitingForDisruptionBudget` condition is set with the blocking budget and the time of
+    /// the next attempt. Otherwise the condition is cleared.
+    ///
+    /// The operation has to wait as well while disruptive operations are paused because of
+    /// unacknowledged modifications, see `detect_tampering`.
+    async fn check_disruption(
+        &self,
+        pod: &Pod,
+        operation: &str,
+    ) -> Result<Option<(WaitReason, String)>, Error> {
+        if self.disruptions_paused {
+            let message = format!(
+                "Not allowed to {} pod [{}] until the modifications by unknown field managers are acknowledged",
+                operation,
+                pod.name()
+            );
+            info!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+            return Ok(Some((WaitReason::DisruptionsPaused, message)));
+        }
+
+        let budgets_api: Api<PodDisruptionBudget> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+        let cluster_name = self.context.name();
+        let budgets = budgets_api
+            .list(&ListParams::default())
+            .await?
+            .items
+            .into_iter()
+            .filter(|budget| {
+                !disruption_budget::is_own_budget_without_disruptions(budget, &cluster_name)
+            })
+            .collect::<Vec<_>>();
+        let conditions = self
+            .zk_status
+            .as_ref()
+            .map(|status| status.conditions.clone())
+            .unwrap_or_default();
+
+        if let Some(budget) = disruption_budget::find_blocking_budget(&budgets, pod) {
+            let now = Utc::now();
+            let attempts = waiting::next_waiting_status(
+                previous_waiting(&self.zk_status),
+                WaitReason::DisruptionBudget,
+                String::new(),
+                &now,
+            )
+            .attempts;
+            let retry_at = now
+                + ChronoDuration::from_std(waiting::requeue_interval(
+                    WaitReason::DisruptionBudget,
+                    attempts,
+                ))
+                .unwrap();
+            let message = format!(
+                "Waiting to {} pod [{}]: PodDisruptionBudget [{}] does not allow any further disruptions, retrying at [{}]",
+                operation,
+                pod.name(),
+                budget.name(),
+                retry_at.to_rfc3339()
+            );
+            info!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+            self.set_condition(
+                &conditions,
+                WAITING_FOR_DISRUPTION_BUDGET_CONDITION,
+                &message,
+                "DisruptionBudgetExhausted",
+                ConditionStatus::True,
+            )
+            .await?;
+            return Ok(Some((WaitReason::DisruptionBudget, message)));
+        }
+
+        self.clear_waiting_for_disruption_budget(&conditions)
+            .await?;
+        Ok(None)
+    }
+
+    async fn clear_waiting_for_disruption_budget(
+        &self,
+        conditions: &[Condition],
+    ) -> OperatorResult<()> {
+        if is_condition_true(conditions, WAITING_FOR_DISRUPTION_BUDGET_CONDITION) {
+            self.set_condition(
+                conditions,
+                WAITING_FOR_DISRUPTION_BUDGET_CONDITION,
+                "No operation is blocked by a PodDisruptionBudget",
+                "",
+                ConditionStatus::False,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn set_upgrading_condition(
+        &self,
+        conditions: &[Condition],
+        message: &str,
+        reason: &str,
+        status: ConditionStatus,
+    ) -> OperatorResult<ZookeeperCluster> {
+        self.set_condition(conditions, "Upgrading", message, reason, status)
+            .await
+    }
+
+    /// Refuses to act on an unsupported version change (see
+    /// [`ZookeeperVersion::is_valid_version_change`]). The ensemble keeps running its current
+    /// version and the refusal is surfaced via the [`VERSION_CHANGE_REJECTED_CONDITION`] and a
+    /// warning event. The event is only published when the condition is first set.
+    async fn reject_version_change(
+        &mut self,
+        conditions: &[Condition],
+        current_version: &ZookeeperVersion,
+        requested_version: &ZookeeperVersion,
+    ) -> OperatorResult<()> {
+        let message = format!(
+            "Changing the version from [{}] to [{}] is not supported because ZooKeeper can not be downgraded to an older release line, keeping version [{}]",
+            current_version, requested_version, current_version
+        );
+        warn!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+
+        if !is_condition_true(conditions, VERSION_CHANGE_REJECTED_CONDITION) {
+            self.publish_event(EventType::Warning, "DowngradeRejected", &message)
+                .await;
+        }
+
+        self.zk_status = self
+            .set_condition(
+                conditions,
+                VERSION_CHANGE_REJECTED_CONDITION,
+                &message,
+                "DowngradeRejected",
+                ConditionStatus::True,
+            )
+            .await?
+            .status;
+        Ok(())
+    }
+
+    /// Resets the [`VERSION_CHANGE_REJECTED_CONDITION`] once the spec does not request an
+    /// unsupported version change anymore.
+    async fn clear_version_change_rejected(
+        &mut self,
+        conditions: &[Condition],
+    ) -> OperatorResult<()> {
+        if is_condition_true(conditions, VERSION_CHANGE_REJECTED_CONDITION) {
+            self.zk_status = self
+                .set_condition(
+                    conditions,
+                    VERSION_CHANGE_REJECTED_CONDITION,
+                    "The requested version is supported",
+                    "VersionChangeAccepted",
+                    ConditionStatus::False,
+                )
+                .await?
+                .status;
+        }
+        Ok(())
+    }
+
+    async fn set_current_version(
+        &self,
+        version: Option<&ZookeeperVersion>,
+    ) -> OperatorResult<ZookeeperCluster> {
+        let resource = self
+            .context
+            .client
+            .merge_patch_status(
+                &self.context.resource,
+                &json!({ "currentVersion": version }),
+            )
+            .await?;
+
+        Ok(resource)
+    }
+
+    async fn set_target_version(
+        &self,
+        version: Option<&ZookeeperVersion>,
+    ) -> OperatorResult<ZookeeperCluster> {
+        let resource = self
+            .context
+            .client
+            .merge_patch_status(&self.context.resource, &json!({ "targetVersion": version }))
+            .await?;
+
+        Ok(resource)
+    }
+
+    /// The version pods are created with: the version we are currently upgrading to or, if no
+    /// upgrade is in progress, the current version. Only if neither is known (yet) we fall back
+    /// to the version from the spec.
+    fn desired_version(&self) -> ZookeeperVersion {
+        self.zk_status
+            .as_ref()
+            .and_then(|status| {
+                status
+                    .target_version
+                    .clone()
+                    .or_else(|| status.current_version.clone())
+            })
+            .unwrap_or_else(|| self.zk_spec.version.clone())
+    }
+
+    /// Returns the canary configuration of `spec.upgrade`.
+    fn canary_config(&self) -> Option<ZookeeperCanary> {
+        self.context
+            .resource
+            .spec
+            .upgrade
+            .as_ref()
+            .and_then(|upgrade| upgrade.canary.clone())
+    }
+
+    /// Returns the canary of the running upgrade, `None` if there is none or canaries are not
+    /// configured (anymore).
+    fn canary_status(&self) -> Option<CanaryStatus> {
+        self.canary_config()?;
+        let status = self.zk_status.as_ref()?;
+        status
+            .canary
+            .clone()
+            .filter(|canary| Some(&canary.version) == status.target_version.as_ref())
+    }
+
+    /// Returns the configured integrity check of upgrades.
+    fn integrity_check_config(&self) -> Option<ZookeeperIntegrityCheck> {
+        self.context
+            .resource
+            .spec
+            .upgrade
+            .as_ref()
+            .and_then(|upgrade| upgrade.integrity_check.clone())
+    }
+
+    /// Returns the integrity check of the running upgrade, `None` if there is none or checks are
+    /// not configured (anymore).
+    fn integrity_check_status(&self) -> Option<IntegrityCheckStatus> {
+        self.integrity_check_config()?;
+        let status = self.zk_status.as_ref()?;
+        status
+            .integrity_check
+            .clone()
+            .filter(|check| Some(&check.version) == status.target_version.as_ref())
+    }
+
+    /// Returns the data directory of the role group of `pod`.
+    fn data_dir(&self, pod: &Pod) -> String {
+        let pod_labels = pod.labels();
+        pod_labels
+            .get(labels::APP_COMPONENT_LABEL)
+            .and_then(|role| self.validated_role_config.get(role))
+            .and_then(|role_groups| role_groups.get(pod_labels.get(labels::APP_ROLE_GROUP_LABEL)?))
+            .and_then(|config| config.get(&PropertyNameKind::File(PROPERTIES_FILE.to_string())))
+            .and_then(|properties| properties.get(DATA_DIR))
+            .cloned()
+            .unwrap_or_else(|| "/tmp/zookeeper".to_string())
+    }
+
+    /// Returns the monitoring configuration if the metrics are served by the JMX exporter
+    /// sidecar, i.e. the desired version lacks the Prometheus metrics provider.
+    fn jmx_exporter_monitoring(&self) -> Option<&ZookeeperMonitoring> {
+        resources::jmx_exporter_monitoring(&self.context.resource, &self.desired_version())
+    }
+
+    /// Required labels for pods. Pods without any of these will deleted and/or replaced.
+    /// The version label is deliberately not part of this, pods running an outdated version are
+    /// replaced one by one in `upgrade_pods`.
+    // TODO: Now we create this every reconcile run, should be created once and reused.
+    pub fn get_required_labels(&self) -> BTreeMap<String, Option<Vec<String>>> {
+        let roles = ZookeeperRole::iter()
+            .map(|role| role.to_string())
+            .collect::<Vec<_>>();
+        let mut mandatory_labels = BTreeMap::new();
+
+        mandatory_labels.insert(labels::APP_COMPONENT_LABEL.to_string(), Some(roles));
+        mandatory_labels.insert(
+            labels::APP_INSTANCE_LABEL.to_string(),
+            Some(vec![self.context.name()]),
+        );
+        mandatory_labels.insert(ID_LABEL.to_string(), None);
+
+        mandatory_labels
+    }
+
+    /// Will initialize the status object if it's never been set.
+    async fn init_status(&mut self) -> ZookeeperReconcileResult {
+        // We'll begin by setting an empty status here because later in this method we might
+        // update its conditions. To avoid any issues we'll just create it once here.
+        if self.zk_status.is_none() {
+            let status = ZookeeperClusterStatus::default();
+            self.context
+                .client
+                .merge_patch_status(&self.context.resource, &status)
+                .await?;
+            self.zk_status = Some(status);
+        }
+
+        // This should always return either the existing one or the one we just created above.
+        let status = self.zk_status.take().unwrap_or_default();
+        let spec_version = self.zk_spec.version.clone();
+
+        match (&status.current_version, &status.target_version) {
+            (None, None) => {
+                // No current_version and no target_version must be initial installation.
+                // We'll set the Upgrading condition and the target_version to the version from spec.
+                info!(
+                    "Initial installation, now moving towards version [{}]",
+                    self.zk_spec.version
+                );
+                self.zk_status = self
+                    .set_upgrading_condition(
+                        &status.conditions,
+                        &format!("Initial installation to version [{}]", spec_version),
+                        "InitialInstallation",
+                        ConditionStatus::True,
+                    )
+                    .await?
+                    .status;
+                self.zk_status = self.set_target_version(Some(&spec_version)).await?.status;
+            }
+            (None, Some(target_version)) => {
+                // No current_version but a target_version means we're still doing the initial
+                // installation. Will continue working towards that goal even if another version
+                // was set in the meantime.
+                debug!(
+                    "Initial installation, still moving towards version [{}]",
+                    target_version
+                );
+                if &spec_version != target_version {
+                    info!("A new target version ([{}]) was requested while we still do the initial installation to [{}], finishing running upgrade first", spec_version, target_version)
+                }
+                // We do this here to update the observedGeneration if needed
+                self.zk_status = self
+                    .set_upgrading_condition(
+                        &status.conditions,
+                        &format!("Initial installation to version [{}]", target_version),
+                        "InitialInstallation",
+                        ConditionStatus::True,
+                    )
+                    .await?
+                    .status;
+            }
+            (Some(current_version), None) => {
+                // We are at a stable version but have no target_version set.
+                // This will be the normal state.
+                // We'll check if there is a different version in spec and if it is will
+                // set it in target_version, but only if it's actually a compatible upgrade.
+                if current_version != &spec_version {
+                    if current_version.is_valid_version_change(&spec_version) {
+                        self.clear_version_change_rejected(&status.conditions)
+                            .await?;
+                        let new_version = spec_version;
+                        let message =
+                            format!("Upgrading from [{}] to [{}]", current_version, &new_version);
+                        info!("{}", message);
+                        self.publish_event(EventType::Normal, "UpgradeStarted", &message)
+                            .await;
+                        self.zk_status = self.set_target_version(Some(&new_version)).await?.status;
+                        self.zk_status = self
+                            .set_upgrading_condition(
+                                &status.conditions,
+                                &message,
+                                "Upgrading",
+                                ConditionStatus::True,
+                            )
+                            .await?
+                            .status;
+                    } else {
+                        // TODO: This should be caught by an validating admission webhook
+                        self.reject_version_change(
+                            &status.conditions,
+                            current_version,
+                            &spec_version,
+                        )
+                        .await?;
+                    }
+                } else {
+                    self.clear_version_change_rejected(&status.conditions)
+                        .await?;
+                    let message = format!(
+                        "No upgrade required [{}] is still the current_version",
+                        current_version
+                    );
+                    trace!("{}", message);
+                    self.zk_status = self
+                        .set_upgrading_condition(
+                            &status.conditions,
+                            &message,
+                            "",
+                            ConditionStatus::False,
+                        )
+                        .await?
+                        .status;
+                }
+            }
+            (Some(current_version), Some(target_version)) => {
+                // current_version and target_version are set means we're still in the process
+                // of upgrading. We'll only do some logging and checks and will update
+                // the condition so observedGeneration can be updated.
+                debug!(
+                    "Still upgrading from [{}] to [{}]",
+                    current_version, target_version
+                );
+                if &self.zk_spec.version != target_version {
+                    info!("A new target version was requested while we still upgrade from [{}] to [{}], finishing running upgrade first", current_version, target_version)
+                }
+                let message = format!(
+                    "Upgrading from [{}] to [{}]",
+                    current_version, target_version
+                );
+
+                self.zk_status = self
+                    .set_upgrading_condition(
+                        &status.conditions,
+                        &message,
+                        "",
+                        ConditionStatus::False,
+                    )
+                    .await?
+                    .status;
+            }
+        }
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    // This looks at all currently existing Pods for the current ZookeeperCluster object.
+    // It checks if all the pods are valid (i.e. contain required labels) and then builds an `IdInformation`
+    // object and sets it on the current state.
+    async fn read_existing_pod_information(&mut self) -> ZookeeperReconcileResult {
+        trace!(
+            "Reading existing pod information for {}",
+            self.context.log_name()
+        );
+
+        // We first create a list of all used ids (`myid`) so we know which we can reuse
+        // At the same time we create a map of id to pod for all pods which already exist
+        // Later we fill those up.
+        // It depends on the ZooKeeper version on whether this requires a full restart of the cluster
+        // or whether this can be done dynamically.
+        // This list also includes all ids currently in use by terminating or otherwise not-ready pods.
+        // We never want to use those as long as there's a chance that some process might be actively
+        // using it.
+        // There can be a maximum of 255 (I believe) ids.
+        let mut used_ids = Vec::with_capacity(self.existing_pods.len());
+
+        // Iterate over all existing pods and read the label which contains the `myid`
+        for pod in &self.existing_pods {
+            if let Some(PodSpec {
+                node_name: Some(_), ..
+            }) = &pod.spec
+            {
+                match pod.metadata.labels.get(ID_LABEL) {
+                    None => {
+                        error!("ZookeeperCluster {}: Pod [{:?}] does not have the `id` label, this is illegal, deleting it.",
+                               self.context.log_name(),
+                               pod);
+                        self.delete_pod(pod).await?;
+                    }
+                    Some(label) => {
+                        let id = match label.parse::<usize>() {
+                            Ok(id) => id,
+                            Err(_) => {
+                                error!("ZookeeperCluster {}: Pod [{:?}] does have the `id` label but the label ([{}]) cannot be parsed, this is illegal, deleting the pod.",
+                                       self.context.log_name(), pod, label);
+                                self.delete_pod(pod).await?;
+                                continue;
+                            }
+                        };
+
+                        // Check if we have seen the same id before
+                        // This should never happen and would currently require manual cleanup
+                        if used_ids.contains(&id) {
+                            // TODO: Update status
+                            error!(
+                                "Found a duplicate `myid` [{}] in Pod [{}], we can't recover \
+                                 from this error and you need to clean up manually",
+                                id,
+                                pod.name()
+                            );
+                            return Err(Error::ReconcileError("Found duplicate id".to_string()));
+                        }
+
+                        used_ids.push(id);
+                    }
+                };
+            } else {
+                error!("ZookeeperCluster {}: Pod [{:?}] does not have any spec or labels, this is illegal, deleting it.",
+                       self.context.log_name(),
+                       pod);
+                self.delete_pod(pod).await?;
+            }
+        }
+
+        debug!(
+            "ZookeeperCluster {}: Found these myids in use [{:?}]",
+            self.context.log_name(),
+            used_ids
+        );
+
+        // the pods without a valid id were deleted above and are skipped
+        let mut id_information = IdInformation::from_pods(&self.existing_pods);
+        // read once per reconciliation, the ids of the other clusters are reserved and their
+        // servers are part of the configuration
+        self.federation_members = self.read_federation_members().await?;
+        reserve_federation_ids(
+            &self.context.resource,
+            &self.federation_members,
+            &mut id_information.used_ids,
+        );
+        self.id_information = Some(id_information);
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Returns the id of the failed server which is being replaced, its replacement keeps it.
+    fn replaced_id(&self) -> Option<usize> {
+        self.zk_status
+            .as_ref()
+            .and_then(|status| status.member_replacement.as_ref())
+            .map(|replacement| replacement.id)
+    }
+
+    /// This function looks at all the requested servers from the spec and assigns ids to those
+    /// that don't have one yet.
+    /// We do this here - and not later - because we need the id mapping information for the
+    /// ConfigMap generation later.
+    /// NOTE: This method will _not_ work if multiple servers should run on a single node
+    async fn assign_ids(&mut self) -> ZookeeperReconcileResult {
+        trace!("Assigning ids to new servers from the spec",);
+
+        let replaced_id = self.replaced_id();
+        let id_information = self.id_information.as_mut().ok_or_else(|| error::Error::ReconcileError(
+            "id_information missing, this is a programming error and should never happen. Please report in our issue tracker.".to_string(),
+        ))?;
+
+        // With a scaling policy only the nodes which get a pod in this step are assigned an id,
+        // otherwise the zoo.cfg of all servers would already contain the whole target ensemble.
+        let mut nodes_in_step = None;
+        if let Some(policy) = &self.zk_spec.scaling {
+            let mut nodes_that_need_pods = vec![];
+            for role in ZookeeperRole::iter() {
+                if let Some(eligible_nodes_for_role) = self.eligible_nodes.get(&role.to_string()) {
+                    for (role_group, (eligible_nodes, replicas)) in eligible_nodes_for_role {
+                        nodes_that_need_pods.extend(
+                            k8s_utils::find_nodes_that_need_pods(
+                                eligible_nodes,
+                                &self.existing_pods,
+                                &get_role_and_group_labels(&role.to_string(), role_group),
+                                *replicas,
+                            )
+                            .into_iter()
+                            .filter_map(|node| node.metadata.name.clone()),
+                        );
+                    }
+                }
+            }
+
+            let existing = id_information.node_name_to_pod.len();
+            self.scale_step = scaling::plan_scale_step(
+                Some(policy),
+                self.zk_status
+                    .as_ref()
+                    .and_then(|status| status.scaling.as_ref()),
+                existing,
+                nodes_that_need_pods.len(),
+                &Utc::now(),
+            );
+
+            match &self.scale_step {
+                ScaleStep::Unlimited => {}
+                ScaleStep::Step { target, started } => {
+                    if *started {
+                        info!(
+                            "ZookeeperCluster {}: Scaling up from [{}] to [{}] servers",
+                            self.context.log_name(),
+                            existing,
+                            target
+                        );
+                        self.zk_status = self
+                            .context
+                            .client
+                            .merge_patch_status(
+                                &self.context.resource,
+                                &json!({ "scaling": { "stepTarget": target } }),
+                            )
+                            .await?
+                            .status;
+                    }
+                    nodes_that_need_pods.truncate(target.saturating_sub(existing));
+                    nodes_in_step = Some(nodes_that_need_pods);
+                }
+                ScaleStep::CoolingDown(remaining) => {
+                    debug!(
+                        "ZookeeperCluster {}: Next scale step starts in [{}] seconds",
+                        self.context.log_name(),
+                        remaining.as_secs()
+                    );
+                    nodes_in_step = Some(vec![]);
+                }
+            }
+        }
+
+        for (node_name, new_id) in id_information.assign_new_ids(
+            &self.eligible_nodes,
+            nodes_in_step.as_deref(),
+            replaced_id,
+        ) {
+            info!(
+                "Assigning new id [{}] to server/node [{}]",
+                new_id, node_name
+            );
+        }
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Reads the servers of the other clusters of `spec.federation` for the `server.<id>` entries
+    /// and publishes the servers of this cluster and the connection string of the whole ensemble
+    /// in `status.federation`.
+    async fn reconcile_federation(&mut self) -> ZookeeperReconcileResult {
+        let previous = self
+            .zk_status
+            .as_ref()
+            .and_then(|status| status.federation.clone());
+        let federation = match &self.context.resource.spec.federation {
+            Some(federation) => federation.clone(),
+            None => {
+                if previous.is_some() {
+                    self.zk_status = self
+                        .context
+                        .client
+                        .merge_patch_status(&self.context.resource, &json!({ "federation": null }))
+                        .await?
+                        .status;
+                }
+                return Ok(ReconcileFunctionAction::Continue);
+            }
+        };
+        let node_addresses = self.node_addresses();
+        let observers = observer_node_names(
+            &node_role_groups(&self.existing_pods, &self.eligible_nodes),
+            &self.validated_role_config,
+        );
+        let ports = self.context.resource.spec.ports.clone().unwrap_or_default();
+        let mut servers = self
+            .existing_pods
+            .iter()
+            .filter_map(|pod| {
+                let node_name = pod_node_name(pod).ok()?;
+                Some(FederationMember {
+                    id: pod_id(pod)?,
+                    address: node_addresses
+                        .get(node_name)
+                        .cloned()
+                        .unwrap_or_else(|| node_name.to_string()),
+                    client_port: Some(client_port(pod)),
+                    quorum_port: Some(ports.quorum()),
+                    leader_election_port: Some(ports.leader_election()),
+                    observer: observers.contains(node_name),
+                })
+            })
+            .collect::<Vec<_>>();
+        servers.sort_by_key(|server| server.id);
+        let conflicting_ids = conflicting_federation_ids(&servers, &self.federation_members);
+        let connection_string = servers
+            .iter()
+            .chain(
+                self.federation_members
+                    .iter()
+                    .filter(|member| servers.iter().all(|server| server.id != member.id)),
+            )
+            .map(FederationMember::client_address)
+            .collect::<Vec<_>>()
+            .join(",");
+        let status = FederationStatus {
+            servers,
+            connection_string: Some(connection_string).filter(|s| !s.is_empty()),
+        };
+        if previous.as_ref() != Some(&status) {
+            self.zk_status = self
+                .context
+                .client
+                .merge_patch_status(&self.context.resource, &json!({ "federation": status }))
+                .await?
+                .status;
+        }
+
+        // the servers of this cluster would silently replace the servers of the other clusters
+        // with the same ids in `zoo.cfg`, so the configuration is not changed until the ids are
+        // unique again
+        let conditions = self
+            .zk_status
+            .as_ref()
+            .map(|status| status.conditions.clone())
+            .unwrap_or_default();
+        if !conflicting_ids.is_empty() {
+            let message = format!(
+                "The servers with the ids {:?} are also servers of other clusters of the federation, give the clusters distinct idOffsets and replace the servers of one of them",
+                conflicting_ids
+            );
+            warn!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+            if !is_condition_true(&conditions, FEDERATION_ID_CONFLICT_CONDITION) {
+                self.publish_event(EventType::Warning, "FederationIdConflict", &message)
+                    .await;
+            }
+            self.zk_status = self
+                .set_condition(
+                    &conditions,
+                    FEDERATION_ID_CONFLICT_CONDITION,
+                    &message,
+                    "IdConflict",
+                    ConditionStatus::True,
+                )
+                .await?
+                .status;
+            return Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(
+                FEDERATION_REFRESH_INTERVAL_SECONDS,
+            )));
+        }
+        if is_condition_true(&conditions, FEDERATION_ID_CONFLICT_CONDITION) {
+            self.zk_status = self
+                .set_condition(
+                    &conditions,
+                    FEDERATION_ID_CONFLICT_CONDITION,
+                    "The ids of the servers are unique across the federation",
+                    "IdsUnique",
+                    ConditionStatus::False,
+                )
+                .await?
+                .status;
+        }
+
+        // changes of the referenced clusters do not trigger a reconciliation
+        if !federation.cluster_refs.is_empty() {
+            self.schedule_requeue(Duration::from_secs(FEDERATION_REFRESH_INTERVAL_SECONDS));
+        }
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Returns the servers of the other clusters of `spec.federation` ordered by id: the `members`
+    /// and the servers the clusters of the `clusterRefs` publish in their status. Clusters
+    /// which do not exist (yet) are skipped.
+    async fn read_federation_members(&self) -> Result<Vec<FederationMember>, Error> {
+        let federation = match &self.context.resource.spec.federation {
+            Some(federation) => federation,
+            None => return Ok(vec![]),
+        };
+        let mut members = federation.members.clone();
+        for cluster_ref in &federation.cluster_refs {
+            let namespace = cluster_ref
+                .namespace
+                .clone()
+                .unwrap_or_else(|| self.context.namespace());
+            let clusters_api: Api<ZookeeperCluster> =
+                self.context.client.get_namespaced_api(&namespace);
+            match clusters_api.get(&cluster_ref.name).await {
+                Ok(cluster) => members.extend(
+                    cluster
+                        .status
+                        .and_then(|status| status.federation)
+                        .map(|federation| federation.servers)
+                        .unwrap_or_default(),
+                ),
+                Err(kube::Error::Api(response)) if response.code == 404 => {
+                    warn!(
+                        "ZookeeperCluster {}: The federated cluster [{}/{}] does not exist",
+                        self.context.log_name(),
+                        namespace,
+                        cluster_ref.name
+                    );
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        members.sort_by_key(|member| member.id);
+        Ok(members)
+    }
+
+    /// Deletes the data and id ConfigMaps which are not used anymore, see [`garbage_collection`].
+    /// ConfigMaps which are not managed by the operator are left alone.
+    #[instrument(skip(self))]
+    async fn delete_obsolete_config_maps(&self) -> ZookeeperReconcileResult {
+        if !self.managed_resources.config_maps {
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+
+        let config_maps = self.list_owned_config_maps().await?;
+        let version = self.desired_version().to_string();
+        let desired = garbage_collection::DesiredConfigMaps {
+            version: &version,
+            role_groups: self
+                .eligible_nodes
+                .iter()
+                .flat_map(|(role, groups)| {
+                    groups
+                        .keys()
+                        .map(move |group| (role.clone(), group.clone()))
+                })
+                .collect(),
+            ids: self
+                .id_information
+                .iter()
+                .flat_map(|id_information| id_information.node_name_to_id.values().copied())
+                .collect(),
+            data_names: self
+                .data_config_maps
+                .values()
+                .map(ResourceExt::name)
+                .collect(),
+        };
+
+        for config_map in garbage_collection::find_obsolete_config_maps(
+            &config_maps,
+            &self.existing_pods,
+            &desired,
+        ) {
+            info!(
+                "ZookeeperCluster {}: Deleting obsolete ConfigMap [{}]",
+                self.context.log_name(),
+                config_map.name()
+            );
+            self.context.client.delete(config_map).await?;
+            self.cache
+                .config_maps
+                .expect_deleted(&self.context.resource, config_map);
+        }
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    pub async fn create_missing_pods(&mut self) -> ZookeeperReconcileResult {
+        trace!("Starting `create_missing_pods`");
+
+        let id_information = self.id_information.as_mut().ok_or_else(|| error::Error::ReconcileError(
+            "id_information missing, this is a programming error and should never happen. Please report in our issue tracker.".to_string(),
+        ))?;
+
+        // The iteration happens in two stages here, to accommodate the way our operators think
+        // about roles and role groups.
+        // The hierarchy is:
+        // - Roles (for ZooKeeper there - currently - is only a single role)
+        //   - Role groups for this role (user defined)
+        for zookeeper_role in ZookeeperRole::iter() {
+            if let Some(nodes_for_role) = self.eligible_nodes.get(&zookeeper_role.to_string()) {
+                for (role_group, (nodes, replicas)) in nodes_for_role {
+                    debug!(
+                        "Identify missing pods for [{}] role and group [{}]",
+                        zookeeper_role, role_group
+                    );
+                    trace!(
+                        "candidate_nodes[{}]: [{:?}]",
+                        nodes.len(),
+                        nodes
+                            .iter()
+                            .map(|node| node.metadata.name.as_ref().unwrap())
+                            .collect::<Vec<_>>()
+                    );
+                    trace!(
+                        "existing_pods[{}]: [{:?}]",
+                        &self.existing_pods.len(),
+                        &self
+                            .existing_pods
+                            .iter()
+                            .map(|pod| pod.metadata.name.as_ref().unwrap())
+                            .collect::<Vec<_>>()
+                    );
+                    trace!(
+                        "labels: [{:?}]",
+                        get_role_and_group_labels(&zookeeper_role.to_string(), role_group)
+                    );
+                    let nodes_that_need_pods = k8s_utils::find_nodes_that_need_pods(
+                        nodes,
+                        &self.existing_pods,
+                        &get_role_and_group_labels(&zookeeper_role.to_string(), role_group),
+                        *replicas,
+                    );
+
+                    for node in nodes_that_need_pods {
+                        let node_name = if let Some(node_name) = &node.metadata.name {
+                            node_name
+                        } else {
+                            warn!("No name found in metadata, this should not happen! Skipping node: [{:?}]", node);
+                            continue;
+                        };
+                        debug!(
+                            "Creating pod on node [{}] for [{}] role and group [{}]",
+                            node.metadata
+                                .name
+                                .as_deref()
+                                .unwrap_or("<no node name found>"),
+                            zookeeper_role,
+                            role_group
+                        );
+
+                        if id_information.node_name_to_pod.get(node_name).is_none() {
+                            if self.scale_step != ScaleStep::Unlimited
+                                && id_information.node_name_to_id.get(node_name).is_none()
+                            {
+                                debug!(
+                                    "Pod for server [{}] is deferred to a later scale step",
+                                    node_name
+                                );
+                                continue;
+                            }
+                            info!("Pod for server [{}] missing, creating now...", node_name);
+
+                            let id = *id_information.node_name_to_id.get(node_name).ok_or_else(
+                                || {
+                                    Error::ReconcileError(format!(
+                                        "We didn't find a `myid` for [{}] but it \
+                                        should have been assigned, this is a bug, please report it",
+                                        node_name
+                                    ))
+                                },
+                            )?;
+
+                            // now we have a node that needs a pod -> get validated config
+                            let validated_config = config_for_role_and_group(
+                                &zookeeper_role.to_string(),
+                                role_group,
+                                &self.validated_role_config,
+                            )?;
+
+                            let config_maps = self
+                                .create_config_maps(&zookeeper_role.to_string(), role_group, id)
+                                .await?;
+
+                            let pod = self
+                                .create_pod(
+                                    &zookeeper_role.to_string(),
+                                    role_group,
+                                    node_name,
+                                    &node.metadata.labels,
+                                    id,
+                                    &config_maps,
+                                    validated_config,
+                                )
+                                .await?;
+                            self.publish_event(
+                                EventType::Normal,
+                                "PodCreated",
+                                &format!(
+                                    "Created pod [{}] for server [{}] on node [{}]",
+                                    pod.name(),
+                                    id,
+                                    node_name
+                                ),
+                            )
+                            .await;
+
+                            return Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10)));
+                        } else {
+                            debug!(
+                                "Pod for server [{}] already created, skipping ...",
+                                node_name
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Creates the config maps required for a zookeeper instance (or role, role_group combination):
+    /// * The 'zoo.cfg' properties file
+    /// * The 'myid' file
+    ///
+    /// The data ConfigMap of the role group was built by `build_data_config_maps`, the id
+    /// ConfigMap is built by [`resources::build_id_config_map`].
+    ///
+    /// Returns a map with a 'type' identifier (e.g. data, id) as key and the corresponding
+    /// ConfigMap as value. This is required to set the volume mounts in the pod later on.
+    ///
+    /// # Arguments
+    ///
+    /// - `role` - The Zookeeper role.
+    /// - `group` - The role group.
+    /// - `id` - The 'myid' for this instance.
+    ///
+    #[instrument(skip(self))]
+    async fn create_config_maps(
+        &self,
+        role: &str,
+        group: &str,
+        id: usize,
+    ) -> Result<BTreeMap<&'static str, ConfigMap>, Error> {
+        let mut config_maps = BTreeMap::new();
+
+        if let Some(cm_data) = self
+            .data_config_maps
+            .get(&(role.to_string(), group.to_string()))
+        {
+            config_maps.insert(
+                CONFIG_MAP_TYPE_DATA,
+                self.apply_config_map(CONFIG_MAP_TYPE_DATA, cm_data.clone())
+                    .await?,
+            );
+        }
+
+        let cm_id = resources::build_id_config_map(
+            &self.context.resource,
+            &self.desired_version(),
+            role,
+            group,
+            id,
+        )?;
+
+        config_maps.insert(
+            CONFIG_MAP_TYPE_ID,
+            self.apply_config_map(CONFIG_MAP_TYPE_ID, cm_id).await?,
+        );
+
+        Ok(config_maps)
+    }
+
+    /// Builds the data ConfigMap containing the 'zoo.cfg' properties file of every role group (see
+    /// [`resources::build_data_config_map`]) for the servers with the ids in `id_information`.
+    ///
+    /// ConfigMaps managed by the operator are named after their configuration, see
+    /// [`resources::version_data_config_map`].
+    fn desired_data_config_maps(
+        &self,
+        id_information: &IdInformation,
+        federation_members: &[FederationMember],
+    ) -> Result<BTreeMap<(String, String), ConfigMap>, Error> {
+        let version = self.desired_version();
+        let node_addresses = self.node_addresses();
+        let role_groups = node_role_groups(&self.existing_pods, &self.eligible_nodes);
+        let observers = observer_node_names(&role_groups, &self.validated_role_config);
+        let mut data_config_maps = BTreeMap::new();
+        for zookeeper_role in ZookeeperRole::iter() {
+            let role = zookeeper_role.to_string();
+            for role_group in self
+                .eligible_nodes
+                .get(&role)
+                .into_iter()
+                .flat_map(|groups| groups.keys())
+            {
+                let validated_config =
+                    config_for_role_and_group(&role, role_group, &self.validated_role_config)?;
+                let mut config_map = match resources::build_data_config_map(
+                    &self.context.resource,
+                    &version,
+                    &role,
+                    role_group,
+                    validated_config,
+                    &id_information.node_name_to_id,
+                    &node_addresses,
+                    &observers,
+                    federation_members,
+                    &role_groups,
+                )? {
+                    Some(config_map) => config_map,
+                    None => continue,
+                };
+                if self.managed_resources.config_maps {
+                    resources::version_data_config_map(&mut config_map);
+                }
+                data_config_maps.insert((role.clone(), role_group.clone()), config_map);
+            }
+        }
+        Ok(data_config_maps)
+    }
+
+    /// Builds the data ConfigMap of every role group once per reconciliation, after the ids were
+    /// assigned and the servers of the federation were read, see `desired_data_config_maps`.
+    ///
+    /// ConfigMaps managed by the operator are only applied when a pod is created with them. So
+    /// the running pods keep the configuration they were started with, even when their
+    /// containers restart, until the rollout replaces them. ConfigMaps which are not managed by
+    /// the operator are read instead.
+    async fn build_data_config_maps(&mut self) -> ZookeeperReconcileResult {
+        let id_information = self.id_information.as_ref().ok_or_else(|| error::Error::ReconcileError(
+            "id_information missing, this is a programming error and should never happen. Please report in our issue tracker.".to_string(),
+        ))?;
+        let mut data_config_maps =
+            self.desired_data_config_maps(id_information, &self.federation_members)?;
+        if !self.managed_resources.config_maps {
+            for config_map in data_config_maps.values_mut() {
+                *config_map = self
+                    .apply_config_map(CONFIG_MAP_TYPE_DATA, config_map.clone())
+                    .await?;
+            }
+        }
+        self.data_config_maps = data_config_maps;
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Returns the hash of the data ConfigMap of every role group, see [`config_hashes`].
+    fn desired_config_hashes(&self) -> Result<BTreeMap<(String, String), String>, Error> {
+        config_hashes(&self.data_config_maps)
+    }
+
+    /// Returns the addresses of the servers by node name: the `InternalIP` of the nodes on the
+    /// host network, none otherwise (the servers are addressed by the node name).
+    fn node_addresses(&self) -> BTreeMap<String, String> {
+        if self.context.resource.spec.host_network {
+            host_network::node_addresses(
+                &self.eligible_nodes,
+                &self.context.resource.spec.ip_families,
+            )
+        } else {
+            BTreeMap::new()
+        }
+    }
+
+    /// Creates or updates the given ConfigMap if ConfigMaps are managed by the operator.
+    /// Otherwise the ConfigMap is expected to have been provided (under the same name) by
+    /// whoever manages them and it is only read.
+    #[instrument(skip(self, config_map))]
+    async fn apply_config_map(
+        &self,
+        cm_type: &'static str,
+        config_map: ConfigMap,
+    ) -> Result<ConfigMap, Error> {
+        if self.managed_resources.config_maps {
+            return Ok(configmap::create_config_map(&self.context.client, config_map).await?);
+        }
+
+        let name = config_map
+            .metadata
+            .name
+            .as_deref()
+            .ok_or(error::Error::MissingConfigMapNameError { cm_type })?;
+        debug!(
+            "ConfigMaps are not managed by the operator, using existing ConfigMap [{}]",
+            name
+        );
+        Ok(self
+            .context
+            .client
+            .get::<ConfigMap>(name, Some(&self.context.namespace()))
+            .await?)
+    }
+
+    /// Creates the pod required for the zookeeper instance.
+    ///
+    /// # Arguments
+    ///
+    /// - `role` - The Zookeeper role.
+    /// - `group` - The role group.
+    /// - `node_name` - The node_name for this pod.
+    /// - `node_labels` - The labels of the node, e.g. its architecture and rack.
+    /// - `id` - The 'myid' for this instance.
+    /// - `config_maps` - The config maps and respective types required for this pod.
+    /// - `validated_config` - The validated product config.
+    ///
+    #[instrument(skip(self, node_labels, config_maps, validated_config))]
+    async fn create_pod(
+        &self,
+        role: &str,
+        group: &str,
+        node_name: &str,
+        node_labels: &BTreeMap<String, String>,
+        id: usize,
+        config_maps: &BTreeMap<&'static str, ConfigMap>,
+        validated_config: &HashMap<PropertyNameKind, BTreeMap<String, String>>,
+    ) -> Result<Pod, Error> {
+        let pod = self.build_pod(
+            role,
+            group,
+            node_name,
+            node_labels,
+            id,
+            config_maps,
+            validated_config,
+        )?;
+
+        let pod = self.context.client.create(&pod).await?;
+        self.cache.pods.expect_created(&self.context.resource, &pod);
+        Ok(pod)
+    }
+
+    /// Builds the pod of a server with the values of this reconciliation, see
+    /// [`resources::build_pod`].
+    fn build_pod(
+        &self,
+        role: &str,
+        group: &str,
+        node_name: &str,
+        node_labels: &BTreeMap<String, String>,
+        id: usize,
+        config_maps: &BTreeMap<&'static str, ConfigMap>,
+        validated_config: &HashMap<PropertyNameKind, BTreeMap<String, String>>,
+    ) -> Result<Pod, Error> {
+        let version = self.desired_version();
+        let image = self.image_resolver.resolve(
+            &version,
+            node_labels.get(scheduling::ARCH_LABEL).map(String::as_str),
+            self.context.resource.spec.image_variant.as_deref(),
+        );
+        let restore_secret = self.restore_secret();
+        resources::build_pod(
+            &self.context.resource,
+            &resources::PodParameters {
+                role,
+                group,
+                node_name,
+                node_labels,
+                id,
+                version: &version,
+                image,
+                config_maps,
+                validated_config,
+                image_architectures: &self.image_resolver.architectures(),
+                certificate_revision: self.certificate_revision,
+                references_hash: self.references_hash.as_deref(),
+                member_replacement: self
+                    .zk_status
+                    .as_ref()
+                    .and_then(|status| status.member_replacement.as_ref()),
+                restore_secret: restore_secret.as_deref(),
+            },
+        )
+    }
+
+    /// Deletes the pod and records the deletion in the [`cache`], so later reconciliations do not
+    /// see it anymore.
+    async fn delete_pod(&self, pod: &Pod) -> Result<(), Error> {
+        self.context.client.delete(pod).await?;
+        self.cache.pods.expect_deleted(&self.context.resource, pod);
+        Ok(())
+    }
+
+    /// Returns the quorum recorded in the status by the last reconciliation.
+    fn recorded_quorum(&self) -> Option<QuorumStatus> {
+        self.zk_status
+            .as_ref()
+            .and_then(|status| status.quorum.clone())
+    }
+
+    /// Orders `pods` for restarting them with the leader last, which is queried from the servers
+    /// right now, see [`restart_order`].
+    async fn restart_order<'a>(&self, pods: Vec<&'a Pod>) -> RestartOrder<'a> {
+        if pods.is_empty() {
+            return RestartOrder::new(pods, None);
+        }
+        let servers = self.check_servers().await;
+        let quorum = quorum::build_status(&servers, &Utc::now().to_rfc3339());
+        RestartOrder::new(pods, Some(quorum))
+    }
+
+    /// Records `step` in `status.lastRestartStep` and returns its description for the Event.
+    async fn record_restart_step(&self, step: Option<RestartStepStatus>) -> Result<String, Error> {
+        let step = match step {
+            Some(step) => step,
+            None => return Ok(String::new()),
+        };
+        let mut patch = json!({ "lastRestartStep": step });
+        // the leader and the remaining pods are not serialized if there are none, but must not be
+        // kept from the previous step by the merge patch
+        patch["lastRestartStep"]["leader"] = json!(step.leader);
+        patch["lastRestartStep"]["remaining"] = json!(step.remaining);
+        self.context
+            .client
+            .merge_patch_status(&self.context.resource, &patch)
+            .await?;
+        Ok(restart_order::describe_step(&step))
+    }
+
+    /// Restarts the next of the `outdated_pods` (the leader last, see [`restart_order`]) if the
+    /// disruption is allowed and requeues, so the pods are restarted one at a time. The restart
+    /// step is recorded with `kind` as its reason and `cause` completes the Event, e.g. "as
+    /// requested". Without outdated pods the reconciliation continues.
+    async fn restart_next_pod(
+        &mut self,
+        outdated_pods: Vec<Pod>,
+        kind: &str,
+        cause: &str,
+    ) -> ZookeeperReconcileResult {
+        let order = self.restart_order(outdated_pods.iter().collect()).await;
+        let pod = match order.next() {
+            Some(pod) => pod,
+            None => return Ok(ReconcileFunctionAction::Continue),
+        };
+        if let Some((reason, message)) = self.check_disruption(pod, "restart").await? {
+            return Ok(waiting::wait(
+                &mut self.waiting,
+                previous_waiting(&self.zk_status),
+                reason,
+                message,
+            ));
+        }
+        info!(
+            "ZookeeperCluster {}: Restarting pod [{}] {}",
+            self.context.log_name(),
+            pod.name(),
+            cause
+        );
+        self.delete_pod(pod).await?;
+        let step = self
+            .record_restart_step(order.next_step(kind, &Utc::now().to_rfc3339()))
+            .await?;
+        self.publish_event(
+            EventType::Normal,
+            "RestartingPod",
+            &format!("Restarting pod [{}] {} ({})", pod.name(), cause, step),
+        )
+        .await;
+        Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10)))
+    }
+
+    /// Returns the ConfigMaps owned by the cluster, from the [`cache`] if it is up to date.
+    async fn list_owned_config_maps(&self) -> Result<Vec<ConfigMap>, Error> {
+        let labels = build_common_labels_for_all_managed_resources(APP_NAME, &self.context.name());
+        match self
+            .cache
+            .config_maps
+            .list_owned(&self.context.resource, &labels)
+        {
+            Some(config_maps) => Ok(config_maps),
+            None => Ok(self.context.list_owned(labels).await?),
+        }
+    }
+
+    /// Validates `spec.schedulerName` and publishes a warning for every pod the custom scheduler
+    /// did not schedule within [`scheduling::SCHEDULING_TIMEOUT_MINUTES`].
+    async fn check_pod_scheduling(&self) -> ZookeeperReconcileResult {
+        let scheduler_name = match &self.context.resource.spec.scheduler_name {
+            Some(scheduler_name) => scheduler_name,
+            None => return Ok(ReconcileFunctionAction::Continue),
+        };
+        scheduling::validate_scheduler_name(scheduler_name)?;
+
+        let now = Utc::now();
+        for pod in &self.existing_pods {
+            if !scheduling::is_scheduling_timed_out(pod, &now) {
+                continue;
+            }
+
+            warn!(
+                "ZookeeperCluster {}: Pod [{}] was not scheduled by [{}] within [{}] minutes",
+                self.context.log_name(),
+                pod.name(),
+                scheduler_name,
+                scheduling::SCHEDULING_TIMEOUT_MINUTES
+            );
+            self.publish_event(
+                EventType::Warning,
+                "SchedulingTimeout",
+                &format!(
+                    "Pod [{}] was not scheduled by scheduler [{}] within [{}] minutes, check that the scheduler is running",
+                    pod.name(),
+                    scheduler_name,
+                    scheduling::SCHEDULING_TIMEOUT_MINUTES
+                ),
+            )
+            .await;
+
+            let pods_api: Api<Pod> = self
+                .context
+                .client
+                .get_namespaced_api(&self.context.namespace());
+            pods_api
+                .patch(
+                    &pod.name(),
+                    &apply::merge_params(),
+                    &Patch::Merge(json!({
+                        "metadata": {
+                            "annotations": {
+                                (scheduling::SCHEDULING_TIMEOUT_REPORTED_ANNOTATION): "true"
+                            }
+                        }
+                    })),
+                )
+                .await?;
+        }
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Replaces the server which was not ready for longer than
+    /// `spec.memberReplacement.notReadySeconds`, see [`member_replacement`].
+    ///
+    /// The failed pod is force deleted because the kubelet of a lost node never confirms the
+    /// deletion. The replacement is created by `create_missing_pods` with the same `myid`,
+    /// recorded in `status.memberReplacement` until it is ready or its deadline passed. Only one
+    /// server is replaced at a time and only while the others form a quorum. With the policy
+    /// `Never` the failure is only reported, once until the server is ready again.
+    #[instrument(skip(self))]
+    async fn replace_failed_members(&mut self) -> ZookeeperReconcileResult {
+        let pods_api: Api<Pod> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+        // a server which is ready again is reported when it fails the next time
+        for pod in self.existing_pods.iter().filter(|pod| {
+            is_pod_condition_true(pod, "Ready")
+                && pod
+                    .annotations()
+                    .contains_key(member_replacement::FAILURE_REPORTED_ANNOTATION)
+        }) {
+            pods_api
+                .patch(
+                    &pod.name(),
+                    &apply::merge_params(),
+                    &Patch::Merge(json!({
+                        "metadata": {
+                            "annotations": {
+                                (member_replacement::FAILURE_REPORTED_ANNOTATION): null
+                            }
+                        }
+                    })),
+                )
+                .await?;
+        }
+
+        let now = Utc::now();
+        if let Some(replacement) = self
+            .zk_status
+            .as_ref()
+            .and_then(|status| status.member_replacement.clone())
+        {
+            let replaced = self.existing_pods.iter().any(|pod| {
+                pod_id(pod) == Some(replacement.id) && is_pod_condition_true(pod, "Ready")
+            });
+            if replaced {
+                info!(
+                    "ZookeeperCluster {}: Server [{}] was replaced",
+                    self.context.log_name(),
+                    replacement.id
+                );
+                self.publish_event(
+                    EventType::Normal,
+                    "MemberReplaced",
+                    &format!(
+                        "The replacement of server [{}] (pod [{}]) is ready",
+                        replacement.id, replacement.pod
+                    ),
+                )
+                .await;
+            } else if member_replacement::is_overdue(&replacement, &now) {
+                let message = format!(
+                    "The replacement of server [{}] (pod [{}]) was not ready by [{}], giving it up",
+                    replacement.id,
+                    replacement.pod,
+                    replacement.deadline.as_deref().unwrap_or_default()
+                );
+                warn!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+                self.publish_event(EventType::Warning, "MemberReplacementExpired", &message)
+                    .await;
+            } else {
+                return Ok(ReconcileFunctionAction::Continue);
+            }
+            self.zk_status = self
+                .context
+                .client
+                .merge_patch_status(
+                    &self.context.resource,
+                    &json!({ "memberReplacement": null }),
+                )
+                .await?
+                .status;
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+
+        let member_replacement = self
+            .context
+            .resource
+            .spec
+            .member_replacement
+            .clone()
+            .unwrap_or_default();
+        let failed = match member_replacement::find_failed_pod(
+            &self.existing_pods,
+            ChronoDuration::seconds(i64::from(member_replacement.not_ready_seconds())),
+            &now,
+        ) {
+            Some(failed) => failed.clone(),
+            None => return Ok(ReconcileFunctionAction::Continue),
+        };
+        let id = pod_id(&failed).ok_or_else(|| {
+            Error::ReconcileError(format!("Pod [{}] has no valid id label", failed.name()))
+        })?;
+        let message = format!(
+            "Server [{}] in pod [{}] was not ready for more than [{}] seconds",
+            id,
+            failed.name(),
+            member_replacement.not_ready_seconds()
+        );
+
+        if member_replacement.policy == MemberReplacementPolicy::Never {
+            if !failed
+                .annotations()
+                .contains_key(member_replacement::FAILURE_REPORTED_ANNOTATION)
+            {
+                warn!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+                self.publish_event(EventType::Warning, "MemberFailed", &message)
+                    .await;
+                pods_api
+                    .patch(
+                        &failed.name(),
+                        &apply::merge_params(),
+                        &Patch::Merge(json!({
+                            "metadata": {
+                                "annotations": {
+                                    (member_replacement::FAILURE_REPORTED_ANNOTATION): "true"
+                                }
+                            }
+                        })),
+                    )
+                    .await?;
+            }
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+        if !member_replacement::has_quorum_without(&self.existing_pods, &failed) {
+            warn!(
+                "ZookeeperCluster {}: {}, but it is not replaced because the other servers do not form a quorum",
+                self.context.log_name(),
+                message
+            );
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+
+        let status = MemberReplacementStatus {
+            pod: failed.name(),
+            id,
+            node: pod_node_name(&failed).unwrap_or_default().to_string(),
+            wipe_data: member_replacement.policy == MemberReplacementPolicy::ReplacePodAndData,
+            started_at: now.to_rfc3339(),
+            deadline: Some(
+                (now + ChronoDuration::seconds(i64::from(member_replacement.not_ready_seconds())))
+                    .to_rfc3339(),
+            ),
+        };
+        // the id is reserved before the pod is gone
+        self.zk_status = self
+            .context
+            .client
+            .merge_patch_status(
+                &self.context.resource,
+                &json!({ "memberReplacement": status }),
+            )
+            .await?
+            .status;
+        warn!(
+            "ZookeeperCluster {}: {}, replacing it",
+            self.context.log_name(),
+            message
+        );
+        match pods_api
+            .delete(
+                &failed.name(),
+                &DeleteParams {
+                    grace_period_seconds: Some(0),
+                    ..DeleteParams::default()
+                },
+            )
+            .await
+        {
+            Ok(_) => {}
+            Err(kube::Error::Api(response)) if response.code == 404 => {}
+            Err(err) => return Err(err.into()),
+        }
+        self.publish_event(
+            EventType::Warning,
+            "ReplacingMember",
+            &format!(
+                "{}, deleted it{}",
+                message,
+                if status.wipe_data {
+                    ", the replacement starts with an empty data directory"
+                } else {
+                    ""
+                }
+            ),
+        )
+        .await;
+
+        Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10)))
+    }
+
+    /// Sets the [`SYNCED_CONDITION`] on all pods whose server has finished synchronizing its data
+    /// with the leader.
+    ///
+    /// Pods are created with a readiness gate on this condition. This way a new member only
+    /// becomes ready (and gets client traffic routed to it) once it actually serves requests and
+    /// not as soon as the process is running, which matters for ensembles with a large dataset.
+    async fn update_synced_conditions(&self) -> ZookeeperReconcileResult {
+        for pod in &self.existing_pods {
+            if !has_readiness_gate(pod, SYNCED_CONDITION)
+                || is_pod_condition_true(pod, SYNCED_CONDITION)
+            {
+                continue;
+            }
+
+            match is_server_serving(pod).await {
+                Ok(true) => {
+                    info!(
+                        "ZookeeperCluster {}: Server in pod [{}] is synced with the ensemble, marking it as ready",
+                        self.context.log_name(),
+                        pod.name()
+                    );
+                    let pods_api: Api<Pod> = self
+                        .context
+                        .client
+                        .get_namespaced_api(&self.context.namespace());
+                    pods_api
+                        .patch_status(
+                            &pod.name(),
+                            &apply::merge_params(),
+                            &Patch::Strategic(json!({
+                                "status": {
+                                    "conditions": [{
+                                        "type": SYNCED_CONDITION,
+                                        "status": "True"
+                                    }]
+                                }
+                            })),
+                        )
+                        .await?;
+                }
+                Ok(false) => debug!(
+                    "ZookeeperCluster {}: Server in pod [{}] is not serving requests yet",
+                    self.context.log_name(),
+                    pod.name()
+                ),
+                Err(err) => debug!(
+                    ?err,
+                    "ZookeeperCluster {}: Could not check sync state of pod [{}]",
+                    self.context.log_name(),
+                    pod.name()
+                ),
+            }
+        }
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Removes pods which are no longer required by the spec (e.g. because `replicas` was
+    /// decreased or a node does not match the selector anymore).
+    ///
+    /// In contrast to the generic implementation in the operator framework we only delete a
+    /// single pod per reconcile run, starting with the highest `myid`, and requeue afterwards.
+    /// This way the ensemble shrinks one member at a time and the remaining servers can keep
+    /// their quorum.
+    #[instrument(skip(self))]
+    async fn delete_excess_pods(&mut self) -> ZookeeperReconcileResult {
+        if let Some(pod) = excess_pods(&self.eligible_nodes, &self.existing_pods).first() {
+            if let Some((reason, message)) = self.check_disruption(pod, "delete excess").await? {
+                return Ok(waiting::wait(
+                    &mut self.waiting,
+                    previous_waiting(&self.zk_status),
+                    reason,
+                    message,
+                ));
+            }
+            info!(
+                "ZookeeperCluster {}: Scaling down, deleting excess pod [{}]",
+                self.context.log_name(),
+                pod.name()
+            );
+            self.delete_pod(pod).await?;
+            self.publish_event(
+                EventType::Normal,
+                "ScalingDown",
+                &format!("Deleted excess pod [{}]", pod.name()),
+            )
+            .await;
+            return Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10)));
+        }
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Finds the pods running with an outdated configuration or with outdated Secrets and
+    /// ConfigMaps, records them in `status.rollout` and decides according to
+    /// `spec.updateStrategy` whether they are restarted, see [`rollout`].
+    async fn plan_rollout(&mut self) -> ZookeeperReconcileResult {
+        let desired_hashes = rollout::DesiredHashes {
+            config: self.desired_config_hashes()?,
+            references: self.references_hash.clone(),
+        };
+
+        let pods = RestartOrder::new(
+            self.existing_pods
+                .iter()
+                .filter(|pod| desired_hashes.is_outdated(pod))
+                .collect(),
+            self.recorded_quorum(),
+        )
+        .names();
+        if pods.is_empty() {
+            self.rollout = None;
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+
+        let revision = desired_hashes.revision();
+        let approved = rollout::is_approved(&self.context.resource, &revision);
+        let planned = self
+            .rollout
+            .as_ref()
+            .map(|rollout| rollout.revision.as_str())
+            == Some(revision.as_str());
+        if !approved && !planned && self.zk_spec.update_strategy == UpdateStrategy::ManualApproval {
+            let message = format!(
+                "{} pods run with an outdated configuration, approve restarting them with the annotation {}={}",
+                pods.len(),
+                rollout::ROLLOUT_APPROVED_ANNOTATION,
+                revision
+            );
+            info!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+            self.publish_event(EventType::Normal, "RolloutApprovalRequired", &message)
+                .await;
+        }
+        self.rollout = Some(RolloutStatus {
+            revision,
+            pods,
+            approved,
+        });
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Returns whether `spec.updateStrategy` holds back the restarts of the outdated pods.
+    fn rollout_held(&self) -> bool {
+        self.rollout
+            .as_ref()
+            .map(|rollout| !rollout.approved)
+            .unwrap_or(false)
+    }
+
+    /// Restarts pods which are running with an outdated configuration, i.e. the hash of the
+    /// 'zoo.cfg' they were started with differs from the currently rendered one.
+    ///
+    /// A single outdated pod is deleted (it will be recreated with the data ConfigMap of the new
+    /// configuration by `create_missing_pods`) and we requeue, the leader after all other
+    /// outdated pods (see [`restart_order`]). Because `wait_for_running_and_ready_pods` runs
+    /// before any of this, the next pod is only restarted once the previous one is back up and
+    /// has rejoined the ensemble.
+    #[instrument(skip(self))]
+    async fn restart_pods_with_outdated_config(&mut self) -> ZookeeperReconcileResult {
+        if self.rollout_held() {
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+        let desired_hashes = rollout::DesiredHashes {
+            config: self.desired_config_hashes()?,
+            references: None,
+        };
+
+        // the outdated pods of all role groups are ordered together, so the leader is restarted
+        // after the followers and observers of every role group
+        let outdated_pods = self
+            .existing_pods
+            .iter()
+            .filter(|pod| desired_hashes.is_outdated(pod))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        self.restart_next_pod(
+            outdated_pods,
+            "configuration",
+            "to apply the changed configuration",
+        )
+        .await
+    }
+
+    /// Lets cert-manager issue the certificate of the servers if `spec.tls.issuerRef` is set:
+    /// creates the Secret with the store password (once) and applies the Certificate. The
+    /// reconciliation waits until the certificate was issued because the servers can not start
+    /// without it.
+    #[cfg(feature = "cert-manager")]
+    async fn reconcile_certificate(&mut self) -> ZookeeperReconcileResult {
+        let tls_config = match &self.zk_spec.tls {
+            Some(tls_config) => tls_config,
+            None => return Ok(ReconcileFunctionAction::Continue),
+        };
+        let issuer_ref = match &tls_config.issuer_ref {
+            Some(issuer_ref) => issuer_ref,
+            None => return Ok(ReconcileFunctionAction::Continue),
+        };
+
+        let secrets_api: Api<k8s_openapi::api::core::v1::Secret> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+        match secrets_api.get(&tls_config.password_secret_name()).await {
+            Ok(_) => {}
+            Err(kube::Error::Api(response)) if response.code == 404 => {
+                info!(
+                    "ZookeeperCluster {}: Creating the Secret [{}] with the store password",
+                    self.context.log_name(),
+                    tls_config.password_secret_name()
+                );
+                let secret =
+                    cert_manager::build_password_secret(&self.context.resource, tls_config)?;
+                self.context.client.create(&secret).await?;
+            }
+            Err(err) => return Err(err.into()),
+        }
+
+        let node_names = self
+            .eligible_nodes
+            .values()
+            .flat_map(|groups| groups.values())
+            .flat_map(|(nodes, _)| nodes.iter())
+            .filter_map(|node| node.metadata.name.clone())
+            .collect();
+        let certificate = cert_manager::build_certificate(
+            &self.context.resource,
+            tls_config,
+            issuer_ref,
+            node_names,
+        )?;
+        let certificates_api: Api<cert_manager::Certificate> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+        let certificate = apply::apply(
+            &certificates_api,
+            &certificate,
+            &self.owned_conflict_policy(),
+        )
+        .await?;
+
+        self.certificate_revision = certificate
+            .status
+            .as_ref()
+            .and_then(|status| status.revision);
+        if self.certificate_revision.is_none() {
+            let message = format!(
+                "Waiting for cert-manager to issue the certificate [{}]",
+                certificate.name()
+            );
+            info!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+            return Ok(waiting::wait(
+                &mut self.waiting,
+                previous_waiting(&self.zk_status),
+                WaitReason::CertificateIssuance,
+                message,
+            ));
+        }
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    #[cfg(not(feature = "cert-manager"))]
+    async fn reconcile_certificate(&mut self) -> ZookeeperReconcileResult {
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Applies the objects every cluster has once, unless they were already applied for the
+    /// current generation and nothing else they are built from changed, see [`last_applied`].
+    async fn reconcile_cluster_objects(&mut self) -> ZookeeperReconcileResult {
+        let generation = self
+            .context
+            .resource
+            .metadata
+            .generation
+            .unwrap_or_default();
+        let fingerprint = self.cluster_objects_fingerprint(generation);
+        if let Some(fingerprint) = &fingerprint {
+            let last_applied = self
+                .zk_status
+                .as_ref()
+                .and_then(|status| status.last_applied.as_ref());
+            if self.dry_run == DryRunMode::Disabled
+                && last_applied::is_applied(last_applied, generation, fingerprint, Utc::now())
+            {
+                trace!(
+                    "{}: Cluster objects are up to date for generation [{}], skipping them",
+                    self.context.log_name(),
+                    generation
+                );
+                return Ok(ReconcileFunctionAction::Continue);
+            }
+        }
+
+        let action = self
+            .reconcile_super_user_secret()
+            .await?
+            .then(self.reconcile_service_account())
+            .await?
+            .then(self.reconcile_disruption_budget())
+            .await?
+            .then(self.reconcile_network_policy())
+            .await?
+            .then(self.reconcile_admin_service())
+            .await?
+            .then(self.reconcile_pod_monitor())
+            .await?
+            .then(self.reconcile_prometheus_rule())
+            .await?;
+        if let ReconcileFunctionAction::Continue = action {
+            self.applied_fingerprint = fingerprint;
+        }
+        Ok(action)
+    }
+
+    /// Returns the fingerprint of everything besides the spec the cluster wide objects are built
+    /// from, `None` if the cached owned objects are not up to date.
+    fn cluster_objects_fingerprint(&self, generation: i64) -> Option<String> {
+        let cluster = &self.context.resource;
+        let labels = build_common_labels_for_all_managed_resources(APP_NAME, &cluster.name());
+        let config_maps = self.cache.config_maps.list_owned(cluster, &labels)?;
+        let services = self.cache.services.list_owned(cluster, &labels)?;
+        let owned_objects = config_maps
+            .iter()
+            .map(|config_map| &config_map.metadata)
+            .chain(services.iter().map(|service| &service.metadata))
+            .collect::<Vec<_>>();
+        let node_names = self
+            .eligible_nodes
+            .values()
+            .flat_map(|role_groups| role_groups.values())
+            .flat_map(|(nodes, _)| nodes.iter().map(|node| node.name()))
+            .collect::<Vec<_>>();
+        Some(last_applied::fingerprint(
+            generation,
+            self.references_hash.as_deref(),
+            &owned_objects,
+            &node_names,
+        ))
+    }
+
+    /// Creates the Secret with the super user credentials (once) if `spec.authentication` is set.
+    /// The credentials are never changed afterwards because the clients depend on them.
+    async fn reconcile_super_user_secret(&self) -> ZookeeperReconcileResult {
+        let authentication = match &self.zk_spec.authentication {
+            Some(authentication) => authentication,
+            None => return Ok(ReconcileFunctionAction::Continue),
+        };
+
+        let secret_name = super_user_secret_name(&self.context.name());
+        let secrets_api: Api<k8s_openapi::api::core::v1::Secret> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+        match secrets_api.get(&secret_name).await {
+            // Secrets created by earlier versions of the operator lack the labels the
+            // controller selects the Secrets it watches by
+            Ok(secret) if secret.metadata.labels.is_empty() => {
+                let secret_labels =
+                    build_common_labels_for_all_managed_resources(APP_NAME, &self.context.name());
+                secrets_api
+                    .patch(
+                        &secret_name,
+                        &apply::merge_params(),
+                        &Patch::Merge(json!({ "metadata": { "labels": secret_labels } })),
+                    )
+                    .await?;
+            }
+            Ok(_) => {}
+            Err(kube::Error::Api(response)) if response.code == 404 => {
+                info!(
+                    "ZookeeperCluster {}: Creating the Secret [{}] with the super user credentials",
+                    self.context.log_name(),
+                    secret_name
+                );
+                let secret = authentication::build_super_user_secret(
+                    &self.context.resource,
+                    authentication,
+                )?;
+                self.context.client.create(&secret).await?;
+            }
+            Err(err) => return Err(err.into()),
+        }
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Creates or updates the ServiceAccount of the servers unless an existing one is used, and
+    /// the RoleBinding granting it `spec.serviceAccount.roleRef`. The RoleBinding is deleted if
+    /// there is no role, as is the Role with the rules earlier versions of the operator granted.
+    /// A ServiceAccount with the same name which is not owned by the cluster (e.g. `default`) is
+    /// never taken over, see [`apply`].
+    #[instrument(skip(self))]
+    async fn reconcile_service_account(&self) -> ZookeeperReconcileResult {
+        let cluster = &self.context.resource;
+        let namespace = self.context.namespace();
+        let service_account = service_account::config(cluster);
+        let policy = self.owned_conflict_policy();
+
+        if service_account.create() {
+            let service_accounts_api: Api<ServiceAccount> =
+                self.context.client.get_namespaced_api(&namespace);
+            let object = service_account::build_service_account(cluster, &service_account)?;
+            apply::apply(&service_accounts_api, &object, &policy).await?;
+        }
+
+        let roles_api: Api<Role> = self.context.client.get_namespaced_api(&namespace);
+        let role_bindings_api: Api<RoleBinding> =
+            self.context.client.get_namespaced_api(&namespace);
+        let name = service_account::role_binding_name(&self.context.name());
+        match service_account::build_role_binding(cluster, &service_account)? {
+            Some(role_binding) => {
+                // the role of a RoleBinding can not be changed, only replaced
+                let existing = apply::get_existing(&role_bindings_api, &name).await?;
+                if existing.map_or(false, |existing| existing.role_ref != role_binding.role_ref)
+                    && apply::delete(&role_bindings_api, &name, &policy).await?
+                {
+                    info!(
+                        "ZookeeperCluster {}: Deleted the RoleBinding [{}] to bind another role",
+                        self.context.log_name(),
+                        name
+                    );
+                }
+                apply::apply(&role_bindings_api, &role_binding, &policy).await?;
+            }
+            None => {
+                if apply::delete(&role_bindings_api, &name, &policy).await? {
+                    info!(
+                        "ZookeeperCluster {}: Deleted the RoleBinding [{}]",
+                        self.context.log_name(),
+                        name
+                    );
+                }
+            }
+        }
+        if apply::delete(&roles_api, &name, &policy).await? {
+            info!(
+                "ZookeeperCluster {}: Deleted the Role [{}] created by an earlier version",
+                self.context.log_name(),
+                name
+            );
+        }
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Creates or updates the PodDisruptionBudget of the cluster, which follows the number of
+    /// desired replicas. It is removed together with the cluster through its owner reference.
+    #[instrument(skip(self))]
+    async fn reconcile_disruption_budget(&self) -> ZookeeperReconcileResult {
+        let budget = disruption_budget::build_budget(
+            &self.context.resource,
+            desired_participants(&self.eligible_nodes, &self.validated_role_config),
+        )?;
+        let budgets_api: Api<PodDisruptionBudget> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+        apply::apply(&budgets_api, &budget, &self.owned_conflict_policy()).await?;
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Creates or updates the NetworkPolicy of the cluster if `spec.networkPolicy` is set, and
+    /// deletes it otherwise.
+    #[instrument(skip(self))]
+    async fn reconcile_network_policy(&self) -> ZookeeperReconcileResult {
+        let network_policies_api: Api<k8s_openapi::api::networking::v1::NetworkPolicy> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+
+        match &self.context.resource.spec.network_policy {
+            Some(network_policy) => {
+                let network_policy =
+                    network_policy::build_network_policy(&self.context.resource, network_policy)?;
+                apply::apply(
+                    &network_policies_api,
+                    &network_policy,
+                    &self.owned_conflict_policy(),
+                )
+                .await?;
+            }
+            None => {
+                let name = network_policy::network_policy_name(&self.context.name());
+                match network_policies_api
+                    .delete(&name, &DeleteParams::default())
+                    .await
+                {
+                    Ok(_) => info!(
+                        "ZookeeperCluster {}: Deleted the NetworkPolicy [{}]",
+                        self.context.log_name(),
+                        name
+                    ),
+                    Err(kube::Error::Api(response)) if response.code == 404 => {}
+                    Err(err) => return Err(err.into()),
+                }
+            }
+        }
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Creates or updates the Service in front of the AdminServers if `spec.adminServer.service`
+    /// is set, and deletes it otherwise.
+    #[instrument(skip(self))]
+    async fn reconcile_admin_service(&self) -> ZookeeperReconcileResult {
+        let services_api: Api<Service> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+
+        match self
+            .context
+            .resource
+            .spec
+            .admin_server
+            .as_ref()
+            .filter(|admin_server| admin_server.service)
+        {
+            Some(admin_server) => {
+                let service = admin_server::build_service(&self.context.resource, admin_server)?;
+                apply::apply(&services_api, &service, &self.owned_conflict_policy()).await?;
+            }
+            None => {
+                // only a Service created for the cluster is deleted, not one of the same name
+                // which belongs to someone else
+                let name = admin_server::service_name(&self.context.name());
+                if apply::delete(&services_api, &name, &self.owned_conflict_policy()).await? {
+                    info!(
+                        "ZookeeperCluster {}: Deleted the Service [{}] of the AdminServer",
+                        self.context.log_name(),
+                        name
+                    );
+                }
+            }
+        }
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Applies the PodMonitor scraping the metrics of the servers if `spec.monitoring` is set and
+    /// deletes it otherwise, see [`ManagedResources::pod_monitors`].
+    #[cfg(feature = "prometheus-operator")]
+    #[instrument(skip(self))]
+    async fn reconcile_pod_monitor(&self) -> ZookeeperReconcileResult {
+        if !self.managed_resources.pod_monitors {
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+        let pod_monitors_api: Api<prometheus_operator::PodMonitor> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+
+        if self.context.resource.spec.monitoring.is_some() {
+            let pod_monitor = prometheus_operator::build_pod_monitor(&self.context.resource)?;
+            apply::apply(
+                &pod_monitors_api,
+                &pod_monitor,
+                &self.owned_conflict_policy(),
+            )
+            .await?;
+        } else {
+            let name = prometheus_operator::pod_monitor_name(&self.context.name());
+            match pod_monitors_api
+                .delete(&name, &DeleteParams::default())
+                .await
+            {
+                Ok(_) => info!(
+                    "ZookeeperCluster {}: Deleted the PodMonitor [{}]",
+                    self.context.log_name(),
+                    name
+                ),
+                Err(kube::Error::Api(response)) if response.code == 404 => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    #[cfg(not(feature = "prometheus-operator"))]
+    async fn reconcile_pod_monitor(&self) -> ZookeeperReconcileResult {
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Applies the PrometheusRule with the alerts of the cluster if `spec.monitoring.alerts` is
+    /// set and deletes it otherwise, see [`prometheus_operator::build_prometheus_rule`]. Stopped
+    /// clusters have no servers which could be alerted about, so the rule is deleted as well.
+    #[cfg(feature = "prometheus-operator")]
+    #[instrument(skip(self))]
+    async fn reconcile_prometheus_rule(&self) -> ZookeeperReconcileResult {
+        if !self.managed_resources.pod_monitors {
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+        let prometheus_rules_api: Api<prometheus_operator::PrometheusRule> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+
+        let alerts = self
+            .context
+            .resource
+            .spec
+            .monitoring
+            .as_ref()
+            .and_then(|monitoring| monitoring.alerts.as_ref())
+            .filter(|_| !self.zk_spec.stopped);
+        if let Some(alerts) = alerts {
+            let node_names = self
+                .eligible_nodes
+                .values()
+                .flat_map(|role_groups| role_groups.values())
+                .flat_map(|(nodes, _)| nodes.iter().map(|node| node.name()))
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect::<Vec<_>>();
+            let prometheus_rule = prometheus_operator::build_prometheus_rule(
+                &self.context.resource,
+                alerts,
+                desired_replicas(&self.eligible_nodes),
+                &node_names,
+            )?;
+            apply::apply(
+                &prometheus_rules_api,
+                &prometheus_rule,
+                &self.owned_conflict_policy(),
+            )
+            .await?;
+        } else {
+            let name = prometheus_operator::prometheus_rule_name(&self.context.name());
+            match prometheus_rules_api
+                .delete(&name, &DeleteParams::default())
+                .await
+            {
+                Ok(_) => info!(
+                    "ZookeeperCluster {}: Deleted the PrometheusRule [{}]",
+                    self.context.log_name(),
+                    name
+                ),
+                Err(kube::Error::Api(response)) if response.code == 404 => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    #[cfg(not(feature = "prometheus-operator"))]
+    async fn reconcile_prometheus_rule(&self) -> ZookeeperReconcileResult {
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Returns how conflicts are resolved when applying objects of the cluster: they are only
+    /// taken over if they are owned by the cluster.
+    fn owned_conflict_policy(&self) -> ConflictPolicy {
+        ConflictPolicy::ForceIfOwnedBy(self.context.resource.uid().unwrap_or_default())
+    }
+
+    /// Records the Secrets and ConfigMaps referenced by the cluster, so their changes trigger a
+    /// reconciliation, and hashes the data of the ones the servers use.
+    async fn track_references(&mut self) -> ZookeeperReconcileResult {
+        let mut placeholder_secrets = BTreeSet::new();
+        for role_groups in self.validated_role_config.values() {
+            for config in role_groups.values() {
+                if let Some(properties) =
+                    config.get(&PropertyNameKind::File(PROPERTIES_FILE.to_string()))
+                {
+                    placeholder_secrets.extend(config_secrets::referenced_secrets(properties));
+                }
+            }
+        }
+        let references =
+            references::references(&self.context.name(), &self.zk_spec, &placeholder_secrets);
+
+        self.references_hash = references::hash_mounted_objects(
+            &self.context.client,
+            &self.context.namespace(),
+            &references,
+        )
+        .await?;
+        self.references
+            .update(&ObjectRef::from_obj(&self.context.resource), references);
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Restarts the pods which were started before a Secret or ConfigMap used by the servers
+    /// changed, one pod at a time. Pods without a hash of the references are annotated with the
+    /// current one instead.
+    async fn restart_pods_with_outdated_references(&mut self) -> ZookeeperReconcileResult {
+        let hash = match &self.references_hash {
+            Some(hash) if !self.rollout_held() => hash.clone(),
+            _ => return Ok(ReconcileFunctionAction::Continue),
+        };
+
+        // pods without the annotation were started before the references were tracked (e.g. by
+        // an older version of the operator), they are assumed to run with the current objects and
+        // only annotated
+        let pods_api: Api<Pod> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+        for pod in self.existing_pods.iter().filter(|pod| {
+            !pod.annotations()
+                .contains_key(references::REFERENCES_HASH_ANNOTATION)
+        }) {
+            pods_api
+                .patch(
+                    &pod.name(),
+                    &apply::merge_params(),
+                    &Patch::Merge(json!({
+                        "metadata": {
+                            "annotations": {
+                                (references::REFERENCES_HASH_ANNOTATION): hash
+                            }
+                        }
+                    })),
+                )
+                .await?;
+        }
+
+        let outdated_pods = self
+            .existing_pods
+            .iter()
+            .filter(|pod| {
+                pod.annotations()
+                    .get(references::REFERENCES_HASH_ANNOTATION)
+                    .map_or(false, |pod_hash| pod_hash != &hash)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        self.restart_next_pod(
+            outdated_pods,
+            "references",
+            "to apply the changed Secrets and ConfigMaps",
+        )
+        .await
+    }
+
+    /// Restarts the pods which were started with an older revision of the certificate issued by
+    /// cert-manager, one pod at a time.
+    async fn restart_pods_with_outdated_certificate(&mut self) -> ZookeeperReconcileResult {
+        let revision = match self.certificate_revision {
+            Some(revision) => revision.to_string(),
+            None => return Ok(ReconcileFunctionAction::Continue),
+        };
+
+        let outdated_pods = self
+            .existing_pods
+            .iter()
+            .filter(|pod| pod.annotations().get(CERTIFICATE_REVISION_ANNOTATION) != Some(&revision))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        self.restart_next_pod(
+            outdated_pods,
+            "certificate",
+            "to apply the renewed certificate",
+        )
+        .await
+    }
+
+    /// Completes the scale step in progress (see [`scaling`]) once all servers of the step exist,
+    /// run with the current configuration and pass the health checks. A step towards more servers
+    /// than desired (because the replicas were lowered during the step) is completed with the
+    /// desired servers. While the next step waits for the cool-down, the reconciliation is requeued
+    /// until it is over.
+    async fn complete_scale_step(&mut self) -> ZookeeperReconcileResult {
+        if self.zk_spec.scaling.is_none() {
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+
+        let step_target = self
+            .zk_status
+            .as_ref()
+            .and_then(|status| status.scaling.as_ref())
+            .and_then(|scaling| scaling.step_target);
+        if let Some(step_target) = step_target {
+            let step_target = step_target.min(desired_replicas(&self.eligible_nodes));
+            let existing = self.existing_pods.len();
+            if existing < step_target {
+                return Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10)));
+            }
+            let healthy = self.count_healthy_replicas().await;
+            if healthy < existing {
+                let message = format!(
+                    "Only [{}] of [{}] servers are healthy, waiting before completing the scale step",
+                    healthy, existing
+                );
+                info!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+                return Ok(waiting::wait(
+                    &mut self.waiting,
+                    previous_waiting(&self.zk_status),
+                    WaitReason::ServersHealthy,
+                    message,
+                ));
+            }
+
+            self.zk_status = self
+                .context
+                .client
+                .merge_patch_status(
+                    &self.context.resource,
+                    &json!({
+                        "scaling": {
+                            "stepTarget": null,
+                            "lastStepCompletedAt": Utc::now().to_rfc3339(),
+                        }
+                    }),
+                )
+                .await?
+                .status;
+            self.publish_event(
+                EventType::Normal,
+                "ScaleStepCompleted",
+                &format!("The ensemble was scaled up to [{}] servers", existing),
+            )
+            .await;
+        }
+
+        match self.scale_step {
+            ScaleStep::CoolingDown(remaining) => Ok(ReconcileFunctionAction::Requeue(remaining)),
+            _ => Ok(ReconcileFunctionAction::Continue),
+        }
+    }
+
+    /// Restarts all pods which were created before the restart requested with the
+    /// [`RESTART_ANNOTATION`] started, one pod at a time.
+    async fn restart_pods_on_request(&mut self) -> ZookeeperReconcileResult {
+        let restart = match self
+            .track_restart()
+            .await?
+            .filter(|restart| restart.completed_at.is_none())
+        {
+            Some(restart) => restart,
+            None => return Ok(ReconcileFunctionAction::Continue),
+        };
+        let requested_at = DateTime::parse_from_rfc3339(&restart.started_at)
+            .map_err(|err| {
+                Error::ReconcileError(format!(
+                    "Invalid timestamp [{}] in status.restart.startedAt: {}",
+                    restart.started_at, err
+                ))
+            })?
+            .with_timezone(&Utc);
+
+        let outdated_pods = self
+            .existing_pods
+            .iter()
+            .filter(|pod| is_pod_created_before(pod, &requested_at))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let action = self
+            .restart_next_pod(outdated_pods, "request", "as requested")
+            .await?;
+        if !matches!(action, ReconcileFunctionAction::Continue) {
+            return Ok(action);
+        }
+
+        let completed = RestartStatus {
+            completed_at: Some(Utc::now().to_rfc3339()),
+            ..restart
+        };
+        self.zk_status = self
+            .context
+            .client
+            .merge_patch_status(&self.context.resource, &json!({ "restart": completed }))
+            .await?
+            .status;
+        self.publish_event(
+            EventType::Normal,
+            "RestartCompleted",
+            &format!(
+                "All pods were restarted as requested with [{}]",
+                completed.token
+            ),
+        )
+        .await;
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Records a new value of the [`RESTART_ANNOTATION`] in `status.restart`, which starts a
+    /// restart of all pods, and returns the restart of the current value. Every value only
+    /// restarts the pods once.
+    async fn track_restart(&mut self) -> Result<Option<RestartStatus>, Error> {
+        let token = match self.context.resource.annotations().get(RESTART_ANNOTATION) {
+            Some(token) => token.clone(),
+            None => return Ok(None),
+        };
+        if let Some(restart) = self
+            .zk_status
+            .as_ref()
+            .and_then(|status| status.restart.clone())
+            .filter(|restart| restart.token == token)
+        {
+            return Ok(Some(restart));
+        }
+
+        let restart = RestartStatus {
+            token,
+            started_at: Utc::now().to_rfc3339(),
+            completed_at: None,
+        };
+        self.zk_status = self
+            .context
+            .client
+            .merge_patch_status(&self.context.resource, &json!({ "restart": restart }))
+            .await?
+            .status;
+        info!(
+            "ZookeeperCluster {}: Restart requested with [{}]",
+            self.context.log_name(),
+            restart.token
+        );
+        self.publish_event(
+            EventType::Normal,
+            "RestartRequested",
+            &format!(
+                "A restart of all pods was requested with [{}]",
+                restart.token
+            ),
+        )
+        .await;
+        Ok(Some(restart))
+    }
+
+    /// Rolls out a version upgrade one pod at a time.
+    ///
+    /// As long as there are pods running an outdated version, we first verify that all servers
+    /// already running the target version are serving requests and only then delete the next
+    /// outdated pod. It is recreated with the target version by `create_missing_pods` in one of
+    /// the next runs. The progress is recorded in the `Upgrading` condition.
+    ///
+    /// Once all pods are running the target version, it becomes the `currentVersion`.
+    #[instrument(skip(self))]
+    async fn upgrade_pods(&mut self) -> ZookeeperReconcileResult {
+        let status = self.zk_status.clone().ok_or_else(|| error::Error::ReconcileError(
+            "`zk_status missing, this is a programming error and should never happen. Please report in our issue tracker.".to_string(),
+        ))?;
+
+        let target_version = match &status.target_version {
+            Some(target_version) => target_version,
+            None => return Ok(ReconcileFunctionAction::Continue),
+        };
+
+        if let Some(action) = self.check_integrity(&status, target_version).await? {
+            return Ok(action);
+        }
+        if let Some(action) = self.upgrade_canary(&status, target_version).await? {
+            return Ok(action);
+        }
+
+        let (upgraded_pods, outdated_pods): (Vec<&Pod>, Vec<&Pod>) = self
+            .existing_pods
+            .iter()
+            .partition(|pod| runs_version(pod, target_version));
+
+        let order = self.restart_order(outdated_pods).await;
+        if let Some(pod) = order.next() {
+            for upgraded_pod in &upgraded_pods {
+                if !is_server_serving(upgraded_pod).await.unwrap_or(false) {
+                    let message = format!(
+                        "Waiting for upgraded pod [{}] to serve requests before upgrading the next one",
+                        upgraded_pod.name()
+                    );
+                    info!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+                    return Ok(waiting::wait(
+                        &mut self.waiting,
+                        previous_waiting(&self.zk_status),
+                        WaitReason::UpgradedServerServing,
+                        message,
+                    ));
+                }
+            }
+
+            if let Some((reason, message)) = self.check_disruption(pod, "upgrade").await? {
+                return Ok(waiting::wait(
+                    &mut self.waiting,
+                    previous_waiting(&self.zk_status),
+                    reason,
+                    message,
+                ));
+            }
+
+            let message = format!(
+                "Upgrading to [{}]: [{}/{}] servers upgraded",
+                target_version,
+                upgraded_pods.len(),
+                self.existing_pods.len()
+            );
+            info!(
+                "ZookeeperCluster {}: {}, upgrading pod [{}] next",
+                self.context.log_name(),
+                message,
+                pod.name()
+            );
+            self.set_upgrading_condition(
+                &status.conditions,
+                &message,
+                "Upgrading",
+                ConditionStatus::True,
+            )
+            .await?;
+            self.delete_pod(pod).await?;
+            let step = self
+                .record_restart_step(order.next_step("upgrade", &Utc::now().to_rfc3339()))
+                .await?;
+            self.publish_event(
+                EventType::Normal,
+                "UpgradingPod",
+                &format!("{}, upgrading pod [{}] ({})", message, pod.name(), step),
+            )
+            .await;
+            return Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10)));
+        }
+
+        // If we reach here it means all pods are running on target_version.
+        // We can now set current_version to target_version and target_version to None
+        self.zk_status = self.set_target_version(None).await?.status;
+        self.zk_status = self.set_current_version(Some(target_version)).await?.status;
+        if status.canary.is_some() {
+            self.zk_status = self
+                .context
+                .client
+                .merge_patch_status(&self.context.resource, &json!({ "canary": null }))
+                .await?
+                .status;
+        }
+        self.zk_status = self
+            .set_upgrading_condition(
+                &status.conditions,
+                &format!(
+                    "No upgrade required [{}] is still the current_version",
+                    target_version
+                ),
+                "",
+                ConditionStatus::False,
+            )
+            .await?
+            .status;
+        self.publish_event(
+            EventType::Normal,
+            "UpgradeCompleted",
+            &format!("All servers are running version [{}]", target_version),
+        )
+        .await;
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Upgrades the canary of `spec.upgrade.canary` before the other servers, see [`upgrade`].
+    /// Returns the action ending `upgrade_pods` while the canary did not pass (yet) and `None`
+    /// once the other servers can be upgraded.
+    async fn upgrade_canary(
+        &mut self,
+        status: &ZookeeperClusterStatus,
+        target_version: &ZookeeperVersion,
+    ) -> Result<Option<ReconcileFunctionAction>, Error> {
+        if self.canary_config().is_none() {
+            return Ok(None);
+        }
+
+        if let Some(canary) = self.canary_status() {
+            return Ok(match canary.phase {
+                CanaryPhase::Passed => None,
+                CanaryPhase::Failed => {
+                    info!(
+                        "ZookeeperCluster {}: The upgrade to [{}] is halted because its canary failed",
+                        self.context.log_name(),
+                        target_version
+                    );
+                    Some(ReconcileFunctionAction::Continue)
+                }
+                CanaryPhase::Starting => Some(waiting::wait(
+                    &mut self.waiting,
+                    previous_waiting(&self.zk_status),
+                    WaitReason::CanaryVerification,
+                    format!(
+                        "Waiting for the canary server [{}] to serve requests with version [{}]",
+                        canary.id, target_version
+                    ),
+                )),
+                CanaryPhase::Soaking => Some(waiting::wait(
+                    &mut self.waiting,
+                    previous_waiting(&self.zk_status),
+                    WaitReason::CanaryVerification,
+                    format!(
+                        "Verifying the canary server [{}] with version [{}] since [{}]",
+                        canary.id,
+                        target_version,
+                        canary.soak_started_at.unwrap_or_default()
+                    ),
+                )),
+            });
+        }
+
+        // servers which already run the target version were upgraded before a canary was
+        // configured (or are new), so there is nothing to try out anymore
+        let target = target_version.to_string();
+        if self
+            .existing_pods
+            .iter()
+            .any(|pod| pod.labels().get(labels::APP_VERSION_LABEL) == Some(&target))
+        {
+            return Ok(None);
+        }
+        let order = self
+            .restart_order(self.existing_pods.iter().collect())
+            .await;
+        let (pod, step) = match order.next() {
+            Some(pod) => (
+                pod.clone(),
+                order.next_step("canary", &Utc::now().to_rfc3339()),
+            ),
+            None => return Ok(None),
+        };
+        if let Some((reason, message)) = self.check_disruption(&pod, "upgrade").await? {
+            return Ok(Some(waiting::wait(
+                &mut self.waiting,
+                previous_waiting(&self.zk_status),
+                reason,
+                message,
+            )));
+        }
+        let id = pod_id(&pod).ok_or_else(|| {
+            Error::ReconcileError(format!("Pod [{}] has no valid id label", pod.name()))
+        })?;
+
+        let message = format!(
+            "Upgrading to [{}]: upgrading server [{}] in pod [{}] as canary",
+            target_version,
+            id,
+            pod.name()
+        );
+        info!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+        self.set_upgrading_condition(
+            &status.conditions,
+            &message,
+            "UpgradingCanary",
+            ConditionStatus::True,
+        )
+        .await?;
+        let canary = CanaryStatus {
+            pod: pod.name(),
+            id,
+            version: target_version.clone(),
+            phase: CanaryPhase::Starting,
+            started_at: Utc::now().to_rfc3339(),
+            soak_started_at: None,
+            message: None,
+        };
+        self.zk_status = self
+            .context
+            .client
+            .merge_patch_status(&self.context.resource, &json!({ "canary": canary }))
+            .await?
+            .status;
+        self.delete_pod(&pod).await?;
+        let step = self.record_restart_step(step).await?;
+        self.publish_event(
+            EventType::Normal,
+            "UpgradingCanary",
+            &format!("{} ({})", message, step),
+        )
+        .await;
+        Ok(Some(ReconcileFunctionAction::Requeue(Duration::from_secs(
+            10,
+        ))))
+    }
+
+    /// Verifies the data of the leader in its server container before any server is upgraded if
+    /// `spec.upgrade.integrityCheck` is set, see [`integrity_check`]. Returns the action ending
+    /// `upgrade_pods` while the check did not pass and `None` once the servers can be upgraded.
+    async fn check_integrity(
+        &mut self,
+        status: &ZookeeperClusterStatus,
+        target_version: &ZookeeperVersion,
+    ) -> Result<Option<ReconcileFunctionAction>, Error> {
+        let config = match self.integrity_check_config() {
+            Some(config) => config,
+            None => return Ok(None),
+        };
+        // the initial installation has no data to check
+        let current_version = match &status.current_version {
+            Some(current_version) => current_version.clone(),
+            None => return Ok(None),
+        };
+
+        if let Some(check) = self.integrity_check_status() {
+            match check.phase {
+                IntegrityCheckPhase::Passed => return Ok(None),
+                IntegrityCheckPhase::Failed => {
+                    if current_version == self.zk_spec.version {
+                        self.cancel_upgrade_after_integrity_check(&check, &current_version)
+                            .await?;
+                    } else {
+                        info!(
+                            "ZookeeperCluster {}: The upgrade to [{}] is halted because the integrity check failed",
+                            self.context.log_name(),
+                            target_version
+                        );
+                    }
+                    return Ok(Some(ReconcileFunctionAction::Continue));
+                }
+                // the operator stopped during the check, it is run again
+                IntegrityCheckPhase::Running => {}
+            }
+        }
+
+        // servers which already run the target version were upgraded before the check was
+        // configured (or are new), so the upgrade is not held back anymore
+        let target = target_version.to_string();
+        if self
+            .existing_pods
+            .iter()
+            .any(|pod| pod.labels().get(labels::APP_VERSION_LABEL) == Some(&target))
+        {
+            return Ok(None);
+        }
+        let leader = status
+            .quorum
+            .as_ref()
+            .and_then(|quorum| quorum.leader.as_ref())
+            .and_then(|leader| self.existing_pods.iter().find(|pod| &pod.name() == leader))
+            .cloned();
+        let leader = match leader {
+            Some(leader) => leader,
+            None => {
+                return Ok(Some(waiting::wait(
+                    &mut self.waiting,
+                    previous_waiting(&self.zk_status),
+                    WaitReason::IntegrityCheck,
+                    format!(
+                        "Waiting for a leader to verify its data before upgrading to [{}]",
+                        target_version
+                    ),
+                )))
+            }
+        };
+
+        let message = format!(
+            "Upgrading to [{}]: verifying the data of the leader [{}] first",
+            target_version,
+            leader.name()
+        );
+        info!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+        self.set_upgrading_condition(
+            &status.conditions,
+            &message,
+            "CheckingIntegrity",
+            ConditionStatus::True,
+        )
+        .await?;
+        let check = IntegrityCheckStatus {
+            version: target_version.clone(),
+            pod: leader.name(),
+            phase: IntegrityCheckPhase::Running,
+            started_at: Utc::now().to_rfc3339(),
+            message: None,
+        };
+        self.zk_status = self
+            .context
+            .client
+            .merge_patch_status(&self.context.resource, &json!({ "integrityCheck": check }))
+            .await?
+            .status;
+        self.publish_event(EventType::Normal, "IntegrityCheckStarted", &message)
+            .await;
+
+        let result = integrity_check::run(
+            self.pod_executor.as_ref(),
+            self.context
+                .client
+                .get_namespaced_api(&self.context.namespace()),
+            &leader,
+            &current_version.package_name(),
+            &self.data_dir(&leader),
+            config.timeout_seconds(),
+        )
+        .await?;
+        self.finish_integrity_check(&check, result).await
+    }
+
+    /// Records the result of an integrity check. A passed check returns `None`, so the upgrade
+    /// proceeds right away, a failed one halts it.
+    async fn finish_integrity_check(
+        &mut self,
+        check: &IntegrityCheckStatus,
+        result: integrity_check::CheckResult,
+    ) -> Result<Option<ReconcileFunctionAction>, Error> {
+        let phase = if result.passed {
+            IntegrityCheckPhase::Passed
+        } else {
+            IntegrityCheckPhase::Failed
+        };
+        let finished = IntegrityCheckStatus {
+            phase,
+            message: Some(result.message.clone()),
+            ..check.clone()
+        };
+        self.zk_status = self
+            .context
+            .client
+            .merge_patch_status(
+                &self.context.resource,
+                &json!({ "integrityCheck": finished }),
+            )
+            .await?
+            .status;
+
+        if phase == IntegrityCheckPhase::Failed {
+            let message = format!(
+                "{}, halting the upgrade to [{}]. Set spec.version back to the current version to cancel the upgrade",
+                result.message, check.version
+            );
+            warn!(
+                "ZookeeperCluster {}: {}. The output of the check in [{}]:\n{}",
+                self.context.log_name(),
+                message,
+                check.pod,
+                result.output
+            );
+            self.publish_event(EventType::Warning, "IntegrityCheckFailed", &message)
+                .await;
+            return Ok(Some(ReconcileFunctionAction::Continue));
+        }
+
+        self.publish_event(
+            EventType::Normal,
+            "IntegrityCheckPassed",
+            &format!(
+                "{}, upgrading the servers to [{}]",
+                result.message, check.version
+            ),
+        )
+        .await;
+        Ok(None)
+    }
+
+    /// Cancels the upgrade halted by a failed integrity check once `spec.version` is set back to
+    /// the current version. No server was upgraded yet, so only the status is reset.
+    async fn cancel_upgrade_after_integrity_check(
+        &mut self,
+        check: &IntegrityCheckStatus,
+        current_version: &ZookeeperVersion,
+    ) -> Result<(), Error> {
+        self.zk_status = self
+            .context
+            .client
+            .merge_patch_status(
+                &self.context.resource,
+                &json!({ "targetVersion": null, "integrityCheck": null }),
+            )
+            .await?
+            .status;
+
+        let message = format!(
+            "Cancelled the upgrade to [{}] after the failed integrity check, staying at [{}]",
+            check.version, current_version
+        );
+        info!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+        let conditions = self
+            .zk_status
+            .as_ref()
+            .map(|status| status.conditions.clone())
+            .unwrap_or_default();
+        self.zk_status = self
+            .set_upgrading_condition(
+                &conditions,
+                &message,
+                "UpgradeCancelled",
+                ConditionStatus::False,
+            )
+            .await?
+            .status;
+        self.publish_event(EventType::Normal, "UpgradeCancelled", &message)
+            .await;
+        Ok(())
+    }
+
+    /// Verifies the canary of a running upgrade, see [`upgrade`]. This happens before the pods
+    /// are waited for, so a canary which never gets ready fails after its start timeout.
+    #[instrument(skip(self))]
+    async fn verify_canary(&mut self) -> ZookeeperReconcileResult {
+        let (canary, status) = match (self.canary_config(), self.canary_status()) {
+            (Some(canary), Some(status)) => (canary, status),
+            _ => return Ok(ReconcileFunctionAction::Continue),
+        };
+        match status.phase {
+            CanaryPhase::Passed => return Ok(ReconcileFunctionAction::Continue),
+            CanaryPhase::Failed => return self.roll_back_canary(&status).await,
+            CanaryPhase::Starting | CanaryPhase::Soaking => {}
+        }
+
+        let servers = self.check_servers().await;
+        let quorum_healthy =
+            servers.iter().filter(|server| server.healthy).count() > servers.len() / 2;
+        let version = status.version.to_string();
+        let serving = self
+            .existing_pods
+            .iter()
+            .filter(|pod| {
+                pod_id(pod) == Some(status.id)
+                    && pod.labels().get(labels::APP_VERSION_LABEL) == Some(&version)
+            })
+            .any(|pod| {
+                servers
+                    .iter()
+                    .any(|server| server.pod == pod.name() && server.healthy)
+            });
+        let verified =
+            upgrade::verify_canary(&status, &canary, serving, quorum_healthy, &Utc::now());
+        if verified.phase == status.phase {
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+
+        self.zk_status = self
+            .context
+            .client
+            .merge_patch_status(&self.context.resource, &json!({ "canary": verified }))
+            .await?
+            .status;
+        match verified.phase {
+            CanaryPhase::Soaking => {
+                self.publish_event(
+                    EventType::Normal,
+                    "CanarySoaking",
+                    &format!(
+                        "The canary server [{}] serves requests with version [{}], verifying it for [{}] seconds",
+                        status.id,
+                        version,
+                        canary.soak_seconds()
+                    ),
+                )
+                .await
+            }
+            CanaryPhase::Passed => {
+                self.publish_event(
+                    EventType::Normal,
+                    "CanaryPassed",
+                    &format!(
+                        "The canary server [{}] worked with version [{}] for [{}] seconds, upgrading the other servers",
+                        status.id,
+                        version,
+                        canary.soak_seconds()
+                    ),
+                )
+                .await
+            }
+            CanaryPhase::Failed => {
+                let message = format!(
+                    "{}, halting the upgrade to [{}]. Set spec.version back to the current version to roll the canary server [{}] back",
+                    verified.message.unwrap_or_default(),
+                    version,
+                    status.id
+                );
+                warn!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+                self.publish_event(EventType::Warning, "CanaryFailed", &message)
+                    .await
+            }
+            CanaryPhase::Starting => {}
+        }
+        Ok(ReconcileFunctionAction::Continue)
+    }
 
-        let id_information = self.id_information.as_mut().ok_or_else(|| error::Error::ReconcileError(
-            "id_information missing, this is a programming error and should never happen. Please report in our issue tracker.".to_string(),
-        ))?;
+    /// Rolls back a failed canary once `spec.version` is set back to the current version: the
+    /// upgrade is cancelled and the pod of the canary is recreated with the current version.
+    async fn roll_back_canary(&mut self, canary: &CanaryStatus) -> ZookeeperReconcileResult {
+        let current_version = match self
+            .zk_status
+            .as_ref()
+            .and_then(|status| status.current_version.clone())
+        {
+            Some(current_version) if current_version == self.zk_spec.version => current_version,
+            _ => return Ok(ReconcileFunctionAction::Continue),
+        };
 
-        // The iteration happens in two stages here, to accommodate the way our operators think
-        // about roles and role groups.
-        // The hierarchy is:
-        // - Roles (for ZooKeeper there - currently - is only a single role)
-        //   - Role groups for this role (user defined)
-        for zookeeper_role in ZookeeperRole::iter() {
-            if let Some(nodes_for_role) = self.eligible_nodes.get(&zookeeper_role.to_string()) {
-                for (role_group, (nodes, replicas)) in nodes_for_role {
-                    debug!(
-                        "Identify missing pods for [{}] role and group [{}]",
-                        zookeeper_role, role_group
-                    );
-                    trace!(
-                        "candidate_nodes[{}]: [{:?}]",
-                        nodes.len(),
-                        nodes
-                            .iter()
-                            .map(|node| node.metadata.name.as_ref().unwrap())
-                            .collect::<Vec<_>>()
-                    );
-                    trace!(
-                        "existing_pods[{}]: [{:?}]",
-                        &self.existing_pods.len(),
-                        &self
-                            .existing_pods
-                            .iter()
-                            .map(|pod| pod.metadata.name.as_ref().unwrap())
-                            .collect::<Vec<_>>()
-                    );
-                    trace!(
-                        "labels: [{:?}]",
-                        get_role_and_group_labels(&zookeeper_role.to_string(), role_group)
-                    );
-                    let nodes_that_need_pods = k8s_utils::find_nodes_that_need_pods(
-                        nodes,
-                        &self.existing_pods,
-                        &get_role_and_group_labels(&zookeeper_role.to_string(), role_group),
-                        *replicas,
-                    );
+        let version = canary.version.to_string();
+        for pod in &self.existing_pods {
+            if pod.labels().get(labels::APP_VERSION_LABEL) == Some(&version) {
+                self.delete_pod(pod).await?;
+            }
+        }
+        self.zk_status = self
+            .context
+            .client
+            .merge_patch_status(
+                &self.context.resource,
+                &json!({ "targetVersion": null, "canary": null }),
+            )
+            .await?
+            .status;
 
-                    for node in nodes_that_need_pods {
-                        let node_name = if let Some(node_name) = &node.metadata.name {
-                            node_name
-                        } else {
-                            warn!("No name found in metadata, this should not happen! Skipping node: [{:?}]", node);
-                            continue;
-                        };
-                        debug!(
-                            "Creating pod on node [{}] for [{}] role and group [{}]",
-                            node.metadata
-                                .name
-                                .as_deref()
-                                .unwrap_or("<no node name found>"),
-                            zookeeper_role,
-                            role_group
-                        );
+        let message = format!(
+            "Rolled the canary server [{}] back from [{}] to [{}]",
+            canary.id, version, current_version
+        );
+        info!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+        let conditions = self
+            .zk_status
+            .as_ref()
+            .map(|status| status.conditions.clone())
+            .unwrap_or_default();
+        self.zk_status = self
+            .set_upgrading_condition(
+                &conditions,
+                &message,
+                "CanaryRolledBack",
+                ConditionStatus::False,
+            )
+            .await?
+            .status;
+        self.publish_event(EventType::Normal, "CanaryRolledBack", &message)
+            .await;
+        Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10)))
+    }
 
-                        if id_information.node_name_to_pod.get(node_name).is_none() {
-                            info!("Pod for server [{}] missing, creating now...", node_name);
+    /// Applies the Services of `spec.externalAccess` for the existing servers, deletes the ones
+    /// which are not needed anymore (e.g. after a scale down) and publishes the external
+    /// endpoints in the status.
+    #[instrument(skip(self))]
+    async fn reconcile_external_access(&mut self) -> ZookeeperReconcileResult {
+        let services_api: Api<Service> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
 
-                            let id = *id_information.node_name_to_id.get(node_name).ok_or_else(
-                                || {
-                                    Error::ReconcileError(format!(
-                                        "We didn't find a `myid` for [{}] but it \
-                                        should have been assigned, this is a bug, please report it",
-                                        node_name
-                                    ))
-                                },
-                            )?;
+        let mut servers = self
+            .existing_pods
+            .iter()
+            .filter_map(|pod| Some((pod_id(pod)?, pod)))
+            .collect::<Vec<_>>();
+        servers.sort_by_key(|(id, _)| *id);
 
-                            // now we have a node that needs a pod -> get validated config
-                            let validated_config = config_for_role_and_group(
-                                &zookeeper_role.to_string(),
-                                role_group,
-                                &self.validated_role_config,
-                            )?;
+        let mut applied = vec![];
+        if let Some(external_access) = &self.context.resource.spec.external_access {
+            let services = external_access::build_services(
+                &self.context.resource,
+                external_access,
+                &servers
+                    .iter()
+                    .map(|(id, pod)| (*id, client_port(pod)))
+                    .collect::<Vec<_>>(),
+            )?;
+            for service in services {
+                applied.push(
+                    apply::apply(&services_api, &service, &self.owned_conflict_policy()).await?,
+                );
+            }
+        }
 
-                            let config_maps = self
-                                .create_config_maps(
-                                    &zookeeper_role.to_string(),
-                                    role_group,
-                                    id,
-                                    validated_config,
-                                )
-                                .await?;
+        let mut service_labels = BTreeMap::new();
+        service_labels.insert(labels::APP_INSTANCE_LABEL.to_string(), self.context.name());
+        service_labels.insert(
+            external_access::EXTERNAL_ACCESS_LABEL.to_string(),
+            "true".to_string(),
+        );
+        let existing = match self
+            .cache
+            .services
+            .list_owned(&self.context.resource, &service_labels)
+        {
+            Some(services) => services,
+            None => self.context.list_owned(service_labels).await?,
+        };
+        for service in existing {
+            if applied
+                .iter()
+                .all(|applied| applied.name() != service.name())
+            {
+                services_api
+                    .delete(&service.name(), &DeleteParams::default())
+                    .await?;
+                self.cache
+                    .services
+                    .expect_deleted(&self.context.resource, &service);
+                info!(
+                    "ZookeeperCluster {}: Deleted the Service [{}] which is not needed for the external access anymore",
+                    self.context.log_name(),
+                    service.name()
+                );
+            }
+        }
 
-                            self.create_pod(
-                                &zookeeper_role.to_string(),
-                                role_group,
-                                node_name,
-                                id,
-                                &config_maps,
-                                validated_config,
+        let status = self
+            .context
+            .resource
+            .spec
+            .external_access
+            .as_ref()
+            .map(|external_access| {
+                let endpoints = match external_access.type_ {
+                    ExternalAccessType::NodePort => servers
+                        .iter()
+                        .zip(&applied)
+                        .filter_map(|((_, pod), service)| {
+                            let node_name = pod_node_name(pod).ok()?;
+                            let node = self
+                                .eligible_nodes
+                                .values()
+                                .flat_map(|role_groups| role_groups.values())
+                                .flat_map(|(nodes, _)| nodes.iter())
+                                .find(|node| node.name() == node_name)?;
+                            external_access::node_port_endpoint(
+                                service,
+                                node,
+                                &self.context.resource.spec.ip_families,
                             )
-                            .await?;
+                        })
+                        .collect(),
+                    ExternalAccessType::LoadBalancer => applied
+                        .iter()
+                        .filter_map(external_access::load_balancer_endpoint)
+                        .collect(),
+                };
+                ExternalAccessStatus { endpoints }
+            });
+        let previous = self
+            .zk_status
+            .as_ref()
+            .and_then(|status| status.external_access.as_ref());
+        if previous != status.as_ref() {
+            self.zk_status = self
+                .context
+                .client
+                .merge_patch_status(&self.context.resource, &json!({ "externalAccess": status }))
+                .await?
+                .status;
+        }
 
-                            return Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10)));
-                        } else {
-                            debug!(
-                                "Pod for server [{}] already created, skipping ...",
-                                node_name
-                            );
-                        }
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Publishes a [`ClientRebalanceHint`] in the status after the ensemble was scaled up, if
+    /// enabled via `rebalanceClientsAfterScaleUp`.
+    async fn update_client_rebalance_hint(&mut self) -> ZookeeperReconcileResult {
+        if !self.zk_spec.rebalance_clients_after_scale_up {
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+
+        let previous = self
+            .zk_status
+            .as_ref()
+            .and_then(|status| status.client_rebalance.as_ref());
+
+        if let Some(hint) =
+            next_client_rebalance_hint(previous, self.existing_pods.len(), Utc::now())
+        {
+            if hint.requested_at != previous.and_then(|previous| previous.requested_at.clone()) {
+                info!(
+                    "ZookeeperCluster {}: Ensemble was scaled up to [{}] servers, requesting clients to rebalance their connections",
+                    self.context.log_name(),
+                    hint.servers
+                );
+            }
+            self.zk_status = self
+                .context
+                .client
+                .merge_patch_status(&self.context.resource, &json!({ "clientRebalance": hint }))
+                .await?
+                .status;
+        }
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Copies the data of the primary into the cluster while it is a standby (see [`standby`])
+    /// and schedules the requeue for the next copy. Once `spec.standbyOf` is removed, the standby
+    /// status is cleared and the promotion is reported.
+    async fn sync_standby(&mut self) -> ZookeeperReconcileResult {
+        let conditions = self
+            .zk_status
+            .as_ref()
+            .map(|status| status.conditions.clone())
+            .unwrap_or_default();
+        let previous = self
+            .zk_status
+            .as_ref()
+            .and_then(|status| status.standby.clone());
+        let standby_of = match &self.context.resource.spec.standby_of {
+            Some(standby_of) => standby_of.clone(),
+            None => {
+                if let Some(previous) = previous {
+                    let message = format!(
+                        "The cluster was promoted, the data of [{}] is no longer copied",
+                        previous.primary
+                    );
+                    info!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+                    self.publish_event(EventType::Normal, "Promoted", &message)
+                        .await;
+                    self.set_condition(
+                        &conditions,
+                        standby::STANDBY_SYNCED_CONDITION,
+                        &message,
+                        "Promoted",
+                        ConditionStatus::False,
+                    )
+                    .await?;
+                    self.zk_status = self
+                        .context
+                        .client
+                        .merge_patch_status(&self.context.resource, &json!({ "standby": null }))
+                        .await?
+                        .status;
+                }
+                return Ok(ReconcileFunctionAction::Continue);
+            }
+        };
+
+        let now = Utc::now();
+        let last_attempt = previous
+            .as_ref()
+            .and_then(|previous| DateTime::parse_from_rfc3339(&previous.last_attempt_time).ok())
+            .map(|last_attempt| last_attempt.with_timezone(&Utc));
+        let interval_seconds = standby_of.sync_interval_seconds();
+        if let Some(wait) = standby::next_sync_in(last_attempt.as_ref(), interval_seconds, &now) {
+            self.schedule_requeue(wait);
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+
+        let primary_namespace = standby_of
+            .cluster_ref
+            .namespace
+            .clone()
+            .unwrap_or_else(|| self.context.namespace());
+        let primary_name = &standby_of.cluster_ref.name;
+        let primary = format!("{}/{}", primary_namespace, primary_name);
+        // the progress is only kept while the same primary is copied
+        let previous = previous.filter(|previous| previous.primary == primary);
+        let mut status = StandbyStatus {
+            primary: primary.clone(),
+            last_attempt_time: now.to_rfc3339(),
+            last_sync_time: previous
+                .as_ref()
+                .and_then(|previous| previous.last_sync_time.clone()),
+            synced_znodes: previous.map_or(0, |previous| previous.synced_znodes),
+            lag_seconds: None,
+        };
+
+        match self
+            .copy_from_primary(&primary_namespace, primary_name)
+            .await
+        {
+            Ok(copied) => {
+                let message = format!("Copied [{}] znodes from [{}]", copied, primary);
+                debug!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+                status.last_sync_time = Some(now.to_rfc3339());
+                status.synced_znodes = copied;
+                status.lag_seconds = Some(0);
+                self.set_condition(
+                    &conditions,
+                    standby::STANDBY_SYNCED_CONDITION,
+                    &message,
+                    "Synced",
+                    ConditionStatus::True,
+                )
+                .await?;
+            }
+            Err(err) => {
+                let message = format!("Copying the data of [{}] failed: {}", primary, err);
+                warn!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+                status.lag_seconds = status
+                    .last_sync_time
+                    .as_ref()
+                    .and_then(|last_sync| DateTime::parse_from_rfc3339(last_sync).ok())
+                    .map(|last_sync| (now - last_sync.with_timezone(&Utc)).num_seconds());
+                self.publish_event(EventType::Warning, "StandbySyncFailed", &message)
+                    .await;
+                self.set_condition(
+                    &conditions,
+                    standby::STANDBY_SYNCED_CONDITION,
+                    &message,
+                    "SyncFailed",
+                    ConditionStatus::False,
+                )
+                .await?;
+            }
+        }
+        self.zk_status = self
+            .context
+            .client
+            .merge_patch_status(&self.context.resource, &json!({ "standby": status }))
+            .await?
+            .status;
+
+        self.schedule_requeue(Duration::from_secs(interval_seconds));
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Copies the znodes of the primary cluster `name` in `namespace` into this cluster, both
+    /// sessions are authenticated as super user if the clusters require authentication.
+    async fn copy_from_primary(&self, namespace: &str, name: &str) -> Result<u64, Error> {
+        if self.existing_pods.is_empty() {
+            return Err(Error::ReconcileError(
+                "There are no servers to copy the data to".to_string(),
+            ));
+        }
+        let clusters_api: Api<ZookeeperCluster> = self.context.client.get_namespaced_api(namespace);
+        let primary = clusters_api.get(name).await?;
+        let primary_credentials = match primary.spec.authentication {
+            Some(_) => Some(
+                authentication::read_super_user_credentials(&self.context.client, namespace, name)
+                    .await?,
+            ),
+            None => None,
+        };
+        let reference = ZookeeperReference {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            chroot: None,
+        };
+        let primary_connection = get_zk_connection_info(&self.context.client, &reference).await?;
+
+        let primary_zk = zk_client::connect_with_credentials(
+            self.zk_connector.as_ref(),
+            &primary_connection.connection_string,
+            primary_credentials.as_ref(),
+        )
+        .await?;
+        let standby_zk = match self.connect_to_servers().await {
+            Ok(standby_zk) => standby_zk,
+            Err(err) => {
+                primary_zk.close().await?;
+                return Err(err);
+            }
+        };
+        let result = standby::copy_znodes(primary_zk.as_ref(), standby_zk.as_ref()).await;
+        primary_zk.close().await?;
+        standby_zk.close().await?;
+        result
+    }
+
+    /// Opens a session with the servers of this cluster, authenticated as super user if the
+    /// cluster requires authentication.
+    async fn connect_to_servers(&self) -> Result<Box<dyn ZookeeperClient>, Error> {
+        if self.existing_pods.is_empty() {
+            return Err(Error::ReconcileError(
+                "There are no servers to connect to".to_string(),
+            ));
+        }
+        let credentials = match self.context.resource.spec.authentication {
+            Some(_) => Some(
+                authentication::read_super_user_credentials(
+                    &self.context.client,
+                    &self.context.namespace(),
+                    &self.context.name(),
+                )
+                .await?,
+            ),
+            None => None,
+        };
+        let connection_string = self
+            .existing_pods
+            .iter()
+            .map(|pod| {
+                Ok(ip_family::format_address(
+                    pod_node_name(pod)?,
+                    client_port(pod),
+                ))
+            })
+            .collect::<Result<Vec<_>, Error>>()?
+            .join(",");
+
+        zk_client::connect_with_credentials(
+            self.zk_connector.as_ref(),
+            &connection_string,
+            credentials.as_ref(),
+        )
+        .await
+    }
+
+    /// Takes a backup of the data directory (see [`backup`]) when it is due according to
+    /// `spec.backup.schedule`, deletes the backups exceeding the retention and schedules the
+    /// requeue for the next backup. Failed backups are reported but do not fail the
+    /// reconciliation, the next one is taken as scheduled.
+    #[cfg(feature = "backup")]
+    async fn run_scheduled_backup(&mut self) -> ZookeeperReconcileResult {
+        let backup_config = match &self.context.resource.spec.backup {
+            Some(backup_config) => backup_config.clone(),
+            None => return Ok(ReconcileFunctionAction::Continue),
+        };
+        let schedule = backup_config
+            .schedule
+            .parse::<cron::CronSchedule>()
+            .map_err(|problem| Error::InvalidConfig {
+                problems: vec![format!("backup.schedule: {}", problem)],
+            })?;
+        let conditions = self
+            .zk_status
+            .as_ref()
+            .map(|status| status.conditions.clone())
+            .unwrap_or_default();
+        let previous = self
+            .zk_status
+            .as_ref()
+            .and_then(|status| status.backup.clone())
+            .unwrap_or_default();
+
+        let now = Utc::now();
+        // the first backup is due at the first scheduled time after the creation of the cluster
+        let last_schedule = previous
+            .last_schedule_time
+            .as_ref()
+            .and_then(|last_schedule| DateTime::parse_from_rfc3339(last_schedule).ok())
+            .map(|last_schedule| last_schedule.with_timezone(&Utc))
+            .or_else(|| {
+                self.context
+                    .resource
+                    .metadata
+                    .creation_timestamp
+                    .as_ref()
+                    .map(|time| time.0)
+            })
+            .unwrap_or(now);
+        if let Some(wait) = backup::next_backup_in(&schedule, &last_schedule, &now) {
+            self.schedule_requeue(wait);
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+
+        let mut status = BackupStatus {
+            last_schedule_time: Some(now.to_rfc3339()),
+            ..previous
+        };
+        let key = backup_config.s3.object_key(
+            &self.context.namespace(),
+            &self.context.name(),
+            &backup::object_time(&now),
+        );
+        match self.take_backup(&backup_config, &key, &now).await {
+            Ok((s3_client, manifest)) => {
+                let message = format!(
+                    "Uploaded the snapshot [{}] and [{}] transaction logs of [{}] to [{}]",
+                    manifest.snapshot().unwrap_or_default(),
+                    manifest.files.len().saturating_sub(1),
+                    manifest.pod,
+                    key
+                );
+                info!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+                status.last_successful_time = Some(now.to_rfc3339());
+                status.last_backup_snapshot = manifest.snapshot().map(str::to_string);
+                status.last_backup_bytes = manifest.bytes();
+                status.backups.push(key);
+
+                // keys which could not be deleted stay in the list to be deleted next time
+                let mut undeleted = vec![];
+                for expired in backup::expire(&mut status.backups, backup_config.retained_backups())
+                {
+                    if let Err(err) = backup::delete(&s3_client, &expired).await {
+                        warn!(
+                            "ZookeeperCluster {}: Failed to delete the expired backup [{}]: {}",
+                            self.context.log_name(),
+                            expired,
+                            err
+                        );
+                        undeleted.push(expired);
                     }
                 }
+                undeleted.append(&mut status.backups);
+                status.backups = undeleted;
+
+                self.publish_event(EventType::Normal, "BackupCompleted", &message)
+                    .await;
+                self.set_condition(
+                    &conditions,
+                    backup::BACKUP_SUCCEEDED_CONDITION,
+                    &message,
+                    "BackupCompleted",
+                    ConditionStatus::True,
+                )
+                .await?;
+            }
+            Err(err) => {
+                let message = format!("The backup [{}] failed: {}", key, err);
+                warn!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+                self.publish_event(EventType::Warning, "BackupFailed", &message)
+                    .await;
+                self.set_condition(
+                    &conditions,
+                    backup::BACKUP_SUCCEEDED_CONDITION,
+                    &message,
+                    "BackupFailed",
+                    ConditionStatus::False,
+                )
+                .await?;
             }
         }
+        self.zk_status = self
+            .context
+            .client
+            .merge_patch_status(&self.context.resource, &json!({ "backup": status }))
+            .await?
+            .status;
 
-        let status = self.zk_status.clone().ok_or_else(|| error::Error::ReconcileError(
-            "`zk_status missing, this is a programming error and should never happen. Please report in our issue tracker.".to_string(),
-        ))?;
+        if let Some(wait) = backup::next_backup_in(&schedule, &now, &now) {
+            self.schedule_requeue(wait);
+        }
+        Ok(ReconcileFunctionAction::Continue)
+    }
 
-        // If we reach here it means all pods must be running on target_version.
-        // We can now set current_version to target_version (if target_version was set) and
-        // target_version to None
-        if let Some(target_version) = &status.target_version {
-            self.zk_status = self.set_target_version(None).await?.status;
-            self.zk_status = self.set_current_version(Some(target_version)).await?.status;
-            self.zk_status = self
-                .set_upgrading_condition(
-                    &status.conditions,
-                    &format!(
-                        "No upgrade required [{:?}] is still the current_version",
-                        target_version
-                    ),
-                    "",
+    #[cfg(not(feature = "backup"))]
+    async fn run_scheduled_backup(&mut self) -> ZookeeperReconcileResult {
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Prepares the restore of the backup of `spec.restore` before the servers of a new cluster
+    /// are created: records its snapshot in `status.restore` and applies the Secret with the
+    /// files which the entrypoint of the servers downloads into their empty data directories (see
+    /// [`backup`]).
+    ///
+    /// The restore is skipped for good if the cluster already has servers, their data is not
+    /// replaced. The servers are not created as long as the backup can not be read.
+    #[cfg(feature = "backup")]
+    async fn prepare_restore(&mut self) -> ZookeeperReconcileResult {
+        let restore = match &self.context.resource.spec.restore {
+            Some(restore) => restore.clone(),
+            None => return Ok(ReconcileFunctionAction::Continue),
+        };
+        let previous = self
+            .zk_status
+            .as_ref()
+            .and_then(|status| status.restore.clone());
+        if previous
+            .as_ref()
+            .map_or(false, |previous| previous.completed_time.is_some())
+        {
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+        let conditions = self
+            .zk_status
+            .as_ref()
+            .map(|status| status.conditions.clone())
+            .unwrap_or_default();
+
+        let started_time = match previous.and_then(|previous| previous.started_time) {
+            Some(started_time) => Some(
+                DateTime::parse_from_rfc3339(&started_time)
+                    .map_err(|err| {
+                        Error::ReconcileError(format!(
+                            "The restore has the invalid start time [{}]: {}",
+                            started_time, err
+                        ))
+                    })?
+                    .with_timezone(&Utc),
+            ),
+            None if !self.existing_pods.is_empty() => {
+                let message = format!(
+                    "The cluster already has servers, the backup [{}] was not restored",
+                    restore.key
+                );
+                warn!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+                self.publish_event(EventType::Warning, "RestoreSkipped", &message)
+                    .await;
+                self.set_condition(
+                    &conditions,
+                    backup::RESTORE_SUCCEEDED_CONDITION,
+                    &message,
+                    "RestoreSkipped",
                     ConditionStatus::False,
                 )
-                .await?
-                .status;
+                .await?;
+                self.patch_restore_status(&RestoreStatus {
+                    key: restore.key.clone(),
+                    completed_time: Some(Utc::now().to_rfc3339()),
+                    ..RestoreStatus::default()
+                })
+                .await?;
+                return Ok(ReconcileFunctionAction::Continue);
+            }
+            None => None,
+        };
+
+        // the URLs are signed when the restore started, so the Secret does not change
+        let signed_at = started_time.unwrap_or_else(Utc::now);
+        match self.apply_restore_secret(&restore, &signed_at).await {
+            Ok(manifest) => {
+                if started_time.is_none() {
+                    let message = format!(
+                        "Restoring the snapshot [{}] of [{}]",
+                        manifest.snapshot().unwrap_or_default(),
+                        restore.key
+                    );
+                    info!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+                    self.publish_event(EventType::Normal, "RestoreStarted", &message)
+                        .await;
+                    self.patch_restore_status(&RestoreStatus {
+                        key: restore.key.clone(),
+                        started_time: Some(signed_at.to_rfc3339()),
+                        completed_time: None,
+                        snapshot: manifest.snapshot().map(str::to_string),
+                    })
+                    .await?;
+                }
+                Ok(ReconcileFunctionAction::Continue)
+            }
+            Err(err) => {
+                let message = format!("The restore of [{}] failed, retrying: {}", restore.key, err);
+                warn!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+                self.publish_event(EventType::Warning, "RestoreFailed", &message)
+                    .await;
+                self.set_condition(
+                    &conditions,
+                    backup::RESTORE_SUCCEEDED_CONDITION,
+                    &message,
+                    "RestoreFailed",
+                    ConditionStatus::False,
+                )
+                .await?;
+                Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(30)))
+            }
         }
+    }
+
+    #[cfg(not(feature = "backup"))]
+    async fn prepare_restore(&mut self) -> ZookeeperReconcileResult {
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Completes the restore of `spec.restore` once the servers started with the files of the
+    /// backup: deletes the Secret with the files and records the completion, which releases the
+    /// following steps, e.g. scheduled backups.
+    #[cfg(feature = "backup")]
+    async fn restore_from_backup(&mut self) -> ZookeeperReconcileResult {
+        let restore = match &self.context.resource.spec.restore {
+            Some(restore) => restore.clone(),
+            None => return Ok(ReconcileFunctionAction::Continue),
+        };
+        let previous = match self
+            .zk_status
+            .as_ref()
+            .and_then(|status| status.restore.clone())
+        {
+            Some(previous) if previous.completed_time.is_none() => previous,
+            _ => return Ok(ReconcileFunctionAction::Continue),
+        };
+        let conditions = self
+            .zk_status
+            .as_ref()
+            .map(|status| status.conditions.clone())
+            .unwrap_or_default();
+
+        let secrets_api: Api<k8s_openapi::api::core::v1::Secret> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+        apply::delete(
+            &secrets_api,
+            &restore_secret_name(&self.context.name()),
+            &self.owned_conflict_policy(),
+        )
+        .await?;
+
+        let message = format!(
+            "Restored the snapshot [{}] of [{}]",
+            previous.snapshot.as_deref().unwrap_or_default(),
+            restore.key
+        );
+        info!("ZookeeperCluster {}: {}", self.context.log_name(), message);
+        self.publish_event(EventType::Normal, "RestoreCompleted", &message)
+            .await;
+        self.set_condition(
+            &conditions,
+            backup::RESTORE_SUCCEEDED_CONDITION,
+            &message,
+            "RestoreCompleted",
+            ConditionStatus::True,
+        )
+        .await?;
+        self.patch_restore_status(&RestoreStatus {
+            completed_time: Some(Utc::now().to_rfc3339()),
+            ..previous
+        })
+        .await?;
+        Ok(ReconcileFunctionAction::Continue)
+    }
 
+    #[cfg(not(feature = "backup"))]
+    async fn restore_from_backup(&mut self) -> ZookeeperReconcileResult {
         Ok(ReconcileFunctionAction::Continue)
     }
 
-    /// Creates the config maps required for a zookeeper instance (or role, role_group combination):
-    /// * The 'zoo.cfg' properties file
-    /// * The 'myid' file
-    ///
-    /// The 'zoo.cfg' properties are read from the product_config and/or merged with the cluster
-    /// custom resource.
-    ///
-    /// Labels are automatically adapted from the `recommended_labels` with a type (data for
-    /// 'zoo.cfg' and id for 'myid'). Names are generated via `name_utils::build_resource_name`.
-    ///
-    /// Returns a map with a 'type' identifier (e.g. data, id) as key and the corresponding
-    /// ConfigMap as value. This is required to set the volume mounts in the pod later on.
-    ///
-    /// # Arguments
-    ///
-    /// - `role` - The Zookeeper role.
-    /// - `group` - The role group.
-    /// - `id` - The 'myid' for this instance.
-    /// - `validated_config` - The validated product config.
-    ///
-    async fn create_config_maps(
+    /// Reads the manifest of the backup of `restore` and applies the Secret with its files, the
+    /// URLs signed at `signed_at` (see [`backup::build_restore_secret`]). Returns the manifest.
+    #[cfg(feature = "backup")]
+    async fn apply_restore_secret(
         &self,
-        role: &str,
-        group: &str,
-        id: usize,
-        validated_config: &HashMap<PropertyNameKind, BTreeMap<String, String>>,
-    ) -> Result<HashMap<&'static str, ConfigMap>, Error> {
-        let mut config_maps = HashMap::new();
+        restore: &ZookeeperRestore,
+        signed_at: &DateTime<Utc>,
+    ) -> Result<backup::BackupManifest, Error> {
+        let credentials = s3::read_credentials(
+            &self.context.client,
+            &self.context.namespace(),
+            &restore.s3.credentials_secret,
+        )
+        .await?;
+        let s3_client = s3::S3Client::new(&restore.s3, credentials);
+        let manifest = backup::parse_manifest(&s3_client.get_object(&restore.key).await?)?;
+        let files = backup::build_restore_files(&manifest, &restore.key, &s3_client, signed_at)?;
 
-        let recommended_labels = get_recommended_labels(
-            &self.context.resource,
-            APP_NAME,
-            &self.context.resource.spec.version.to_string(),
-            role,
-            group,
-        );
+        let secrets_api: Api<k8s_openapi::api::core::v1::Secret> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+        apply::apply(
+            &secrets_api,
+            &backup::build_restore_secret(&self.context.resource, &files)?,
+            &self.owned_conflict_policy(),
+        )
+        .await?;
+        Ok(manifest)
+    }
 
-        // Get config from product-config for the zookeeper properties file (zoo.cfg)
-        if let Some(config) =
-            validated_config.get(&PropertyNameKind::File(PROPERTIES_FILE.to_string()))
-        {
-            let id_information = self.id_information.as_ref().ok_or_else(|| error::Error::ReconcileError(
-                "id_information missing, this is a programming error and should never happen. Please report in our issue tracker.".to_string(),
-            ))?;
-
-            // We need to convert from <String, String> to <String, Option<String>> to deal with
-            // CLI flags or the java properties writer etc. We can not currently represent that
-            // via operator-rs / product-config. This is a preparation for that.
-            let mut transformed_config: BTreeMap<String, Option<String>> = config
-                .iter()
-                .map(|(k, v)| (k.clone(), Some(v.clone())))
-                .collect();
-
-            // add dynamic config map requirement for server ids
-            for (node_name, id) in &id_information.node_name_to_id {
-                transformed_config.insert(
-                    format!("server.{}", id),
-                    Some(format!("{}:2888:3888", node_name)),
-                );
-            }
+    /// Returns the Secret with the files of the restore while it is in progress, the servers
+    /// created meanwhile download them (see [`backup`]).
+    #[cfg(feature = "backup")]
+    fn restore_secret(&self) -> Option<String> {
+        self.zk_status
+            .as_ref()
+            .and_then(|status| status.restore.as_ref())
+            .filter(|restore| restore.started_time.is_some() && restore.completed_time.is_none())
+            .map(|_| restore_secret_name(&self.context.name()))
+    }
 
-            let zoo_cfg =
-                product_config::writer::to_java_properties_string(transformed_config.iter())?;
+    #[cfg(not(feature = "backup"))]
+    fn restore_secret(&self) -> Option<String> {
+        None
+    }
 
-            // enhance with config map type label
-            let mut cm_config_data_labels = recommended_labels.clone();
-            cm_config_data_labels.insert(
-                configmap::CONFIGMAP_TYPE_LABEL.to_string(),
-                CONFIG_MAP_TYPE_DATA.to_string(),
-            );
+    #[cfg(feature = "backup")]
+    async fn patch_restore_status(&mut self, status: &RestoreStatus) -> Result<(), Error> {
+        self.zk_status = self
+            .context
+            .client
+            .merge_patch_status(&self.context.resource, &json!({ "restore": status }))
+            .await?
+            .status;
+        Ok(())
+    }
 
-            let cm_data_name = name_utils::build_resource_name(
-                APP_NAME,
-                &self.context.name(),
-                role,
-                Some(group),
-                None,
-                Some(CONFIG_MAP_TYPE_DATA),
-            )?;
+    /// Copies the files of a backup from the data directory of the leader to the bucket, the
+    /// manifest as the object `key` (see [`backup`]). Returns the client of the bucket and the
+    /// manifest.
+    #[cfg(feature = "backup")]
+    async fn take_backup(
+        &self,
+        backup_config: &ZookeeperBackup,
+        key: &str,
+        now: &DateTime<Utc>,
+    ) -> Result<(s3::S3Client, backup::BackupManifest), Error> {
+        // the leader has all committed transactions, a single server runs standalone
+        let leader = self.recorded_quorum().and_then(|quorum| {
+            quorum.leader.or_else(|| {
+                quorum
+                    .servers
+                    .into_iter()
+                    .find(|server| server.mode == "standalone")
+                    .map(|server| server.pod)
+            })
+        });
+        let leader = leader
+            .and_then(|leader| self.existing_pods.iter().find(|pod| pod.name() == leader))
+            .ok_or_else(|| {
+                Error::ReconcileError("There is no leader to copy the data from".to_string())
+            })?;
+
+        let credentials = s3::read_credentials(
+            &self.context.client,
+            &self.context.namespace(),
+            &backup_config.s3.credentials_secret,
+        )
+        .await?;
+        let s3_client = s3::S3Client::new(&backup_config.s3, credentials);
 
-            let mut cm_config_data = BTreeMap::new();
-            cm_config_data.insert(PROPERTIES_FILE.to_string(), zoo_cfg);
+        let manifest = backup::upload(
+            self.pod_executor.as_ref(),
+            self.context
+                .client
+                .get_namespaced_api(&self.context.namespace()),
+            leader,
+            &self.data_dir(leader),
+            &s3_client,
+            key,
+            backup::BackupManifest::new(
+                &format!("{}/{}", self.context.namespace(), self.context.name()),
+                now,
+                leader,
+            ),
+        )
+        .await?;
+        Ok((s3_client, manifest))
+    }
 
-            let cm_data = configmap::build_config_map(
-                &self.context.resource,
-                &cm_data_name,
-                &self.context.namespace(),
-                cm_config_data_labels,
-                cm_config_data,
-            )?;
+    /// Remembers to requeue after `after` unless an earlier requeue was already scheduled.
+    fn schedule_requeue(&mut self, after: Duration) {
+        self.scheduled_requeue = Some(match self.scheduled_requeue {
+            Some(scheduled) => scheduled.min(after),
+            None => after,
+        });
+    }
 
-            config_maps.insert(
-                CONFIG_MAP_TYPE_DATA,
-                configmap::create_config_map(&self.context.client, cm_data).await?,
-            );
+    /// Returns the interval of `spec.quorumReporting`, `0` if the servers are not probed
+    /// periodically.
+    fn quorum_probe_interval_seconds(&self) -> u32 {
+        self.context
+            .resource
+            .spec
+            .quorum_reporting
+            .as_ref()
+            .map_or(DEFAULT_PROBE_INTERVAL_SECONDS, |quorum_reporting| {
+                quorum_reporting.probe_interval_seconds()
+            })
+    }
+
+    /// Schedules the next probe of the servers for `status.quorum`.
+    async fn schedule_quorum_probe(&mut self) -> ZookeeperReconcileResult {
+        let interval_seconds = self.quorum_probe_interval_seconds();
+        if interval_seconds > 0 {
+            self.schedule_requeue(Duration::from_secs(u64::from(interval_seconds)));
         }
+        Ok(ReconcileFunctionAction::Continue)
+    }
 
-        // config map for the data directory (which only contains the 'myid' file)
-        let cm_config_id_name = name_utils::build_resource_name(
-            APP_NAME,
-            &self.context.name(),
-            role,
-            Some(group),
-            None,
-            Some(CONFIG_MAP_TYPE_ID),
-        )?;
+    /// The last step: requeues when the next periodic task is due (see `schedule_requeue`) or
+    /// the resync interval passed, whichever comes first. The requeue is staggered (see
+    /// [`throttle::stagger`]), so the clusters do not all come due at the same time.
+    async fn requeue_when_scheduled(&self) -> ZookeeperReconcileResult {
+        let after = match (self.scheduled_requeue, self.resync_interval) {
+            (Some(scheduled), Some(resync)) => Some(scheduled.min(resync)),
+            (scheduled, resync) => scheduled.or(resync),
+        };
+        Ok(match after {
+            Some(after) => ReconcileFunctionAction::Requeue(throttle::stagger(
+                after,
+                rand::thread_rng().gen_range(0.0..=1.0),
+            )),
+            None => ReconcileFunctionAction::Continue,
+        })
+    }
 
-        // enhance with config map type label and the id for differentiation
-        let mut cm_config_id_labels = recommended_labels.clone();
-        cm_config_id_labels.insert(
-            configmap::CONFIGMAP_TYPE_LABEL.to_string(),
-            CONFIG_MAP_TYPE_ID.to_string(),
-        );
-        cm_config_id_labels.insert(ID_LABEL.to_string(), id.to_string());
+    /// Counts the replicas whose pod is ready and whose server passes the health check, see
+    /// [`ZookeeperState::check_servers`].
+    async fn count_healthy_replicas(&self) -> usize {
+        self.check_servers()
+            .await
+            .iter()
+            .filter(|server| server.healthy)
+            .count()
+    }
 
-        let mut cm_id_data = BTreeMap::new();
-        cm_id_data.insert("myid".to_string(), id.to_string());
+    /// Runs the health check (see [`check_server_health`]) against the servers of all ready
+    /// pods, a server is healthy if it responds and is part of the quorum.
+    ///
+    /// The readiness of a pod alone is not enough: the kubelet only rechecks it periodically and
+    /// a server which lost the quorum keeps running (and stays ready) until then.
+    #[instrument(skip(self))]
+    async fn check_servers(&self) -> Vec<quorum::ServerHealth> {
+        let node_addresses = self.node_addresses();
+        let mut servers = vec![];
+        for pod in &self.existing_pods {
+            let mut server = quorum::ServerHealth {
+                pod: pod.name(),
+                id: pod_id(pod),
+                address: pod_node_name(pod).ok().map(|node_name| {
+                    ip_family::format_address(
+                        node_addresses
+                            .get(node_name)
+                            .map_or(node_name, String::as_str),
+                        client_port(pod),
+                    )
+                }),
+                ready: is_pod_condition_true(pod, "Ready"),
+                ..quorum::ServerHealth::default()
+            };
+            if !server.ready {
+                servers.push(server);
+                continue;
+            }
+            match check_server_health(pod).await {
+                Ok((true, mntr)) => {
+                    server.healthy = true;
+                    server.mntr = mntr;
+                }
+                Ok((false, mntr)) => {
+                    server.mntr = mntr;
+                    debug!(
+                        "ZookeeperCluster {}: Server in pod [{}] is not part of the quorum, not counting it as ready",
+                        self.context.log_name(),
+                        pod.name()
+                    );
+                }
+                Err(err) => debug!(
+                    "ZookeeperCluster {}: Health check of pod [{}] failed, not counting it as ready: {}",
+                    self.context.log_name(),
+                    pod.name(),
+                    err
+                ),
+            }
+            servers.push(server);
+        }
+        servers
+    }
 
-        let cm_id = configmap::build_config_map(
-            &self.context.resource,
-            &cm_config_id_name,
-            &self.context.namespace(),
-            cm_config_id_labels,
-            cm_id_data,
-        )?;
+    /// Tracks the transition towards the desired state (see [`status::track_transition`]) and
+    /// returns the status fields to patch. Completed transitions are recorded in the metrics.
+    fn update_transition(&self, desired_state: bool) -> serde_json::Value {
+        let resource = &self.context.resource;
+        let generation = match resource.metadata.generation {
+            Some(generation) => generation,
+            None => return json!({}),
+        };
 
-        config_maps.insert(
-            CONFIG_MAP_TYPE_ID,
-            configmap::create_config_map(&self.context.client, cm_id).await?,
+        let update = status::track_transition(
+            resource.status.as_ref(),
+            generation,
+            resource
+                .metadata
+                .creation_timestamp
+                .as_ref()
+                .map(|created| &created.0),
+            &Utc::now(),
+            desired_state,
         );
 
-        Ok(config_maps)
+        let mut patch = json!({ "pendingTransition": update.pending });
+        if let Some((trigger, duration)) = update.completed {
+            info!(
+                "ZookeeperCluster {}: Reached the desired state [{}] seconds after the {}",
+                self.context.log_name(),
+                duration.seconds,
+                trigger
+            );
+            self.metrics.observe_time_to_ready(
+                &self.context.namespace(),
+                &self.context.name(),
+                &trigger,
+                duration.seconds,
+            );
+            patch["lastTransitionDurations"] = json!({ trigger: duration });
+        }
+        patch
     }
 
-    /// Creates the pod required for the zookeeper instance.
-    ///
-    /// # Arguments
-    ///
-    /// - `role` - The Zookeeper role.
-    /// - `group` - The role group.
-    /// - `node_name` - The node_name for this pod.
-    /// - `id` - The 'myid' for this instance.
-    /// - `config_maps` - The config maps and respective types required for this pod.
-    /// - `validated_config` - The validated product config.
-    ///
-    async fn create_pod(
-        &self,
-        role: &str,
-        group: &str,
-        node_name: &str,
-        id: usize,
-        config_maps: &HashMap<&'static str, ConfigMap>,
-        validated_config: &HashMap<PropertyNameKind, BTreeMap<String, String>>,
-    ) -> Result<Pod, Error> {
-        let mut env_vars = vec![];
-        let mut metrics_port: Option<String> = None;
-        let mut client_port: Option<String> = None;
-        let mut admin_port: Option<String> = None;
-        let mut data_dir: Option<String> = None;
-
-        let version: &ZookeeperVersion = &self.context.resource.spec.version;
-
-        for (property_name_kind, config) in validated_config {
-            match property_name_kind {
-                PropertyNameKind::File(_) => {
-                    // we need to extract the client port here to add to container ports later
-                    client_port = config.get(CLIENT_PORT).cloned();
-                    // we need to extract the admin port here to add to container ports later
-                    admin_port = config.get(ADMIN_PORT).cloned();
-                    // we need to extract the data dir for the volume mounts later
-                    data_dir = config.get(DATA_DIR).cloned();
-                }
-                PropertyNameKind::Env => {
-                    for (property_name, property_value) in config {
-                        if property_name.is_empty() {
-                            warn!("Received empty property_name for ENV... skipping");
-                            continue;
-                        }
+    /// Returns the capabilities of the version all servers run, `None` before the initial
+    /// installation is completed.
+    fn capabilities(&self) -> Option<ZookeeperCapabilities> {
+        self.zk_status
+            .as_ref()
+            .and_then(|status| status.current_version.as_ref())
+            .map(|current_version| ZookeeperCapabilities {
+                version: current_version.to_string(),
+                features: current_version.features(),
+            })
+    }
 
-                        // if a metrics port is provided (for now by user, it is not required in
-                        // product config to be able to not configure any monitoring / metrics)
-                        if property_name == METRICS_PORT {
-                            metrics_port = Some(property_value.to_string());
-                            env_vars.push(EnvVar {
-                                name: "SERVER_JVMFLAGS".to_string(),
-                                value: Some(format!("-javaagent:{{{{packageroot}}}}/{}/stackable/lib/jmx_prometheus_javaagent-0.16.1.jar={}:{{{{packageroot}}}}/{}/stackable/conf/jmx_exporter.yaml",
-                                                    version.package_name(), property_value, version.package_name())),
-                                ..EnvVar::default()
-                            });
-                            continue;
-                        }
+    /// Runs the smoke test (see [`smoke_test`]) if the pods changed since it last succeeded and
+    /// returns the status to record, `None` if it did not run.
+    #[instrument(skip(self))]
+    async fn run_smoke_test(&self) -> Result<Option<SmokeTestStatus>, Error> {
+        let pods_fingerprint = smoke_test::pods_fingerprint(&self.existing_pods);
+        let last_fingerprint = self
+            .zk_status
+            .as_ref()
+            .and_then(|status| status.smoke_test.as_ref())
+            .map(|smoke_test| smoke_test.pods_fingerprint.as_str());
+        if last_fingerprint == Some(pods_fingerprint.as_str()) {
+            return Ok(None);
+        }
 
-                        env_vars.push(EnvVar {
-                            name: property_name.clone(),
-                            value: Some(property_value.clone()),
-                            value_from: None,
-                        });
-                    }
-                }
-                _ => {}
+        let connection_string = self
+            .existing_pods
+            .iter()
+            .map(|pod| {
+                Ok(ip_family::format_address(
+                    pod_node_name(pod)?,
+                    client_port(pod),
+                ))
+            })
+            .collect::<Result<Vec<_>, Error>>()?
+            .join(",");
+        let now = Utc::now();
+        let probe = format!("{}-{}", self.context.name(), now.timestamp_millis());
+
+        match smoke_test::run_smoke_test(self.zk_connector.as_ref(), &connection_string, &probe)
+            .await
+        {
+            Ok(latency) => {
+                info!(
+                    "ZookeeperCluster {}: The smoke test succeeded after [{}] ms",
+                    self.context.log_name(),
+                    latency.as_millis()
+                );
+                self.metrics.observe_smoke_test_latency(
+                    &self.context.namespace(),
+                    &self.context.name(),
+                    latency,
+                );
+                Ok(Some(SmokeTestStatus {
+                    pods_fingerprint,
+                    latency_milliseconds: latency.as_millis() as u64,
+                    succeeded_at: now.to_rfc3339(),
+                }))
+            }
+            Err(err) => {
+                warn!(
+                    "ZookeeperCluster {}: The smoke test failed: {}",
+                    self.context.log_name(),
+                    err
+                );
+                self.metrics
+                    .inc_smoke_test_failures(&self.context.namespace(), &self.context.name());
+                Err(err)
             }
         }
+    }
+
+    /// Writes the ready replica count, the observed generation, the capabilities, the transition
+    /// durations (see [`ZookeeperState::update_transition`]) and the summary conditions (see
+    /// [`status::compute_conditions`]) to the status, based on the `outcome` of the
+    /// reconciliation. Once the ensemble looks healthy after pods changed, the desired state is
+    /// only reached if the smoke test succeeds as well.
+    #[instrument(skip(self, outcome))]
+    async fn update_status(&mut self, outcome: &ZookeeperReconcileResult) -> Result<(), Error> {
+        if self.context.resource.metadata.deletion_timestamp.is_some() {
+            return Ok(());
+        }
 
-        let pod_name = name_utils::build_resource_name(
-            APP_NAME,
+        // a stopped cluster is in its desired state without any servers
+        let desired_replicas = if self.zk_spec.stopped {
+            0
+        } else {
+            desired_replicas(&self.eligible_nodes)
+        };
+        let servers = self.check_servers().await;
+        let ready_replicas = servers.iter().filter(|server| server.healthy).count();
+        self.metrics.set_ready_replicas(
+            &self.context.namespace(),
             &self.context.name(),
-            role,
-            Some(group),
-            Some(node_name),
-            None,
-        )?;
+            ready_replicas,
+        );
+        self.metrics.set_ensemble_metrics(
+            &self.context.namespace(),
+            &self.context.name(),
+            &servers,
+        );
+        let upgrading = self
+            .zk_status
+            .as_ref()
+            .map(|status| status.target_version.is_some())
+            .unwrap_or(false);
 
-        let mut container_builder = ContainerBuilder::new(APP_NAME);
-        container_builder.image(format!("stackable/zookeeper:{}", version.to_string()));
-        container_builder.command(vec![
-            format!(
-                "{}/bin/zkServer.sh",
-                self.context.resource.spec.version.package_name()
-            ),
-            "start-foreground".to_string(),
-            // "--config".to_string(), TODO: Version 3.4 does not support --config but later versions do
-            format!("{{{{configroot}}}}/{}/zoo.cfg", CONFIG_DIR_NAME),
-        ]);
-
-        // One mount for the config directory
-        if let Some(config_map_data) = config_maps.get(CONFIG_MAP_TYPE_DATA) {
-            if let Some(name) = config_map_data.metadata.name.as_ref() {
-                container_builder.add_configmapvolume(name, CONFIG_DIR_NAME.to_string());
-            } else {
-                return Err(error::Error::MissingConfigMapNameError {
-                    cm_type: CONFIG_MAP_TYPE_DATA,
-                });
+        // all steps ran if a periodic task scheduled the requeue, the ensemble is not progressing
+        let completed = Ok(ReconcileFunctionAction::Continue);
+        let ensemble_outcome = match (outcome, self.scheduled_requeue) {
+            (Ok(ReconcileFunctionAction::Requeue(_)), Some(_)) => &completed,
+            _ => outcome,
+        };
+        let mut cluster_conditions = status::compute_conditions(&status::EnsembleState {
+            desired_replicas,
+            ready_replicas,
+            upgrading,
+            outcome: ensemble_outcome,
+        });
+        cluster_conditions.push(status::compute_stalled_condition(outcome));
+        if let Some(canary) = self
+            .canary_status()
+            .filter(|canary| canary.phase == CanaryPhase::Failed)
+        {
+            status::apply_canary_failure(
+                &mut cluster_conditions,
+                &canary.message.unwrap_or_default(),
+            );
+        }
+        if let Some(check) = self
+            .integrity_check_status()
+            .filter(|check| check.phase == IntegrityCheckPhase::Failed)
+        {
+            status::apply_integrity_check_failure(
+                &mut cluster_conditions,
+                &check.message.unwrap_or_default(),
+            );
+        }
+        // the smoke test writes to the ensemble, which a paused cluster or a dry run must not do
+        let mut smoke_test_status = None;
+        if status::is_desired_state(&cluster_conditions)
+            && !is_paused(&self.context.resource)
+            && self.dry_run == DryRunMode::Disabled
+        {
+            match self.run_smoke_test().await {
+                Ok(status) => smoke_test_status = status,
+                Err(err) => status::apply_smoke_test_failure(&mut cluster_conditions, &err),
             }
-        } else {
-            return Err(error::Error::MissingConfigMapError {
-                cm_type: CONFIG_MAP_TYPE_DATA,
-                pod_name,
-            });
         }
 
-        // We need a second mount for the data directory
-        // because we need to write the 'myid' file into the data directory
-        if let Some(config_map_data) = config_maps.get(CONFIG_MAP_TYPE_ID) {
-            if let Some(name) = config_map_data.metadata.name.as_ref() {
-                container_builder.add_configmapvolume(
-                    name,
-                    data_dir.unwrap_or_else(|| "/tmp/zookeeper".to_string()),
-                );
-            } else {
-                return Err(error::Error::MissingConfigMapNameError {
-                    cm_type: CONFIG_MAP_TYPE_ID,
-                });
+        let mut patch = self.update_transition(status::is_desired_state(&cluster_conditions));
+        if let Some(smoke_test_status) = smoke_test_status {
+            patch["smokeTest"] = json!(smoke_test_status);
+        }
+        patch["readyReplicas"] = json!(ready_replicas);
+        let now = Utc::now();
+        let quorum_status = quorum::build_status(&servers, &now.to_rfc3339());
+        let previous_leader = self
+            .zk_status
+            .as_ref()
+            .and_then(|status| status.quorum.as_ref())
+            .and_then(|quorum| quorum.leader.as_ref());
+        if let (Some(previous_leader), Some(leader)) = (previous_leader, &quorum_status.leader) {
+            if previous_leader != leader {
+                self.metrics
+                    .inc_leader_changes(&self.context.namespace(), &self.context.name());
             }
-        } else {
-            return Err(error::Error::MissingConfigMapError {
-                cm_type: CONFIG_MAP_TYPE_ID,
-                pod_name,
+        }
+        let probe_interval_seconds = self.quorum_probe_interval_seconds();
+        if quorum::is_update_due(
+            self.zk_status
+                .as_ref()
+                .and_then(|status| status.quorum.as_ref()),
+            &quorum_status,
+            now,
+            Some(ChronoDuration::seconds(i64::from(probe_interval_seconds)))
+                .filter(|_| probe_interval_seconds > 0),
+        ) {
+            patch["quorum"] = json!(quorum_status);
+        }
+        patch["members"] = json!(quorum::build_members(&servers));
+        patch["replicas"] = json!(self.existing_pods.len());
+        patch["selector"] = json!(build_pod_selector(&self.context.name()));
+        patch["observedGeneration"] = json!(self.context.resource.metadata.generation);
+        patch["waiting"] = json!(self.waiting);
+        patch["rollout"] = json!(self.rollout);
+        if let (Some(fingerprint), DryRunMode::Disabled) = (&self.applied_fingerprint, self.dry_run)
+        {
+            patch["lastApplied"] = json!(LastAppliedStatus {
+                generation: self
+                    .context
+                    .resource
+                    .metadata
+                    .generation
+                    .unwrap_or_default(),
+                fingerprint: fingerprint.clone(),
+                applied_at: now.to_rfc3339(),
             });
         }
+        if let Some(capabilities) = self.capabilities() {
+            patch["capabilities"] = json!(capabilities);
+        }
+        patch["effectiveSpec"] = json!(defaulting::default_spec(
+            &self.context.resource.spec,
+            replicas_per_role_group(&self.eligible_nodes),
+        ));
 
-        container_builder.add_env_vars(env_vars);
+        // most reconciliations do not change the status, which then does not need to be written
+        let current_status = serde_json::to_value(&self.zk_status)?;
+        let mut status = match diff::prune_merge_patch(&patch, &current_status) {
+            Some(patch) => {
+                self.context
+                    .client
+                    .merge_patch_status(&self.context.resource, &patch)
+                    .await?
+                    .status
+            }
+            None => self.zk_status.clone(),
+        };
 
-        let mut annotations = BTreeMap::new();
-        // only add metrics container port and annotation if available
-        if let Some(metrics_port) = metrics_port {
-            annotations.insert(SHOULD_BE_SCRAPED.to_string(), "true".to_string());
-            container_builder.add_container_port(
-                ContainerPortBuilder::new(metrics_port.parse()?)
-                    .name("metrics")
-                    .build(),
-            );
-        }
-        // add client port if available
-        if let Some(client_port) = client_port {
-            container_builder.add_container_port(
-                ContainerPortBuilder::new(client_port.parse()?)
-                    .name("client")
-                    .build(),
-            );
+        for condition in cluster_conditions {
+            let conditions = status
+                .as_ref()
+                .map(|status| status.conditions.clone())
+                .unwrap_or_default();
+            if is_condition_current(
+                &conditions,
+                &condition,
+                self.context.resource.metadata.generation,
+            ) {
+                continue;
+            }
+            let condition_status = if condition.status {
+                ConditionStatus::True
+            } else {
+                ConditionStatus::False
+            };
+            status = self
+                .set_condition(
+                    &conditions,
+                    condition.condition_type,
+                    &condition.message,
+                    condition.reason,
+                    condition_status,
+                )
+                .await?
+                .status;
         }
 
-        // add admin port if available
-        if let Some(admin_port) = admin_port {
-            container_builder.add_container_port(
-                ContainerPortBuilder::new(admin_port.parse()?)
-                    .name("admin")
-                    .build(),
-            );
+        // Nothing is blocked anymore once a reconciliation succeeded without waiting.
+        if outcome.is_ok() && self.waiting.is_none() {
+            let conditions = status
+                .as_ref()
+                .map(|status| status.conditions.clone())
+                .unwrap_or_default();
+            self.clear_waiting_for_disruption_budget(&conditions)
+                .await?;
         }
 
-        let mut pod_labels = get_recommended_labels(
-            &self.context.resource,
-            APP_NAME,
-            &self.context.resource.spec.version.to_string(),
-            role,
-            group,
-        );
-        // we need to add the zookeeper id to the labels
-        pod_labels.insert(ID_LABEL.to_string(), id.to_string());
-
-        let pod = PodBuilder::new()
-            .metadata(
-                ObjectMetaBuilder::new()
-                    .generate_name(pod_name)
-                    .namespace(&self.context.client.default_namespace)
-                    .with_labels(pod_labels)
-                    .with_annotations(annotations)
-                    .ownerreference_from_resource(&self.context.resource, Some(true), Some(true))?
-                    .build()?,
-            )
-            .add_stackable_agent_tolerations()
-            .add_container(container_builder.build())
-            .node_name(node_name)
-            .build()?;
+        self.zk_status = status;
+        Ok(())
+    }
 
-        Ok(self.context.client.create(&pod).await?)
+    /// Orphans the objects of the deleted cluster if its deletion policy is `Orphan`, see
+    /// [`deletion`]. They are orphaned before the finalizer is removed, so they can not be
+    /// garbage collected in the meantime.
+    async fn orphan_objects_on_deletion(&self) -> ZookeeperReconcileResult {
+        let cluster = &self.context.resource;
+        if cluster.metadata.deletion_timestamp.is_some()
+            && cluster.spec.deletion_policy == DeletionPolicy::Orphan
+        {
+            deletion::orphan_objects(
+                &self.context.client,
+                cluster,
+                &self.existing_pods,
+                &build_pod_selector(&self.context.name()),
+            )
+            .await?;
+        }
+        Ok(ReconcileFunctionAction::Continue)
     }
 
+    /// Deletes the pods of the deleted cluster unless they were orphaned, see
+    /// `orphan_objects_on_deletion`.
     async fn delete_all_pods(&self) -> OperatorResult<ReconcileFunctionAction> {
+        if self.context.resource.spec.deletion_policy == DeletionPolicy::Orphan {
+            return Ok(ReconcileFunctionAction::Done);
+        }
         for pod in &self.existing_pods {
             self.context.client.delete(pod).await?;
         }
         Ok(ReconcileFunctionAction::Done)
     }
+
+    /// Only refreshes the status of a settled cluster after the status of a pod changed, see
+    /// [`status_refresh`], and requeues it when the next full reconciliation is due.
+    async fn refresh_status(
+        &mut self,
+        remaining: Duration,
+        started: std::time::Instant,
+    ) -> ReconcileFunctionAction {
+        debug!(
+            "ZookeeperCluster {}: Only the status of pods changed, refreshing the status",
+            self.context.log_name()
+        );
+        // the requeue was scheduled by the last full reconciliation, which ran all steps
+        self.scheduled_requeue = Some(remaining);
+        let result = Ok(ReconcileFunctionAction::Requeue(remaining));
+        if let Err(err) = self.update_status(&result).await {
+            warn!(
+                "ZookeeperCluster {}: Failed to update status: {}",
+                self.context.log_name(),
+                err
+            );
+        }
+        self.metrics
+            .observe_reconcile_duration("cluster", started.elapsed());
+        self.metrics.record_successful_reconcile();
+        let key = ObjectRef::from_obj(&self.context.resource);
+        self.record_reconcile_state(&key, &result, started.elapsed());
+        ReconcileFunctionAction::Requeue(remaining)
+    }
+
+    /// Returns after which time the cluster is reconciled again if the reconciliation with
+    /// `result` ran all steps without waiting, so only the status needs to be refreshed until
+    /// then, otherwise `None`.
+    fn settled_requeue(&self, result: &ZookeeperReconcileResult) -> Option<Duration> {
+        if self.waiting.is_some() {
+            return None;
+        }
+        match (result, self.scheduled_requeue) {
+            (Ok(ReconcileFunctionAction::Requeue(after)), Some(_)) => Some(*after),
+            (Ok(ReconcileFunctionAction::Continue), _) | (Ok(ReconcileFunctionAction::Done), _) => {
+                Some(Duration::from_secs(status_refresh::MAX_REFRESH_SECONDS))
+            }
+            _ => None,
+        }
+    }
+
+    /// Records the outcome of this reconciliation, see [`reconcile_state`].
+    fn record_reconcile_state(
+        &self,
+        key: &ObjectRef,
+        result: &ZookeeperReconcileResult,
+        duration: Duration,
+    ) {
+        let now = Utc::now().to_rfc3339();
+        let (outcome, requeue_after_seconds) = reconcile_state::outcome(result);
+        let conditions = self
+            .zk_status
+            .as_ref()
+            .map(|status| status.conditions.as_slice())
+            .unwrap_or_default();
+        let phase = reconcile_state::phase(
+            outcome,
+            is_paused(&self.context.resource),
+            self.waiting.is_some(),
+            conditions,
+        );
+        self.usage_statistics.record_reconcile(
+            key.clone(),
+            reconcile_state::ClusterReconcileState {
+                last_reconcile: now.clone(),
+                duration_millis: duration.as_millis() as u64,
+                outcome,
+                requeue_after_seconds,
+                phase,
+                last_error: result
+                    .as_ref()
+                    .err()
+                    .map(|err| reconcile_state::ReconcileFailure {
+                        time: now,
+                        message: err.to_string(),
+                    }),
+            },
+        );
+    }
+
+    /// Runs all reconciliation steps in order, stopping at the first one that does not continue.
+    async fn reconcile_steps(&mut self) -> ZookeeperReconcileResult {
+        self.init_status()
+            .await?
+            .then(self.orphan_objects_on_deletion())
+            .await?
+            .then(self.context.handle_deletion(
+                Box::pin(self.delete_all_pods()),
+                FINALIZER_NAME,
+                true,
+            ))
+            .await?
+            .then(self.check_paused())
+            .await?
+            .then(self.validate_spec())
+            .await?
+            .then(self.validate_config())
+            .await?
+            .then(self.plan_dry_run())
+            .await?
+            .then(self.detect_tampering())
+            .await?
+            .then(self.reconcile_certificate())
+            .await?
+            .then(self.track_references())
+            .await?
+            .then(self.reconcile_cluster_objects())
+            .await?
+            .then(self.stop_servers())
+            .await?
+            .then(self.context.delete_illegal_pods(
+                self.existing_pods.as_slice(),
+                &self.get_required_labels(),
+                ContinuationStrategy::OneRequeue,
+            ))
+            .await?
+            .then(self.replace_failed_members())
+            .await?
+            .then(self.verify_canary())
+            .await?
+            .then(
+                self.context
+                    .wait_for_terminating_pods(self.existing_pods.as_slice()),
+            )
+            .await?
+            .then(self.update_synced_conditions())
+            .await?
+            .then(self.check_pod_scheduling())
+            .await?
+            .then(
+                self.context
+                    .wait_for_running_and_ready_pods(&self.existing_pods),
+            )
+            .await?
+            .then(self.delete_excess_pods())
+            .await?
+            .then(self.read_existing_pod_information())
+            .await?
+            .then(self.assign_ids())
+            .await?
+            .then(self.reconcile_federation())
+            .await?
+            .then(self.build_data_config_maps())
+            .await?
+            .then(self.delete_obsolete_config_maps())
+            .await?
+            .then(self.prepare_restore())
+            .await?
+            .then(self.create_missing_pods())
+            .await?
+            .then(self.upgrade_pods())
+            .await?
+            .then(self.plan_rollout())
+            .await?
+            .then(self.restart_pods_with_outdated_config())
+            .await?
+            .then(self.restart_pods_with_outdated_certificate())
+            .await?
+            .then(self.restart_pods_with_outdated_references())
+            .await?
+            .then(self.complete_scale_step())
+            .await?
+            .then(self.restart_pods_on_request())
+            .await?
+            .then(self.restore_from_backup())
+            .await?
+            .then(self.reconcile_external_access())
+            .await?
+            .then(self.update_client_rebalance_hint())
+            .await?
+            .then(self.sync_standby())
+            .await?
+            .then(self.run_scheduled_backup())
+            .await?
+            .then(self.schedule_quorum_probe())
+            .await?
+            .then(self.requeue_when_scheduled())
+            .await
+    }
 }
 
 impl ReconciliationState for ZookeeperState {
@@ -936,55 +6393,204 @@ impl ReconciliationState for ZookeeperState {
     {
         info!("========================= Starting reconciliation =========================");
 
-        Box::pin(async move {
-            self.init_status()
-                .await?
-                .then(self.context.handle_deletion(
-                    Box::pin(self.delete_all_pods()),
-                    FINALIZER_NAME,
-                    true,
-                ))
-                .await?
-                .then(self.context.delete_illegal_pods(
-                    self.existing_pods.as_slice(),
-                    &self.get_required_labels(),
-                    ContinuationStrategy::OneRequeue,
-                ))
-                .await?
-                .then(
-                    self.context
-                        .wait_for_terminating_pods(self.existing_pods.as_slice()),
-                )
-                .await?
-                .then(
-                    self.context
-                        .wait_for_running_and_ready_pods(&self.existing_pods),
-                )
-                .await?
-                .then(self.context.delete_excess_pods(
-                    list_eligible_nodes_for_role_and_group(&self.eligible_nodes).as_slice(),
+        let span = info_span!(
+            "reconcile",
+            namespace = %self.context.namespace(),
+            cluster = %self.context.name()
+        );
+        Box::pin(
+            async move {
+                let started = std::time::Instant::now();
+                if let Some(remaining) = self.status_refresh.status_only(
+                    &self.context.resource,
                     &self.existing_pods,
-                    ContinuationStrategy::OneRequeue,
-                ))
-                .await?
-                .then(self.read_existing_pod_information())
-                .await?
-                .then(self.assign_ids())
-                .await?
-                .then(self.create_missing_pods())
-                .await
-        })
+                    started,
+                ) {
+                    return Ok(self.refresh_status(remaining, started).await);
+                }
+                let result = self.reconcile_steps().await;
+                self.metrics
+                    .observe_reconcile_duration("cluster", started.elapsed());
+
+                if let Err(err) = &result {
+                    self.metrics.inc_reconcile_errors("cluster", err);
+                    self.publish_event(EventType::Warning, "ReconcileFailed", &err.to_string())
+                        .await;
+                }
+
+                if let Err(err) = self.update_status(&result).await {
+                    warn!(
+                        "ZookeeperCluster {}: Failed to update status: {}",
+                        self.context.log_name(),
+                        err
+                    );
+                }
+
+                let key = ObjectRef::from_obj(&self.context.resource);
+                if self.context.resource.metadata.deletion_timestamp.is_none() {
+                    self.record_reconcile_state(&key, &result, started.elapsed());
+                }
+                self.status_refresh.reconciled(
+                    &self.context.resource,
+                    &self.existing_pods,
+                    self.settled_requeue(&result),
+                    started,
+                );
+
+                // failures are retried depending on their class instead of the fixed interval
+                // the framework would requeue errors with: transient errors with a backoff,
+                // conflicts right away and terminal errors not at all, the `Stalled` condition
+                // tells why
+                match result {
+                    Ok(action) => {
+                        self.backoff.reset(&key);
+                        self.metrics.record_successful_reconcile();
+                        Ok(action)
+                    }
+                    Err(err) if err.class() == ErrorClass::Terminal => {
+                        self.backoff.reset(&key);
+                        error!(
+                            "ZookeeperCluster {}: Reconciliation failed, not retrying until the cluster is changed: {}",
+                            self.context.log_name(),
+                            err
+                        );
+                        Ok(ReconcileFunctionAction::Done)
+                    }
+                    Err(err) => {
+                        let retry_after = match err.class() {
+                            ErrorClass::Conflict => self.backoff.conflicted(&key),
+                            _ => self.backoff.failed(&key),
+                        };
+                        error!(
+                            "ZookeeperCluster {}: Reconciliation failed, retrying in {:?}: {}",
+                            self.context.log_name(),
+                            retry_after,
+                            err
+                        );
+                        Ok(ReconcileFunctionAction::Requeue(retry_after))
+                    }
+                }
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// Selects which kinds of child resources are managed (i.e. created and updated) by the operator.
+///
+/// Disabling a kind allows teams with existing platform automation to keep owning those objects
+/// while adopting the operator incrementally.
+#[derive(Clone, Debug)]
+pub struct ManagedResources {
+    /// If disabled, the ConfigMaps (`zoo.cfg` and `myid`) are expected to exist under the names
+    /// the operator would have generated and are only read, never written.
+    pub config_maps: bool,
+    /// If disabled, no PodMonitors (and PrometheusRules for `spec.monitoring.alerts`) are created
+    /// for clusters with `spec.monitoring`, e.g. because their metrics are scraped by other means.
+    /// They are only created if the operator was built with the `prometheus-operator` feature and
+    /// the Prometheus Operator CRDs are installed.
+    pub pod_monitors: bool,
+}
+
+impl Default for ManagedResources {
+    fn default() -> Self {
+        ManagedResources {
+            config_maps: true,
+            pod_monitors: true,
+        }
+    }
+}
+
+/// The configuration of the cluster controller.
+#[derive(Clone, Debug)]
+pub struct ControllerConfig {
+    pub managed_resources: ManagedResources,
+    /// Whether reconciliation fails if the spec of a cluster contains unknown fields.
+    pub strict_spec_validation: bool,
+    /// The dry-run mode of clusters without the [`dry_run::DRY_RUN_ANNOTATION`].
+    pub dry_run: DryRunMode,
+    /// The policy for clusters without `spec.ensembleSizePolicy`.
+    pub ensemble_size_policy: EnsembleSizePolicy,
+    /// The time after which a failed reconciliation is retried, it doubles with every further
+    /// consecutive failure of the same cluster up to `max_requeue_interval`, see [`backoff`].
+    pub requeue_interval: Duration,
+    pub max_requeue_interval: Duration,
+    /// If set, clusters are reconciled again after this time even if nothing changed.
+    pub resync_interval: Option<Duration>,
+    /// Limits the reconciliations of all controllers created with (clones of) this config.
+    pub throttle: ReconcileThrottle,
+}
+
+impl Default for ControllerConfig {
+    fn default() -> Self {
+        ControllerConfig {
+            managed_resources: ManagedResources::default(),
+            strict_spec_validation: false,
+            dry_run: DryRunMode::Disabled,
+            ensemble_size_policy: EnsembleSizePolicy::Warn,
+            requeue_interval: Duration::from_secs(10),
+            max_requeue_interval: Duration::from_secs(300),
+            resync_interval: None,
+            throttle: ReconcileThrottle::default(),
+        }
     }
 }
 
-struct ZookeeperStrategy {
+/// The reconciliation of ZookeeperClusters, built with [`ControllerBuilder::build_strategy`] to
+/// run it in a [`Controller`] of an embedding binary.
+pub struct ZookeeperStrategy {
     config: Arc<ProductConfigManager>,
+    managed_resources: ManagedResources,
+    strict_spec_validation: bool,
+    dry_run: DryRunMode,
+    ensemble_size_policy: EnsembleSizePolicy,
+    usage_statistics: UsageStatistics,
+    metrics: Metrics,
+    image_resolver: Arc<dyn ImageResolver>,
+    zk_connector: Arc<dyn ZookeeperConnector>,
+    pod_executor: Arc<dyn PodExecutor>,
+    backoff: Backoff,
+    status_refresh: StatusRefresh,
+    resync_interval: Option<Duration>,
+    throttle: ReconcileThrottle,
+    references: ReferenceIndex,
+    cache: OwnedObjectCache,
 }
 
 impl ZookeeperStrategy {
-    pub fn new(config: ProductConfigManager) -> ZookeeperStrategy {
+    pub fn new(
+        config: ProductConfigManager,
+        managed_resources: ManagedResources,
+        strict_spec_validation: bool,
+        dry_run: DryRunMode,
+        ensemble_size_policy: EnsembleSizePolicy,
+        usage_statistics: UsageStatistics,
+        metrics: Metrics,
+        image_resolver: Arc<dyn ImageResolver>,
+        backoff: Backoff,
+        status_refresh: StatusRefresh,
+        resync_interval: Option<Duration>,
+        throttle: ReconcileThrottle,
+        references: ReferenceIndex,
+        cache: OwnedObjectCache,
+    ) -> ZookeeperStrategy {
         ZookeeperStrategy {
             config: Arc::new(config),
+            managed_resources,
+            strict_spec_validation,
+            dry_run,
+            ensemble_size_policy,
+            usage_statistics,
+            metrics,
+            image_resolver,
+            zk_connector: zk_client::default_connector(),
+            pod_executor: pod_exec::default_executor(),
+            backoff,
+            status_refresh,
+            resync_interval,
+            throttle,
+            references,
+            cache,
         }
     }
 }
@@ -1002,12 +6608,27 @@ impl ControllerStrategy for ZookeeperStrategy {
         &self,
         context: ReconciliationContext<Self::Item>,
     ) -> Result<Self::State, Self::Error> {
-        let existing_pods = context
-            .list_owned(build_common_labels_for_all_managed_resources(
-                APP_NAME,
-                &context.resource.name(),
-            ))
-            .await?;
+        let reconcile_permit = self
+            .throttle
+            .acquire(throttle::ReconcilePriority::of(&context.resource))
+            .await;
+        self.usage_statistics.record(&context.resource);
+        self.metrics
+            .set_managed_clusters(self.usage_statistics.cluster_count());
+        if context.resource.metadata.deletion_timestamp.is_some() {
+            self.metrics
+                .remove_cluster(&context.namespace(), &context.resource.name());
+            self.backoff.reset(&ObjectRef::from_obj(&context.resource));
+            self.references
+                .remove(&ObjectRef::from_obj(&context.resource));
+        }
+
+        let pod_labels =
+            build_common_labels_for_all_managed_resources(APP_NAME, &context.resource.name());
+        let existing_pods = match self.cache.pods.list_owned(&context.resource, &pod_labels) {
+            Some(pods) => pods,
+            None => context.list_owned(pod_labels).await?,
+        };
         trace!(
             "{}: Found [{}] pods",
             context.log_name(),
@@ -1023,33 +6644,57 @@ impl ControllerStrategy for ZookeeperStrategy {
             role_utils::find_nodes_that_fit_selectors(&context.client, None, &zk_spec.servers)
                 .await?,
         );
-
-        let mut roles = HashMap::new();
-        roles.insert(
-            ZookeeperRole::Server.to_string(),
-            (
-                vec![
-                    PropertyNameKind::File(PROPERTIES_FILE.to_string()),
-                    PropertyNameKind::Env,
-                ],
-                context.resource.spec.servers.clone().into(),
+        scheduling::apply_placement(
+            &mut eligible_nodes,
+            zk_spec.placement.as_ref(),
+            &scheduling::platform_requirements(
+                zk_spec.placement.as_ref(),
+                &self.image_resolver.architectures(),
             ),
+            &existing_pods,
         );
+        apply_spec_replicas(&mut eligible_nodes, zk_spec.replicas);
 
-        let role_config = transform_all_roles_to_config(&context.resource, roles);
-        let validated_role_config = validate_all_roles_and_groups_config(
-            &context.resource.spec.version.to_string(),
-            &role_config,
-            &self.config,
-            false,
-            false,
-        )?;
+        let validated_role_config = validate_role_config(&context.resource, &self.config)?;
 
         Ok(ZookeeperState {
+            managed_resources: self.managed_resources.clone(),
+            strict_spec_validation: self.strict_spec_validation,
+            dry_run: dry_run::mode(&context.resource, self.dry_run),
+            ensemble_size_policy: context
+                .resource
+                .spec
+                .ensemble_size_policy
+                .unwrap_or(self.ensemble_size_policy),
+            metrics: self.metrics.clone(),
+            image_resolver: self.image_resolver.clone(),
+            zk_connector: self.zk_connector.clone(),
+            pod_executor: self.pod_executor.clone(),
+            backoff: self.backoff.clone(),
+            status_refresh: self.status_refresh.clone(),
+            resync_interval: self.resync_interval,
+            _reconcile_permit: reconcile_permit,
+            usage_statistics: self.usage_statistics.clone(),
             zk_spec: context.resource.spec.clone(),
             zk_status: context.resource.status.clone(),
+            rollout: context
+                .resource
+                .status
+                .as_ref()
+                .and_then(|status| status.rollout.clone()),
             context,
             id_information: None,
+            federation_members: vec![],
+            data_config_maps: BTreeMap::new(),
+            scale_step: ScaleStep::Unlimited,
+            certificate_revision: None,
+            references: self.references.clone(),
+            cache: self.cache.clone(),
+            references_hash: None,
+            applied_fingerprint: None,
+            disruptions_paused: false,
+            waiting: None,
+            scheduled_requeue: None,
             existing_pods,
             eligible_nodes,
             validated_role_config,
@@ -1057,33 +6702,319 @@ impl ControllerStrategy for ZookeeperStrategy {
     }
 }
 
+/// Returns the clusters to reconcile after the referenced Secret or ConfigMap `object` changed,
+/// which need a full reconciliation.
+fn referencing_clusters<K: ResourceExt>(
+    references: &ReferenceIndex,
+    status_refresh: &StatusRefresh,
+    kind: ReferenceKind,
+    object: &K,
+) -> Vec<reflector::ObjectRef<ZookeeperCluster>> {
+    references
+        .clusters_referencing(kind, &ObjectRef::from_obj(object))
+        .into_iter()
+        .map(|cluster| {
+            status_refresh.changed(&cluster);
+            reflector::ObjectRef::new(&cluster.name).within(&cluster.namespace)
+        })
+        .collect()
+}
+
 /// This creates an instance of a [`Controller`] which waits for incoming events and reconciles them.
+/// It watches the clusters in `namespace` or, if `None`, in all namespaces.
 ///
 /// This is an async method and the returned future needs to be consumed to make progress.
-pub async fn create_controller(client: Client, product_config_path: &str) -> OperatorResult<()> {
-    let zk_api: Api<ZookeeperCluster> = client.get_all_api();
-    let pods_api: Api<Pod> = client.get_all_api();
-    let config_maps_api: Api<ConfigMap> = client.get_all_api();
+pub async fn create_controller(
+    client: Client,
+    namespace: Option<String>,
+    product_config_path: &str,
+    config: ControllerConfig,
+    usage_statistics: UsageStatistics,
+    metrics: Metrics,
+    image_resolver: Arc<dyn ImageResolver>,
+) -> OperatorResult<()> {
+    let product_config = ProductConfigManager::from_yaml_file(product_config_path).unwrap();
 
-    let controller = Controller::new(zk_api)
-        .owns(pods_api, ListParams::default())
-        .owns(config_maps_api, ListParams::default());
+    ControllerBuilder::new(client, product_config, metrics)
+        .namespace(namespace)
+        .config(config)
+        .usage_statistics(usage_statistics)
+        .image_resolver(image_resolver)
+        .run()
+        .await
+}
 
-    let product_config = ProductConfigManager::from_yaml_file(product_config_path).unwrap();
+/// Builds the cluster controller for embedding it into another binary, e.g. to run it together
+/// with the controllers of other operators.
+///
+/// Everything the controller shares with the rest of the binary is injected: the client, the
+/// product config and the [`Metrics`], which are registered in the registry passed to
+/// [`Metrics::with_registry`] instead of a global one. The other settings default to the ones of
+/// the operator binary.
+pub struct ControllerBuilder {
+    client: Client,
+    product_config: ProductConfigManager,
+    metrics: Metrics,
+    namespace: Option<String>,
+    config: ControllerConfig,
+    usage_statistics: UsageStatistics,
+    image_resolver: Arc<dyn ImageResolver>,
+}
 
-    let strategy = ZookeeperStrategy::new(product_config);
+impl ControllerBuilder {
+    pub fn new(
+        client: Client,
+        product_config: ProductConfigManager,
+        metrics: Metrics,
+    ) -> ControllerBuilder {
+        ControllerBuilder {
+            client,
+            product_config,
+            metrics,
+            namespace: None,
+            config: ControllerConfig::default(),
+            usage_statistics: UsageStatistics::default(),
+            image_resolver: Arc::new(DefaultImageResolver::default()),
+        }
+    }
 
-    controller
-        .run(client, strategy, Duration::from_secs(10))
-        .await;
+    /// Only watches the clusters in `namespace`, all namespaces if `None` (the default).
+    pub fn namespace(mut self, namespace: Option<String>) -> ControllerBuilder {
+        self.namespace = namespace;
+        self
+    }
+
+    pub fn config(mut self, config: ControllerConfig) -> ControllerBuilder {
+        self.config = config;
+        self
+    }
+
+    /// Records the reconciled clusters, shared with [`serve_usage_report`].
+    pub fn usage_statistics(mut self, usage_statistics: UsageStatistics) -> ControllerBuilder {
+        self.usage_statistics = usage_statistics;
+        self
+    }
+
+    pub fn image_resolver(mut self, image_resolver: Arc<dyn ImageResolver>) -> ControllerBuilder {
+        self.image_resolver = image_resolver;
+        self
+    }
+
+    /// Builds the reconciliation of ZookeeperClusters for running it in a [`Controller`] of the
+    /// embedding binary, which decides what triggers reconciliations.
+    ///
+    /// Without the watches of [`ControllerBuilder::run`], changes of the Secrets and ConfigMaps
+    /// referenced by a cluster only trigger a reconciliation if the embedding controller watches
+    /// them, and the owned objects are always listed from the API server.
+    pub async fn build_strategy(self) -> ZookeeperStrategy {
+        #[cfg(feature = "prometheus-operator")]
+        let managed_resources = ManagedResources {
+            pod_monitors: self.config.managed_resources.pod_monitors
+                && prometheus_operator::is_installed(&self.client).await,
+            ..self.config.managed_resources.clone()
+        };
+        #[cfg(not(feature = "prometheus-operator"))]
+        let managed_resources = self.config.managed_resources.clone();
+        // the caches are never synchronized without their watches
+        let (cache, _) = OwnedObjectCache::new(
+            scoped_api(&self.client, self.namespace.as_deref()),
+            scoped_api(&self.client, self.namespace.as_deref()),
+            scoped_api(&self.client, self.namespace.as_deref()),
+            ListParams::default(),
+            self.metrics.clone(),
+        );
+
+        self.into_strategy(
+            managed_resources,
+            ReferenceIndex::default(),
+            StatusRefresh::default(),
+            cache,
+        )
+    }
+
+    fn into_strategy(
+        self,
+        managed_resources: ManagedResources,
+        references: ReferenceIndex,
+        status_refresh: StatusRefresh,
+        cache: OwnedObjectCache,
+    ) -> ZookeeperStrategy {
+        let ControllerConfig {
+            strict_spec_validation,
+            dry_run,
+            ensemble_size_policy,
+            requeue_interval,
+            max_requeue_interval,
+            resync_interval,
+            throttle,
+            ..
+        } = self.config;
 
-    Ok(())
+        ZookeeperStrategy::new(
+            self.product_config,
+            managed_resources,
+            strict_spec_validation,
+            dry_run,
+            ensemble_size_policy,
+            self.usage_statistics,
+            self.metrics,
+            self.image_resolver,
+            Backoff::new(requeue_interval, max_requeue_interval),
+            status_refresh,
+            resync_interval,
+            throttle,
+            references,
+            cache,
+        )
+    }
+
+    /// Watches the clusters and the objects they own or reference and reconciles the clusters.
+    ///
+    /// The returned future needs to be consumed to make progress.
+    pub async fn run(self) -> OperatorResult<()> {
+        let client = self.client.clone();
+        let namespace = self.namespace.clone();
+        let metrics = self.metrics.clone();
+        let managed_resources = self.config.managed_resources.clone();
+        let requeue_interval = self.config.requeue_interval;
+
+        let zk_api: Api<ZookeeperCluster> = scoped_api(&client, namespace.as_deref());
+        let pods_api: Api<Pod> = scoped_api(&client, namespace.as_deref());
+        let config_maps_api: Api<ConfigMap> = scoped_api(&client, namespace.as_deref());
+        let budgets_api: Api<PodDisruptionBudget> = scoped_api(&client, namespace.as_deref());
+        let network_policies_api: Api<k8s_openapi::api::networking::v1::NetworkPolicy> =
+            scoped_api(&client, namespace.as_deref());
+        let secrets_api: Api<k8s_openapi::api::core::v1::Secret> =
+            scoped_api(&client, namespace.as_deref());
+        let services_api: Api<Service> = scoped_api(&client, namespace.as_deref());
+        let referenced_secrets_api: Api<k8s_openapi::api::core::v1::Secret> =
+            scoped_api(&client, namespace.as_deref());
+        let referenced_config_maps_api: Api<ConfigMap> = scoped_api(&client, namespace.as_deref());
+        let references = ReferenceIndex::default();
+        let status_refresh = StatusRefresh::default();
+        let (cache, reflect_cache) = OwnedObjectCache::new(
+            scoped_api(&client, namespace.as_deref()),
+            scoped_api(&client, namespace.as_deref()),
+            scoped_api(&client, namespace.as_deref()),
+            ListParams::default().labels(&format!("{}={}", labels::APP_NAME_LABEL, APP_NAME)),
+            metrics.clone(),
+        );
+
+        // Changes to the objects owned by a cluster trigger its reconciliation, so manual edits
+        // and deletions are reverted right away instead of with the next periodic requeue.
+        let controller = Controller::new(zk_api)
+            // changes of the status of the pods only refresh the status, see [`status_refresh`]
+            .watches(pods_api, ListParams::default(), {
+                let status_refresh = status_refresh.clone();
+                move |pod| {
+                    status_refresh
+                        .pod_changed(&pod)
+                        .map(|cluster| {
+                            reflector::ObjectRef::new(&cluster.name).within(&cluster.namespace)
+                        })
+                        .into_iter()
+                        .collect::<Vec<_>>()
+                }
+            })
+            .owns(config_maps_api, ListParams::default())
+            .owns(budgets_api, ListParams::default())
+            .owns(network_policies_api, ListParams::default())
+            // the allocated node ports and load balancers end up in the status
+            .owns(
+                services_api,
+                ListParams::default()
+                    .labels(&format!("{}=true", external_access::EXTERNAL_ACCESS_LABEL)),
+            )
+            .owns(
+                secrets_api,
+                ListParams::default().labels(&format!("{}={}", labels::APP_NAME_LABEL, APP_NAME)),
+            )
+            // Secrets and ConfigMaps referenced in the spec (e.g. the TLS Secret) are not owned,
+            // they are mapped back to the clusters with the references recorded by the
+            // reconciliations
+            .watches(
+                referenced_secrets_api,
+                references::watch_params(ReferenceKind::Secret),
+                {
+                    let references = references.clone();
+                    let status_refresh = status_refresh.clone();
+                    move |secret| {
+                        referencing_clusters(
+                            &references,
+                            &status_refresh,
+                            ReferenceKind::Secret,
+                            &secret,
+                        )
+                    }
+                },
+            )
+            .watches(
+                referenced_config_maps_api,
+                references::watch_params(ReferenceKind::ConfigMap),
+                {
+                    let references = references.clone();
+                    let status_refresh = status_refresh.clone();
+                    move |config_map| {
+                        referencing_clusters(
+                            &references,
+                            &status_refresh,
+                            ReferenceKind::ConfigMap,
+                            &config_map,
+                        )
+                    }
+                },
+            );
+        // renewed certificates are noticed through the status of the Certificates
+        #[cfg(feature = "cert-manager")]
+        let controller = if cert_manager::is_installed(&client).await {
+            let certificates_api: Api<cert_manager::Certificate> =
+                scoped_api(&client, namespace.as_deref());
+            controller.owns(certificates_api, ListParams::default())
+        } else {
+            controller
+        };
+        #[cfg(feature = "prometheus-operator")]
+        let (controller, managed_resources) =
+            if managed_resources.pod_monitors && prometheus_operator::is_installed(&client).await {
+                let pod_monitors_api: Api<prometheus_operator::PodMonitor> =
+                    scoped_api(&client, namespace.as_deref());
+                let prometheus_rules_api: Api<prometheus_operator::PrometheusRule> =
+                    scoped_api(&client, namespace.as_deref());
+                (
+                    controller
+                        .owns(pod_monitors_api, ListParams::default())
+                        .owns(prometheus_rules_api, ListParams::default()),
+                    managed_resources,
+                )
+            } else {
+                (
+                    controller,
+                    ManagedResources {
+                        pod_monitors: false,
+                        ..managed_resources
+                    },
+                )
+            };
+
+        let strategy = self.into_strategy(managed_resources, references, status_refresh, cache);
+
+        // the caches are only needed (and watched) as long as the controller runs
+        tokio::select! {
+            _ = controller.run(client, strategy, requeue_interval) => {}
+            _ = reflect_cache => {}
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::test_support::{self, pod_with_id, FakeApiServer, FakeZookeeper};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+    use kube::api::PatchParams;
     use rstest::rstest;
 
     #[rstest]
@@ -1097,4 +7028,995 @@ mod tests {
         let first = find_first_missing(&input);
         assert_eq!(first, expected);
     }
+
+    #[test]
+    fn test_reserve_federation_ids() {
+        let mut cluster: ZookeeperCluster = serde_json::from_value(json!({
+            "apiVersion": "zookeeper.stackable.tech/v1alpha1",
+            "kind": "ZookeeperCluster",
+            "metadata": { "name": "simple" },
+            "spec": {
+                "version": "3.5.8",
+                "servers": { "roleGroups": {} },
+                "federation": {
+                    "idOffset": 2,
+                    "members": [{ "id": 4, "address": "zk-4.example.com" }]
+                }
+            }
+        }))
+        .unwrap();
+
+        let mut used_ids = vec![5, 2];
+        reserve_federation_ids(&cluster, &[], &mut used_ids);
+        assert_eq!(used_ids, vec![1, 2, 4, 5]);
+        assert_eq!(find_first_missing(&used_ids), 3);
+
+        // the servers of the clusterRefs
+        let members = vec![FederationMember {
+            id: 3,
+            address: "node-3".to_string(),
+            ..FederationMember::default()
+        }];
+        let mut used_ids = vec![5, 2];
+        reserve_federation_ids(&cluster, &members, &mut used_ids);
+        assert_eq!(used_ids, vec![1, 2, 3, 4, 5]);
+        assert_eq!(find_first_missing(&used_ids), 6);
+
+        cluster.spec.federation = None;
+        let mut used_ids = vec![3, 1];
+        reserve_federation_ids(&cluster, &[], &mut used_ids);
+        assert_eq!(used_ids, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_assign_new_ids() {
+        let cluster = test_support::cluster("simple", json!({}));
+        let nodes = ["node-1", "node-2", "node-3", "node-4"]
+            .iter()
+            .map(|name| test_support::node(name, &[], "10.0.0.1"))
+            .collect::<Vec<_>>();
+        let mut role_groups = HashMap::new();
+        // node-1 is eligible for both role groups
+        role_groups.insert("a".to_string(), (nodes[..1].to_vec(), None));
+        role_groups.insert("default".to_string(), (nodes, None));
+        let mut eligible_nodes = HashMap::new();
+        eligible_nodes.insert(ZookeeperRole::Server.to_string(), role_groups);
+        let pods = vec![
+            test_support::server_pod(&cluster, "default", 2, "node-2"),
+            test_support::server_pod(&cluster, "default", 4, "node-4"),
+        ];
+
+        let mut id_information = IdInformation::from_pods(&pods);
+        let mut assigned = id_information.assign_new_ids(&eligible_nodes, None, None);
+        assigned.sort();
+        assert_eq!(
+            assigned,
+            vec![("node-1".to_string(), 1), ("node-3".to_string(), 3)]
+        );
+
+        // the replacement keeps its id, the nodes outside of the scale step get none
+        let mut id_information = IdInformation::from_pods(&pods);
+        assert_eq!(
+            id_information.assign_new_ids(&eligible_nodes, Some(&["node-3".to_string()]), Some(5)),
+            vec![("node-3".to_string(), 5)]
+        );
+        assert_eq!(id_information.used_ids, vec![2, 4, 5]);
+    }
+
+    #[test]
+    fn test_order_pods_for_removal() {
+        let pods = vec![
+            pod_with_id("two", Some("2")),
+            pod_with_id("ten", Some("10")),
+            pod_with_id("none", None),
+            pod_with_id("three", Some("3")),
+            pod_with_id("invalid", Some("abc")),
+        ];
+
+        let ordered = order_pods_for_removal(pods.iter().collect())
+            .into_iter()
+            .map(|pod| pod.name())
+            .collect::<Vec<_>>();
+
+        assert_eq!(ordered, vec!["none", "invalid", "ten", "three", "two"]);
+    }
+
+    #[test]
+    fn test_client_port() {
+        let pod: Pod = serde_json::from_value(json!({
+            "spec": {
+                "containers": [{
+                    "name": "zookeeper",
+                    "ports": [
+                        { "name": "metrics", "containerPort": 9505 },
+                        { "name": "client", "containerPort": 12181 }
+                    ]
+                }]
+            }
+        }))
+        .unwrap();
+        assert_eq!(client_port(&pod), 12181);
+        assert_eq!(client_port(&Pod::default()), DEFAULT_CLIENT_PORT);
+    }
+
+    #[test]
+    fn test_synced_condition() {
+        let pod: Pod = serde_json::from_value(json!({
+            "spec": {
+                "containers": [],
+                "readinessGates": [{ "conditionType": SYNCED_CONDITION }]
+            },
+            "status": {
+                "conditions": [{ "type": SYNCED_CONDITION, "status": "True" }]
+            }
+        }))
+        .unwrap();
+        assert!(has_readiness_gate(&pod, SYNCED_CONDITION));
+        assert!(is_pod_condition_true(&pod, SYNCED_CONDITION));
+        assert!(!has_readiness_gate(&Pod::default(), SYNCED_CONDITION));
+        assert!(!is_pod_condition_true(&Pod::default(), SYNCED_CONDITION));
+    }
+
+    #[rstest]
+    #[case::first_run(None, 3, Some((3, None)))]
+    #[case::unchanged(Some((3, None)), 3, None)]
+    #[case::scale_down(Some((5, Some("2021-09-01T11:00:00+00:00"))), 3, Some((3, Some("2021-09-01T11:00:00+00:00"))))]
+    #[case::scale_up(Some((3, None)), 5, Some((5, Some("2021-09-01T12:00:00+00:00"))))]
+    #[case::scale_up_after_interval(Some((3, Some("2021-09-01T11:00:00+00:00"))), 5, Some((5, Some("2021-09-01T12:00:00+00:00"))))]
+    #[case::scale_up_rate_limited(Some((3, Some("2021-09-01T11:58:00+00:00"))), 5, None)]
+    fn test_next_client_rebalance_hint(
+        #[case] previous: Option<(usize, Option<&str>)>,
+        #[case] servers: usize,
+        #[case] expected: Option<(usize, Option<&str>)>,
+    ) {
+        let to_hint = |(servers, requested_at): (usize, Option<&str>)| ClientRebalanceHint {
+            servers,
+            requested_at: requested_at.map(String::from),
+        };
+        let now = DateTime::parse_from_rfc3339("2021-09-01T12:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(
+            next_client_rebalance_hint(previous.map(to_hint).as_ref(), servers, now),
+            expected.map(to_hint)
+        );
+    }
+
+    #[test]
+    fn test_is_condition_true() {
+        let conditions: Vec<Condition> = serde_json::from_value(json!([
+            {
+                "type": VERSION_CHANGE_REJECTED_CONDITION,
+                "status": "True",
+                "reason": "DowngradeRejected",
+                "message": "",
+                "lastTransitionTime": "2021-09-01T12:00:00Z"
+            },
+            {
+                "type": "Upgrading",
+                "status": "False",
+                "reason": "",
+                "message": "",
+                "lastTransitionTime": "2021-09-01T12:00:00Z"
+            }
+        ]))
+        .unwrap();
+
+        assert!(is_condition_true(
+            &conditions,
+            VERSION_CHANGE_REJECTED_CONDITION
+        ));
+        assert!(!is_condition_true(&conditions, "Upgrading"));
+        assert!(!is_condition_true(&conditions, "Unknown"));
+    }
+
+    #[test]
+    fn test_is_condition_current() {
+        let conditions: Vec<Condition> = serde_json::from_value(json!([{
+            "type": "Available",
+            "status": "True",
+            "reason": "EnsembleReady",
+            "message": "3 of 3 servers are ready",
+            "lastTransitionTime": "2021-09-01T12:00:00Z",
+            "observedGeneration": 2
+        }]))
+        .unwrap();
+        let condition = |status, message: &str| status::ClusterCondition {
+            condition_type: "Available",
+            status,
+            reason: "EnsembleReady",
+            message: message.to_string(),
+        };
+
+        assert!(is_condition_current(
+            &conditions,
+            &condition(true, "3 of 3 servers are ready"),
+            Some(2)
+        ));
+        assert!(!is_condition_current(
+            &conditions,
+            &condition(true, "3 of 3 servers are ready"),
+            Some(3)
+        ));
+        assert!(!is_condition_current(
+            &conditions,
+            &condition(false, "3 of 3 servers are ready"),
+            Some(2)
+        ));
+        assert!(!is_condition_current(
+            &conditions,
+            &condition(true, "2 of 3 servers are ready"),
+            Some(2)
+        ));
+    }
+
+    #[test]
+    fn test_plan_config_map() {
+        let config_map = |name: &str, zoo_cfg: &str| {
+            let mut config_map = ConfigMap::default();
+            config_map.metadata.name = Some(name.to_string());
+            config_map
+                .data
+                .insert("zoo.cfg".to_string(), zoo_cfg.to_string());
+            config_map
+        };
+        let existing = vec![config_map("simple-server-default-data", "tickTime=2000")];
+
+        assert_eq!(
+            plan_config_map(
+                &config_map("simple-server-default-data", "tickTime=2000"),
+                &existing
+            ),
+            None
+        );
+        assert_eq!(
+            plan_config_map(
+                &config_map("simple-server-default-data", "tickTime=3000"),
+                &existing
+            )
+            .map(|change| (change.action, change.fields)),
+            Some((PlannedAction::Update, vec!["data.zoo.cfg".to_string()]))
+        );
+        assert_eq!(
+            plan_config_map(
+                &config_map("simple-server-other-data", "tickTime=2000"),
+                &existing
+            )
+            .map(|change| change.action),
+            Some(PlannedAction::Create)
+        );
+    }
+
+    #[rstest]
+    #[case(false, None, false)]
+    #[case(true, None, true)]
+    #[case(false, Some("true"), true)]
+    #[case(false, Some("false"), false)]
+    fn test_is_paused(
+        #[case] spec_paused: bool,
+        #[case] annotation: Option<&str>,
+        #[case] expected: bool,
+    ) {
+        let mut cluster: ZookeeperCluster = serde_json::from_value(json!({
+            "apiVersion": "zookeeper.stackable.tech/v1alpha1",
+            "kind": "ZookeeperCluster",
+            "metadata": { "name": "simple" },
+            "spec": {
+                "version": "3.5.8",
+                "paused": spec_paused,
+                "servers": { "roleGroups": {} }
+            }
+        }))
+        .unwrap();
+        if let Some(annotation) = annotation {
+            cluster
+                .annotations_mut()
+                .insert(PAUSED_ANNOTATION.to_string(), annotation.to_string());
+        }
+
+        assert_eq!(is_paused(&cluster), expected);
+    }
+
+    #[test]
+    fn test_is_pod_created_before() {
+        let timestamp = DateTime::parse_from_rfc3339("2021-09-01T12:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut pod = pod_with_id("pod", Some("1"));
+
+        assert!(is_pod_created_before(&pod, &timestamp));
+
+        pod.metadata.creation_timestamp = Some(Time(timestamp - ChronoDuration::minutes(1)));
+        assert!(is_pod_created_before(&pod, &timestamp));
+
+        pod.metadata.creation_timestamp = Some(Time(timestamp));
+        assert!(!is_pod_created_before(&pod, &timestamp));
+    }
+
+    #[test]
+    fn test_desired_replicas() {
+        let nodes = vec![Node::default(), Node::default(), Node::default()];
+        let mut role_groups = HashMap::new();
+        role_groups.insert("default".to_string(), (nodes.clone(), None));
+        role_groups.insert("limited".to_string(), (nodes.clone(), Some(2)));
+        role_groups.insert("too_many".to_string(), (nodes, Some(5)));
+        let mut eligible_nodes = HashMap::new();
+        eligible_nodes.insert(ZookeeperRole::Server.to_string(), role_groups);
+
+        assert_eq!(desired_replicas(&eligible_nodes), 8);
+        assert_eq!(
+            replicas_per_role_group(&eligible_nodes),
+            vec![
+                ("default".to_string(), 3),
+                ("limited".to_string(), 2),
+                ("too_many".to_string(), 3),
+            ]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>()
+        );
+    }
+
+    #[test]
+    fn test_apply_spec_replicas() {
+        let nodes = vec![Node::default(), Node::default(), Node::default()];
+        let mut role_groups = HashMap::new();
+        role_groups.insert("default".to_string(), (nodes, None));
+        let mut eligible_nodes = HashMap::new();
+        eligible_nodes.insert(ZookeeperRole::Server.to_string(), role_groups);
+
+        apply_spec_replicas(&mut eligible_nodes, None);
+        assert_eq!(desired_replicas(&eligible_nodes), 3);
+
+        apply_spec_replicas(&mut eligible_nodes, Some(1));
+        assert_eq!(desired_replicas(&eligible_nodes), 1);
+    }
+
+    #[test]
+    fn test_observers() {
+        let node = |name: &str| {
+            let mut node = Node::default();
+            node.metadata.name = Some(name.to_string());
+            node
+        };
+        let mut role_groups = HashMap::new();
+        role_groups.insert(
+            "participants".to_string(),
+            (vec![node("node-1"), node("node-2"), node("node-3")], None),
+        );
+        role_groups.insert(
+            "observers".to_string(),
+            (vec![node("node-4"), node("node-5")], Some(1)),
+        );
+        let mut eligible_nodes = HashMap::new();
+        eligible_nodes.insert(ZookeeperRole::Server.to_string(), role_groups);
+
+        let mut observer_properties = BTreeMap::new();
+        observer_properties.insert(PEER_TYPE.to_string(), "observer".to_string());
+        let mut observer_config = HashMap::new();
+        observer_config.insert(
+            PropertyNameKind::File(PROPERTIES_FILE.to_string()),
+            observer_properties,
+        );
+        let mut validated_groups = HashMap::new();
+        validated_groups.insert("participants".to_string(), HashMap::new());
+        validated_groups.insert("observers".to_string(), observer_config);
+        let mut validated_role_config = HashMap::new();
+        validated_role_config.insert(ZookeeperRole::Server.to_string(), validated_groups);
+
+        assert_eq!(desired_replicas(&eligible_nodes), 4);
+        assert_eq!(
+            desired_participants(&eligible_nodes, &validated_role_config),
+            3
+        );
+        assert_eq!(
+            observer_node_names(
+                &node_role_groups(&[], &eligible_nodes),
+                &validated_role_config
+            ),
+            vec!["node-4".to_string(), "node-5".to_string()]
+                .into_iter()
+                .collect()
+        );
+        assert!(validate_peer_types_of_nodes(&eligible_nodes, &validated_role_config).is_empty());
+        // the participants run on all matching nodes
+        assert_eq!(
+            explicit_participants(&eligible_nodes, &validated_role_config),
+            None
+        );
+
+        apply_spec_replicas(&mut eligible_nodes, Some(11));
+        assert_eq!(
+            explicit_participants(&eligible_nodes, &validated_role_config),
+            Some(11)
+        );
+
+        // the selectors of both role groups match node-3
+        let role_groups = eligible_nodes
+            .get_mut(&ZookeeperRole::Server.to_string())
+            .unwrap();
+        role_groups
+            .get_mut("observers")
+            .unwrap()
+            .0
+            .push(node("node-3"));
+        assert_eq!(
+            validate_peer_types_of_nodes(&eligible_nodes, &validated_role_config),
+            vec!["servers: The node [node-3] is eligible for the observer and participant role groups [observers, participants], narrow their selectors".to_string()]
+        );
+        // the server of node-3 belongs to the role group of its pod
+        let cluster = test_support::cluster("simple", json!({}));
+        let pods = vec![test_support::server_pod(
+            &cluster,
+            "participants",
+            3,
+            "node-3",
+        )];
+        assert!(!observer_node_names(
+            &node_role_groups(&pods, &eligible_nodes),
+            &validated_role_config
+        )
+        .contains("node-3"));
+    }
+
+    /// Builds the strategy of the controller with a fake ensemble.
+    async fn strategy(server: &FakeApiServer) -> ZookeeperStrategy {
+        let product_config =
+            ProductConfigManager::from_yaml_file("../deploy/config-spec/properties.yaml").unwrap();
+        let mut strategy = ControllerBuilder::new(
+            server.operator_client(),
+            product_config,
+            Metrics::new().unwrap(),
+        )
+        .build_strategy()
+        .await;
+        strategy.zk_connector = FakeZookeeper::new().connector();
+        strategy
+    }
+
+    /// Reconciles the cluster `name` once like the controller and lets the kubelet start all pods.
+    async fn reconcile(strategy: &ZookeeperStrategy, server: &FakeApiServer, name: &str) {
+        let cluster = server
+            .get::<ZookeeperCluster>(Some(test_support::NAMESPACE), name)
+            .unwrap();
+        let context =
+            ReconciliationContext::new(server.operator_client(), cluster, Duration::from_secs(10));
+        let mut state = strategy.init_reconcile_state(context).await.unwrap();
+        state.reconcile().await.unwrap();
+        server.set_pods_ready();
+    }
+
+    /// Returns the node and the `myid` of every server pod, ordered by the `myid`.
+    fn servers(server: &FakeApiServer) -> Vec<(String, usize)> {
+        let mut servers = server
+            .list::<Pod>()
+            .iter()
+            .map(|pod| {
+                (
+                    pod_node_name(pod).unwrap().to_string(),
+                    pod_id(pod).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        servers.sort_by_key(|(_, id)| *id);
+        servers
+    }
+
+    fn config_maps(server: &FakeApiServer, config_map_type: &str) -> Vec<ConfigMap> {
+        server
+            .list::<ConfigMap>()
+            .into_iter()
+            .filter(|config_map| {
+                config_map
+                    .metadata
+                    .labels
+                    .get(configmap::CONFIGMAP_TYPE_LABEL)
+                    == Some(&config_map_type.to_string())
+            })
+            .collect()
+    }
+
+    fn expected_servers(ids: &[usize]) -> Vec<(String, usize)> {
+        ids.iter().map(|id| (format!("node-{}", id), *id)).collect()
+    }
+
+    /// Sets `spec.stopped` of the cluster `name`.
+    async fn set_stopped(server: &FakeApiServer, name: &str, stopped: bool) {
+        let clusters_api: Api<ZookeeperCluster> =
+            Api::namespaced(server.client(), test_support::NAMESPACE);
+        clusters_api
+            .patch(
+                name,
+                &PatchParams::default(),
+                &Patch::Merge(json!({ "spec": { "stopped": stopped } })),
+            )
+            .await
+            .unwrap();
+    }
+
+    /// Creates the cluster `simple` with the given spec on three nodes labeled `zookeeper=true`
+    /// and reconciles it until all servers run.
+    async fn three_servers(server: &FakeApiServer, spec: serde_json::Value) -> ZookeeperStrategy {
+        for i in 1..=3 {
+            server.insert(&test_support::node(
+                &format!("node-{}", i),
+                &[("zookeeper", "true")],
+                &format!("10.0.0.{}", i),
+            ));
+        }
+        server.insert(&test_support::cluster("simple", spec));
+        let strategy = strategy(server).await;
+        for _ in 0..10 {
+            if servers(server).len() == 3 {
+                break;
+            }
+            reconcile(&strategy, server, "simple").await;
+        }
+        assert_eq!(servers(server), expected_servers(&[1, 2, 3]));
+        strategy
+    }
+
+    #[tokio::test]
+    async fn test_stop_servers() {
+        let server = FakeApiServer::new();
+        let strategy = three_servers(
+            &server,
+            json!({
+                "servers": {
+                    "roleGroups": {
+                        "default": {
+                            "selector": { "matchLabels": { "zookeeper": "true" } },
+                            "config": { "dataDir": "/data/zookeeper" }
+                        }
+                    }
+                },
+                "extraVolumes": {
+                    "volumes": [{
+                        "name": "data",
+                        "persistentVolumeClaim": { "claimName": "zookeeper-data" }
+                    }],
+                    "mounts": [{ "name": "data", "mountPath": "/data" }]
+                }
+            }),
+        )
+        .await;
+
+        set_stopped(&server, "simple", true).await;
+        reconcile(&strategy, &server, "simple").await;
+
+        assert!(servers(&server).is_empty());
+        // the ids are kept, so the same ensemble comes back
+        assert_eq!(config_maps(&server, CONFIG_MAP_TYPE_ID).len(), 3);
+        let cluster = server
+            .get::<ZookeeperCluster>(Some(test_support::NAMESPACE), "simple")
+            .unwrap();
+        assert!(is_condition_true(
+            &cluster.status.unwrap().conditions,
+            STOPPED_CONDITION
+        ));
+
+        set_stopped(&server, "simple", false).await;
+        for _ in 0..10 {
+            if servers(&server).len() == 3 {
+                break;
+            }
+            reconcile(&strategy, &server, "simple").await;
+        }
+        assert_eq!(servers(&server), expected_servers(&[1, 2, 3]));
+        let cluster = server
+            .get::<ZookeeperCluster>(Some(test_support::NAMESPACE), "simple")
+            .unwrap();
+        assert!(!is_condition_true(
+            &cluster.status.unwrap().conditions,
+            STOPPED_CONDITION
+        ));
+    }
+
+    #[cfg(feature = "prometheus-operator")]
+    #[tokio::test]
+    async fn test_stop_servers_deletes_alerts() {
+        let server = FakeApiServer::new();
+        let strategy = three_servers(
+            &server,
+            json!({
+                "servers": {
+                    "roleGroups": {
+                        "default": { "selector": { "matchLabels": { "zookeeper": "true" } } }
+                    }
+                },
+                "monitoring": { "alerts": {} }
+            }),
+        )
+        .await;
+        assert_eq!(
+            server.list::<prometheus_operator::PrometheusRule>().len(),
+            1
+        );
+
+        // otherwise the quorum of the stopped cluster would be reported as lost
+        set_stopped(&server, "simple", true).await;
+        reconcile(&strategy, &server, "simple").await;
+        assert!(server
+            .list::<prometheus_operator::PrometheusRule>()
+            .is_empty());
+
+        set_stopped(&server, "simple", false).await;
+        reconcile(&strategy, &server, "simple").await;
+        assert_eq!(
+            server.list::<prometheus_operator::PrometheusRule>().len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_keep_foreign_admin_service() {
+        let server = FakeApiServer::new();
+        let service: Service = serde_json::from_value(json!({
+            "apiVersion": "v1",
+            "kind": "Service",
+            "metadata": { "name": "simple-admin", "namespace": test_support::NAMESPACE }
+        }))
+        .unwrap();
+        server.insert(&service);
+
+        three_servers(
+            &server,
+            json!({
+                "servers": {
+                    "roleGroups": {
+                        "default": { "selector": { "matchLabels": { "zookeeper": "true" } } }
+                    }
+                }
+            }),
+        )
+        .await;
+
+        assert!(server
+            .get::<Service>(Some(test_support::NAMESPACE), "simple-admin")
+            .is_some());
+        assert!(!server
+            .requests()
+            .iter()
+            .any(|request| request.starts_with("DELETE") && request.ends_with("/simple-admin")));
+    }
+
+    #[tokio::test]
+    async fn test_stop_servers_without_persistent_data_dir() {
+        let server = FakeApiServer::new();
+        let strategy = three_servers(
+            &server,
+            json!({
+                "servers": {
+                    "roleGroups": {
+                        "default": { "selector": { "matchLabels": { "zookeeper": "true" } } }
+                    }
+                }
+            }),
+        )
+        .await;
+
+        set_stopped(&server, "simple", true).await;
+        reconcile(&strategy, &server, "simple").await;
+
+        // the data directory lives in the containers, so the servers keep running
+        assert_eq!(servers(&server), expected_servers(&[1, 2, 3]));
+        let cluster = server
+            .get::<ZookeeperCluster>(Some(test_support::NAMESPACE), "simple")
+            .unwrap();
+        assert!(!is_condition_true(
+            &cluster.status.unwrap().conditions,
+            STOPPED_CONDITION
+        ));
+    }
+
+    /// Reconciles a cluster with a role group with the given `replicas` on ten nodes and returns
+    /// the number of servers.
+    async fn servers_on_ten_nodes(replicas: Option<u16>) -> usize {
+        let server = FakeApiServer::new();
+        for i in 1..=10 {
+            server.insert(&test_support::node(
+                &format!("node-{}", i),
+                &[("zookeeper", "true")],
+                &format!("10.0.0.{}", i),
+            ));
+        }
+        server.insert(&test_support::cluster(
+            "simple",
+            json!({
+                "servers": {
+                    "roleGroups": {
+                        "default": {
+                            "selector": { "matchLabels": { "zookeeper": "true" } },
+                            "replicas": replicas
+                        }
+                    }
+                }
+            }),
+        ));
+        let strategy = strategy(&server).await;
+        for _ in 0..12 {
+            reconcile(&strategy, &server, "simple").await;
+        }
+        servers(&server).len()
+    }
+
+    #[tokio::test]
+    async fn test_too_many_participants() {
+        // only replicas asking for too many participants are rejected, the number of matching
+        // nodes is only warned about
+        assert_eq!(servers_on_ten_nodes(Some(10)).await, 0);
+        assert_eq!(servers_on_ten_nodes(None).await, 10);
+    }
+
+    #[tokio::test]
+    async fn test_federation_id_conflict() {
+        let server = FakeApiServer::new();
+        let strategy = three_servers(
+            &server,
+            json!({
+                "servers": {
+                    "roleGroups": {
+                        "default": { "selector": { "matchLabels": { "zookeeper": "true" } } }
+                    }
+                },
+                "federation": { "clusterRefs": [{ "name": "other" }] }
+            }),
+        )
+        .await;
+        let mut other = test_support::cluster("other", json!({}));
+        other.status = Some(ZookeeperClusterStatus {
+            federation: Some(FederationStatus {
+                servers: vec![FederationMember {
+                    id: 2,
+                    address: "10.0.1.2".to_string(),
+                    ..FederationMember::default()
+                }],
+                connection_string: Some("10.0.1.2:2181".to_string()),
+            }),
+            ..ZookeeperClusterStatus::default()
+        });
+        server.insert(&other);
+
+        reconcile(&strategy, &server, "simple").await;
+
+        let cluster = server
+            .get::<ZookeeperCluster>(Some(test_support::NAMESPACE), "simple")
+            .unwrap();
+        assert!(is_condition_true(
+            &cluster.status.unwrap().conditions,
+            FEDERATION_ID_CONFLICT_CONDITION
+        ));
+        // the server of the other cluster is not added to zoo.cfg
+        assert!(config_maps(&server, "data").iter().all(|config_map| {
+            !config_map
+                .data
+                .values()
+                .any(|data| data.contains("10.0.1.2"))
+        }));
+    }
+
+    /// Returns the `zoo.cfg` of the data ConfigMap mounted by every server pod by pod name.
+    fn mounted_zoo_cfgs(server: &FakeApiServer) -> BTreeMap<String, String> {
+        let data_config_maps = config_maps(server, CONFIG_MAP_TYPE_DATA);
+        server
+            .list::<Pod>()
+            .iter()
+            .map(|pod| {
+                let zoo_cfg = pod
+                    .spec
+                    .iter()
+                    .flat_map(|spec| spec.volumes.iter())
+                    .filter_map(|volume| volume.config_map.as_ref()?.name.as_ref())
+                    .filter_map(|name| {
+                        data_config_maps
+                            .iter()
+                            .find(|config_map| &config_map.name() == name)
+                    })
+                    .filter_map(|config_map| config_map.data.get(PROPERTIES_FILE).cloned())
+                    .next()
+                    .unwrap();
+                (pod.name(), zoo_cfg)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_held_rollout_keeps_configuration() {
+        let server = FakeApiServer::new();
+        let strategy = three_servers(
+            &server,
+            json!({
+                "updateStrategy": "OnDelete",
+                "servers": {
+                    "roleGroups": {
+                        "default": { "selector": { "matchLabels": { "zookeeper": "true" } } }
+                    }
+                }
+            }),
+        )
+        .await;
+        let zoo_cfgs = mounted_zoo_cfgs(&server);
+
+        let clusters_api: Api<ZookeeperCluster> =
+            Api::namespaced(server.client(), test_support::NAMESPACE);
+        clusters_api
+            .patch(
+                "simple",
+                &PatchParams::default(),
+                &Patch::Merge(json!({
+                    "spec": {
+                        "servers": {
+                            "roleGroups": { "default": { "config": { "tickTime": 3000 } } }
+                        }
+                    }
+                })),
+            )
+            .await
+            .unwrap();
+        reconcile(&strategy, &server, "simple").await;
+        reconcile(&strategy, &server, "simple").await;
+
+        // restarted containers render the configuration of their pod
+        assert_eq!(mounted_zoo_cfgs(&server), zoo_cfgs);
+
+        let pods_api: Api<Pod> = Api::namespaced(server.client(), test_support::NAMESPACE);
+        let deleted = zoo_cfgs.keys().next().unwrap().clone();
+        pods_api
+            .delete(&deleted, &DeleteParams::default())
+            .await
+            .unwrap();
+        reconcile(&strategy, &server, "simple").await;
+
+        let rolled_out = mounted_zoo_cfgs(&server);
+        assert_eq!(rolled_out.len(), 3);
+        for (pod, zoo_cfg) in rolled_out {
+            assert_eq!(zoo_cfg.contains("tickTime=3000"), pod == deleted);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pod_without_references_hash_is_annotated() {
+        let server = FakeApiServer::new();
+        let tls_secret: k8s_openapi::api::core::v1::Secret = serde_json::from_value(json!({
+            "metadata": { "name": "zookeeper-tls", "namespace": test_support::NAMESPACE },
+            "data": { "tls.crt": "Y2VydA==" }
+        }))
+        .unwrap();
+        server.insert(&tls_secret);
+        let strategy = three_servers(
+            &server,
+            json!({
+                "tls": { "secretName": "zookeeper-tls" },
+                "servers": {
+                    "roleGroups": {
+                        "default": { "selector": { "matchLabels": { "zookeeper": "true" } } }
+                    }
+                }
+            }),
+        )
+        .await;
+        let pods = server.list::<Pod>();
+        let hash = pods[0]
+            .annotations()
+            .get(references::REFERENCES_HASH_ANNOTATION)
+            .unwrap()
+            .clone();
+
+        // a pod started before the references were tracked
+        let pods_api: Api<Pod> = Api::namespaced(server.client(), test_support::NAMESPACE);
+        pods_api
+            .patch(
+                &pods[0].name(),
+                &PatchParams::default(),
+                &Patch::Merge(json!({
+                    "metadata": {
+                        "annotations": { (references::REFERENCES_HASH_ANNOTATION): null }
+                    }
+                })),
+            )
+            .await
+            .unwrap();
+        reconcile(&strategy, &server, "simple").await;
+
+        let pod = server
+            .get::<Pod>(Some(test_support::NAMESPACE), &pods[0].name())
+            .unwrap();
+        assert_eq!(pod.uid(), pods[0].uid());
+        assert_eq!(
+            pod.annotations()
+                .get(references::REFERENCES_HASH_ANNOTATION),
+            Some(&hash)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_existing_service_account_is_not_taken_over() {
+        let server = FakeApiServer::new();
+        server.insert(&test_support::node("node-1", &[], "10.0.0.1"));
+        server.insert(&test_support::cluster(
+            "simple",
+            json!({ "serviceAccount": { "name": "default" } }),
+        ));
+        let default_service_account: ServiceAccount = serde_json::from_value(json!({
+            "metadata": { "name": "default", "namespace": test_support::NAMESPACE }
+        }))
+        .unwrap();
+        server.insert(&default_service_account);
+        let strategy = strategy(&server).await;
+
+        reconcile(&strategy, &server, "simple").await;
+
+        let service_account = server
+            .get::<ServiceAccount>(Some(test_support::NAMESPACE), "default")
+            .unwrap();
+        assert!(service_account.metadata.owner_references.is_empty());
+        assert!(servers(&server).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_ensemble() {
+        let server = FakeApiServer::new();
+        for i in 1..=3 {
+            server.insert(&test_support::node(
+                &format!("node-{}", i),
+                &[("zookeeper", "true")],
+                &format!("10.0.0.{}", i),
+            ));
+        }
+        server.insert(&test_support::cluster(
+            "simple",
+            json!({
+                "servers": {
+                    "roleGroups": {
+                        "default": { "selector": { "matchLabels": { "zookeeper": "true" } } }
+                    }
+                }
+            }),
+        ));
+        let strategy = strategy(&server).await;
+
+        // one pod is created per reconciliation
+        for _ in 0..10 {
+            if servers(&server).len() == 3 {
+                break;
+            }
+            reconcile(&strategy, &server, "simple").await;
+        }
+        assert_eq!(servers(&server), expected_servers(&[1, 2, 3]));
+
+        let mut ids = config_maps(&server, CONFIG_MAP_TYPE_ID)
+            .iter()
+            .map(|config_map| config_map.data.get("myid").unwrap().clone())
+            .collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids, vec!["1", "2", "3"]);
+        let data_config_maps = config_maps(&server, CONFIG_MAP_TYPE_DATA);
+        assert_eq!(data_config_maps.len(), 1);
+        let zoo_cfg = data_config_maps[0].data.get(PROPERTIES_FILE).unwrap();
+        assert_eq!(
+            zoo_cfg
+                .lines()
+                .filter(|line| line.starts_with("server."))
+                .count(),
+            3
+        );
+
+        // the pods on nodes which are no longer selected are removed one at a time, the highest
+        // myid first
+        let nodes_api: Api<Node> = Api::all(server.client());
+        for node in &["node-2", "node-3"] {
+            nodes_api
+                .patch(
+                    node,
+                    &PatchParams::default(),
+                    &Patch::Merge(json!({ "metadata": { "labels": { "zookeeper": null } } })),
+                )
+                .await
+                .unwrap();
+        }
+        reconcile(&strategy, &server, "simple").await;
+        assert_eq!(servers(&server), expected_servers(&[1, 2]));
+        reconcile(&strategy, &server, "simple").await;
+        assert_eq!(servers(&server), expected_servers(&[1]));
+    }
 }
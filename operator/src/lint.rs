@@ -0,0 +1,410 @@
+//! Offline checks of ZookeeperCluster manifests, e.g. before they are merged into a GitOps
+//! repository.
+//!
+//! Every ZookeeperCluster in a (multi-document) YAML file is checked with the validations the
+//! operator applies during the reconciliation and with policies for a fault tolerant ensemble.
+//! Documents of other kinds are skipped. The findings can be rendered as JSON or as SARIF for
+//! code scanning tools.
+use crate::error::Error;
+use crate::strict;
+use crate::validation;
+use crate::{is_observer_group, validate_cluster, validate_role_config, ZookeeperRole};
+
+use product_config::ProductConfigManager;
+use serde::Serialize;
+use serde_json::{json, Value};
+use stackable_zookeeper_crd::{EnsembleSizePolicy, ZookeeperCluster};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+
+const CLUSTER_KIND: &str = "ZookeeperCluster";
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+#[derive(Clone, Copy, Debug, Display, EnumIter, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum LintRule {
+    /// The document is not a valid ZookeeperCluster.
+    InvalidManifest,
+    /// The spec contains fields which are not part of the CRD and would be pruned.
+    UnknownField,
+    /// The operator would reject the spec during the reconciliation.
+    InvalidConfig,
+    /// The ensemble does not tolerate the failure of a server.
+    EnsembleSize,
+}
+
+impl LintRule {
+    pub fn description(self) -> &'static str {
+        match self {
+            LintRule::InvalidManifest => "The document is not a valid ZookeeperCluster",
+            LintRule::UnknownField => {
+                "The spec contains fields which are not part of the CRD and are silently pruned"
+            }
+            LintRule::InvalidConfig => "The operator rejects the spec during the reconciliation",
+            LintRule::EnsembleSize => {
+                "The number of participants does not make the ensemble fault tolerant"
+            }
+        }
+    }
+
+    fn severity(self) -> Severity {
+        match self {
+            LintRule::InvalidManifest | LintRule::InvalidConfig => Severity::Error,
+            LintRule::UnknownField | LintRule::EnsembleSize => Severity::Warning,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintFinding {
+    pub rule: LintRule,
+    pub severity: Severity,
+    /// The cluster as `<namespace>/<name>` if the document could be parsed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<String>,
+    /// The line the document starts at (1-based).
+    pub line: usize,
+    pub message: String,
+}
+
+impl LintFinding {
+    fn new(rule: LintRule, cluster: Option<&str>, line: usize, message: String) -> Self {
+        LintFinding {
+            rule,
+            severity: rule.severity(),
+            cluster: cluster.map(str::to_string),
+            line,
+            message,
+        }
+    }
+}
+
+/// Checks all ZookeeperClusters in `manifests` and returns the findings in document order.
+pub fn lint_manifests(manifests: &str, product_config: &ProductConfigManager) -> Vec<LintFinding> {
+    split_documents(manifests)
+        .into_iter()
+        .flat_map(|(line, document)| lint_document(&document, line, product_config))
+        .collect()
+}
+
+/// Returns whether any finding is an error, warnings do not fail the check.
+pub fn has_errors(findings: &[LintFinding]) -> bool {
+    findings
+        .iter()
+        .any(|finding| finding.severity == Severity::Error)
+}
+
+/// Renders the findings as SARIF 2.1.0 log of the tool `tool_version` for the file `uri`.
+pub fn to_sarif(findings: &[LintFinding], uri: &str, tool_version: &str) -> Value {
+    let rules = LintRule::iter()
+        .map(|rule| {
+            json!({
+                "id": rule.to_string(),
+                "shortDescription": { "text": rule.description() },
+                "defaultConfiguration": { "level": rule.severity().to_string() },
+            })
+        })
+        .collect::<Vec<_>>();
+    let results = findings
+        .iter()
+        .map(|finding| {
+            json!({
+                "ruleId": finding.rule.to_string(),
+                "level": finding.severity.to_string(),
+                "message": { "text": finding.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": uri },
+                        "region": { "startLine": finding.line },
+                    }
+                }],
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "stackable-zookeeper-operator",
+                    "version": tool_version,
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }],
+    })
+}
+
+fn lint_document(
+    document: &str,
+    line: usize,
+    product_config: &ProductConfigManager,
+) -> Vec<LintFinding> {
+    let value: serde_yaml::Value = match serde_yaml::from_str(document) {
+        Ok(serde_yaml::Value::Null) => return vec![],
+        Ok(value) => value,
+        Err(err) => {
+            return vec![LintFinding::new(
+                LintRule::InvalidManifest,
+                None,
+                line,
+                format!("Invalid YAML: {}", err),
+            )]
+        }
+    };
+    if value.get("kind").and_then(serde_yaml::Value::as_str) != Some(CLUSTER_KIND) {
+        return vec![];
+    }
+
+    let cluster: ZookeeperCluster = match serde_yaml::from_value(value.clone()) {
+        Ok(cluster) => cluster,
+        Err(err) => {
+            return vec![LintFinding::new(
+                LintRule::InvalidManifest,
+                None,
+                line,
+                err.to_string(),
+            )]
+        }
+    };
+    let name = format!(
+        "{}/{}",
+        cluster.metadata.namespace.as_deref().unwrap_or("default"),
+        cluster.metadata.name.as_deref().unwrap_or_default()
+    );
+    let finding = |rule, message| LintFinding::new(rule, Some(name.as_str()), line, message);
+
+    let mut findings = vec![];
+    if cluster.metadata.name.is_none() {
+        findings.push(finding(
+            LintRule::InvalidManifest,
+            "metadata.name is missing".to_string(),
+        ));
+    }
+    match serde_json::to_string(&value)
+        .map_err(Error::from)
+        .and_then(|applied| strict::find_unknown_fields(&applied))
+    {
+        Ok(unknown_fields) => findings.extend(unknown_fields.into_iter().map(|field| {
+            finding(
+                LintRule::UnknownField,
+                format!("spec.{} is not part of the CRD", field),
+            )
+        })),
+        Err(err) => findings.push(finding(LintRule::InvalidManifest, err.to_string())),
+    }
+
+    let validated_role_config = match validate_role_config(&cluster, product_config) {
+        Ok(validated_role_config) => validated_role_config,
+        Err(err) => {
+            findings.push(finding(LintRule::InvalidConfig, err.to_string()));
+            return findings;
+        }
+    };
+    findings.extend(
+        validate_cluster(&cluster, &cluster.spec.version, &validated_role_config)
+            .into_iter()
+            .map(|problem| finding(LintRule::InvalidConfig, problem)),
+    );
+
+    // role groups without `replicas` run on all matching nodes, their size is unknown
+    let servers = serde_json::to_value(&cluster.spec.servers).unwrap_or_default();
+    let role = ZookeeperRole::Server.to_string();
+    let mut participants = Some(0);
+    let mut has_observers = false;
+    for (group, role_group) in servers
+        .get("roleGroups")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+    {
+        if is_observer_group(&validated_role_config, &role, group) {
+            has_observers = true;
+            continue;
+        }
+        let replicas = cluster
+            .spec
+            .replicas
+            .map(u64::from)
+            .or_else(|| role_group.get("replicas").and_then(Value::as_u64));
+        participants = participants.and_then(|sum| replicas.map(|replicas| sum + replicas));
+    }
+    let participants = participants.map(|participants| participants as usize);
+    findings.extend(
+        validation::validate(&cluster.spec, participants)
+            .into_iter()
+            .map(|violation| finding(LintRule::InvalidConfig, violation.to_string())),
+    );
+    match participants {
+        Some(0) if has_observers => findings.push(finding(
+            LintRule::InvalidConfig,
+            "servers: At least one role group must run participants, all are observers".to_string(),
+        )),
+        Some(participants) => findings.extend(validation::ensemble_size_warning(participants).map(
+            |warning| match cluster.spec.ensemble_size_policy {
+                Some(EnsembleSizePolicy::Reject) => finding(
+                    LintRule::InvalidConfig,
+                    validation::SpecViolation::EnsembleSizeRejected { warning }.to_string(),
+                ),
+                _ => finding(LintRule::EnsembleSize, warning),
+            },
+        )),
+        None => {}
+    }
+    findings
+}
+
+/// Splits a multi-document YAML file at `---` lines and returns the documents with the line they
+/// start at.
+fn split_documents(manifests: &str) -> Vec<(usize, String)> {
+    let mut documents = vec![];
+    let mut start = 1;
+    let mut document = String::new();
+    for (index, line) in manifests.lines().enumerate() {
+        if line.trim_end() == "---" || line.starts_with("--- ") {
+            documents.push((start, std::mem::take(&mut document)));
+            start = index + 2;
+        } else {
+            document.push_str(line);
+            document.push('\n');
+        }
+    }
+    documents.push((start, document));
+    documents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    fn product_config() -> ProductConfigManager {
+        ProductConfigManager::from_yaml_file("../deploy/config-spec/properties.yaml").unwrap()
+    }
+
+    #[test]
+    fn test_split_documents() {
+        let documents = split_documents(indoc! {"
+            ---
+            kind: ConfigMap
+            ---
+            kind: ZookeeperCluster
+        "});
+
+        assert_eq!(
+            documents,
+            vec![
+                (1, "".to_string()),
+                (2, "kind: ConfigMap\n".to_string()),
+                (4, "kind: ZookeeperCluster\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_manifests() {
+        let findings = lint_manifests(
+            indoc! {"
+                apiVersion: v1
+                kind: ConfigMap
+                metadata:
+                  name: unrelated
+                ---
+                apiVersion: zookeeper.stackable.tech/v1alpha1
+                kind: ZookeeperCluster
+                metadata:
+                  name: simple
+                spec:
+                  version: 3.4.14
+                  replcias: 3
+                  config:
+                    skipACL: \"yes\"
+                  servers:
+                    roleGroups:
+                      default:
+                        selector:
+                          matchLabels:
+                            kubernetes.io/arch: stackable-linux
+                        replicas: 2
+                ---
+                kind: ZookeeperCluster
+                metadata:
+                  name: broken
+                spec:
+                  version: 1.0.0
+            "},
+            &product_config(),
+        );
+
+        let summary = findings
+            .iter()
+            .map(|finding| (finding.rule, finding.line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (LintRule::UnknownField, 6),
+                (LintRule::InvalidConfig, 6),
+                (LintRule::EnsembleSize, 6),
+                (LintRule::InvalidManifest, 23),
+            ]
+        );
+        assert_eq!(findings[0].cluster.as_deref(), Some("default/simple"));
+        assert_eq!(findings[0].message, "spec.replcias is not part of the CRD");
+        assert!(has_errors(&findings));
+
+        let sarif = to_sarif(&findings, "clusters.yaml", "0.1.0");
+        let results = sarif["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0]["ruleId"], "unknown-field");
+        assert_eq!(results[0]["level"], "warning");
+        assert_eq!(
+            results[3]["locations"][0]["physicalLocation"]["region"]["startLine"],
+            23
+        );
+    }
+
+    #[test]
+    fn test_lint_rejected_ensemble_size() {
+        let findings = lint_manifests(
+            indoc! {"
+                apiVersion: zookeeper.stackable.tech/v1alpha1
+                kind: ZookeeperCluster
+                metadata:
+                  name: simple
+                spec:
+                  version: 3.5.8
+                  ensembleSizePolicy: Reject
+                  servers:
+                    roleGroups:
+                      default:
+                        selector:
+                          matchLabels:
+                            kubernetes.io/arch: stackable-linux
+                        replicas: 4
+            "},
+            &product_config(),
+        );
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule, LintRule::InvalidConfig);
+        assert_eq!(
+            findings[0].message,
+            "[4] participants tolerate as few failures as [3], use an odd number, the ensembleSizePolicy `Reject` does not allow it"
+        );
+        assert!(has_errors(&findings));
+    }
+}
@@ -0,0 +1,163 @@
+//! Initialization of the logging and, with the `otel` feature, of the export of traces via
+//! OpenTelemetry (OTLP).
+//!
+//! Log records are written to stdout either as human readable text or as one JSON object per
+//! line (with the fields of the record and its spans), which log pipelines can ingest without
+//! custom parsing.
+//!
+//! The spans of the reconciliations, the Kubernetes requests made by the steps and the ZooKeeper
+//! client calls are exported to the configured collector (e.g. Jaeger or Tempo), the log records
+//! are attached to them as events.
+use crate::error::Error;
+
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// The format of the log records.
+#[derive(Clone, Copy, Debug, PartialEq, strum_macros::Display, strum_macros::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// How the operator logs and where traces are exported to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoggingConfig {
+    /// The filter of the log records (e.g. `info,stackable_zookeeper_operator=debug`), which
+    /// takes precedence over the filter in the environment variable.
+    pub filter: Option<String>,
+    pub format: LogFormat,
+    pub otlp: Option<OtlpConfig>,
+}
+
+/// The name of the operator in the exported traces (`service.name`).
+#[cfg(feature = "otel")]
+const SERVICE_NAME: &str = "zookeeper-operator";
+
+/// Where and how many traces are exported.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OtlpConfig {
+    /// The gRPC endpoint of the collector, e.g. `http://localhost:4317`.
+    pub endpoint: String,
+    /// The share of the traces which are exported, between 0 and 1. Traces whose parent was
+    /// sampled are always exported.
+    pub sampling_ratio: f64,
+}
+
+/// Initializes the logging with the filter of the `config` or, if not set, of the environment
+/// variable `env` (e.g. `stackable_zookeeper_operator=debug`), which defaults to `info`. If
+/// `config.otlp` is set, the spans are exported as well.
+pub fn initialize_logging(env: &str, config: &LoggingConfig) -> Result<(), Error> {
+    let filter = build_filter(config.filter.as_deref(), std::env::var(env).ok().as_deref())?;
+    let (text_layer, json_layer) = match config.format {
+        LogFormat::Text => (Some(tracing_subscriber::fmt::layer()), None),
+        LogFormat::Json => (None, Some(tracing_subscriber::fmt::layer().json())),
+    };
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(text_layer)
+        .with(json_layer);
+    let otlp = config.otlp.as_ref();
+
+    #[cfg(feature = "otel")]
+    {
+        let otel_layer = match otlp {
+            Some(otlp) => Some(tracing_opentelemetry::layer().with_tracer(build_tracer(otlp)?)),
+            None => None,
+        };
+        registry.with(otel_layer).init();
+    }
+    #[cfg(not(feature = "otel"))]
+    {
+        registry.init();
+        if let Some(otlp) = otlp {
+            tracing::warn!(
+                "The operator was built without the otel feature, traces are not exported to [{}]",
+                otlp.endpoint
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "otel")]
+fn build_tracer(otlp: &OtlpConfig) -> Result<opentelemetry::sdk::trace::Tracer, Error> {
+    use opentelemetry::sdk::trace::{self, Sampler};
+    use opentelemetry::sdk::Resource;
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+
+    Ok(opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(&otlp.endpoint),
+        )
+        .with_trace_config(
+            trace::config()
+                .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                    otlp.sampling_ratio,
+                ))))
+                .with_resource(Resource::new(vec![KeyValue::new(
+                    "service.name",
+                    SERVICE_NAME,
+                )])),
+        )
+        .install_batch(opentelemetry::runtime::Tokio)?)
+}
+
+/// Builds the filter from the configured filter or, if not set, the one of the environment
+/// variable. Only an invalid configured filter is an error, an invalid environment variable
+/// falls back to `info` to not break existing deployments.
+fn build_filter(filter: Option<&str>, env_filter: Option<&str>) -> Result<EnvFilter, Error> {
+    match filter {
+        Some(filter) => EnvFilter::try_new(filter).map_err(|source| Error::InvalidLogFilter {
+            filter: filter.to_string(),
+            source,
+        }),
+        None => Ok(env_filter
+            .and_then(|env_filter| EnvFilter::try_new(env_filter).ok())
+            .unwrap_or_else(|| EnvFilter::new("info"))),
+    }
+}
+
+/// Exports the remaining spans, called before the process exits.
+pub fn shutdown_tracing() {
+    #[cfg(feature = "otel")]
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Some("debug"), None, Some("debug"))]
+    #[case(Some("debug"), Some("warn"), Some("debug"))]
+    #[case(None, Some("warn"), Some("warn"))]
+    #[case(None, Some("foo=notalevel"), Some("info"))]
+    #[case(None, None, Some("info"))]
+    #[case(Some("foo=notalevel"), None, None)]
+    fn test_build_filter(
+        #[case] filter: Option<&str>,
+        #[case] env_filter: Option<&str>,
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(
+            build_filter(filter, env_filter)
+                .ok()
+                .map(|filter| filter.to_string()),
+            expected.map(str::to_string)
+        );
+    }
+
+    #[test]
+    fn test_log_format() {
+        assert_eq!("json".parse::<LogFormat>(), Ok(LogFormat::Json));
+        assert_eq!(LogFormat::Text.to_string(), "text");
+    }
+}
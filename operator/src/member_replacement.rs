@@ -0,0 +1,168 @@
+//! Detecting failed servers for `spec.memberReplacement`, see
+//! [`stackable_zookeeper_crd::member_replacement`].
+use k8s_openapi::api::core::v1::Pod;
+use k8s_openapi::chrono::{DateTime, Duration, Utc};
+use stackable_zookeeper_crd::member_replacement::MemberReplacementStatus;
+
+/// The annotation marking pods whose failure was already reported with an event.
+pub const FAILURE_REPORTED_ANNOTATION: &str = "zookeeper.stackable.tech/failure-reported";
+/// The environment variable which makes the entrypoint wipe the data directory once per token,
+/// see `entrypoint.sh`.
+pub const WIPE_DATA_TOKEN_ENV_VAR: &str = "ZOOKEEPER_WIPE_DATA_TOKEN";
+
+/// Returns since when `pod` is not ready: the last transition of its `Ready` condition, or its
+/// creation if it never reported one. `None` is returned for ready pods.
+pub fn not_ready_since(pod: &Pod) -> Option<DateTime<Utc>> {
+    let created = pod.metadata.creation_timestamp.as_ref().map(|time| time.0);
+    let ready = pod.status.as_ref().and_then(|status| {
+        status
+            .conditions
+            .iter()
+            .find(|condition| condition.type_ == "Ready")
+    });
+    match ready {
+        Some(condition) if condition.status == "True" => None,
+        Some(condition) => condition
+            .last_transition_time
+            .as_ref()
+            .map(|time| time.0)
+            .or(created),
+        None => created,
+    }
+}
+
+/// Returns the pod which is not ready for the longest time, if that is at least `threshold`.
+/// Terminating pods are included because pods on a lost node never finish terminating.
+pub fn find_failed_pod<'a>(
+    pods: &'a [Pod],
+    threshold: Duration,
+    now: &DateTime<Utc>,
+) -> Option<&'a Pod> {
+    pods.iter()
+        .filter_map(|pod| Some((not_ready_since(pod)?, pod)))
+        .filter(|(since, _)| *now - *since >= threshold)
+        .min_by_key(|(since, _)| *since)
+        .map(|(_, pod)| pod)
+}
+
+/// Checks whether the deadline of the `replacement` passed. Replacements recorded without a
+/// deadline never expire, an invalid deadline counts as passed.
+pub fn is_overdue(replacement: &MemberReplacementStatus, now: &DateTime<Utc>) -> bool {
+    replacement.deadline.as_ref().map_or(false, |deadline| {
+        DateTime::parse_from_rfc3339(deadline).map_or(true, |deadline| *now > deadline)
+    })
+}
+
+/// Checks whether more than half of the servers are ready without `failed`, so replacing it does
+/// not risk the quorum.
+pub fn has_quorum_without(pods: &[Pod], failed: &Pod) -> bool {
+    let ready = pods
+        .iter()
+        .filter(|pod| pod.metadata.name != failed.metadata.name)
+        .filter(|pod| not_ready_since(pod).is_none())
+        .count();
+    ready > pods.len() / 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    fn pod(name: &str, ready: &str, last_transition_time: &str) -> Pod {
+        serde_yaml::from_str(&format!(
+            indoc! {"
+                metadata:
+                  name: {}
+                  creationTimestamp: 2021-06-01T10:00:00Z
+                status:
+                  conditions:
+                    - type: Ready
+                      status: '{}'
+                      lastTransitionTime: {}
+            "},
+            name, ready, last_transition_time
+        ))
+        .unwrap()
+    }
+
+    fn time(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_not_ready_since() {
+        assert_eq!(
+            not_ready_since(&pod("simple-1", "True", "2021-06-01T11:00:00Z")),
+            None
+        );
+        assert_eq!(
+            not_ready_since(&pod("simple-1", "False", "2021-06-01T11:00:00Z")),
+            Some(time("2021-06-01T11:00:00Z"))
+        );
+        let pending: Pod = serde_yaml::from_str(indoc! {"
+            metadata:
+              name: simple-1
+              creationTimestamp: 2021-06-01T10:00:00Z
+        "})
+        .unwrap();
+        assert_eq!(
+            not_ready_since(&pending),
+            Some(time("2021-06-01T10:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn test_find_failed_pod() {
+        let pods = vec![
+            pod("simple-1", "True", "2021-06-01T10:05:00Z"),
+            pod("simple-2", "False", "2021-06-01T11:55:00Z"),
+            pod("simple-3", "False", "2021-06-01T11:40:00Z"),
+        ];
+        let now = time("2021-06-01T12:00:00Z");
+
+        assert_eq!(
+            find_failed_pod(&pods, Duration::minutes(10), &now)
+                .and_then(|pod| pod.metadata.name.as_deref()),
+            Some("simple-3")
+        );
+        assert_eq!(find_failed_pod(&pods, Duration::minutes(30), &now), None);
+    }
+
+    #[test]
+    fn test_has_quorum_without() {
+        let failed = pod("simple-3", "False", "2021-06-01T11:40:00Z");
+        let healthy = vec![
+            pod("simple-1", "True", "2021-06-01T10:05:00Z"),
+            pod("simple-2", "True", "2021-06-01T10:05:00Z"),
+            failed.clone(),
+        ];
+        let degraded = vec![
+            pod("simple-1", "True", "2021-06-01T10:05:00Z"),
+            pod("simple-2", "False", "2021-06-01T11:55:00Z"),
+            failed.clone(),
+        ];
+
+        assert!(has_quorum_without(&healthy, &failed));
+        assert!(!has_quorum_without(&degraded, &failed));
+    }
+
+    #[test]
+    fn test_is_overdue() {
+        let mut replacement = MemberReplacementStatus {
+            started_at: "2021-06-01T11:50:00+00:00".to_string(),
+            ..MemberReplacementStatus::default()
+        };
+        let now = time("2021-06-01T12:00:00Z");
+
+        assert!(!is_overdue(&replacement, &now));
+        replacement.deadline = Some("2021-06-01T12:00:00+00:00".to_string());
+        assert!(!is_overdue(&replacement, &now));
+        replacement.deadline = Some("2021-06-01T11:59:59+00:00".to_string());
+        assert!(is_overdue(&replacement, &now));
+        replacement.deadline = Some("tomorrow".to_string());
+        assert!(is_overdue(&replacement, &now));
+    }
+}
@@ -0,0 +1,719 @@
+//! Prometheus metrics about the operator itself and a few key metrics of the managed ensembles.
+//!
+//! The servers expose their own metrics (natively from 3.6 on, see `spec.monitoring`), the
+//! ensemble metrics here are taken from the `mntr` responses of the health checks every
+//! reconciliation, so they are also available for 3.5 clusters without any setup.
+use crate::error::Error;
+use crate::health::{self, HealthReport, LIVENESS_PATH, READINESS_PATH};
+use crate::quorum::ServerHealth;
+use crate::throttle::ReconcileThrottle;
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use k8s_openapi::chrono::Utc;
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::MetricFamily;
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tracing::info;
+
+/// The path the metrics are served on.
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Buckets (in seconds) of the time to ready histogram, from ten seconds to an hour.
+const TIME_TO_READY_BUCKETS: [f64; 9] = [
+    10.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 1800.0, 3600.0,
+];
+
+/// Buckets (in seconds) of the smoke test latency histogram, from five milliseconds to ten
+/// seconds.
+const SMOKE_TEST_LATENCY_BUCKETS: [f64; 9] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0, 2.5, 10.0];
+
+/// Buckets (in seconds) of the reconcile duration histogram, from ten milliseconds to a minute.
+const RECONCILE_DURATION_BUCKETS: [f64; 10] =
+    [0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 60.0];
+
+/// The key metrics of an ensemble, aggregated from the `mntr` responses of its servers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EnsembleMetrics {
+    /// The number of znodes, the highest of all servers (followers may lag behind).
+    pub znode_count: Option<f64>,
+    /// The number of watches, summed up over all servers (every server has its own clients).
+    pub watch_count: Option<f64>,
+    /// The average request latency in milliseconds, averaged over all servers.
+    pub avg_latency: Option<f64>,
+    /// The number of queued requests, summed up over all servers.
+    pub outstanding_requests: Option<f64>,
+}
+
+impl EnsembleMetrics {
+    /// Aggregates the `mntr` responses of the servers, a metric is `None` if no server reported
+    /// it (e.g. while all pods are down).
+    pub fn aggregate(servers: &[ServerHealth]) -> EnsembleMetrics {
+        let values = |key: &str| {
+            servers
+                .iter()
+                .filter_map(|server| server.mntr.get(key)?.parse::<f64>().ok())
+                .collect::<Vec<_>>()
+        };
+        let max = |values: Vec<f64>| {
+            values.into_iter().fold(None, |max: Option<f64>, value| {
+                Some(max.map_or(value, |max| max.max(value)))
+            })
+        };
+        let sum =
+            |values: Vec<f64>| Some(values.iter().sum::<f64>()).filter(|_| !values.is_empty());
+        let mean = |values: Vec<f64>| {
+            Some(values.iter().sum::<f64>() / values.len() as f64).filter(|_| !values.is_empty())
+        };
+        EnsembleMetrics {
+            znode_count: max(values("zk_znode_count")),
+            watch_count: sum(values("zk_watch_count")),
+            avg_latency: mean(values("zk_avg_latency")),
+            outstanding_requests: sum(values("zk_outstanding_requests")),
+        }
+    }
+}
+
+/// Exports the reconciliations waiting for the [`ReconcileThrottle`], read whenever the metrics
+/// are gathered.
+struct ReconcileQueueCollector {
+    throttle: ReconcileThrottle,
+    queue_depth: IntGaugeVec,
+}
+
+impl Collector for ReconcileQueueCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.queue_depth.desc()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let queue_depth = self.throttle.queue_depth();
+        self.queue_depth
+            .with_label_values(&["pendingChanges"])
+            .set(queue_depth.pending_changes as i64);
+        self.queue_depth
+            .with_label_values(&["periodic"])
+            .set(queue_depth.periodic as i64);
+        self.queue_depth.collect()
+    }
+}
+
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    time_to_ready: HistogramVec,
+    smoke_test_latency: HistogramVec,
+    smoke_test_failures: IntCounterVec,
+    controller_restarts: IntCounterVec,
+    reconcile_errors: IntCounterVec,
+    cached_objects: IntGaugeVec,
+    reconcile_duration: HistogramVec,
+    managed_clusters: IntGauge,
+    ready_replicas: IntGaugeVec,
+    leader: IntGauge,
+    api_reachable: IntGauge,
+    last_successful_reconcile: IntGauge,
+    ensemble_znodes: GaugeVec,
+    ensemble_watches: GaugeVec,
+    ensemble_avg_latency: GaugeVec,
+    ensemble_outstanding_requests: GaugeVec,
+    ensemble_leader_changes: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Metrics, Error> {
+        Metrics::with_registry(Registry::new())
+    }
+
+    /// Registers the metrics in `registry`, e.g. the one a binary embedding the operator exports
+    /// the metrics of all its controllers with. [`Metrics::encode`] then encodes all metrics of
+    /// the registry.
+    pub fn with_registry(registry: Registry) -> Result<Metrics, Error> {
+        let time_to_ready = HistogramVec::new(
+            HistogramOpts::new(
+                "zookeeper_operator_time_to_ready_seconds",
+                "Time from the creation or spec change of a ZookeeperCluster until it reached the desired state",
+            )
+            .buckets(TIME_TO_READY_BUCKETS.to_vec()),
+            &["namespace", "cluster", "trigger"],
+        )?;
+        registry.register(Box::new(time_to_ready.clone()))?;
+        let smoke_test_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "zookeeper_operator_smoke_test_latency_seconds",
+                "Time the smoke test after a disruptive operation took to create, read and delete a probe znode",
+            )
+            .buckets(SMOKE_TEST_LATENCY_BUCKETS.to_vec()),
+            &["namespace", "cluster"],
+        )?;
+        registry.register(Box::new(smoke_test_latency.clone()))?;
+        let smoke_test_failures = IntCounterVec::new(
+            Opts::new(
+                "zookeeper_operator_smoke_test_failures_total",
+                "Number of failed smoke tests after disruptive operations",
+            ),
+            &["namespace", "cluster"],
+        )?;
+        registry.register(Box::new(smoke_test_failures.clone()))?;
+        let controller_restarts = IntCounterVec::new(
+            Opts::new(
+                "zookeeper_operator_controller_restarts_total",
+                "Number of times a controller was restarted after its watches ended",
+            ),
+            &["controller"],
+        )?;
+        registry.register(Box::new(controller_restarts.clone()))?;
+        let reconcile_errors = IntCounterVec::new(
+            Opts::new(
+                "zookeeper_operator_reconcile_errors_total",
+                "Number of failed reconciliations by the source of the error (kubernetes, zookeeper or operator), its variant and its class (transient, conflict or terminal)",
+            ),
+            &["controller", "source", "error", "class"],
+        )?;
+        registry.register(Box::new(reconcile_errors.clone()))?;
+        let cached_objects = IntGaugeVec::new(
+            Opts::new(
+                "zookeeper_operator_cached_objects",
+                "Number of objects in the caches of the operator, updated on every watch event",
+            ),
+            &["kind"],
+        )?;
+        registry.register(Box::new(cached_objects.clone()))?;
+        let reconcile_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "zookeeper_operator_reconcile_duration_seconds",
+                "Time a reconciliation took, including failed ones",
+            )
+            .buckets(RECONCILE_DURATION_BUCKETS.to_vec()),
+            &["controller"],
+        )?;
+        registry.register(Box::new(reconcile_duration.clone()))?;
+        let managed_clusters = IntGauge::new(
+            "zookeeper_operator_managed_clusters",
+            "Number of ZookeeperClusters managed by the operator",
+        )?;
+        registry.register(Box::new(managed_clusters.clone()))?;
+        let ready_replicas = IntGaugeVec::new(
+            Opts::new(
+                "zookeeper_operator_cluster_ready_replicas",
+                "Number of servers of a ZookeeperCluster which are ready and part of the quorum",
+            ),
+            &["namespace", "cluster"],
+        )?;
+        registry.register(Box::new(ready_replicas.clone()))?;
+        let leader = IntGauge::new(
+            "zookeeper_operator_leader",
+            "1 if this replica of the operator runs the controllers, 0 if it waits for the leader election",
+        )?;
+        registry.register(Box::new(leader.clone()))?;
+        let api_reachable = IntGauge::new(
+            "zookeeper_operator_api_reachable",
+            "0 if the last reconciliation failed because the Kubernetes API server could not be reached, 1 otherwise",
+        )?;
+        api_reachable.set(1);
+        registry.register(Box::new(api_reachable.clone()))?;
+        let last_successful_reconcile = IntGauge::new(
+            "zookeeper_operator_cluster_last_successful_reconcile_timestamp_seconds",
+            "Unix time of the last successful reconciliation of a ZookeeperCluster",
+        )?;
+        registry.register(Box::new(last_successful_reconcile.clone()))?;
+        let ensemble_znodes = GaugeVec::new(
+            Opts::new(
+                "zookeeper_ensemble_znode_count",
+                "Number of znodes of a ZookeeperCluster as reported by its servers",
+            ),
+            &["namespace", "cluster"],
+        )?;
+        registry.register(Box::new(ensemble_znodes.clone()))?;
+        let ensemble_watches = GaugeVec::new(
+            Opts::new(
+                "zookeeper_ensemble_watch_count",
+                "Number of watches on all servers of a ZookeeperCluster",
+            ),
+            &["namespace", "cluster"],
+        )?;
+        registry.register(Box::new(ensemble_watches.clone()))?;
+        let ensemble_avg_latency = GaugeVec::new(
+            Opts::new(
+                "zookeeper_ensemble_avg_latency_milliseconds",
+                "Average request latency of the servers of a ZookeeperCluster",
+            ),
+            &["namespace", "cluster"],
+        )?;
+        registry.register(Box::new(ensemble_avg_latency.clone()))?;
+        let ensemble_outstanding_requests = GaugeVec::new(
+            Opts::new(
+                "zookeeper_ensemble_outstanding_requests",
+                "Number of requests queued on all servers of a ZookeeperCluster",
+            ),
+            &["namespace", "cluster"],
+        )?;
+        registry.register(Box::new(ensemble_outstanding_requests.clone()))?;
+        let ensemble_leader_changes = IntCounterVec::new(
+            Opts::new(
+                "zookeeper_ensemble_leader_changes_total",
+                "Number of changes of the leader of a ZookeeperCluster observed by the health checks",
+            ),
+            &["namespace", "cluster"],
+        )?;
+        registry.register(Box::new(ensemble_leader_changes.clone()))?;
+
+        Ok(Metrics {
+            registry,
+            time_to_ready,
+            smoke_test_latency,
+            smoke_test_failures,
+            controller_restarts,
+            reconcile_errors,
+            cached_objects,
+            reconcile_duration,
+            managed_clusters,
+            ready_replicas,
+            leader,
+            api_reachable,
+            last_successful_reconcile,
+            ensemble_znodes,
+            ensemble_watches,
+            ensemble_avg_latency,
+            ensemble_outstanding_requests,
+            ensemble_leader_changes,
+        })
+    }
+
+    /// Exports the number of reconciliations waiting for `throttle` by priority, see
+    /// [`crate::throttle`].
+    pub fn register_reconcile_queue(&self, throttle: &ReconcileThrottle) -> Result<(), Error> {
+        let queue_depth = IntGaugeVec::new(
+            Opts::new(
+                "zookeeper_operator_reconcile_queue_depth",
+                "Number of reconciliations waiting for the concurrency and rate limits by priority (pendingChanges or periodic)",
+            ),
+            &["priority"],
+        )?;
+        self.registry.register(Box::new(ReconcileQueueCollector {
+            throttle: throttle.clone(),
+            queue_depth,
+        }))?;
+        Ok(())
+    }
+
+    /// Records that the given controller was restarted.
+    pub fn inc_controller_restarts(&self, controller: &str) {
+        self.controller_restarts
+            .with_label_values(&[controller])
+            .inc();
+    }
+
+    /// Records a failed reconciliation of the given controller.
+    pub fn inc_reconcile_errors(&self, controller: &str, error: &Error) {
+        self.reconcile_errors
+            .with_label_values(&[
+                controller,
+                error.source_kind(),
+                error.variant_name(),
+                &error.class().to_string(),
+            ])
+            .inc();
+        if error.is_api_unreachable() {
+            self.api_reachable.set(0);
+        }
+    }
+
+    /// Records a successful reconciliation of a ZookeeperCluster, which implies that the API
+    /// server is reachable.
+    pub fn record_successful_reconcile(&self) {
+        self.last_successful_reconcile.set(Utc::now().timestamp());
+        self.api_reachable.set(1);
+    }
+
+    /// Returns the liveness and readiness of the operator, see [`health`].
+    pub fn health(&self) -> HealthReport {
+        let last_successful_reconcile = match self.last_successful_reconcile.get() {
+            0 => None,
+            timestamp => Some(Utc::now().timestamp() - timestamp),
+        };
+        health::evaluate(
+            self.leader.get() == 1,
+            self.api_reachable.get() == 1,
+            last_successful_reconcile,
+        )
+    }
+
+    /// Records how long a reconciliation of the given controller took.
+    pub fn observe_reconcile_duration(&self, controller: &str, duration: Duration) {
+        self.reconcile_duration
+            .with_label_values(&[controller])
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Records how many clusters the operator manages.
+    pub fn set_managed_clusters(&self, count: usize) {
+        self.managed_clusters.set(count as i64);
+    }
+
+    /// Records the ready replicas of the cluster `namespace/name`.
+    pub fn set_ready_replicas(&self, namespace: &str, name: &str, ready_replicas: usize) {
+        self.ready_replicas
+            .with_label_values(&[namespace, name])
+            .set(ready_replicas as i64);
+    }
+
+    /// Records the ensemble metrics of the cluster `namespace/name` from the health checks of
+    /// its servers. Metrics no server reported are removed instead of exporting stale values.
+    pub fn set_ensemble_metrics(&self, namespace: &str, name: &str, servers: &[ServerHealth]) {
+        let ensemble_metrics = EnsembleMetrics::aggregate(servers);
+        for (gauge, value) in &[
+            (&self.ensemble_znodes, ensemble_metrics.znode_count),
+            (&self.ensemble_watches, ensemble_metrics.watch_count),
+            (&self.ensemble_avg_latency, ensemble_metrics.avg_latency),
+            (
+                &self.ensemble_outstanding_requests,
+                ensemble_metrics.outstanding_requests,
+            ),
+        ] {
+            match value {
+                Some(value) => gauge.with_label_values(&[namespace, name]).set(*value),
+                None => {
+                    let _ = gauge.remove_label_values(&[namespace, name]);
+                }
+            }
+        }
+    }
+
+    /// Removes the metrics of the deleted cluster `namespace/name` which would otherwise be
+    /// exported with their last value until the operator restarts.
+    pub fn remove_cluster(&self, namespace: &str, name: &str) {
+        // fails if the metrics were already removed or the status was never updated, which is fine
+        let _ = self.ready_replicas.remove_label_values(&[namespace, name]);
+        for gauge in &[
+            &self.ensemble_znodes,
+            &self.ensemble_watches,
+            &self.ensemble_avg_latency,
+            &self.ensemble_outstanding_requests,
+        ] {
+            let _ = gauge.remove_label_values(&[namespace, name]);
+        }
+        let _ = self
+            .ensemble_leader_changes
+            .remove_label_values(&[namespace, name]);
+    }
+
+    /// Records that the health checks found another server of the cluster `namespace/name` to be
+    /// the leader than before.
+    pub fn inc_leader_changes(&self, namespace: &str, name: &str) {
+        self.ensemble_leader_changes
+            .with_label_values(&[namespace, name])
+            .inc();
+    }
+
+    /// Records whether this replica is the leader (see [`crate::leader_election`]).
+    pub fn set_leader(&self, leader: bool) {
+        self.leader.set(i64::from(leader));
+    }
+
+    /// Records how many objects of the given kind the cache of the operator holds, see
+    /// [`crate::cache`].
+    pub fn set_cached_objects(&self, kind: &str, count: usize) {
+        self.cached_objects
+            .with_label_values(&[kind])
+            .set(count as i64);
+    }
+
+    /// Records a successful smoke test of the cluster `namespace/name` (see
+    /// [`crate::smoke_test`]).
+    pub fn observe_smoke_test_latency(&self, namespace: &str, name: &str, latency: Duration) {
+        self.smoke_test_latency
+            .with_label_values(&[namespace, name])
+            .observe(latency.as_secs_f64());
+    }
+
+    /// Records a failed smoke test of the cluster `namespace/name`.
+    pub fn inc_smoke_test_failures(&self, namespace: &str, name: &str) {
+        self.smoke_test_failures
+            .with_label_values(&[namespace, name])
+            .inc();
+    }
+
+    /// Records that the cluster `namespace/name` reached its desired state `seconds` after the
+    /// `trigger` (see [`crate::status::track_transition`]).
+    pub fn observe_time_to_ready(&self, namespace: &str, name: &str, trigger: &str, seconds: i64) {
+        self.time_to_ready
+            .with_label_values(&[namespace, name, trigger])
+            .observe(seconds as f64);
+    }
+
+    /// Renders all metrics in the Prometheus text format.
+    pub fn encode(&self) -> Result<String, Error> {
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    metrics: Metrics,
+) -> Result<Response<Body>, Infallible> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, METRICS_PATH) => match metrics.encode() {
+            Ok(text) => Response::builder()
+                .header("Content-Type", TextEncoder::new().format_type())
+                .body(Body::from(text)),
+            Err(err) => Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from(err.to_string())),
+        },
+        (&Method::GET, path @ LIVENESS_PATH) | (&Method::GET, path @ READINESS_PATH) => {
+            let report = metrics.health();
+            let healthy = if path == LIVENESS_PATH {
+                report.live
+            } else {
+                report.ready
+            };
+            Response::builder()
+                .status(if healthy {
+                    StatusCode::OK
+                } else {
+                    StatusCode::SERVICE_UNAVAILABLE
+                })
+                .header("Content-Type", "application/json")
+                // serializing the report can not fail, it only contains strings and numbers
+                .body(Body::from(
+                    serde_json::to_string_pretty(&report).unwrap_or_default(),
+                ))
+        }
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty()),
+    };
+
+    // Building the response only fails for invalid headers which are static here.
+    Ok(response.unwrap())
+}
+
+/// Serves the [`Metrics`] on `GET /metrics` until the process exits.
+pub async fn serve_metrics(address: SocketAddr, metrics: Metrics) -> Result<(), Error> {
+    let make_service = make_service_fn(move |_connection| {
+        let metrics = metrics.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle_request(request, metrics.clone())
+            }))
+        }
+    });
+
+    info!(
+        "Serving metrics on http://{0}{1}, the liveness on {2} and the readiness on {3}",
+        address, METRICS_PATH, LIVENESS_PATH, READINESS_PATH
+    );
+    Server::try_bind(&address)?.serve(make_service).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_time_to_ready() {
+        let metrics = Metrics::new().unwrap();
+        metrics.observe_time_to_ready("default", "simple", "creation", 90);
+
+        let text = metrics.encode().unwrap();
+
+        assert!(text.contains(
+            r#"zookeeper_operator_time_to_ready_seconds_bucket{cluster="simple",namespace="default",trigger="creation",le="120"} 1"#
+        ));
+        assert!(text.contains(
+            r#"zookeeper_operator_time_to_ready_seconds_bucket{cluster="simple",namespace="default",trigger="creation",le="60"} 0"#
+        ));
+    }
+
+    #[test]
+    fn test_with_registry() {
+        let registry = Registry::new();
+        let metrics = Metrics::with_registry(registry.clone()).unwrap();
+        metrics.set_managed_clusters(2);
+
+        assert!(registry
+            .gather()
+            .iter()
+            .any(|family| family.get_name() == "zookeeper_operator_managed_clusters"));
+        assert!(Metrics::with_registry(registry).is_err());
+    }
+
+    #[test]
+    fn test_encode_controller_health() {
+        let metrics = Metrics::new().unwrap();
+        metrics.inc_controller_restarts("cluster");
+        metrics.inc_reconcile_errors("cluster", &Error::ReconcileError("test".to_string()));
+        metrics.set_cached_objects("Pod", 3);
+
+        let text = metrics.encode().unwrap();
+
+        assert!(text
+            .contains(r#"zookeeper_operator_controller_restarts_total{controller="cluster"} 1"#));
+        assert!(text.contains(
+            r#"zookeeper_operator_reconcile_errors_total{class="transient",controller="cluster",error="ReconcileError",source="operator"} 1"#
+        ));
+        assert!(text.contains(r#"zookeeper_operator_cached_objects{kind="Pod"} 3"#));
+    }
+
+    #[test]
+    fn test_health() {
+        let metrics = Metrics::new().unwrap();
+        metrics.set_leader(true);
+        assert!(metrics.health().ready);
+
+        metrics.inc_reconcile_errors(
+            "cluster",
+            &Error::ReconcileError("not an API problem".to_string()),
+        );
+        assert!(metrics.health().ready);
+
+        // a restarted controller does not make the operator unhealthy
+        metrics.inc_controller_restarts("cluster");
+        assert!(metrics.health().live);
+
+        metrics.record_successful_reconcile();
+        let report = metrics.health();
+        assert!(report.ready);
+        assert!(report.seconds_since_last_successful_reconcile.is_some());
+    }
+
+    #[test]
+    fn test_encode_smoke_test() {
+        let metrics = Metrics::new().unwrap();
+        metrics.observe_smoke_test_latency("default", "simple", Duration::from_millis(20));
+        metrics.inc_smoke_test_failures("default", "simple");
+
+        let text = metrics.encode().unwrap();
+
+        assert!(text.contains(
+            r#"zookeeper_operator_smoke_test_latency_seconds_bucket{cluster="simple",namespace="default",le="0.025"} 1"#
+        ));
+        assert!(text.contains(
+            r#"zookeeper_operator_smoke_test_failures_total{cluster="simple",namespace="default"} 1"#
+        ));
+    }
+
+    #[test]
+    fn test_encode_clusters() {
+        let metrics = Metrics::new().unwrap();
+        metrics.observe_reconcile_duration("cluster", Duration::from_millis(300));
+        metrics.set_managed_clusters(2);
+        metrics.set_ready_replicas("default", "simple", 3);
+        metrics.set_ready_replicas("default", "deleted", 1);
+        metrics.remove_cluster("default", "deleted");
+        metrics.remove_cluster("default", "deleted");
+        metrics.set_leader(true);
+
+        let text = metrics.encode().unwrap();
+
+        assert!(text.contains(
+            r#"zookeeper_operator_reconcile_duration_seconds_bucket{controller="cluster",le="0.5"} 1"#
+        ));
+        assert!(text.contains("zookeeper_operator_managed_clusters 2"));
+        assert!(text.contains(
+            r#"zookeeper_operator_cluster_ready_replicas{cluster="simple",namespace="default"} 3"#
+        ));
+        assert!(!text.contains(r#"cluster="deleted""#));
+        assert!(text.contains("zookeeper_operator_leader 1"));
+    }
+
+    #[test]
+    fn test_encode_reconcile_queue() {
+        let metrics = Metrics::new().unwrap();
+        metrics
+            .register_reconcile_queue(&ReconcileThrottle::default())
+            .unwrap();
+
+        let text = metrics.encode().unwrap();
+
+        assert!(text
+            .contains(r#"zookeeper_operator_reconcile_queue_depth{priority="pendingChanges"} 0"#));
+        assert!(text.contains(r#"zookeeper_operator_reconcile_queue_depth{priority="periodic"} 0"#));
+    }
+
+    fn server(mntr: &[(&str, &str)]) -> ServerHealth {
+        ServerHealth {
+            mntr: mntr
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            ..ServerHealth::default()
+        }
+    }
+
+    #[test]
+    fn test_aggregate_ensemble_metrics() {
+        let servers = vec![
+            server(&[
+                ("zk_znode_count", "42"),
+                ("zk_watch_count", "10"),
+                ("zk_avg_latency", "2"),
+                ("zk_outstanding_requests", "0"),
+            ]),
+            server(&[
+                ("zk_znode_count", "40"),
+                ("zk_watch_count", "5"),
+                ("zk_avg_latency", "1.5"),
+                ("zk_outstanding_requests", "3"),
+            ]),
+            server(&[]),
+        ];
+
+        assert_eq!(
+            EnsembleMetrics::aggregate(&servers),
+            EnsembleMetrics {
+                znode_count: Some(42.0),
+                watch_count: Some(15.0),
+                avg_latency: Some(1.75),
+                outstanding_requests: Some(3.0),
+            }
+        );
+        assert_eq!(
+            EnsembleMetrics::aggregate(&[server(&[])]),
+            EnsembleMetrics::default()
+        );
+    }
+
+    #[test]
+    fn test_encode_ensemble_metrics() {
+        let metrics = Metrics::new().unwrap();
+        metrics.set_ensemble_metrics(
+            "default",
+            "simple",
+            &[server(&[("zk_znode_count", "42"), ("zk_watch_count", "7")])],
+        );
+        metrics.set_ensemble_metrics("default", "deleted", &[server(&[("zk_znode_count", "5")])]);
+        metrics.inc_leader_changes("default", "simple");
+        metrics.inc_leader_changes("default", "deleted");
+        metrics.remove_cluster("default", "deleted");
+
+        let text = metrics.encode().unwrap();
+
+        assert!(text.contains(
+            r#"zookeeper_ensemble_znode_count{cluster="simple",namespace="default"} 42"#
+        ));
+        assert!(text
+            .contains(r#"zookeeper_ensemble_watch_count{cluster="simple",namespace="default"} 7"#));
+        assert!(!text.contains("zookeeper_ensemble_avg_latency_milliseconds{"));
+        assert!(text.contains(
+            r#"zookeeper_ensemble_leader_changes_total{cluster="simple",namespace="default"} 1"#
+        ));
+        assert!(!text.contains(r#"cluster="deleted""#));
+
+        metrics.set_ensemble_metrics("default", "simple", &[server(&[])]);
+        assert!(!metrics
+            .encode()
+            .unwrap()
+            .contains("zookeeper_ensemble_znode_count{"));
+    }
+}
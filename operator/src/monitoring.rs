@@ -0,0 +1,174 @@
+//! Building the JMX exporter sidecar which exposes the metrics of servers without the Prometheus
+//! metrics provider, see [`stackable_zookeeper_crd::monitoring`].
+use k8s_openapi::api::core::v1::{
+    ConfigMapVolumeSource, Container, ContainerPort, KeyToPath, Volume, VolumeMount,
+};
+use stackable_zookeeper_crd::monitoring::{ZookeeperMonitoring, JMX_PORT};
+use std::collections::BTreeMap;
+
+/// The file in the data ConfigMap holding the configuration of the JMX exporter.
+pub const JMX_EXPORTER_CONFIG_FILE: &str = "jmx_exporter.yaml";
+
+pub const JMX_EXPORTER_CONTAINER_NAME: &str = "jmx-exporter";
+pub const JMX_EXPORTER_VOLUME_NAME: &str = "jmx-exporter-config";
+const JMX_EXPORTER_CONFIG_DIR: &str = "/stackable/jmx-exporter";
+
+/// The rules mapping the ZooKeeper MBeans to metrics as `(pattern, name, type, labels)`, taken
+/// from the ZooKeeper example of the JMX exporter.
+const RULES: [(&str, &str, &str, &[(&str, &str)]); 5] = [
+    (
+        r"org.apache.ZooKeeperService<name0=ReplicatedServer_id(\d+)><>(\w+)",
+        "zookeeper_$2",
+        "GAUGE",
+        &[],
+    ),
+    (
+        r"org.apache.ZooKeeperService<name0=ReplicatedServer_id(\d+), name1=replica.(\d+)><>(\w+)",
+        "zookeeper_$3",
+        "GAUGE",
+        &[("replicaId", "$2")],
+    ),
+    (
+        r"org.apache.ZooKeeperService<name0=ReplicatedServer_id(\d+), name1=replica.(\d+), name2=(\w+)><>(Packets\w+)",
+        "zookeeper_$4",
+        "COUNTER",
+        &[("replicaId", "$2"), ("memberType", "$3")],
+    ),
+    (
+        r"org.apache.ZooKeeperService<name0=ReplicatedServer_id(\d+), name1=replica.(\d+), name2=(\w+)><>(\w+)",
+        "zookeeper_$4",
+        "GAUGE",
+        &[("replicaId", "$2"), ("memberType", "$3")],
+    ),
+    (
+        r"org.apache.ZooKeeperService<name0=ReplicatedServer_id(\d+), name1=replica.(\d+), name2=(\w+), name3=(\w+)><>(\w+)",
+        "zookeeper_$4_$5",
+        "GAUGE",
+        &[("replicaId", "$2"), ("memberType", "$3")],
+    ),
+];
+
+/// Renders the JVM flags opening the JMX port for the sidecar. Both the JMX and the RMI
+/// connections only accept connections from localhost, so they do not need authentication.
+pub fn build_jmx_flags() -> Vec<String> {
+    vec![
+        format!("-Dcom.sun.management.jmxremote.port={}", JMX_PORT),
+        format!("-Dcom.sun.management.jmxremote.rmi.port={}", JMX_PORT),
+        "-Dcom.sun.management.jmxremote.host=127.0.0.1".to_string(),
+        "-Djava.rmi.server.hostname=127.0.0.1".to_string(),
+        "-Dcom.sun.management.jmxremote.authenticate=false".to_string(),
+        "-Dcom.sun.management.jmxremote.ssl=false".to_string(),
+    ]
+}
+
+/// Renders the configuration of the JMX exporter. The labels of the monitoring configuration
+/// are added to every rule, the labels of the rules take precedence.
+pub fn build_jmx_exporter_config(monitoring: &ZookeeperMonitoring) -> String {
+    let mut config = format!(
+        "hostPort: 127.0.0.1:{}\nlowercaseOutputName: true\nrules:\n",
+        JMX_PORT
+    );
+    for (pattern, name, metric_type, rule_labels) in RULES.iter() {
+        config.push_str(&format!("  - pattern: {}\n", quote(pattern)));
+        config.push_str(&format!("    name: {}\n", quote(name)));
+        config.push_str(&format!("    type: {}\n", metric_type));
+
+        let mut labels: BTreeMap<&str, &str> = monitoring
+            .labels
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        labels.extend(rule_labels.iter().copied());
+        if !labels.is_empty() {
+            config.push_str("    labels:\n");
+            for (key, value) in labels {
+                config.push_str(&format!("      {}: {}\n", key, quote(value)));
+            }
+        }
+    }
+    config
+}
+
+/// Quotes the value as double-quoted YAML string (which JSON strings are).
+fn quote(value: &str) -> String {
+    serde_json::Value::String(value.to_string()).to_string()
+}
+
+/// Builds the JMX exporter sidecar and the volume with its configuration from the data
+/// ConfigMap.
+pub fn build_jmx_exporter_sidecar(
+    monitoring: &ZookeeperMonitoring,
+    data_config_map_name: &str,
+) -> (Container, Volume) {
+    let container = Container {
+        name: JMX_EXPORTER_CONTAINER_NAME.to_string(),
+        image: Some(monitoring.jmx_exporter_image().to_string()),
+        args: vec![
+            monitoring.port().to_string(),
+            format!("{}/{}", JMX_EXPORTER_CONFIG_DIR, JMX_EXPORTER_CONFIG_FILE),
+        ],
+        ports: vec![ContainerPort {
+            name: Some("metrics".to_string()),
+            container_port: i32::from(monitoring.port()),
+            ..ContainerPort::default()
+        }],
+        volume_mounts: vec![VolumeMount {
+            name: JMX_EXPORTER_VOLUME_NAME.to_string(),
+            mount_path: JMX_EXPORTER_CONFIG_DIR.to_string(),
+            read_only: Some(true),
+            ..VolumeMount::default()
+        }],
+        ..Container::default()
+    };
+    let volume = Volume {
+        name: JMX_EXPORTER_VOLUME_NAME.to_string(),
+        config_map: Some(ConfigMapVolumeSource {
+            name: Some(data_config_map_name.to_string()),
+            items: vec![KeyToPath {
+                key: JMX_EXPORTER_CONFIG_FILE.to_string(),
+                path: JMX_EXPORTER_CONFIG_FILE.to_string(),
+                ..KeyToPath::default()
+            }],
+            ..ConfigMapVolumeSource::default()
+        }),
+        ..Volume::default()
+    };
+    (container, volume)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_build_jmx_exporter_config() {
+        let monitoring: ZookeeperMonitoring = serde_yaml::from_str(indoc! {"
+            labels:
+              environment: production
+              replicaId: ignored
+        "})
+        .unwrap();
+
+        let config = build_jmx_exporter_config(&monitoring);
+
+        assert!(config.starts_with(indoc! {r#"
+            hostPort: 127.0.0.1:9010
+            lowercaseOutputName: true
+            rules:
+              - pattern: "org.apache.ZooKeeperService<name0=ReplicatedServer_id(\\d+)><>(\\w+)"
+                name: "zookeeper_$2"
+                type: GAUGE
+                labels:
+                  environment: "production"
+              - pattern: "org.apache.ZooKeeperService<name0=ReplicatedServer_id(\\d+), name1=replica.(\\d+)><>(\\w+)"
+                name: "zookeeper_$3"
+                type: GAUGE
+                labels:
+                  environment: "production"
+                  replicaId: "$2"
+        "#}));
+        let parsed: serde_yaml::Value = serde_yaml::from_str(&config).unwrap();
+        assert_eq!(parsed["rules"].as_sequence().unwrap().len(), RULES.len());
+    }
+}
@@ -0,0 +1,174 @@
+//! Building the NetworkPolicy isolating the servers of a cluster, see
+//! [`stackable_zookeeper_crd::network_policy`].
+use crate::error::Error;
+
+use k8s_openapi::api::networking::v1::{
+    NetworkPolicy, NetworkPolicyIngressRule, NetworkPolicyPeer, NetworkPolicyPort,
+    NetworkPolicySpec,
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector;
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use kube::ResourceExt;
+use stackable_operator::builder::ObjectMetaBuilder;
+use stackable_operator::labels::build_common_labels_for_all_managed_resources;
+use stackable_zookeeper_crd::network_policy::{ZookeeperClientPeer, ZookeeperNetworkPolicy};
+use stackable_zookeeper_crd::{ZookeeperCluster, APP_NAME};
+use std::collections::BTreeMap;
+
+/// The label identifying the pods of the operator, which connects to the client port.
+const OPERATOR_NAME_LABEL: (&str, &str) = ("app.kubernetes.io/name", "zookeeper-operator");
+
+/// The names of the container ports clients may connect to.
+const CLIENT_PORT_NAMES: [&str; 4] = ["client", "client-tls", "admin", "metrics"];
+
+/// Returns the name of the NetworkPolicy of the given cluster.
+pub fn network_policy_name(cluster_name: &str) -> String {
+    format!("{}-server", cluster_name)
+}
+
+/// Builds the NetworkPolicy which only allows quorum and leader election traffic between the
+/// servers of `cluster` and client traffic from the operator and the allowed clients.
+pub fn build_network_policy(
+    cluster: &ZookeeperCluster,
+    network_policy: &ZookeeperNetworkPolicy,
+) -> Result<NetworkPolicy, Error> {
+    let cluster_name = cluster.name();
+    let ports = cluster.spec.ports.clone().unwrap_or_default();
+    let server_selector = LabelSelector {
+        match_labels: build_common_labels_for_all_managed_resources(APP_NAME, &cluster_name),
+        ..LabelSelector::default()
+    };
+
+    let ensemble_rule = NetworkPolicyIngressRule {
+        from: vec![NetworkPolicyPeer {
+            pod_selector: Some(server_selector.clone()),
+            ..NetworkPolicyPeer::default()
+        }],
+        ports: [ports.quorum(), ports.leader_election()]
+            .iter()
+            .map(|port| build_port(IntOrString::Int(i32::from(*port))))
+            .collect(),
+    };
+
+    let client_rule = NetworkPolicyIngressRule {
+        from: std::iter::once(build_operator_peer())
+            .chain(network_policy.allowed_clients.iter().map(build_client_peer))
+            .collect(),
+        ports: CLIENT_PORT_NAMES
+            .iter()
+            .map(|name| build_port(IntOrString::String(name.to_string())))
+            .collect(),
+    };
+
+    Ok(NetworkPolicy {
+        metadata: ObjectMetaBuilder::new()
+            .name(network_policy_name(&cluster_name))
+            .namespace(cluster.metadata.namespace.as_deref().unwrap_or_default())
+            .ownerreference_from_resource(cluster, Some(true), Some(true))?
+            .build()?,
+        spec: Some(NetworkPolicySpec {
+            pod_selector: server_selector,
+            ingress: vec![ensemble_rule, client_rule],
+            policy_types: vec!["Ingress".to_string()],
+            ..NetworkPolicySpec::default()
+        }),
+    })
+}
+
+fn build_port(port: IntOrString) -> NetworkPolicyPort {
+    NetworkPolicyPort {
+        port: Some(port),
+        protocol: Some("TCP".to_string()),
+        ..NetworkPolicyPort::default()
+    }
+}
+
+/// The pods of the operator in any namespace.
+fn build_operator_peer() -> NetworkPolicyPeer {
+    let mut labels = BTreeMap::new();
+    labels.insert(
+        OPERATOR_NAME_LABEL.0.to_string(),
+        OPERATOR_NAME_LABEL.1.to_string(),
+    );
+    NetworkPolicyPeer {
+        namespace_selector: Some(LabelSelector::default()),
+        pod_selector: Some(LabelSelector {
+            match_labels: labels,
+            ..LabelSelector::default()
+        }),
+        ..NetworkPolicyPeer::default()
+    }
+}
+
+fn build_client_peer(peer: &ZookeeperClientPeer) -> NetworkPolicyPeer {
+    NetworkPolicyPeer {
+        namespace_selector: peer
+            .namespace_selector_labels()
+            .map(|match_labels| LabelSelector {
+                match_labels,
+                ..LabelSelector::default()
+            }),
+        pod_selector: Some(LabelSelector {
+            match_labels: peer.pod_labels.clone(),
+            ..LabelSelector::default()
+        }),
+        ..NetworkPolicyPeer::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use serde_json::json;
+
+    #[test]
+    fn test_build_network_policy() {
+        let cluster = test_support::cluster(
+            "simple",
+            json!({
+                "ports": { "leaderElection": 13888 },
+                "networkPolicy": {
+                    "allowedClients": [
+                        { "namespace": "kafka" },
+                        { "podLabels": { "app": "nifi" } }
+                    ]
+                }
+            }),
+        );
+
+        let policy =
+            build_network_policy(&cluster, cluster.spec.network_policy.as_ref().unwrap()).unwrap();
+        let spec = policy.spec.unwrap();
+
+        assert_eq!(policy.metadata.name, Some("simple-server".to_string()));
+        assert_eq!(spec.policy_types, vec!["Ingress"]);
+        assert_eq!(spec.ingress[0].ports[0].port, Some(IntOrString::Int(2888)));
+        assert_eq!(spec.ingress[0].ports[1].port, Some(IntOrString::Int(13888)));
+        assert_eq!(
+            spec.ingress[0].from[0].pod_selector,
+            Some(spec.pod_selector.clone())
+        );
+        // the operator and both clients
+        assert_eq!(spec.ingress[1].from.len(), 3);
+        assert_eq!(
+            spec.ingress[1].from[1]
+                .namespace_selector
+                .as_ref()
+                .map(|selector| selector.match_labels.clone()),
+            Some(
+                vec![(
+                    "kubernetes.io/metadata.name".to_string(),
+                    "kafka".to_string()
+                )]
+                .into_iter()
+                .collect()
+            )
+        );
+        assert_eq!(spec.ingress[1].from[2].namespace_selector, None);
+        assert_eq!(
+            spec.ingress[1].ports[0].port,
+            Some(IntOrString::String("client".to_string()))
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! Keys for bookkeeping about namespaced objects.
+//!
+//! The operator may watch all namespaces, so objects with the same name in different namespaces
+//! must never share state. [`ObjectRef`] identifies an object by namespace and name and is used
+//! instead of plain names (or ad hoc `<namespace>/<name>` strings) as key in all such maps.
+use crate::error::Error;
+
+use kube::ResourceExt;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The namespace and name of an object, rendered and parsed as `<namespace>/<name>`.
+///
+/// It is (de)serialized as string, so it can be used as key of JSON objects.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ObjectRef {
+    pub namespace: String,
+    pub name: String,
+}
+
+impl ObjectRef {
+    pub fn new(namespace: &str, name: &str) -> ObjectRef {
+        ObjectRef {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    /// Returns the reference to the given namespaced object.
+    pub fn from_obj<K: ResourceExt>(obj: &K) -> ObjectRef {
+        ObjectRef {
+            namespace: obj.namespace().unwrap_or_default(),
+            name: obj.name(),
+        }
+    }
+}
+
+impl Display for ObjectRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.namespace, self.name)
+    }
+}
+
+impl FromStr for ObjectRef {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('/') {
+            Some((namespace, name))
+                if !namespace.is_empty() && !name.is_empty() && !name.contains('/') =>
+            {
+                Ok(ObjectRef::new(namespace, name))
+            }
+            _ => Err(Error::ReconcileError(format!(
+                "Invalid object reference [{}], expected <namespace>/<name>",
+                s
+            ))),
+        }
+    }
+}
+
+impl Serialize for ObjectRef {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ObjectRef {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_parse_and_display() {
+        let object_ref: ObjectRef = "default/simple".parse().unwrap();
+        assert_eq!(object_ref, ObjectRef::new("default", "simple"));
+        assert_eq!(object_ref.to_string(), "default/simple");
+    }
+
+    #[rstest]
+    #[case("simple")]
+    #[case("/simple")]
+    #[case("default/")]
+    #[case("default/simple/extra")]
+    fn test_parse_invalid(#[case] input: &str) {
+        input.parse::<ObjectRef>().unwrap_err();
+    }
+
+    #[test]
+    fn test_same_name_in_different_namespaces() {
+        let mut map = BTreeMap::new();
+        map.insert(ObjectRef::new("team-a", "simple"), 1);
+        map.insert(ObjectRef::new("team-b", "simple"), 2);
+
+        assert_eq!(map.len(), 2);
+
+        let json = serde_json::to_string(&map).unwrap();
+        assert_eq!(json, r#"{"team-a/simple":1,"team-b/simple":2}"#);
+        assert_eq!(
+            serde_json::from_str::<BTreeMap<ObjectRef, i32>>(&json).unwrap(),
+            map
+        );
+    }
+}
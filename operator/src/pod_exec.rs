@@ -0,0 +1,86 @@
+//! Running commands in the containers of the server pods, like `kubectl exec`.
+//!
+//! The reconciliation gets a [`PodExecutor`] from its strategy. The default implementation uses
+//! the `exec` subresource of the pods, tests can swap in their own implementation.
+use crate::error::Error;
+
+use async_trait::async_trait;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::{Api, AttachParams};
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+use tracing::instrument;
+
+/// The result of a command run in a container.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ExecOutput {
+    /// Whether the command exited with 0.
+    pub success: bool,
+    /// The standard output of the command.
+    pub stdout: String,
+}
+
+/// Runs commands in the containers of pods.
+#[async_trait]
+pub trait PodExecutor: Send + Sync {
+    /// Runs `command` in the `container` of the pod `pod` of `pods_api` and waits until it
+    /// exited.
+    async fn exec(
+        &self,
+        pods_api: Api<Pod>,
+        pod: &str,
+        container: &str,
+        command: Vec<String>,
+    ) -> Result<ExecOutput, Error>;
+}
+
+pub fn default_executor() -> Arc<dyn PodExecutor> {
+    Arc::new(KubePodExecutor {})
+}
+
+struct KubePodExecutor {}
+
+#[async_trait]
+impl PodExecutor for KubePodExecutor {
+    #[instrument(skip(self, pods_api, command))]
+    async fn exec(
+        &self,
+        pods_api: Api<Pod>,
+        pod: &str,
+        container: &str,
+        command: Vec<String>,
+    ) -> Result<ExecOutput, Error> {
+        let mut process = pods_api
+            .exec(
+                pod,
+                command,
+                &AttachParams::default().container(container).stderr(false),
+            )
+            .await?;
+        let exec_error = |message: String| Error::PodExecError {
+            pod: pod.to_string(),
+            container: container.to_string(),
+            message,
+        };
+
+        let mut stdout = String::new();
+        if let Some(mut reader) = process.stdout() {
+            reader
+                .read_to_string(&mut stdout)
+                .await
+                .map_err(|err| exec_error(err.to_string()))?;
+        }
+        let status = match process.take_status() {
+            Some(status) => status.await,
+            None => None,
+        };
+        let _ = process.join().await;
+        let status = status
+            .ok_or_else(|| exec_error("The command did not report its status".to_string()))?;
+
+        Ok(ExecOutput {
+            success: status.status.as_deref() == Some("Success"),
+            stdout,
+        })
+    }
+}
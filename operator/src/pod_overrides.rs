@@ -0,0 +1,139 @@
+//! Merges the `podOverrides` of a cluster into the server pods built by the operator.
+//!
+//! The merge order is:
+//!
+//! 1. Labels and annotations are added, the ones set by the operator (e.g. the recommended
+//!    labels, the `myid` and the config hash) take precedence because the operator relies on them.
+//! 2. Environment variables of the server container replace the ones of the operator with the
+//!    same name and are appended otherwise. The variables the operator relies on are rejected by
+//!    the validation (see [`RESERVED_ENV_VARS`]).
+//! 3. The security context and the PriorityClass replace the ones of the pod.
+use crate::entrypoint::MYID_ENV_VAR;
+use crate::jvm::SERVER_JVMFLAGS;
+
+use k8s_openapi::api::core::v1::Pod;
+use stackable_zookeeper_crd::pod_overrides::ZookeeperPodOverrides;
+use stackable_zookeeper_crd::APP_NAME;
+
+/// The environment variables which can not be overridden, `SERVER_JVMFLAGS` is configured with
+/// `spec.jvm`.
+pub const RESERVED_ENV_VARS: [&str; 2] = [MYID_ENV_VAR, SERVER_JVMFLAGS];
+
+/// Merges `overrides` into `pod`.
+pub fn apply(pod: &mut Pod, overrides: &ZookeeperPodOverrides) {
+    for (key, value) in &overrides.labels {
+        pod.metadata
+            .labels
+            .entry(key.clone())
+            .or_insert_with(|| value.clone());
+    }
+    for (key, value) in &overrides.annotations {
+        pod.metadata
+            .annotations
+            .entry(key.clone())
+            .or_insert_with(|| value.clone());
+    }
+
+    let spec = match pod.spec.as_mut() {
+        Some(spec) => spec,
+        None => return,
+    };
+    if let Some(container) = spec
+        .containers
+        .iter_mut()
+        .find(|container| container.name == APP_NAME)
+    {
+        for env_var in &overrides.env {
+            match container
+                .env
+                .iter_mut()
+                .find(|existing| existing.name == env_var.name)
+            {
+                Some(existing) => *existing = env_var.clone(),
+                None => container.env.push(env_var.clone()),
+            }
+        }
+    }
+    if let Some(security_context) = &overrides.security_context {
+        spec.security_context = Some(security_context.clone());
+    }
+    if let Some(priority_class_name) = &overrides.priority_class_name {
+        spec.priority_class_name = Some(priority_class_name.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_apply() {
+        let mut pod: Pod = serde_yaml::from_str(indoc! {"
+            metadata:
+              labels:
+                app.kubernetes.io/name: zookeeper
+              annotations:
+                zookeeper.stackable.tech/config-hash: abc
+            spec:
+              containers:
+                - name: zookeeper
+                  env:
+                    - name: ZOOKEEPER_MYID
+                      value: '1'
+                    - name: TZ
+                      value: UTC
+                - name: jmx-exporter
+        "})
+        .unwrap();
+        let overrides: ZookeeperPodOverrides = serde_yaml::from_str(indoc! {"
+            labels:
+              app.kubernetes.io/name: other
+              cost-center: '4711'
+            annotations:
+              zookeeper.stackable.tech/config-hash: xyz
+            env:
+              - name: TZ
+                value: Europe/Berlin
+              - name: JAVA_TOOL_OPTIONS
+                value: -Dfile.encoding=UTF-8
+            securityContext:
+              fsGroup: 1000
+            priorityClassName: critical
+        "})
+        .unwrap();
+
+        apply(&mut pod, &overrides);
+
+        let labels = &pod.metadata.labels;
+        assert_eq!(labels.get("app.kubernetes.io/name").unwrap(), "zookeeper");
+        assert_eq!(labels.get("cost-center").unwrap(), "4711");
+        assert_eq!(
+            pod.metadata
+                .annotations
+                .get("zookeeper.stackable.tech/config-hash")
+                .unwrap(),
+            "abc"
+        );
+        let spec = pod.spec.unwrap();
+        let env = spec.containers[0]
+            .env
+            .iter()
+            .map(|env_var| (env_var.name.as_str(), env_var.value.as_deref().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            env,
+            vec![
+                ("ZOOKEEPER_MYID", "1"),
+                ("TZ", "Europe/Berlin"),
+                ("JAVA_TOOL_OPTIONS", "-Dfile.encoding=UTF-8"),
+            ]
+        );
+        assert!(spec.containers[1].env.is_empty());
+        assert_eq!(
+            spec.security_context.and_then(|context| context.fs_group),
+            Some(1000)
+        );
+        assert_eq!(spec.priority_class_name.as_deref(), Some("critical"));
+    }
+}
@@ -0,0 +1,158 @@
+//! Liveness and readiness probes of the ZooKeeper container.
+//!
+//! Both probes ask the server whether it is running with `ruok`. If the version ships the
+//! AdminServer (3.5+) and an admin port is configured, its `/commands/ruok` endpoint is used,
+//! otherwise the four letter word is sent to the client port by a small shell command. Whether a
+//! server is actually synced with the ensemble is not part of the probes but handled by a
+//! readiness gate, see `update_synced_conditions`.
+use k8s_openapi::api::core::v1::{ExecAction, HTTPGetAction, Probe};
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use stackable_zookeeper_crd::{ProbeTimings, ZookeeperProbes};
+
+/// Restarting a ZooKeeper server is expensive (it has to resync with the leader), so the
+/// liveness probe only fails after the server did not answer for a minute.
+const LIVENESS_DEFAULTS: ProbeTimings = ProbeTimings {
+    initial_delay_seconds: Some(30),
+    period_seconds: Some(10),
+    timeout_seconds: Some(5),
+    failure_threshold: Some(6),
+};
+
+const READINESS_DEFAULTS: ProbeTimings = ProbeTimings {
+    initial_delay_seconds: Some(10),
+    period_seconds: Some(10),
+    timeout_seconds: Some(5),
+    failure_threshold: Some(3),
+};
+
+/// Builds the liveness and readiness probes, the timings of `overrides` take precedence over
+/// the defaults.
+///
+/// # Arguments
+///
+/// - `client_port` - The client port of the server.
+/// - `admin_port` - The port of the AdminServer, `None` if it is not available.
+/// - `overrides` - The probe timings from the spec.
+///
+pub fn build_probes(
+    client_port: u16,
+    admin_port: Option<u16>,
+    overrides: Option<&ZookeeperProbes>,
+) -> (Probe, Probe) {
+    let liveness_overrides = overrides.and_then(|probes| probes.liveness.as_ref());
+    let readiness_overrides = overrides.and_then(|probes| probes.readiness.as_ref());
+    (
+        build_ruok_probe(
+            client_port,
+            admin_port,
+            &with_defaults(&LIVENESS_DEFAULTS, liveness_overrides),
+        ),
+        build_ruok_probe(
+            client_port,
+            admin_port,
+            &with_defaults(&READINESS_DEFAULTS, readiness_overrides),
+        ),
+    )
+}
+
+/// Returns the probe timings of `overrides` with the defaults filled in for all unset values.
+pub fn default_probes(overrides: Option<&ZookeeperProbes>) -> ZookeeperProbes {
+    ZookeeperProbes {
+        liveness: Some(with_defaults(
+            &LIVENESS_DEFAULTS,
+            overrides.and_then(|probes| probes.liveness.as_ref()),
+        )),
+        readiness: Some(with_defaults(
+            &READINESS_DEFAULTS,
+            overrides.and_then(|probes| probes.readiness.as_ref()),
+        )),
+    }
+}
+
+fn with_defaults(defaults: &ProbeTimings, overrides: Option<&ProbeTimings>) -> ProbeTimings {
+    let timing = |get: fn(&ProbeTimings) -> Option<i32>| overrides.and_then(get).or(get(defaults));
+
+    ProbeTimings {
+        initial_delay_seconds: timing(|timings| timings.initial_delay_seconds),
+        period_seconds: timing(|timings| timings.period_seconds),
+        timeout_seconds: timing(|timings| timings.timeout_seconds),
+        failure_threshold: timing(|timings| timings.failure_threshold),
+    }
+}
+
+fn build_ruok_probe(client_port: u16, admin_port: Option<u16>, timings: &ProbeTimings) -> Probe {
+    let mut probe = Probe {
+        initial_delay_seconds: timings.initial_delay_seconds,
+        period_seconds: timings.period_seconds,
+        timeout_seconds: timings.timeout_seconds,
+        failure_threshold: timings.failure_threshold,
+        ..Probe::default()
+    };
+
+    match admin_port {
+        Some(admin_port) => {
+            probe.http_get = Some(HTTPGetAction {
+                path: Some("/commands/ruok".to_string()),
+                port: IntOrString::Int(admin_port.into()),
+                ..HTTPGetAction::default()
+            })
+        }
+        None => {
+            probe.exec = Some(ExecAction {
+                command: vec![
+                    "bash".to_string(),
+                    "-c".to_string(),
+                    format!(
+                        "exec 3<>/dev/tcp/127.0.0.1/{} && echo ruok >&3 && grep -q imok <&3",
+                        client_port
+                    ),
+                ],
+            })
+        }
+    }
+
+    probe
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_probes_with_admin_server() {
+        let (liveness, readiness) = build_probes(2181, Some(8080), None);
+
+        let http_get = liveness.http_get.unwrap();
+        assert_eq!(http_get.path, Some("/commands/ruok".to_string()));
+        assert_eq!(http_get.port, IntOrString::Int(8080));
+        assert_eq!(liveness.failure_threshold, Some(6));
+        assert!(liveness.exec.is_none());
+
+        assert_eq!(readiness.initial_delay_seconds, Some(10));
+    }
+
+    #[test]
+    fn test_build_probes_without_admin_server() {
+        let (liveness, _) = build_probes(2182, None, None);
+
+        assert!(liveness.http_get.is_none());
+        assert!(liveness.exec.unwrap().command[2].contains("/dev/tcp/127.0.0.1/2182"));
+    }
+
+    #[test]
+    fn test_build_probes_with_overrides() {
+        let overrides = ZookeeperProbes {
+            liveness: Some(ProbeTimings {
+                period_seconds: Some(30),
+                ..ProbeTimings::default()
+            }),
+            readiness: None,
+        };
+
+        let (liveness, readiness) = build_probes(2181, Some(8080), Some(&overrides));
+
+        assert_eq!(liveness.period_seconds, Some(30));
+        assert_eq!(liveness.timeout_seconds, Some(5));
+        assert_eq!(readiness.period_seconds, Some(10));
+    }
+}
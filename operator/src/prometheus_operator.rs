@@ -0,0 +1,357 @@
+//! Scraping the metrics of `spec.monitoring` with the Prometheus Operator.
+//!
+//! The servers are not behind a Service, so the operator creates a `PodMonitor` for every cluster
+//! with monitoring enabled, which selects the server pods and scrapes their `metrics` port. The
+//! id and role group of the servers are added to their metrics.
+//!
+//! If `spec.monitoring.alerts` is set, a `PrometheusRule` with alerts for the cluster is created
+//! as well. The alerts are based on the metrics of the operator (see [`crate::metrics`]), which
+//! are the same for all versions, and the disk usage reported by the node exporter.
+use crate::error::Error;
+use crate::ID_LABEL;
+
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector;
+use kube::{Api, CustomResource, ResourceExt};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use stackable_operator::builder::ObjectMetaBuilder;
+use stackable_operator::client::Client;
+use stackable_operator::labels::{
+    build_common_labels_for_all_managed_resources, APP_ROLE_GROUP_LABEL,
+};
+use stackable_zookeeper_crd::monitoring::ZookeeperAlerts;
+use stackable_zookeeper_crd::{ZookeeperCluster, APP_NAME};
+use std::collections::BTreeMap;
+use tracing::{info, warn};
+
+/// The subset of the Prometheus Operator `PodMonitor` used by the operator.
+#[derive(Clone, CustomResource, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[kube(
+    group = "monitoring.coreos.com",
+    version = "v1",
+    kind = "PodMonitor",
+    plural = "podmonitors",
+    namespaced
+)]
+#[serde(rename_all = "camelCase")]
+pub struct PodMonitorSpec {
+    pub selector: LabelSelector,
+    pub pod_metrics_endpoints: Vec<PodMetricsEndpoint>,
+    pub pod_target_labels: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PodMetricsEndpoint {
+    pub port: String,
+    pub path: String,
+}
+
+/// The subset of the Prometheus Operator `PrometheusRule` used by the operator.
+#[derive(Clone, CustomResource, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[kube(
+    group = "monitoring.coreos.com",
+    version = "v1",
+    kind = "PrometheusRule",
+    plural = "prometheusrules",
+    namespaced
+)]
+#[serde(rename_all = "camelCase")]
+pub struct PrometheusRuleSpec {
+    pub groups: Vec<RuleGroup>,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleGroup {
+    pub name: String,
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    pub alert: String,
+    pub expr: String,
+    #[serde(rename = "for")]
+    pub for_duration: String,
+    pub labels: BTreeMap<String, String>,
+    pub annotations: BTreeMap<String, String>,
+}
+
+/// Checks whether the Prometheus Operator CRDs are installed. PodMonitors are only created and
+/// watched if they are, otherwise every reconciliation of a cluster with monitoring would fail.
+pub async fn is_installed(client: &Client) -> bool {
+    let crds_api: Api<CustomResourceDefinition> = client.get_all_api();
+    match crds_api.get("podmonitors.monitoring.coreos.com").await {
+        Ok(_) => true,
+        Err(kube::Error::Api(response)) if response.code == 404 => {
+            info!("The Prometheus Operator is not installed, not creating PodMonitors");
+            false
+        }
+        Err(err) => {
+            warn!(
+                "Could not check whether the Prometheus Operator is installed, not creating PodMonitors: {}",
+                err
+            );
+            false
+        }
+    }
+}
+
+/// Returns the name of the PodMonitor of the cluster with the given name.
+pub fn pod_monitor_name(cluster_name: &str) -> String {
+    format!("{}-server", cluster_name)
+}
+
+/// Builds the PodMonitor scraping the metrics of all servers of `cluster`.
+pub fn build_pod_monitor(cluster: &ZookeeperCluster) -> Result<PodMonitor, Error> {
+    let cluster_name = cluster.name();
+    let mut pod_monitor = PodMonitor::new(
+        &pod_monitor_name(&cluster_name),
+        PodMonitorSpec {
+            selector: LabelSelector {
+                match_labels: build_common_labels_for_all_managed_resources(
+                    APP_NAME,
+                    &cluster_name,
+                ),
+                ..LabelSelector::default()
+            },
+            pod_metrics_endpoints: vec![PodMetricsEndpoint {
+                port: "metrics".to_string(),
+                path: "/metrics".to_string(),
+            }],
+            pod_target_labels: vec![ID_LABEL.to_string(), APP_ROLE_GROUP_LABEL.to_string()],
+        },
+    );
+    pod_monitor.metadata = ObjectMetaBuilder::new()
+        .name(pod_monitor_name(&cluster_name))
+        .namespace(cluster.metadata.namespace.as_deref().unwrap_or_default())
+        .ownerreference_from_resource(cluster, Some(true), Some(true))?
+        .build()?;
+    Ok(pod_monitor)
+}
+
+/// Returns the name of the PrometheusRule of the cluster with the given name.
+pub fn prometheus_rule_name(cluster_name: &str) -> String {
+    format!("{}-alerts", cluster_name)
+}
+
+/// Builds the PrometheusRule with the alerts of `cluster`:
+///
+/// - `ZookeeperQuorumLost` - fewer servers are ready than a quorum of the `desired_replicas`.
+/// - `ZookeeperLeaderFlapping` - the leader changed more often than the threshold within 15
+///   minutes.
+/// - `ZookeeperOutstandingRequestsHigh` - more requests than the threshold are queued.
+/// - `ZookeeperDiskNearlyFull` - the file system of the data directory on one of the
+///   `node_names` is used above the threshold. It is left out without nodes.
+pub fn build_prometheus_rule(
+    cluster: &ZookeeperCluster,
+    alerts: &ZookeeperAlerts,
+    desired_replicas: usize,
+    node_names: &[String],
+) -> Result<PrometheusRule, Error> {
+    let cluster_name = cluster.name();
+    let namespace = cluster.metadata.namespace.as_deref().unwrap_or_default();
+    let selector = format!("namespace=\"{}\",cluster=\"{}\"", namespace, cluster_name);
+    let rule = |alert: &str, expr: String, for_duration: &str, severity: &str, summary: String| {
+        let mut labels = alerts.labels.clone();
+        labels.insert("severity".to_string(), severity.to_string());
+        labels.insert("namespace".to_string(), namespace.to_string());
+        labels.insert("cluster".to_string(), cluster_name.clone());
+        Rule {
+            alert: alert.to_string(),
+            expr,
+            for_duration: for_duration.to_string(),
+            labels,
+            annotations: vec![(
+                "summary".to_string(),
+                format!(
+                    "ZookeeperCluster {}/{}: {}",
+                    namespace, cluster_name, summary
+                ),
+            )]
+            .into_iter()
+            .collect(),
+        }
+    };
+
+    let quorum = desired_replicas / 2 + 1;
+    let mut rules = vec![
+        rule(
+            "ZookeeperQuorumLost",
+            format!(
+                "zookeeper_operator_cluster_ready_replicas{{{}}} < {}",
+                selector, quorum
+            ),
+            "1m",
+            "critical",
+            format!(
+                "Fewer than {} servers are ready, the quorum is lost",
+                quorum
+            ),
+        ),
+        rule(
+            "ZookeeperLeaderFlapping",
+            format!(
+                "increase(zookeeper_ensemble_leader_changes_total{{{}}}[15m]) > {}",
+                selector,
+                alerts.leader_changes_threshold()
+            ),
+            "0m",
+            "warning",
+            format!(
+                "The leader changed more than {} times within 15 minutes",
+                alerts.leader_changes_threshold()
+            ),
+        ),
+        rule(
+            "ZookeeperOutstandingRequestsHigh",
+            format!(
+                "zookeeper_ensemble_outstanding_requests{{{}}} > {}",
+                selector,
+                alerts.outstanding_requests_threshold()
+            ),
+            "5m",
+            "warning",
+            format!(
+                "More than {} requests are queued on the servers",
+                alerts.outstanding_requests_threshold()
+            ),
+        ),
+    ];
+    if !node_names.is_empty() {
+        let filesystem = format!(
+            "mountpoint=\"{}\",instance=~\"{}\"",
+            alerts.data_mountpoint(),
+            node_names
+                .iter()
+                .map(|node_name| node_name.replace('.', "\\\\."))
+                .collect::<Vec<_>>()
+                .join("|")
+        );
+        rules.push(rule(
+            "ZookeeperDiskNearlyFull",
+            format!(
+                "100 * (1 - node_filesystem_avail_bytes{{{0}}} / node_filesystem_size_bytes{{{0}}}) > {1}",
+                filesystem,
+                alerts.disk_usage_threshold_percent()
+            ),
+            "10m",
+            "warning",
+            format!(
+                "More than {}% of the file system of the data directory are used",
+                alerts.disk_usage_threshold_percent()
+            ),
+        ));
+    }
+
+    let mut prometheus_rule = PrometheusRule::new(
+        &prometheus_rule_name(&cluster_name),
+        PrometheusRuleSpec {
+            groups: vec![RuleGroup {
+                name: format!("zookeeper-{}-{}", namespace, cluster_name),
+                rules,
+            }],
+        },
+    );
+    prometheus_rule.metadata = ObjectMetaBuilder::new()
+        .name(prometheus_rule_name(&cluster_name))
+        .namespace(namespace)
+        .with_labels(build_common_labels_for_all_managed_resources(
+            APP_NAME,
+            &cluster_name,
+        ))
+        .ownerreference_from_resource(cluster, Some(true), Some(true))?
+        .build()?;
+    Ok(prometheus_rule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use serde_json::json;
+
+    #[test]
+    fn test_build_pod_monitor() {
+        let cluster = test_support::cluster("simple", json!({ "monitoring": {} }));
+
+        let pod_monitor = build_pod_monitor(&cluster).unwrap();
+
+        assert_eq!(pod_monitor.metadata.name, Some("simple-server".to_string()));
+        assert_eq!(
+            pod_monitor.spec.selector.match_labels,
+            build_common_labels_for_all_managed_resources(APP_NAME, "simple")
+        );
+        assert_eq!(pod_monitor.spec.pod_metrics_endpoints[0].port, "metrics");
+        assert_eq!(
+            pod_monitor.spec.pod_target_labels,
+            vec![ID_LABEL, APP_ROLE_GROUP_LABEL]
+        );
+        assert_eq!(pod_monitor.metadata.owner_references.len(), 1);
+    }
+
+    #[test]
+    fn test_build_prometheus_rule() {
+        let cluster = test_support::cluster(
+            "simple",
+            json!({
+                "monitoring": {
+                    "alerts": {
+                        "outstandingRequestsThreshold": 50,
+                        "labels": { "team": "platform" }
+                    }
+                }
+            }),
+        );
+        let alerts = cluster
+            .spec
+            .monitoring
+            .as_ref()
+            .and_then(|monitoring| monitoring.alerts.clone())
+            .unwrap();
+        let node_names = vec!["node-1.example.com".to_string(), "node-2".to_string()];
+
+        let prometheus_rule = build_prometheus_rule(&cluster, &alerts, 3, &node_names).unwrap();
+
+        assert_eq!(
+            prometheus_rule.metadata.name,
+            Some("simple-alerts".to_string())
+        );
+        assert_eq!(prometheus_rule.metadata.owner_references.len(), 1);
+        let rules = &prometheus_rule.spec.groups[0].rules;
+        assert_eq!(
+            rules
+                .iter()
+                .map(|rule| rule.alert.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "ZookeeperQuorumLost",
+                "ZookeeperLeaderFlapping",
+                "ZookeeperOutstandingRequestsHigh",
+                "ZookeeperDiskNearlyFull",
+            ]
+        );
+        assert_eq!(
+            rules[0].expr,
+            r#"zookeeper_operator_cluster_ready_replicas{namespace="default",cluster="simple"} < 2"#
+        );
+        assert_eq!(
+            rules[2].expr,
+            r#"zookeeper_ensemble_outstanding_requests{namespace="default",cluster="simple"} > 50"#
+        );
+        assert!(rules[3]
+            .expr
+            .contains(r#"mountpoint="/",instance=~"node-1\\.example\\.com|node-2""#));
+        assert_eq!(rules[0].labels.get("team"), Some(&"platform".to_string()));
+        assert_eq!(
+            rules[0].labels.get("severity"),
+            Some(&"critical".to_string())
+        );
+
+        let without_nodes = build_prometheus_rule(&cluster, &alerts, 3, &[]).unwrap();
+        assert_eq!(without_nodes.spec.groups[0].rules.len(), 3);
+    }
+}
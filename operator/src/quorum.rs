@@ -0,0 +1,309 @@
+//! Deriving the quorum health and the members of the ensemble in the status from the health
+//! checks of the servers, see [`stackable_zookeeper_crd::quorum`].
+use k8s_openapi::chrono::{DateTime, Duration, Utc};
+use stackable_zookeeper_crd::quorum::{EnsembleMember, QuorumStatus, ServerQuorumStatus};
+use std::collections::BTreeMap;
+
+/// The mode of servers which did not answer the health check.
+const UNKNOWN_MODE: &str = "unknown";
+const LEADER_MODE: &str = "leader";
+
+/// The outcome of the health check of the server in a pod.
+#[derive(Clone, Debug, Default)]
+pub struct ServerHealth {
+    pub pod: String,
+    /// The `myid` of the server from the id label of the pod.
+    pub id: Option<usize>,
+    /// The address clients connect to, `None` if the pod is not scheduled yet.
+    pub address: Option<String>,
+    /// Whether the pod is ready, only the servers of ready pods are checked.
+    pub ready: bool,
+    /// Whether the server responds and is part of the quorum.
+    pub healthy: bool,
+    /// The parsed `mntr` response, empty if the server did not answer.
+    pub mntr: BTreeMap<String, String>,
+}
+
+/// Builds the quorum status from the health checks of all servers. The leader reports the
+/// followers and their synchronization, so these are missing while there is no leader.
+pub fn build_status(servers: &[ServerHealth], probed_at: &str) -> QuorumStatus {
+    let mut servers = servers.iter().collect::<Vec<_>>();
+    servers.sort_by(|a, b| a.pod.cmp(&b.pod));
+
+    let leader = servers
+        .iter()
+        .find(|server| server.mntr.get("zk_server_state").map(String::as_str) == Some(LEADER_MODE));
+    let leader_metric = |key: &str| {
+        leader
+            .and_then(|leader| leader.mntr.get(key))
+            .and_then(|value| value.parse().ok())
+    };
+
+    QuorumStatus {
+        leader: leader.map(|leader| leader.pod.clone()),
+        followers: leader_metric("zk_followers"),
+        synced_followers: leader_metric("zk_synced_followers"),
+        pending_syncs: leader_metric("zk_pending_syncs"),
+        servers: servers
+            .iter()
+            .map(|server| ServerQuorumStatus {
+                pod: server.pod.clone(),
+                mode: server
+                    .mntr
+                    .get("zk_server_state")
+                    .cloned()
+                    .unwrap_or_else(|| UNKNOWN_MODE.to_string()),
+                synced: server.healthy,
+                outstanding_requests: server
+                    .mntr
+                    .get("zk_outstanding_requests")
+                    .and_then(|value| value.parse().ok()),
+            })
+            .collect(),
+        probed_at: probed_at.to_string(),
+    }
+}
+
+/// Builds the members of the ensemble from the health checks of all servers, ordered by id.
+/// Servers without id (which are not part of the ensemble yet) come last.
+pub fn build_members(servers: &[ServerHealth]) -> Vec<EnsembleMember> {
+    let mut members = servers
+        .iter()
+        .map(|server| EnsembleMember {
+            pod: server.pod.clone(),
+            id: server.id,
+            role: server
+                .mntr
+                .get("zk_server_state")
+                .cloned()
+                .unwrap_or_else(|| UNKNOWN_MODE.to_string()),
+            address: server.address.clone(),
+            ready: server.ready,
+        })
+        .collect::<Vec<_>>();
+    members.sort_by(|a, b| (a.id.is_none(), a.id, &a.pod).cmp(&(b.id.is_none(), b.id, &b.pod)));
+    members
+}
+
+/// Checks whether `current` has to be written to the status.
+///
+/// Every status update triggers another reconciliation, so the load of the servers (which changes
+/// all the time) is only refreshed once `interval` passed since `previous` was probed, and never
+/// on its own without an interval. Changes of the leader or the synchronization are written right
+/// away.
+pub fn is_update_due(
+    previous: Option<&QuorumStatus>,
+    current: &QuorumStatus,
+    now: DateTime<Utc>,
+    interval: Option<Duration>,
+) -> bool {
+    let previous = match previous {
+        Some(previous) => previous,
+        None => return true,
+    };
+    let without_load = |status: &QuorumStatus| QuorumStatus {
+        servers: status
+            .servers
+            .iter()
+            .map(|server| ServerQuorumStatus {
+                outstanding_requests: None,
+                ..server.clone()
+            })
+            .collect(),
+        probed_at: String::new(),
+        ..status.clone()
+    };
+    let interval_passed = |interval: Duration| {
+        DateTime::parse_from_rfc3339(&previous.probed_at).map_or(true, |probed_at| {
+            now - probed_at.with_timezone(&Utc) >= interval
+        })
+    };
+    without_load(previous) != without_load(current) || interval.map_or(false, interval_passed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(pod: &str, healthy: bool, mntr: &[(&str, &str)]) -> ServerHealth {
+        ServerHealth {
+            pod: pod.to_string(),
+            healthy,
+            mntr: mntr
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            ..ServerHealth::default()
+        }
+    }
+
+    #[test]
+    fn test_build_status() {
+        let servers = vec![
+            server(
+                "simple-server-2",
+                true,
+                &[
+                    ("zk_server_state", "leader"),
+                    ("zk_outstanding_requests", "0"),
+                    ("zk_followers", "2"),
+                    ("zk_synced_followers", "1"),
+                    ("zk_pending_syncs", "1"),
+                ],
+            ),
+            server(
+                "simple-server-1",
+                true,
+                &[
+                    ("zk_server_state", "follower"),
+                    ("zk_outstanding_requests", "3"),
+                ],
+            ),
+            server("simple-server-3", false, &[]),
+        ];
+
+        let status = build_status(&servers, "2021-06-01T12:00:00+00:00");
+
+        assert_eq!(status.leader.as_deref(), Some("simple-server-2"));
+        assert_eq!(status.followers, Some(2));
+        assert_eq!(status.synced_followers, Some(1));
+        assert_eq!(status.pending_syncs, Some(1));
+        assert_eq!(
+            status.servers,
+            vec![
+                ServerQuorumStatus {
+                    pod: "simple-server-1".to_string(),
+                    mode: "follower".to_string(),
+                    synced: true,
+                    outstanding_requests: Some(3),
+                },
+                ServerQuorumStatus {
+                    pod: "simple-server-2".to_string(),
+                    mode: "leader".to_string(),
+                    synced: true,
+                    outstanding_requests: Some(0),
+                },
+                ServerQuorumStatus {
+                    pod: "simple-server-3".to_string(),
+                    mode: "unknown".to_string(),
+                    synced: false,
+                    outstanding_requests: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_status_without_leader() {
+        let servers = vec![server(
+            "simple-server-1",
+            false,
+            &[("zk_server_state", "looking")],
+        )];
+
+        let status = build_status(&servers, "2021-06-01T12:00:00+00:00");
+
+        assert_eq!(status.leader, None);
+        assert_eq!(status.followers, None);
+        assert_eq!(status.servers[0].mode, "looking");
+    }
+
+    #[test]
+    fn test_build_members() {
+        let mut unscheduled = server("simple-server-3", false, &[]);
+        unscheduled.id = Some(3);
+        let mut follower = server("simple-server-1", true, &[("zk_server_state", "follower")]);
+        follower.id = Some(2);
+        follower.address = Some("node-1:2181".to_string());
+        follower.ready = true;
+        let mut leader = server("simple-server-2", true, &[("zk_server_state", "leader")]);
+        leader.id = Some(1);
+        leader.address = Some("node-2:2181".to_string());
+        leader.ready = true;
+        let new = server("simple-server-4", false, &[]);
+
+        let members = build_members(&[new, unscheduled, follower, leader]);
+
+        assert_eq!(
+            members,
+            vec![
+                EnsembleMember {
+                    pod: "simple-server-2".to_string(),
+                    id: Some(1),
+                    role: "leader".to_string(),
+                    address: Some("node-2:2181".to_string()),
+                    ready: true,
+                },
+                EnsembleMember {
+                    pod: "simple-server-1".to_string(),
+                    id: Some(2),
+                    role: "follower".to_string(),
+                    address: Some("node-1:2181".to_string()),
+                    ready: true,
+                },
+                EnsembleMember {
+                    pod: "simple-server-3".to_string(),
+                    id: Some(3),
+                    role: "unknown".to_string(),
+                    address: None,
+                    ready: false,
+                },
+                EnsembleMember {
+                    pod: "simple-server-4".to_string(),
+                    id: None,
+                    role: "unknown".to_string(),
+                    address: None,
+                    ready: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_update_due() {
+        let probed_at = "2021-06-01T12:00:00+00:00";
+        let previous = build_status(
+            &[server(
+                "simple-server-1",
+                true,
+                &[
+                    ("zk_server_state", "standalone"),
+                    ("zk_outstanding_requests", "0"),
+                ],
+            )],
+            probed_at,
+        );
+        let busy = build_status(
+            &[server(
+                "simple-server-1",
+                true,
+                &[
+                    ("zk_server_state", "standalone"),
+                    ("zk_outstanding_requests", "12"),
+                ],
+            )],
+            probed_at,
+        );
+        let down = build_status(&[server("simple-server-1", false, &[])], probed_at);
+        let now = DateTime::parse_from_rfc3339(probed_at)
+            .unwrap()
+            .with_timezone(&Utc);
+        let interval = Some(Duration::seconds(60));
+
+        assert!(is_update_due(None, &busy, now, interval));
+        assert!(!is_update_due(Some(&previous), &busy, now, interval));
+        assert!(is_update_due(Some(&previous), &down, now, interval));
+        assert!(is_update_due(
+            Some(&previous),
+            &busy,
+            now + Duration::seconds(60),
+            interval
+        ));
+        assert!(!is_update_due(
+            Some(&previous),
+            &busy,
+            now + Duration::seconds(60),
+            None
+        ));
+    }
+}
@@ -0,0 +1,152 @@
+//! The outcome of the latest reconciliation of every ZookeeperCluster.
+//!
+//! The status of a cluster shows where the cluster is, but not what the operator did with it:
+//! a cluster which is not reconciled at all (e.g. because the operator is throttled or stuck)
+//! looks the same as one which is fine. The states are kept in memory by
+//! [`UsageStatistics`](crate::UsageStatistics) and served next to the usage report.
+use crate::status::{AVAILABLE_CONDITION, DEGRADED_CONDITION, PROGRESSING_CONDITION};
+
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
+use serde::Serialize;
+use stackable_operator::reconcile::ReconcileFunctionAction;
+
+/// The latest reconciliation of a cluster.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterReconcileState {
+    /// When the reconciliation finished (RFC 3339).
+    pub last_reconcile: String,
+    pub duration_millis: u64,
+    pub outcome: ReconcileOutcome,
+    /// When the next reconciliation is scheduled at the latest, if it was requeued.
+    pub requeue_after_seconds: Option<u64>,
+    pub phase: ClusterPhase,
+    /// The latest failure, it is kept after later reconciliations succeeded.
+    pub last_error: Option<ReconcileFailure>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum ReconcileOutcome {
+    /// All steps ran.
+    Completed,
+    /// A step requeued the reconciliation, e.g. to wait for pods.
+    Requeued,
+    Failed,
+}
+
+/// What the cluster is doing, from the perspective of the operator.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum ClusterPhase {
+    Failed,
+    Paused,
+    /// The servers are stopped with `spec.stopped`.
+    Stopped,
+    /// The reconciliation waits for an external condition, see `status.waiting`.
+    Waiting,
+    Progressing,
+    Degraded,
+    Available,
+    Unavailable,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileFailure {
+    pub time: String,
+    pub message: String,
+}
+
+/// Returns the outcome of a reconciliation and after how many seconds it was requeued.
+pub fn outcome<E>(result: &Result<ReconcileFunctionAction, E>) -> (ReconcileOutcome, Option<u64>) {
+    match result {
+        Ok(ReconcileFunctionAction::Requeue(after)) => {
+            (ReconcileOutcome::Requeued, Some(after.as_secs()))
+        }
+        Ok(_) => (ReconcileOutcome::Completed, None),
+        Err(_) => (ReconcileOutcome::Failed, None),
+    }
+}
+
+/// Derives the phase of a cluster from the outcome of its reconciliation and its conditions.
+pub fn phase(
+    outcome: ReconcileOutcome,
+    paused: bool,
+    waiting: bool,
+    conditions: &[Condition],
+) -> ClusterPhase {
+    if outcome == ReconcileOutcome::Failed {
+        ClusterPhase::Failed
+    } else if paused {
+        ClusterPhase::Paused
+    } else if waiting {
+        ClusterPhase::Waiting
+    } else if crate::is_condition_true(conditions, crate::STOPPED_CONDITION) {
+        ClusterPhase::Stopped
+    } else if crate::is_condition_true(conditions, PROGRESSING_CONDITION) {
+        ClusterPhase::Progressing
+    } else if crate::is_condition_true(conditions, DEGRADED_CONDITION) {
+        ClusterPhase::Degraded
+    } else if crate::is_condition_true(conditions, AVAILABLE_CONDITION) {
+        ClusterPhase::Available
+    } else {
+        ClusterPhase::Unavailable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+    use k8s_openapi::chrono::Utc;
+    use rstest::rstest;
+    use std::time::Duration;
+
+    fn conditions(true_types: &[&str]) -> Vec<Condition> {
+        true_types
+            .iter()
+            .map(|condition_type| Condition {
+                last_transition_time: Time(Utc::now()),
+                message: String::new(),
+                observed_generation: None,
+                reason: String::new(),
+                status: "True".to_string(),
+                type_: condition_type.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_outcome() {
+        assert_eq!(
+            outcome::<()>(&Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(
+                10
+            )))),
+            (ReconcileOutcome::Requeued, Some(10))
+        );
+        assert_eq!(
+            outcome::<()>(&Ok(ReconcileFunctionAction::Done)),
+            (ReconcileOutcome::Completed, None)
+        );
+        assert_eq!(outcome(&Err(())), (ReconcileOutcome::Failed, None));
+    }
+
+    #[rstest]
+    #[case(ReconcileOutcome::Failed, true, &[AVAILABLE_CONDITION], ClusterPhase::Failed)]
+    #[case(ReconcileOutcome::Completed, true, &[AVAILABLE_CONDITION], ClusterPhase::Paused)]
+    #[case(ReconcileOutcome::Requeued, false, &[PROGRESSING_CONDITION, AVAILABLE_CONDITION], ClusterPhase::Progressing)]
+    #[case(ReconcileOutcome::Completed, false, &[DEGRADED_CONDITION, AVAILABLE_CONDITION], ClusterPhase::Degraded)]
+    #[case(ReconcileOutcome::Completed, false, &[AVAILABLE_CONDITION], ClusterPhase::Available)]
+    #[case(ReconcileOutcome::Completed, false, &[crate::STOPPED_CONDITION], ClusterPhase::Stopped)]
+    #[case(ReconcileOutcome::Completed, false, &[], ClusterPhase::Unavailable)]
+    fn test_phase(
+        #[case] outcome: ReconcileOutcome,
+        #[case] paused: bool,
+        #[case] true_conditions: &[&str],
+        #[case] expected: ClusterPhase,
+    ) {
+        assert_eq!(
+            phase(outcome, paused, false, &conditions(true_conditions)),
+            expected
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! Secrets and ConfigMaps which are referenced (rather than owned) by a ZookeeperCluster, e.g.
+//! the TLS Secret or the credentials of the backup bucket.
+//!
+//! Every reconciliation records the references of its cluster in the [`ReferenceIndex`], which the
+//! watches of the controller use to map a changed Secret or ConfigMap back to the clusters
+//! referencing it. The data of the objects the servers use is hashed into an annotation of the
+//! pods (see [`REFERENCES_HASH_ANNOTATION`]), so the pods are restarted one at a time when it
+//! changes.
+use crate::error::Error;
+use crate::object_ref::ObjectRef;
+
+use k8s_openapi::api::core::v1::{ConfigMap, Secret};
+use kube::api::ListParams;
+use kube::Api;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use stackable_operator::client::Client;
+use stackable_operator::labels::APP_NAME_LABEL;
+use stackable_zookeeper_crd::authentication::super_user_secret_name;
+use stackable_zookeeper_crd::{ZookeeperClusterSpec, APP_NAME};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+
+/// Annotation on the pods holding the hash of the data of the referenced objects they were
+/// started with.
+pub const REFERENCES_HASH_ANNOTATION: &str = "zookeeper.stackable.tech/references-hash";
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum ReferenceKind {
+    Secret,
+    ConfigMap,
+}
+
+/// A Secret or ConfigMap in the namespace of the cluster.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Reference {
+    pub kind: ReferenceKind,
+    pub name: String,
+    /// Whether the servers use the object, so they have to be restarted when it changes.
+    pub mounted: bool,
+}
+
+impl Reference {
+    fn new(kind: ReferenceKind, name: &str, mounted: bool) -> Reference {
+        Reference {
+            kind,
+            name: name.to_string(),
+            mounted,
+        }
+    }
+}
+
+/// Returns the objects referenced by `spec`, including the Secrets of the `${secret:NAME:KEY}`
+/// placeholders in the properties.
+///
+/// The Secret of a certificate issued by cert-manager is not included, renewals are noticed
+/// through the revision of the Certificate.
+pub fn references(
+    cluster_name: &str,
+    spec: &ZookeeperClusterSpec,
+    placeholder_secrets: &BTreeSet<String>,
+) -> BTreeSet<Reference> {
+    let mut references = BTreeSet::new();
+    if let Some(tls) = spec.tls.as_ref().filter(|tls| tls.issuer_ref.is_none()) {
+        references.insert(Reference::new(
+            ReferenceKind::Secret,
+            &tls.secret_name,
+            true,
+        ));
+    }
+    if spec.authentication.is_some() {
+        references.insert(Reference::new(
+            ReferenceKind::Secret,
+            &super_user_secret_name(cluster_name),
+            true,
+        ));
+    }
+    if let Some(kerberos) = &spec.kerberos {
+        references.insert(Reference::new(
+            ReferenceKind::Secret,
+            &kerberos.keytab_secret_name,
+            true,
+        ));
+        references.insert(Reference::new(
+            ReferenceKind::ConfigMap,
+            &kerberos.krb5_config_map_name,
+            true,
+        ));
+    }
+    for secret in placeholder_secrets {
+        references.insert(Reference::new(ReferenceKind::Secret, secret, true));
+    }
+    // the operator reads the credentials whenever it accesses the bucket
+    for bucket in spec
+        .backup
+        .iter()
+        .map(|backup| &backup.s3)
+        .chain(spec.restore.iter().map(|restore| &restore.s3))
+    {
+        references.insert(Reference::new(
+            ReferenceKind::Secret,
+            &bucket.credentials_secret,
+            false,
+        ));
+    }
+    references
+}
+
+/// Reads the mounted objects among `references` and hashes their data, see [`hash_data`].
+/// `None` is returned if there are none.
+pub async fn hash_mounted_objects(
+    client: &Client,
+    namespace: &str,
+    references: &BTreeSet<Reference>,
+) -> Result<Option<String>, Error> {
+    let secrets_api: Api<Secret> = client.get_namespaced_api(namespace);
+    let config_maps_api: Api<ConfigMap> = client.get_namespaced_api(namespace);
+
+    let mut data = BTreeMap::new();
+    for reference in references.iter().filter(|reference| reference.mounted) {
+        let result = match reference.kind {
+            ReferenceKind::Secret => secrets_api
+                .get(&reference.name)
+                .await
+                .map(|secret| serde_json::to_value(&secret.data)),
+            ReferenceKind::ConfigMap => {
+                config_maps_api
+                    .get(&reference.name)
+                    .await
+                    .map(|config_map| {
+                        serde_json::to_value((&config_map.data, &config_map.binary_data))
+                    })
+            }
+        };
+        let value = match result {
+            Ok(value) => Some(value?),
+            // the servers do not start without the object, it is hashed as missing
+            Err(kube::Error::Api(response)) if response.code == 404 => None,
+            Err(err) => return Err(err.into()),
+        };
+        data.insert(reference.clone(), value);
+    }
+
+    if data.is_empty() {
+        Ok(None)
+    } else {
+        hash_data(&data).map(Some)
+    }
+}
+
+/// Calculates a stable hash of the data of the referenced objects, `None` stands for a missing
+/// object. Like the configuration hash it only depends on the content.
+pub fn hash_data(data: &BTreeMap<Reference, Option<Value>>) -> Result<String, Error> {
+    let canonical = data
+        .iter()
+        .map(|(reference, value)| (format!("{:?}/{}", reference.kind, reference.name), value))
+        .collect::<BTreeMap<_, _>>();
+    Ok(format!(
+        "{:x}",
+        Sha256::digest(&serde_json::to_vec(&canonical)?)
+    ))
+}
+
+/// Returns the parameters of the watch of the objects of `kind` which may be referenced.
+///
+/// The objects of the operator (like the super user Secret) are left out, they are watched
+/// through their owner already. So are the tokens of ServiceAccounts and the releases of Helm,
+/// which make up most of the Secrets of a cluster and are never referenced.
+pub fn watch_params(kind: ReferenceKind) -> ListParams {
+    let params = ListParams::default().labels(&format!("{}!={}", APP_NAME_LABEL, APP_NAME));
+    match kind {
+        ReferenceKind::Secret => {
+            params.fields("type!=kubernetes.io/service-account-token,type!=helm.sh/release.v1")
+        }
+        ReferenceKind::ConfigMap => params,
+    }
+}
+
+/// The references of all clusters, shared between the reconciliations and the watches of the
+/// controller.
+#[derive(Clone, Debug, Default)]
+pub struct ReferenceIndex {
+    references: Arc<Mutex<BTreeMap<ObjectRef, BTreeSet<Reference>>>>,
+}
+
+impl ReferenceIndex {
+    /// Records the current references of `cluster`.
+    pub fn update(&self, cluster: &ObjectRef, references: BTreeSet<Reference>) {
+        self.references
+            .lock()
+            .unwrap()
+            .insert(cluster.clone(), references);
+    }
+
+    /// Forgets the references of a deleted cluster.
+    pub fn remove(&self, cluster: &ObjectRef) {
+        self.references.lock().unwrap().remove(cluster);
+    }
+
+    /// Returns the clusters referencing the object of the given kind.
+    pub fn clusters_referencing(&self, kind: ReferenceKind, object: &ObjectRef) -> Vec<ObjectRef> {
+        self.references
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(cluster, _)| cluster.namespace == object.namespace)
+            .filter(|(_, references)| {
+                references
+                    .iter()
+                    .any(|reference| reference.kind == kind && reference.name == object.name)
+            })
+            .map(|(cluster, _)| cluster.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use serde_json::json;
+
+    fn spec(yaml: &str) -> ZookeeperClusterSpec {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_references() {
+        let spec = spec(indoc! {"
+            version: 3.5.8
+            servers:
+              roleGroups: {}
+            tls:
+              secretName: zookeeper-tls
+            backup:
+              schedule: 0 3 * * *
+              s3:
+                bucket: backups
+                credentialsSecret: s3-credentials
+        "});
+        let placeholder_secrets = vec!["quorum-password".to_string()].into_iter().collect();
+
+        assert_eq!(
+            references("simple", &spec, &placeholder_secrets),
+            vec![
+                Reference::new(ReferenceKind::Secret, "quorum-password", true),
+                Reference::new(ReferenceKind::Secret, "s3-credentials", false),
+                Reference::new(ReferenceKind::Secret, "zookeeper-tls", true),
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[test]
+    fn test_hash_data() {
+        let reference = Reference::new(ReferenceKind::Secret, "zookeeper-tls", true);
+        let hash = |value: Option<Value>| {
+            let mut data = BTreeMap::new();
+            data.insert(reference.clone(), value);
+            hash_data(&data).unwrap()
+        };
+
+        assert_eq!(
+            hash(Some(json!({ "password": "c2VjcmV0" }))),
+            hash(Some(json!({ "password": "c2VjcmV0" })))
+        );
+        assert_ne!(
+            hash(Some(json!({ "password": "c2VjcmV0" }))),
+            hash(Some(json!({ "password": "b3RoZXI=" })))
+        );
+        assert_ne!(hash(Some(json!({}))), hash(None));
+    }
+
+    #[test]
+    fn test_watch_params() {
+        let secrets = watch_params(ReferenceKind::Secret);
+        assert_eq!(
+            secrets.label_selector.as_deref(),
+            Some("app.kubernetes.io/name!=zookeeper")
+        );
+        assert_eq!(
+            secrets.field_selector.as_deref(),
+            Some("type!=kubernetes.io/service-account-token,type!=helm.sh/release.v1")
+        );
+
+        let config_maps = watch_params(ReferenceKind::ConfigMap);
+        assert_eq!(
+            config_maps.label_selector.as_deref(),
+            Some("app.kubernetes.io/name!=zookeeper")
+        );
+        assert_eq!(config_maps.field_selector, None);
+    }
+
+    #[test]
+    fn test_clusters_referencing() {
+        let index = ReferenceIndex::default();
+        let references: BTreeSet<_> =
+            vec![Reference::new(ReferenceKind::Secret, "zookeeper-tls", true)]
+                .into_iter()
+                .collect();
+        index.update(&ObjectRef::new("team-a", "simple"), references.clone());
+        index.update(&ObjectRef::new("team-b", "simple"), references);
+
+        assert_eq!(
+            index.clusters_referencing(
+                ReferenceKind::Secret,
+                &ObjectRef::new("team-a", "zookeeper-tls")
+            ),
+            vec![ObjectRef::new("team-a", "simple")]
+        );
+        assert!(index
+            .clusters_referencing(
+                ReferenceKind::ConfigMap,
+                &ObjectRef::new("team-a", "zookeeper-tls")
+            )
+            .is_empty());
+
+        index.remove(&ObjectRef::new("team-a", "simple"));
+        assert!(index
+            .clusters_referencing(
+                ReferenceKind::Secret,
+                &ObjectRef::new("team-a", "zookeeper-tls")
+            )
+            .is_empty());
+    }
+}
@@ -0,0 +1,1133 @@
+//! Builders for the pods and ConfigMaps of the servers.
+//!
+//! The builders only take the cluster and values computed by the reconciliation (e.g. the
+//! resolved image or the `myid` of a server), they do not talk to the API server. This way the
+//! generated objects can be tested without a cluster. The other objects of a cluster have builders
+//! in the modules of their features, e.g. [`crate::disruption_budget::build_budget`] or
+//! [`crate::admin_server::build_service`].
+use crate::error::Error;
+use crate::{
+    authentication, compute_resources, config, config_secrets, entrypoint, extra_containers,
+    extra_volumes, host_network, jvm, kerberos, member_replacement, monitoring, pod_overrides,
+    probes, references, scheduling, security_context, service_account, tls,
+    CERTIFICATE_REVISION_ANNOTATION, CONFIG_DIR_NAME, ID_LABEL, PROPERTIES_FILE, SHOULD_BE_SCRAPED,
+    SYNCED_CONDITION,
+};
+
+use k8s_openapi::api::core::v1::{ConfigMap, EnvVar, LocalObjectReference, Pod, PodReadinessGate};
+use kube::ResourceExt;
+use product_config::types::PropertyNameKind;
+use stackable_operator::builder::{
+    ContainerBuilder, ContainerPortBuilder, ObjectMetaBuilder, PodBuilder,
+};
+use stackable_operator::configmap;
+use stackable_operator::labels::{
+    build_common_labels_for_all_managed_resources, get_recommended_labels,
+};
+use stackable_operator::name_utils;
+use stackable_zookeeper_crd::admin_server::ZookeeperAdminServer;
+use stackable_zookeeper_crd::federation::FederationMember;
+use stackable_zookeeper_crd::ip_family::{IpFamily, PREFER_IPV6_ADDRESSES_FLAG};
+use stackable_zookeeper_crd::kerberos::JAAS_CONFIG_FILE;
+use stackable_zookeeper_crd::logging::LOG4J_CONFIG_FILE;
+use stackable_zookeeper_crd::member_replacement::MemberReplacementStatus;
+use stackable_zookeeper_crd::monitoring::ZookeeperMonitoring;
+use stackable_zookeeper_crd::ports::DEFAULT_CLIENT_PORT;
+use stackable_zookeeper_crd::{
+    ZookeeperCluster, ZookeeperVersion, ADMIN_PORT, APP_NAME, CLIENT_PORT, CONFIG_MAP_TYPE_DATA,
+    CONFIG_MAP_TYPE_ID, DATA_DIR, METRICS_PORT,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use tracing::warn;
+
+/// How many characters of the config hash are part of the name of a data ConfigMap, see
+/// [`version_data_config_map`].
+const CONFIG_HASH_NAME_LENGTH: usize = 10;
+
+/// The values computed by the reconciliation which the pod of a server is built from.
+pub struct PodParameters<'a> {
+    /// The Zookeeper role.
+    pub role: &'a str,
+    /// The role group.
+    pub group: &'a str,
+    /// The node the pod runs on.
+    pub node_name: &'a str,
+    /// The labels of the node, e.g. its rack.
+    pub node_labels: &'a BTreeMap<String, String>,
+    /// The `myid` of the server.
+    pub id: usize,
+    /// The version the server runs.
+    pub version: &'a ZookeeperVersion,
+    /// The image resolved for the version and the architecture of the node, before
+    /// `spec.image` is applied.
+    pub image: String,
+    /// The ConfigMaps of the server by type, [`CONFIG_MAP_TYPE_DATA`] and [`CONFIG_MAP_TYPE_ID`].
+    pub config_maps: &'a BTreeMap<&'static str, ConfigMap>,
+    /// The validated product config of the role group.
+    pub validated_config: &'a HashMap<PropertyNameKind, BTreeMap<String, String>>,
+    /// The CPU architectures the image resolver declares for its images, see
+    /// [`scheduling::platform_requirements`].
+    pub image_architectures: &'a [String],
+    /// The revision of the certificate issued by cert-manager.
+    pub certificate_revision: Option<i64>,
+    /// The hash of the Secrets and ConfigMaps the servers use, see [`references`].
+    pub references_hash: Option<&'a str>,
+    /// The replacement of this server if it is replaced, see [`member_replacement`].
+    pub member_replacement: Option<&'a MemberReplacementStatus>,
+    /// The Secret with the files of a pending restore, see [`crate::backup`].
+    pub restore_secret: Option<&'a str>,
+}
+
+/// Returns `spec.monitoring` if the metrics are exported by the JMX exporter sidecar, i.e. the
+/// `version` has no metrics provider of its own.
+pub fn jmx_exporter_monitoring<'a>(
+    cluster: &'a ZookeeperCluster,
+    version: &ZookeeperVersion,
+) -> Option<&'a ZookeeperMonitoring> {
+    cluster
+        .spec
+        .monitoring
+        .as_ref()
+        .filter(|_| !version.has_prometheus_metrics_provider())
+}
+
+/// Builds the ConfigMap containing the 'zoo.cfg' properties file for a role group.
+/// The ConfigMap is annotated with the hash of its content.
+///
+/// Returns `None` if there is no config for the properties file.
+///
+/// # Arguments
+///
+/// - `cluster` - The cluster.
+/// - `version` - The version the servers run.
+/// - `role` - The Zookeeper role.
+/// - `group` - The role group.
+/// - `validated_config` - The validated product config of the role group.
+/// - `node_name_to_id` - The mapping of node names to the `myid` of the server running there.
+/// - `node_addresses` - The addresses of the servers by node name, see [`config::build_zoo_cfg`].
+/// - `observer_node_names` - The nodes whose servers are observers.
+/// - `federation_members` - The servers of the other clusters of the federation.
+/// - `node_role_groups` - The role group of the server on every node, for the groups of a
+///   hierarchical quorum.
+///
+pub fn build_data_config_map(
+    cluster: &ZookeeperCluster,
+    version: &ZookeeperVersion,
+    role: &str,
+    group: &str,
+    validated_config: &HashMap<PropertyNameKind, BTreeMap<String, String>>,
+    node_name_to_id: &BTreeMap<String, usize>,
+    node_addresses: &BTreeMap<String, String>,
+    observer_node_names: &BTreeSet<String>,
+    federation_members: &[FederationMember],
+    node_role_groups: &BTreeMap<String, String>,
+) -> Result<Option<ConfigMap>, Error> {
+    // Get config from product-config for the zookeeper properties file (zoo.cfg)
+    let properties =
+        match validated_config.get(&PropertyNameKind::File(PROPERTIES_FILE.to_string())) {
+            Some(properties) => properties,
+            None => return Ok(None),
+        };
+
+    let mut properties = config_secrets::replace_secret_placeholders(properties)?;
+    if cluster.spec.host_network {
+        properties
+            .entry(host_network::QUORUM_LISTEN_ON_ALL_IPS.to_string())
+            .or_insert_with(|| "true".to_string());
+    }
+    if let Some(hierarchical_quorum) = &cluster.spec.hierarchical_quorum {
+        let mut ids_by_role_group = BTreeMap::<String, Vec<usize>>::new();
+        for (node_name, id) in node_name_to_id {
+            if let Some(role_group) = node_role_groups.get(node_name) {
+                ids_by_role_group
+                    .entry(role_group.clone())
+                    .or_default()
+                    .push(*id);
+            }
+        }
+        properties.extend(hierarchical_quorum.build_properties(&ids_by_role_group));
+    }
+    let zoo_cfg = config::build_zoo_cfg(
+        &properties,
+        node_name_to_id,
+        node_addresses,
+        observer_node_names,
+        federation_members,
+        &cluster.spec.ports.clone().unwrap_or_default(),
+    )?;
+
+    // enhance with config map type label
+    let mut cm_config_data_labels =
+        get_recommended_labels(cluster, APP_NAME, &version.to_string(), role, group);
+    cm_config_data_labels.insert(
+        configmap::CONFIGMAP_TYPE_LABEL.to_string(),
+        CONFIG_MAP_TYPE_DATA.to_string(),
+    );
+
+    let cm_data_name = name_utils::build_resource_name(
+        APP_NAME,
+        &cluster.name(),
+        role,
+        Some(group),
+        None,
+        Some(CONFIG_MAP_TYPE_DATA),
+    )?;
+
+    let mut cm_config_data = BTreeMap::new();
+    cm_config_data.insert(PROPERTIES_FILE.to_string(), zoo_cfg);
+    cm_config_data.insert(
+        entrypoint::ENTRYPOINT_FILE.to_string(),
+        entrypoint::build_entrypoint_script(),
+    );
+    if let Some(kerberos_config) = &cluster.spec.kerberos {
+        cm_config_data.insert(JAAS_CONFIG_FILE.to_string(), kerberos_config.jaas_config());
+    }
+    if let Some(logging) = &cluster.spec.logging {
+        cm_config_data.insert(LOG4J_CONFIG_FILE.to_string(), logging.log4j_properties());
+    }
+    if let Some(monitoring) = jmx_exporter_monitoring(cluster, version) {
+        cm_config_data.insert(
+            monitoring::JMX_EXPORTER_CONFIG_FILE.to_string(),
+            monitoring::build_jmx_exporter_config(monitoring),
+        );
+    }
+
+    let mut cm_data = configmap::build_config_map(
+        cluster,
+        &cm_data_name,
+        &cluster.namespace().unwrap_or_default(),
+        cm_config_data_labels,
+        cm_config_data,
+    )?;
+    let config_hash = config::hash_config_map(&cm_data)?;
+    cm_data
+        .metadata
+        .annotations
+        .insert(config::CONFIG_HASH_ANNOTATION.to_string(), config_hash);
+
+    Ok(Some(cm_data))
+}
+
+/// Appends the beginning of the config hash to the name of the data ConfigMap `config_map`, so
+/// every configuration gets its own ConfigMap. Pods keep mounting the ConfigMap they were created
+/// with, so a restarted container renders the configuration of its pod and not the one of a
+/// rollout which is still held back.
+pub fn version_data_config_map(config_map: &mut ConfigMap) {
+    let hash = config_map
+        .metadata
+        .annotations
+        .get(config::CONFIG_HASH_ANNOTATION);
+    if let (Some(name), Some(hash)) = (config_map.metadata.name.as_mut(), hash) {
+        name.push('-');
+        name.push_str(&hash[..hash.len().min(CONFIG_HASH_NAME_LENGTH)]);
+    }
+}
+
+/// Builds the ConfigMap for the data directory of a server, which only contains the 'myid' file.
+pub fn build_id_config_map(
+    cluster: &ZookeeperCluster,
+    version: &ZookeeperVersion,
+    role: &str,
+    group: &str,
+    id: usize,
+) -> Result<ConfigMap, Error> {
+    let cm_config_id_name = name_utils::build_resource_name(
+        APP_NAME,
+        &cluster.name(),
+        role,
+        Some(group),
+        None,
+        Some(CONFIG_MAP_TYPE_ID),
+    )?;
+
+    // enhance with config map type label and the id for differentiation
+    let mut cm_config_id_labels =
+        get_recommended_labels(cluster, APP_NAME, &version.to_string(), role, group);
+    cm_config_id_labels.insert(
+        configmap::CONFIGMAP_TYPE_LABEL.to_string(),
+        CONFIG_MAP_TYPE_ID.to_string(),
+    );
+    cm_config_id_labels.insert(ID_LABEL.to_string(), id.to_string());
+
+    let mut cm_id_data = BTreeMap::new();
+    cm_id_data.insert("myid".to_string(), id.to_string());
+
+    Ok(configmap::build_config_map(
+        cluster,
+        &cm_config_id_name,
+        &cluster.namespace().unwrap_or_default(),
+        cm_config_id_labels,
+        cm_id_data,
+    )?)
+}
+
+/// Builds the pod of a server.
+pub fn build_pod(cluster: &ZookeeperCluster, params: &PodParameters) -> Result<Pod, Error> {
+    let mut env_vars = vec![];
+    let mut metrics_port: Option<String> = None;
+    let mut java_agent: Option<String> = None;
+    let mut client_port: Option<String> = None;
+    let mut admin_port: Option<String> = None;
+    let mut data_dir: Option<String> = None;
+
+    let version = params.version;
+
+    for (property_name_kind, config) in params.validated_config {
+        match property_name_kind {
+            PropertyNameKind::File(_) => {
+                // we need to extract the client port here to add to container ports later
+                client_port = config.get(CLIENT_PORT).cloned();
+                // we need to extract the admin port here to add to container ports later
+                admin_port = config.get(ADMIN_PORT).cloned();
+                // we need to extract the data dir for the volume mounts later
+                data_dir = config.get(DATA_DIR).cloned();
+                // the values of `${secret:NAME:KEY}` placeholders
+                env_vars.extend(config_secrets::build_env_vars(config)?);
+            }
+            PropertyNameKind::Env => {
+                for (property_name, property_value) in config {
+                    if property_name.is_empty() {
+                        warn!("Received empty property_name for ENV... skipping");
+                        continue;
+                    }
+
+                    // if a metrics port is provided (for now by user, it is not required in
+                    // product config to be able to not configure any monitoring / metrics)
+                    if property_name == METRICS_PORT {
+                        metrics_port = Some(property_value.to_string());
+                        java_agent = Some(format!("-javaagent:{{{{packageroot}}}}/{}/stackable/lib/jmx_prometheus_javaagent-0.16.1.jar={}:{{{{packageroot}}}}/{}/stackable/conf/jmx_exporter.yaml",
+                                                  version.package_name(), property_value, version.package_name()));
+                        continue;
+                    }
+
+                    env_vars.push(EnvVar {
+                        name: property_name.clone(),
+                        value: Some(property_value.clone()),
+                        value_from: None,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let pod_name = name_utils::build_resource_name(
+        APP_NAME,
+        &cluster.name(),
+        params.role,
+        Some(params.group),
+        Some(params.node_name),
+        None,
+    )?;
+
+    let image_config = cluster.spec.image.as_ref();
+    let image = match image_config {
+        Some(image_config) => image_config.apply(&params.image),
+        None => params.image.clone(),
+    };
+    let mut container_builder = ContainerBuilder::new(APP_NAME);
+    container_builder.image(image);
+    let data_dir = data_dir.unwrap_or_else(|| "/tmp/zookeeper".to_string());
+    let (command, args) = entrypoint::build_command(
+        &version.package_name(),
+        &format!("{{{{configroot}}}}/{}", CONFIG_DIR_NAME),
+        &data_dir,
+        &cluster.spec.command,
+        &cluster.spec.args,
+    );
+    container_builder.command(command);
+
+    // One mount for the config directory
+    if let Some(config_map_data) = params.config_maps.get(CONFIG_MAP_TYPE_DATA) {
+        if let Some(name) = config_map_data.metadata.name.as_ref() {
+            container_builder.add_configmapvolume(name, CONFIG_DIR_NAME.to_string());
+        } else {
+            return Err(Error::MissingConfigMapNameError {
+                cm_type: CONFIG_MAP_TYPE_DATA,
+            });
+        }
+    } else {
+        return Err(Error::MissingConfigMapError {
+            cm_type: CONFIG_MAP_TYPE_DATA,
+            pod_name,
+        });
+    }
+
+    // We need a second mount for the data directory
+    // because we need to write the 'myid' file into the data directory
+    if let Some(config_map_data) = params.config_maps.get(CONFIG_MAP_TYPE_ID) {
+        if let Some(name) = config_map_data.metadata.name.as_ref() {
+            container_builder.add_configmapvolume(name, data_dir);
+        } else {
+            return Err(Error::MissingConfigMapNameError {
+                cm_type: CONFIG_MAP_TYPE_ID,
+            });
+        }
+    } else {
+        return Err(Error::MissingConfigMapError {
+            cm_type: CONFIG_MAP_TYPE_ID,
+            pod_name,
+        });
+    }
+
+    let resource_requirements =
+        compute_resources::build_resource_requirements(cluster.spec.resources.as_ref());
+
+    // the heap is derived from the memory limit unless it is configured explicitly
+    let mut server_jvm_flags = vec![jvm::build_server_jvm_flags(
+        cluster.spec.jvm.as_ref(),
+        resource_requirements
+            .limits
+            .get("memory")
+            .map(|quantity| quantity.0.as_str()),
+        java_agent,
+    )?];
+    // the store passwords are not part of zoo.cfg because it ends up in a ConfigMap
+    if let Some(tls_config) = &cluster.spec.tls {
+        env_vars.push(tls::build_password_env_var(tls_config));
+        server_jvm_flags.extend(tls::build_password_jvm_flags(tls_config));
+    }
+    // the super user digest is read from its Secret for the same reason
+    if let Some(authentication) = &cluster.spec.authentication {
+        env_vars.push(authentication::build_super_digest_env_var(&cluster.name()));
+        server_jvm_flags.extend(authentication::build_jvm_flags(authentication));
+    }
+    if cluster.spec.kerberos.is_some() {
+        let jaas_config_path = format!(
+            "{{{{configroot}}}}/{}/{}",
+            CONFIG_DIR_NAME, JAAS_CONFIG_FILE
+        );
+        server_jvm_flags.extend(kerberos::build_jvm_flags(
+            &jaas_config_path,
+            params.node_name,
+        ));
+    }
+    // replaces the log4j.properties on the classpath of the server
+    if cluster.spec.logging.is_some() {
+        server_jvm_flags.push(format!(
+            "-Dlog4j.configuration=file:{{{{configroot}}}}/{}/{}",
+            CONFIG_DIR_NAME, LOG4J_CONFIG_FILE
+        ));
+    }
+    let jmx_exporter_monitoring = jmx_exporter_monitoring(cluster, version);
+    if jmx_exporter_monitoring.is_some() {
+        server_jvm_flags.extend(monitoring::build_jmx_flags());
+    }
+    // Java prefers IPv4 addresses when resolving the names of the servers on dual-stack nodes
+    if cluster.spec.ip_families.first() == Some(&IpFamily::IPv6) {
+        server_jvm_flags.push(PREFER_IPV6_ADDRESSES_FLAG.to_string());
+    }
+    let server_jvm_flags = jvm::join_flags(&server_jvm_flags);
+    if !server_jvm_flags.is_empty() {
+        env_vars.push(EnvVar {
+            name: jvm::SERVER_JVMFLAGS.to_string(),
+            value: Some(server_jvm_flags),
+            ..EnvVar::default()
+        });
+    }
+
+    env_vars.push(EnvVar {
+        name: entrypoint::MYID_ENV_VAR.to_string(),
+        value: Some(params.id.to_string()),
+        ..EnvVar::default()
+    });
+    if let Some(replacement) = params
+        .member_replacement
+        .filter(|replacement| replacement.wipe_data && replacement.id == params.id)
+    {
+        env_vars.push(EnvVar {
+            name: member_replacement::WIPE_DATA_TOKEN_ENV_VAR.to_string(),
+            value: Some(replacement.started_at.clone()),
+            ..EnvVar::default()
+        });
+    }
+    if let Some(restore_secret) = params.restore_secret {
+        env_vars.push(entrypoint::build_restore_env_var(restore_secret));
+    }
+    let rack = scheduling::rack(cluster.spec.placement.as_ref(), params.node_labels);
+    if let Some(rack) = rack {
+        env_vars.push(EnvVar {
+            name: scheduling::RACK_ENV_VAR.to_string(),
+            value: Some(rack.to_string()),
+            ..EnvVar::default()
+        });
+    }
+    container_builder.add_env_vars(env_vars);
+
+    let mut annotations = BTreeMap::new();
+    // remember which configuration the pod was started with, this is used to detect pods
+    // that need to be restarted after the configuration changed
+    if let Some(config_map_data) = params.config_maps.get(CONFIG_MAP_TYPE_DATA) {
+        annotations.insert(
+            config::CONFIG_HASH_ANNOTATION.to_string(),
+            config::hash_config_map(config_map_data)?,
+        );
+    }
+    // remember which certificate the pod was started with to restart it after a renewal
+    if let Some(revision) = params.certificate_revision {
+        annotations.insert(
+            CERTIFICATE_REVISION_ANNOTATION.to_string(),
+            revision.to_string(),
+        );
+    }
+    // remember which Secrets and ConfigMaps the pod was started with to restart it when they
+    // change
+    if let Some(hash) = params.references_hash {
+        annotations.insert(
+            references::REFERENCES_HASH_ANNOTATION.to_string(),
+            hash.to_string(),
+        );
+    }
+    // only add metrics container port and annotation if available
+    if let Some(metrics_port) = metrics_port {
+        annotations.insert(SHOULD_BE_SCRAPED.to_string(), "true".to_string());
+        container_builder.add_container_port(
+            ContainerPortBuilder::new(metrics_port.parse()?)
+                .name("metrics")
+                .build(),
+        );
+    }
+    if let Some(monitoring_config) = &cluster.spec.monitoring {
+        annotations.insert(SHOULD_BE_SCRAPED.to_string(), "true".to_string());
+        // without the metrics provider, the port belongs to the sidecar
+        if jmx_exporter_monitoring.is_none() {
+            container_builder.add_container_port(
+                ContainerPortBuilder::new(monitoring_config.port())
+                    .name("metrics")
+                    .build(),
+            );
+        }
+    }
+    // the probes need the ports before they are moved into the container ports below
+    let probe_client_port = match &client_port {
+        Some(client_port) => client_port.parse()?,
+        None => DEFAULT_CLIENT_PORT,
+    };
+    let admin_server_enabled = version.has_admin_server()
+        && cluster
+            .spec
+            .admin_server
+            .as_ref()
+            .map_or(true, ZookeeperAdminServer::enabled);
+    let probe_admin_port = match &admin_port {
+        Some(admin_port) if admin_server_enabled => Some(admin_port.parse()?),
+        _ => None,
+    };
+    let (liveness_probe, readiness_probe) = probes::build_probes(
+        probe_client_port,
+        probe_admin_port,
+        cluster.spec.probes.as_ref(),
+    );
+
+    // add client port if available
+    if let Some(client_port) = client_port {
+        container_builder.add_container_port(
+            ContainerPortBuilder::new(client_port.parse()?)
+                .name("client")
+                .build(),
+        );
+    }
+
+    // the other servers connect to these, the ports are the same for all servers
+    let ports = cluster.spec.ports.clone().unwrap_or_default();
+    container_builder.add_container_port(
+        ContainerPortBuilder::new(ports.quorum())
+            .name("quorum")
+            .build(),
+    );
+    container_builder.add_container_port(
+        ContainerPortBuilder::new(ports.leader_election())
+            .name("leader-election")
+            .build(),
+    );
+
+    // add admin port if available
+    if let Some(admin_port) = admin_port.filter(|_| admin_server_enabled) {
+        container_builder.add_container_port(
+            ContainerPortBuilder::new(admin_port.parse()?)
+                .name("admin")
+                .build(),
+        );
+    }
+
+    // add the TLS client port if enabled
+    let tls_config = cluster.spec.tls.as_ref();
+    if let Some(tls_config) = tls_config.filter(|tls_config| tls_config.client) {
+        container_builder.add_container_port(
+            ContainerPortBuilder::new(
+                tls_config
+                    .secure_client_port
+                    .unwrap_or(stackable_zookeeper_crd::tls::DEFAULT_SECURE_CLIENT_PORT),
+            )
+            .name("client-tls")
+            .build(),
+        );
+    }
+    let tls_volume = tls_config.map(tls::build_volume);
+    let sasl_volume = cluster
+        .spec
+        .authentication
+        .as_ref()
+        .map(|_| authentication::build_volume(&cluster.name()));
+    let kerberos_volumes = cluster
+        .spec
+        .kerberos
+        .as_ref()
+        .map(kerberos::build_volumes)
+        .unwrap_or_default();
+
+    let mut container = container_builder.build();
+    container.args = args;
+    for (_, volume_mount) in tls_volume
+        .iter()
+        .chain(sasl_volume.iter())
+        .chain(kerberos_volumes.iter())
+    {
+        container.volume_mounts.push(volume_mount.clone());
+    }
+    container.liveness_probe = Some(liveness_probe);
+    container.readiness_probe = Some(readiness_probe);
+    container.resources = Some(resource_requirements);
+    container.image_pull_policy = image_config
+        .and_then(|image_config| image_config.pull_policy)
+        .map(|pull_policy| pull_policy.to_string());
+
+    let mut pod_labels = get_recommended_labels(
+        cluster,
+        APP_NAME,
+        &version.to_string(),
+        params.role,
+        params.group,
+    );
+    // we need to add the zookeeper id to the labels
+    pod_labels.insert(ID_LABEL.to_string(), params.id.to_string());
+    if let Some(rack) = rack {
+        pod_labels.insert(scheduling::RACK_POD_LABEL.to_string(), rack.to_string());
+    }
+
+    let mut pod = PodBuilder::new()
+        .metadata(
+            ObjectMetaBuilder::new()
+                .generate_name(pod_name)
+                .namespace(&cluster.namespace().unwrap_or_default())
+                .with_labels(pod_labels)
+                .with_annotations(annotations)
+                .ownerreference_from_resource(cluster, Some(true), Some(true))?
+                .build()?,
+        )
+        .add_stackable_agent_tolerations()
+        .add_container(container)
+        .node_name(params.node_name)
+        .build()?;
+
+    // The pod should only become ready once the server has synced with the leader,
+    // see `update_synced_conditions`.
+    if let Some(spec) = pod.spec.as_mut() {
+        spec.readiness_gates.push(PodReadinessGate {
+            condition_type: SYNCED_CONDITION.to_string(),
+        });
+
+        if let Some(image_config) = image_config {
+            spec.image_pull_secrets = image_config
+                .pull_secrets
+                .iter()
+                .map(|secret| LocalObjectReference {
+                    name: Some(secret.clone()),
+                })
+                .collect();
+        }
+
+        for (volume, _) in tls_volume
+            .into_iter()
+            .chain(sasl_volume)
+            .chain(kerberos_volumes)
+        {
+            spec.volumes.push(volume);
+        }
+
+        if let Some(monitoring) = jmx_exporter_monitoring {
+            let data_config_map_name = params
+                .config_maps
+                .get(CONFIG_MAP_TYPE_DATA)
+                .and_then(|config_map| config_map.metadata.name.as_deref())
+                .unwrap_or_default();
+            let (sidecar, volume) =
+                monitoring::build_jmx_exporter_sidecar(monitoring, data_config_map_name);
+            spec.containers.push(sidecar);
+            spec.volumes.push(volume);
+        }
+        if let Some(extra) = &cluster.spec.extra_containers {
+            extra_containers::apply(spec, extra);
+        }
+        if let Some(extra_volumes) = &cluster.spec.extra_volumes {
+            extra_volumes::apply(spec, extra_volumes);
+        }
+        if cluster.spec.host_network {
+            spec.host_network = Some(true);
+        }
+        spec.dns_policy = host_network::dns_policy(cluster).map(|policy| policy.to_string());
+
+        let service_account = service_account::config(cluster);
+        spec.service_account_name = Some(service_account.name(&cluster.name()));
+        spec.automount_service_account_token = Some(service_account.automount_token());
+
+        let security = cluster.spec.security_context.clone().unwrap_or_default();
+        if security.enabled() {
+            let writable_dirs =
+                security_context::writable_dirs(&security, cluster.spec.logging.as_ref());
+            security_context::apply(spec, &security, &writable_dirs);
+        }
+
+        let placement = cluster.spec.placement.as_ref();
+        if let Some(tolerations) = placement.and_then(|placement| placement.tolerations.as_ref()) {
+            spec.tolerations = tolerations.clone();
+        } else if !placement
+            .cloned()
+            .unwrap_or_default()
+            .stackable_agent_tolerations(params.image_architectures)
+        {
+            spec.tolerations.clear();
+        }
+        if let Some(placement) = placement {
+            spec.node_selector.extend(placement.node_selector.clone());
+        }
+
+        // A custom scheduler gets the pod unbound but pinned to the node, see `scheduling`.
+        let scheduler_name = cluster.spec.scheduler_name.as_ref();
+        if let Some(scheduler_name) = scheduler_name {
+            spec.node_name = None;
+            spec.scheduler_name = Some(scheduler_name.clone());
+        }
+        spec.affinity = Some(scheduling::build_affinity(
+            placement,
+            &scheduling::platform_requirements(placement, params.image_architectures),
+            build_common_labels_for_all_managed_resources(APP_NAME, &cluster.name()),
+            scheduler_name.map(|_| params.node_name),
+        ));
+        spec.topology_spread_constraints = scheduling::build_topology_spread_constraints(
+            placement,
+            &build_common_labels_for_all_managed_resources(APP_NAME, &cluster.name()),
+        );
+    }
+    if let Some(overrides) = &cluster.spec.pod_overrides {
+        pod_overrides::apply(&mut pod, overrides);
+    }
+
+    Ok(pod)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use indoc::indoc;
+    use serde_json::json;
+    use std::str::FromStr;
+
+    fn cluster() -> ZookeeperCluster {
+        test_support::cluster("simple", json!({}))
+    }
+
+    fn validated_config() -> HashMap<PropertyNameKind, BTreeMap<String, String>> {
+        let mut properties = BTreeMap::new();
+        properties.insert(CLIENT_PORT.to_string(), "2181".to_string());
+        properties.insert(DATA_DIR.to_string(), "/stackable/data".to_string());
+        let mut validated_config = HashMap::new();
+        validated_config.insert(
+            PropertyNameKind::File(PROPERTIES_FILE.to_string()),
+            properties,
+        );
+        validated_config
+    }
+
+    #[test]
+    fn test_build_id_config_map() {
+        let cluster = cluster();
+        let version = ZookeeperVersion::from_str("3.5.8").unwrap();
+
+        let config_map = build_id_config_map(&cluster, &version, "server", "default", 3).unwrap();
+
+        assert_eq!(config_map.metadata.namespace.as_deref(), Some("default"));
+        assert_eq!(config_map.metadata.labels.get(ID_LABEL).unwrap(), "3");
+        assert_eq!(config_map.data.get("myid").unwrap(), "3");
+    }
+
+    #[test]
+    fn test_build_data_config_map() {
+        let cluster = cluster();
+        let version = ZookeeperVersion::from_str("3.5.8").unwrap();
+        let mut node_name_to_id = BTreeMap::new();
+        node_name_to_id.insert("node-1".to_string(), 1);
+
+        let config_map = build_data_config_map(
+            &cluster,
+            &version,
+            "server",
+            "default",
+            &validated_config(),
+            &node_name_to_id,
+            &BTreeMap::new(),
+            &BTreeSet::new(),
+            &[],
+            &BTreeMap::new(),
+        )
+        .unwrap()
+        .unwrap();
+
+        let zoo_cfg = config_map.data.get(PROPERTIES_FILE).unwrap();
+        assert!(zoo_cfg.contains("clientPort=2181"));
+        assert!(zoo_cfg.contains("server.1=node-1"));
+        assert!(config_map.data.contains_key(entrypoint::ENTRYPOINT_FILE));
+        assert_eq!(
+            config_map
+                .metadata
+                .annotations
+                .get(config::CONFIG_HASH_ANNOTATION),
+            Some(&config::hash_config_map(&config_map).unwrap())
+        );
+
+        assert!(build_data_config_map(
+            &cluster,
+            &version,
+            "server",
+            "default",
+            &HashMap::new(),
+            &node_name_to_id,
+            &BTreeMap::new(),
+            &BTreeSet::new(),
+            &[],
+            &BTreeMap::new(),
+        )
+        .unwrap()
+        .is_none());
+    }
+
+    #[test]
+    fn test_version_data_config_map() {
+        let cluster = cluster();
+        let version = ZookeeperVersion::from_str("3.5.8").unwrap();
+        let node_name_to_id = vec![("node-1".to_string(), 1)].into_iter().collect();
+        let build = |node_name_to_id: &BTreeMap<String, usize>| {
+            let mut config_map = build_data_config_map(
+                &cluster,
+                &version,
+                "server",
+                "default",
+                &validated_config(),
+                node_name_to_id,
+                &BTreeMap::new(),
+                &BTreeSet::new(),
+                &[],
+                &BTreeMap::new(),
+            )
+            .unwrap()
+            .unwrap();
+            version_data_config_map(&mut config_map);
+            config_map
+        };
+
+        let config_map = build(&node_name_to_id);
+        let hash = config::hash_config_map(&config_map).unwrap();
+        assert_eq!(
+            config_map.metadata.name,
+            Some(format!("simple-server-default-data-{}", &hash[..10]))
+        );
+        assert_eq!(
+            build(&node_name_to_id).metadata.name,
+            config_map.metadata.name
+        );
+
+        let mut scaled_up = node_name_to_id;
+        scaled_up.insert("node-2".to_string(), 2);
+        assert_ne!(build(&scaled_up).metadata.name, config_map.metadata.name);
+    }
+
+    #[test]
+    fn test_build_data_config_map_with_hierarchical_quorum() {
+        let mut cluster = cluster();
+        cluster.spec.hierarchical_quorum = Some(
+            serde_yaml::from_str(indoc! {"
+                roleGroups:
+                  east:
+                    group: 1
+                  west:
+                    group: 2
+                    weight: 2
+            "})
+            .unwrap(),
+        );
+        let version = ZookeeperVersion::from_str("3.5.8").unwrap();
+        let mut node_name_to_id = BTreeMap::new();
+        let mut node_role_groups = BTreeMap::new();
+        for (node_name, id, role_group) in &[
+            ("node-1", 1, "east"),
+            ("node-2", 2, "west"),
+            ("node-3", 3, "east"),
+            ("node-4", 4, "observers"),
+        ] {
+            node_name_to_id.insert(node_name.to_string(), *id);
+            node_role_groups.insert(node_name.to_string(), role_group.to_string());
+        }
+
+        let config_map = build_data_config_map(
+            &cluster,
+            &version,
+            "server",
+            "east",
+            &validated_config(),
+            &node_name_to_id,
+            &BTreeMap::new(),
+            &BTreeSet::new(),
+            &[],
+            &node_role_groups,
+        )
+        .unwrap()
+        .unwrap();
+
+        let zoo_cfg = config_map.data.get(PROPERTIES_FILE).unwrap();
+        let group_1 = zoo_cfg
+            .lines()
+            .find(|line| line.starts_with("group.1="))
+            .unwrap();
+        // the colon may be escaped in the properties file
+        assert!(group_1.starts_with("group.1=1") && group_1.ends_with('3'));
+        assert!(zoo_cfg.contains("group.2=2"));
+        assert!(zoo_cfg.contains("weight.2=2"));
+        assert!(!zoo_cfg.contains("weight.4"));
+    }
+
+    #[test]
+    fn test_build_pod() {
+        let cluster = cluster();
+        let version = ZookeeperVersion::from_str("3.5.8").unwrap();
+        let mut config_maps = BTreeMap::new();
+        for (cm_type, name) in &[
+            (CONFIG_MAP_TYPE_DATA, "simple-server-default-data"),
+            (CONFIG_MAP_TYPE_ID, "simple-server-default-id"),
+        ] {
+            let mut config_map = ConfigMap::default();
+            config_map.metadata.name = Some(name.to_string());
+            config_maps.insert(*cm_type, config_map);
+        }
+
+        let pod = build_pod(
+            &cluster,
+            &PodParameters {
+                role: "server",
+                group: "default",
+                node_name: "node-1",
+                node_labels: &BTreeMap::new(),
+                id: 1,
+                version: &version,
+                image: "stackable/zookeeper:3.5.8".to_string(),
+                config_maps: &config_maps,
+                validated_config: &validated_config(),
+                image_architectures: &[],
+                certificate_revision: None,
+                references_hash: Some("1234"),
+                member_replacement: None,
+                restore_secret: Some("simple-restore"),
+            },
+        )
+        .unwrap();
+        let spec = pod.spec.unwrap();
+        let container = &spec.containers[0];
+        let port_names = container
+            .ports
+            .iter()
+            .filter_map(|port| port.name.as_deref())
+            .collect::<Vec<_>>();
+
+        assert_eq!(pod.metadata.namespace.as_deref(), Some("default"));
+        assert_eq!(pod.metadata.labels.get(ID_LABEL).unwrap(), "1");
+        assert_eq!(
+            pod.metadata
+                .annotations
+                .get(references::REFERENCES_HASH_ANNOTATION)
+                .unwrap(),
+            "1234"
+        );
+        assert_eq!(pod.metadata.owner_references[0].uid, "simple-uid");
+        assert_eq!(spec.node_name.as_deref(), Some("node-1"));
+        assert_eq!(spec.readiness_gates[0].condition_type, SYNCED_CONDITION);
+        // the tolerations of the Stackable agent
+        assert!(!spec.tolerations.is_empty());
+        assert_eq!(spec.service_account_name.as_deref(), Some("simple-server"));
+        assert_eq!(spec.automount_service_account_token, Some(false));
+        assert_eq!(
+            spec.security_context
+                .as_ref()
+                .and_then(|context| context.run_as_non_root),
+            Some(true)
+        );
+        assert_eq!(
+            container.image.as_deref(),
+            Some("stackable/zookeeper:3.5.8")
+        );
+        assert!(container
+            .env
+            .iter()
+            .any(|env| env.name == entrypoint::MYID_ENV_VAR && env.value.as_deref() == Some("1")));
+        assert!(container
+            .env
+            .contains(&entrypoint::build_restore_env_var("simple-restore")));
+        assert!(port_names.contains(&"client"));
+        assert!(port_names.contains(&"quorum"));
+        assert!(port_names.contains(&"leader-election"));
+    }
+
+    #[test]
+    fn test_build_pod_in_namespace_of_cluster() {
+        let mut cluster = cluster();
+        cluster.metadata.namespace = Some("team-a".to_string());
+        let version = ZookeeperVersion::from_str("3.5.8").unwrap();
+        let mut config_maps = BTreeMap::new();
+        for (cm_type, name) in &[
+            (CONFIG_MAP_TYPE_DATA, "simple-server-default-data"),
+            (CONFIG_MAP_TYPE_ID, "simple-server-default-id"),
+        ] {
+            let mut config_map = ConfigMap::default();
+            config_map.metadata.name = Some(name.to_string());
+            config_maps.insert(*cm_type, config_map);
+        }
+
+        let pod = build_pod(
+            &cluster,
+            &PodParameters {
+                role: "server",
+                group: "default",
+                node_name: "node-1",
+                node_labels: &BTreeMap::new(),
+                id: 1,
+                version: &version,
+                image: "stackable/zookeeper:3.5.8".to_string(),
+                config_maps: &config_maps,
+                validated_config: &validated_config(),
+                image_architectures: &[],
+                certificate_revision: None,
+                references_hash: None,
+                member_replacement: None,
+                restore_secret: None,
+            },
+        )
+        .unwrap();
+
+        // not the default namespace of the client the operator runs with
+        assert_eq!(pod.metadata.namespace.as_deref(), Some("team-a"));
+    }
+
+    #[test]
+    fn test_build_pod_for_architectures() {
+        let mut cluster = cluster();
+        cluster.spec.placement = Some(
+            serde_yaml::from_str(indoc! {"
+                operatingSystems: [linux]
+            "})
+            .unwrap(),
+        );
+        let version = ZookeeperVersion::from_str("3.5.8").unwrap();
+        let mut config_maps = BTreeMap::new();
+        for (cm_type, name) in &[
+            (CONFIG_MAP_TYPE_DATA, "simple-server-default-data"),
+            (CONFIG_MAP_TYPE_ID, "simple-server-default-id"),
+        ] {
+            let mut config_map = ConfigMap::default();
+            config_map.metadata.name = Some(name.to_string());
+            config_maps.insert(*cm_type, config_map);
+        }
+
+        let pod = build_pod(
+            &cluster,
+            &PodParameters {
+                role: "server",
+                group: "default",
+                node_name: "node-1",
+                node_labels: &BTreeMap::new(),
+                id: 1,
+                version: &version,
+                image: "stackable/zookeeper:3.5.8".to_string(),
+                config_maps: &config_maps,
+                validated_config: &validated_config(),
+                image_architectures: &["amd64".to_string(), "arm64".to_string()],
+                certificate_revision: None,
+                references_hash: None,
+                member_replacement: None,
+                restore_secret: None,
+            },
+        )
+        .unwrap();
+        let spec = pod.spec.unwrap();
+        let requirements = &spec
+            .affinity
+            .and_then(|affinity| affinity.node_affinity)
+            .and_then(|node_affinity| {
+                node_affinity.required_during_scheduling_ignored_during_execution
+            })
+            .unwrap()
+            .node_selector_terms[0]
+            .match_expressions;
+
+        assert!(spec.tolerations.is_empty());
+        assert_eq!(requirements[0].key, scheduling::ARCH_LABEL);
+        assert_eq!(requirements[0].values, vec!["amd64", "arm64"]);
+        assert_eq!(requirements[1].key, scheduling::OS_LABEL);
+        assert_eq!(requirements[1].values, vec!["linux"]);
+
+        // the nodes of the Stackable agent match none of the architectures of the images
+        let pod = build_pod(
+            &cluster(),
+            &PodParameters {
+                role: "server",
+                group: "default",
+                node_name: "node-1",
+                node_labels: &BTreeMap::new(),
+                id: 1,
+                version: &version,
+                image: "stackable/zookeeper:3.5.8".to_string(),
+                config_maps: &config_maps,
+                validated_config: &validated_config(),
+                image_architectures: &["amd64".to_string()],
+                certificate_revision: None,
+                references_hash: None,
+                member_replacement: None,
+                restore_secret: None,
+            },
+        )
+        .unwrap();
+        assert!(pod.spec.unwrap().tolerations.is_empty());
+    }
+
+    #[test]
+    fn test_build_pod_without_config_maps() {
+        let cluster = cluster();
+        let version = ZookeeperVersion::from_str("3.5.8").unwrap();
+
+        let result = build_pod(
+            &cluster,
+            &PodParameters {
+                role: "server",
+                group: "default",
+                node_name: "node-1",
+                node_labels: &BTreeMap::new(),
+                id: 1,
+                version: &version,
+                image: "stackable/zookeeper:3.5.8".to_string(),
+                config_maps: &BTreeMap::new(),
+                validated_config: &validated_config(),
+                image_architectures: &[],
+                certificate_revision: None,
+                references_hash: None,
+                member_replacement: None,
+                restore_secret: None,
+            },
+        );
+
+        assert!(matches!(
+            result,
+            Err(Error::MissingConfigMapError {
+                cm_type: CONFIG_MAP_TYPE_DATA,
+                ..
+            })
+        ));
+    }
+}
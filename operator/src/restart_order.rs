@@ -0,0 +1,176 @@
+//! The order of rolling restarts and upgrades: followers and observers first, the leader last.
+//!
+//! Every restart of the leader forces a leader election, during which the ensemble does not serve
+//! any requests. Restarting the leader last makes sure this happens only once per rolling restart
+//! (or upgrade) instead of whenever a newly elected leader is restarted again. Right before every
+//! step the servers are asked for their mode with the health checks of `status.quorum`, so a
+//! leader elected in between is taken into account. While no server reports to be the leader,
+//! the pods are restarted in the order of their `myid`.
+use crate::pod_id;
+
+use k8s_openapi::api::core::v1::Pod;
+use kube::ResourceExt;
+use stackable_zookeeper_crd::quorum::QuorumStatus;
+use stackable_zookeeper_crd::RestartStepStatus;
+
+/// The mode of pods which are missing in the quorum status.
+const UNKNOWN_MODE: &str = "unknown";
+
+/// Pods in the order they are restarted.
+#[derive(Clone, Debug)]
+pub struct RestartOrder<'a> {
+    pub pods: Vec<&'a Pod>,
+    quorum: Option<QuorumStatus>,
+}
+
+impl<'a> RestartOrder<'a> {
+    /// Orders `pods` by their `myid` (pods without one last), except for the leader of `quorum`,
+    /// which is restarted after all other pods.
+    pub fn new(mut pods: Vec<&'a Pod>, quorum: Option<QuorumStatus>) -> RestartOrder<'a> {
+        let leader = quorum.as_ref().and_then(|quorum| quorum.leader.as_deref());
+        pods.sort_by_key(|pod| {
+            (
+                leader == Some(pod.name().as_str()),
+                pod_id(pod).unwrap_or(usize::MAX),
+            )
+        });
+        RestartOrder { pods, quorum }
+    }
+
+    /// Returns the pod which is restarted next.
+    pub fn next(&self) -> Option<&'a Pod> {
+        self.pods.first().copied()
+    }
+
+    /// Returns the names of the pods in order.
+    pub fn names(&self) -> Vec<String> {
+        self.pods.iter().map(|pod| pod.name()).collect()
+    }
+
+    /// Returns the mode `pod` reported, `unknown` if it did not report one.
+    fn mode(&self, pod: &Pod) -> String {
+        self.quorum
+            .as_ref()
+            .and_then(|quorum| {
+                quorum
+                    .servers
+                    .iter()
+                    .find(|server| server.pod == pod.name())
+            })
+            .map_or_else(|| UNKNOWN_MODE.to_string(), |server| server.mode.clone())
+    }
+
+    /// Returns the step restarting the next pod for `reason` at `now`.
+    pub fn next_step(&self, reason: &str, now: &str) -> Option<RestartStepStatus> {
+        let pod = self.next()?;
+        Some(RestartStepStatus {
+            pod: pod.name(),
+            reason: reason.to_string(),
+            mode: self.mode(pod),
+            leader: self
+                .quorum
+                .as_ref()
+                .and_then(|quorum| quorum.leader.clone()),
+            remaining: self.names().into_iter().skip(1).collect(),
+            restarted_at: now.to_string(),
+        })
+    }
+}
+
+/// Describes `step` for the Events of the restarts, e.g. `follower, 2 more pods, the leader
+/// [simple-server-default-2] last`.
+pub fn describe_step(step: &RestartStepStatus) -> String {
+    let mut description = format!("{}, {} more pods", step.mode, step.remaining.len());
+    if let Some(leader) = step.leader.as_ref().filter(|leader| **leader != step.pod) {
+        description.push_str(&format!(", the leader [{}] last", leader));
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::pod_with_id;
+    use rstest::rstest;
+    use stackable_zookeeper_crd::quorum::ServerQuorumStatus;
+
+    fn quorum(leader: Option<&str>) -> QuorumStatus {
+        QuorumStatus {
+            leader: leader.map(str::to_string),
+            servers: vec![
+                ServerQuorumStatus {
+                    pod: "two".to_string(),
+                    mode: "follower".to_string(),
+                    ..ServerQuorumStatus::default()
+                },
+                ServerQuorumStatus {
+                    pod: "three".to_string(),
+                    mode: "leader".to_string(),
+                    ..ServerQuorumStatus::default()
+                },
+            ],
+            ..QuorumStatus::default()
+        }
+    }
+
+    #[rstest]
+    #[case::without_leader(None, vec!["two", "three", "ten", "none"])]
+    #[case::leader_last(Some("three"), vec!["two", "ten", "none", "three"])]
+    #[case::leader_not_restarted(Some("four"), vec!["two", "three", "ten", "none"])]
+    fn test_order(#[case] leader: Option<&str>, #[case] expected: Vec<&str>) {
+        let pods = vec![
+            pod_with_id("ten", Some("10")),
+            pod_with_id("none", None),
+            pod_with_id("two", Some("2")),
+            pod_with_id("three", Some("3")),
+        ];
+        let order = RestartOrder::new(pods.iter().collect(), Some(quorum(leader)));
+
+        assert_eq!(order.names(), expected);
+    }
+
+    #[test]
+    fn test_next_step() {
+        let pods = vec![
+            pod_with_id("three", Some("3")),
+            pod_with_id("two", Some("2")),
+        ];
+        let order = RestartOrder::new(pods.iter().collect(), Some(quorum(Some("three"))));
+
+        let step = order
+            .next_step("upgrade", "2021-09-01T10:00:00+00:00")
+            .unwrap();
+
+        assert_eq!(
+            step,
+            RestartStepStatus {
+                pod: "two".to_string(),
+                reason: "upgrade".to_string(),
+                mode: "follower".to_string(),
+                leader: Some("three".to_string()),
+                remaining: vec!["three".to_string()],
+                restarted_at: "2021-09-01T10:00:00+00:00".to_string(),
+            }
+        );
+        assert_eq!(
+            describe_step(&step),
+            "follower, 1 more pods, the leader [three] last"
+        );
+        assert_eq!(
+            RestartOrder::new(vec![], None).next_step("upgrade", "2021-09-01T10:00:00+00:00"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_describe_leader_step() {
+        let pods = vec![pod_with_id("three", Some("3"))];
+        let order = RestartOrder::new(pods.iter().collect(), Some(quorum(Some("three"))));
+
+        let step = order
+            .next_step("configuration", "2021-09-01T10:00:00+00:00")
+            .unwrap();
+
+        assert_eq!(describe_step(&step), "leader, 0 more pods");
+    }
+}
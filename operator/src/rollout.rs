@@ -0,0 +1,189 @@
+//! Rollouts of a changed configuration to the running servers.
+//!
+//! A pod is outdated if the hash of the configuration of its role group or the hash of the
+//! referenced Secrets and ConfigMaps differs from the one it was started with. The outdated pods
+//! form the plan of the rollout, identified by a revision of the desired hashes, which is
+//! recorded in `status.rollout`. Depending on `spec.updateStrategy` the operator restarts the
+//! outdated pods one by one right away, never (they get the new configuration once they are
+//! deleted) or once [`ROLLOUT_APPROVED_ANNOTATION`] is set to the revision.
+use crate::config::CONFIG_HASH_ANNOTATION;
+use crate::references::REFERENCES_HASH_ANNOTATION;
+
+use k8s_openapi::api::core::v1::Pod;
+use kube::ResourceExt;
+use sha2::{Digest, Sha256};
+use stackable_operator::labels;
+use stackable_zookeeper_crd::{UpdateStrategy, ZookeeperCluster};
+use std::collections::BTreeMap;
+
+/// Annotation on a ZookeeperCluster approving the rollout with the given revision if
+/// `spec.updateStrategy` is `ManualApproval`.
+pub const ROLLOUT_APPROVED_ANNOTATION: &str = "zookeeper.stackable.tech/rollout-approved";
+
+/// The hashes of the configuration every pod should run with.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DesiredHashes {
+    /// The hash of the data ConfigMap by role and role group.
+    pub config: BTreeMap<(String, String), String>,
+    /// The hash of the Secrets and ConfigMaps the servers use.
+    pub references: Option<String>,
+}
+
+impl DesiredHashes {
+    /// Returns whether `pod` runs with a different configuration. Pods of role groups without a
+    /// configuration are never outdated.
+    pub fn is_outdated(&self, pod: &Pod) -> bool {
+        let pod_labels = pod.labels();
+        let role_group = (
+            pod_labels
+                .get(labels::APP_COMPONENT_LABEL)
+                .cloned()
+                .unwrap_or_default(),
+            pod_labels
+                .get(labels::APP_ROLE_GROUP_LABEL)
+                .cloned()
+                .unwrap_or_default(),
+        );
+        let config_outdated = match self.config.get(&role_group) {
+            Some(hash) => pod.annotations().get(CONFIG_HASH_ANNOTATION) != Some(hash),
+            None => false,
+        };
+        // pods without the annotation were started before the references were tracked, they
+        // are annotated instead of restarted
+        let references_outdated = match (
+            &self.references,
+            pod.annotations().get(REFERENCES_HASH_ANNOTATION),
+        ) {
+            (Some(hash), Some(pod_hash)) => pod_hash != hash,
+            _ => false,
+        };
+        config_outdated || references_outdated
+    }
+
+    /// Returns the revision identifying these hashes.
+    pub fn revision(&self) -> String {
+        let mut hasher = Sha256::new();
+        for ((role, role_group), hash) in &self.config {
+            hasher.update(format!("{}/{}={}\n", role, role_group, hash));
+        }
+        hasher.update(self.references.as_deref().unwrap_or_default());
+        format!("{:x}", hasher.finalize())[..16].to_string()
+    }
+}
+
+/// Returns whether the operator may restart the outdated pods for the rollout of `revision`.
+pub fn is_approved(cluster: &ZookeeperCluster, revision: &str) -> bool {
+    match cluster.spec.update_strategy {
+        UpdateStrategy::RollingUpdate => true,
+        UpdateStrategy::OnDelete => false,
+        UpdateStrategy::ManualApproval => {
+            cluster
+                .annotations()
+                .get(ROLLOUT_APPROVED_ANNOTATION)
+                .map(String::as_str)
+                == Some(revision)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use indoc::indoc;
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn desired_hashes() -> DesiredHashes {
+        DesiredHashes {
+            config: vec![(
+                ("server".to_string(), "default".to_string()),
+                "abc".to_string(),
+            )]
+            .into_iter()
+            .collect(),
+            references: Some("def".to_string()),
+        }
+    }
+
+    #[rstest]
+    #[case::up_to_date("default", "abc", "def", false)]
+    #[case::config_changed("default", "old", "def", true)]
+    #[case::references_changed("default", "abc", "old", true)]
+    #[case::unknown_role_group("other", "old", "def", false)]
+    fn test_is_outdated(
+        #[case] role_group: &str,
+        #[case] config_hash: &str,
+        #[case] references_hash: &str,
+        #[case] expected: bool,
+    ) {
+        let pod: Pod = serde_yaml::from_str(&format!(
+            indoc! {"
+                metadata:
+                  name: simple-server-{0}-node-1
+                  labels:
+                    app.kubernetes.io/component: server
+                    app.kubernetes.io/role-group: {0}
+                  annotations:
+                    zookeeper.stackable.tech/config-hash: {1}
+                    zookeeper.stackable.tech/references-hash: {2}
+            "},
+            role_group, config_hash, references_hash
+        ))
+        .unwrap();
+
+        assert_eq!(desired_hashes().is_outdated(&pod), expected);
+    }
+
+    #[test]
+    fn test_is_outdated_without_references_hash() {
+        let pod: Pod = serde_yaml::from_str(indoc! {"
+            metadata:
+              name: simple-server-default-node-1
+              labels:
+                app.kubernetes.io/component: server
+                app.kubernetes.io/role-group: default
+              annotations:
+                zookeeper.stackable.tech/config-hash: abc
+        "})
+        .unwrap();
+
+        assert!(!desired_hashes().is_outdated(&pod));
+    }
+
+    #[test]
+    fn test_revision() {
+        let hashes = desired_hashes();
+        let changed = DesiredHashes {
+            references: Some("ghi".to_string()),
+            ..desired_hashes()
+        };
+
+        assert_eq!(hashes.revision(), desired_hashes().revision());
+        assert_eq!(hashes.revision().len(), 16);
+        assert_ne!(hashes.revision(), changed.revision());
+    }
+
+    #[rstest]
+    #[case::rolling_update("RollingUpdate", None, true)]
+    #[case::on_delete("OnDelete", Some("0123456789abcdef"), false)]
+    #[case::not_approved("ManualApproval", None, false)]
+    #[case::other_revision_approved("ManualApproval", Some("fedcba9876543210"), false)]
+    #[case::approved("ManualApproval", Some("0123456789abcdef"), true)]
+    fn test_is_approved(
+        #[case] update_strategy: &str,
+        #[case] approved_revision: Option<&str>,
+        #[case] expected: bool,
+    ) {
+        let mut cluster =
+            test_support::cluster("simple", json!({ "updateStrategy": update_strategy }));
+        if let Some(revision) = approved_revision {
+            cluster.metadata.annotations.insert(
+                ROLLOUT_APPROVED_ANNOTATION.to_string(),
+                revision.to_string(),
+            );
+        }
+
+        assert_eq!(is_approved(&cluster, "0123456789abcdef"), expected);
+    }
+}
@@ -0,0 +1,201 @@
+//! Scaling up in bounded steps, see `spec.scaling`.
+//!
+//! Every new server is added to the `zoo.cfg` of all servers, so adding many servers at once
+//! raises the quorum size before the new servers are able to vote. With a scaling policy the
+//! operator only assigns ids to (and therefore only creates) `maxStep` new servers at a time.
+//! A step is completed once all servers run with the new membership and pass the health checks,
+//! the next step starts after the cool-down.
+use k8s_openapi::chrono::{DateTime, Utc};
+use stackable_zookeeper_crd::{ScalingStatus, ZookeeperScalingPolicy};
+use std::time::Duration;
+
+const DEFAULT_MAX_STEP: u16 = 1;
+const DEFAULT_COOLDOWN_SECONDS: u64 = 60;
+
+/// How many new servers may be added in this reconciliation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ScaleStep {
+    /// There is no scaling policy (or no running ensemble yet), all missing servers are added.
+    Unlimited,
+    /// The step in progress (or starting now if `started`) allows up to `target` servers.
+    Step { target: usize, started: bool },
+    /// The last step was completed recently, the next one starts after the given duration.
+    CoolingDown(Duration),
+}
+
+/// Decides how many servers may be added.
+///
+/// # Arguments
+///
+/// - `policy` - The scaling policy from the spec.
+/// - `status` - The scaling status of the cluster.
+/// - `existing` - The number of existing servers.
+/// - `missing` - The number of servers which still need to be added.
+/// - `now` - The current time.
+///
+pub fn plan_scale_step(
+    policy: Option<&ZookeeperScalingPolicy>,
+    status: Option<&ScalingStatus>,
+    existing: usize,
+    missing: usize,
+    now: &DateTime<Utc>,
+) -> ScaleStep {
+    let policy = match policy {
+        // The initial creation is not limited, there is no quorum to lose.
+        Some(policy) if existing > 0 && missing > 0 => policy,
+        _ => return ScaleStep::Unlimited,
+    };
+
+    // the replicas may have been lowered since the step started
+    if let Some(target) = status.and_then(|status| status.step_target) {
+        return ScaleStep::Step {
+            target: target.min(existing + missing),
+            started: false,
+        };
+    }
+
+    let step_cooldown = cooldown(policy);
+    let elapsed = status
+        .and_then(|status| status.last_step_completed_at.as_ref())
+        .and_then(|completed_at| DateTime::parse_from_rfc3339(completed_at).ok())
+        .and_then(|completed_at| (*now - completed_at.with_timezone(&Utc)).to_std().ok());
+    if let Some(elapsed) = elapsed {
+        if elapsed < step_cooldown {
+            return ScaleStep::CoolingDown(step_cooldown - elapsed);
+        }
+    }
+
+    let max_step = usize::from(policy.max_step.unwrap_or(DEFAULT_MAX_STEP).max(1));
+    ScaleStep::Step {
+        target: existing + missing.min(max_step),
+        started: true,
+    }
+}
+
+fn cooldown(policy: &ZookeeperScalingPolicy) -> Duration {
+    Duration::from_secs(policy.cooldown_seconds.unwrap_or(DEFAULT_COOLDOWN_SECONDS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2021-09-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[rstest]
+    #[case(None, 3, 6)]
+    #[case(Some(ZookeeperScalingPolicy::default()), 0, 3)]
+    #[case(Some(ZookeeperScalingPolicy::default()), 3, 0)]
+    fn test_unlimited(
+        #[case] policy: Option<ZookeeperScalingPolicy>,
+        #[case] existing: usize,
+        #[case] missing: usize,
+    ) {
+        assert_eq!(
+            plan_scale_step(policy.as_ref(), None, existing, missing, &now()),
+            ScaleStep::Unlimited
+        );
+    }
+
+    #[test]
+    fn test_start_step() {
+        let policy = ZookeeperScalingPolicy {
+            max_step: Some(2),
+            cooldown_seconds: None,
+        };
+
+        assert_eq!(
+            plan_scale_step(Some(&policy), None, 3, 6, &now()),
+            ScaleStep::Step {
+                target: 5,
+                started: true
+            }
+        );
+        assert_eq!(
+            plan_scale_step(Some(&policy), None, 3, 1, &now()),
+            ScaleStep::Step {
+                target: 4,
+                started: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_step_in_progress() {
+        let status = ScalingStatus {
+            step_target: Some(4),
+            last_step_completed_at: None,
+        };
+
+        assert_eq!(
+            plan_scale_step(
+                Some(&ZookeeperScalingPolicy::default()),
+                Some(&status),
+                3,
+                6,
+                &now()
+            ),
+            ScaleStep::Step {
+                target: 4,
+                started: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_step_in_progress_above_lowered_replicas() {
+        let status = ScalingStatus {
+            step_target: Some(6),
+            last_step_completed_at: None,
+        };
+
+        assert_eq!(
+            plan_scale_step(
+                Some(&ZookeeperScalingPolicy::default()),
+                Some(&status),
+                3,
+                1,
+                &now()
+            ),
+            ScaleStep::Step {
+                target: 4,
+                started: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let policy = ZookeeperScalingPolicy {
+            max_step: None,
+            cooldown_seconds: Some(120),
+        };
+        let status = ScalingStatus {
+            step_target: None,
+            last_step_completed_at: Some("2021-09-01T11:59:00Z".to_string()),
+        };
+
+        assert_eq!(
+            plan_scale_step(Some(&policy), Some(&status), 4, 5, &now()),
+            ScaleStep::CoolingDown(Duration::from_secs(60))
+        );
+        assert_eq!(
+            plan_scale_step(
+                Some(&policy),
+                Some(&status),
+                4,
+                5,
+                &(now() + k8s_openapi::chrono::Duration::minutes(2))
+            ),
+            ScaleStep::Step {
+                target: 5,
+                started: true
+            }
+        );
+    }
+}
@@ -0,0 +1,658 @@
+//! Placement of the server pods and their scheduling by a custom scheduler.
+//!
+//! By default the operator binds every pod directly to its node. If `spec.schedulerName` is set,
+//! the pod is handed to that scheduler instead, pinned to the node via a required node affinity
+//! on the node name (like the DaemonSet controller does). This keeps the one server per node
+//! layout the `myid` assignment relies on, while the scheduler can still delay or reject pods,
+//! e.g. to wait for capacity or to enforce topology constraints.
+//!
+//! Because the operator selects the nodes itself, the node selector, the required node affinity,
+//! the anti-affinity and the architectures and operating systems of `spec.placement` are applied
+//! to the eligible nodes as well, see [`apply_placement`].
+use crate::error::Error;
+
+use k8s_openapi::api::core::v1::{
+    Affinity, Node, NodeAffinity, NodeSelector, NodeSelectorRequirement, NodeSelectorTerm, Pod,
+    PodAffinityTerm, PodAntiAffinity, TopologySpreadConstraint, WeightedPodAffinityTerm,
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector;
+use k8s_openapi::chrono::{DateTime, Duration as ChronoDuration, Utc};
+use stackable_operator::role_utils::EligibleNodesForRoleAndGroup;
+use stackable_zookeeper_crd::placement::{
+    AntiAffinityMode, ZookeeperAntiAffinity, ZookeeperPlacement,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The node label holding the CPU architecture of a node.
+pub const ARCH_LABEL: &str = "kubernetes.io/arch";
+/// The node label holding the operating system of a node.
+pub const OS_LABEL: &str = "kubernetes.io/os";
+
+/// The environment variable holding the rack of the node a server runs on.
+pub const RACK_ENV_VAR: &str = "ZOOKEEPER_RACK";
+/// Label on the pods holding the rack of their node.
+pub const RACK_POD_LABEL: &str = "zookeeper.stackable.tech/rack";
+
+/// Annotation set on a pod once its scheduling timeout was reported, so it is only reported once.
+pub const SCHEDULING_TIMEOUT_REPORTED_ANNOTATION: &str =
+    "zookeeper.stackable.tech/scheduling-timeout-reported";
+
+/// How long a scheduler may take to schedule a pod before a warning is published.
+pub const SCHEDULING_TIMEOUT_MINUTES: i64 = 5;
+
+/// Checks that `scheduler_name` is a valid DNS subdomain (RFC 1123), which is what Kubernetes
+/// requires for `schedulerName`.
+pub fn validate_scheduler_name(scheduler_name: &str) -> Result<(), Error> {
+    let valid_characters = scheduler_name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.');
+    let valid_boundaries = scheduler_name
+        .chars()
+        .next()
+        .into_iter()
+        .chain(scheduler_name.chars().last())
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+
+    if !scheduler_name.is_empty()
+        && scheduler_name.len() <= 253
+        && valid_characters
+        && valid_boundaries
+    {
+        Ok(())
+    } else {
+        Err(Error::InvalidSchedulerName {
+            scheduler_name: scheduler_name.to_string(),
+        })
+    }
+}
+
+/// Returns the requirements on the `kubernetes.io/arch` and `kubernetes.io/os` labels of the
+/// nodes: the architectures of the placement (or `image_architectures` if it declares none) and
+/// its operating systems.
+pub fn platform_requirements(
+    placement: Option<&ZookeeperPlacement>,
+    image_architectures: &[String],
+) -> Vec<NodeSelectorRequirement> {
+    let architectures = placement
+        .map(|placement| placement.architectures.as_slice())
+        .filter(|architectures| !architectures.is_empty())
+        .unwrap_or(image_architectures);
+    let operating_systems = placement
+        .map(|placement| placement.operating_systems.as_slice())
+        .unwrap_or_default();
+
+    vec![(ARCH_LABEL, architectures), (OS_LABEL, operating_systems)]
+        .into_iter()
+        .filter(|(_, values)| !values.is_empty())
+        .map(|(key, values)| NodeSelectorRequirement {
+            key: key.to_string(),
+            operator: "In".to_string(),
+            values: values.to_vec(),
+        })
+        .collect()
+}
+
+/// Builds the affinity of a server pod: the affinity of the placement, the anti-affinity between
+/// the servers (selected by `server_labels`), the `platform` requirements (see
+/// [`platform_requirements`]) and, if the pod is handed to a custom scheduler, the required node
+/// affinity pinning it to `pinned_node_name`.
+pub fn build_affinity(
+    placement: Option<&ZookeeperPlacement>,
+    platform: &[NodeSelectorRequirement],
+    server_labels: BTreeMap<String, String>,
+    pinned_node_name: Option<&str>,
+) -> Affinity {
+    let mut affinity = placement
+        .and_then(|placement| placement.affinity.clone())
+        .unwrap_or_default();
+
+    let anti_affinity = placement
+        .and_then(|placement| placement.anti_affinity.clone())
+        .unwrap_or_default();
+    let term = PodAffinityTerm {
+        label_selector: Some(LabelSelector {
+            match_labels: server_labels,
+            ..LabelSelector::default()
+        }),
+        topology_key: anti_affinity.topology_key().to_string(),
+        ..PodAffinityTerm::default()
+    };
+    let pod_anti_affinity = affinity
+        .pod_anti_affinity
+        .get_or_insert_with(PodAntiAffinity::default);
+    match anti_affinity.mode {
+        AntiAffinityMode::Required => pod_anti_affinity
+            .required_during_scheduling_ignored_during_execution
+            .push(term),
+        AntiAffinityMode::Preferred => pod_anti_affinity
+            .preferred_during_scheduling_ignored_during_execution
+            .push(WeightedPodAffinityTerm {
+                pod_affinity_term: term,
+                weight: 100,
+            }),
+    }
+
+    if !platform.is_empty() || pinned_node_name.is_some() {
+        let required = affinity
+            .node_affinity
+            .get_or_insert_with(NodeAffinity::default)
+            .required_during_scheduling_ignored_during_execution
+            .get_or_insert_with(NodeSelector::default);
+        if required.node_selector_terms.is_empty() {
+            required
+                .node_selector_terms
+                .push(NodeSelectorTerm::default());
+        }
+        // the terms are ORed, so the requirements have to be part of every term
+        for term in &mut required.node_selector_terms {
+            term.match_expressions.extend(platform.iter().cloned());
+            if let Some(node_name) = pinned_node_name {
+                term.match_fields.push(pinned_node_requirement(node_name));
+            }
+        }
+    }
+
+    affinity
+}
+
+/// Builds the topology spread constraints of a server pod, constraints without a label selector
+/// select the servers (by `server_labels`).
+pub fn build_topology_spread_constraints(
+    placement: Option<&ZookeeperPlacement>,
+    server_labels: &BTreeMap<String, String>,
+) -> Vec<TopologySpreadConstraint> {
+    placement
+        .map(|placement| placement.topology_spread_constraints.clone())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|mut constraint| {
+            if constraint.label_selector.is_none() {
+                constraint.label_selector = Some(LabelSelector {
+                    match_labels: server_labels.clone(),
+                    ..LabelSelector::default()
+                });
+            }
+            constraint
+        })
+        .collect()
+}
+
+/// Returns the rack of a node, i.e. the value of its label named in `spec.placement.rackLabel`.
+pub fn rack<'a>(
+    placement: Option<&ZookeeperPlacement>,
+    node_labels: &'a BTreeMap<String, String>,
+) -> Option<&'a str> {
+    placement
+        .and_then(|placement| placement.rack_label.as_ref())
+        .and_then(|rack_label| node_labels.get(rack_label))
+        .map(String::as_str)
+}
+
+fn pinned_node_requirement(node_name: &str) -> NodeSelectorRequirement {
+    NodeSelectorRequirement {
+        key: "metadata.name".to_string(),
+        operator: "In".to_string(),
+        values: vec![node_name.to_string()],
+    }
+}
+
+/// Checks whether `node` matches the node selector and the required node affinity of
+/// `placement`.
+pub fn node_matches(node: &Node, placement: &ZookeeperPlacement) -> bool {
+    let labels = &node.metadata.labels;
+    let selector_matches = placement
+        .node_selector
+        .iter()
+        .all(|(key, value)| labels.get(key) == Some(value));
+
+    let affinity_matches = placement
+        .affinity
+        .as_ref()
+        .and_then(|affinity| affinity.node_affinity.as_ref())
+        .and_then(|node_affinity| {
+            node_affinity
+                .required_during_scheduling_ignored_during_execution
+                .as_ref()
+        })
+        .map(|required| {
+            required
+                .node_selector_terms
+                .iter()
+                .any(|term| node_selector_term_matches(node, term))
+        })
+        .unwrap_or(true);
+
+    selector_matches && affinity_matches
+}
+
+fn node_selector_term_matches(node: &Node, term: &NodeSelectorTerm) -> bool {
+    let expressions_match = term.match_expressions.iter().all(|requirement| {
+        requirement_matches(node.metadata.labels.get(&requirement.key), requirement)
+    });
+    let fields_match = term.match_fields.iter().all(|requirement| {
+        let value = match requirement.key.as_str() {
+            "metadata.name" => node.metadata.name.as_ref(),
+            _ => None,
+        };
+        requirement_matches(value, requirement)
+    });
+    // a term without requirements matches no node
+    (!term.match_expressions.is_empty() || !term.match_fields.is_empty())
+        && expressions_match
+        && fields_match
+}
+
+fn requirement_matches(value: Option<&String>, requirement: &NodeSelectorRequirement) -> bool {
+    let as_number = |value: &str| value.parse::<i64>().ok();
+    match (requirement.operator.as_str(), value) {
+        ("In", Some(value)) => requirement.values.contains(value),
+        ("In", None) => false,
+        ("NotIn", Some(value)) => !requirement.values.contains(value),
+        ("NotIn", None) => true,
+        ("Exists", value) => value.is_some(),
+        ("DoesNotExist", value) => value.is_none(),
+        ("Gt", Some(value)) => match (as_number(value), requirement.values.first()) {
+            (Some(value), Some(bound)) => as_number(bound).map_or(false, |bound| value > bound),
+            _ => false,
+        },
+        ("Lt", Some(value)) => match (as_number(value), requirement.values.first()) {
+            (Some(value), Some(bound)) => as_number(bound).map_or(false, |bound| value < bound),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Restricts and orders the eligible nodes according to `placement`.
+///
+/// Nodes which do not match the node selector, the required node affinity or the `platform`
+/// requirements (see [`platform_requirements`]) are removed. Of the
+/// remaining nodes, the nodes already running a server come first, the others are ordered by
+/// name. With a required anti-affinity only one node per topology domain is kept (nodes running
+/// a server are always kept), with a preferred anti-affinity the nodes of unused domains are
+/// moved to the front. Nodes without the topology label are not restricted.
+pub fn apply_placement(
+    eligible_nodes: &mut EligibleNodesForRoleAndGroup,
+    placement: Option<&ZookeeperPlacement>,
+    platform: &[NodeSelectorRequirement],
+    existing_pods: &[Pod],
+) {
+    let default_placement = ZookeeperPlacement::default();
+    let placement = placement.unwrap_or(&default_placement);
+    let anti_affinity = placement
+        .anti_affinity
+        .clone()
+        .unwrap_or_else(ZookeeperAntiAffinity::default);
+    let topology_key = anti_affinity.topology_key();
+
+    let hosting_nodes = existing_pods
+        .iter()
+        .filter_map(|pod| pod.spec.as_ref().and_then(|spec| spec.node_name.clone()))
+        .collect::<BTreeSet<_>>();
+    let runs_server = |node: &Node| {
+        node.metadata
+            .name
+            .as_ref()
+            .map_or(false, |name| hosting_nodes.contains(name))
+    };
+
+    let mut used_domains = eligible_nodes
+        .values()
+        .flat_map(|role_groups| role_groups.values())
+        .flat_map(|(nodes, _)| nodes.iter())
+        .filter(|node| runs_server(node))
+        .filter_map(|node| node.metadata.labels.get(topology_key).cloned())
+        .collect::<BTreeSet<_>>();
+
+    for role_groups in eligible_nodes.values_mut() {
+        let mut group_names = role_groups.keys().cloned().collect::<Vec<_>>();
+        group_names.sort();
+        for group_name in group_names {
+            let nodes = match role_groups.get_mut(&group_name) {
+                Some((nodes, _)) => nodes,
+                None => continue,
+            };
+            nodes.retain(|node| {
+                node_matches(node, placement)
+                    && platform.iter().all(|requirement| {
+                        requirement_matches(node.metadata.labels.get(&requirement.key), requirement)
+                    })
+            });
+            nodes.sort_by_key(|node| (!runs_server(node), node.metadata.name.clone()));
+
+            let mut fresh = Vec::new();
+            let mut crowded = Vec::new();
+            for node in nodes.drain(..) {
+                let in_fresh_domain = runs_server(&node)
+                    || match node.metadata.labels.get(topology_key) {
+                        Some(domain) => used_domains.insert(domain.clone()),
+                        None => true,
+                    };
+                if in_fresh_domain {
+                    fresh.push(node);
+                } else {
+                    crowded.push(node);
+                }
+            }
+            *nodes = fresh;
+            if anti_affinity.mode == AntiAffinityMode::Preferred {
+                nodes.extend(crowded);
+            }
+        }
+    }
+}
+
+/// Checks whether the pod was not scheduled within [`SCHEDULING_TIMEOUT_MINUTES`] and this was
+/// not reported yet.
+pub fn is_scheduling_timed_out(pod: &Pod, now: &DateTime<Utc>) -> bool {
+    let scheduled = pod
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.node_name.as_ref())
+        .is_some();
+    let reported = pod
+        .metadata
+        .annotations
+        .contains_key(SCHEDULING_TIMEOUT_REPORTED_ANNOTATION);
+    let timed_out = pod
+        .metadata
+        .creation_timestamp
+        .as_ref()
+        .map(|created| *now - created.0 > ChronoDuration::minutes(SCHEDULING_TIMEOUT_MINUTES))
+        .unwrap_or(false);
+
+    !scheduled && !reported && timed_out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("topology-scheduler", true)]
+    #[case("scheduler.example.com", true)]
+    #[case("", false)]
+    #[case("Scheduler", false)]
+    #[case("-scheduler", false)]
+    #[case("scheduler.", false)]
+    #[case("my_scheduler", false)]
+    fn test_validate_scheduler_name(#[case] scheduler_name: &str, #[case] valid: bool) {
+        assert_eq!(validate_scheduler_name(scheduler_name).is_ok(), valid);
+    }
+
+    fn node(name: &str, zone: &str) -> Node {
+        // the nodes of zone c are the only ARM nodes
+        let arch = if zone == "c" { "arm64" } else { "amd64" };
+        serde_yaml::from_str(&format!(
+            indoc! {"
+                metadata:
+                  name: {}
+                  labels:
+                    kubernetes.io/hostname: {}
+                    kubernetes.io/arch: {}
+                    kubernetes.io/os: linux
+                    topology.kubernetes.io/zone: {}
+                    disktype: ssd
+            "},
+            name, name, arch, zone
+        ))
+        .unwrap()
+    }
+
+    fn pod_on(node_name: &str) -> Pod {
+        let mut pod = Pod::default();
+        pod.spec = Some(k8s_openapi::api::core::v1::PodSpec {
+            node_name: Some(node_name.to_string()),
+            ..k8s_openapi::api::core::v1::PodSpec::default()
+        });
+        pod
+    }
+
+    fn placed_nodes(placement: &str, existing_pods: &[Pod]) -> Vec<String> {
+        let placement: ZookeeperPlacement = serde_yaml::from_str(placement).unwrap();
+        let mut role_groups = std::collections::HashMap::new();
+        role_groups.insert(
+            "default".to_string(),
+            (
+                vec![
+                    node("node-4", "b"),
+                    node("node-1", "a"),
+                    node("node-2", "a"),
+                    node("node-3", "b"),
+                    node("node-5", "c"),
+                ],
+                None,
+            ),
+        );
+        let mut eligible_nodes = std::collections::HashMap::new();
+        eligible_nodes.insert("server".to_string(), role_groups);
+
+        apply_placement(
+            &mut eligible_nodes,
+            Some(&placement),
+            &platform_requirements(Some(&placement), &[]),
+            existing_pods,
+        );
+
+        eligible_nodes["server"]["default"]
+            .0
+            .iter()
+            .filter_map(|node| node.metadata.name.clone())
+            .collect()
+    }
+
+    #[rstest]
+    #[case::default("{}", &[], &["node-1", "node-2", "node-3", "node-4", "node-5"])]
+    #[case::running_first("{}", &["node-4"], &["node-4", "node-1", "node-2", "node-3", "node-5"])]
+    #[case::node_selector("nodeSelector: {disktype: hdd}", &[], &[])]
+    #[case::node_affinity(
+        "affinity: {nodeAffinity: {requiredDuringSchedulingIgnoredDuringExecution: {nodeSelectorTerms: [{matchExpressions: [{key: topology.kubernetes.io/zone, operator: NotIn, values: [a]}]}]}}}",
+        &[],
+        &["node-3", "node-4", "node-5"]
+    )]
+    #[case::architectures("architectures: [arm64]", &[], &["node-5"])]
+    #[case::operating_systems("operatingSystems: [windows]", &[], &[])]
+    #[case::required_zone(
+        "antiAffinity: {topologyKey: topology.kubernetes.io/zone}",
+        &["node-4"],
+        &["node-4", "node-1", "node-5"]
+    )]
+    #[case::preferred_zone(
+        "antiAffinity: {mode: preferred, topologyKey: topology.kubernetes.io/zone}",
+        &[],
+        &["node-1", "node-3", "node-5", "node-2", "node-4"]
+    )]
+    fn test_apply_placement(
+        #[case] placement: &str,
+        #[case] hosting_nodes: &[&str],
+        #[case] expected: &[&str],
+    ) {
+        let existing_pods = hosting_nodes
+            .iter()
+            .map(|node_name| pod_on(node_name))
+            .collect::<Vec<_>>();
+
+        assert_eq!(placed_nodes(placement, &existing_pods), expected);
+    }
+
+    #[rstest]
+    #[case::unrestricted("{}", &[], &[])]
+    #[case::image_architectures("{}", &["amd64", "arm64"], &[("kubernetes.io/arch", &["amd64", "arm64"])])]
+    #[case::declared_architectures("architectures: [arm64]", &["amd64"], &[("kubernetes.io/arch", &["arm64"])])]
+    #[case::operating_systems(
+        "operatingSystems: [linux]",
+        &["amd64"],
+        &[("kubernetes.io/arch", &["amd64"]), ("kubernetes.io/os", &["linux"])]
+    )]
+    fn test_platform_requirements(
+        #[case] placement: &str,
+        #[case] image_architectures: &[&str],
+        #[case] expected: &[(&str, &[&str])],
+    ) {
+        let placement: ZookeeperPlacement = serde_yaml::from_str(placement).unwrap();
+        let image_architectures = image_architectures
+            .iter()
+            .map(|architecture| architecture.to_string())
+            .collect::<Vec<_>>();
+
+        let requirements = platform_requirements(Some(&placement), &image_architectures);
+
+        assert_eq!(
+            requirements
+                .iter()
+                .map(|requirement| (requirement.key.as_str(), requirement.values.clone()))
+                .collect::<Vec<_>>(),
+            expected
+                .iter()
+                .map(|(key, values)| (*key, values.iter().map(|value| value.to_string()).collect()))
+                .collect::<Vec<(&str, Vec<String>)>>()
+        );
+        assert!(requirements
+            .iter()
+            .all(|requirement| requirement.operator == "In"));
+    }
+
+    #[test]
+    fn test_build_affinity() {
+        let mut labels = BTreeMap::new();
+        labels.insert(
+            "app.kubernetes.io/instance".to_string(),
+            "simple".to_string(),
+        );
+
+        let affinity = build_affinity(None, &[], labels.clone(), None);
+        assert_eq!(affinity.node_affinity, None);
+        let required = affinity
+            .pod_anti_affinity
+            .unwrap()
+            .required_during_scheduling_ignored_during_execution;
+        assert_eq!(required.len(), 1);
+        assert_eq!(required[0].topology_key, "kubernetes.io/hostname");
+
+        let placement: ZookeeperPlacement = serde_yaml::from_str(indoc! {"
+            affinity:
+              nodeAffinity:
+                requiredDuringSchedulingIgnoredDuringExecution:
+                  nodeSelectorTerms:
+                    - matchExpressions:
+                        - key: disktype
+                          operator: In
+                          values: [ssd]
+            antiAffinity:
+              mode: preferred
+        "})
+        .unwrap();
+        let platform = platform_requirements(Some(&placement), &["amd64".to_string()]);
+        let affinity = build_affinity(Some(&placement), &platform, labels, Some("node-1"));
+        let terms = affinity
+            .node_affinity
+            .unwrap()
+            .required_during_scheduling_ignored_during_execution
+            .unwrap()
+            .node_selector_terms;
+        assert_eq!(terms.len(), 1);
+        assert_eq!(terms[0].match_expressions[0].key, "disktype");
+        assert_eq!(terms[0].match_expressions[1].key, ARCH_LABEL);
+        assert_eq!(terms[0].match_expressions[1].values, vec!["amd64"]);
+        assert_eq!(terms[0].match_fields[0].values, vec!["node-1"]);
+        assert_eq!(
+            affinity
+                .pod_anti_affinity
+                .unwrap()
+                .preferred_during_scheduling_ignored_during_execution
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_build_topology_spread_constraints() {
+        let placement: ZookeeperPlacement = serde_yaml::from_str(indoc! {"
+            topologySpreadConstraints:
+              - maxSkew: 1
+                topologyKey: topology.kubernetes.io/zone
+                whenUnsatisfiable: DoNotSchedule
+              - maxSkew: 2
+                topologyKey: kubernetes.io/hostname
+                whenUnsatisfiable: ScheduleAnyway
+                labelSelector:
+                  matchLabels:
+                    app: zookeeper
+        "})
+        .unwrap();
+        let mut labels = BTreeMap::new();
+        labels.insert(
+            "app.kubernetes.io/instance".to_string(),
+            "simple".to_string(),
+        );
+
+        let constraints = build_topology_spread_constraints(Some(&placement), &labels);
+
+        assert_eq!(
+            constraints[0]
+                .label_selector
+                .as_ref()
+                .map(|selector| &selector.match_labels),
+            Some(&labels)
+        );
+        assert_eq!(
+            constraints[1]
+                .label_selector
+                .as_ref()
+                .and_then(|selector| selector.match_labels.get("app")),
+            Some(&"zookeeper".to_string())
+        );
+        assert!(build_topology_spread_constraints(None, &labels).is_empty());
+    }
+
+    #[test]
+    fn test_rack() {
+        let placement: ZookeeperPlacement =
+            serde_yaml::from_str("rackLabel: topology.kubernetes.io/zone").unwrap();
+        let node = node("node-1", "eu-central-1a");
+
+        assert_eq!(
+            rack(Some(&placement), &node.metadata.labels),
+            Some("eu-central-1a")
+        );
+        assert_eq!(rack(None, &node.metadata.labels), None);
+    }
+
+    #[test]
+    fn test_is_scheduling_timed_out() {
+        let mut pod: Pod = serde_yaml::from_str(indoc! {"
+            apiVersion: v1
+            kind: Pod
+            metadata:
+              name: zookeeper-1
+              creationTimestamp: 2021-09-01T12:00:00Z
+            spec:
+              schedulerName: topology-scheduler
+              containers: []
+        "})
+        .unwrap();
+        let created = DateTime::parse_from_rfc3339("2021-09-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert!(!is_scheduling_timed_out(
+            &pod,
+            &(created + ChronoDuration::minutes(1))
+        ));
+        assert!(is_scheduling_timed_out(
+            &pod,
+            &(created + ChronoDuration::minutes(10))
+        ));
+
+        pod.metadata.annotations.insert(
+            SCHEDULING_TIMEOUT_REPORTED_ANNOTATION.to_string(),
+            "true".to_string(),
+        );
+        assert!(!is_scheduling_timed_out(
+            &pod,
+            &(created + ChronoDuration::minutes(10))
+        ));
+    }
+}
@@ -0,0 +1,348 @@
+//! Generates the security context of the server pods from `spec.securityContext`, see
+//! [`stackable_zookeeper_crd::security_context`].
+//!
+//! The pod runs as the configured non-root user with the `RuntimeDefault` seccomp profile. The
+//! containers of the operator drop all capabilities, can not escalate their privileges and
+//! have a read-only root filesystem. The directories the server writes to (`/tmp`, which
+//! contains the default data directory, the directories of the log files and the
+//! `writablePaths`) are `emptyDir` volumes, unless they are already below a writable volume,
+//! e.g. a shared volume of `spec.extraContainers` or a persistent volume of `spec.extraVolumes`.
+//!
+//! Init containers and sidecars without a security context of their own get the same one, but
+//! keep a writable root filesystem because the operator does not know where they write to.
+use crate::monitoring::JMX_EXPORTER_CONTAINER_NAME;
+
+use k8s_openapi::api::core::v1::{
+    Capabilities, Container, EmptyDirVolumeSource, PodSecurityContext, PodSpec, SeccompProfile,
+    SecurityContext, Volume, VolumeMount,
+};
+use stackable_zookeeper_crd::extra_containers::ZookeeperExtraContainers;
+use stackable_zookeeper_crd::extra_volumes::ZookeeperExtraVolumes;
+use stackable_zookeeper_crd::logging::ZookeeperLogging;
+use stackable_zookeeper_crd::security_context::{is_below, ZookeeperSecurityContext, TMP_DIR};
+use stackable_zookeeper_crd::APP_NAME;
+
+/// The prefix of the names of the `emptyDir` volumes for the writable directories, followed by
+/// their index.
+pub const WRITABLE_VOLUME_PREFIX: &str = "writable-";
+
+/// Returns the directories of the server container which have to be writable: `/tmp`, the
+/// directories of the log files and the `writablePaths`.
+pub fn writable_dirs(
+    security_context: &ZookeeperSecurityContext,
+    logging: Option<&ZookeeperLogging>,
+) -> Vec<String> {
+    let log_files = logging.into_iter().flat_map(|logging| {
+        logging
+            .file
+            .iter()
+            .chain(logging.audit.iter().flat_map(|audit| audit.file.iter()))
+    });
+    std::iter::once(TMP_DIR.to_string())
+        .chain(log_files.filter_map(|file| {
+            file.path
+                .rsplit_once('/')
+                .map(|(dir, _)| dir)
+                .filter(|dir| !dir.is_empty())
+                .map(str::to_string)
+        }))
+        .chain(security_context.writable_paths.iter().cloned())
+        .collect()
+}
+
+/// Returns a problem if the `data_dir` of a role group is not writable with a read-only root
+/// filesystem, i.e. neither below one of the [`writable_dirs`] nor below a shared or persistent
+/// volume.
+pub fn validate_data_dir(
+    security_context: &ZookeeperSecurityContext,
+    logging: Option<&ZookeeperLogging>,
+    extra_containers: Option<&ZookeeperExtraContainers>,
+    extra_volumes: Option<&ZookeeperExtraVolumes>,
+    data_dir: &str,
+) -> Option<String> {
+    if !security_context.enabled() || !security_context.read_only_root_filesystem() {
+        return None;
+    }
+    if extra_volumes.map_or(false, |extra_volumes| extra_volumes.is_persistent(data_dir)) {
+        return None;
+    }
+    let shared_volumes = extra_containers
+        .into_iter()
+        .flat_map(|extra| extra.shared_volumes.iter())
+        .map(|volume| volume.mount_path.clone());
+    let writable = writable_dirs(security_context, logging)
+        .into_iter()
+        .chain(shared_volumes)
+        .any(|dir| is_below(data_dir, &dir));
+    if writable {
+        None
+    } else {
+        Some(format!(
+            "securityContext: The dataDir [{}] is not writable with a read-only root filesystem, add it to writablePaths",
+            data_dir
+        ))
+    }
+}
+
+/// Sets the security context of the pod and its containers and mounts `emptyDir` volumes at
+/// the `writable_dirs` of the server container.
+pub fn apply(
+    spec: &mut PodSpec,
+    security_context: &ZookeeperSecurityContext,
+    writable_dirs: &[String],
+) {
+    spec.security_context = Some(PodSecurityContext {
+        run_as_non_root: Some(true),
+        run_as_user: Some(security_context.run_as_user()),
+        run_as_group: Some(security_context.run_as_group()),
+        fs_group: Some(security_context.fs_group()),
+        seccomp_profile: Some(SeccompProfile {
+            type_: "RuntimeDefault".to_string(),
+            localhost_profile: None,
+        }),
+        ..PodSecurityContext::default()
+    });
+
+    let read_only_root_filesystem = security_context.read_only_root_filesystem();
+    for container in spec
+        .init_containers
+        .iter_mut()
+        .chain(spec.containers.iter_mut())
+    {
+        if is_operator_container(container) {
+            container.security_context =
+                Some(build_container_security_context(read_only_root_filesystem));
+        } else if container.security_context.is_none() {
+            container.security_context = Some(build_container_security_context(false));
+        }
+    }
+    if !read_only_root_filesystem {
+        return;
+    }
+
+    let volumes = &mut spec.volumes;
+    let container = match spec
+        .containers
+        .iter_mut()
+        .find(|container| container.name == APP_NAME)
+    {
+        Some(container) => container,
+        None => return,
+    };
+    // e.g. the shared volumes of the sidecars or persistent extra volumes
+    let mut writable_mounts = container
+        .volume_mounts
+        .iter()
+        .filter(|mount| !mount.read_only.unwrap_or(false))
+        .filter(|mount| {
+            volumes.iter().any(|volume| {
+                volume.name == mount.name
+                    && (volume.empty_dir.is_some()
+                        || volume.persistent_volume_claim.is_some()
+                        || volume.host_path.is_some())
+            })
+        })
+        .map(|mount| mount.mount_path.clone())
+        .collect::<Vec<_>>();
+    let mut index = 0;
+    for dir in writable_dirs {
+        if writable_mounts
+            .iter()
+            .any(|writable_mount| is_below(dir, writable_mount))
+        {
+            continue;
+        }
+        let name = format!("{}{}", WRITABLE_VOLUME_PREFIX, index);
+        index += 1;
+        volumes.push(Volume {
+            name: name.clone(),
+            empty_dir: Some(EmptyDirVolumeSource::default()),
+            ..Volume::default()
+        });
+        container.volume_mounts.push(VolumeMount {
+            name,
+            mount_path: dir.clone(),
+            ..VolumeMount::default()
+        });
+        writable_mounts.push(dir.clone());
+    }
+}
+
+fn is_operator_container(container: &Container) -> bool {
+    container.name == APP_NAME || container.name == JMX_EXPORTER_CONTAINER_NAME
+}
+
+fn build_container_security_context(read_only_root_filesystem: bool) -> SecurityContext {
+    SecurityContext {
+        allow_privilege_escalation: Some(false),
+        capabilities: Some(Capabilities {
+            add: vec![],
+            drop: vec!["ALL".to_string()],
+        }),
+        read_only_root_filesystem: Some(read_only_root_filesystem),
+        run_as_non_root: Some(true),
+        ..SecurityContext::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    fn pod_spec() -> PodSpec {
+        serde_yaml::from_str(indoc! {"
+            containers:
+              - name: zookeeper
+                volumeMounts:
+                  - name: logs
+                    mountPath: /stackable/logs
+              - name: jmx-exporter
+              - name: log-shipper
+            volumes:
+              - name: logs
+                emptyDir: {}
+        "})
+        .unwrap()
+    }
+
+    #[test]
+    fn test_writable_dirs() {
+        let security_context: ZookeeperSecurityContext = serde_yaml::from_str(indoc! {"
+            writablePaths:
+              - /data
+        "})
+        .unwrap();
+        let logging: ZookeeperLogging = serde_yaml::from_str(indoc! {"
+            file:
+              path: /stackable/logs/zookeeper.log
+            audit:
+              file:
+                path: /var/log/audit/zookeeper.log
+        "})
+        .unwrap();
+
+        assert_eq!(
+            writable_dirs(&security_context, Some(&logging)),
+            vec!["/tmp", "/stackable/logs", "/var/log/audit", "/data"]
+        );
+    }
+
+    #[test]
+    fn test_validate_data_dir() {
+        let security_context = ZookeeperSecurityContext::default();
+        let extra_containers: ZookeeperExtraContainers = serde_yaml::from_str(indoc! {"
+            sharedVolumes:
+              - name: data
+                mountPath: /data
+        "})
+        .unwrap();
+        let extra_volumes: ZookeeperExtraVolumes = serde_yaml::from_str(indoc! {"
+            volumes:
+              - name: data
+                persistentVolumeClaim:
+                  claimName: zookeeper-data
+            mounts:
+              - name: data
+                mountPath: /data
+        "})
+        .unwrap();
+
+        assert_eq!(
+            validate_data_dir(&security_context, None, None, None, "/tmp/zookeeper"),
+            None
+        );
+        assert_eq!(
+            validate_data_dir(
+                &security_context,
+                None,
+                Some(&extra_containers),
+                None,
+                "/data/zookeeper"
+            ),
+            None
+        );
+        assert_eq!(
+            validate_data_dir(
+                &security_context,
+                None,
+                None,
+                Some(&extra_volumes),
+                "/data/zookeeper"
+            ),
+            None
+        );
+        assert_eq!(
+            validate_data_dir(&security_context, None, None, None, "/data/zookeeper").as_deref(),
+            Some("securityContext: The dataDir [/data/zookeeper] is not writable with a read-only root filesystem, add it to writablePaths")
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut spec = pod_spec();
+        let security_context: ZookeeperSecurityContext = serde_yaml::from_str(indoc! {"
+            runAsUser: 1001
+        "})
+        .unwrap();
+
+        apply(
+            &mut spec,
+            &security_context,
+            &[
+                "/tmp".to_string(),
+                "/stackable/logs".to_string(),
+                "/data".to_string(),
+            ],
+        );
+
+        let pod_security_context = spec.security_context.unwrap();
+        assert_eq!(pod_security_context.run_as_user, Some(1001));
+        assert_eq!(pod_security_context.fs_group, Some(1000));
+        assert_eq!(pod_security_context.run_as_non_root, Some(true));
+        let read_only = spec
+            .containers
+            .iter()
+            .map(|container| {
+                container
+                    .security_context
+                    .as_ref()
+                    .and_then(|context| context.read_only_root_filesystem)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(read_only, vec![Some(true), Some(true), Some(false)]);
+        assert_eq!(
+            spec.containers[0]
+                .volume_mounts
+                .iter()
+                .map(|mount| (mount.name.as_str(), mount.mount_path.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("logs", "/stackable/logs"),
+                ("writable-0", "/tmp"),
+                ("writable-1", "/data"),
+            ]
+        );
+        assert_eq!(spec.volumes.len(), 3);
+    }
+
+    #[test]
+    fn test_apply_with_writable_root_filesystem() {
+        let mut spec = pod_spec();
+        let security_context: ZookeeperSecurityContext = serde_yaml::from_str(indoc! {"
+            readOnlyRootFilesystem: false
+        "})
+        .unwrap();
+
+        apply(&mut spec, &security_context, &["/tmp".to_string()]);
+
+        assert_eq!(spec.volumes.len(), 1);
+        assert_eq!(
+            spec.containers[0]
+                .security_context
+                .as_ref()
+                .and_then(|context| context.read_only_root_filesystem),
+            Some(false)
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Building the ServiceAccount of the server pods and the RoleBinding granting it the configured
+//! role, see [`stackable_zookeeper_crd::service_account`].
+use crate::error::Error;
+
+use k8s_openapi::api::core::v1::ServiceAccount;
+use k8s_openapi::api::rbac::v1::{RoleBinding, RoleRef, Subject};
+use kube::ResourceExt;
+use stackable_operator::builder::ObjectMetaBuilder;
+use stackable_operator::labels::build_common_labels_for_all_managed_resources;
+use stackable_zookeeper_crd::service_account::{RoleKind, ZookeeperServiceAccount};
+use stackable_zookeeper_crd::{ZookeeperCluster, APP_NAME};
+
+/// Returns the name of the RoleBinding of the given cluster. Earlier versions of the operator
+/// also created a Role with this name.
+pub fn role_binding_name(cluster_name: &str) -> String {
+    format!("{}-server", cluster_name)
+}
+
+/// Returns the ServiceAccount settings of `cluster`, the defaults if `spec.serviceAccount` is
+/// not set.
+pub fn config(cluster: &ZookeeperCluster) -> ZookeeperServiceAccount {
+    cluster.spec.service_account.clone().unwrap_or_default()
+}
+
+/// Builds the ServiceAccount of the servers of `cluster`.
+pub fn build_service_account(
+    cluster: &ZookeeperCluster,
+    service_account: &ZookeeperServiceAccount,
+) -> Result<ServiceAccount, Error> {
+    Ok(ServiceAccount {
+        metadata: ObjectMetaBuilder::new()
+            .name(service_account.name(&cluster.name()))
+            .namespace(cluster.metadata.namespace.as_deref().unwrap_or_default())
+            .with_labels(build_common_labels_for_all_managed_resources(
+                APP_NAME,
+                &cluster.name(),
+            ))
+            .with_annotations(service_account.annotations.clone())
+            .ownerreference_from_resource(cluster, Some(true), Some(true))?
+            .build()?,
+        ..ServiceAccount::default()
+    })
+}
+
+/// Builds the RoleBinding granting the role of `spec.serviceAccount.roleRef` to the
+/// ServiceAccount, `None` if no role is configured.
+pub fn build_role_binding(
+    cluster: &ZookeeperCluster,
+    service_account: &ZookeeperServiceAccount,
+) -> Result<Option<RoleBinding>, Error> {
+    let role_ref = match &service_account.role_ref {
+        Some(role_ref) => role_ref,
+        None => return Ok(None),
+    };
+    let cluster_name = cluster.name();
+    let namespace = cluster.metadata.namespace.as_deref().unwrap_or_default();
+    let kind = match role_ref.kind {
+        RoleKind::Role => "Role",
+        RoleKind::ClusterRole => "ClusterRole",
+    };
+
+    Ok(Some(RoleBinding {
+        metadata: ObjectMetaBuilder::new()
+            .name(role_binding_name(&cluster_name))
+            .namespace(namespace)
+            .with_labels(build_common_labels_for_all_managed_resources(
+                APP_NAME,
+                &cluster_name,
+            ))
+            .ownerreference_from_resource(cluster, Some(true), Some(true))?
+            .build()?,
+        role_ref: RoleRef {
+            api_group: "rbac.authorization.k8s.io".to_string(),
+            kind: kind.to_string(),
+            name: role_ref.name.clone(),
+        },
+        subjects: vec![Subject {
+            kind: "ServiceAccount".to_string(),
+            name: service_account.name(&cluster_name),
+            namespace: Some(namespace.to_string()),
+            ..Subject::default()
+        }],
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use serde_json::json;
+
+    fn cluster() -> ZookeeperCluster {
+        test_support::cluster(
+            "simple",
+            json!({
+                "serviceAccount": {
+                    "annotations": {
+                        "iam.gke.io/gcp-service-account": "zookeeper@project.iam.gserviceaccount.com"
+                    },
+                    "roleRef": { "kind": "ClusterRole", "name": "zookeeper-server" }
+                }
+            }),
+        )
+    }
+
+    #[test]
+    fn test_build_service_account() {
+        let cluster = cluster();
+        let service_account = build_service_account(&cluster, &config(&cluster)).unwrap();
+
+        assert_eq!(service_account.name(), "simple-server");
+        assert_eq!(
+            service_account
+                .metadata
+                .annotations
+                .get("iam.gke.io/gcp-service-account")
+                .map(String::as_str),
+            Some("zookeeper@project.iam.gserviceaccount.com")
+        );
+        assert_eq!(service_account.metadata.owner_references.len(), 1);
+    }
+
+    #[test]
+    fn test_build_role_binding() {
+        let cluster = cluster();
+        let role_binding = build_role_binding(&cluster, &config(&cluster))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(role_binding.name(), "simple-server");
+        assert_eq!(role_binding.role_ref.kind, "ClusterRole");
+        assert_eq!(role_binding.role_ref.name, "zookeeper-server");
+        assert_eq!(role_binding.subjects[0].name, "simple-server");
+        assert_eq!(
+            role_binding.subjects[0].namespace.as_deref(),
+            Some("default")
+        );
+        assert_eq!(
+            build_role_binding(&cluster, &ZookeeperServiceAccount::default()).unwrap(),
+            None
+        );
+    }
+}
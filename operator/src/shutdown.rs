@@ -0,0 +1,135 @@
+//! Graceful shutdown of the operator.
+//!
+//! Kubernetes sends SIGTERM before it kills the operator (e.g. during a rollout). Exiting at once
+//! would abort the reconciliations in flight between two steps, e.g. after a pod was deleted but
+//! before its replacement was created, and leave the Lease to expire before a standby can take
+//! over. Instead, no further reconciliation of a ZookeeperCluster is started, the running ones
+//! may finish within a timeout, the Lease is released and the buffered spans are exported before
+//! the process exits. The other controllers only create or patch single objects, so they are
+//! simply stopped.
+use crate::leader_election::LeaderElection;
+use crate::throttle::ReconcileThrottle;
+
+use futures::future::FusedFuture;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{info, warn};
+
+/// Waits until the process is asked to terminate with SIGTERM or SIGINT (Ctrl+C).
+pub async fn wait_for_termination() -> std::io::Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    tokio::select! {
+        _ = terminate.recv() => info!("Received SIGTERM, shutting down"),
+        _ = interrupt.recv() => info!("Received SIGINT, shutting down"),
+    }
+    Ok(())
+}
+
+/// Waits up to `timeout` for the running reconciliations, releases the Lease if leader election
+/// is enabled and exports the buffered spans. The process can exit afterwards.
+///
+/// The reconciliations run within the `controllers`, so they are polled until the running
+/// reconciliations finished, dropping them would abort the reconciliations right away.
+pub async fn shut_down<F>(
+    controllers: F,
+    throttle: &ReconcileThrottle,
+    leader_election: Option<&LeaderElection>,
+    timeout: Duration,
+) where
+    F: FusedFuture + Unpin,
+{
+    info!(
+        "Waiting up to {:?} for the running reconciliations to finish",
+        timeout
+    );
+    if drain(controllers, throttle, timeout).await {
+        info!("All running reconciliations finished");
+    } else {
+        warn!(
+            "Not all reconciliations finished within {:?}, they are aborted and retried after the restart",
+            timeout
+        );
+    }
+    if let Some(leader_election) = leader_election {
+        leader_election.release().await;
+    }
+    crate::logging::shutdown_tracing();
+}
+
+/// Waits up to `timeout` until the running reconciliations finished while polling the
+/// `controllers` they run in, see [`ReconcileThrottle::drain`]. Returns whether all of them
+/// finished in time.
+async fn drain<F>(mut controllers: F, throttle: &ReconcileThrottle, timeout: Duration) -> bool
+where
+    F: FusedFuture + Unpin,
+{
+    let drain = throttle.drain(timeout);
+    tokio::pin!(drain);
+    loop {
+        tokio::select! {
+            drained = &mut drain => return drained,
+            // the controllers end if their watches fail, which also ends their reconciliations
+            _ = &mut controllers, if !controllers.is_terminated() => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::throttle::ReconcilePriority;
+    use futures::FutureExt;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_drain_finishes_running_reconciliation() {
+        let throttle = ReconcileThrottle::new(2, None);
+        let finished = Arc::new(AtomicBool::new(false));
+        let controllers = {
+            let throttle = throttle.clone();
+            let finished = finished.clone();
+            async move {
+                let _permit = throttle.acquire(ReconcilePriority::PendingChanges).await;
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                finished.store(true, Ordering::SeqCst);
+                // the controllers never end by themselves
+                futures::future::pending::<()>().await;
+            }
+        }
+        .boxed()
+        .fuse();
+        tokio::pin!(controllers);
+        // the reconciliation starts before the termination signal arrives
+        tokio::select! {
+            _ = &mut controllers => unreachable!("the controllers do not end"),
+            _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+        }
+        assert!(!finished.load(Ordering::SeqCst));
+
+        assert!(drain(&mut controllers, &throttle, Duration::from_secs(5)).await);
+        assert!(finished.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_drain_timeout() {
+        let throttle = ReconcileThrottle::new(1, None);
+        let controllers = {
+            let throttle = throttle.clone();
+            async move {
+                let _permit = throttle.acquire(ReconcilePriority::PendingChanges).await;
+                futures::future::pending::<()>().await;
+            }
+        }
+        .boxed()
+        .fuse();
+        tokio::pin!(controllers);
+        tokio::select! {
+            _ = &mut controllers => unreachable!("the controllers do not end"),
+            _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+        }
+
+        assert!(!drain(&mut controllers, &throttle, Duration::from_millis(10)).await);
+    }
+}
@@ -0,0 +1,79 @@
+//! Synthetic check of an ensemble after disruptive operations.
+//!
+//! Health checks only tell that every server is part of the quorum. After pods were restarted,
+//! upgraded or added, the operator additionally writes, reads and deletes a probe znode below
+//! [`SMOKE_TEST_ZNODE`] through the client library before it considers the ensemble to be in the
+//! desired state. The pods the check succeeded with are remembered by a fingerprint of their uids,
+//! so it only runs again after pods changed.
+use crate::error::Error;
+use crate::zk_client::ZookeeperConnector;
+
+use k8s_openapi::api::core::v1::Pod;
+use sha2::{Digest, Sha256};
+use std::time::{Duration, Instant};
+
+/// The znode reserved for the probes of the operator.
+pub const SMOKE_TEST_ZNODE: &str = "/zookeeper-operator/smoke-test";
+
+/// Computes the fingerprint of the given pods, which changes whenever a pod is recreated, added
+/// or removed.
+pub fn pods_fingerprint(pods: &[Pod]) -> String {
+    let mut uids = pods
+        .iter()
+        .filter_map(|pod| pod.metadata.uid.as_deref())
+        .collect::<Vec<_>>();
+    uids.sort_unstable();
+    format!("{:x}", Sha256::digest(uids.join(",").as_bytes()))
+}
+
+/// Creates, reads and deletes the probe znode `probe` below [`SMOKE_TEST_ZNODE`] and returns how
+/// long it took, including connecting to the ensemble.
+pub async fn run_smoke_test(
+    zk_connector: &dyn ZookeeperConnector,
+    connection_string: &str,
+    probe: &str,
+) -> Result<Duration, Error> {
+    let started = Instant::now();
+    let path = format!("{}/{}", SMOKE_TEST_ZNODE, probe);
+    let data = probe.as_bytes().to_vec();
+
+    let zk = zk_connector.connect(connection_string).await?;
+    let result: Result<(), Error> = async {
+        zk.ensure_path(SMOKE_TEST_ZNODE).await?;
+        zk.create(&path, data.clone()).await?;
+        let read = zk.get_data(&path).await?;
+        zk.delete_recursive(&path).await?;
+        if read != data {
+            return Err(Error::ReconcileError(format!(
+                "The probe znode [{}] contained unexpected data",
+                path
+            )));
+        }
+        Ok(())
+    }
+    .await;
+    zk.close().await?;
+    result?;
+
+    Ok(started.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pod(uid: &str) -> Pod {
+        let mut pod = Pod::default();
+        pod.metadata.uid = Some(uid.to_string());
+        pod
+    }
+
+    #[test]
+    fn test_pods_fingerprint() {
+        let fingerprint = pods_fingerprint(&[pod("a"), pod("b")]);
+
+        assert_eq!(fingerprint, pods_fingerprint(&[pod("b"), pod("a")]));
+        assert_ne!(fingerprint, pods_fingerprint(&[pod("a"), pod("c")]));
+        assert_ne!(fingerprint, pods_fingerprint(&[pod("a")]));
+    }
+}
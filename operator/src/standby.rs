@@ -0,0 +1,126 @@
+//! Copying the data of a primary cluster into a warm standby (`spec.standbyOf`).
+//!
+//! Every `syncIntervalSeconds` the operator walks the znode tree of the primary and writes the
+//! data and ACLs of all persistent znodes into the standby. Znodes of the standby which do not
+//! exist in the primary (anymore) are deleted. Ephemeral znodes belong to client sessions of the
+//! primary and are not copied, neither are the znodes of ZooKeeper itself and of the operator.
+use crate::error::Error;
+use crate::zk_client::ZookeeperClient;
+
+use k8s_openapi::chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::time::Duration;
+
+/// The condition which tells whether the last copy of the primary's data succeeded.
+pub const STANDBY_SYNCED_CONDITION: &str = "StandbySynced";
+
+/// The znode trees of ZooKeeper and the operator (e.g. the smoke test probes) which are never
+/// copied nor deleted.
+const EXCLUDED_ZNODES: [&str; 2] = ["/zookeeper", "/zookeeper-operator"];
+
+/// Returns how long to wait until the next copy is due, `None` if it is due now.
+pub fn next_sync_in(
+    last_attempt: Option<&DateTime<Utc>>,
+    interval_seconds: u64,
+    now: &DateTime<Utc>,
+) -> Option<Duration> {
+    let next_attempt = *last_attempt? + ChronoDuration::seconds(interval_seconds as i64);
+    (next_attempt - *now)
+        .to_std()
+        .ok()
+        .filter(|wait| *wait > Duration::default())
+}
+
+/// Copies all persistent znodes of `primary` into `standby` and returns the number of copied
+/// znodes.
+pub async fn copy_znodes(
+    primary: &dyn ZookeeperClient,
+    standby: &dyn ZookeeperClient,
+) -> Result<u64, Error> {
+    let mut copied = 0;
+    let mut paths = vec!["/".to_string()];
+    while let Some(path) = paths.pop() {
+        if path != "/" {
+            // ephemeral znodes can not have children
+            if primary.is_ephemeral(&path).await? {
+                continue;
+            }
+            let data = primary.get_data(&path).await?;
+            let acls = primary.get_acls(&path).await?;
+            if standby.exists(&path).await? {
+                standby.set_data(&path, data).await?;
+                standby.set_acls(&path, acls).await?;
+            } else {
+                standby.create_with_acls(&path, data, acls).await?;
+            }
+            copied += 1;
+        }
+
+        let children = primary.get_children(&path).await?;
+        for child in standby.get_children(&path).await? {
+            let child_path = child_path(&path, &child);
+            if !children.contains(&child) && !is_excluded(&child_path) {
+                standby.delete_recursive(&child_path).await?;
+            }
+        }
+        paths.extend(
+            children
+                .iter()
+                .map(|child| child_path(&path, child))
+                .filter(|child_path| !is_excluded(child_path)),
+        );
+    }
+    Ok(copied)
+}
+
+/// Returns the path of the znode `child` below `parent`.
+pub fn child_path(parent: &str, child: &str) -> String {
+    format!("{}/{}", parent.trim_end_matches('/'), child)
+}
+
+/// Whether the znode at `path` belongs to ZooKeeper or the operator.
+pub fn is_excluded(path: &str) -> bool {
+    EXCLUDED_ZNODES.contains(&path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_next_sync_in() {
+        let now = DateTime::parse_from_rfc3339("2021-09-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(next_sync_in(None, 300, &now), None);
+        assert_eq!(
+            next_sync_in(Some(&(now - ChronoDuration::seconds(100))), 300, &now),
+            Some(Duration::from_secs(200))
+        );
+        assert_eq!(
+            next_sync_in(Some(&(now - ChronoDuration::seconds(300))), 300, &now),
+            None
+        );
+        assert_eq!(
+            next_sync_in(Some(&(now - ChronoDuration::seconds(900))), 300, &now),
+            None
+        );
+    }
+
+    #[rstest]
+    #[case("/", "app", "/app", false)]
+    #[case("/app", "config", "/app/config", false)]
+    #[case("/", "zookeeper", "/zookeeper", true)]
+    #[case("/", "zookeeper-operator", "/zookeeper-operator", true)]
+    #[case("/app", "zookeeper", "/app/zookeeper", false)]
+    fn test_child_path(
+        #[case] parent: &str,
+        #[case] child: &str,
+        #[case] expected: &str,
+        #[case] excluded: bool,
+    ) {
+        assert_eq!(child_path(parent, child), expected);
+        assert_eq!(is_excluded(expected), excluded);
+    }
+}
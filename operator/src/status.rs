@@ -0,0 +1,497 @@
+//! Computation of the summary conditions (`Available`, `Progressing`, `Degraded` and `Stalled`) which are
+//! written to the status of a ZookeeperCluster at the end of every reconciliation, and of the
+//! time it took to reach the desired state.
+use crate::error::{Error, ErrorClass};
+
+use k8s_openapi::chrono::{DateTime, Utc};
+use stackable_operator::reconcile::ReconcileFunctionAction;
+use stackable_zookeeper_crd::{PendingTransition, TransitionDuration, ZookeeperClusterStatus};
+
+pub const AVAILABLE_CONDITION: &str = "Available";
+pub const PROGRESSING_CONDITION: &str = "Progressing";
+pub const DEGRADED_CONDITION: &str = "Degraded";
+pub const STALLED_CONDITION: &str = "Stalled";
+
+/// The transition started with the creation of the ZookeeperCluster.
+pub const CREATION_TRIGGER: &str = "creation";
+/// The transition started with a change of the spec.
+pub const SPEC_CHANGE_TRIGGER: &str = "specChange";
+
+/// A condition as it should be set on the ZookeeperCluster.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClusterCondition {
+    pub condition_type: &'static str,
+    pub status: bool,
+    pub reason: &'static str,
+    pub message: String,
+}
+
+/// Summary of the state of an ensemble after a reconciliation.
+pub struct EnsembleState<'a> {
+    /// The number of servers requested by the spec.
+    pub desired_replicas: usize,
+    /// The number of servers whose pods are running and ready.
+    pub ready_replicas: usize,
+    /// Whether a version upgrade is in progress.
+    pub upgrading: bool,
+    /// The outcome of the reconciliation.
+    pub outcome: &'a Result<ReconcileFunctionAction, Error>,
+}
+
+/// Computes the `Available`, `Progressing` and `Degraded` conditions.
+///
+/// - `Available`: a quorum (a majority of the desired servers) is ready to serve requests.
+/// - `Progressing`: the operator is still working towards the desired state (pods are being
+///   created, restarted or upgraded).
+/// - `Degraded`: servers are missing or the last reconciliation failed, e.g. because the spec
+///   violates the rules of [`crate::validation`].
+pub fn compute_conditions(state: &EnsembleState) -> Vec<ClusterCondition> {
+    let quorum = state.desired_replicas / 2 + 1;
+    let available = state.desired_replicas > 0 && state.ready_replicas >= quorum;
+    let requeued = matches!(state.outcome, Ok(ReconcileFunctionAction::Requeue(_)));
+    let missing_replicas = state.ready_replicas < state.desired_replicas;
+
+    let available_condition = if available {
+        ClusterCondition {
+            condition_type: AVAILABLE_CONDITION,
+            status: true,
+            reason: "QuorumAvailable",
+            message: format!(
+                "[{}/{}] servers are ready, a quorum of [{}] is available",
+                state.ready_replicas, state.desired_replicas, quorum
+            ),
+        }
+    } else {
+        ClusterCondition {
+            condition_type: AVAILABLE_CONDITION,
+            status: false,
+            reason: "QuorumUnavailable",
+            message: format!(
+                "[{}/{}] servers are ready, a quorum of [{}] is required",
+                state.ready_replicas, state.desired_replicas, quorum
+            ),
+        }
+    };
+
+    let progressing_condition = if state.upgrading {
+        ClusterCondition {
+            condition_type: PROGRESSING_CONDITION,
+            status: true,
+            reason: "Upgrading",
+            message: "A version upgrade is in progress".to_string(),
+        }
+    } else if requeued || missing_replicas {
+        ClusterCondition {
+            condition_type: PROGRESSING_CONDITION,
+            status: true,
+            reason: "Reconciling",
+            message: "The ensemble is being moved towards the desired state".to_string(),
+        }
+    } else {
+        ClusterCondition {
+            condition_type: PROGRESSING_CONDITION,
+            status: false,
+            reason: "ReconciliationComplete",
+            message: "The ensemble is in the desired state".to_string(),
+        }
+    };
+
+    let degraded_condition = match state.outcome {
+        Err(err @ Error::ZookeeperClusterIsBad { .. }) => ClusterCondition {
+            condition_type: DEGRADED_CONDITION,
+            status: true,
+            reason: "InvalidSpec",
+            message: err.to_string(),
+        },
+        Err(err) => ClusterCondition {
+            condition_type: DEGRADED_CONDITION,
+            status: true,
+            reason: "ReconciliationFailed",
+            message: err.to_string(),
+        },
+        Ok(_) if missing_replicas => ClusterCondition {
+            condition_type: DEGRADED_CONDITION,
+            status: true,
+            reason: "ServersNotReady",
+            message: format!(
+                "[{}] of [{}] servers are not ready",
+                state.desired_replicas - state.ready_replicas,
+                state.desired_replicas
+            ),
+        },
+        Ok(_) => ClusterCondition {
+            condition_type: DEGRADED_CONDITION,
+            status: false,
+            reason: "AllServersReady",
+            message: "All servers are ready".to_string(),
+        },
+    };
+
+    vec![
+        available_condition,
+        progressing_condition,
+        degraded_condition,
+    ]
+}
+
+/// Computes the `Stalled` condition, which is `True` if the last reconciliation failed with a
+/// terminal error (see [`Error::class`]). Such a cluster is not retried until it is changed,
+/// because retrying cannot succeed without an intervention.
+pub fn compute_stalled_condition(
+    outcome: &Result<ReconcileFunctionAction, Error>,
+) -> ClusterCondition {
+    match outcome {
+        Err(err) if err.class() == ErrorClass::Terminal => ClusterCondition {
+            condition_type: STALLED_CONDITION,
+            status: true,
+            reason: err.variant_name(),
+            message: format!(
+                "The reconciliation is not retried until the cluster is changed: {}",
+                err
+            ),
+        },
+        _ => ClusterCondition {
+            condition_type: STALLED_CONDITION,
+            status: false,
+            reason: "Retrying",
+            message: "Failed reconciliations are retried".to_string(),
+        },
+    }
+}
+
+/// Marks the ensemble as degraded because the smoke test after a disruptive operation failed
+/// (see [`crate::smoke_test`]), which also keeps the transition towards the desired state open.
+pub fn apply_smoke_test_failure(conditions: &mut [ClusterCondition], error: &Error) {
+    set_degraded(
+        conditions,
+        "SmokeTestFailed",
+        format!("The smoke test failed: {}", error),
+    );
+}
+
+/// Marks the ensemble as degraded because the canary of an upgrade failed (see
+/// [`crate::upgrade`]), which halts the upgrade.
+pub fn apply_canary_failure(conditions: &mut [ClusterCondition], message: &str) {
+    set_degraded(
+        conditions,
+        "CanaryFailed",
+        format!("The canary of the upgrade failed: {}", message),
+    );
+}
+
+/// Marks the ensemble as degraded because the integrity check before an upgrade failed (see
+/// [`crate::integrity_check`]), which halts the upgrade.
+pub fn apply_integrity_check_failure(conditions: &mut [ClusterCondition], message: &str) {
+    set_degraded(
+        conditions,
+        "IntegrityCheckFailed",
+        format!("The integrity check before the upgrade failed: {}", message),
+    );
+}
+
+fn set_degraded(conditions: &mut [ClusterCondition], reason: &'static str, message: String) {
+    for condition in conditions {
+        if condition.condition_type == DEGRADED_CONDITION {
+            condition.status = true;
+            condition.reason = reason;
+            condition.message = message.clone();
+        }
+    }
+}
+
+/// Checks whether the conditions describe an ensemble in the desired state: available, not
+/// progressing and not degraded.
+pub fn is_desired_state(conditions: &[ClusterCondition]) -> bool {
+    conditions
+        .iter()
+        .all(|condition| condition.status == (condition.condition_type == AVAILABLE_CONDITION))
+}
+
+/// The outcome of [`track_transition`].
+#[derive(Debug, Eq, PartialEq)]
+pub struct TransitionUpdate {
+    /// The transition which is still in progress.
+    pub pending: Option<PendingTransition>,
+    /// The trigger and the duration of the transition which just completed.
+    pub completed: Option<(String, TransitionDuration)>,
+}
+
+/// Tracks the transition of a ZookeeperCluster towards its desired state.
+///
+/// A transition starts when a generation is seen for the first time: with the creation timestamp
+/// for new clusters and with the current time for spec changes. Further spec changes while a
+/// transition is in progress extend it. The transition completes as soon as the desired state is
+/// reached.
+///
+/// # Arguments
+///
+/// - `status` - The status before this reconciliation.
+/// - `generation` - The current `metadata.generation`.
+/// - `created_at` - The creation timestamp of the ZookeeperCluster.
+/// - `now` - The current time.
+/// - `desired_state` - Whether the desired state is reached, see [`is_desired_state`].
+///
+pub fn track_transition(
+    status: Option<&ZookeeperClusterStatus>,
+    generation: i64,
+    created_at: Option<&DateTime<Utc>>,
+    now: &DateTime<Utc>,
+    desired_state: bool,
+) -> TransitionUpdate {
+    let observed_generation = status.and_then(|status| status.observed_generation);
+    let mut pending = status.and_then(|status| status.pending_transition.clone());
+
+    if observed_generation != Some(generation) {
+        match pending.as_mut() {
+            Some(pending) => pending.generation = generation,
+            None if observed_generation.is_none() => {
+                pending = Some(PendingTransition {
+                    trigger: CREATION_TRIGGER.to_string(),
+                    generation,
+                    started_at: created_at.unwrap_or(now).to_rfc3339(),
+                })
+            }
+            None => {
+                pending = Some(PendingTransition {
+                    trigger: SPEC_CHANGE_TRIGGER.to_string(),
+                    generation,
+                    started_at: now.to_rfc3339(),
+                })
+            }
+        }
+    }
+
+    match pending {
+        Some(pending) if desired_state => {
+            // An unparseable timestamp was not written by us, the duration is unknown then.
+            let seconds = DateTime::parse_from_rfc3339(&pending.started_at)
+                .map(|started_at| (*now - started_at.with_timezone(&Utc)).num_seconds())
+                .unwrap_or_default();
+            TransitionUpdate {
+                pending: None,
+                completed: Some((
+                    pending.trigger,
+                    TransitionDuration {
+                        generation: pending.generation,
+                        seconds,
+                        completed_at: now.to_rfc3339(),
+                    },
+                )),
+            }
+        }
+        pending => TransitionUpdate {
+            pending,
+            completed: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::chrono::Duration as ChronoDuration;
+    use rstest::rstest;
+    use std::time::Duration;
+
+    fn statuses(conditions: &[ClusterCondition]) -> Vec<(&str, bool, &str)> {
+        conditions
+            .iter()
+            .map(|condition| (condition.condition_type, condition.status, condition.reason))
+            .collect()
+    }
+
+    #[rstest]
+    #[case::healthy(3, 3, false, Ok(ReconcileFunctionAction::Continue), vec![
+        (AVAILABLE_CONDITION, true, "QuorumAvailable"),
+        (PROGRESSING_CONDITION, false, "ReconciliationComplete"),
+        (DEGRADED_CONDITION, false, "AllServersReady"),
+    ])]
+    #[case::one_missing(3, 2, false, Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10))), vec![
+        (AVAILABLE_CONDITION, true, "QuorumAvailable"),
+        (PROGRESSING_CONDITION, true, "Reconciling"),
+        (DEGRADED_CONDITION, true, "ServersNotReady"),
+    ])]
+    #[case::no_quorum(3, 1, false, Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10))), vec![
+        (AVAILABLE_CONDITION, false, "QuorumUnavailable"),
+        (PROGRESSING_CONDITION, true, "Reconciling"),
+        (DEGRADED_CONDITION, true, "ServersNotReady"),
+    ])]
+    #[case::upgrading(3, 3, true, Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10))), vec![
+        (AVAILABLE_CONDITION, true, "QuorumAvailable"),
+        (PROGRESSING_CONDITION, true, "Upgrading"),
+        (DEGRADED_CONDITION, false, "AllServersReady"),
+    ])]
+    #[case::failed(3, 3, false, Err(Error::ReconcileError("test".to_string())), vec![
+        (AVAILABLE_CONDITION, true, "QuorumAvailable"),
+        (PROGRESSING_CONDITION, false, "ReconciliationComplete"),
+        (DEGRADED_CONDITION, true, "ReconciliationFailed"),
+    ])]
+    #[case::invalid_spec(3, 3, false, Err(Error::ZookeeperClusterIsBad { violations: vec![] }), vec![
+        (AVAILABLE_CONDITION, true, "QuorumAvailable"),
+        (PROGRESSING_CONDITION, false, "ReconciliationComplete"),
+        (DEGRADED_CONDITION, true, "InvalidSpec"),
+    ])]
+    #[case::no_servers(0, 0, false, Ok(ReconcileFunctionAction::Continue), vec![
+        (AVAILABLE_CONDITION, false, "QuorumUnavailable"),
+        (PROGRESSING_CONDITION, false, "ReconciliationComplete"),
+        (DEGRADED_CONDITION, false, "AllServersReady"),
+    ])]
+    fn test_compute_conditions(
+        #[case] desired_replicas: usize,
+        #[case] ready_replicas: usize,
+        #[case] upgrading: bool,
+        #[case] outcome: Result<ReconcileFunctionAction, Error>,
+        #[case] expected: Vec<(&str, bool, &str)>,
+    ) {
+        let conditions = compute_conditions(&EnsembleState {
+            desired_replicas,
+            ready_replicas,
+            upgrading,
+            outcome: &outcome,
+        });
+
+        assert_eq!(statuses(&conditions), expected);
+    }
+
+    #[rstest]
+    #[case::succeeded(Ok(ReconcileFunctionAction::Continue), false, "Retrying")]
+    #[case::transient(Err(Error::ReconcileError("test".to_string())), false, "Retrying")]
+    #[case::invalid_spec(Err(Error::ZookeeperClusterIsBad { violations: vec![] }), true, "ZookeeperClusterIsBad")]
+    fn test_compute_stalled_condition(
+        #[case] outcome: Result<ReconcileFunctionAction, Error>,
+        #[case] expected_status: bool,
+        #[case] expected_reason: &str,
+    ) {
+        let condition = compute_stalled_condition(&outcome);
+
+        assert_eq!(condition.condition_type, STALLED_CONDITION);
+        assert_eq!(condition.status, expected_status);
+        assert_eq!(condition.reason, expected_reason);
+    }
+
+    #[test]
+    fn test_is_desired_state() {
+        let conditions = |ready_replicas| {
+            compute_conditions(&EnsembleState {
+                desired_replicas: 3,
+                ready_replicas,
+                upgrading: false,
+                outcome: &Ok(ReconcileFunctionAction::Continue),
+            })
+        };
+
+        assert!(is_desired_state(&conditions(3)));
+        assert!(!is_desired_state(&conditions(2)));
+    }
+
+    #[test]
+    fn test_apply_smoke_test_failure() {
+        let mut conditions = compute_conditions(&EnsembleState {
+            desired_replicas: 3,
+            ready_replicas: 3,
+            upgrading: false,
+            outcome: &Ok(ReconcileFunctionAction::Continue),
+        });
+
+        apply_smoke_test_failure(&mut conditions, &Error::ReconcileError("test".to_string()));
+
+        assert_eq!(
+            statuses(&conditions),
+            vec![
+                (AVAILABLE_CONDITION, true, "QuorumAvailable"),
+                (PROGRESSING_CONDITION, false, "ReconciliationComplete"),
+                (DEGRADED_CONDITION, true, "SmokeTestFailed"),
+            ]
+        );
+        assert!(!is_desired_state(&conditions));
+    }
+
+    #[test]
+    fn test_apply_canary_failure() {
+        let mut conditions = compute_conditions(&EnsembleState {
+            desired_replicas: 3,
+            ready_replicas: 3,
+            upgrading: true,
+            outcome: &Ok(ReconcileFunctionAction::Continue),
+        });
+
+        apply_canary_failure(&mut conditions, "test");
+
+        assert_eq!(
+            statuses(&conditions),
+            vec![
+                (AVAILABLE_CONDITION, true, "QuorumAvailable"),
+                (PROGRESSING_CONDITION, true, "Upgrading"),
+                (DEGRADED_CONDITION, true, "CanaryFailed"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_integrity_check_failure() {
+        let mut conditions = compute_conditions(&EnsembleState {
+            desired_replicas: 3,
+            ready_replicas: 3,
+            upgrading: true,
+            outcome: &Ok(ReconcileFunctionAction::Continue),
+        });
+
+        apply_integrity_check_failure(&mut conditions, "test");
+
+        assert_eq!(
+            statuses(&conditions),
+            vec![
+                (AVAILABLE_CONDITION, true, "QuorumAvailable"),
+                (PROGRESSING_CONDITION, true, "Upgrading"),
+                (DEGRADED_CONDITION, true, "IntegrityCheckFailed"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_track_transition() {
+        let created_at = DateTime::parse_from_rfc3339("2021-09-01T12:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let now = created_at + ChronoDuration::seconds(90);
+
+        // a new cluster which is not ready yet
+        let update = track_transition(None, 1, Some(&created_at), &now, false);
+        let pending = update.pending.unwrap();
+        assert_eq!(pending.trigger, CREATION_TRIGGER);
+        assert_eq!(pending.started_at, created_at.to_rfc3339());
+        assert_eq!(update.completed, None);
+
+        // the spec changes before the cluster is ready, which extends the transition
+        let mut status = ZookeeperClusterStatus {
+            observed_generation: Some(1),
+            pending_transition: Some(pending),
+            ..ZookeeperClusterStatus::default()
+        };
+        let update = track_transition(Some(&status), 2, Some(&created_at), &now, true);
+        assert_eq!(update.pending, None);
+        let (trigger, duration) = update.completed.unwrap();
+        assert_eq!(trigger, CREATION_TRIGGER);
+        assert_eq!(duration.generation, 2);
+        assert_eq!(duration.seconds, 90);
+
+        // a spec change of a ready cluster
+        status.observed_generation = Some(2);
+        status.pending_transition = None;
+        let update = track_transition(Some(&status), 3, Some(&created_at), &now, false);
+        let pending = update.pending.unwrap();
+        assert_eq!(pending.trigger, SPEC_CHANGE_TRIGGER);
+        assert_eq!(pending.started_at, now.to_rfc3339());
+
+        // nothing changed
+        status.observed_generation = Some(3);
+        let update = track_transition(Some(&status), 3, Some(&created_at), &now, true);
+        assert_eq!(
+            update,
+            TransitionUpdate {
+                pending: None,
+                completed: None
+            }
+        );
+    }
+}
@@ -0,0 +1,344 @@
+//! Refreshing only the status of a cluster if only the status of its pods changed.
+//!
+//! A pod which crashes or becomes ready triggers a reconciliation, but once a cluster reached its
+//! desired state there is nothing to apply: only `readyReplicas`, the members and the conditions
+//! change. The pods are therefore watched with [`StatusRefresh::pod_changed`], which tells changes
+//! of the status of a pod apart from all other changes by a fingerprint of the rest of the pod.
+//! A reconciliation only refreshes the status instead of running all steps if
+//!
+//! - the last full reconciliation ran all steps without waiting,
+//! - only changes of the status of pods triggered it since then,
+//! - the generation, the labels and the annotations of the cluster and everything but the status
+//!   of its pods are the same as in the last full reconciliation,
+//! - all pods are ready and `spec.memberReplacement` is not configured, because a server which
+//!   lost its readiness may have to be waited for, reported or replaced and
+//! - the next full reconciliation the last one scheduled is not due yet, which is at most
+//!   [`MAX_REFRESH_SECONDS`] later.
+//!
+//! Changes of other owned objects are not recorded, if one coincides with a change of the status
+//! of a pod it is only reverted by the next full reconciliation.
+use crate::is_pod_condition_true;
+use crate::object_ref::ObjectRef;
+
+use k8s_openapi::api::core::v1::Pod;
+use kube::ResourceExt;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use stackable_zookeeper_crd::ZookeeperCluster;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A full reconciliation runs at least this often, even if the last one did not schedule one.
+pub const MAX_REFRESH_SECONDS: u64 = 600;
+
+/// The pod changes and the outcome of the last full reconciliation of every cluster.
+#[derive(Clone, Debug, Default)]
+pub struct StatusRefresh {
+    clusters: Arc<Mutex<BTreeMap<ObjectRef, ClusterState>>>,
+}
+
+#[derive(Debug, Default)]
+struct ClusterState {
+    /// The fingerprints of the pods of the cluster by name.
+    pods: BTreeMap<String, String>,
+    /// Whether the status of a pod changed since the last reconciliation.
+    pod_status_changed: bool,
+    /// Whether anything else which was recorded changed since the last reconciliation.
+    changed: bool,
+    /// The fingerprint of the cluster after the last full reconciliation which ran all steps and
+    /// when the next one is due.
+    settled: Option<(String, Instant)>,
+}
+
+impl StatusRefresh {
+    /// Records a change of `pod` and returns the cluster owning it, which is reconciled.
+    pub fn pod_changed(&self, pod: &Pod) -> Option<ObjectRef> {
+        let owner = pod
+            .owner_references()
+            .iter()
+            .find(|owner| owner.kind == "ZookeeperCluster")?;
+        let key = ObjectRef::new(&pod.namespace().unwrap_or_default(), &owner.name);
+
+        let fingerprint = pod_fingerprint(pod);
+        let mut clusters = self.clusters.lock().unwrap();
+        let cluster = clusters.entry(key.clone()).or_default();
+        if cluster.pods.insert(pod.name(), fingerprint.clone()) == Some(fingerprint) {
+            cluster.pod_status_changed = true;
+        } else {
+            cluster.changed = true;
+        }
+        Some(key)
+    }
+
+    /// Records a change which requires a full reconciliation of the cluster `key`, e.g. of a
+    /// referenced Secret.
+    pub fn changed(&self, key: &ObjectRef) {
+        let mut clusters = self.clusters.lock().unwrap();
+        clusters.entry(key.clone()).or_default().changed = true;
+    }
+
+    /// Returns the time until the next full reconciliation of `cluster` with `pods` is due if the
+    /// reconciliation at `now` only has to refresh the status, otherwise `None`.
+    ///
+    /// The changes recorded so far count as handled either way.
+    pub fn status_only(
+        &self,
+        cluster: &ZookeeperCluster,
+        pods: &[Pod],
+        now: Instant,
+    ) -> Option<Duration> {
+        let mut clusters = self.clusters.lock().unwrap();
+        let state = clusters.entry(ObjectRef::from_obj(cluster)).or_default();
+        let pod_status_changed = std::mem::take(&mut state.pod_status_changed);
+        let changed = std::mem::take(&mut state.changed);
+        let (fingerprint, due) = state.settled.as_ref()?;
+        if !pod_status_changed
+            || changed
+            || cluster.metadata.deletion_timestamp.is_some()
+            || cluster.spec.member_replacement.is_some()
+            || !pods.iter().all(|pod| is_pod_condition_true(pod, "Ready"))
+            || *fingerprint != cluster_fingerprint(cluster, pods)
+            || now >= *due
+        {
+            return None;
+        }
+        Some(*due - now)
+    }
+
+    /// Records a full reconciliation of `cluster` at `now`, which started with `pods`.
+    ///
+    /// `requeue_after` is the interval after which it is due again if it ran all steps without
+    /// waiting, otherwise `None`, so the next reconciliation runs all steps as well.
+    pub fn reconciled(
+        &self,
+        cluster: &ZookeeperCluster,
+        pods: &[Pod],
+        requeue_after: Option<Duration>,
+        now: Instant,
+    ) {
+        let mut clusters = self.clusters.lock().unwrap();
+        let key = ObjectRef::from_obj(cluster);
+        if cluster.metadata.deletion_timestamp.is_some() {
+            clusters.remove(&key);
+            return;
+        }
+        let state = clusters.entry(key).or_default();
+        // forget deleted pods, a pod created meanwhile is merely seen as changed once more
+        state
+            .pods
+            .retain(|name, _| pods.iter().any(|pod| pod.name() == *name));
+        state.settled = requeue_after.map(|requeue_after| {
+            let due = now + requeue_after.min(Duration::from_secs(MAX_REFRESH_SECONDS));
+            (cluster_fingerprint(cluster, pods), due)
+        });
+    }
+}
+
+/// Hashes everything of `pod` the reconciliation depends on besides its status.
+fn pod_fingerprint(pod: &Pod) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(
+        json!({
+            "uid": pod.metadata.uid,
+            "labels": pod.metadata.labels,
+            "annotations": pod.metadata.annotations,
+            "deletionTimestamp": pod.metadata.deletion_timestamp,
+            "spec": pod.spec,
+        })
+        .to_string(),
+    );
+    format!("{:x}", hasher.finalize())
+}
+
+/// Hashes the generation, the labels and the annotations of `cluster` and the fingerprints of
+/// its `pods`, the order of them does not matter.
+fn cluster_fingerprint(cluster: &ZookeeperCluster, pods: &[Pod]) -> String {
+    let mut pods = pods
+        .iter()
+        .map(|pod| format!("{}={}", pod.name(), pod_fingerprint(pod)))
+        .collect::<Vec<_>>();
+    pods.sort();
+
+    let mut hasher = Sha256::new();
+    hasher.update(
+        json!({
+            "generation": cluster.metadata.generation,
+            "labels": cluster.metadata.labels,
+            "annotations": cluster.metadata.annotations,
+            "pods": pods,
+        })
+        .to_string(),
+    );
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use indoc::formatdoc;
+    use rstest::rstest;
+    use stackable_zookeeper_crd::member_replacement::ZookeeperMemberReplacement;
+
+    fn cluster() -> ZookeeperCluster {
+        test_support::cluster("simple", json!({}))
+    }
+
+    fn pod(name: &str, image: &str, ready: &str) -> Pod {
+        serde_yaml::from_str(&formatdoc! {"
+            metadata:
+              name: {}
+              namespace: default
+              ownerReferences:
+                - apiVersion: zookeeper.stackable.tech/v1alpha1
+                  kind: ZookeeperCluster
+                  name: simple
+                  uid: 0c4b8e7c-2a1e-4d8f-9a1e-5f0a2b3c4d5e
+            spec:
+              containers:
+                - name: zookeeper
+                  image: {}
+            status:
+              conditions:
+                - type: Ready
+                  status: \"{}\"
+        ", name, image, ready})
+        .unwrap()
+    }
+
+    /// Returns a `StatusRefresh` which saw `pods` and settled the cluster with them at `now`,
+    /// due again after `requeue_seconds`.
+    fn settled(pods: &[Pod], requeue_seconds: u64, now: Instant) -> StatusRefresh {
+        let status_refresh = StatusRefresh::default();
+        for pod in pods {
+            status_refresh.pod_changed(pod);
+        }
+        status_refresh.status_only(&cluster(), pods, now);
+        status_refresh.reconciled(
+            &cluster(),
+            pods,
+            Some(Duration::from_secs(requeue_seconds)),
+            now,
+        );
+        status_refresh
+    }
+
+    #[test]
+    fn test_pod_changed_returns_owner() {
+        let status_refresh = StatusRefresh::default();
+
+        assert_eq!(
+            status_refresh.pod_changed(&pod("simple-1", "zookeeper:3.5.8", "True")),
+            Some(ObjectRef::new("default", "simple"))
+        );
+        assert_eq!(status_refresh.pod_changed(&Pod::default()), None);
+    }
+
+    #[test]
+    fn test_status_change_only_refreshes_status() {
+        let now = Instant::now();
+        let status_refresh = settled(&[pod("simple-1", "zookeeper:3.5.8", "False")], 60, now);
+        let recovered = vec![pod("simple-1", "zookeeper:3.5.8", "True")];
+
+        status_refresh.pod_changed(&recovered[0]);
+
+        assert_eq!(
+            status_refresh.status_only(&cluster(), &recovered, now + Duration::from_secs(10)),
+            Some(Duration::from_secs(50))
+        );
+        // nothing changed since this reconciliation, e.g. it was requeued
+        assert_eq!(
+            status_refresh.status_only(&cluster(), &recovered, now),
+            None
+        );
+    }
+
+    #[test]
+    fn test_member_replacement_needs_full_reconciliation() {
+        let now = Instant::now();
+        let pods = vec![pod("simple-1", "zookeeper:3.5.8", "True")];
+        let status_refresh = settled(&pods, 60, now);
+        let mut cluster = cluster();
+        cluster.spec.member_replacement = Some(ZookeeperMemberReplacement::default());
+
+        status_refresh.pod_changed(&pods[0]);
+
+        assert_eq!(status_refresh.status_only(&cluster, &pods, now), None);
+    }
+
+    #[rstest]
+    #[case::spec_changed(pod("simple-1", "zookeeper:3.6.3", "True"), 10)]
+    #[case::other_pod(pod("simple-2", "zookeeper:3.5.8", "True"), 10)]
+    #[case::not_ready(pod("simple-1", "zookeeper:3.5.8", "False"), 10)]
+    #[case::full_reconciliation_due(pod("simple-1", "zookeeper:3.5.8", "True"), 60)]
+    fn test_other_changes_need_full_reconciliation(
+        #[case] changed_pod: Pod,
+        #[case] elapsed_seconds: u64,
+    ) {
+        let now = Instant::now();
+        let status_refresh = settled(&[pod("simple-1", "zookeeper:3.5.8", "True")], 60, now);
+
+        status_refresh.pod_changed(&changed_pod);
+
+        assert_eq!(
+            status_refresh.status_only(
+                &cluster(),
+                &[changed_pod],
+                now + Duration::from_secs(elapsed_seconds)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_changed_needs_full_reconciliation() {
+        let now = Instant::now();
+        let pods = vec![pod("simple-1", "zookeeper:3.5.8", "True")];
+        let status_refresh = settled(&pods, 60, now);
+
+        status_refresh.pod_changed(&pods[0]);
+        status_refresh.changed(&ObjectRef::new("default", "simple"));
+
+        assert_eq!(status_refresh.status_only(&cluster(), &pods, now), None);
+    }
+
+    #[test]
+    fn test_cluster_changed_needs_full_reconciliation() {
+        let now = Instant::now();
+        let pods = vec![pod("simple-1", "zookeeper:3.5.8", "True")];
+        let status_refresh = settled(&pods, 60, now);
+        let mut cluster = cluster();
+        cluster.metadata.generation = Some(2);
+
+        status_refresh.pod_changed(&pods[0]);
+
+        assert_eq!(status_refresh.status_only(&cluster, &pods, now), None);
+    }
+
+    #[test]
+    fn test_unsettled_reconciliation_needs_full_reconciliation() {
+        let now = Instant::now();
+        let pods = vec![pod("simple-1", "zookeeper:3.5.8", "True")];
+        let status_refresh = settled(&pods, 60, now);
+
+        status_refresh.reconciled(&cluster(), &pods, None, now);
+        status_refresh.pod_changed(&pods[0]);
+
+        assert_eq!(status_refresh.status_only(&cluster(), &pods, now), None);
+    }
+
+    #[test]
+    fn test_full_reconciliation_due_after_max_refresh_interval() {
+        let now = Instant::now();
+        let pods = vec![pod("simple-1", "zookeeper:3.5.8", "True")];
+        let status_refresh = settled(&pods, 3600, now);
+
+        status_refresh.pod_changed(&pods[0]);
+
+        assert_eq!(
+            status_refresh.status_only(&cluster(), &pods, now),
+            Some(Duration::from_secs(MAX_REFRESH_SECONDS))
+        );
+    }
+}
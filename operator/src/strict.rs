@@ -0,0 +1,170 @@
+//! Detection of unknown fields in the spec of a ZookeeperCluster.
+//!
+//! The Kubernetes API server silently prunes fields which are not part of the CRD schema, so a
+//! typo like `replcias` is simply ignored. The spec a user applied with `kubectl apply` is still
+//! available in the `kubectl.kubernetes.io/last-applied-configuration` annotation though, which
+//! allows us to compare it with what we actually understood.
+use crate::error::Error;
+
+use serde_json::Value;
+use stackable_zookeeper_crd::ZookeeperClusterSpec;
+
+/// The annotation in which `kubectl apply` stores the applied object.
+pub const LAST_APPLIED_CONFIGURATION_ANNOTATION: &str =
+    "kubectl.kubernetes.io/last-applied-configuration";
+
+/// Returns the paths (e.g. `servers.roleGroups.default.replcias`) of all fields in the `spec` of
+/// the `applied` object which are not part of [`ZookeeperClusterSpec`].
+pub fn find_unknown_fields(applied: &str) -> Result<Vec<String>, Error> {
+    let applied: Value = serde_json::from_str(applied)?;
+    let applied_spec = match applied.get("spec") {
+        Some(spec) => spec,
+        None => return Ok(vec![]),
+    };
+
+    let spec: ZookeeperClusterSpec = serde_json::from_value(applied_spec.clone())?;
+    let known_spec = serde_json::to_value(&spec)?;
+
+    let mut unknown_fields = vec![];
+    collect_unknown_fields("", applied_spec, &known_spec, &mut unknown_fields);
+    unknown_fields.sort();
+    Ok(unknown_fields)
+}
+
+fn collect_unknown_fields(path: &str, applied: &Value, known: &Value, unknown: &mut Vec<String>) {
+    match (applied, known) {
+        (Value::Object(applied), Value::Object(known)) => {
+            for (key, applied_value) in applied {
+                let field_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                match known.get(key) {
+                    Some(known_value) => {
+                        collect_unknown_fields(&field_path, applied_value, known_value, unknown)
+                    }
+                    // Unset optional fields and empty lists and maps are not serialized, so they
+                    // can not be told apart from unknown fields. They do not matter either way.
+                    None if is_empty(applied_value) => {}
+                    None => unknown.push(field_path),
+                }
+            }
+        }
+        (Value::Array(applied), Value::Array(known)) => {
+            for (index, (applied_item, known_item)) in applied.iter().zip(known).enumerate() {
+                let item_path = format!("{}[{}]", path, index);
+                collect_unknown_fields(&item_path, applied_item, known_item, unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Array(items) => items.is_empty(),
+        Value::Object(fields) => fields.is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_find_unknown_fields() {
+        let applied = r#"{
+            "apiVersion": "zookeeper.stackable.tech/v1alpha1",
+            "kind": "ZookeeperCluster",
+            "metadata": {"name": "simple"},
+            "spec": {
+                "version": "3.5.8",
+                "verison": "3.5.8",
+                "servers": {
+                    "roleGroups": {
+                        "default": {
+                            "selector": {"matchLabels": {"kubernetes.io/arch": "stackable-linux"}},
+                            "replcias": 3,
+                            "config": {"tickTime": 2000, "tikcTime": 2000}
+                        }
+                    }
+                }
+            }
+        }"#;
+
+        assert_eq!(
+            find_unknown_fields(applied).unwrap(),
+            vec![
+                "servers.roleGroups.default.config.tikcTime",
+                "servers.roleGroups.default.replcias",
+                "verison",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_no_unknown_fields() {
+        let applied = r#"{
+            "spec": {
+                "version": "3.5.8",
+                "servers": {
+                    "roleGroups": {
+                        "default": {
+                            "selector": {"matchLabels": {"kubernetes.io/arch": "stackable-linux"}},
+                            "replicas": null,
+                            "config": {"metricsPort": 9505}
+                        }
+                    }
+                }
+            }
+        }"#;
+
+        assert!(find_unknown_fields(applied).unwrap().is_empty());
+    }
+
+    #[rstest]
+    #[case::empty_list(r#"{"spec": {"version": "3.5.8", "command": []}}"#, &[])]
+    #[case::empty_map(r#"{"spec": {"version": "3.5.8", "config": {}}}"#, &[])]
+    #[case::empty_nested_list(
+        r#"{"spec": {"version": "3.5.8", "federation": {"members": []}}}"#,
+        &[]
+    )]
+    #[case::list_items(
+        r#"{"spec": {
+            "version": "3.5.8",
+            "federation": {
+                "members": [
+                    {"id": 4, "address": "zk-4.example.com"},
+                    {"id": 5, "adress": "zk-5.example.com"}
+                ]
+            }
+        }}"#,
+        &["federation.members[1].adress"]
+    )]
+    #[case::nested_lists(
+        r#"{"spec": {
+            "version": "3.5.8",
+            "extraVolumes": {
+                "volumes": [{
+                    "name": "scripts",
+                    "configMap": {
+                        "name": "scripts",
+                        "items": [{"key": "a.sh", "path": "a.sh", "mdoe": 493}]
+                    }
+                }],
+                "mounts": []
+            }
+        }}"#,
+        &["extraVolumes.volumes[0].configMap.items[0].mdoe"]
+    )]
+    fn test_find_unknown_fields_in_lists_and_maps(
+        #[case] applied: &str,
+        #[case] expected: &[&str],
+    ) {
+        assert_eq!(find_unknown_fields(applied).unwrap(), expected);
+    }
+}
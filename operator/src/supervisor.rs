@@ -0,0 +1,47 @@
+//! Keeping the controllers running.
+//!
+//! A controller returns when its watch streams end, e.g. after the connection to the API server
+//! was lost for too long. Without supervision the operator would then silently stop reconciling
+//! while the process keeps running. [`supervise_controller`] restarts the controller instead and
+//! counts the restarts in the [`Metrics`], so alerts can be set up on degraded watches.
+use crate::metrics::Metrics;
+
+use stackable_operator::error::OperatorResult;
+use std::future::Future;
+use std::time::Duration;
+use tracing::{error, warn};
+
+/// The delay before a stopped controller is started again.
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+
+/// Runs the controller created by `start` and starts it again whenever it stops. This function
+/// never returns.
+///
+/// # Arguments
+///
+/// - `controller` - The name of the controller used in logs and metrics, e.g. `cluster`.
+/// - `metrics` - The metrics the restarts are counted in.
+/// - `start` - Creates and runs the controller.
+///
+pub async fn supervise_controller<F, Fut>(controller: &str, metrics: Metrics, mut start: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = OperatorResult<()>>,
+{
+    loop {
+        let result = start().await;
+
+        match result {
+            Ok(()) => warn!(
+                "The {} controller stopped, restarting it in {:?}",
+                controller, RESTART_DELAY
+            ),
+            Err(err) => error!(
+                "The {} controller failed, restarting it in {:?}: {}",
+                controller, RESTART_DELAY, err
+            ),
+        }
+        metrics.inc_controller_restarts(controller);
+        tokio::time::sleep(RESTART_DELAY).await;
+    }
+}
@@ -0,0 +1,104 @@
+//! Detection of modifications of the objects owned by a cluster (pods and ConfigMaps) by field
+//! managers other than the operator and the Kubernetes components.
+//!
+//! Every write to an object is recorded in its `managedFields` with the name of the field manager
+//! (e.g. `kubectl-edit` or `kubectl-client-side-apply`) and the time of the last write. If
+//! `spec.tamperDetection` is set, writes of unknown managers are reported until an administrator
+//! acknowledges them by setting [`TAMPER_ACKNOWLEDGED_AT_ANNOTATION`] to a later time.
+use crate::apply::FIELD_MANAGER;
+
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use k8s_openapi::chrono::{DateTime, Utc};
+use stackable_zookeeper_crd::tamper_detection::ZookeeperTamperDetection;
+use std::collections::BTreeSet;
+
+/// Annotation on a ZookeeperCluster acknowledging all modifications by unknown field managers up
+/// to the given RFC 3339 timestamp.
+pub const TAMPER_ACKNOWLEDGED_AT_ANNOTATION: &str =
+    "zookeeper.stackable.tech/tamper-acknowledged-at";
+
+/// The field managers of the operator and the Kubernetes components which update the owned
+/// objects. Earlier versions of the operator applied objects as `zookeeper-operator`.
+const KNOWN_MANAGERS: [&str; 5] = [
+    FIELD_MANAGER,
+    stackable_zookeeper_crd::MANAGED_BY,
+    "kubelet",
+    "kube-controller-manager",
+    "kube-scheduler",
+];
+
+/// Returns the names of the unknown field managers which modified one of the given objects after
+/// `acknowledged_at`.
+pub fn find_unknown_managers<'a>(
+    objects: impl IntoIterator<Item = &'a ObjectMeta>,
+    tamper_detection: &ZookeeperTamperDetection,
+    acknowledged_at: Option<&DateTime<Utc>>,
+) -> BTreeSet<String> {
+    objects
+        .into_iter()
+        .flat_map(|metadata| metadata.managed_fields.iter())
+        .filter(|entry| match (acknowledged_at, &entry.time) {
+            (Some(acknowledged_at), Some(time)) => time.0 > *acknowledged_at,
+            (Some(_), None) => false,
+            (None, _) => true,
+        })
+        .filter_map(|entry| entry.manager.clone())
+        .filter(|manager| {
+            !KNOWN_MANAGERS.contains(&manager.as_str())
+                && !tamper_detection.allowed_managers.contains(manager)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_find_unknown_managers() {
+        let metadata: Vec<ObjectMeta> = serde_yaml::from_str(indoc! {"
+            - name: simple-server-default-node-1
+              managedFields:
+                - manager: zookeeper.stackable.tech
+                  operation: Update
+                  time: 2021-09-01T12:00:00Z
+                - manager: kubelet
+                  operation: Update
+                  time: 2021-09-01T12:01:00Z
+                - manager: kubectl-edit
+                  operation: Update
+                  time: 2021-09-01T13:00:00Z
+            - name: simple-server-default-data
+              managedFields:
+                - manager: zookeeper-operator
+                  operation: Apply
+                  time: 2021-09-01T12:00:00Z
+                - manager: argocd-controller
+                  operation: Update
+                  time: 2021-09-01T14:00:00Z
+        "})
+        .unwrap();
+        let mut tamper_detection = ZookeeperTamperDetection::default();
+
+        assert_eq!(
+            find_unknown_managers(&metadata, &tamper_detection, None),
+            vec!["argocd-controller".to_string(), "kubectl-edit".to_string()]
+                .into_iter()
+                .collect()
+        );
+
+        let acknowledged_at = DateTime::parse_from_rfc3339("2021-09-01T13:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            find_unknown_managers(&metadata, &tamper_detection, Some(&acknowledged_at)),
+            vec!["argocd-controller".to_string()].into_iter().collect()
+        );
+
+        tamper_detection.allowed_managers = vec!["argocd-controller".to_string()];
+        assert!(
+            find_unknown_managers(&metadata, &tamper_detection, Some(&acknowledged_at)).is_empty()
+        );
+    }
+}
@@ -0,0 +1,1209 @@
+//! Test doubles and fixtures for covering the controller logic without a Kubernetes cluster or
+//! ZooKeeper servers, enabled with the `test-support` feature (and always in the tests of this
+//! crate).
+//!
+//! - [`FakeApiServer`] keeps Kubernetes objects in memory and answers the requests of a
+//!   `kube::Client` ([`FakeApiServer::client`]) like the API server would: get, list (with label
+//!   selectors), create (also with `generateName`), replace, merge, strategic merge and
+//!   server-side apply patches (see [`merge_keyed`]), the status subresource and delete. Watches
+//!   end right away, JSON patches are rejected. [`FakeApiServer::set_pods_ready`] plays the
+//!   kubelet.
+//! - [`FakeZookeeper`] is an in-memory ensemble behind the [`ZookeeperConnector`] used for
+//!   znodes, smoke tests and backups.
+//! - [`FakePodExecutor`] records the commands run in containers, e.g. the integrity check.
+//! - [`cluster`], [`server_pod`] and [`node`] build the objects a reconciliation works on.
+use crate::error::Error;
+use crate::pod_exec::{ExecOutput, PodExecutor};
+use crate::zk_client::{EnsembleConfig, ZookeeperClient, ZookeeperConnector};
+use crate::{ZookeeperRole, ID_LABEL};
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use hyper::service::Service;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use k8s_openapi::api::core::v1::{Node, Pod};
+use kube::{Resource, ResourceExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use stackable_operator::labels::{self, build_common_labels_for_all_managed_resources};
+use stackable_zookeeper_crd::{ZookeeperCluster, APP_NAME};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use zookeeper_async::{Acl, ZkError};
+
+/// The namespace of the fixtures and the default namespace of [`FakeApiServer::client`].
+pub const NAMESPACE: &str = "default";
+/// The version of the fixture clusters.
+pub const VERSION: &str = "3.5.8";
+
+/// The identity of an object in the [`FakeApiServer`].
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct ObjectKey {
+    /// `/api/v1` or `/apis/<group>/<version>`.
+    api: String,
+    plural: String,
+    namespace: Option<String>,
+    name: String,
+}
+
+/// A request path split into its parts, e.g.
+/// `/apis/zookeeper.stackable.tech/v1alpha1/namespaces/default/zookeeperclusters/simple/status`.
+#[derive(Debug, Eq, PartialEq)]
+struct RequestPath {
+    api: String,
+    plural: String,
+    namespace: Option<String>,
+    name: Option<String>,
+    subresource: Option<String>,
+}
+
+impl RequestPath {
+    fn parse(path: &str) -> Option<RequestPath> {
+        let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+        let (api, rest) = match segments.as_slice() {
+            ["api", version, rest @ ..] => (format!("/api/{}", version), rest),
+            ["apis", group, version, rest @ ..] => (format!("/apis/{}/{}", group, version), rest),
+            _ => return None,
+        };
+        let (namespace, rest) = match rest {
+            ["namespaces", namespace, rest @ ..] if !rest.is_empty() => {
+                (Some(namespace.to_string()), rest)
+            }
+            _ => (None, rest),
+        };
+        let (plural, name, subresource) = match rest {
+            [plural] => (plural, None, None),
+            [plural, name] => (plural, Some(name.to_string()), None),
+            [plural, name, subresource] => (
+                plural,
+                Some(name.to_string()),
+                Some(subresource.to_string()),
+            ),
+            _ => return None,
+        };
+        Some(RequestPath {
+            api,
+            plural: plural.to_string(),
+            namespace,
+            name,
+            subresource,
+        })
+    }
+
+    fn key(&self, name: &str) -> ObjectKey {
+        ObjectKey {
+            api: self.api.clone(),
+            plural: self.plural.clone(),
+            namespace: self.namespace.clone(),
+            name: name.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ApiState {
+    objects: BTreeMap<ObjectKey, Value>,
+    resource_version: u64,
+    requests: Vec<String>,
+}
+
+/// An in-memory Kubernetes API server, clones share the objects.
+#[derive(Clone, Debug, Default)]
+pub struct FakeApiServer {
+    state: Arc<Mutex<ApiState>>,
+}
+
+impl FakeApiServer {
+    pub fn new() -> FakeApiServer {
+        FakeApiServer::default()
+    }
+
+    /// Returns a client sending its requests to this server.
+    pub fn client(&self) -> kube::Client {
+        kube::Client::new(self.clone(), NAMESPACE)
+    }
+
+    /// Returns a client of the operator framework sending its requests to this server.
+    pub fn operator_client(&self) -> stackable_operator::client::Client {
+        stackable_operator::client::Client::new(
+            self.client(),
+            Some(crate::FIELD_MANAGER.to_string()),
+            NAMESPACE.to_string(),
+        )
+    }
+
+    /// Stores `object` as if it was created, e.g. the objects a test starts with.
+    pub fn insert<K>(&self, object: &K)
+    where
+        K: Resource + Serialize,
+        K::DynamicType: Default,
+    {
+        let path = K::url_path(&K::DynamicType::default(), object.namespace().as_deref());
+        let path = RequestPath::parse(&path).expect("the path of a resource is valid");
+        let mut state = self.state.lock().unwrap();
+        let value = serde_json::to_value(object).expect("resources can be serialized");
+        let created = state.create(path.key(&object.name()), value);
+        debug_assert!(created.is_some(), "[{}] exists already", object.name());
+    }
+
+    /// Returns the object `name` in `namespace` (`None` for cluster scoped objects).
+    pub fn get<K>(&self, namespace: Option<&str>, name: &str) -> Option<K>
+    where
+        K: Resource + DeserializeOwned,
+        K::DynamicType: Default,
+    {
+        let path = RequestPath::parse(&K::url_path(&K::DynamicType::default(), namespace))?;
+        let state = self.state.lock().unwrap();
+        let object = state.objects.get(&path.key(name))?;
+        Some(serde_json::from_value(object.clone()).expect("stored objects are valid"))
+    }
+
+    /// Returns all objects of the given kind in all namespaces.
+    pub fn list<K>(&self) -> Vec<K>
+    where
+        K: Resource + DeserializeOwned,
+        K::DynamicType: Default,
+    {
+        let path = match RequestPath::parse(&K::url_path(&K::DynamicType::default(), None)) {
+            Some(path) => path,
+            None => return vec![],
+        };
+        let state = self.state.lock().unwrap();
+        state
+            .objects
+            .iter()
+            .filter(|(key, _)| key.api == path.api && key.plural == path.plural)
+            .map(|(_, object)| {
+                serde_json::from_value(object.clone()).expect("stored objects are valid")
+            })
+            .collect()
+    }
+
+    /// Sets all pods running and ready, including the conditions of their readiness gates, like
+    /// the kubelet once their containers started.
+    pub fn set_pods_ready(&self) {
+        let mut state = self.state.lock().unwrap();
+        let pods = state
+            .objects
+            .keys()
+            .filter(|key| key.api == "/api/v1" && key.plural == "pods")
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in pods {
+            state.update(key, Some("status"), |mut pod| {
+                let gates = pod["spec"]["readinessGates"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+                let conditions = std::iter::once(json!("Ready"))
+                    .chain(gates.iter().map(|gate| gate["conditionType"].clone()))
+                    .map(|condition_type| json!({ "type": condition_type, "status": "True" }))
+                    .collect::<Vec<_>>();
+                pod["status"] = json!({ "phase": "Running", "conditions": conditions });
+                pod
+            });
+        }
+    }
+
+    /// Returns the requests received so far, e.g. `PATCH /api/v1/namespaces/default/pods/a`.
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    fn handle(&self, method: &Method, path: &str, query: &str, body: &[u8]) -> Response<Body> {
+        let mut state = self.state.lock().unwrap();
+        state.requests.push(format!("{} {}", method, path));
+
+        let path = match RequestPath::parse(path) {
+            Some(path) => path,
+            None => return status_response(StatusCode::NOT_FOUND, "NotFound", path),
+        };
+        let query = parse_query(query);
+        let body = if body.is_empty() {
+            Value::Null
+        } else {
+            match serde_json::from_slice::<Value>(body) {
+                Ok(body) => body,
+                Err(err) => {
+                    return status_response(StatusCode::BAD_REQUEST, "BadRequest", &err.to_string())
+                }
+            }
+        };
+
+        match (method, &path.name) {
+            (&Method::GET, None) if is_watch(&query) => Response::new(Body::empty()),
+            (&Method::GET, None) => {
+                let selector = query.get("labelSelector").map(String::as_str);
+                let items = state
+                    .objects
+                    .iter()
+                    .filter(|(key, _)| {
+                        key.api == path.api
+                            && key.plural == path.plural
+                            && (path.namespace.is_none() || key.namespace == path.namespace)
+                    })
+                    .map(|(_, object)| object)
+                    .filter(|object| {
+                        selector.map_or(true, |selector| matches_selector(object, selector))
+                    })
+                    .cloned()
+                    .collect::<Vec<_>>();
+                json_response(
+                    StatusCode::OK,
+                    &json!({
+                        "apiVersion": "v1",
+                        "kind": "List",
+                        "metadata": { "resourceVersion": state.resource_version.to_string() },
+                        "items": items,
+                    }),
+                )
+            }
+            (&Method::GET, Some(name)) => match state.objects.get(&path.key(name)) {
+                Some(object) => json_response(StatusCode::OK, object),
+                None => not_found(name),
+            },
+            (&Method::POST, None) => {
+                let name = match body["metadata"]["name"].as_str() {
+                    Some(name) => name.to_string(),
+                    None => format!(
+                        "{}{}",
+                        body["metadata"]["generateName"]
+                            .as_str()
+                            .unwrap_or_default(),
+                        state.resource_version + 1
+                    ),
+                };
+                match state.create(path.key(&name), body) {
+                    Some(object) => json_response(StatusCode::CREATED, &object),
+                    None => status_response(
+                        StatusCode::CONFLICT,
+                        "AlreadyExists",
+                        &format!("[{}] already exists", name),
+                    ),
+                }
+            }
+            (&Method::PUT, Some(name)) => {
+                match state.update(path.key(name), path.subresource.as_deref(), |_| body) {
+                    Some(object) => json_response(StatusCode::OK, &object),
+                    None => not_found(name),
+                }
+            }
+            (&Method::PATCH, Some(_)) if is_json_patch(&query) => status_response(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "UnsupportedMediaType",
+                "JSON patches are not supported by the fake API server",
+            ),
+            (&Method::PATCH, Some(name)) => {
+                let key = path.key(name);
+                let object = if state.objects.contains_key(&key) {
+                    let keyed = is_keyed_patch(&query);
+                    state.update(key, path.subresource.as_deref(), |mut object| {
+                        if keyed {
+                            merge_keyed(&mut object, &body);
+                        } else {
+                            merge(&mut object, &body);
+                        }
+                        object
+                    })
+                } else if is_apply_patch(&query) && path.subresource.is_none() {
+                    // server-side apply creates missing objects
+                    state.create(key, body)
+                } else {
+                    None
+                };
+                match object {
+                    Some(object) => json_response(StatusCode::OK, &object),
+                    None => not_found(name),
+                }
+            }
+            (&Method::DELETE, Some(name)) => match state.objects.remove(&path.key(name)) {
+                Some(object) => json_response(StatusCode::OK, &object),
+                None => not_found(name),
+            },
+            _ => status_response(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "MethodNotAllowed",
+                &format!("{} is not supported", method),
+            ),
+        }
+    }
+}
+
+impl ApiState {
+    fn next_resource_version(&mut self) -> String {
+        self.resource_version += 1;
+        self.resource_version.to_string()
+    }
+
+    /// Stores a new object, `None` if it exists already.
+    fn create(&mut self, key: ObjectKey, mut object: Value) -> Option<Value> {
+        if self.objects.contains_key(&key) {
+            return None;
+        }
+        let resource_version = self.next_resource_version();
+        let metadata = &mut object["metadata"];
+        metadata["name"] = json!(key.name);
+        if let Some(namespace) = &key.namespace {
+            metadata["namespace"] = json!(namespace);
+        }
+        metadata["uid"] = json!(format!(
+            "00000000-0000-0000-0000-{:012}",
+            self.resource_version
+        ));
+        metadata["resourceVersion"] = json!(resource_version);
+        metadata["generation"] = json!(1);
+        if metadata["creationTimestamp"].is_null() {
+            metadata["creationTimestamp"] = json!("2021-06-01T12:00:00Z");
+        }
+        self.objects.insert(key, object.clone());
+        Some(object)
+    }
+
+    /// Replaces the existing object with the result of `update`. Only the `status` is taken from
+    /// it for the status subresource and the `status` is kept otherwise, like for resources with
+    /// a status subresource. The generation increases if the spec changed.
+    fn update(
+        &mut self,
+        key: ObjectKey,
+        subresource: Option<&str>,
+        update: impl FnOnce(Value) -> Value,
+    ) -> Option<Value> {
+        let existing = self.objects.get(&key)?.clone();
+        let updated = update(existing.clone());
+        let mut object = existing.clone();
+        if subresource == Some("status") {
+            object["status"] = updated["status"].clone();
+        } else {
+            object = updated;
+            object["status"] = existing["status"].clone();
+            object["metadata"]["uid"] = existing["metadata"]["uid"].clone();
+            object["metadata"]["creationTimestamp"] =
+                existing["metadata"]["creationTimestamp"].clone();
+            let generation = existing["metadata"]["generation"].as_i64().unwrap_or(1);
+            object["metadata"]["generation"] = json!(if object["spec"] != existing["spec"] {
+                generation + 1
+            } else {
+                generation
+            });
+        }
+        if object["status"].is_null() {
+            if let Some(object) = object.as_object_mut() {
+                object.remove("status");
+            }
+        }
+        object["metadata"]["resourceVersion"] = json!(self.next_resource_version());
+        self.objects.insert(key, object.clone());
+        Some(object)
+    }
+}
+
+impl Service<Request<Body>> for FakeApiServer {
+    type Response = Response<Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Response<Body>, Infallible>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let server = self.clone();
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+            // the patch type is only known from the content type
+            let mut query = parts.uri.query().unwrap_or_default().to_string();
+            match parts
+                .headers
+                .get("content-type")
+                .map(|content_type| content_type.as_bytes())
+            {
+                Some(b"application/json-patch+json") => query.push_str("&patchType=json"),
+                Some(b"application/strategic-merge-patch+json") => {
+                    query.push_str("&patchType=strategic")
+                }
+                Some(b"application/apply-patch+yaml") => query.push_str("&patchType=apply"),
+                _ => {}
+            }
+            Ok(server.handle(&parts.method, parts.uri.path(), &query, &body))
+        })
+    }
+}
+
+fn is_watch(query: &BTreeMap<String, String>) -> bool {
+    matches!(
+        query.get("watch").map(String::as_str),
+        Some("true") | Some("1")
+    )
+}
+
+fn is_json_patch(query: &BTreeMap<String, String>) -> bool {
+    query.get("patchType").map(String::as_str) == Some("json")
+}
+
+fn is_apply_patch(query: &BTreeMap<String, String>) -> bool {
+    query.get("patchType").map(String::as_str) == Some("apply")
+}
+
+/// Checks whether the patch merges lists by their keys, see [`merge_keyed`].
+fn is_keyed_patch(query: &BTreeMap<String, String>) -> bool {
+    matches!(
+        query.get("patchType").map(String::as_str),
+        Some("strategic") | Some("apply")
+    )
+}
+
+/// Applies the JSON merge patch (RFC 7386) `patch` to `target`.
+fn merge(target: &mut Value, patch: &Value) {
+    match patch {
+        Value::Object(patch) => {
+            if !target.is_object() {
+                *target = json!({});
+            }
+            let target = target.as_object_mut().expect("target is an object");
+            for (key, value) in patch {
+                if value.is_null() {
+                    target.remove(key);
+                } else {
+                    merge(target.entry(key.clone()).or_insert(Value::Null), value);
+                }
+            }
+        }
+        patch => *target = patch.clone(),
+    }
+}
+
+/// The keys lists of objects are merged by in strategic merge and server-side apply patches, in
+/// the order they are tried, e.g. `name` for containers and `type` for conditions.
+const MERGE_KEYS: &[&str] = &["name", "type", "uid", "containerPort", "mountPath"];
+
+/// Applies the strategic merge patch (or the configuration applied with server-side apply)
+/// `patch` to `target`: like [`merge`], but lists of objects which all have one of the
+/// [`MERGE_KEYS`] are merged item by item, other lists are replaced. Patch directives like
+/// `$patch` and the field ownership of server-side apply are not supported, so fields are never
+/// removed because a manager stopped applying them.
+fn merge_keyed(target: &mut Value, patch: &Value) {
+    match patch {
+        Value::Object(patch) => {
+            if !target.is_object() {
+                *target = json!({});
+            }
+            let target = target.as_object_mut().expect("target is an object");
+            for (key, value) in patch {
+                if value.is_null() {
+                    target.remove(key);
+                } else {
+                    merge_keyed(target.entry(key.clone()).or_insert(Value::Null), value);
+                }
+            }
+        }
+        Value::Array(items) => {
+            let merge_key = MERGE_KEYS.iter().copied().find(|key| {
+                !items.is_empty()
+                    && items.iter().all(|item| has_merge_key(item, key))
+                    && target.as_array().map_or(false, |existing| {
+                        existing.iter().all(|item| has_merge_key(item, key))
+                    })
+            });
+            match merge_key {
+                Some(key) => {
+                    let existing = target.as_array_mut().expect("target is an array");
+                    for item in items {
+                        match existing.iter_mut().find(|entry| entry[key] == item[key]) {
+                            Some(entry) => merge_keyed(entry, item),
+                            None => existing.push(item.clone()),
+                        }
+                    }
+                }
+                None => *target = patch.clone(),
+            }
+        }
+        patch => *target = patch.clone(),
+    }
+}
+
+fn has_merge_key(item: &Value, key: &str) -> bool {
+    item.get(key).map_or(false, |value| !value.is_null())
+}
+
+/// Checks whether the labels of `object` match the label `selector`, which supports `key=value`,
+/// `key==value`, `key!=value`, `key` and `!key`.
+fn matches_selector(object: &Value, selector: &str) -> bool {
+    let labels = &object["metadata"]["labels"];
+    let label = |key: &str| labels[key.trim()].as_str();
+    selector
+        .split(',')
+        .filter(|requirement| !requirement.trim().is_empty())
+        .all(|requirement| {
+            if let Some((key, value)) = requirement.split_once("!=") {
+                label(key) != Some(value.trim())
+            } else if let Some((key, value)) = requirement
+                .split_once("==")
+                .or_else(|| requirement.split_once('='))
+            {
+                label(key) == Some(value.trim())
+            } else if let Some(key) = requirement.trim().strip_prefix('!') {
+                label(key).is_none()
+            } else {
+                label(requirement).is_some()
+            }
+        })
+}
+
+/// Parses the query string of a request, percent-encoded characters are decoded.
+fn parse_query(query: &str) -> BTreeMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' if index + 2 < bytes.len() => {
+                let byte = std::str::from_utf8(&bytes[index + 1..index + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match byte {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        index += 3;
+                        continue;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            b'+' => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn json_response(status: StatusCode, body: &Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert("content-type", "application/json".parse().unwrap());
+    response
+}
+
+fn status_response(status: StatusCode, reason: &str, message: &str) -> Response<Body> {
+    json_response(
+        status,
+        &json!({
+            "apiVersion": "v1",
+            "kind": "Status",
+            "status": "Failure",
+            "message": message,
+            "reason": reason,
+            "code": status.as_u16(),
+        }),
+    )
+}
+
+fn not_found(name: &str) -> Response<Body> {
+    status_response(
+        StatusCode::NOT_FOUND,
+        "NotFound",
+        &format!("[{}] not found", name),
+    )
+}
+
+#[derive(Clone, Debug)]
+struct Znode {
+    data: Vec<u8>,
+    acls: Vec<Acl>,
+}
+
+#[derive(Debug, Default)]
+struct EnsembleState {
+    znodes: BTreeMap<String, Znode>,
+    config: EnsembleConfig,
+    connection_strings: Vec<String>,
+    unavailable: bool,
+}
+
+/// An in-memory ZooKeeper ensemble, clones share the znodes.
+#[derive(Clone, Debug, Default)]
+pub struct FakeZookeeper {
+    state: Arc<Mutex<EnsembleState>>,
+}
+
+impl FakeZookeeper {
+    pub fn new() -> FakeZookeeper {
+        FakeZookeeper::default()
+    }
+
+    /// Returns a connector opening sessions with this ensemble.
+    pub(crate) fn connector(&self) -> Arc<dyn ZookeeperConnector> {
+        Arc::new(self.clone())
+    }
+
+    /// Makes new sessions fail like an unreachable ensemble, or succeed again.
+    pub fn set_unavailable(&self, unavailable: bool) {
+        self.state.lock().unwrap().unavailable = unavailable;
+    }
+
+    /// Sets the dynamic configuration returned by `get_config`.
+    pub fn set_config(&self, config: EnsembleConfig) {
+        self.state.lock().unwrap().config = config;
+    }
+
+    /// Returns the paths of all znodes in order.
+    pub fn paths(&self) -> Vec<String> {
+        self.state.lock().unwrap().znodes.keys().cloned().collect()
+    }
+
+    /// Returns the data of the znode at `path`.
+    pub fn data(&self, path: &str) -> Option<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        state.znodes.get(path).map(|znode| znode.data.clone())
+    }
+
+    /// Returns the connection strings of all sessions opened so far.
+    pub fn connection_strings(&self) -> Vec<String> {
+        self.state.lock().unwrap().connection_strings.clone()
+    }
+
+    fn with_state<T>(
+        &self,
+        operation: impl FnOnce(&mut EnsembleState) -> Result<T, ZkError>,
+    ) -> Result<T, Error> {
+        let mut state = self.state.lock().unwrap();
+        if state.unavailable {
+            return Err(ZkError::ConnectionLoss.into());
+        }
+        Ok(operation(&mut state)?)
+    }
+}
+
+fn parent(path: &str) -> Option<&str> {
+    match path.rfind('/') {
+        Some(0) if path.len() > 1 => Some("/"),
+        Some(index) if index > 0 => Some(&path[..index]),
+        _ => None,
+    }
+}
+
+impl EnsembleState {
+    fn create(&mut self, path: &str, data: Vec<u8>, acls: Vec<Acl>) -> Result<(), ZkError> {
+        if self.znodes.contains_key(path) {
+            return Err(ZkError::NodeExists);
+        }
+        match parent(path) {
+            Some("/") | None => {}
+            Some(parent) if self.znodes.contains_key(parent) => {}
+            Some(_) => return Err(ZkError::NoNode),
+        }
+        self.znodes.insert(path.to_string(), Znode { data, acls });
+        Ok(())
+    }
+
+    fn znode(&mut self, path: &str) -> Result<&mut Znode, ZkError> {
+        self.znodes.get_mut(path).ok_or(ZkError::NoNode)
+    }
+
+    fn children(&self, path: &str) -> Vec<String> {
+        self.znodes
+            .keys()
+            .filter(|child| parent(child) == Some(path))
+            .filter_map(|child| child.rsplit('/').next())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[async_trait]
+impl ZookeeperConnector for FakeZookeeper {
+    async fn connect(&self, connection_string: &str) -> Result<Box<dyn ZookeeperClient>, Error> {
+        self.with_state(|state| {
+            state.connection_strings.push(connection_string.to_string());
+            Ok(())
+        })?;
+        Ok(Box::new(self.clone()))
+    }
+}
+
+#[async_trait]
+impl ZookeeperClient for FakeZookeeper {
+    async fn create(&self, path: &str, data: Vec<u8>) -> Result<(), Error> {
+        self.with_state(|state| state.create(path, data, Acl::open_unsafe().clone()))
+    }
+
+    async fn create_with_acls(
+        &self,
+        path: &str,
+        data: Vec<u8>,
+        acls: Vec<Acl>,
+    ) -> Result<(), Error> {
+        self.with_state(|state| state.create(path, data, acls))
+    }
+
+    async fn get_acls(&self, path: &str) -> Result<Vec<Acl>, Error> {
+        self.with_state(|state| Ok(state.znode(path)?.acls.clone()))
+    }
+
+    async fn set_acls(&self, path: &str, acls: Vec<Acl>) -> Result<(), Error> {
+        self.with_state(|state| {
+            state.znode(path)?.acls = acls;
+            Ok(())
+        })
+    }
+
+    async fn ensure_path(&self, path: &str) -> Result<(), Error> {
+        self.with_state(|state| {
+            let mut current = String::new();
+            for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+                current = format!("{}/{}", current, segment);
+                if !state.znodes.contains_key(&current) {
+                    state.create(&current, vec![], Acl::open_unsafe().clone())?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    async fn delete_recursive(&self, path: &str) -> Result<(), Error> {
+        self.with_state(|state| {
+            let prefix = format!("{}/", path.trim_end_matches('/'));
+            state
+                .znodes
+                .retain(|znode, _| znode != path && !znode.starts_with(&prefix));
+            Ok(())
+        })
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool, Error> {
+        self.with_state(|state| Ok(state.znodes.contains_key(path)))
+    }
+
+    async fn is_ephemeral(&self, _path: &str) -> Result<bool, Error> {
+        // sessions of the fake ensemble never create ephemeral znodes
+        self.with_state(|_| Ok(false))
+    }
+
+    async fn get_children(&self, path: &str) -> Result<Vec<String>, Error> {
+        self.with_state(|state| {
+            if path != "/" {
+                state.znode(path)?;
+            }
+            Ok(state.children(path))
+        })
+    }
+
+    async fn get_data(&self, path: &str) -> Result<Vec<u8>, Error> {
+        self.with_state(|state| Ok(state.znode(path)?.data.clone()))
+    }
+
+    async fn set_data(&self, path: &str, data: Vec<u8>) -> Result<(), Error> {
+        self.with_state(|state| {
+            state.znode(path)?.data = data;
+            Ok(())
+        })
+    }
+
+    async fn get_config(&self) -> Result<EnsembleConfig, Error> {
+        self.with_state(|state| Ok(state.config.clone()))
+    }
+
+    async fn add_auth(&self, _scheme: &str, _auth: Vec<u8>) -> Result<(), Error> {
+        self.with_state(|_| Ok(()))
+    }
+
+    async fn close(self: Box<Self>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// A command run by the [`FakePodExecutor`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Exec {
+    pub pod: String,
+    pub container: String,
+    pub command: Vec<String>,
+}
+
+/// Records the commands run in containers and answers them all with the same output, clones
+/// share the records.
+#[derive(Clone, Debug, Default)]
+pub struct FakePodExecutor {
+    output: ExecOutput,
+    execs: Arc<Mutex<Vec<Exec>>>,
+}
+
+impl FakePodExecutor {
+    pub fn new(output: ExecOutput) -> FakePodExecutor {
+        FakePodExecutor {
+            output,
+            execs: Arc::default(),
+        }
+    }
+
+    /// Returns an executor recording into this one.
+    pub(crate) fn executor(&self) -> Arc<dyn PodExecutor> {
+        Arc::new(self.clone())
+    }
+
+    /// Returns the commands run so far in order.
+    pub fn execs(&self) -> Vec<Exec> {
+        self.execs.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl PodExecutor for FakePodExecutor {
+    async fn exec(
+        &self,
+        _pods_api: kube::Api<Pod>,
+        pod: &str,
+        container: &str,
+        command: Vec<String>,
+    ) -> Result<ExecOutput, Error> {
+        self.execs.lock().unwrap().push(Exec {
+            pod: pod.to_string(),
+            container: container.to_string(),
+            command,
+        });
+        Ok(self.output.clone())
+    }
+}
+
+/// Builds a ZookeeperCluster `name` in [`NAMESPACE`] with the [`VERSION`] and a role group
+/// `default`, `spec` is merged into this spec.
+pub fn cluster(name: &str, spec: Value) -> ZookeeperCluster {
+    let mut cluster_spec = json!({
+        "version": VERSION,
+        "servers": {
+            "roleGroups": {
+                "default": {
+                    "selector": { "matchLabels": {} },
+                    "config": {}
+                }
+            }
+        }
+    });
+    merge(&mut cluster_spec, &spec);
+    serde_json::from_value(json!({
+        "apiVersion": "zookeeper.stackable.tech/v1alpha1",
+        "kind": "ZookeeperCluster",
+        "metadata": {
+            "name": name,
+            "namespace": NAMESPACE,
+            "uid": format!("{}-uid", name),
+            "generation": 1,
+            "creationTimestamp": "2021-06-01T12:00:00Z"
+        },
+        "spec": cluster_spec,
+    }))
+    .expect("the fixture cluster is valid")
+}
+
+/// Builds the ready server pod with the given `myid` of `cluster` in `role_group` on `node`, as
+/// created by the operator.
+pub fn server_pod(cluster: &ZookeeperCluster, role_group: &str, id: usize, node: &str) -> Pod {
+    let mut pod_labels = build_common_labels_for_all_managed_resources(APP_NAME, &cluster.name());
+    pod_labels.insert(
+        labels::APP_COMPONENT_LABEL.to_string(),
+        ZookeeperRole::Server.to_string(),
+    );
+    pod_labels.insert(
+        labels::APP_ROLE_GROUP_LABEL.to_string(),
+        role_group.to_string(),
+    );
+    pod_labels.insert(
+        labels::APP_VERSION_LABEL.to_string(),
+        cluster.spec.version.to_string(),
+    );
+    pod_labels.insert(ID_LABEL.to_string(), id.to_string());
+
+    serde_json::from_value(json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": {
+            "name": format!("{}-server-{}-{}", cluster.name(), role_group, id),
+            "namespace": cluster.namespace(),
+            "uid": format!("{}-server-{}-uid", cluster.name(), id),
+            "labels": pod_labels,
+            "ownerReferences": [{
+                "apiVersion": "zookeeper.stackable.tech/v1alpha1",
+                "kind": "ZookeeperCluster",
+                "name": cluster.name(),
+                "uid": cluster.metadata.uid,
+                "controller": true
+            }]
+        },
+        "spec": {
+            "nodeName": node,
+            "containers": [{
+                "name": "zookeeper",
+                "ports": [{ "name": "client", "containerPort": 2181 }]
+            }]
+        },
+        "status": {
+            "phase": "Running",
+            "conditions": [{ "type": "Ready", "status": "True" }]
+        }
+    }))
+    .expect("the fixture pod is valid")
+}
+
+/// Builds the pod `name` with the given `myid` label and nothing else.
+pub fn pod_with_id(name: &str, id: Option<&str>) -> Pod {
+    let mut pod = Pod::default();
+    pod.metadata.name = Some(name.to_string());
+    if let Some(id) = id {
+        pod.metadata
+            .labels
+            .insert(ID_LABEL.to_string(), id.to_string());
+    }
+    pod
+}
+
+/// Builds the Node `name` with the given labels and `InternalIP`.
+pub fn node(name: &str, node_labels: &[(&str, &str)], internal_ip: &str) -> Node {
+    let node_labels = node_labels
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<BTreeMap<_, _>>();
+    serde_json::from_value(json!({
+        "apiVersion": "v1",
+        "kind": "Node",
+        "metadata": { "name": name, "labels": node_labels },
+        "status": {
+            "addresses": [{ "type": "InternalIP", "address": internal_ip }]
+        }
+    }))
+    .expect("the fixture node is valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::smoke_test;
+    use k8s_openapi::api::core::v1::ConfigMap;
+    use kube::api::{ListParams, Patch, PatchParams, PostParams};
+    use kube::Api;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(
+        "/api/v1/namespaces/default/pods/simple-server-default-1",
+        "/api/v1",
+        Some("default"),
+        "pods",
+        Some("simple-server-default-1"),
+        None
+    )]
+    #[case("/api/v1/nodes", "/api/v1", None, "nodes", None, None)]
+    #[case(
+        "/apis/zookeeper.stackable.tech/v1alpha1/namespaces/default/zookeeperclusters/simple/status",
+        "/apis/zookeeper.stackable.tech/v1alpha1",
+        Some("default"),
+        "zookeeperclusters",
+        Some("simple"),
+        Some("status")
+    )]
+    fn test_parse_request_path(
+        #[case] path: &str,
+        #[case] api: &str,
+        #[case] namespace: Option<&str>,
+        #[case] plural: &str,
+        #[case] name: Option<&str>,
+        #[case] subresource: Option<&str>,
+    ) {
+        assert_eq!(
+            RequestPath::parse(path),
+            Some(RequestPath {
+                api: api.to_string(),
+                plural: plural.to_string(),
+                namespace: namespace.map(str::to_string),
+                name: name.map(str::to_string),
+                subresource: subresource.map(str::to_string),
+            })
+        );
+    }
+
+    #[rstest]
+    #[case("app=zookeeper", true)]
+    #[case("app%3Dzookeeper", true)]
+    #[case("app=zookeeper,tier!=web", true)]
+    #[case("app==kafka", false)]
+    #[case("tier", false)]
+    #[case("!tier", true)]
+    fn test_matches_selector(#[case] selector: &str, #[case] expected: bool) {
+        let object = json!({ "metadata": { "labels": { "app": "zookeeper" } } });
+        assert_eq!(
+            matches_selector(&object, &percent_decode(selector)),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut target = json!({ "a": 1, "b": { "c": 2, "d": 3 } });
+        merge(
+            &mut target,
+            &json!({ "a": null, "b": { "c": 4 }, "e": [5] }),
+        );
+        assert_eq!(target, json!({ "b": { "c": 4, "d": 3 }, "e": [5] }));
+    }
+
+    #[test]
+    fn test_merge_keyed() {
+        let mut target = json!({
+            "conditions": [
+                { "type": "Ready", "status": "True" },
+                { "type": "Synced", "status": "False" }
+            ],
+            "finalizers": ["a"],
+            "ports": [{ "containerPort": 2181 }]
+        });
+        merge_keyed(
+            &mut target,
+            &json!({
+                "conditions": [
+                    { "type": "Synced", "status": "True" },
+                    { "type": "Started", "status": "True" }
+                ],
+                "finalizers": ["b"],
+                "ports": [{ "name": "client", "containerPort": 2182 }]
+            }),
+        );
+        assert_eq!(
+            target,
+            json!({
+                "conditions": [
+                    { "type": "Ready", "status": "True" },
+                    { "type": "Synced", "status": "True" },
+                    { "type": "Started", "status": "True" }
+                ],
+                "finalizers": ["b"],
+                "ports": [{ "containerPort": 2181 }, { "name": "client", "containerPort": 2182 }]
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fake_api_server() {
+        let server = FakeApiServer::new();
+        let clusters: Api<ZookeeperCluster> = Api::namespaced(server.client(), NAMESPACE);
+
+        clusters
+            .create(&PostParams::default(), &cluster("simple", json!({})))
+            .await
+            .unwrap();
+        clusters
+            .patch_status(
+                "simple",
+                &PatchParams::default(),
+                &Patch::Merge(json!({ "status": { "readyReplicas": 3 } })),
+            )
+            .await
+            .unwrap();
+        let patched = clusters
+            .patch(
+                "simple",
+                &PatchParams::default(),
+                &Patch::Merge(json!({ "spec": { "paused": true }, "status": null })),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(patched.metadata.generation, Some(2));
+        assert_eq!(patched.status.map(|status| status.ready_replicas), Some(3));
+        assert!(clusters.get("missing").await.is_err());
+
+        server.insert(&server_pod(&patched, "default", 1, "node-1"));
+        let config_maps: Api<ConfigMap> = Api::namespaced(server.client(), NAMESPACE);
+        assert!(config_maps
+            .list(&ListParams::default())
+            .await
+            .unwrap()
+            .items
+            .is_empty());
+        let pods: Api<Pod> = Api::namespaced(server.client(), NAMESPACE);
+        let selected = pods
+            .list(&ListParams::default().labels(&format!("{}=1", ID_LABEL)))
+            .await
+            .unwrap();
+        assert_eq!(selected.items.len(), 1);
+        assert_eq!(server.list::<Pod>().len(), 1);
+
+        // conditions are merged by their type
+        let pod = pods
+            .patch_status(
+                &selected.items[0].name(),
+                &PatchParams::default(),
+                &Patch::Strategic(json!({
+                    "status": { "conditions": [{ "type": "Synced", "status": "True" }] }
+                })),
+            )
+            .await
+            .unwrap();
+        assert_eq!(pod.status.map(|status| status.conditions.len()), Some(2));
+
+        let config_map = config_maps
+            .create(
+                &PostParams::default(),
+                &serde_json::from_value(json!({ "metadata": { "generateName": "simple-" } }))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(config_map.name().starts_with("simple-"));
+        assert!(server
+            .requests()
+            .contains(&"PATCH /apis/zookeeper.stackable.tech/v1alpha1/namespaces/default/zookeeperclusters/simple/status".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_smoke_test_against_fake_zookeeper() {
+        let zookeeper = FakeZookeeper::new();
+
+        smoke_test::run_smoke_test(zookeeper.connector().as_ref(), "node-1:2181", "probe")
+            .await
+            .unwrap();
+
+        assert_eq!(zookeeper.connection_strings(), vec!["node-1:2181"]);
+        assert!(!zookeeper
+            .paths()
+            .iter()
+            .any(|path| path.ends_with("/probe")));
+
+        zookeeper.set_unavailable(true);
+        assert!(
+            smoke_test::run_smoke_test(zookeeper.connector().as_ref(), "node-1:2181", "probe")
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fake_zookeeper() {
+        let zookeeper = FakeZookeeper::new();
+        let zk = zookeeper.connector().connect("node-1:2181").await.unwrap();
+
+        zk.ensure_path("/app/config").await.unwrap();
+        zk.create("/app/config/a", b"1".to_vec()).await.unwrap();
+        assert!(zk.create("/missing/a", vec![]).await.is_err());
+        assert_eq!(zk.get_children("/app/config").await.unwrap(), vec!["a"]);
+
+        zk.delete_recursive("/app").await.unwrap();
+        assert!(zookeeper.paths().is_empty());
+        assert_eq!(zookeeper.data("/app/config/a"), None);
+    }
+}
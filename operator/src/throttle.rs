@@ -0,0 +1,315 @@
+//! Limits for the reconciliations of ZookeeperClusters.
+//!
+//! Every reconciliation lists the pods and Nodes of its cluster and patches a handful of objects.
+//! When the operator starts (or its watches restart) all clusters are reconciled at once, which
+//! with hundreds of clusters floods the API server with requests. The [`ReconcileThrottle`] caps
+//! the number of reconciliations running at the same time and, optionally, the rate at which
+//! they start, which bounds the requests the operator sends.
+//!
+//! With many clusters the reconciliations queue up behind these limits, so:
+//!
+//! - Clusters with pending changes (see [`ReconcilePriority::of`]) go first, the periodic
+//!   re-checks of clusters in their desired state wait while any of them is queued.
+//! - Periodic requeues are lengthened by a random fraction (see [`stagger`]), so clusters which
+//!   were reconciled at the same time (e.g. when the operator started) drift apart.
+//! - The number of queued reconciliations is exported, see [`ReconcileThrottle::queue_depth`].
+use stackable_zookeeper_crd::ZookeeperCluster;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+
+/// The maximum fraction periodic requeues are lengthened by, see [`stagger`].
+pub const MAX_REQUEUE_JITTER: f64 = 0.1;
+
+/// Lengthens `interval` by up to [`MAX_REQUEUE_JITTER`] depending on `random` (between 0 and 1).
+pub fn stagger(interval: Duration, random: f64) -> Duration {
+    interval.mul_f64(1.0 + random.max(0.0).min(1.0) * MAX_REQUEUE_JITTER)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReconcilePriority {
+    /// The cluster was created, changed or deleted, or has not reached its desired state yet.
+    PendingChanges,
+    /// The cluster is re-checked, e.g. for the resync interval or a quorum probe.
+    Periodic,
+}
+
+impl ReconcilePriority {
+    /// Returns the priority of the next reconciliation of `cluster`: clusters whose current
+    /// generation was not observed yet, which are being deleted or which are still on their way
+    /// to the desired state have pending changes.
+    pub fn of(cluster: &ZookeeperCluster) -> ReconcilePriority {
+        let status = match &cluster.status {
+            Some(status) => status,
+            None => return ReconcilePriority::PendingChanges,
+        };
+        if cluster.metadata.deletion_timestamp.is_some()
+            || status.observed_generation != cluster.metadata.generation
+            || status.pending_transition.is_some()
+        {
+            ReconcilePriority::PendingChanges
+        } else {
+            ReconcilePriority::Periodic
+        }
+    }
+}
+
+/// The number of reconciliations waiting for the throttle by priority.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct QueueDepth {
+    pub pending_changes: usize,
+    pub periodic: usize,
+}
+
+/// Shared by all controllers of ZookeeperClusters (one per watched namespace), clones share the
+/// limits.
+#[derive(Clone, Debug)]
+pub struct ReconcileThrottle {
+    permits: Arc<Semaphore>,
+    max_concurrent: usize,
+    rate: Option<Arc<Mutex<TokenBucket>>>,
+    queue_depth: Arc<Mutex<QueueDepth>>,
+    /// Notified whenever a reconciliation with pending changes leaves the queue.
+    pending_changes_dequeued: Arc<Notify>,
+}
+
+impl ReconcileThrottle {
+    /// # Arguments
+    ///
+    /// - `max_concurrent` - The maximum number of reconciliations running at the same time.
+    /// - `per_second` - The maximum number of reconciliations started per second, unlimited if
+    ///   `None`. Up to one second worth of them may start at once.
+    ///
+    pub fn new(max_concurrent: usize, per_second: Option<f64>) -> ReconcileThrottle {
+        let max_concurrent = max_concurrent.max(1);
+        ReconcileThrottle {
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+            max_concurrent,
+            rate: per_second
+                .map(|rate| Arc::new(Mutex::new(TokenBucket::new(rate, Instant::now())))),
+            queue_depth: Arc::default(),
+            pending_changes_dequeued: Arc::default(),
+        }
+    }
+
+    /// Returns the number of reconciliations currently waiting to start.
+    pub fn queue_depth(&self) -> QueueDepth {
+        *self.queue_depth.lock().unwrap()
+    }
+
+    /// Waits until a reconciliation with the given priority may start, it may run as long as
+    /// the permit is held. Periodic reconciliations only start while no reconciliation with
+    /// pending changes is waiting.
+    pub async fn acquire(&self, priority: ReconcilePriority) -> OwnedSemaphorePermit {
+        let _queued = QueuedReconciliation::new(self, priority);
+        if let Some(rate) = &self.rate {
+            let wait = rate.lock().unwrap().take(Instant::now());
+            if wait > Duration::from_secs(0) {
+                debug!(
+                    "Delaying the reconciliation by {:?} because of the rate limit",
+                    wait
+                );
+                tokio::time::sleep(wait).await;
+            }
+        }
+        loop {
+            if priority == ReconcilePriority::Periodic {
+                self.wait_for_pending_changes().await;
+            }
+            let permit = self
+                .permits
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("the semaphore is never closed");
+            // a reconciliation with pending changes may have been queued in the meantime, the
+            // permit is handed on to it
+            if priority == ReconcilePriority::PendingChanges
+                || self.queue_depth().pending_changes == 0
+            {
+                return permit;
+            }
+        }
+    }
+
+    /// Waits until no reconciliation with pending changes is queued anymore.
+    async fn wait_for_pending_changes(&self) {
+        loop {
+            // created before checking the queue, so a dequeue in between is not missed
+            let dequeued = self.pending_changes_dequeued.notified();
+            if self.queue_depth().pending_changes == 0 {
+                return;
+            }
+            dequeued.await;
+        }
+    }
+
+    /// Waits up to `timeout` until the running reconciliations finished and keeps all permits,
+    /// so no further reconciliation starts. Returns whether all of them finished in time.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        match tokio::time::timeout(
+            timeout,
+            self.permits.acquire_many(self.max_concurrent as u32),
+        )
+        .await
+        {
+            Ok(permits) => {
+                permits.expect("the semaphore is never closed").forget();
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// Counts a reconciliation in the [`QueueDepth`] until it started (or was cancelled).
+struct QueuedReconciliation<'a> {
+    throttle: &'a ReconcileThrottle,
+    priority: ReconcilePriority,
+}
+
+impl<'a> QueuedReconciliation<'a> {
+    fn new(throttle: &'a ReconcileThrottle, priority: ReconcilePriority) -> Self {
+        let mut queue_depth = throttle.queue_depth.lock().unwrap();
+        match priority {
+            ReconcilePriority::PendingChanges => queue_depth.pending_changes += 1,
+            ReconcilePriority::Periodic => queue_depth.periodic += 1,
+        }
+        QueuedReconciliation { throttle, priority }
+    }
+}
+
+impl Drop for QueuedReconciliation<'_> {
+    fn drop(&mut self) {
+        let mut queue_depth = self.throttle.queue_depth.lock().unwrap();
+        match self.priority {
+            ReconcilePriority::PendingChanges => {
+                queue_depth.pending_changes -= 1;
+                self.throttle.pending_changes_dequeued.notify_waiters();
+            }
+            ReconcilePriority::Periodic => queue_depth.periodic -= 1,
+        }
+    }
+}
+
+impl Default for ReconcileThrottle {
+    fn default() -> Self {
+        ReconcileThrottle::new(10, None)
+    }
+}
+
+/// Hands out `rate` tokens per second and holds at most one second worth of them.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, now: Instant) -> TokenBucket {
+        let capacity = rate.max(1.0);
+        TokenBucket {
+            rate,
+            capacity,
+            tokens: capacity,
+            updated: now,
+        }
+    }
+
+    /// Takes a token and returns how long to wait until it is available. Tokens which are not
+    /// available yet are reserved, so every caller waits for a token of its own.
+    fn take(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn cluster(generation: i64, status: serde_json::Value) -> ZookeeperCluster {
+        serde_json::from_value(json!({
+            "apiVersion": "zookeeper.stackable.tech/v1alpha1",
+            "kind": "ZookeeperCluster",
+            "metadata": { "name": "simple", "generation": generation },
+            "spec": { "version": "3.5.8", "servers": { "roleGroups": {} } },
+            "status": status,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_priority() {
+        assert_eq!(
+            ReconcilePriority::of(&cluster(1, json!(null))),
+            ReconcilePriority::PendingChanges
+        );
+        assert_eq!(
+            ReconcilePriority::of(&cluster(2, json!({ "observedGeneration": 1 }))),
+            ReconcilePriority::PendingChanges
+        );
+        assert_eq!(
+            ReconcilePriority::of(&cluster(
+                1,
+                json!({
+                    "observedGeneration": 1,
+                    "pendingTransition": {
+                        "generation": 1,
+                        "startedAt": "2021-06-01T12:00:00+00:00",
+                        "trigger": "specChange"
+                    }
+                })
+            )),
+            ReconcilePriority::PendingChanges
+        );
+        assert_eq!(
+            ReconcilePriority::of(&cluster(1, json!({ "observedGeneration": 1 }))),
+            ReconcilePriority::Periodic
+        );
+    }
+
+    #[test]
+    fn test_stagger() {
+        assert_eq!(
+            stagger(Duration::from_secs(60), 0.0),
+            Duration::from_secs(60)
+        );
+        assert_eq!(
+            stagger(Duration::from_secs(60), 1.0),
+            Duration::from_secs(66)
+        );
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2.0, start);
+
+        assert_eq!(bucket.take(start), Duration::from_secs(0));
+        assert_eq!(bucket.take(start), Duration::from_secs(0));
+        assert_eq!(bucket.take(start), Duration::from_millis(500));
+        assert_eq!(bucket.take(start), Duration::from_secs(1));
+
+        // the reserved tokens are paid back before new ones are available
+        assert_eq!(
+            bucket.take(start + Duration::from_secs(1)),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            bucket.take(start + Duration::from_secs(10)),
+            Duration::from_secs(0)
+        );
+    }
+}
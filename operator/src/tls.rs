@@ -0,0 +1,82 @@
+//! Mounting the TLS Secret into the server pods, see [`stackable_zookeeper_crd::tls`] for the
+//! `zoo.cfg` properties.
+use k8s_openapi::api::core::v1::{
+    EnvVar, EnvVarSource, SecretKeySelector, SecretVolumeSource, Volume, VolumeMount,
+};
+use stackable_zookeeper_crd::tls::{ZookeeperTls, PASSWORD_KEY, TLS_DIR};
+
+/// The environment variable holding the password of the stores, it is referenced by the JVM
+/// flags.
+const TLS_PASSWORD_ENV: &str = "TLS_STORE_PASSWORD";
+
+pub const TLS_VOLUME_NAME: &str = "tls";
+
+/// Builds the volume containing the TLS Secret and its mount.
+pub fn build_volume(tls: &ZookeeperTls) -> (Volume, VolumeMount) {
+    let volume = Volume {
+        name: TLS_VOLUME_NAME.to_string(),
+        secret: Some(SecretVolumeSource {
+            secret_name: Some(tls.secret_name.clone()),
+            ..SecretVolumeSource::default()
+        }),
+        ..Volume::default()
+    };
+    let volume_mount = VolumeMount {
+        name: TLS_VOLUME_NAME.to_string(),
+        mount_path: TLS_DIR.to_string(),
+        read_only: Some(true),
+        ..VolumeMount::default()
+    };
+    (volume, volume_mount)
+}
+
+/// Builds the environment variable with the store password read from its Secret. It must
+/// precede the variable containing the flags of [`build_password_jvm_flags`], otherwise
+/// Kubernetes does not expand the reference.
+pub fn build_password_env_var(tls: &ZookeeperTls) -> EnvVar {
+    EnvVar {
+        name: TLS_PASSWORD_ENV.to_string(),
+        value_from: Some(EnvVarSource {
+            secret_key_ref: Some(SecretKeySelector {
+                name: Some(tls.password_secret_name()),
+                key: PASSWORD_KEY.to_string(),
+                optional: Some(false),
+            }),
+            ..EnvVarSource::default()
+        }),
+        ..EnvVar::default()
+    }
+}
+
+/// Builds the JVM flags setting the store passwords.
+pub fn build_password_jvm_flags(tls: &ZookeeperTls) -> Vec<String> {
+    tls.password_properties()
+        .iter()
+        .map(|property| format!("-D{}=$({})", property, TLS_PASSWORD_ENV))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_password_jvm_flags() {
+        let tls = ZookeeperTls {
+            secret_name: "zookeeper-tls".to_string(),
+            client: false,
+            secure_client_port: None,
+            client_auth: None,
+            quorum: true,
+            issuer_ref: None,
+        };
+
+        assert_eq!(
+            build_password_jvm_flags(&tls),
+            vec![
+                "-Dzookeeper.ssl.quorum.keyStore.password=$(TLS_STORE_PASSWORD)",
+                "-Dzookeeper.ssl.quorum.trustStore.password=$(TLS_STORE_PASSWORD)"
+            ]
+        );
+    }
+}
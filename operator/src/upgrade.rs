@@ -0,0 +1,204 @@
+//! Verifying the canary of an upgrade, see [`stackable_zookeeper_crd::upgrade`].
+use k8s_openapi::chrono::{DateTime, Duration, Utc};
+use stackable_zookeeper_crd::upgrade::{CanaryPhase, CanaryStatus, ZookeeperCanary};
+
+/// Returns the status of the canary after checking it at `now`.
+///
+/// A starting canary is soaked as soon as it serves requests while the quorum is healthy, and
+/// fails if that does not happen within the start timeout. A soaking canary passes once the soak
+/// time is over and fails as soon as it or the quorum is not healthy anymore. Passed and failed
+/// canaries are final.
+pub fn verify_canary(
+    status: &CanaryStatus,
+    canary: &ZookeeperCanary,
+    serving: bool,
+    quorum_healthy: bool,
+    now: &DateTime<Utc>,
+) -> CanaryStatus {
+    let elapsed = |since: Option<&String>, seconds: u32| {
+        since
+            .and_then(|since| DateTime::parse_from_rfc3339(since).ok())
+            .map_or(true, |since| {
+                *now - since.with_timezone(&Utc) >= Duration::seconds(i64::from(seconds))
+            })
+    };
+    let failed = |message: String| CanaryStatus {
+        phase: CanaryPhase::Failed,
+        message: Some(message),
+        ..status.clone()
+    };
+
+    match status.phase {
+        CanaryPhase::Starting if serving && quorum_healthy => CanaryStatus {
+            phase: CanaryPhase::Soaking,
+            soak_started_at: Some(now.to_rfc3339()),
+            ..status.clone()
+        },
+        CanaryPhase::Starting
+            if elapsed(Some(&status.started_at), canary.start_timeout_seconds()) =>
+        {
+            failed(if serving {
+                format!(
+                    "The quorum was not healthy within [{}] seconds after the canary was upgraded",
+                    canary.start_timeout_seconds()
+                )
+            } else {
+                format!(
+                    "The canary did not serve requests within [{}] seconds",
+                    canary.start_timeout_seconds()
+                )
+            })
+        }
+        CanaryPhase::Soaking if !serving => {
+            failed("The canary stopped serving requests during the soak time".to_string())
+        }
+        CanaryPhase::Soaking if !quorum_healthy => {
+            failed("The quorum was not healthy during the soak time".to_string())
+        }
+        CanaryPhase::Soaking if elapsed(status.soak_started_at.as_ref(), canary.soak_seconds()) => {
+            CanaryStatus {
+                phase: CanaryPhase::Passed,
+                ..status.clone()
+            }
+        }
+        _ => status.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const STARTED_AT: &str = "2021-06-01T12:00:00+00:00";
+    const SOAK_STARTED_AT: &str = "2021-06-01T12:02:00+00:00";
+
+    fn status(phase: CanaryPhase) -> CanaryStatus {
+        CanaryStatus {
+            pod: "simple-server-1".to_string(),
+            id: 1,
+            version: "3.6.3".parse().unwrap(),
+            phase,
+            started_at: STARTED_AT.to_string(),
+            soak_started_at: if phase == CanaryPhase::Starting {
+                None
+            } else {
+                Some(SOAK_STARTED_AT.to_string())
+            },
+            message: None,
+        }
+    }
+
+    fn time(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[rstest]
+    #[case(
+        CanaryPhase::Starting,
+        true,
+        true,
+        "2021-06-01T12:01:00Z",
+        CanaryPhase::Soaking
+    )]
+    #[case(
+        CanaryPhase::Starting,
+        false,
+        true,
+        "2021-06-01T12:09:59Z",
+        CanaryPhase::Starting
+    )]
+    #[case(
+        CanaryPhase::Starting,
+        false,
+        true,
+        "2021-06-01T12:10:00Z",
+        CanaryPhase::Failed
+    )]
+    #[case(
+        CanaryPhase::Starting,
+        true,
+        false,
+        "2021-06-01T12:10:00Z",
+        CanaryPhase::Failed
+    )]
+    #[case(
+        CanaryPhase::Soaking,
+        true,
+        true,
+        "2021-06-01T12:06:59Z",
+        CanaryPhase::Soaking
+    )]
+    #[case(
+        CanaryPhase::Soaking,
+        true,
+        true,
+        "2021-06-01T12:07:00Z",
+        CanaryPhase::Passed
+    )]
+    #[case(
+        CanaryPhase::Soaking,
+        false,
+        true,
+        "2021-06-01T12:03:00Z",
+        CanaryPhase::Failed
+    )]
+    #[case(
+        CanaryPhase::Soaking,
+        true,
+        false,
+        "2021-06-01T12:03:00Z",
+        CanaryPhase::Failed
+    )]
+    #[case(
+        CanaryPhase::Passed,
+        false,
+        false,
+        "2021-06-01T12:30:00Z",
+        CanaryPhase::Passed
+    )]
+    #[case(
+        CanaryPhase::Failed,
+        true,
+        true,
+        "2021-06-01T12:30:00Z",
+        CanaryPhase::Failed
+    )]
+    fn test_verify_canary(
+        #[case] phase: CanaryPhase,
+        #[case] serving: bool,
+        #[case] quorum_healthy: bool,
+        #[case] now: &str,
+        #[case] expected: CanaryPhase,
+    ) {
+        let verified = verify_canary(
+            &status(phase),
+            &ZookeeperCanary::default(),
+            serving,
+            quorum_healthy,
+            &time(now),
+        );
+
+        assert_eq!(verified.phase, expected);
+        assert_eq!(
+            verified.message.is_some(),
+            expected == CanaryPhase::Failed && phase != CanaryPhase::Failed
+        );
+    }
+
+    #[test]
+    fn test_verify_canary_starts_soaking() {
+        let now = time("2021-06-01T12:01:00Z");
+        let verified = verify_canary(
+            &status(CanaryPhase::Starting),
+            &ZookeeperCanary::default(),
+            true,
+            true,
+            &now,
+        );
+
+        assert_eq!(verified.soak_started_at, Some(now.to_rfc3339()));
+    }
+}
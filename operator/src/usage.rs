@@ -0,0 +1,348 @@
+//! Fleet wide statistics about which features of the ZookeeperCluster spec are in use.
+//!
+//! Platform teams can use the report to plan deprecations and capacity. It is computed from the
+//! specs the controller already received for reconciliation, so serving it does not cause any
+//! additional load on the Kubernetes API server.
+use crate::campaign::RestartCampaign;
+use crate::error::Error;
+use crate::object_ref::ObjectRef;
+use crate::reconcile_state::ClusterReconcileState;
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Serialize;
+use serde_json::Value;
+use stackable_zookeeper_crd::{ZookeeperCluster, ZookeeperClusterSpec};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+/// The path the usage report is served on.
+pub const USAGE_REPORT_PATH: &str = "/usage";
+/// The path the latest reconciliation of every cluster is served on.
+pub const RECONCILE_STATES_PATH: &str = "/clusters";
+
+/// Keeps the most recently reconciled spec and the latest reconciliation of every
+/// ZookeeperCluster and the progress of all restart campaigns.
+#[derive(Clone, Debug, Default)]
+pub struct UsageStatistics {
+    specs: Arc<Mutex<BTreeMap<ObjectRef, ZookeeperClusterSpec>>>,
+    reconciles: Arc<Mutex<BTreeMap<ObjectRef, ClusterReconcileState>>>,
+    campaigns: Arc<Mutex<BTreeMap<ObjectRef, RestartCampaign>>>,
+}
+
+#[derive(Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageReport {
+    /// The number of ZookeeperClusters.
+    pub clusters: usize,
+    /// The number of clusters per ZooKeeper version.
+    pub versions: BTreeMap<String, usize>,
+    /// The number of role groups across all clusters.
+    pub role_groups: usize,
+    /// The sum of all requested replicas. Role groups without `replicas` are not included.
+    pub replicas: usize,
+    /// The number of clusters using an optional spec feature.
+    pub features: BTreeMap<String, usize>,
+    /// The number of clusters overriding a property, keyed by `<file>/<property>`.
+    pub config_override_keys: BTreeMap<String, usize>,
+    /// The number of clusters overriding an environment variable.
+    pub env_override_keys: BTreeMap<String, usize>,
+    /// The number of clusters overriding a command line argument.
+    pub cli_override_keys: BTreeMap<String, usize>,
+    /// The progress of all restart campaigns.
+    pub restart_campaigns: Vec<RestartCampaign>,
+}
+
+impl UsageStatistics {
+    /// Records the spec of `zk`, or forgets it if the cluster is being deleted.
+    pub fn record(&self, zk: &ZookeeperCluster) {
+        let key = ObjectRef::from_obj(zk);
+        let mut specs = self.specs.lock().unwrap();
+        if zk.metadata.deletion_timestamp.is_some() {
+            self.reconciles.lock().unwrap().remove(&key);
+            specs.remove(&key);
+        } else {
+            specs.insert(key, zk.spec.clone());
+        }
+    }
+
+    /// Returns the number of clusters whose spec is kept.
+    pub fn cluster_count(&self) -> usize {
+        self.specs.lock().unwrap().len()
+    }
+
+    /// Records the latest reconciliation of a cluster which is not being deleted. The previous
+    /// failure is kept if the reconciliation succeeded.
+    pub fn record_reconcile(&self, key: ObjectRef, mut state: ClusterReconcileState) {
+        let mut reconciles = self.reconciles.lock().unwrap();
+        if state.last_error.is_none() {
+            state.last_error = reconciles
+                .get(&key)
+                .and_then(|previous| previous.last_error.clone());
+        }
+        reconciles.insert(key, state);
+    }
+
+    /// Returns the latest reconciliation of every cluster.
+    pub fn reconcile_states(&self) -> BTreeMap<ObjectRef, ClusterReconcileState> {
+        self.reconciles.lock().unwrap().clone()
+    }
+
+    pub fn record_campaign(&self, campaign: RestartCampaign) {
+        let key = ObjectRef::new(&campaign.namespace, &campaign.name);
+        self.campaigns.lock().unwrap().insert(key, campaign);
+    }
+
+    pub fn report(&self) -> Result<UsageReport, Error> {
+        let specs = self.specs.lock().unwrap();
+        let mut report = build_report(specs.values())?;
+        report.restart_campaigns = self.campaigns.lock().unwrap().values().cloned().collect();
+        Ok(report)
+    }
+}
+
+/// Builds a [`UsageReport`] from the given specs.
+///
+/// The overrides are read from the serialized spec so that role and role group level settings
+/// are covered the same way. Every cluster counts at most once per key.
+pub fn build_report<'a>(
+    specs: impl IntoIterator<Item = &'a ZookeeperClusterSpec>,
+) -> Result<UsageReport, Error> {
+    let mut report = UsageReport::default();
+
+    for spec in specs {
+        report.clusters += 1;
+        *report.versions.entry(spec.version.to_string()).or_default() += 1;
+        if spec.rebalance_clients_after_scale_up {
+            *report
+                .features
+                .entry("rebalanceClientsAfterScaleUp".to_string())
+                .or_default() += 1;
+        }
+
+        let servers = serde_json::to_value(&spec.servers)?;
+        let role_groups = servers
+            .get("roleGroups")
+            .and_then(Value::as_object)
+            .map(|role_groups| role_groups.values().collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        report.role_groups += role_groups.len();
+        report.replicas += role_groups
+            .iter()
+            .filter_map(|role_group| role_group.get("replicas").and_then(Value::as_u64))
+            .sum::<u64>() as usize;
+
+        let mut config_override_keys = Vec::new();
+        let mut env_override_keys = Vec::new();
+        let mut cli_override_keys = Vec::new();
+        for level in role_groups.into_iter().chain(Some(&servers)) {
+            for (file, properties) in object_entries(level.get("configOverrides")) {
+                for (property, _) in object_entries(Some(properties)) {
+                    config_override_keys.push(format!("{}/{}", file, property));
+                }
+            }
+            env_override_keys.extend(object_keys(level.get("envOverrides")));
+            cli_override_keys.extend(object_keys(level.get("cliOverrides")));
+        }
+
+        count_once(&mut report.config_override_keys, config_override_keys);
+        count_once(&mut report.env_override_keys, env_override_keys);
+        count_once(&mut report.cli_override_keys, cli_override_keys);
+    }
+
+    Ok(report)
+}
+
+fn object_entries(value: Option<&Value>) -> Vec<(&String, &Value)> {
+    value
+        .and_then(Value::as_object)
+        .map(|object| object.iter().collect())
+        .unwrap_or_default()
+}
+
+fn object_keys(value: Option<&Value>) -> Vec<String> {
+    object_entries(value)
+        .into_iter()
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+fn count_once(counts: &mut BTreeMap<String, usize>, mut keys: Vec<String>) {
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        *counts.entry(key).or_default() += 1;
+    }
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    statistics: UsageStatistics,
+) -> Result<Response<Body>, Infallible> {
+    let json = match (request.method(), request.uri().path()) {
+        (&Method::GET, USAGE_REPORT_PATH) => Some(
+            statistics
+                .report()
+                .and_then(|report| serde_json::to_string_pretty(&report).map_err(Error::from)),
+        ),
+        (&Method::GET, RECONCILE_STATES_PATH) => {
+            Some(serde_json::to_string_pretty(&statistics.reconcile_states()).map_err(Error::from))
+        }
+        _ => None,
+    };
+    let response = match json {
+        Some(Ok(json)) => Response::builder()
+            .header("Content-Type", "application/json")
+            .body(Body::from(json)),
+        Some(Err(err)) => Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::from(err.to_string())),
+        None => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty()),
+    };
+
+    // Building the response only fails for invalid headers which are static here.
+    Ok(response.unwrap())
+}
+
+/// Serves the [`UsageReport`] as JSON on `GET /usage` until the process exits.
+pub async fn serve_usage_report(
+    address: SocketAddr,
+    statistics: UsageStatistics,
+) -> Result<(), Error> {
+    let make_service = make_service_fn(move |_connection| {
+        let statistics = statistics.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle_request(request, statistics.clone())
+            }))
+        }
+    });
+
+    info!(
+        "Serving usage report on http://{0}{1} and the reconcile states on http://{0}{2}",
+        address, USAGE_REPORT_PATH, RECONCILE_STATES_PATH
+    );
+    Server::try_bind(&address)?.serve(make_service).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reconcile_state::{ClusterPhase, ReconcileFailure, ReconcileOutcome};
+    use crate::test_support;
+    use indoc::indoc;
+    use serde_json::json;
+
+    #[test]
+    fn test_record_same_name_in_different_namespaces() {
+        let cluster = |namespace: &str| -> ZookeeperCluster {
+            let mut cluster = test_support::cluster("simple", json!({}));
+            cluster.metadata.namespace = Some(namespace.to_string());
+            cluster
+        };
+        let statistics = UsageStatistics::default();
+
+        statistics.record(&cluster("team-a"));
+        statistics.record(&cluster("team-b"));
+        statistics.record(&cluster("team-a"));
+        assert_eq!(statistics.report().unwrap().clusters, 2);
+        assert_eq!(statistics.cluster_count(), 2);
+
+        let mut deleted = cluster("team-a");
+        deleted.metadata.deletion_timestamp = Some(
+            k8s_openapi::apimachinery::pkg::apis::meta::v1::Time(k8s_openapi::chrono::Utc::now()),
+        );
+        statistics.record(&deleted);
+        assert_eq!(statistics.report().unwrap().clusters, 1);
+        assert_eq!(statistics.cluster_count(), 1);
+    }
+
+    #[test]
+    fn test_record_reconcile_keeps_last_error() {
+        let statistics = UsageStatistics::default();
+        let key = ObjectRef::new("default", "simple");
+        let state = |outcome, last_error: Option<&str>| ClusterReconcileState {
+            last_reconcile: "2021-09-01T12:00:00+00:00".to_string(),
+            duration_millis: 100,
+            outcome,
+            requeue_after_seconds: None,
+            phase: ClusterPhase::Available,
+            last_error: last_error.map(|message| ReconcileFailure {
+                time: "2021-09-01T11:00:00+00:00".to_string(),
+                message: message.to_string(),
+            }),
+        };
+
+        statistics.record_reconcile(key.clone(), state(ReconcileOutcome::Failed, Some("boom")));
+        statistics.record_reconcile(key.clone(), state(ReconcileOutcome::Completed, None));
+
+        assert_eq!(
+            statistics.reconcile_states().get(&key),
+            Some(&state(ReconcileOutcome::Completed, Some("boom")))
+        );
+    }
+
+    #[test]
+    fn test_build_report() {
+        let specs: Vec<ZookeeperClusterSpec> = serde_yaml::from_str(indoc! {"
+            - version: 3.5.8
+              rebalanceClientsAfterScaleUp: true
+              servers:
+                configOverrides:
+                  zoo.cfg:
+                    tickTime: '3000'
+                roleGroups:
+                  default:
+                    selector:
+                      matchLabels:
+                        kubernetes.io/arch: stackable-linux
+                    replicas: 3
+                    configOverrides:
+                      zoo.cfg:
+                        tickTime: '4000'
+                  second:
+                    selector:
+                      matchLabels:
+                        kubernetes.io/arch: stackable-linux
+                    envOverrides:
+                      ZK_SERVER_HEAP: '2048'
+            - version: 3.4.14
+              servers:
+                roleGroups:
+                  default:
+                    selector:
+                      matchLabels:
+                        kubernetes.io/arch: stackable-linux
+                    replicas: 1
+                    configOverrides:
+                      zoo.cfg:
+                        tickTime: '3000'
+        "})
+        .unwrap();
+
+        let report = build_report(&specs).unwrap();
+
+        assert_eq!(report.clusters, 2);
+        assert_eq!(report.versions.get("3.5.8"), Some(&1));
+        assert_eq!(report.versions.get("3.4.14"), Some(&1));
+        assert_eq!(report.role_groups, 3);
+        assert_eq!(report.replicas, 4);
+        assert_eq!(
+            report.features.get("rebalanceClientsAfterScaleUp"),
+            Some(&1)
+        );
+        assert_eq!(
+            report.config_override_keys.get("zoo.cfg/tickTime"),
+            Some(&2)
+        );
+        assert_eq!(report.env_override_keys.get("ZK_SERVER_HEAP"), Some(&1));
+        assert!(report.cli_override_keys.is_empty());
+    }
+}
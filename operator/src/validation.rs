@@ -0,0 +1,214 @@
+//! Rules for the size of the ensemble, the role groups and the `zoo.cfg` overrides of a
+//! ZookeeperCluster.
+//!
+//! The rules only look at the spec, so they are shared by the reconciliation, which rejects
+//! clusters violating them with [`Error::ZookeeperClusterIsBad`](crate::error::Error), and by the
+//! offline checks in [`crate::lint`].
+use crate::config;
+use crate::PROPERTIES_FILE;
+
+use serde_json::Value;
+use stackable_zookeeper_crd::ZookeeperClusterSpec;
+use std::collections::BTreeMap;
+
+/// The maximum number of participants. Every write has to be acknowledged by a majority of them,
+/// so larger ensembles become slower instead of faster.
+pub const MAX_PARTICIPANTS: usize = 9;
+
+/// `zoo.cfg` properties which can not be overridden because the operator writes them itself.
+const FORBIDDEN_OVERRIDE_KEYS: [&str; 1] = ["dynamicConfigFile"];
+
+/// A rule of the spec which is violated.
+#[derive(Clone, Debug, Eq, PartialEq, thiserror::Error)]
+pub enum SpecViolation {
+    #[error("[{participants}] participants exceed the supported maximum of [{max}], set `peerType: observer` in further role groups to scale reads")]
+    TooManyParticipants { participants: usize, max: usize },
+
+    #[error("replicas: It can only be set with a single role group but there are [{role_groups}], set the replicas of the role groups instead")]
+    ReplicasWithSeveralRoleGroups { role_groups: usize },
+
+    #[error("{warning}, the ensembleSizePolicy `Reject` does not allow it")]
+    EnsembleSizeRejected { warning: String },
+
+    #[error("{path}: [{key}] is managed by the operator, remove it from the overrides")]
+    ManagedConfigOverride { path: String, key: String },
+
+    #[error("{path}: [{key}={value}] is not allowed: {reason}")]
+    DangerousConfigOverride {
+        path: String,
+        key: String,
+        value: String,
+        reason: &'static str,
+    },
+}
+
+/// Checks `spec` against the rules of this module and returns all violations.
+///
+/// # Arguments
+///
+/// - `spec` - The spec of the cluster.
+/// - `participants` - The number of servers which vote, `None` if it is not known (e.g. because
+///   role groups without `replicas` run on all matching nodes) and can not be checked.
+///
+pub fn validate(spec: &ZookeeperClusterSpec, participants: Option<usize>) -> Vec<SpecViolation> {
+    let mut violations = vec![];
+    if let Some(participants) = participants {
+        if participants > MAX_PARTICIPANTS {
+            violations.push(SpecViolation::TooManyParticipants {
+                participants,
+                max: MAX_PARTICIPANTS,
+            });
+        }
+    }
+
+    // the role groups and overrides are hash maps, they are sorted for stable messages
+    let servers = serde_json::to_value(&spec.servers).unwrap_or_default();
+    let role_group_count = sorted_entries(servers.get("roleGroups")).len();
+    if spec.replicas.is_some() && role_group_count != 1 {
+        violations.push(SpecViolation::ReplicasWithSeveralRoleGroups {
+            role_groups: role_group_count,
+        });
+    }
+    let role_groups = sorted_entries(servers.get("roleGroups"))
+        .into_iter()
+        .map(|(group, role_group)| (format!("servers.roleGroups.{}", group), role_group));
+    for (path, level) in Some(("servers".to_string(), &servers))
+        .into_iter()
+        .chain(role_groups)
+    {
+        let overrides = level
+            .get("configOverrides")
+            .and_then(|overrides| overrides.get(PROPERTIES_FILE));
+        for (key, value) in sorted_entries(overrides) {
+            let path = format!("{}.configOverrides", path);
+            let value = value.as_str().unwrap_or_default();
+            if key.starts_with("server.") || FORBIDDEN_OVERRIDE_KEYS.contains(&key.as_str()) {
+                violations.push(SpecViolation::ManagedConfigOverride {
+                    path,
+                    key: key.clone(),
+                });
+            } else if let Some(reason) = config::find_danger(key, value) {
+                violations.push(SpecViolation::DangerousConfigOverride {
+                    path,
+                    key: key.clone(),
+                    value: value.to_string(),
+                    reason,
+                });
+            }
+        }
+    }
+    violations
+}
+
+fn sorted_entries(value: Option<&Value>) -> BTreeMap<&String, &Value> {
+    value
+        .and_then(Value::as_object)
+        .map(|object| object.iter().collect())
+        .unwrap_or_default()
+}
+
+/// Returns why an ensemble of `participants` is not recommended, i.e. if it does not tolerate
+/// any failure or is even and tolerates as few failures as the next smaller one.
+pub fn ensemble_size_warning(participants: usize) -> Option<String> {
+    match participants {
+        1 => Some("A single participant does not tolerate any failure, run at least 3".to_string()),
+        participants if participants > 0 && participants % 2 == 0 => Some(format!(
+            "[{}] participants tolerate as few failures as [{}], use an odd number",
+            participants,
+            participants - 1
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use rstest::rstest;
+
+    fn spec(yaml: &str) -> ZookeeperClusterSpec {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_validate() {
+        let spec = spec(indoc! {"
+            version: 3.5.8
+            servers:
+              configOverrides:
+                zoo.cfg:
+                  server.4: zk-4:2888:3888
+              roleGroups:
+                default:
+                  selector:
+                    matchLabels:
+                      kubernetes.io/os: linux
+                  configOverrides:
+                    zoo.cfg:
+                      forceSync: 'no'
+                      maxClientCnxns: '100'
+                      dynamicConfigFile: /tmp/zoo.cfg.dynamic
+        "});
+
+        assert_eq!(validate(&spec, None).len(), 3);
+        assert_eq!(
+            validate(&spec, Some(11)),
+            vec![
+                SpecViolation::TooManyParticipants {
+                    participants: 11,
+                    max: MAX_PARTICIPANTS
+                },
+                SpecViolation::ManagedConfigOverride {
+                    path: "servers.configOverrides".to_string(),
+                    key: "server.4".to_string()
+                },
+                SpecViolation::ManagedConfigOverride {
+                    path: "servers.roleGroups.default.configOverrides".to_string(),
+                    key: "dynamicConfigFile".to_string()
+                },
+                SpecViolation::DangerousConfigOverride {
+                    path: "servers.roleGroups.default.configOverrides".to_string(),
+                    key: "forceSync".to_string(),
+                    value: "no".to_string(),
+                    reason: "transactions would not be synced to disk before they are acknowledged"
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_replicas() {
+        let spec = |role_groups: &str| {
+            spec(&format!(
+                indoc! {"
+                    version: 3.5.8
+                    replicas: 5
+                    servers:
+                      roleGroups: {}
+                "},
+                role_groups
+            ))
+        };
+
+        assert_eq!(
+            validate(&spec("{default: {selector: {}}}"), Some(5)),
+            vec![]
+        );
+        assert_eq!(
+            validate(&spec("{a: {selector: {}}, b: {selector: {}}}"), Some(5)),
+            vec![SpecViolation::ReplicasWithSeveralRoleGroups { role_groups: 2 }]
+        );
+    }
+
+    #[rstest]
+    #[case(0, false)]
+    #[case(1, true)]
+    #[case(2, true)]
+    #[case(3, false)]
+    #[case(4, true)]
+    #[case(5, false)]
+    fn test_ensemble_size_warning(#[case] participants: usize, #[case] expected: bool) {
+        assert_eq!(ensemble_size_warning(participants).is_some(), expected);
+    }
+}
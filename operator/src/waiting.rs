@@ -0,0 +1,149 @@
+//! Requeueing while the reconciliation waits for an external condition.
+//!
+//! Instead of polling with a fixed interval, every wait reason has its own progressive schedule:
+//! the first checks happen quickly, later ones less often up to a cap. The current wait is
+//! recorded in `status.waiting` and removed once a reconciliation no longer waits.
+use k8s_openapi::chrono::{DateTime, Utc};
+use stackable_operator::reconcile::ReconcileFunctionAction;
+use stackable_zookeeper_crd::WaitingStatus;
+use std::time::Duration;
+use strum_macros::Display;
+
+#[derive(Clone, Copy, Debug, Display, Eq, PartialEq)]
+pub enum WaitReason {
+    /// The canary of an upgrade is not verified yet.
+    CanaryVerification,
+    /// cert-manager has not issued the certificate yet.
+    CertificateIssuance,
+    /// A PodDisruptionBudget does not allow taking down the next pod.
+    DisruptionBudget,
+    /// Disruptive operations are paused until modifications are acknowledged.
+    DisruptionsPaused,
+    /// The data of the leader is not verified yet before an upgrade.
+    IntegrityCheck,
+    /// Not all servers are healthy yet after a scale step.
+    ServersHealthy,
+    /// An upgraded server does not serve requests yet.
+    UpgradedServerServing,
+}
+
+impl WaitReason {
+    /// The requeue intervals in seconds, the last one is repeated.
+    fn intervals(self) -> &'static [u64] {
+        match self {
+            WaitReason::CanaryVerification | WaitReason::IntegrityCheck => &[5, 15, 30],
+            WaitReason::CertificateIssuance => &[5, 15, 60, 120],
+            WaitReason::DisruptionBudget => &[15, 30, 60, 120],
+            WaitReason::DisruptionsPaused => &[30, 60, 300],
+            WaitReason::ServersHealthy | WaitReason::UpgradedServerServing => &[5, 15, 60],
+        }
+    }
+}
+
+/// Returns the status of a wait for `reason`. The attempts are counted up while the previous
+/// wait had the same reason and start from 1 otherwise.
+pub fn next_waiting_status(
+    previous: Option<&WaitingStatus>,
+    reason: WaitReason,
+    message: String,
+    now: &DateTime<Utc>,
+) -> WaitingStatus {
+    match previous.filter(|previous| previous.reason == reason.to_string()) {
+        Some(previous) => WaitingStatus {
+            message,
+            attempts: previous.attempts.saturating_add(1),
+            ..previous.clone()
+        },
+        None => WaitingStatus {
+            reason: reason.to_string(),
+            message,
+            since: now.to_rfc3339(),
+            attempts: 1,
+        },
+    }
+}
+
+/// Returns the interval to requeue after for the given attempt (starting from 1) of a wait.
+pub fn requeue_interval(reason: WaitReason, attempts: u32) -> Duration {
+    let intervals = reason.intervals();
+    let index = (attempts.max(1) as usize - 1).min(intervals.len() - 1);
+    Duration::from_secs(intervals[index])
+}
+
+/// Records a wait for `reason` in `waiting` (based on the `previous` wait of the last
+/// reconciliation) and returns the action requeueing the reconciliation accordingly.
+pub fn wait(
+    waiting: &mut Option<WaitingStatus>,
+    previous: Option<&WaitingStatus>,
+    reason: WaitReason,
+    message: String,
+) -> ReconcileFunctionAction {
+    let status = next_waiting_status(previous, reason, message, &Utc::now());
+    let interval = requeue_interval(reason, status.attempts);
+    *waiting = Some(status);
+    ReconcileFunctionAction::Requeue(interval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(WaitReason::CanaryVerification, 5, 30)]
+    #[case(WaitReason::CertificateIssuance, 1, 5)]
+    #[case(WaitReason::CertificateIssuance, 2, 15)]
+    #[case(WaitReason::CertificateIssuance, 4, 120)]
+    #[case(WaitReason::CertificateIssuance, 100, 120)]
+    #[case(WaitReason::DisruptionBudget, 0, 15)]
+    #[case(WaitReason::IntegrityCheck, 2, 15)]
+    #[case(WaitReason::ServersHealthy, 3, 60)]
+    fn test_requeue_interval(
+        #[case] reason: WaitReason,
+        #[case] attempts: u32,
+        #[case] expected_seconds: u64,
+    ) {
+        assert_eq!(
+            requeue_interval(reason, attempts),
+            Duration::from_secs(expected_seconds)
+        );
+    }
+
+    #[test]
+    fn test_next_waiting_status() {
+        let started = DateTime::parse_from_rfc3339("2021-09-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let later = DateTime::parse_from_rfc3339("2021-09-01T12:05:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let first = next_waiting_status(
+            None,
+            WaitReason::DisruptionBudget,
+            "first".to_string(),
+            &started,
+        );
+        assert_eq!(first.reason, "DisruptionBudget");
+        assert_eq!(first.attempts, 1);
+
+        let second = next_waiting_status(
+            Some(&first),
+            WaitReason::DisruptionBudget,
+            "second".to_string(),
+            &later,
+        );
+        assert_eq!(second.attempts, 2);
+        assert_eq!(second.since, started.to_rfc3339());
+        assert_eq!(second.message, "second");
+
+        let other = next_waiting_status(
+            Some(&second),
+            WaitReason::ServersHealthy,
+            "other".to_string(),
+            &later,
+        );
+        assert_eq!(other.attempts, 1);
+        assert_eq!(other.since, later.to_rfc3339());
+    }
+}
@@ -0,0 +1,77 @@
+//! Restricting the operator to some namespaces.
+//!
+//! By default the controllers watch all namespaces. In multi-tenant clusters every tenant can
+//! instead run its own operator which only watches (and only needs permissions in) the
+//! namespaces of the tenant. The watches of the controllers are either cluster wide or limited
+//! to a single namespace, so a controller is started per watched namespace.
+use kube::{Api, Resource};
+use stackable_operator::client::Client;
+use std::collections::BTreeSet;
+
+/// The namespaces the operator watches.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WatchNamespace {
+    All,
+    Namespaces(BTreeSet<String>),
+}
+
+impl WatchNamespace {
+    /// Parses a comma separated list of namespaces, e.g. `team-a,team-b`. An empty list means
+    /// all namespaces.
+    pub fn parse(namespaces: &str) -> WatchNamespace {
+        let namespaces = namespaces
+            .split(',')
+            .map(str::trim)
+            .filter(|namespace| !namespace.is_empty())
+            .map(str::to_string)
+            .collect::<BTreeSet<_>>();
+        if namespaces.is_empty() {
+            WatchNamespace::All
+        } else {
+            WatchNamespace::Namespaces(namespaces)
+        }
+    }
+
+    /// Returns the scopes a controller has to be started for, where `None` stands for all
+    /// namespaces.
+    pub fn scopes(&self) -> Vec<Option<String>> {
+        match self {
+            WatchNamespace::All => vec![None],
+            WatchNamespace::Namespaces(namespaces) => {
+                namespaces.iter().cloned().map(Some).collect()
+            }
+        }
+    }
+}
+
+/// Returns the API of the given kind in `namespace` or, if `None`, in all namespaces.
+pub fn scoped_api<K>(client: &Client, namespace: Option<&str>) -> Api<K>
+where
+    K: Resource<DynamicType = ()>,
+{
+    match namespace {
+        Some(namespace) => client.get_namespaced_api(namespace),
+        None => client.get_all_api(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("", vec![None])]
+    #[case(" , ", vec![None])]
+    #[case("team-a", vec![Some("team-a")])]
+    #[case("team-b, team-a,team-b", vec![Some("team-a"), Some("team-b")])]
+    fn test_scopes(#[case] namespaces: &str, #[case] expected: Vec<Option<&str>>) {
+        assert_eq!(
+            WatchNamespace::parse(namespaces).scopes(),
+            expected
+                .into_iter()
+                .map(|namespace| namespace.map(str::to_string))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! Client abstraction for operations on a ZooKeeper ensemble itself (as opposed to the
+//! Kubernetes objects it runs in), like managing znodes or reading the ensemble configuration.
+//!
+//! Controllers get a [`ZookeeperConnector`] from their strategy and open a short lived session per
+//! reconciliation. The default implementation wraps the `zookeeper-async` crate, tests can swap in
+//! their own implementation.
+use crate::error::Error;
+
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::instrument;
+use zookeeper_async::{Acl, CreateMode, WatchedEvent, ZkError, ZooKeeper, ZooKeeperExt};
+
+/// The znode containing the dynamic configuration of the ensemble (ZooKeeper 3.5+).
+pub const CONFIG_ZNODE: &str = "/zookeeper/config";
+/// The znode below which ZooKeeper stores quotas, mirroring the paths they apply to.
+pub const QUOTA_ZNODE: &str = "/zookeeper/quota";
+/// The znode holding the limits of a quota, e.g. `count=100,bytes=-1`.
+const QUOTA_LIMITS_ZNODE: &str = "zookeeper_limits";
+/// The znode holding the current usage of a quota, which is maintained by the server.
+const QUOTA_STATS_ZNODE: &str = "zookeeper_stats";
+
+const SESSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The ensemble configuration as stored in [`CONFIG_ZNODE`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EnsembleConfig {
+    /// Server specifications by `myid`, e.g. `host:2888:3888:participant;0.0.0.0:2181`.
+    pub servers: BTreeMap<usize, String>,
+    /// The (hexadecimal) version of the configuration.
+    pub version: Option<String>,
+}
+
+impl EnsembleConfig {
+    /// Parses the content of [`CONFIG_ZNODE`], lines which can not be parsed are ignored.
+    pub fn parse(config: &str) -> EnsembleConfig {
+        let mut ensemble_config = EnsembleConfig::default();
+        for (key, value) in config.lines().filter_map(|line| line.split_once('=')) {
+            if key == "version" {
+                ensemble_config.version = Some(value.trim().to_string());
+            } else if let Some(id) = key
+                .strip_prefix("server.")
+                .and_then(|id| id.parse::<usize>().ok())
+            {
+                ensemble_config.servers.insert(id, value.trim().to_string());
+            }
+        }
+        ensemble_config
+    }
+}
+
+/// Returns the znode holding the quota of the znode at `path`.
+pub fn quota_path(path: &str) -> String {
+    format!("{}{}", QUOTA_ZNODE, path)
+}
+
+/// A session with a ZooKeeper ensemble.
+#[async_trait]
+pub trait ZookeeperClient: Send + Sync {
+    /// Creates a persistent, world accessible znode with the given data.
+    async fn create(&self, path: &str, data: Vec<u8>) -> Result<(), Error>;
+
+    /// Creates a persistent znode with the given data and ACLs.
+    async fn create_with_acls(
+        &self,
+        path: &str,
+        data: Vec<u8>,
+        acls: Vec<Acl>,
+    ) -> Result<(), Error>;
+
+    /// Reads the ACLs of the znode at `path`.
+    async fn get_acls(&self, path: &str) -> Result<Vec<Acl>, Error>;
+
+    /// Replaces the ACLs of the znode at `path`.
+    async fn set_acls(&self, path: &str, acls: Vec<Acl>) -> Result<(), Error>;
+
+    /// Creates the znode at `path` including all missing parents, existing znodes are left alone.
+    async fn ensure_path(&self, path: &str) -> Result<(), Error>;
+
+    /// Deletes the znode at `path` including all of its children, a missing znode is not an error.
+    async fn delete_recursive(&self, path: &str) -> Result<(), Error>;
+
+    /// Checks whether the znode at `path` exists.
+    async fn exists(&self, path: &str) -> Result<bool, Error>;
+
+    /// Checks whether the znode at `path` is ephemeral, a missing znode is not ephemeral.
+    async fn is_ephemeral(&self, path: &str) -> Result<bool, Error>;
+
+    /// Lists the names of the children of the znode at `path`.
+    async fn get_children(&self, path: &str) -> Result<Vec<String>, Error>;
+
+    /// Reads the data of the znode at `path`.
+    async fn get_data(&self, path: &str) -> Result<Vec<u8>, Error>;
+
+    /// Replaces the data of the existing znode at `path`.
+    async fn set_data(&self, path: &str, data: Vec<u8>) -> Result<(), Error>;
+
+    /// Reads the current ensemble configuration.
+    async fn get_config(&self) -> Result<EnsembleConfig, Error>;
+
+    /// Authenticates the session, e.g. with the `digest` scheme and `user:password`.
+    async fn add_auth(&self, scheme: &str, auth: Vec<u8>) -> Result<(), Error>;
+
+    /// Closes the session.
+    async fn close(self: Box<Self>) -> Result<(), Error>;
+}
+
+/// Sets the quota of the znode at `path` to `limits` (e.g. `count=100,bytes=-1`), creating the
+/// quota znodes if necessary.
+///
+/// The usage in `zookeeper_stats` is only initialized when the quota is created, afterwards the
+/// server keeps it up to date.
+pub async fn set_quota(zk: &dyn ZookeeperClient, path: &str, limits: &str) -> Result<(), Error> {
+    let quota_path = quota_path(path);
+    let limits_path = format!("{}/{}", quota_path, QUOTA_LIMITS_ZNODE);
+    let stats_path = format!("{}/{}", quota_path, QUOTA_STATS_ZNODE);
+
+    zk.ensure_path(&quota_path).await?;
+    if zk.exists(&limits_path).await? {
+        zk.set_data(&limits_path, limits.as_bytes().to_vec())
+            .await?;
+    } else {
+        zk.create(&limits_path, limits.as_bytes().to_vec()).await?;
+    }
+    if !zk.exists(&stats_path).await? {
+        zk.create(&stats_path, b"count=0,bytes=0".to_vec()).await?;
+    }
+    Ok(())
+}
+
+/// Removes the quota of the znode at `path`, a missing quota is not an error.
+pub async fn delete_quota(zk: &dyn ZookeeperClient, path: &str) -> Result<(), Error> {
+    zk.delete_recursive(&quota_path(path)).await
+}
+
+/// Opens sessions with ZooKeeper ensembles.
+#[async_trait]
+pub trait ZookeeperConnector: Send + Sync {
+    /// Connects to the ensemble described by `connection_string` (`host:port[,host:port...]`).
+    async fn connect(&self, connection_string: &str) -> Result<Box<dyn ZookeeperClient>, Error>;
+}
+
+/// Opens a session with the ensemble described by `connection_string`, authenticated as super
+/// user if credentials (user name and password) are given.
+pub async fn connect_with_credentials(
+    zk_connector: &dyn ZookeeperConnector,
+    connection_string: &str,
+    credentials: Option<&(String, String)>,
+) -> Result<Box<dyn ZookeeperClient>, Error> {
+    let zk = zk_connector.connect(connection_string).await?;
+
+    if let Some((username, password)) = credentials {
+        let auth = format!("{}:{}", username, password).into_bytes();
+        if let Err(err) = zk.add_auth("digest", auth).await {
+            zk.close().await?;
+            return Err(err);
+        }
+    }
+    Ok(zk)
+}
+
+/// Returns the default [`ZookeeperConnector`].
+pub fn default_connector() -> Arc<dyn ZookeeperConnector> {
+    Arc::new(AsyncZookeeperConnector {})
+}
+
+struct AsyncZookeeperConnector {}
+
+#[async_trait]
+impl ZookeeperConnector for AsyncZookeeperConnector {
+    #[instrument(skip(self))]
+    async fn connect(&self, connection_string: &str) -> Result<Box<dyn ZookeeperClient>, Error> {
+        let zk =
+            ZooKeeper::connect(connection_string, SESSION_TIMEOUT, |_: WatchedEvent| {}).await?;
+        Ok(Box::new(AsyncZookeeperClient { zk }))
+    }
+}
+
+struct AsyncZookeeperClient {
+    zk: ZooKeeper,
+}
+
+#[async_trait]
+impl ZookeeperClient for AsyncZookeeperClient {
+    #[instrument(skip(self, data))]
+    async fn create(&self, path: &str, data: Vec<u8>) -> Result<(), Error> {
+        self.create_with_acls(path, data, Acl::open_unsafe().clone())
+            .await
+    }
+
+    #[instrument(skip(self, data, acls))]
+    async fn create_with_acls(
+        &self,
+        path: &str,
+        data: Vec<u8>,
+        acls: Vec<Acl>,
+    ) -> Result<(), Error> {
+        self.zk
+            .create(path, data, acls, CreateMode::Persistent)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn get_acls(&self, path: &str) -> Result<Vec<Acl>, Error> {
+        let (acls, _stat) = self.zk.get_acl(path).await?;
+        Ok(acls)
+    }
+
+    #[instrument(skip(self, acls))]
+    async fn set_acls(&self, path: &str, acls: Vec<Acl>) -> Result<(), Error> {
+        self.zk.set_acl(path, acls, None).await?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn ensure_path(&self, path: &str) -> Result<(), Error> {
+        Ok(self.zk.ensure_path(path).await?)
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_recursive(&self, path: &str) -> Result<(), Error> {
+        match self.zk.delete_recursive(path).await {
+            Ok(()) | Err(ZkError::NoNode) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn exists(&self, path: &str) -> Result<bool, Error> {
+        Ok(self.zk.exists(path, false).await?.is_some())
+    }
+
+    #[instrument(skip(self))]
+    async fn is_ephemeral(&self, path: &str) -> Result<bool, Error> {
+        Ok(self
+            .zk
+            .exists(path, false)
+            .await?
+            .map_or(false, |stat| stat.ephemeral_owner != 0))
+    }
+
+    #[instrument(skip(self))]
+    async fn get_children(&self, path: &str) -> Result<Vec<String>, Error> {
+        Ok(self.zk.get_children(path, false).await?)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_data(&self, path: &str) -> Result<Vec<u8>, Error> {
+        let (data, _stat) = self.zk.get_data(path, false).await?;
+        Ok(data)
+    }
+
+    #[instrument(skip(self, data))]
+    async fn set_data(&self, path: &str, data: Vec<u8>) -> Result<(), Error> {
+        self.zk.set_data(path, data, None).await?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn get_config(&self) -> Result<EnsembleConfig, Error> {
+        let (data, _stat) = self.zk.get_data(CONFIG_ZNODE, false).await?;
+        Ok(EnsembleConfig::parse(&String::from_utf8_lossy(&data)))
+    }
+
+    #[instrument(skip(self, auth))]
+    async fn add_auth(&self, scheme: &str, auth: Vec<u8>) -> Result<(), Error> {
+        Ok(self.zk.add_auth(scheme, auth).await?)
+    }
+
+    async fn close(self: Box<Self>) -> Result<(), Error> {
+        Ok(self.zk.close().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_parse_ensemble_config() {
+        let config = EnsembleConfig::parse(indoc! {"
+            server.1=node-1:2888:3888:participant;0.0.0.0:2181
+            server.10=node-10:2888:3888:observer;0.0.0.0:2181
+            server.2=node-2:2888:3888:participant;0.0.0.0:2181
+            server.x=invalid
+            version=100000000
+        "});
+
+        assert_eq!(
+            config.servers.keys().copied().collect::<Vec<_>>(),
+            vec![1, 2, 10]
+        );
+        assert_eq!(
+            config.servers.get(&10),
+            Some(&"node-10:2888:3888:observer;0.0.0.0:2181".to_string())
+        );
+        assert_eq!(config.version, Some("100000000".to_string()));
+    }
+
+    #[test]
+    fn test_quota_path() {
+        assert_eq!(quota_path("/znode-1234"), "/zookeeper/quota/znode-1234");
+    }
+
+    #[test]
+    fn test_parse_empty_ensemble_config() {
+        assert_eq!(EnsembleConfig::parse(""), EnsembleConfig::default());
+    }
+}
@@ -0,0 +1,917 @@
+//! Controller for `ZookeeperZnode` resources.
+//!
+//! For every ZookeeperZnode a znode with a unique path is created in the referenced
+//! ZookeeperCluster. The connection string including the znode as chroot is published in a
+//! ConfigMap with the same name as the ZookeeperZnode, which applications can mount or read their
+//! environment from. If a quota is requested, it is kept in sync with the spec. When the
+//! ZookeeperZnode is deleted, the znode and all of its children (and its quota) are deleted as
+//! well.
+//!
+//! The ACLs of the znode are set on creation and kept in sync with the spec. If the cluster
+//! requires authentication, the controller connects as super user, which always keeps all
+//! permissions on the znode so it can be managed and deleted regardless of the requested ACLs.
+//!
+//! The credentials of the users of the znode are generated into Secrets owned by the
+//! ZookeeperZnode. The passwords are kept as long as the Secrets exist, the `digest` ACLs of the
+//! users are derived from them and added to the requested ACLs.
+//!
+//! If `seed` references a ConfigMap, the znodes in it are created below the znode with the same
+//! ACLs (and missing parents without data), see [`seed`]. Existing znodes are only updated with
+//! `overwrite`, changes of the ConfigMap are applied by the next reconciliation.
+use crate::error::Error;
+use crate::watch_namespace::scoped_api;
+use crate::zk_client::{self, ZookeeperClient, ZookeeperConnector};
+use crate::{apply, authentication};
+
+use async_trait::async_trait;
+use k8s_openapi::api::core::v1::{ConfigMap, Secret};
+use kube::api::{DeleteParams, ListParams};
+use kube::Api;
+use serde_json::json;
+use stackable_operator::client::Client;
+use stackable_operator::configmap;
+use stackable_operator::controller::Controller;
+use stackable_operator::controller::{ControllerStrategy, ReconciliationState};
+use stackable_operator::error::OperatorResult;
+use stackable_operator::labels::{APP_MANAGED_BY_LABEL, APP_NAME_LABEL};
+use stackable_operator::reconcile::{
+    ReconcileFunctionAction, ReconcileResult, ReconciliationContext,
+};
+use stackable_zookeeper_crd::util::{get_zk_connection_info, ZookeeperReference};
+use stackable_zookeeper_crd::znode::{
+    build_znode_path, user_secret_name, ZnodeAcl, ZnodeAclScheme, ZookeeperZnode, PERMISSION_ALL,
+    ZNODE_LABEL, ZOOKEEPER_CHROOT_DISCOVERY_KEY, ZOOKEEPER_DISCOVERY_KEY,
+};
+use stackable_zookeeper_crd::{ZookeeperCluster, APP_NAME, MANAGED_BY};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use zookeeper_async::{Acl, Permission};
+
+const FINALIZER_NAME: &str = "zookeeper.stackable.tech/znode";
+
+type ZnodeReconcileResult = ReconcileResult<Error>;
+
+struct ZnodeState {
+    context: ReconciliationContext<ZookeeperZnode>,
+    zk_connector: Arc<dyn ZookeeperConnector>,
+    /// The ACLs of the users of the znode, see `reconcile_user_secrets`.
+    user_acls: Vec<ZnodeAcl>,
+}
+
+impl ZnodeState {
+    fn cluster_reference(&self, chroot: Option<String>) -> ZookeeperReference {
+        let resource = &self.context.resource;
+        ZookeeperReference {
+            namespace: resource
+                .cluster_namespace()
+                .unwrap_or_else(|| self.context.namespace()),
+            name: resource.spec.cluster_ref.name.clone(),
+            chroot,
+        }
+    }
+
+    /// Returns the ACLs requested in the spec, including the ones of the users.
+    fn requested_acls(&self) -> Vec<ZnodeAcl> {
+        let mut acls = self.context.resource.spec.acls.clone();
+        acls.extend(self.user_acls.iter().cloned());
+        acls
+    }
+
+    fn znode_path(&self) -> Option<String> {
+        self.context
+            .resource
+            .status
+            .as_ref()
+            .and_then(|status| status.znode_path.clone())
+    }
+
+    /// Reads the super user credentials (user name and password) of the referenced cluster if it
+    /// requires authentication.
+    async fn super_user_credentials(
+        &self,
+        reference: &ZookeeperReference,
+    ) -> Result<Option<(String, String)>, Error> {
+        let clusters_api: Api<ZookeeperCluster> =
+            self.context.client.get_namespaced_api(&reference.namespace);
+        let cluster = clusters_api.get(&reference.name).await?;
+        if cluster.spec.authentication.is_none() {
+            return Ok(None);
+        }
+        let credentials = authentication::read_super_user_credentials(
+            &self.context.client,
+            &reference.namespace,
+            &reference.name,
+        )
+        .await?;
+        Ok(Some(credentials))
+    }
+
+    /// Opens a session with the referenced cluster, authenticated as super user if credentials
+    /// are given.
+    async fn connect(
+        &self,
+        reference: &ZookeeperReference,
+        credentials: Option<&(String, String)>,
+    ) -> Result<Box<dyn ZookeeperClient>, Error> {
+        let connection_info = get_zk_connection_info(&self.context.client, reference).await?;
+        zk_client::connect_with_credentials(
+            self.zk_connector.as_ref(),
+            &connection_info.connection_string,
+            credentials,
+        )
+        .await
+    }
+
+    /// Assigns the unique znode path once and records it in the status.
+    async fn assign_znode_path(&mut self) -> ZnodeReconcileResult {
+        if self.znode_path().is_some() {
+            return Ok(ReconcileFunctionAction::Continue);
+        }
+
+        let uid = self.context.resource.metadata.uid.clone().ok_or_else(|| {
+            Error::ReconcileError("ZookeeperZnode has no uid, this should not happen".to_string())
+        })?;
+        let znode_path = build_znode_path(&uid)?;
+
+        self.context.resource = self
+            .context
+            .client
+            .merge_patch_status(&self.context.resource, &json!({ "znodePath": znode_path }))
+            .await?;
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Creates a Secret with generated credentials for every user in the spec which does not have
+    /// one yet and deletes the Secrets of users which were removed. The ACLs of the users are
+    /// derived from the credentials in their Secrets. Secrets which are not owned by the znode or
+    /// belong to another user are neither used nor deleted, see
+    /// [`authentication::is_user_secret`].
+    async fn reconcile_user_secrets(&mut self) -> ZnodeReconcileResult {
+        let secrets_api: Api<Secret> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+        let znode = &self.context.resource;
+        let znode_name = self.context.name();
+
+        let mut user_acls = vec![];
+        let mut secret_names = vec![];
+        for user in &znode.spec.users {
+            user.validate()?;
+            let secret_name = user_secret_name(&znode_name, &user.name);
+            let secret = match apply::get_existing(&secrets_api, &secret_name).await? {
+                Some(secret) if authentication::is_user_secret(&secret, znode, &user.name) => {
+                    secret
+                }
+                Some(_) => {
+                    return Err(Error::UserSecretConflict {
+                        secret: secret_name,
+                        user: user.name.clone(),
+                    })
+                }
+                None => {
+                    // earlier versions named the Secrets `<znode name>-<user name>`, the
+                    // credentials are kept so the clients do not have to be reconfigured
+                    let legacy_name = format!("{}-{}", znode_name, user.name);
+                    let password = match apply::get_existing(&secrets_api, &legacy_name)
+                        .await?
+                        .filter(|secret| authentication::is_user_secret(secret, znode, &user.name))
+                    {
+                        Some(legacy) => authentication::read_credentials(&legacy)?.1,
+                        None => authentication::generate_password(),
+                    };
+                    info!(
+                        "ZookeeperZnode {}: Creating the Secret [{}] with the credentials of user [{}]",
+                        self.context.log_name(),
+                        secret_name,
+                        user.name
+                    );
+                    let secret = authentication::build_user_secret(znode, user, password)?;
+                    self.context.client.create(&secret).await?
+                }
+            };
+            let (username, password) = authentication::read_credentials(&secret)?;
+            user_acls.push(user.acl(&authentication::build_super_digest(&username, &password)));
+            secret_names.push(secret_name);
+        }
+
+        let existing = secrets_api
+            .list(&ListParams::default().labels(&format!("{}={}", ZNODE_LABEL, znode_name)))
+            .await?;
+        let uid = znode.metadata.uid.clone().unwrap_or_default();
+        for secret in existing.items {
+            let name = secret.metadata.name.clone().unwrap_or_default();
+            let owned = secret
+                .metadata
+                .owner_references
+                .iter()
+                .any(|owner| owner.uid == uid);
+            if owned && !secret_names.contains(&name) {
+                info!(
+                    "ZookeeperZnode {}: Deleting the Secret [{}] of a removed user",
+                    self.context.log_name(),
+                    name
+                );
+                match secrets_api.delete(&name, &DeleteParams::default()).await {
+                    Ok(_) => {}
+                    Err(kube::Error::Api(response)) if response.code == 404 => {}
+                    Err(err) => return Err(err.into()),
+                }
+            }
+        }
+
+        self.user_acls = user_acls;
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Creates the znode with the requested ACLs if it does not exist yet.
+    async fn create_znode(&self) -> ZnodeReconcileResult {
+        let znode_path = match self.znode_path() {
+            Some(znode_path) => znode_path,
+            None => return Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10))),
+        };
+
+        let reference = self.cluster_reference(None);
+        let credentials = self.super_user_credentials(&reference).await?;
+        let acls = build_acls(&self.requested_acls(), credentials.as_ref())?;
+
+        let zk = self.connect(&reference, credentials.as_ref()).await?;
+        let result: Result<(), Error> = async {
+            if !zk.exists(&znode_path).await? {
+                zk.create_with_acls(&znode_path, vec![], acls).await?;
+            }
+            Ok(())
+        }
+        .await;
+        zk.close().await?;
+        result?;
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Sets the ACLs of the znode if they differ from the requested ones.
+    async fn sync_acls(&self) -> ZnodeReconcileResult {
+        let znode_path = match self.znode_path() {
+            Some(znode_path) => znode_path,
+            None => return Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10))),
+        };
+
+        let reference = self.cluster_reference(None);
+        let credentials = self.super_user_credentials(&reference).await?;
+        let acls = build_acls(&self.requested_acls(), credentials.as_ref())?;
+
+        let zk = self.connect(&reference, credentials.as_ref()).await?;
+        let result: Result<(), Error> = async {
+            if !same_acls(&zk.get_acls(&znode_path).await?, &acls) {
+                info!(
+                    "ZookeeperZnode {}: Updating the ACLs of znode [{}]",
+                    self.context.log_name(),
+                    znode_path
+                );
+                zk.set_acls(&znode_path, acls).await?;
+            }
+            Ok(())
+        }
+        .await;
+        zk.close().await?;
+        result?;
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Sets the quota of the znode as requested in the spec, or removes it if none is requested
+    /// (anymore).
+    async fn sync_quota(&self) -> ZnodeReconcileResult {
+        let znode_path = match self.znode_path() {
+            Some(znode_path) => znode_path,
+            None => return Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10))),
+        };
+
+        let reference = self.cluster_reference(None);
+        let credentials = self.super_user_credentials(&reference).await?;
+        let zk = self.connect(&reference, credentials.as_ref()).await?;
+        let result = match &self.context.resource.spec.quota {
+            Some(quota) => zk_client::set_quota(zk.as_ref(), &znode_path, &quota.to_limits()).await,
+            None => zk_client::delete_quota(zk.as_ref(), &znode_path).await,
+        };
+        zk.close().await?;
+        result?;
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Creates the znodes of the seed ConfigMap below the znode, see [`seed`].
+    async fn seed_znodes(&self) -> ZnodeReconcileResult {
+        let znode_path = match self.znode_path() {
+            Some(znode_path) => znode_path,
+            None => return Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10))),
+        };
+        let znode_seed = match &self.context.resource.spec.seed {
+            Some(znode_seed) => znode_seed,
+            None => return Ok(ReconcileFunctionAction::Continue),
+        };
+
+        let config_maps_api: Api<ConfigMap> = self
+            .context
+            .client
+            .get_namespaced_api(&self.context.namespace());
+        let config_map = match config_maps_api.get(&znode_seed.config_map_name).await {
+            Ok(config_map) => config_map,
+            Err(kube::Error::Api(response)) if response.code == 404 => {
+                warn!(
+                    "ZookeeperZnode {}: The seed ConfigMap [{}] does not exist, retrying",
+                    self.context.log_name(),
+                    znode_seed.config_map_name
+                );
+                return Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10)));
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut entries = BTreeMap::new();
+        let values = config_map
+            .data
+            .into_iter()
+            .map(|(key, value)| (key, value.into_bytes()))
+            .chain(
+                config_map
+                    .binary_data
+                    .into_iter()
+                    .map(|(key, value)| (key, value.0)),
+            );
+        for (key, value) in values {
+            entries.insert(znode_seed.seed_path(&znode_path, &key)?, value);
+        }
+
+        let reference = self.cluster_reference(None);
+        let credentials = self.super_user_credentials(&reference).await?;
+        let acls = build_acls(&self.requested_acls(), credentials.as_ref())?;
+
+        let zk = self.connect(&reference, credentials.as_ref()).await?;
+        let result = seed(
+            zk.as_ref(),
+            &znode_path,
+            &entries,
+            &acls,
+            znode_seed.overwrite,
+        )
+        .await;
+        zk.close().await?;
+        for path in result? {
+            info!(
+                "ZookeeperZnode {}: Seeded znode [{}] from the ConfigMap [{}]",
+                self.context.log_name(),
+                path,
+                znode_seed.config_map_name
+            );
+        }
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Publishes the connection string including the znode as chroot in a ConfigMap.
+    async fn publish_discovery_config_map(&self) -> ZnodeReconcileResult {
+        let znode_path = match self.znode_path() {
+            Some(znode_path) => znode_path,
+            None => return Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10))),
+        };
+
+        let connection_info = get_zk_connection_info(
+            &self.context.client,
+            &self.cluster_reference(Some(znode_path.clone())),
+        )
+        .await?;
+
+        let mut labels = BTreeMap::new();
+        labels.insert(APP_NAME_LABEL.to_string(), APP_NAME.to_string());
+        labels.insert(APP_MANAGED_BY_LABEL.to_string(), MANAGED_BY.to_string());
+
+        let mut data = BTreeMap::new();
+        data.insert(
+            ZOOKEEPER_DISCOVERY_KEY.to_string(),
+            connection_info.connection_string,
+        );
+        data.insert(ZOOKEEPER_CHROOT_DISCOVERY_KEY.to_string(), znode_path);
+
+        let config_map = configmap::build_config_map(
+            &self.context.resource,
+            &self.context.name(),
+            &self.context.namespace(),
+            labels,
+            data,
+        )?;
+        configmap::create_config_map(&self.context.client, config_map).await?;
+
+        Ok(ReconcileFunctionAction::Continue)
+    }
+
+    /// Deletes the znode including all of its children and its quota.
+    ///
+    /// The finalizer is only removed once this succeeded, failures are retried. If the referenced
+    /// ZookeeperCluster does not exist anymore there is nothing left to clean up.
+    async fn delete_znode(&self) -> OperatorResult<ReconcileFunctionAction> {
+        let znode_path = match self.znode_path() {
+            Some(znode_path) => znode_path,
+            None => return Ok(ReconcileFunctionAction::Done),
+        };
+
+        let result: Result<bool, Error> = async {
+            let reference = self.cluster_reference(None);
+            let clusters_api: Api<ZookeeperCluster> =
+                self.context.client.get_namespaced_api(&reference.namespace);
+            match clusters_api.get(&reference.name).await {
+                Err(kube::Error::Api(response)) if response.code == 404 => return Ok(false),
+                result => result?,
+            };
+
+            let credentials = self.super_user_credentials(&reference).await?;
+            let zk = self.connect(&reference, credentials.as_ref()).await?;
+            let mut result = zk.delete_recursive(&znode_path).await;
+            if result.is_ok() {
+                result = zk_client::delete_quota(zk.as_ref(), &znode_path).await;
+            }
+            zk.close().await?;
+            result.map(|()| true)
+        }
+        .await;
+
+        match result {
+            Ok(true) => {
+                info!(
+                    "ZookeeperZnode {}: Deleted znode [{}]",
+                    self.context.log_name(),
+                    znode_path
+                );
+                Ok(ReconcileFunctionAction::Done)
+            }
+            Ok(false) => {
+                warn!(
+                    "ZookeeperZnode {}: The ZookeeperCluster does not exist anymore, skipping the deletion of znode [{}]",
+                    self.context.log_name(),
+                    znode_path
+                );
+                Ok(ReconcileFunctionAction::Done)
+            }
+            Err(err) => {
+                warn!(
+                    "ZookeeperZnode {}: Failed to delete znode [{}], retrying: {}",
+                    self.context.log_name(),
+                    znode_path,
+                    err
+                );
+                Ok(ReconcileFunctionAction::Requeue(Duration::from_secs(10)))
+            }
+        }
+    }
+}
+
+impl ReconciliationState for ZnodeState {
+    type Error = Error;
+
+    fn reconcile(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<ReconcileFunctionAction, Self::Error>> + Send + '_>>
+    {
+        Box::pin(async move {
+            self.context
+                .handle_deletion(Box::pin(self.delete_znode()), FINALIZER_NAME, true)
+                .await?
+                .then(self.assign_znode_path())
+                .await?
+                .then(self.reconcile_user_secrets())
+                .await?
+                .then(self.create_znode())
+                .await?
+                .then(self.sync_acls())
+                .await?
+                .then(self.sync_quota())
+                .await?
+                .then(self.seed_znodes())
+                .await?
+                .then(self.publish_discovery_config_map())
+                .await
+        })
+    }
+}
+
+/// Creates the znodes in `entries` (paths below `root` with their data) with `acls`, missing
+/// parents are created without data. Existing znodes keep their data unless `overwrite` is set.
+///
+/// Returns the paths of the created or updated znodes.
+async fn seed(
+    zk: &dyn ZookeeperClient,
+    root: &str,
+    entries: &BTreeMap<String, Vec<u8>>,
+    acls: &[Acl],
+    overwrite: bool,
+) -> Result<Vec<String>, Error> {
+    let mut changed = vec![];
+    for (path, data) in entries {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let mut parent = root.to_string();
+        let mut segments = relative.split('/').filter(|segment| !segment.is_empty());
+        let name = segments.next_back().unwrap_or_default();
+        for segment in segments {
+            parent = format!("{}/{}", parent, segment);
+            if !zk.exists(&parent).await? {
+                zk.create_with_acls(&parent, vec![], acls.to_vec()).await?;
+                changed.push(parent.clone());
+            }
+        }
+
+        let path = format!("{}/{}", parent, name);
+        if !zk.exists(&path).await? {
+            zk.create_with_acls(&path, data.clone(), acls.to_vec())
+                .await?;
+            changed.push(path);
+        } else if overwrite && zk.get_data(&path).await? != *data {
+            zk.set_data(&path, data.clone()).await?;
+            changed.push(path);
+        }
+    }
+    Ok(changed)
+}
+
+/// Builds the ACLs of the znode from the requested ones.
+///
+/// Without requested ACLs the znode is world accessible. Otherwise the super user (given by its
+/// credentials) is granted all permissions in addition, which is why ACLs can only be requested
+/// for clusters which require authentication. `auth` is resolved to the super user because the
+/// controller sets the ACLs as super user, this is also what ZooKeeper reports afterwards.
+fn build_acls(
+    requested_acls: &[ZnodeAcl],
+    super_user_credentials: Option<&(String, String)>,
+) -> Result<Vec<Acl>, Error> {
+    if requested_acls.is_empty() {
+        return Ok(Acl::open_unsafe().clone());
+    }
+    let super_digest = match super_user_credentials {
+        Some((username, password)) => authentication::build_super_digest(username, password),
+        None => {
+            return Err(Error::ReconcileError(
+                "ACLs can only be set in ZookeeperClusters with spec.authentication, otherwise the operator could lock itself out of the znode".to_string(),
+            ))
+        }
+    };
+
+    let mut permissions_by_id = BTreeMap::new();
+    permissions_by_id.insert(
+        (
+            ZnodeAclScheme::Digest.as_str().to_string(),
+            super_digest.clone(),
+        ),
+        PERMISSION_ALL,
+    );
+    for acl in requested_acls {
+        let id = acl.zookeeper_id()?;
+        let (scheme, id) = match acl.scheme {
+            ZnodeAclScheme::Auth => (ZnodeAclScheme::Digest, super_digest.clone()),
+            scheme => (scheme, id),
+        };
+        *permissions_by_id
+            .entry((scheme.as_str().to_string(), id))
+            .or_default() |= acl.permission_bits()?;
+    }
+
+    Ok(permissions_by_id
+        .into_iter()
+        .map(|((scheme, id), permissions)| Acl::new(Permission::from_raw(permissions), scheme, id))
+        .collect())
+}
+
+/// Compares ACLs regardless of their order.
+fn same_acls(left: &[Acl], right: &[Acl]) -> bool {
+    let normalize = |acls: &[Acl]| {
+        let mut acls = acls
+            .iter()
+            .map(|acl| (acl.scheme.clone(), acl.id.clone(), acl.perms.code()))
+            .collect::<Vec<_>>();
+        acls.sort();
+        acls
+    };
+    normalize(left) == normalize(right)
+}
+
+struct ZnodeStrategy {
+    zk_connector: Arc<dyn ZookeeperConnector>,
+}
+
+#[async_trait]
+impl ControllerStrategy for ZnodeStrategy {
+    type Item = ZookeeperZnode;
+    type State = ZnodeState;
+    type Error = Error;
+
+    async fn init_reconcile_state(
+        &self,
+        context: ReconciliationContext<Self::Item>,
+    ) -> Result<Self::State, Self::Error> {
+        Ok(ZnodeState {
+            context,
+            zk_connector: self.zk_connector.clone(),
+            user_acls: vec![],
+        })
+    }
+}
+
+/// Creates the controller for `ZookeeperZnode` resources and runs it until the process exits.
+pub async fn create_znode_controller(
+    client: Client,
+    namespace: Option<String>,
+    requeue_interval: Duration,
+) -> OperatorResult<()> {
+    let znode_api: Api<ZookeeperZnode> = scoped_api(&client, namespace.as_deref());
+    let config_maps_api: Api<ConfigMap> = scoped_api(&client, namespace.as_deref());
+    let secrets_api: Api<Secret> = scoped_api(&client, namespace.as_deref());
+
+    let controller = Controller::new(znode_api)
+        .owns(config_maps_api, ListParams::default())
+        .owns(secrets_api, ListParams::default().labels(ZNODE_LABEL));
+
+    controller
+        .run(
+            client,
+            ZnodeStrategy {
+                zk_connector: zk_client::default_connector(),
+            },
+            requeue_interval,
+        )
+        .await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, FakeApiServer, FakeZookeeper};
+    use kube::api::{Patch, PatchParams};
+    use stackable_zookeeper_crd::chroot::{self, ChrootRequest};
+    use stackable_zookeeper_crd::error::Error as CrdError;
+    use stackable_zookeeper_crd::znode::{
+        ZnodeUser, ZookeeperClusterRef, PERMISSION_READ, PERMISSION_WRITE,
+    };
+
+    fn acl(scheme: ZnodeAclScheme, id: Option<&str>, permissions: &str) -> ZnodeAcl {
+        ZnodeAcl {
+            scheme,
+            id: id.map(str::to_string),
+            permissions: permissions.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_build_acls_without_requested_acls() {
+        assert!(same_acls(
+            &build_acls(&[], None).unwrap(),
+            Acl::open_unsafe()
+        ));
+    }
+
+    #[test]
+    fn test_build_acls_requires_authentication() {
+        build_acls(&[acl(ZnodeAclScheme::World, None, "r")], None).unwrap_err();
+    }
+
+    #[test]
+    fn test_build_acls_keeps_super_user() {
+        let credentials = ("super".to_string(), "secret".to_string());
+        let super_digest = "super:lK75jTNcA+U9vtVEw5vB51mj/w4=";
+
+        let acls = build_acls(
+            &[
+                acl(ZnodeAclScheme::World, None, "r"),
+                acl(ZnodeAclScheme::Digest, Some("app:bWFkZSB1cA=="), "rw"),
+                acl(ZnodeAclScheme::Auth, None, "r"),
+            ],
+            Some(&credentials),
+        )
+        .unwrap();
+
+        assert!(same_acls(
+            &acls,
+            &[
+                Acl::new(
+                    Permission::from_raw(PERMISSION_READ),
+                    "world".to_string(),
+                    "anyone".to_string()
+                ),
+                Acl::new(
+                    Permission::from_raw(PERMISSION_READ | PERMISSION_WRITE),
+                    "digest".to_string(),
+                    "app:bWFkZSB1cA==".to_string()
+                ),
+                Acl::new(
+                    Permission::from_raw(PERMISSION_ALL),
+                    "digest".to_string(),
+                    super_digest.to_string()
+                ),
+            ]
+        ));
+    }
+
+    fn znode_state(server: &FakeApiServer) -> ZnodeState {
+        let znode: ZookeeperZnode = serde_json::from_value(json!({
+            "apiVersion": "zookeeper.stackable.tech/v1alpha1",
+            "kind": "ZookeeperZnode",
+            "metadata": {
+                "name": "kafka",
+                "namespace": test_support::NAMESPACE,
+                "uid": "kafka-uid"
+            },
+            "spec": {
+                "clusterRef": { "name": "simple" },
+                "users": [{ "name": "broker", "permissions": "cdrw" }]
+            }
+        }))
+        .unwrap();
+        ZnodeState {
+            context: ReconciliationContext::new(
+                server.operator_client(),
+                znode,
+                Duration::from_secs(10),
+            ),
+            zk_connector: FakeZookeeper::new().connector(),
+            user_acls: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_user_secret_of_other_znode_is_not_used() {
+        let server = FakeApiServer::new();
+        let mut state = znode_state(&server);
+        let mut other_znode = state.context.resource.clone();
+        other_znode.metadata.uid = Some("other-uid".to_string());
+        let user = other_znode.spec.users[0].clone();
+        server.insert(
+            &authentication::build_user_secret(&other_znode, &user, "secret".to_string()).unwrap(),
+        );
+
+        let result = state.reconcile_user_secrets().await;
+
+        assert!(matches!(result, Err(Error::UserSecretConflict { .. })));
+        assert!(state.user_acls.is_empty());
+        assert!(server
+            .get::<Secret>(Some(test_support::NAMESPACE), "kafka.broker")
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_legacy_user_secret_is_migrated() {
+        let server = FakeApiServer::new();
+        let mut state = znode_state(&server);
+        let user = state.context.resource.spec.users[0].clone();
+        let mut legacy =
+            authentication::build_user_secret(&state.context.resource, &user, "secret".to_string())
+                .unwrap();
+        legacy.metadata.name = Some("kafka-broker".to_string());
+        server.insert(&legacy);
+
+        state.reconcile_user_secrets().await.unwrap();
+
+        let secret = server
+            .get::<Secret>(Some(test_support::NAMESPACE), "kafka.broker")
+            .unwrap();
+        assert_eq!(
+            authentication::read_credentials(&secret).unwrap(),
+            ("broker".to_string(), "secret".to_string())
+        );
+        assert!(server
+            .get::<Secret>(Some(test_support::NAMESPACE), "kafka-broker")
+            .is_none());
+        assert_eq!(
+            state.user_acls,
+            vec![user.acl(&authentication::build_super_digest("broker", "secret"))]
+        );
+    }
+
+    fn chroot_consumer(uid: &str) -> ConfigMap {
+        serde_json::from_value(json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": {
+                "name": "kafka",
+                "namespace": test_support::NAMESPACE,
+                "uid": uid
+            }
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_request_chroot() {
+        let server = FakeApiServer::new();
+        let client = server.operator_client();
+        let request = ChrootRequest {
+            cluster_ref: ZookeeperClusterRef {
+                name: "simple".to_string(),
+                namespace: None,
+            },
+            users: vec![ZnodeUser {
+                name: "broker".to_string(),
+                permissions: "cdrwa".to_string(),
+            }],
+            ..ChrootRequest::default()
+        };
+        let consumer = chroot_consumer("kafka-uid");
+
+        // the znode controller did not publish the chroot yet
+        assert_eq!(
+            chroot::request_chroot(&client, &consumer, &request)
+                .await
+                .unwrap(),
+            None
+        );
+        let name = "kafka-configmap-zookeeper";
+        assert!(server
+            .get::<ZookeeperZnode>(Some(test_support::NAMESPACE), name)
+            .is_some());
+
+        let znodes_api: Api<ZookeeperZnode> =
+            Api::namespaced(server.client(), test_support::NAMESPACE);
+        znodes_api
+            .patch_status(
+                name,
+                &PatchParams::default(),
+                &Patch::Merge(json!({ "status": { "znodePath": "/znode-1" } })),
+            )
+            .await
+            .unwrap();
+        let mut discovery = ConfigMap::default();
+        discovery.metadata.name = Some(name.to_string());
+        discovery.metadata.namespace = Some(test_support::NAMESPACE.to_string());
+        discovery.data.insert(
+            ZOOKEEPER_DISCOVERY_KEY.to_string(),
+            "server1:2181/znode-1".to_string(),
+        );
+        server.insert(&discovery);
+
+        let chroot = chroot::request_chroot(&client, &consumer, &request)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(chroot.connection_string, "server1:2181/znode-1");
+        assert_eq!(chroot.config_map, name);
+        assert_eq!(chroot.reference.chroot.as_deref(), Some("/znode-1"));
+        assert_eq!(chroot.user_secrets, vec![user_secret_name(name, "broker")]);
+
+        // another consumer of the same kind and name does not take the chroot over
+        let result = chroot::request_chroot(&client, &chroot_consumer("other-uid"), &request).await;
+        assert!(matches!(result, Err(CrdError::ChrootConflict { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_seed() {
+        let zookeeper = FakeZookeeper::new();
+        let zk = zookeeper.connector().connect("node-1:2181").await.unwrap();
+        zk.ensure_path("/znode-1").await.unwrap();
+        let mut entries = BTreeMap::new();
+        entries.insert("/znode-1/config/db/url".to_string(), b"db:5432".to_vec());
+        entries.insert("/znode-1/config/mode".to_string(), b"active".to_vec());
+
+        let changed = seed(zk.as_ref(), "/znode-1", &entries, Acl::open_unsafe(), false)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            changed,
+            vec![
+                "/znode-1/config",
+                "/znode-1/config/db",
+                "/znode-1/config/db/url",
+                "/znode-1/config/mode",
+            ]
+        );
+        assert_eq!(zookeeper.data("/znode-1/config"), Some(vec![]));
+        assert_eq!(
+            zookeeper.data("/znode-1/config/db/url"),
+            Some(b"db:5432".to_vec())
+        );
+
+        zk.set_data("/znode-1/config/mode", b"passive".to_vec())
+            .await
+            .unwrap();
+        let changed = seed(zk.as_ref(), "/znode-1", &entries, Acl::open_unsafe(), false)
+            .await
+            .unwrap();
+        assert!(changed.is_empty());
+        assert_eq!(
+            zookeeper.data("/znode-1/config/mode"),
+            Some(b"passive".to_vec())
+        );
+
+        let changed = seed(zk.as_ref(), "/znode-1", &entries, Acl::open_unsafe(), true)
+            .await
+            .unwrap();
+        assert_eq!(changed, vec!["/znode-1/config/mode"]);
+        assert_eq!(
+            zookeeper.data("/znode-1/config/mode"),
+            Some(b"active".to_vec())
+        );
+    }
+}
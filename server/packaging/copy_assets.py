@@ -38,6 +38,11 @@ def copy_assets(root, assets):
               "etc/stackable/zookeeper-operator/crd/",
               "644"
             ],
+            [
+              "../deploy/crd/zookeeperznode.crd.yaml",
+              "etc/stackable/zookeeper-operator/crd/",
+              "644"
+            ],
             [
               "../deploy/config-spec/properties.yaml",
               "etc/stackable/zookeeper-operator/config-spec/",
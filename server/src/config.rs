@@ -0,0 +1,463 @@
+//! The configuration of the operator from the command line and the environment.
+use clap::{Arg, ArgMatches};
+use stackable_zookeeper_crd::EnsembleSizePolicy;
+use stackable_zookeeper_operator::logging::{LogFormat, LoggingConfig, OtlpConfig};
+use stackable_zookeeper_operator::{
+    ControllerConfig, DryRunMode, ManagedResources, ReconcileThrottle, WatchNamespace,
+    DEFAULT_LEASE_NAME,
+};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// The configuration of the operator, parsed from the arguments returned by [`args`].
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The kubeconfig used instead of `~/.kube/config` or the in-cluster configuration.
+    pub kubeconfig: Option<String>,
+    pub watch_namespace: WatchNamespace,
+    pub metrics_address: Option<SocketAddr>,
+    pub usage_report_address: Option<SocketAddr>,
+    pub image_template: Option<String>,
+    /// The CPU architectures the images are available for, empty if unknown.
+    pub image_architectures: Vec<String>,
+    /// Whether the CRDs are created or updated on startup.
+    pub install_crds: bool,
+    /// Whether the operator refuses to start if the installed CRDs do not match its own,
+    /// otherwise it only warns.
+    pub enforce_crd_compatibility: bool,
+    pub controller: ControllerConfig,
+    /// The Lease the replicas compete for, `None` if leader election is disabled.
+    pub leader_election: Option<LeaderElectionConfig>,
+    /// How long the running reconciliations may take to finish on shutdown.
+    pub shutdown_timeout: Duration,
+    pub logging: LoggingConfig,
+}
+
+#[derive(Clone, Debug)]
+pub struct LeaderElectionConfig {
+    pub namespace: String,
+    pub lease_name: String,
+}
+
+/// Returns the arguments configuring the operator.
+pub fn args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("kubeconfig")
+            .long("kubeconfig")
+            .takes_value(true)
+            .env("KUBECONFIG")
+            .help("The kubeconfig used to connect to Kubernetes, defaults to ~/.kube/config or the in-cluster configuration."),
+        Arg::with_name("install-crds")
+            .long("install-crds")
+            .takes_value(true)
+            .possible_values(&["true", "false"])
+            .default_value("false")
+            .help("Whether the operator creates or updates its CRDs on startup, otherwise it waits for them to be installed."),
+        Arg::with_name("crd-compatibility-check")
+            .long("crd-compatibility-check")
+            .takes_value(true)
+            .possible_values(&["enforce", "warn"])
+            .default_value("enforce")
+            .help("Whether the operator refuses to start (`enforce`) or only warns (`warn`) if the installed CRDs are older or newer than the ones of the operator, whose fields would be pruned or ignored."),
+        Arg::with_name("manage-configmaps")
+            .long("manage-configmaps")
+            .takes_value(true)
+            .possible_values(&["true", "false"])
+            .default_value("true")
+            .help("Whether the operator creates and updates the ConfigMaps of a cluster. If disabled, the ConfigMaps need to be provided by other means."),
+        Arg::with_name("manage-pod-monitors")
+            .long("manage-pod-monitors")
+            .takes_value(true)
+            .possible_values(&["true", "false"])
+            .default_value("true")
+            .help("Whether the operator creates PodMonitors for ZookeeperClusters with monitoring enabled. They are only created if the Prometheus Operator CRDs are installed."),
+        Arg::with_name("strict-spec-validation")
+            .long("strict-spec-validation")
+            .takes_value(true)
+            .possible_values(&["true", "false"])
+            .default_value("false")
+            .help("Whether reconciliation fails if the applied spec of a ZookeeperCluster contains unknown (e.g. misspelled) fields."),
+        Arg::with_name("dry-run")
+            .long("dry-run")
+            .takes_value(true)
+            .env("ZOOKEEPER_OPERATOR_DRY_RUN")
+            .possible_values(&["none", "client", "server"])
+            .default_value("none")
+            .help("Whether the operator only records the changes it would make in the status of the ZookeeperClusters instead of making them, `server` computes them with server-side dry runs. The zookeeper.stackable.tech/dry-run annotation of a cluster takes precedence."),
+        Arg::with_name("ensemble-size-policy")
+            .long("ensemble-size-policy")
+            .takes_value(true)
+            .possible_values(&["warn", "reject"])
+            .default_value("warn")
+            .help("What happens to ZookeeperClusters with less than 3 or an even number of participants and no spec.ensembleSizePolicy: `warn` creates them with a warning, `reject` refuses to reconcile them."),
+        Arg::with_name("requeue-interval")
+            .long("requeue-interval")
+            .takes_value(true)
+            .env("ZOOKEEPER_OPERATOR_REQUEUE_INTERVAL")
+            .default_value("10")
+            .help("The seconds after which a failed reconciliation is retried. Further consecutive failures of a ZookeeperCluster are retried with an exponential backoff."),
+        Arg::with_name("max-requeue-interval")
+            .long("max-requeue-interval")
+            .takes_value(true)
+            .env("ZOOKEEPER_OPERATOR_MAX_REQUEUE_INTERVAL")
+            .default_value("300")
+            .help("The maximum seconds after which a failed reconciliation of a ZookeeperCluster is retried."),
+        Arg::with_name("resync-interval")
+            .long("resync-interval")
+            .takes_value(true)
+            .env("ZOOKEEPER_OPERATOR_RESYNC_INTERVAL")
+            .help("If set, ZookeeperClusters are reconciled again after this many seconds even if nothing changed."),
+        Arg::with_name("max-concurrent-reconciles")
+            .long("max-concurrent-reconciles")
+            .takes_value(true)
+            .env("ZOOKEEPER_OPERATOR_MAX_CONCURRENT_RECONCILES")
+            .default_value("10")
+            .help("The maximum number of ZookeeperClusters reconciled at the same time."),
+        Arg::with_name("max-reconciles-per-second")
+            .long("max-reconciles-per-second")
+            .takes_value(true)
+            .env("ZOOKEEPER_OPERATOR_MAX_RECONCILES_PER_SECOND")
+            .help("If set, limits how many reconciliations of ZookeeperClusters start per second (e.g. 0.5 for one every two seconds)."),
+        Arg::with_name("shutdown-timeout")
+            .long("shutdown-timeout")
+            .takes_value(true)
+            .env("ZOOKEEPER_OPERATOR_SHUTDOWN_TIMEOUT")
+            .default_value("25")
+            .help("The seconds the running reconciliations may take to finish when the operator is terminated, must be shorter than the termination grace period of its pod (30 seconds by default)."),
+        Arg::with_name("usage-report-address")
+            .long("usage-report-address")
+            .takes_value(true)
+            .help("If set, a report about which ZookeeperCluster features are in use is served as JSON on http://<address>/usage (e.g. 0.0.0.0:9100)."),
+        Arg::with_name("image-template")
+            .long("image-template")
+            .takes_value(true)
+            .help("If set, the images of the servers are built from this template instead of using the official images, the placeholders {version}, {arch} and {variant} are replaced (e.g. registry.example.com/zookeeper:{version}-{arch})."),
+        Arg::with_name("image-architectures")
+            .long("image-architectures")
+            .takes_value(true)
+            .help("A comma separated list of the CPU architectures the images are available for (e.g. amd64,arm64). If set, servers are only placed on nodes whose kubernetes.io/arch label is one of them, unless the cluster declares spec.placement.architectures."),
+        Arg::with_name("metrics-address")
+            .long("metrics-address")
+            .takes_value(true)
+            .env("ZOOKEEPER_OPERATOR_METRICS_ADDRESS")
+            .help("If set, metrics about the operator itself are served in the Prometheus format on http://<address>/metrics (e.g. 0.0.0.0:9101)."),
+        Arg::with_name("watch-namespace")
+            .long("watch-namespace")
+            .takes_value(true)
+            .env("WATCH_NAMESPACE")
+            .help("A comma separated list of the namespaces the operator watches (e.g. team-a,team-b), all namespaces if not set."),
+        Arg::with_name("leader-election")
+            .long("leader-election")
+            .takes_value(true)
+            .possible_values(&["true", "false"])
+            .default_value("false")
+            .help("Whether the replicas of the operator elect a leader with a Lease, only the leader runs the controllers. Required when running more than one replica."),
+        Arg::with_name("leader-election-namespace")
+            .long("leader-election-namespace")
+            .takes_value(true)
+            .env("POD_NAMESPACE")
+            .default_value("default")
+            .help("The namespace of the Lease used for the leader election."),
+        Arg::with_name("leader-election-lease-name")
+            .long("leader-election-lease-name")
+            .takes_value(true)
+            .default_value(DEFAULT_LEASE_NAME)
+            .help("The name of the Lease used for the leader election."),
+        Arg::with_name("log-format")
+            .long("log-format")
+            .takes_value(true)
+            .env("ZOOKEEPER_OPERATOR_LOG_FORMAT")
+            .possible_values(&["text", "json"])
+            .default_value("text")
+            .help("The format of the log records, `json` writes one JSON object per line."),
+        Arg::with_name("log-filter")
+            .long("log-filter")
+            .takes_value(true)
+            .help("The filter of the log records (e.g. info,stackable_zookeeper_operator=debug), takes precedence over the ZOOKEEPER_OPERATOR_LOG environment variable."),
+        Arg::with_name("otlp-endpoint")
+            .long("otlp-endpoint")
+            .takes_value(true)
+            .env("OTEL_EXPORTER_OTLP_ENDPOINT")
+            .help("If set, the spans of the reconciliations are exported via OTLP (gRPC) to this endpoint (e.g. http://localhost:4317). Requires the otel feature."),
+        Arg::with_name("otlp-sampling-ratio")
+            .long("otlp-sampling-ratio")
+            .takes_value(true)
+            .env("OTEL_TRACES_SAMPLER_ARG")
+            .default_value("1.0")
+            .help("The share of the traces which are exported, between 0 and 1."),
+    ]
+}
+
+impl Config {
+    /// Builds the configuration from the parsed [`args`], returns a message if a value is
+    /// invalid.
+    pub fn from_matches(matches: &ArgMatches) -> Result<Config, String> {
+        // Arguments with a default value or possible values are unwrapped, clap already
+        // rejected invalid values of them.
+        let requeue_interval =
+            parse_seconds(matches, "requeue-interval", "requeue interval")?.unwrap();
+        let max_requeue_interval =
+            parse_seconds(matches, "max-requeue-interval", "maximum requeue interval")?.unwrap();
+        if max_requeue_interval < requeue_interval {
+            return Err(format!(
+                "The maximum requeue interval [{:?}] must not be shorter than the requeue interval [{:?}]",
+                max_requeue_interval, requeue_interval
+            ));
+        }
+
+        let max_concurrent_reconciles = matches.value_of("max-concurrent-reconciles").unwrap();
+        let max_concurrent_reconciles = match max_concurrent_reconciles.parse::<usize>() {
+            Ok(max) if max > 0 => max,
+            _ => {
+                return Err(format!(
+                    "Invalid maximum of concurrent reconciles [{}], it must be positive",
+                    max_concurrent_reconciles
+                ))
+            }
+        };
+        let max_reconciles_per_second = match matches.value_of("max-reconciles-per-second") {
+            Some(rate) => match rate.parse::<f64>() {
+                Ok(parsed) if parsed > 0.0 && parsed.is_finite() => Some(parsed),
+                _ => {
+                    return Err(format!(
+                        "Invalid maximum of reconciles per second [{}], it must be positive",
+                        rate
+                    ))
+                }
+            },
+            None => None,
+        };
+
+        let otlp = match matches.value_of("otlp-endpoint") {
+            Some(endpoint) => {
+                let sampling_ratio = matches.value_of("otlp-sampling-ratio").unwrap();
+                match sampling_ratio.parse::<f64>() {
+                    Ok(sampling_ratio) if (0.0..=1.0).contains(&sampling_ratio) => {
+                        Some(OtlpConfig {
+                            endpoint: endpoint.to_string(),
+                            sampling_ratio,
+                        })
+                    }
+                    _ => {
+                        return Err(format!(
+                            "Invalid OTLP sampling ratio [{}], it must be between 0 and 1",
+                            sampling_ratio
+                        ))
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let leader_election = if matches.value_of("leader-election") == Some("true") {
+            Some(LeaderElectionConfig {
+                namespace: matches
+                    .value_of("leader-election-namespace")
+                    .unwrap()
+                    .to_string(),
+                lease_name: matches
+                    .value_of("leader-election-lease-name")
+                    .unwrap()
+                    .to_string(),
+            })
+        } else {
+            None
+        };
+
+        Ok(Config {
+            kubeconfig: matches.value_of("kubeconfig").map(str::to_string),
+            watch_namespace: WatchNamespace::parse(
+                matches.value_of("watch-namespace").unwrap_or_default(),
+            ),
+            metrics_address: parse_address(matches, "metrics-address", "metrics address")?,
+            usage_report_address: parse_address(
+                matches,
+                "usage-report-address",
+                "usage report address",
+            )?,
+            image_template: matches.value_of("image-template").map(str::to_string),
+            image_architectures: matches
+                .value_of("image-architectures")
+                .map(|architectures| {
+                    architectures
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|architecture| !architecture.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            install_crds: matches.value_of("install-crds") == Some("true"),
+            enforce_crd_compatibility: matches.value_of("crd-compatibility-check")
+                == Some("enforce"),
+            controller: ControllerConfig {
+                managed_resources: ManagedResources {
+                    config_maps: matches.value_of("manage-configmaps") == Some("true"),
+                    pod_monitors: matches.value_of("manage-pod-monitors") == Some("true"),
+                },
+                strict_spec_validation: matches.value_of("strict-spec-validation") == Some("true"),
+                dry_run: matches
+                    .value_of("dry-run")
+                    .unwrap()
+                    .parse::<DryRunMode>()
+                    .unwrap(),
+                ensemble_size_policy: if matches.value_of("ensemble-size-policy") == Some("reject")
+                {
+                    EnsembleSizePolicy::Reject
+                } else {
+                    EnsembleSizePolicy::Warn
+                },
+                requeue_interval,
+                max_requeue_interval,
+                resync_interval: parse_seconds(matches, "resync-interval", "resync interval")?,
+                throttle: ReconcileThrottle::new(
+                    max_concurrent_reconciles,
+                    max_reconciles_per_second,
+                ),
+            },
+            leader_election,
+            shutdown_timeout: parse_seconds(matches, "shutdown-timeout", "shutdown timeout")?
+                .unwrap(),
+            logging: LoggingConfig {
+                filter: matches.value_of("log-filter").map(str::to_string),
+                format: matches
+                    .value_of("log-format")
+                    .unwrap()
+                    .parse::<LogFormat>()
+                    .unwrap(),
+                otlp,
+            },
+        })
+    }
+}
+
+/// Parses the positive number of seconds of the argument `name`, if set.
+fn parse_seconds(
+    matches: &ArgMatches,
+    name: &str,
+    description: &str,
+) -> Result<Option<Duration>, String> {
+    match matches.value_of(name) {
+        Some(seconds) => match seconds.parse::<u64>() {
+            Ok(seconds) if seconds > 0 => Ok(Some(Duration::from_secs(seconds))),
+            _ => Err(format!(
+                "Invalid {} [{}], it must be a positive number of seconds",
+                description, seconds
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
+fn parse_address(
+    matches: &ArgMatches,
+    name: &str,
+    description: &str,
+) -> Result<Option<SocketAddr>, String> {
+    match matches.value_of(name) {
+        Some(address) => address
+            .parse()
+            .map(Some)
+            .map_err(|err| format!("Invalid {} [{}]: {}", description, address, err)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::App;
+
+    fn parse(args: &[&str]) -> Result<Config, String> {
+        let matches = App::new("test")
+            .args(&super::args())
+            .get_matches_from_safe(std::iter::once("test").chain(args.iter().copied()))
+            .map_err(|err| err.to_string())?;
+        Config::from_matches(&matches)
+    }
+
+    #[test]
+    fn test_from_matches() {
+        let config = parse(&[
+            "--watch-namespace",
+            "team-a,team-b",
+            "--metrics-address",
+            "0.0.0.0:9101",
+            "--requeue-interval",
+            "30",
+            "--leader-election",
+            "true",
+            "--log-format",
+            "json",
+            "--image-architectures",
+            "amd64, arm64",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            config.watch_namespace,
+            WatchNamespace::parse("team-a,team-b")
+        );
+        assert_eq!(
+            config.metrics_address,
+            Some("0.0.0.0:9101".parse().unwrap())
+        );
+        assert_eq!(config.controller.requeue_interval, Duration::from_secs(30));
+        assert_eq!(
+            config.controller.max_requeue_interval,
+            Duration::from_secs(300)
+        );
+        assert_eq!(config.controller.resync_interval, None);
+        assert_eq!(
+            config.leader_election.map(|lease| lease.lease_name),
+            Some(DEFAULT_LEASE_NAME.to_string())
+        );
+        assert_eq!(config.logging.format, LogFormat::Json);
+        assert_eq!(config.image_architectures, vec!["amd64", "arm64"]);
+        assert!(parse(&[]).unwrap().image_architectures.is_empty());
+        assert_eq!(config.controller.dry_run, DryRunMode::Disabled);
+        assert!(config.enforce_crd_compatibility);
+        assert!(
+            !parse(&["--crd-compatibility-check", "warn"])
+                .unwrap()
+                .enforce_crd_compatibility
+        );
+        assert_eq!(
+            config.controller.ensemble_size_policy,
+            EnsembleSizePolicy::Warn
+        );
+
+        assert!(parse(&["--requeue-interval", "0"]).is_err());
+        assert!(parse(&["--requeue-interval", "60", "--max-requeue-interval", "30"]).is_err());
+        assert_eq!(
+            parse(&["--resync-interval", "600"])
+                .unwrap()
+                .controller
+                .resync_interval,
+            Some(Duration::from_secs(600))
+        );
+        assert_eq!(
+            parse(&["--dry-run", "server"]).unwrap().controller.dry_run,
+            DryRunMode::Server
+        );
+        assert!(parse(&["--dry-run", "maybe"]).is_err());
+        assert_eq!(
+            parse(&["--ensemble-size-policy", "reject"])
+                .unwrap()
+                .controller
+                .ensemble_size_policy,
+            EnsembleSizePolicy::Reject
+        );
+        assert!(parse(&["--metrics-address", "localhost"]).is_err());
+        assert!(parse(&["--max-concurrent-reconciles", "0"]).is_err());
+        assert!(parse(&["--max-reconciles-per-second", "0.5"]).is_ok());
+        assert!(parse(&["--max-reconciles-per-second", "-1"]).is_err());
+        assert_eq!(config.shutdown_timeout, Duration::from_secs(25));
+        assert_eq!(
+            parse(&["--shutdown-timeout", "50"])
+                .unwrap()
+                .shutdown_timeout,
+            Duration::from_secs(50)
+        );
+    }
+}
@@ -1,9 +1,23 @@
-use clap::{crate_version, App, AppSettings, SubCommand};
+use clap::{crate_version, App, AppSettings, Arg, SubCommand};
+use futures::FutureExt;
+use product_config::ProductConfigManager;
+use stackable_operator::cli;
 use stackable_operator::crd::CustomResourceExt;
-use stackable_operator::{cli, logging};
 use stackable_operator::{client, error};
+use stackable_zookeeper_crd::benchmark::ZookeeperBenchmark;
+use stackable_zookeeper_crd::znode::ZookeeperZnode;
 use stackable_zookeeper_crd::ZookeeperCluster;
-use tracing::error;
+use stackable_zookeeper_operator::logging;
+use stackable_zookeeper_operator::{
+    lint, supervise_controller, DefaultImageResolver, ImageResolver, LeaderElection, Metrics,
+    TemplateImageResolver, UsageStatistics, WatchNamespace, FIELD_MANAGER,
+};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+mod config;
+
+use crate::config::Config;
 
 mod built_info {
     // The file has been placed there by the build script.
@@ -12,31 +26,185 @@ mod built_info {
 
 #[tokio::main]
 async fn main() -> Result<(), error::Error> {
-    logging::initialize_logging("ZOOKEEPER_OPERATOR_LOG");
-
     // Handle CLI arguments
     let matches = App::new(built_info::PKG_DESCRIPTION)
         .author("Stackable GmbH - info@stackable.de")
         .about(built_info::PKG_DESCRIPTION)
         .version(crate_version!())
         .arg(cli::generate_productconfig_arg())
+        .args(&config::args())
         .subcommand(
             SubCommand::with_name("crd")
                 .setting(AppSettings::ArgRequiredElseHelp)
-                .subcommand(cli::generate_crd_subcommand::<ZookeeperCluster>()),
+                .subcommand(
+                    SubCommand::with_name("print")
+                        .about("Prints the CRDs of all custom resources of the operator as YAML."),
+                )
+                .subcommand(
+                    SubCommand::with_name("install")
+                        .about("Creates or updates the CRDs of all custom resources of the operator in the cluster."),
+                )
+                .subcommand(cli::generate_crd_subcommand::<ZookeeperCluster>())
+                .subcommand(cli::generate_crd_subcommand::<ZookeeperZnode>())
+                .subcommand(cli::generate_crd_subcommand::<ZookeeperBenchmark>()),
+        )
+        .subcommand(
+            SubCommand::with_name("restart")
+                .about("Starts a rolling restart of all ZookeeperClusters matching a label selector, one cluster at a time. The running operator carries out the restart.")
+                .arg(
+                    Arg::with_name("selector")
+                        .long("selector")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The label selector of the clusters to restart (e.g. team=payments)."),
+                )
+                .arg(
+                    Arg::with_name("namespace")
+                        .long("namespace")
+                        .takes_value(true)
+                        .default_value("default")
+                        .help("The namespace the ConfigMap tracking the restart is created in."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("lint")
+                .about("Checks the ZookeeperClusters in a YAML file with the validations of the operator and policies for fault tolerant ensembles, without connecting to Kubernetes. Exits with 1 if any error was found.")
+                .arg(
+                    Arg::with_name("file")
+                        .required(true)
+                        .help("The YAML file with the manifests, other kinds are skipped."),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["text", "json", "sarif"])
+                        .default_value("text")
+                        .help("The output format of the findings."),
+                ),
         )
         .get_matches();
 
-    if let ("crd", Some(subcommand)) = matches.subcommand() {
-        if cli::handle_crd_subcommand::<ZookeeperCluster>(subcommand)? {
-            return Ok(());
-        };
+    let config = match Config::from_matches(&matches) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    if let Err(err) = logging::initialize_logging("ZOOKEEPER_OPERATOR_LOG", &config.logging) {
+        eprintln!("Failed to initialize logging: {}", err);
+        std::process::exit(1);
+    }
+    if let Some(kubeconfig) = &config.kubeconfig {
+        // The Kubernetes client reads the kubeconfig from this variable if it is set.
+        std::env::set_var("KUBECONFIG", kubeconfig);
     }
 
     let paths = vec![
         "deploy/config-spec/properties.yaml",
         "/etc/stackable/zookeeper-operator/config-spec/properties.yaml",
     ];
+
+    if let ("crd", Some(subcommand)) = matches.subcommand() {
+        match subcommand.subcommand_name() {
+            Some("print") => match stackable_zookeeper_operator::crds_yaml() {
+                Ok(yaml) => {
+                    print!("{}", yaml);
+                    return Ok(());
+                }
+                Err(err) => {
+                    error!("Failed to generate the CRDs: {}", err);
+                    std::process::exit(1);
+                }
+            },
+            Some("install") => {
+                let client = client::create_client(Some(FIELD_MANAGER.to_string())).await?;
+                if let Err(err) = stackable_zookeeper_operator::install_crds(&client).await {
+                    error!("Failed to install the CRDs: {}", err);
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+        if cli::handle_crd_subcommand::<ZookeeperCluster>(subcommand)? {
+            return Ok(());
+        };
+        if cli::handle_crd_subcommand::<ZookeeperZnode>(subcommand)? {
+            return Ok(());
+        };
+        if cli::handle_crd_subcommand::<ZookeeperBenchmark>(subcommand)? {
+            return Ok(());
+        };
+    }
+
+    if let ("lint", Some(subcommand)) = matches.subcommand() {
+        // Both arguments are either required or have a default value
+        let file = subcommand.value_of("file").unwrap();
+        let format = subcommand.value_of("format").unwrap();
+        let product_config_path = cli::handle_productconfig_arg(&matches, paths)?;
+        let product_config = match ProductConfigManager::from_yaml_file(&product_config_path) {
+            Ok(product_config) => product_config,
+            Err(err) => {
+                error!(
+                    "Failed to read the product config [{}]: {}",
+                    product_config_path, err
+                );
+                std::process::exit(2);
+            }
+        };
+        let manifests = match std::fs::read_to_string(file) {
+            Ok(manifests) => manifests,
+            Err(err) => {
+                error!("Failed to read [{}]: {}", file, err);
+                std::process::exit(2);
+            }
+        };
+
+        let findings = lint::lint_manifests(&manifests, &product_config);
+        match format {
+            "json" => println!("{}", serde_json::json!(findings)),
+            "sarif" => println!(
+                "{}",
+                lint::to_sarif(&findings, file, built_info::PKG_VERSION)
+            ),
+            _ => {
+                for finding in &findings {
+                    println!(
+                        "{}:{}: {} [{}] {}: {}",
+                        file,
+                        finding.line,
+                        finding.severity,
+                        finding.rule,
+                        finding.cluster.as_deref().unwrap_or("-"),
+                        finding.message
+                    );
+                }
+            }
+        }
+        std::process::exit(if lint::has_errors(&findings) { 1 } else { 0 });
+    }
+
+    if let ("restart", Some(subcommand)) = matches.subcommand() {
+        let client = client::create_client(Some(FIELD_MANAGER.to_string())).await?;
+        // Both arguments are either required or have a default value
+        let selector = subcommand.value_of("selector").unwrap();
+        let namespace = subcommand.value_of("namespace").unwrap();
+        match stackable_zookeeper_operator::create_restart_campaign(&client, namespace, selector)
+            .await
+        {
+            Ok(name) => {
+                println!("Created restart campaign [{}/{}]", namespace, name);
+                return Ok(());
+            }
+            Err(err) => {
+                eprintln!("Failed to create restart campaign: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+
     let product_config_path = cli::handle_productconfig_arg(&matches, paths)?;
 
     stackable_operator::utils::print_startup_string(
@@ -48,19 +216,190 @@ async fn main() -> Result<(), error::Error> {
         built_info::RUSTC_VERSION,
     );
 
-    let client = client::create_client(Some("zookeeper.stackable.tech".to_string())).await?;
+    if let WatchNamespace::Namespaces(namespaces) = &config.watch_namespace {
+        info!("Watching the namespaces {:?}", namespaces);
+    }
+
+    let image_architectures = config.image_architectures.clone();
+    let image_resolver: Arc<dyn ImageResolver> = match &config.image_template {
+        Some(template) => {
+            Arc::new(TemplateImageResolver::new(template).with_architectures(image_architectures))
+        }
+        None => Arc::new(DefaultImageResolver::default().with_architectures(image_architectures)),
+    };
+
+    let usage_statistics = UsageStatistics::default();
+    if let Some(address) = config.usage_report_address {
+        let usage_statistics = usage_statistics.clone();
+        tokio::spawn(async move {
+            if let Err(err) =
+                stackable_zookeeper_operator::serve_usage_report(address, usage_statistics).await
+            {
+                error!("Usage report server failed: {}", err);
+            }
+        });
+    }
+
+    let metrics = match Metrics::new() {
+        Ok(metrics) => metrics,
+        Err(err) => {
+            error!("Failed to register metrics: {}", err);
+            std::process::exit(1);
+        }
+    };
+    if let Err(err) = metrics.register_reconcile_queue(&config.controller.throttle) {
+        error!("Failed to register metrics: {}", err);
+        std::process::exit(1);
+    }
+    if let Some(address) = config.metrics_address {
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            if let Err(err) = stackable_zookeeper_operator::serve_metrics(address, metrics).await {
+                error!("Metrics server failed: {}", err);
+            }
+        });
+    }
+
+    let client = client::create_client(Some(FIELD_MANAGER.to_string())).await?;
+
+    if config.install_crds {
+        if let Err(err) = stackable_zookeeper_operator::install_crds(&client).await {
+            error!("Failed to install the CRDs, aborting: {}", err);
+            std::process::exit(1);
+        }
+    }
 
     if let Err(error) = stackable_operator::crd::wait_until_crds_present(
         &client,
-        vec![&ZookeeperCluster::crd_name()],
+        vec![
+            &ZookeeperCluster::crd_name(),
+            &ZookeeperZnode::crd_name(),
+            &ZookeeperBenchmark::crd_name(),
+        ],
         None,
     )
     .await
     {
-        error!("Required CRDs missing, aborting: {:?}", error);
+        error!(
+            "Required CRDs missing (install them with `crd install` or --install-crds true), aborting: {:?}",
+            error
+        );
         return Err(error);
     };
 
-    stackable_zookeeper_operator::create_controller(client, &product_config_path).await?;
+    // the API server prunes the fields an older CRD does not know, so an operator running with
+    // it would lose them silently
+    if let Err(err) = stackable_zookeeper_operator::check_crds(&client).await {
+        if config.enforce_crd_compatibility {
+            error!(
+                "{} (update the CRDs with `crd install` or --install-crds true), aborting",
+                err
+            );
+            std::process::exit(1);
+        }
+        warn!("{}", err);
+    }
+
+    let leader_election = if let Some(leader_election) = &config.leader_election {
+        let identity = std::env::var("POD_NAME")
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .unwrap_or_else(|_| format!("zookeeper-operator-{}", std::process::id()));
+        let leader_election = Arc::new(LeaderElection::new(
+            client.clone(),
+            &leader_election.namespace,
+            &leader_election.lease_name,
+            &identity,
+            metrics.clone(),
+        ));
+        leader_election.acquire().await;
+        tokio::spawn({
+            let leader_election = leader_election.clone();
+            async move { leader_election.keep_leading().await }
+        });
+        Some(leader_election)
+    } else {
+        metrics.set_leader(true);
+        None
+    };
+
+    tokio::spawn(stackable_zookeeper_operator::run_restart_campaigns(
+        client.clone(),
+        config.watch_namespace.clone(),
+        usage_statistics.clone(),
+    ));
+
+    // The controllers are started once per watched namespace (or once for all namespaces) and
+    // restarted whenever their watches end, so they only stop if the process is terminated.
+    let throttle = config.controller.throttle.clone();
+    let requeue_interval = config.controller.requeue_interval;
+    let controllers = config
+        .watch_namespace
+        .scopes()
+        .into_iter()
+        .map(|namespace| {
+            let client = client.clone();
+            let product_config_path = product_config_path.clone();
+            let controller_config = config.controller.clone();
+            let usage_statistics = usage_statistics.clone();
+            let metrics = metrics.clone();
+            let image_resolver = image_resolver.clone();
+            async move {
+                tokio::join!(
+                    supervise_controller("cluster", metrics.clone(), || {
+                        stackable_zookeeper_operator::create_controller(
+                            client.clone(),
+                            namespace.clone(),
+                            &product_config_path,
+                            controller_config.clone(),
+                            usage_statistics.clone(),
+                            metrics.clone(),
+                            image_resolver.clone(),
+                        )
+                    }),
+                    supervise_controller("discovery", metrics.clone(), || {
+                        stackable_zookeeper_operator::create_discovery_controller(
+                            client.clone(),
+                            namespace.clone(),
+                            requeue_interval,
+                        )
+                    }),
+                    supervise_controller("znode", metrics.clone(), || {
+                        stackable_zookeeper_operator::create_znode_controller(
+                            client.clone(),
+                            namespace.clone(),
+                            requeue_interval,
+                        )
+                    }),
+                    supervise_controller("benchmark", metrics.clone(), || {
+                        stackable_zookeeper_operator::create_benchmark_controller(
+                            client.clone(),
+                            namespace.clone(),
+                            requeue_interval,
+                        )
+                    }),
+                );
+            }
+        });
+    // the controllers keep running while the reconciliations in flight finish
+    let controllers = futures::future::join_all(controllers).fuse();
+    tokio::pin!(controllers);
+    tokio::select! {
+        _ = &mut controllers => {}
+        result = stackable_zookeeper_operator::wait_for_termination() => {
+            if let Err(err) = result {
+                error!("Failed to listen for termination signals: {}", err);
+                logging::shutdown_tracing();
+                std::process::exit(1);
+            }
+        }
+    }
+
+    stackable_zookeeper_operator::shut_down(
+        controllers,
+        &throttle,
+        leader_election.as_deref(),
+        config.shutdown_timeout,
+    )
+    .await;
     Ok(())
 }